@@ -0,0 +1,690 @@
+//! Agent-client protocol over stdio (`crabclaw acp`).
+//!
+//! Editors (Zed, Neovim plugins, ...) start crabclaw as a subprocess and talk
+//! JSON-RPC 2.0 to it, one message per line, following the shape of the
+//! Agent Client Protocol:
+//!
+//! - `initialize` → protocol version and agent info
+//! - `session/new` `{cwd?}` → `{sessionId}`; each session is an
+//!   [`AgentLoop`] with its own tape in `cwd` (default: the server's
+//!   workspace)
+//! - `session/prompt` `{sessionId, prompt}` → `{stopReason}` once the turn is
+//!   over. While it runs, `session/update` notifications stream
+//!   `agent_message_chunk`, `tool_call` and `tool_call_update` updates.
+//! - `session/cancel` `{sessionId}` (notification) stops the running turn;
+//!   its prompt answers `{"stopReason": "cancelled"}`.
+//!
+//! Before a tool runs a command or changes files, and before an assistant
+//! shell command runs, the server asks the client with a
+//! `session/request_permission` request and waits for the answer. Tools run
+//! synchronously, so a cancel takes effect once the current tool returns.
+//! Prompts run one at a time; messages arriving meanwhile are handled after
+//! the turn.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+
+use serde_json::{Value, json};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tracing::{debug, info, warn};
+
+use crate::core::agent_loop::AgentLoop;
+use crate::core::config::AppConfig;
+use crate::core::error::Result;
+use crate::core::model_runner::{RESTART_NOTICE, StreamEvent};
+
+/// Protocol version answered to `initialize`.
+pub const PROTOCOL_VERSION: u64 = 1;
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// Loads the config for a session's workspace.
+pub type ConfigLoader = Box<dyn Fn(&Path) -> Result<AppConfig>>;
+
+/// Writes JSON-RPC messages, one per line.
+#[derive(Clone)]
+struct Output(Arc<Mutex<Box<dyn Write + Send>>>);
+
+impl Output {
+    fn send(&self, message: &Value) {
+        let mut out = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = writeln!(out, "{message}").and_then(|()| out.flush());
+    }
+
+    fn respond(&self, id: &Value, result: Value) {
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "result": result }));
+    }
+
+    fn error(&self, id: &Value, code: i64, message: &str) {
+        self.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message },
+        }));
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+}
+
+/// A request or notification from the client.
+#[derive(Debug)]
+struct Incoming {
+    /// `None` for notifications.
+    id: Option<Value>,
+    method: String,
+    params: Value,
+}
+
+/// What the reader thread passes to a waiting permission request.
+enum Reply {
+    /// The client answered our request `id`.
+    Result { id: u64, result: Value },
+    /// The client sent `session/cancel`.
+    Cancelled,
+}
+
+/// Sends `session/request_permission` requests and waits for the answers.
+struct Permissions {
+    output: Output,
+    replies: Mutex<mpsc::Receiver<Reply>>,
+    /// `session/cancel` notifications seen so far, so a cancel that arrived
+    /// before a request does not answer it.
+    cancels: Arc<AtomicU64>,
+    next_id: AtomicU64,
+    /// Tool call being executed, linked in the permission request.
+    current_call: Mutex<Option<String>>,
+}
+
+impl Permissions {
+    /// Ask the client; anything but an explicit "allow" is a no.
+    fn ask(&self, session_id: &str, mut tool_call: Value) -> bool {
+        let replies = self.replies.lock().unwrap_or_else(|e| e.into_inner());
+        let cancels = self.cancels.load(Ordering::SeqCst);
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        if let Some(call_id) = self.current_call.lock().unwrap().clone() {
+            tool_call["toolCallId"] = json!(call_id);
+        }
+        self.output.send(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "session/request_permission",
+            "params": {
+                "sessionId": session_id,
+                "toolCall": tool_call,
+                "options": [
+                    { "optionId": "allow", "name": "Allow", "kind": "allow_once" },
+                    { "optionId": "reject", "name": "Reject", "kind": "reject_once" },
+                ],
+            },
+        }));
+        loop {
+            match replies.recv() {
+                Ok(Reply::Result {
+                    id: answered,
+                    result,
+                }) if answered == id => {
+                    let outcome = &result["outcome"];
+                    return outcome["outcome"] == "selected" && outcome["optionId"] == "allow";
+                }
+                Ok(Reply::Result { .. }) => continue,
+                Ok(Reply::Cancelled) if self.cancels.load(Ordering::SeqCst) == cancels => continue,
+                Ok(Reply::Cancelled) | Err(_) => return false,
+            }
+        }
+    }
+}
+
+/// Read client messages on a thread of their own, so answers to permission
+/// requests arrive while a tool is blocked waiting for them.
+fn spawn_reader(
+    input: impl BufRead + Send + 'static,
+    output: Output,
+    requests: UnboundedSender<Incoming>,
+    replies: mpsc::Sender<Reply>,
+    cancels: Arc<AtomicU64>,
+) {
+    std::thread::spawn(move || {
+        for line in input.lines() {
+            let Ok(line) = line else { break };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let message: Value = match serde_json::from_str(&line) {
+                Ok(message) => message,
+                Err(e) => {
+                    output.error(&Value::Null, PARSE_ERROR, &format!("parse error: {e}"));
+                    continue;
+                }
+            };
+            let Some(method) = message["method"].as_str() else {
+                if let Some(id) = message["id"].as_u64() {
+                    let result = message.get("result").cloned().unwrap_or(Value::Null);
+                    let _ = replies.send(Reply::Result { id, result });
+                }
+                continue;
+            };
+            if method == "session/cancel" {
+                cancels.fetch_add(1, Ordering::SeqCst);
+                let _ = replies.send(Reply::Cancelled);
+            }
+            let incoming = Incoming {
+                id: message.get("id").cloned(),
+                method: method.to_string(),
+                params: message.get("params").cloned().unwrap_or(Value::Null),
+            };
+            if requests.send(incoming).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// ACP kind of a tool, used by clients to pick an icon.
+fn tool_kind(name: &str) -> &'static str {
+    match name {
+        "shell.exec" | "rust.check" | "rust.clippy" | "rust.test" => "execute",
+        "file.write" | "file.edit" | "archive.extract" => "edit",
+        "file.search" | "web.search" | "tape.search" => "search",
+        "web.fetch" => "fetch",
+        n if n.starts_with("file.") || n.ends_with(".read") || n.ends_with(".list") => "read",
+        _ => "other",
+    }
+}
+
+fn raw_input(arguments: &str) -> Value {
+    serde_json::from_str(arguments).unwrap_or_else(|_| json!(arguments))
+}
+
+/// The `update` of a `session/update` notification for `event`.
+fn session_update(event: StreamEvent<'_>) -> Value {
+    match event {
+        StreamEvent::Token(text) => json!({
+            "sessionUpdate": "agent_message_chunk",
+            "content": { "type": "text", "text": text },
+        }),
+        // Sent chunks cannot be taken back; mark where the answer restarts.
+        StreamEvent::Restart => json!({
+            "sessionUpdate": "agent_message_chunk",
+            "content": { "type": "text", "text": RESTART_NOTICE },
+        }),
+        StreamEvent::ToolCall(call) => json!({
+            "sessionUpdate": "tool_call",
+            "toolCallId": call.id,
+            "title": call.function.name,
+            "kind": tool_kind(&call.function.name),
+            "status": "in_progress",
+            "rawInput": raw_input(&call.function.arguments),
+        }),
+        StreamEvent::ToolResult { call, result } => json!({
+            "sessionUpdate": "tool_call_update",
+            "toolCallId": call.id,
+            "status": if result.starts_with("Error") { "failed" } else { "completed" },
+            "content": [{ "type": "content", "content": { "type": "text", "text": result } }],
+        }),
+    }
+}
+
+/// Text of a `session/prompt` prompt: a list of content blocks (text blocks
+/// and embedded text resources are used), or a plain string.
+fn prompt_text(prompt: &Value) -> String {
+    if let Some(text) = prompt.as_str() {
+        return text.to_string();
+    }
+    let blocks = prompt.as_array().map(Vec::as_slice).unwrap_or_default();
+    blocks
+        .iter()
+        .filter_map(|block| match block["type"].as_str() {
+            Some("text") => block["text"].as_str().map(str::to_string),
+            Some("resource") => {
+                let resource = &block["resource"];
+                let text = resource["text"].as_str()?;
+                let uri = resource["uri"].as_str().unwrap_or("resource");
+                Some(format!("{uri}:\n```\n{text}\n```"))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// The ACP server: sessions by ID, and the connection to the client.
+pub struct AcpServer {
+    workspace: PathBuf,
+    load_config: ConfigLoader,
+    sessions: HashMap<String, AgentLoop>,
+    output: Output,
+    permissions: Arc<Permissions>,
+    /// Messages that arrived while a prompt was running.
+    queued: VecDeque<Incoming>,
+}
+
+impl AcpServer {
+    /// Serve `input` until it is closed, writing to `output`.
+    pub async fn serve(
+        workspace: &Path,
+        load_config: ConfigLoader,
+        input: impl BufRead + Send + 'static,
+        output: impl Write + Send + 'static,
+    ) {
+        let output = Output(Arc::new(Mutex::new(Box::new(output))));
+        let (requests_tx, mut requests) = unbounded_channel();
+        let (replies_tx, replies) = mpsc::channel();
+        let cancels = Arc::new(AtomicU64::new(0));
+        spawn_reader(
+            input,
+            output.clone(),
+            requests_tx,
+            replies_tx,
+            Arc::clone(&cancels),
+        );
+        let mut server = Self {
+            workspace: workspace.to_path_buf(),
+            load_config,
+            sessions: HashMap::new(),
+            permissions: Arc::new(Permissions {
+                output: output.clone(),
+                replies: Mutex::new(replies),
+                cancels,
+                next_id: AtomicU64::new(0),
+                current_call: Mutex::new(None),
+            }),
+            output,
+            queued: VecDeque::new(),
+        };
+        info!(workspace = %workspace.display(), "acp.started");
+        loop {
+            let message = match server.queued.pop_front() {
+                Some(message) => message,
+                None => match requests.recv().await {
+                    Some(message) => message,
+                    None => break,
+                },
+            };
+            server.handle(message, &mut requests).await;
+        }
+        info!("acp.stopped");
+    }
+
+    async fn handle(&mut self, message: Incoming, requests: &mut UnboundedReceiver<Incoming>) {
+        debug!(method = %message.method, "acp.message");
+        let Some(id) = message.id else {
+            // `session/cancel` outside a prompt has nothing to stop.
+            return;
+        };
+        match message.method.as_str() {
+            "initialize" => self.output.respond(
+                &id,
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "agentInfo": { "name": "crabclaw", "version": env!("CARGO_PKG_VERSION") },
+                    "agentCapabilities": {
+                        "loadSession": false,
+                        "promptCapabilities": { "image": false, "embeddedContext": true },
+                    },
+                    "authMethods": [],
+                }),
+            ),
+            "session/new" => match self.new_session(&message.params) {
+                Ok(session_id) => self.output.respond(&id, json!({ "sessionId": session_id })),
+                Err(e) => self.output.error(&id, INTERNAL_ERROR, &e.user_message()),
+            },
+            "session/prompt" => self.prompt(&id, &message.params, requests).await,
+            method => {
+                self.output
+                    .error(&id, METHOD_NOT_FOUND, &format!("unknown method {method}"));
+            }
+        }
+    }
+
+    fn new_session(&mut self, params: &Value) -> Result<String> {
+        let workspace = params["cwd"]
+            .as_str()
+            .map_or_else(|| self.workspace.clone(), PathBuf::from);
+        let config = (self.load_config)(&workspace)?;
+        let session_id = format!(
+            "acp-{}-{}",
+            chrono::Local::now().format("%Y%m%d-%H%M%S"),
+            self.sessions.len() + 1
+        );
+        let mut agent = AgentLoop::open(&config, &workspace, &session_id, None, None)?;
+
+        let (permissions, session) = (Arc::clone(&self.permissions), session_id.clone());
+        agent.set_tool_approver(Arc::new(move |name, arguments| {
+            let call =
+                json!({ "title": name, "kind": tool_kind(name), "rawInput": raw_input(arguments) });
+            permissions.ask(&session, call)
+        }));
+        let (permissions, session) = (Arc::clone(&self.permissions), session_id.clone());
+        agent.set_command_approver(Arc::new(move |command| {
+            let call = json!({
+                "title": format!("shell: {command}"),
+                "kind": "execute",
+                "rawInput": { "command": command },
+            });
+            permissions.ask(&session, call)
+        }));
+
+        info!(session = %session_id, workspace = %workspace.display(), "acp.session.new");
+        self.sessions.insert(session_id.clone(), agent);
+        Ok(session_id)
+    }
+
+    async fn prompt(
+        &mut self,
+        id: &Value,
+        params: &Value,
+        requests: &mut UnboundedReceiver<Incoming>,
+    ) {
+        let session_id = params["sessionId"].as_str().unwrap_or_default();
+        let Some(agent) = self.sessions.get_mut(session_id) else {
+            let message = format!("unknown session '{session_id}'");
+            return self.output.error(id, INVALID_PARAMS, &message);
+        };
+        let text = prompt_text(&params["prompt"]);
+        if text.trim().is_empty() {
+            return self.output.error(id, INVALID_PARAMS, "prompt has no text");
+        }
+
+        let (output, permissions) = (self.output.clone(), Arc::clone(&self.permissions));
+        let turn = agent.handle_input_events(&text, |event| {
+            match event {
+                StreamEvent::ToolCall(call) => {
+                    *permissions.current_call.lock().unwrap() = Some(call.id.clone());
+                }
+                StreamEvent::ToolResult { .. } => {
+                    *permissions.current_call.lock().unwrap() = None;
+                }
+                StreamEvent::Token(_) | StreamEvent::Restart => {}
+            }
+            let update = session_update(event);
+            output.notify(
+                "session/update",
+                json!({ "sessionId": session_id, "update": update }),
+            );
+        });
+        tokio::pin!(turn);
+
+        let mut open = true;
+        let result = loop {
+            tokio::select! {
+                result = &mut turn => break Some(result),
+                message = requests.recv(), if open => match message {
+                    Some(m) if m.method == "session/cancel"
+                        && m.params["sessionId"].as_str() == Some(session_id) =>
+                    {
+                        break None;
+                    }
+                    Some(m) => self.queued.push_back(m),
+                    None => open = false,
+                },
+            }
+        };
+        *self.permissions.current_call.lock().unwrap() = None;
+
+        let Some(result) = result else {
+            info!(session = session_id, "acp.prompt.cancelled");
+            return self
+                .output
+                .respond(id, json!({ "stopReason": "cancelled" }));
+        };
+        if let Some(text) = result.immediate_output.as_deref().filter(|t| !t.is_empty()) {
+            self.output.notify(
+                "session/update",
+                json!({ "sessionId": session_id, "update": session_update(StreamEvent::Token(text)) }),
+            );
+        }
+        match &result.error {
+            Some(error) => {
+                warn!(session = session_id, error = %error, "acp.prompt.failed");
+                self.output.error(id, INTERNAL_ERROR, error);
+            }
+            None => self.output.respond(id, json!({ "stopReason": "end_turn" })),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+
+    /// A writer tests can read back.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// A client talking to a server running on its own thread.
+    struct Client {
+        input: Option<std::io::PipeWriter>,
+        output: Captured,
+        server: Option<std::thread::JoinHandle<()>>,
+    }
+
+    impl Client {
+        fn start(api_base: String, workspace: &Path) -> Self {
+            let (reader, writer) = std::io::pipe().unwrap();
+            let output = Captured::default();
+            let (workspace, out) = (workspace.to_path_buf(), output.clone());
+            let server = std::thread::spawn(move || {
+                let loader: ConfigLoader = Box::new(move |_| {
+                    let env_vars = HashMap::from([
+                        ("API_KEY".to_string(), "key".to_string()),
+                        ("BASE_URL".to_string(), api_base.clone()),
+                        ("MODEL".to_string(), "openai:test-model".to_string()),
+                        ("CIRCUIT_BREAKER_FAILURES".to_string(), "0".to_string()),
+                        ("SESSION_TITLES".to_string(), "message".to_string()),
+                    ]);
+                    crate::core::config::resolve_config(
+                        None,
+                        &crate::core::config::CliConfigOverrides::default(),
+                        &env_vars,
+                        &HashMap::new(),
+                    )
+                });
+                let input = std::io::BufReader::new(reader);
+                tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .unwrap()
+                    .block_on(AcpServer::serve(&workspace, loader, input, out));
+            });
+            Self {
+                input: Some(writer),
+                output,
+                server: Some(server),
+            }
+        }
+
+        fn send(&mut self, message: Value) {
+            writeln!(self.input.as_mut().unwrap(), "{message}").unwrap();
+        }
+
+        /// Complete messages written so far; a line still being written is
+        /// left out.
+        fn messages(&self) -> Vec<Value> {
+            let output = String::from_utf8(self.output.0.lock().unwrap().clone()).unwrap();
+            let complete = &output[..output.rfind('\n').map_or(0, |i| i + 1)];
+            complete
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+
+        /// Wait for the first message matching `matches`.
+        fn wait_for(&self, matches: impl Fn(&Value) -> bool) -> Value {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                if let Some(message) = self.messages().into_iter().find(&matches) {
+                    return message;
+                }
+                assert!(
+                    Instant::now() < deadline,
+                    "timed out: {:?}",
+                    self.messages()
+                );
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+
+        fn response(&self, id: u64) -> Value {
+            self.wait_for(|m| m["id"] == id && m.get("method").is_none())
+        }
+
+        fn updates(&self, kind: &str) -> Vec<Value> {
+            self.messages()
+                .into_iter()
+                .filter(|m| m["method"] == "session/update")
+                .map(|m| m["params"]["update"].clone())
+                .filter(|u| u["sessionUpdate"] == kind)
+                .collect()
+        }
+
+        fn new_session(&mut self, id: u64) -> String {
+            self.send(json!({"jsonrpc": "2.0", "id": id, "method": "session/new", "params": {}}));
+            let session = self.response(id)["result"]["sessionId"].clone();
+            session.as_str().unwrap().to_string()
+        }
+
+        fn prompt(&mut self, id: u64, session: &str, text: &str) {
+            self.send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "session/prompt",
+                "params": {"sessionId": session, "prompt": [{"type": "text", "text": text}]},
+            }));
+        }
+
+        /// Answer the server's permission request `id`.
+        fn answer(&mut self, id: u64, option: &str) {
+            let request =
+                self.wait_for(|m| m["method"] == "session/request_permission" && m["id"] == id);
+            assert_eq!(request["params"]["toolCall"]["toolCallId"], "call_1");
+            self.send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": {"outcome": {"outcome": "selected", "optionId": option}},
+            }));
+        }
+
+        fn stop(mut self) -> Vec<Value> {
+            self.input.take();
+            self.server.take().unwrap().join().unwrap();
+            self.messages()
+        }
+    }
+
+    fn sse(chunk: &str) -> String {
+        format!("data: {chunk}\n\ndata: [DONE]\n\n")
+    }
+
+    async fn mock_turn(server: &mut mockito::Server, tool: &str, arguments: &str, answer: &str) {
+        let arguments = arguments.replace('"', "\\\"");
+        server
+            .mock("POST", "/chat/completions")
+            .with_header("content-type", "text/event-stream")
+            .with_body(sse(&format!(
+                r#"{{"choices":[{{"delta":{{"tool_calls":[{{"index":0,"id":"call_1","function":{{"name":"{tool}","arguments":"{arguments}"}}}}]}},"finish_reason":null}}]}}"#
+            )))
+            .create_async()
+            .await;
+        server
+            .mock("POST", "/chat/completions")
+            .with_header("content-type", "text/event-stream")
+            .with_body(sse(&format!(
+                r#"{{"choices":[{{"delta":{{"content":"{answer}"}},"finish_reason":null}}]}}"#
+            )))
+            .create_async()
+            .await;
+    }
+
+    #[tokio::test]
+    async fn prompts_stream_updates_and_ask_before_writing() {
+        let mut server = mockito::Server::new_async().await;
+        let write = r#"{"path":"notes.txt","content":"milk"}"#;
+        mock_turn(&mut server, "file.write", write, "Not written.").await;
+        mock_turn(&mut server, "file.write", write, "Written.").await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut client = Client::start(server.url(), dir.path());
+
+        client.send(json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {}}));
+        assert_eq!(
+            client.response(1)["result"]["protocolVersion"],
+            PROTOCOL_VERSION
+        );
+        let session = client.new_session(2);
+
+        client.prompt(3, &session, "note milk");
+        client.answer(0, "reject");
+        assert_eq!(client.response(3)["result"]["stopReason"], "end_turn");
+        assert!(!dir.path().join("notes.txt").exists());
+        let calls = client.updates("tool_call");
+        assert_eq!(calls[0]["title"], "file.write");
+        assert_eq!(calls[0]["kind"], "edit");
+        assert_eq!(calls[0]["rawInput"]["path"], "notes.txt");
+        let results = client.updates("tool_call_update");
+        assert_eq!(results[0]["status"], "failed");
+        assert_eq!(
+            client.updates("agent_message_chunk")[0]["content"]["text"],
+            "Not written."
+        );
+
+        client.prompt(4, &session, "note milk, really");
+        client.answer(1, "allow");
+        assert_eq!(client.response(4)["result"]["stopReason"], "end_turn");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("notes.txt")).unwrap(),
+            "milk"
+        );
+
+        client.prompt(5, "nope", "hi");
+        assert_eq!(client.response(5)["error"]["code"], INVALID_PARAMS);
+        client.send(json!({"jsonrpc": "2.0", "id": 6, "method": "session/load", "params": {}}));
+        assert_eq!(client.response(6)["error"]["code"], METHOD_NOT_FOUND);
+        client.stop();
+    }
+
+    #[tokio::test]
+    async fn cancel_declines_the_pending_permission_and_ends_the_turn() {
+        let mut server = mockito::Server::new_async().await;
+        mock_turn(
+            &mut server,
+            "shell.exec",
+            r#"{"command":"touch ran"}"#,
+            "Done.",
+        )
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let mut client = Client::start(server.url(), dir.path());
+        let session = client.new_session(1);
+
+        client.prompt(2, &session, "touch a file");
+        client.wait_for(|m| m["method"] == "session/request_permission");
+        client.send(json!({
+            "jsonrpc": "2.0",
+            "method": "session/cancel",
+            "params": {"sessionId": session},
+        }));
+        assert_eq!(client.response(2)["result"]["stopReason"], "cancelled");
+        assert!(!dir.path().join("ran").exists());
+        client.stop();
+    }
+}
@@ -0,0 +1,317 @@
+use std::path::Path;
+
+use crate::core::agent_loop::TURN_USAGE_EVENT;
+use crate::core::title::TITLE_EVENT;
+use crate::llm::api_types::Usage;
+use crate::tape::store::TapeStore;
+
+/// Management commands available to configured admins (`,admin ...`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AdminCommand {
+    Help,
+    /// Token usage per session.
+    Usage,
+    /// Known sessions with message counts and last activity.
+    Sessions,
+    /// Send a message to every known chat.
+    Broadcast(String),
+    /// Re-read `.env.local` and the environment.
+    ReloadConfig,
+}
+
+/// Whether a user is listed as an admin (by user ID or username).
+pub fn is_admin(admins: &[String], user_id: &str, username: Option<&str>) -> bool {
+    admins.iter().any(|a| a == user_id)
+        || username
+            .filter(|u| !u.is_empty())
+            .is_some_and(|u| admins.iter().any(|a| a.trim_start_matches('@') == u))
+}
+
+/// Parse an `,admin` command line (`prefix` replacing the `,`).
+///
+/// Returns `None` when the text is not an admin command at all.
+pub fn parse_admin_command(text: &str, prefix: char) -> Option<Result<AdminCommand, String>> {
+    let rest = text.trim().strip_prefix(prefix)?.strip_prefix("admin")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = rest.trim();
+    let (sub, arg) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let command = match sub {
+        "" | "help" => Ok(AdminCommand::Help),
+        "usage" => Ok(AdminCommand::Usage),
+        "sessions" => Ok(AdminCommand::Sessions),
+        "broadcast" if arg.trim().is_empty() => Err("usage: ,admin broadcast <message>".into()),
+        "broadcast" => Ok(AdminCommand::Broadcast(arg.trim().to_string())),
+        "reload-config" => Ok(AdminCommand::ReloadConfig),
+        other => Err(format!("unknown admin command: {other}")),
+    };
+    Some(command)
+}
+
+/// Commands that change process-wide settings or the config file, and are
+/// therefore reserved for admins.
+const ADMIN_ONLY_COMMANDS: [&str; 2] = ["debug", "config"];
+
+/// The admin-only command `text` starts with, if any.
+pub fn admin_only_command(text: &str, prefix: char) -> Option<&'static str> {
+    let rest = text.trim().strip_prefix(prefix)?;
+    ADMIN_ONLY_COMMANDS.into_iter().find(|name| {
+        rest.strip_prefix(name)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))
+    })
+}
+
+pub fn admin_help() -> String {
+    "\
+Admin commands:
+  ,admin usage            — Token usage per session
+  ,admin sessions         — Sessions with titles, message counts and activity
+  ,admin broadcast <msg>  — Send a message to every known chat
+  ,admin reload-config    — Re-read .env.local and environment"
+        .to_string()
+}
+
+/// Activity and token usage recorded in one session tape.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionSummary {
+    pub name: String,
+    /// Generated title (see [`crate::core::title`]).
+    pub title: Option<String>,
+    pub messages: usize,
+    pub last_activity: Option<String>,
+    pub usage: Usage,
+    /// Estimated cost in millionths of a USD (per-token billed models only).
+    pub cost_micros: u64,
+}
+
+/// Summarize every session tape under `<workspace>/.crabclaw`.
+pub fn session_summaries(workspace: &Path) -> Vec<SessionSummary> {
+    let dir = workspace.join(".crabclaw");
+    let Ok(read_dir) = std::fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = read_dir
+        .flatten()
+        .filter_map(|e| {
+            let path = e.path();
+            if path.extension().is_some_and(|ext| ext == "jsonl") {
+                path.file_stem().map(|s| s.to_string_lossy().to_string())
+            } else {
+                None
+            }
+        })
+        .collect();
+    names.sort();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let tape = TapeStore::open(&dir, &name).ok()?;
+            let mut summary = SessionSummary {
+                name,
+                ..Default::default()
+            };
+            for entry in tape.entries() {
+                match entry.kind.as_str() {
+                    "message" => summary.messages += 1,
+                    TITLE_EVENT => {
+                        summary.title = entry.payload["title"].as_str().map(ToString::to_string);
+                    }
+                    TURN_USAGE_EVENT => {
+                        if let Ok(usage) = serde_json::from_value::<Usage>(entry.payload.clone()) {
+                            summary.usage.accumulate(&usage);
+                        }
+                        if let Some(cost) = entry.payload["cost_usd"].as_f64() {
+                            summary.cost_micros += (cost * 1_000_000.0).round() as u64;
+                        }
+                    }
+                    "schedule.report" => {
+                        if let Ok(usage) =
+                            serde_json::from_value::<Usage>(entry.payload["usage"].clone())
+                        {
+                            summary.usage.accumulate(&usage);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            summary.last_activity = tape.entries().last().map(|e| e.timestamp.clone());
+            Some(summary)
+        })
+        .collect()
+}
+
+pub fn format_usage(sessions: &[SessionSummary]) -> String {
+    if sessions.is_empty() {
+        return "No sessions recorded.".to_string();
+    }
+    let mut total = Usage::default();
+    let mut total_cost = 0;
+    let mut lines = vec!["Token usage by session:".to_string()];
+    for s in sessions {
+        total.accumulate(&s.usage);
+        total_cost += s.cost_micros;
+        lines.push(format!(
+            "  {}: {} prompt + {} completion = {}{}",
+            s.name,
+            s.usage.prompt_tokens,
+            s.usage.completion_tokens,
+            s.usage.total_tokens,
+            format_cost(s.cost_micros)
+        ));
+    }
+    lines.push(format!(
+        "Total: {} prompt + {} completion = {}{}",
+        total.prompt_tokens,
+        total.completion_tokens,
+        total.total_tokens,
+        format_cost(total_cost)
+    ));
+    lines.join("\n")
+}
+
+fn format_cost(micros: u64) -> String {
+    if micros == 0 {
+        return String::new();
+    }
+    format!(" (~${:.4})", micros as f64 / 1_000_000.0)
+}
+
+pub fn format_sessions(sessions: &[SessionSummary]) -> String {
+    if sessions.is_empty() {
+        return "No sessions recorded.".to_string();
+    }
+    let mut lines = vec![format!("Sessions ({}):", sessions.len())];
+    for s in sessions {
+        let title = s
+            .title
+            .as_deref()
+            .map(|t| format!(" \"{t}\""))
+            .unwrap_or_default();
+        lines.push(format!(
+            "  {}{title}: {} message(s), last activity {}",
+            s.name,
+            s.messages,
+            s.last_activity.as_deref().unwrap_or("-")
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Telegram chat IDs of known sessions.
+///
+/// Tapes are named `telegram_<chat_id>`, or `telegram_<chat_id>_<user_id>`
+/// when users are isolated.
+pub fn telegram_chat_ids(sessions: &[SessionSummary]) -> Vec<i64> {
+    let mut ids: Vec<i64> = sessions
+        .iter()
+        .filter_map(|s| {
+            let rest = s.name.strip_prefix("telegram_")?;
+            rest.split('_').next()?.parse().ok()
+        })
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn admin_matches_id_or_username() {
+        let admins = vec!["42".to_string(), "@alice".to_string()];
+        assert!(is_admin(&admins, "42", None));
+        assert!(is_admin(&admins, "7", Some("alice")));
+        assert!(!is_admin(&admins, "7", Some("bob")));
+        assert!(!is_admin(&[], "42", Some("alice")));
+    }
+
+    #[test]
+    fn recognizes_admin_only_commands() {
+        assert_eq!(admin_only_command(",debug dump on", ','), Some("debug"));
+        assert_eq!(admin_only_command(" /debug", '/'), Some("debug"));
+        assert_eq!(admin_only_command(",config set MODEL", ','), Some("config"));
+        assert_eq!(admin_only_command(",debugger", ','), None);
+        assert_eq!(admin_only_command("debug dump on", ','), None);
+    }
+
+    #[test]
+    fn parses_admin_subcommands() {
+        assert_eq!(parse_admin_command("hello", ','), None);
+        assert_eq!(parse_admin_command(",administer", ','), None);
+        assert_eq!(
+            parse_admin_command(",admin", ','),
+            Some(Ok(AdminCommand::Help))
+        );
+        assert_eq!(
+            parse_admin_command(",admin usage", ','),
+            Some(Ok(AdminCommand::Usage))
+        );
+        assert_eq!(
+            parse_admin_command(",admin broadcast  maintenance at 5pm ", ','),
+            Some(Ok(AdminCommand::Broadcast("maintenance at 5pm".into())))
+        );
+        assert!(matches!(
+            parse_admin_command(",admin broadcast", ','),
+            Some(Err(_))
+        ));
+        assert!(matches!(
+            parse_admin_command(",admin nuke", ','),
+            Some(Err(_))
+        ));
+        assert_eq!(
+            parse_admin_command("/admin usage", '/'),
+            Some(Ok(AdminCommand::Usage))
+        );
+        assert_eq!(parse_admin_command(",admin", '/'), None);
+    }
+
+    #[test]
+    fn summaries_count_messages_and_usage() {
+        let ws = tempdir().unwrap();
+        let dir = ws.path().join(".crabclaw");
+        let mut tape = TapeStore::open(&dir, "telegram_123").unwrap();
+        tape.append_message("user", "hi").unwrap();
+        tape.append_message("assistant", "hello").unwrap();
+        tape.append_event(TITLE_EVENT, serde_json::json!({"title": "Greetings"}))
+            .unwrap();
+        tape.append_event(
+            TURN_USAGE_EVENT,
+            serde_json::json!({
+                "prompt_tokens": 10,
+                "completion_tokens": 4,
+                "total_tokens": 14,
+                "cost_usd": 0.0015
+            }),
+        )
+        .unwrap();
+        std::fs::write(dir.join("schedule.json"), "{}").unwrap();
+
+        let sessions = session_summaries(ws.path());
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].messages, 2);
+        assert_eq!(sessions[0].usage.total_tokens, 14);
+        assert_eq!(telegram_chat_ids(&sessions), vec![123]);
+
+        let isolated = vec![
+            SessionSummary {
+                name: "telegram_-100_7".to_string(),
+                ..Default::default()
+            },
+            SessionSummary {
+                name: "telegram_-100_8".to_string(),
+                ..Default::default()
+            },
+        ];
+        assert_eq!(telegram_chat_ids(&isolated), vec![-100]);
+        assert_eq!(sessions[0].cost_micros, 1_500);
+        assert!(
+            format_usage(&sessions).contains("Total: 10 prompt + 4 completion = 14 (~$0.0015)")
+        );
+        assert!(format_sessions(&sessions).contains("telegram_123 \"Greetings\": 2 message(s)"));
+    }
+}
@@ -0,0 +1,153 @@
+//! Live agent loops kept between Telegram messages.
+//!
+//! Opening an [`AgentLoop`] reads the whole tape, discovers skills and
+//! builds the tool registry. For an active chat that work is repeated on
+//! every message, so the most recently used loops are kept here, keyed by
+//! session. A turn takes its loop out of the cache and puts it back when
+//! done; turns of one session never overlap, so a loop is never shared.
+//!
+//! A cached loop is dropped instead of reused when the config was reloaded,
+//! or when its tape file changed behind its back, e.g. through `crabclaw
+//! tape` commands or a scheduled agent job running in the same session.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use tracing::debug;
+
+use crate::core::agent_loop::AgentLoop;
+use crate::core::config::AppConfig;
+
+/// Size and modification time of a tape file; `None` when it is missing.
+type TapeFileState = Option<(u64, SystemTime)>;
+
+fn tape_file_state(agent: &AgentLoop) -> TapeFileState {
+    let meta = std::fs::metadata(agent.tape().path()).ok()?;
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+struct Entry {
+    session: String,
+    config: Arc<AppConfig>,
+    tape: TapeFileState,
+    agent: AgentLoop,
+}
+
+/// Least recently used cache of agent loops.
+pub(crate) struct AgentCache {
+    capacity: usize,
+    /// Most recently used first.
+    entries: Mutex<VecDeque<Entry>>,
+}
+
+impl AgentCache {
+    /// A cache holding at most `capacity` loops; 0 keeps none.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Take the loop of `session` out of the cache, if one is cached and
+    /// still matches `config` and the tape on disk.
+    pub(crate) fn take(&self, session: &str, config: &Arc<AppConfig>) -> Option<AgentLoop> {
+        let entry = {
+            let mut entries = self.entries.lock().unwrap();
+            let index = entries.iter().position(|e| e.session == session)?;
+            entries.remove(index)?
+        };
+        if !Arc::ptr_eq(&entry.config, config) {
+            debug!(session, "agent_cache.config_changed");
+            return None;
+        }
+        if tape_file_state(&entry.agent) != entry.tape {
+            debug!(session, "agent_cache.tape_changed");
+            return None;
+        }
+        Some(entry.agent)
+    }
+
+    /// Keep `agent` for the next turn of `session`, evicting the least
+    /// recently used loop when the cache is full.
+    pub(crate) fn put(&self, session: &str, config: &Arc<AppConfig>, agent: AgentLoop) {
+        if self.capacity == 0 {
+            return;
+        }
+        let entry = Entry {
+            session: session.to_string(),
+            config: Arc::clone(config),
+            tape: tape_file_state(&agent),
+            agent,
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|e| e.session != session);
+        entries.push_front(entry);
+        entries.truncate(self.capacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::TempDir;
+
+    fn config() -> Arc<AppConfig> {
+        let env_vars = HashMap::from([("API_KEY".to_string(), "key".to_string())]);
+        Arc::new(
+            crate::core::config::resolve_config(
+                None,
+                &crate::core::config::CliConfigOverrides::default(),
+                &env_vars,
+                &HashMap::new(),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn open(config: &Arc<AppConfig>, dir: &TempDir, session: &str) -> AgentLoop {
+        AgentLoop::open_shared(Arc::clone(config), dir.path(), session, None, None).unwrap()
+    }
+
+    #[test]
+    fn loops_are_reused_until_evicted() {
+        let dir = TempDir::new().unwrap();
+        let config = config();
+        let cache = AgentCache::new(2);
+        for session in ["a", "b", "c"] {
+            cache.put(session, &config, open(&config, &dir, session));
+        }
+        // "a" was the least recently used and made room for "c".
+        assert!(cache.take("a", &config).is_none());
+        let mut b = cache.take("b", &config).unwrap();
+        b.tape_mut().append_message("user", "hello").unwrap();
+        cache.put("b", &config, b);
+        let b = cache.take("b", &config).unwrap();
+        assert_eq!(b.tape().entries().last().unwrap().kind, "message");
+        // Taking a loop removes it until it is put back.
+        assert!(cache.take("b", &config).is_none());
+        assert!(AgentCache::new(0).take("b", &config).is_none());
+    }
+
+    #[test]
+    fn outside_tape_changes_and_config_reloads_drop_the_loop() {
+        let dir = TempDir::new().unwrap();
+        let config = config();
+        let cache = AgentCache::new(4);
+
+        cache.put("s", &config, open(&config, &dir, "s"));
+        let mut other = open(&config, &dir, "s");
+        other.reset_tape().unwrap();
+        other
+            .tape_mut()
+            .append_message("user", "elsewhere")
+            .unwrap();
+        assert!(cache.take("s", &config).is_none());
+
+        cache.put("s", &config, open(&config, &dir, "s"));
+        let reloaded = Arc::new(AppConfig::clone(&config));
+        assert!(cache.take("s", &reloaded).is_none());
+    }
+}
@@ -1,6 +1,8 @@
 use async_trait::async_trait;
 use serde::Serialize;
 
+use crate::tools::output::ToolOutput;
+
 /// Metadata for a message received from a channel.
 #[derive(Debug, Clone, Serialize)]
 pub struct ChannelMessage {
@@ -21,6 +23,8 @@ pub struct ChannelResponse {
     pub assistant_output: Option<String>,
     /// Error message, if any.
     pub error: Option<String>,
+    /// Files and images to send after the reply.
+    pub attachments: Vec<ToolOutput>,
 }
 
 impl ChannelResponse {
@@ -86,7 +90,7 @@ mod tests {
         let r = ChannelResponse {
             immediate_output: Some("cmd output".to_string()),
             assistant_output: Some("model reply".to_string()),
-            error: None,
+            ..Default::default()
         };
         assert_eq!(r.to_reply().unwrap(), "cmd output\n\nmodel reply");
     }
@@ -0,0 +1,277 @@
+//! Non-interactive batch runner (`crabclaw batch <tasks.yaml>`).
+//!
+//! Each task runs as its own agent session: the task workspace's config
+//! (`.env.local`) is loaded, a fresh tape is opened in that workspace, and
+//! the prompt goes through the full agent loop. Results are aggregated into
+//! one JSON report.
+//!
+//! ```yaml
+//! concurrency: 2              # optional, default 1
+//! model: openai:gpt-4o        # optional default for every task
+//! tasks:
+//!   - name: deps-a
+//!     workspace: ../repo-a    # relative to the tasks file; default: its directory
+//!     prompt: Update outdated dependencies and run the tests.
+//!   - workspace: ../repo-b
+//!     model: anthropic:claude-sonnet-4-20250514
+//!     prompt: Summarize open TODOs.
+//!     schema:                 # optional: answer as JSON matching this schema
+//!       type: object
+//!       properties:
+//!         todos: { type: array, items: { type: string } }
+//!       required: [todos]
+//! ```
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::core::agent_loop::AgentLoop;
+use crate::core::config::{CliConfigOverrides, load_runtime_config};
+use crate::core::error::{CrabClawError, Result};
+
+/// A parsed tasks file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchFile {
+    /// Tasks run at the same time (default 1).
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// Model for tasks that do not name one.
+    #[serde(default)]
+    pub model: Option<String>,
+    pub tasks: Vec<BatchTask>,
+}
+
+/// One prompt to run.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchTask {
+    /// Name used in the report and the tape name (default `task-<n>`).
+    #[serde(default)]
+    pub name: Option<String>,
+    pub prompt: String,
+    #[serde(default)]
+    pub workspace: Option<PathBuf>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// JSON Schema the answer must match; the parsed value is reported as
+    /// `structured`.
+    #[serde(default)]
+    pub schema: Option<serde_json::Value>,
+}
+
+/// Outcome of one task.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskResult {
+    pub name: String,
+    pub workspace: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub structured: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub tool_rounds: usize,
+    pub duration_ms: u64,
+    /// Workspace snapshot taken before the task (see `core::snapshot`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+/// Aggregated results of a batch run.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchReport {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<TaskResult>,
+}
+
+/// Read and validate a tasks file. Task workspaces are resolved against the
+/// file's directory and task names are filled in and checked for duplicates.
+pub fn load_batch_file(path: &Path) -> Result<BatchFile> {
+    let content = std::fs::read_to_string(path).map_err(CrabClawError::Io)?;
+    let base_dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    parse_batch_file(&content, base_dir)
+}
+
+fn parse_batch_file(content: &str, base_dir: &Path) -> Result<BatchFile> {
+    let mut file: BatchFile = serde_norway::from_str(content)
+        .map_err(|e| CrabClawError::Config(format!("invalid batch file: {e}")))?;
+    if file.tasks.is_empty() {
+        return Err(CrabClawError::Config("batch file has no tasks".to_string()));
+    }
+    let mut names = HashSet::new();
+    for (i, task) in file.tasks.iter_mut().enumerate() {
+        let name = task.name.get_or_insert_with(|| format!("task-{}", i + 1));
+        if !names.insert(name.clone()) {
+            return Err(CrabClawError::Config(format!(
+                "duplicate batch task name '{name}'"
+            )));
+        }
+        if task.prompt.trim().is_empty() {
+            return Err(CrabClawError::Config(format!(
+                "batch task '{name}' has an empty prompt"
+            )));
+        }
+        if task.schema.as_ref().is_some_and(|s| !s.is_object()) {
+            return Err(CrabClawError::Config(format!(
+                "batch task '{name}' has a schema that is not an object"
+            )));
+        }
+        let workspace = match task.workspace.take() {
+            Some(dir) if dir.is_absolute() => dir,
+            Some(dir) => base_dir.join(dir),
+            None => base_dir.to_path_buf(),
+        };
+        task.workspace = Some(workspace);
+    }
+    Ok(file)
+}
+
+/// Run every task, at most `concurrency` at a time, and collect the results
+/// in task order. `overrides` are the command-line settings; a task's model
+/// (or the file's) takes precedence over `--model`.
+pub async fn run_batch(
+    file: &BatchFile,
+    profile: Option<&str>,
+    overrides: &CliConfigOverrides,
+    concurrency: usize,
+) -> BatchReport {
+    let run_id = chrono::Utc::now().format("%Y%m%dT%H%M%S").to_string();
+    let mut results: Vec<(usize, TaskResult)> =
+        futures_util::stream::iter(file.tasks.iter().enumerate())
+            .map(|(i, task)| {
+                let model = task.model.clone().or_else(|| file.model.clone());
+                let run_id = &run_id;
+                async move {
+                    let result = run_task(task, model, profile, overrides, run_id).await;
+                    (i, result)
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+    results.sort_by_key(|(i, _)| *i);
+
+    let results: Vec<TaskResult> = results.into_iter().map(|(_, r)| r).collect();
+    let succeeded = results.iter().filter(|r| r.ok).count();
+    BatchReport {
+        total: results.len(),
+        succeeded,
+        failed: results.len() - succeeded,
+        results,
+    }
+}
+
+async fn run_task(
+    task: &BatchTask,
+    model: Option<String>,
+    profile: Option<&str>,
+    overrides: &CliConfigOverrides,
+    run_id: &str,
+) -> TaskResult {
+    let name = task.name.clone().unwrap_or_default();
+    let workspace = task.workspace.clone().unwrap_or_else(|| PathBuf::from("."));
+    let started = Instant::now();
+    let mut result = TaskResult {
+        name: name.clone(),
+        workspace: workspace.display().to_string(),
+        model: model.clone(),
+        ok: false,
+        output: None,
+        structured: None,
+        error: None,
+        tool_rounds: 0,
+        duration_ms: 0,
+        snapshot: None,
+    };
+
+    let mut overrides = overrides.clone();
+    if model.is_some() {
+        overrides.model = model;
+    }
+    let outcome = async {
+        let config = load_runtime_config(&workspace, profile, &overrides)?;
+        let session_id = format!("batch:{name}:{run_id}");
+        let mut agent = AgentLoop::open(&config, &workspace, &session_id, None, None)?;
+        result.snapshot = crate::core::snapshot::create_before_run(
+            &workspace,
+            &format!("before batch task {name}"),
+        );
+        info!(task = %name, model = %config.model, "batch.task.start");
+        agent.set_next_output_schema(task.schema.clone());
+        let loop_result = agent.handle_input(&task.prompt).await;
+        Ok::<_, CrabClawError>((config.model.clone(), loop_result))
+    }
+    .await;
+
+    match outcome {
+        Ok((model, loop_result)) => {
+            result.model = Some(model);
+            result.output = loop_result.to_reply();
+            result.structured = loop_result.structured_output;
+            result.tool_rounds = loop_result.tool_rounds;
+            result.ok = loop_result.error.is_none();
+            result.error = loop_result.error;
+        }
+        Err(e) => result.error = Some(e.to_string()),
+    }
+    result.duration_ms = started.elapsed().as_millis() as u64;
+    info!(
+        task = %name,
+        ok = result.ok,
+        duration_ms = result.duration_ms,
+        "batch.task.done"
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tasks_get_names_and_resolved_workspaces() {
+        let file = parse_batch_file(
+            "concurrency: 3\nmodel: openai:gpt-4o\ntasks:\n  - prompt: one\n  - name: b\n    workspace: repo-b\n    prompt: two\n    model: anthropic:claude\n",
+            Path::new("/work"),
+        )
+        .unwrap();
+        assert_eq!(file.concurrency, Some(3));
+        assert_eq!(file.tasks[0].name.as_deref(), Some("task-1"));
+        assert_eq!(file.tasks[0].workspace.as_deref(), Some(Path::new("/work")));
+        assert_eq!(
+            file.tasks[1].workspace.as_deref(),
+            Some(Path::new("/work/repo-b"))
+        );
+        assert_eq!(file.tasks[1].model.as_deref(), Some("anthropic:claude"));
+    }
+
+    #[test]
+    fn invalid_batch_files_are_rejected() {
+        let base = Path::new("/work");
+        assert!(parse_batch_file("tasks: []", base).is_err());
+        assert!(parse_batch_file("tasks:\n  - prompt: '  '", base).is_err());
+        assert!(parse_batch_file("tasks:\n  - prompt: x\n    repo: y", base).is_err());
+        assert!(parse_batch_file("tasks:\n  - prompt: x\n    schema: object", base).is_err());
+        let dup = "tasks:\n  - name: a\n    prompt: x\n  - name: a\n    prompt: y";
+        assert!(
+            parse_batch_file(dup, base)
+                .unwrap_err()
+                .to_string()
+                .contains("duplicate")
+        );
+    }
+}
@@ -1,7 +1,7 @@
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use clap::{Args, Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 use serde::Serialize;
 
 use crate::core::config::{CliConfigOverrides, load_runtime_config};
@@ -26,8 +26,146 @@ enum Commands {
     Interactive(InteractiveArgs),
     /// Start channel server (Telegram, etc.)
     Serve(ServeArgs),
+    /// Serve the agent-client protocol over stdio, for editor integrations
+    Acp(AcpArgs),
     /// Manage OAuth authentication
     Auth(AuthArgs),
+    /// Inspect workspace and user skills
+    Skills(SkillsArgs),
+    /// Inspect scheduled jobs and their run history
+    Schedule(ScheduleArgs),
+    /// Inspect the audit log of shell commands and file mutations
+    Audit(AuditArgs),
+    /// Inspect recorded session tapes
+    Tape(TapeArgs),
+    /// Run a YAML list of prompts, each as its own agent session
+    Batch(BatchArgs),
+    /// Run a YAML suite of scenarios and score the answers
+    Eval(EvalArgs),
+    /// Update crabclaw to the latest GitHub release
+    SelfUpdate(SelfUpdateArgs),
+    /// Check config, provider access, Telegram, tapes, shell and clock
+    Doctor(DoctorArgs),
+}
+
+#[derive(Debug, Args)]
+struct DoctorArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+    /// Workspace directory (defaults to the current directory)
+    #[arg(long, env = "CRABCLAW_WORKSPACE")]
+    workspace: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct SelfUpdateArgs {
+    /// Only report whether a newer release exists
+    #[arg(long, default_value_t = false)]
+    check: bool,
+    /// Latest-release API endpoint
+    #[arg(
+        long = "releases-url",
+        env = "CRABCLAW_RELEASES_URL",
+        default_value = crate::core::self_update::DEFAULT_RELEASES_URL,
+        hide = true
+    )]
+    releases_url: String,
+}
+
+#[derive(Debug, Args)]
+struct BatchArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+    /// Tasks file (see `channels::batch` for the format)
+    tasks: PathBuf,
+    /// Tasks run at the same time (overrides the file's `concurrency`)
+    #[arg(long)]
+    concurrency: Option<usize>,
+    /// Write the JSON report to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct EvalArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+    /// Suite file (see `channels::eval` for the format)
+    suite: PathBuf,
+    /// Cases run at the same time (overrides the suite's `concurrency`)
+    #[arg(long)]
+    concurrency: Option<usize>,
+    /// Write the JSON report to this file; a summary still goes to stderr
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct TapeArgs {
+    #[command(subcommand)]
+    action: TapeAction,
+}
+
+#[derive(Debug, Subcommand)]
+enum TapeAction {
+    /// List sessions with their titles, message counts and last activity
+    List {
+        /// Workspace whose tapes to list (defaults to the current directory)
+        #[arg(long)]
+        workspace: Option<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct AuditArgs {
+    #[command(subcommand)]
+    action: AuditAction,
+}
+
+#[derive(Debug, Subcommand)]
+enum AuditAction {
+    /// Print the most recent audit records
+    Tail {
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Workspace whose log to read (defaults to the current directory)
+        #[arg(long)]
+        workspace: Option<PathBuf>,
+        /// Print raw JSON lines
+        #[arg(long, default_value_t = false)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+struct ScheduleArgs {
+    #[command(subcommand)]
+    action: ScheduleAction,
+}
+
+#[derive(Debug, Subcommand)]
+enum ScheduleAction {
+    /// List persisted jobs with next fire time and failure counts
+    List,
+    /// Show one job and its recent runs
+    Show { id: String },
+    /// Show the most recent runs across all jobs
+    History {
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Debug, Args)]
+struct SkillsArgs {
+    #[command(subcommand)]
+    action: SkillsAction,
+}
+
+#[derive(Debug, Subcommand)]
+enum SkillsAction {
+    /// Validate every discovered SKILL.md (exits non-zero on errors)
+    Lint,
 }
 
 #[derive(Debug, Args)]
@@ -38,12 +176,33 @@ struct AuthArgs {
 
 #[derive(Debug, Subcommand)]
 enum AuthAction {
-    /// Login with your ChatGPT account via OAuth
-    Login,
-    /// Remove stored OAuth tokens
-    Logout,
-    /// Show current auth status
+    /// Login via ChatGPT OAuth (codex) or store a validated API key
+    Login {
+        /// codex, openai or anthropic
+        #[arg(default_value = "codex")]
+        provider: String,
+        /// API key (otherwise read from the provider env var or stdin)
+        #[arg(long)]
+        key: Option<String>,
+        /// API base used for the validation ping
+        #[arg(long = "api-base")]
+        api_base: Option<String>,
+    },
+    /// Remove stored credentials for a provider
+    Logout {
+        #[arg(default_value = "codex")]
+        provider: String,
+    },
+    /// Show auth status for all providers
     Status,
+    /// Refresh the codex token now, or re-validate a stored API key
+    Refresh {
+        #[arg(default_value = "codex")]
+        provider: String,
+        /// API base used for the validation ping
+        #[arg(long = "api-base")]
+        api_base: Option<String>,
+    },
 }
 
 /// Common CLI arguments shared across all subcommands.
@@ -73,28 +232,105 @@ impl CommonArgs {
     }
 }
 
+/// Where a session runs: the workspace directory and the session (tape)
+/// name.
+#[derive(Debug, Args)]
+struct SessionArgs {
+    /// Workspace directory (defaults to the current directory)
+    #[arg(long, env = "CRABCLAW_WORKSPACE")]
+    workspace: Option<PathBuf>,
+    /// Session name; each session keeps its own tape
+    #[arg(long, env = "CRABCLAW_SESSION", default_value = "default")]
+    session: String,
+}
+
+impl SessionArgs {
+    fn workspace(&self) -> Result<PathBuf> {
+        resolve_workspace(self.workspace.as_deref())
+    }
+
+    fn session(&self) -> Result<&str> {
+        let name = self.session.trim();
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            return Err(CrabClawError::Config(format!(
+                "invalid session name '{}': use letters, digits, '-', '_' or ':'",
+                self.session
+            )));
+        }
+        Ok(name)
+    }
+}
+
+/// `path` made absolute, or the current directory. The workspace must exist.
+fn resolve_workspace(path: Option<&std::path::Path>) -> Result<PathBuf> {
+    match path {
+        Some(path) => path
+            .canonicalize()
+            .map_err(|e| CrabClawError::Config(format!("workspace {}: {e}", path.display()))),
+        None => std::env::current_dir().map_err(CrabClawError::Io),
+    }
+}
+
 #[derive(Debug, Args)]
 struct RunArgs {
     #[command(flatten)]
     common: CommonArgs,
+    #[command(flatten)]
+    session: SessionArgs,
     #[arg(long)]
     prompt: Option<String>,
     #[arg(long = "prompt-file")]
     prompt_file: Option<PathBuf>,
     #[arg(long, default_value_t = false)]
     dry_run: bool,
+    /// `text` streams the answer; `jsonl` prints one JSON event per line
+    /// (see `channels::jsonl`)
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Jsonl,
 }
 
 #[derive(Debug, Args)]
 struct InteractiveArgs {
     #[command(flatten)]
     common: CommonArgs,
+    #[command(flatten)]
+    session: SessionArgs,
 }
 
 #[derive(Debug, Args)]
 struct ServeArgs {
     #[command(flatten)]
     common: CommonArgs,
+    /// Workspace directory (defaults to the current directory)
+    #[arg(long, env = "CRABCLAW_WORKSPACE")]
+    workspace: Option<PathBuf>,
+    /// Do not check for newer releases while running
+    #[arg(long, env = "CRABCLAW_NO_UPDATE_CHECK", default_value_t = false)]
+    no_update_check: bool,
+    /// Latest-release API endpoint
+    #[arg(
+        long = "releases-url",
+        env = "CRABCLAW_RELEASES_URL",
+        default_value = crate::core::self_update::DEFAULT_RELEASES_URL,
+        hide = true
+    )]
+    releases_url: String,
+}
+
+#[derive(Debug, Args)]
+struct AcpArgs {
+    #[command(flatten)]
+    common: CommonArgs,
+    /// Workspace for sessions that do not name a `cwd` (defaults to the
+    /// current directory)
+    #[arg(long, env = "CRABCLAW_WORKSPACE")]
+    workspace: Option<PathBuf>,
 }
 
 #[derive(Debug, Serialize)]
@@ -122,33 +358,219 @@ fn dispatch(cli: Cli) -> Result<()> {
         Commands::Run(args) => run_command(args),
         Commands::Interactive(args) => interactive_command(args),
         Commands::Serve(args) => serve_command(args),
+        Commands::Acp(args) => acp_command(args),
         Commands::Auth(args) => auth_command(args),
+        Commands::Skills(args) => skills_command(args),
+        Commands::Schedule(args) => schedule_command(args),
+        Commands::Audit(args) => audit_command(args),
+        Commands::Tape(args) => tape_command(args),
+        Commands::Batch(args) => batch_command(args),
+        Commands::Eval(args) => eval_command(args),
+        Commands::SelfUpdate(args) => self_update_command(args),
+        Commands::Doctor(args) => doctor_command(args),
+    }
+}
+
+fn audit_command(args: AuditArgs) -> Result<()> {
+    use crate::core::audit::{format_record, tail};
+
+    match args.action {
+        AuditAction::Tail {
+            lines,
+            workspace,
+            json,
+        } => {
+            let workspace = match workspace {
+                Some(path) => path,
+                None => std::env::current_dir().map_err(CrabClawError::Io)?,
+            };
+            let records = tail(&workspace, lines)?;
+            if records.is_empty() {
+                println!("(no audit records)");
+            }
+            for record in &records {
+                if json {
+                    println!("{}", serde_json::to_string(record)?);
+                } else {
+                    println!("{}", format_record(record));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn tape_command(args: TapeArgs) -> Result<()> {
+    use crate::channels::admin::{format_sessions, session_summaries};
+
+    match args.action {
+        TapeAction::List { workspace } => {
+            let workspace = match workspace {
+                Some(path) => path,
+                None => std::env::current_dir().map_err(CrabClawError::Io)?,
+            };
+            println!("{}", format_sessions(&session_summaries(&workspace)));
+        }
     }
+    Ok(())
+}
+
+fn schedule_command(args: ScheduleArgs) -> Result<()> {
+    use crate::tools::schedule_store::{
+        JobStore, format_history, format_job_detail, format_job_list,
+    };
+
+    let workspace = std::env::current_dir().map_err(CrabClawError::Io)?;
+    let store = JobStore::load(&JobStore::workspace_path(&workspace))?;
+    match args.action {
+        ScheduleAction::List => println!("{}", format_job_list(store.jobs())),
+        ScheduleAction::Show { id } => {
+            let job = store
+                .get(&id)
+                .ok_or_else(|| CrabClawError::Config(format!("schedule job not found: {id}")))?;
+            println!("{}", format_job_detail(job));
+        }
+        ScheduleAction::History { limit } => {
+            println!("{}", format_history(store.jobs(), limit))
+        }
+    }
+    Ok(())
+}
+
+fn skills_command(args: SkillsArgs) -> Result<()> {
+    use crate::tools::skills::{LintSeverity, lint_skills};
+
+    let workspace = std::env::current_dir().map_err(CrabClawError::Io)?;
+    match args.action {
+        SkillsAction::Lint => {
+            let issues = lint_skills(&workspace);
+            let errors = issues
+                .iter()
+                .filter(|i| i.severity == LintSeverity::Error)
+                .count();
+            for issue in &issues {
+                let label = match issue.severity {
+                    LintSeverity::Error => "error",
+                    LintSeverity::Warning => "warning",
+                };
+                println!("{label}: {}: {}", issue.location.display(), issue.message);
+            }
+            if errors > 0 {
+                return Err(CrabClawError::Config(format!(
+                    "skills lint failed: {errors} error(s), {} warning(s)",
+                    issues.len() - errors
+                )));
+            }
+            println!("skills lint passed ({} warning(s))", issues.len());
+        }
+    }
+    Ok(())
 }
 
 fn auth_command(args: AuthArgs) -> Result<()> {
+    use crate::core::auth::{self, Provider};
+
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .map_err(|e| CrabClawError::Network(format!("failed to start runtime: {e}")))?;
 
     match args.action {
-        AuthAction::Login => {
-            rt.block_on(crate::core::auth::login())?;
-        }
-        AuthAction::Logout => {
-            crate::core::auth::clear_tokens()?;
-            println!("✅ Logged out. OAuth tokens removed.");
-        }
+        AuthAction::Login {
+            provider,
+            key,
+            api_base,
+        } => match Provider::parse(&provider)? {
+            Provider::Codex => {
+                rt.block_on(auth::login())?;
+            }
+            provider => {
+                let key = match key {
+                    Some(key) => key,
+                    None => read_api_key(provider)?,
+                };
+                let base = api_base.as_deref().unwrap_or(provider.default_api_base());
+                rt.block_on(auth::validate_api_key(provider, base, &key))?;
+                auth::save_api_key(provider, &key)?;
+                println!(
+                    "✅ {} key {} validated and saved.",
+                    provider.name(),
+                    auth::mask_key(&key)
+                );
+            }
+        },
+        AuthAction::Logout { provider } => match Provider::parse(&provider)? {
+            Provider::Codex => {
+                auth::clear_tokens()?;
+                println!("✅ Logged out. OAuth tokens removed.");
+            }
+            provider => {
+                if auth::clear_api_key(provider)? {
+                    println!("✅ {} key removed.", provider.name());
+                } else {
+                    println!("No stored {} key.", provider.name());
+                }
+            }
+        },
         AuthAction::Status => {
-            crate::core::auth::status();
+            auth::status_all();
         }
+        AuthAction::Refresh { provider, api_base } => match Provider::parse(&provider)? {
+            Provider::Codex => {
+                let tokens = auth::load_tokens().ok_or_else(|| {
+                    CrabClawError::Auth(
+                        "not logged in. Run `crabclaw auth login` first.".to_string(),
+                    )
+                })?;
+                rt.block_on(auth::refresh_access_token(&tokens))?;
+                auth::status();
+            }
+            provider => {
+                let key = auth::load_api_key(provider).ok_or_else(|| {
+                    CrabClawError::Auth(format!(
+                        "no stored {0} key. Run `crabclaw auth login {0}` first.",
+                        provider.name()
+                    ))
+                })?;
+                let base = api_base.as_deref().unwrap_or(provider.default_api_base());
+                rt.block_on(auth::validate_api_key(provider, base, &key))?;
+                auth::save_api_key(provider, &key)?;
+                println!(
+                    "✅ {} key {} is valid.",
+                    provider.name(),
+                    auth::mask_key(&key)
+                );
+            }
+        },
     }
     Ok(())
 }
 
+/// Read an API key from the provider's env var, or prompt for it on stdin.
+fn read_api_key(provider: crate::core::auth::Provider) -> Result<String> {
+    if let Some(var) = provider.key_env()
+        && let Ok(key) = std::env::var(var)
+        && !key.trim().is_empty()
+    {
+        println!("Using {var} from the environment.");
+        return Ok(key.trim().to_string());
+    }
+    print!("{} API key: ", provider.name());
+    std::io::Write::flush(&mut std::io::stdout()).map_err(CrabClawError::Io)?;
+    let mut line = String::new();
+    std::io::stdin()
+        .read_line(&mut line)
+        .map_err(CrabClawError::Io)?;
+    let key = line.trim().to_string();
+    if key.is_empty() {
+        return Err(CrabClawError::Auth("no API key given".to_string()));
+    }
+    Ok(key)
+}
+
 fn run_command(args: RunArgs) -> Result<()> {
-    let workspace = std::env::current_dir().map_err(CrabClawError::Io)?;
+    let workspace = args.session.workspace()?;
+    let session = args.session.session()?;
     let overrides = args.common.to_overrides();
     let config = load_runtime_config(&workspace, args.common.profile.as_deref(), &overrides)?;
     let prompt = resolve_prompt(args.prompt, args.prompt_file)?;
@@ -169,13 +591,24 @@ fn run_command(args: RunArgs) -> Result<()> {
     }
 
     let mut agent =
-        crate::core::agent_loop::AgentLoop::open(&config, &workspace, "default", None, None)?;
+        crate::core::agent_loop::AgentLoop::open(&config, &workspace, session, None, None)?;
 
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
         .map_err(|e| CrabClawError::Network(format!("failed to start runtime: {e}")))?;
 
+    if args.output == OutputFormat::Jsonl {
+        let mut stdout = std::io::stdout().lock();
+        rt.block_on(crate::channels::jsonl::run_turn(
+            &mut agent,
+            session,
+            &prompt,
+            &mut stdout,
+        ));
+        return Ok(());
+    }
+
     let mut has_started_text = false;
     let result = rt.block_on(agent.handle_input_stream(&prompt, |token| {
         if !has_started_text {
@@ -198,30 +631,172 @@ fn run_command(args: RunArgs) -> Result<()> {
         println!("{output}");
     }
 
+    for path in result.attachments.iter().filter_map(|a| a.path()) {
+        println!("[attached: {}]", path.display());
+    }
+
     if let Some(err) = &result.error {
         eprintln!("error: {err}");
     }
 
+    if let Some(timing) = &result.timing {
+        eprintln!("({})", timing.footer());
+    }
+
+    Ok(())
+}
+
+fn batch_command(args: BatchArgs) -> Result<()> {
+    use crate::channels::batch::{load_batch_file, run_batch};
+
+    let file = load_batch_file(&args.tasks)?;
+    let concurrency = args.concurrency.or(file.concurrency).unwrap_or(1);
+    let overrides = args.common.to_overrides();
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| CrabClawError::Network(format!("failed to start runtime: {e}")))?;
+    let report = rt.block_on(run_batch(
+        &file,
+        args.common.profile.as_deref(),
+        &overrides,
+        concurrency,
+    ));
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, format!("{json}\n")).map_err(CrabClawError::Io)?,
+        None => println!("{json}"),
+    }
+
+    if report.failed > 0 {
+        return Err(CrabClawError::Api(format!(
+            "batch failed: {} of {} task(s) failed",
+            report.failed, report.total
+        )));
+    }
+    Ok(())
+}
+
+fn eval_command(args: EvalArgs) -> Result<()> {
+    use crate::channels::eval::{load_eval_suite, run_eval};
+
+    let suite = load_eval_suite(&args.suite)?;
+    let concurrency = args.concurrency.or(suite.concurrency).unwrap_or(1);
+    let overrides = args.common.to_overrides();
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| CrabClawError::Network(format!("failed to start runtime: {e}")))?;
+    let report = rt.block_on(run_eval(
+        &suite,
+        args.common.profile.as_deref(),
+        &overrides,
+        concurrency,
+    ));
+
+    let json = serde_json::to_string_pretty(&report)?;
+    match &args.output {
+        Some(path) => std::fs::write(path, format!("{json}\n")).map_err(CrabClawError::Io)?,
+        None => println!("{json}"),
+    }
+    eprintln!("{}", report.summary());
+
+    if report.failed > 0 {
+        return Err(CrabClawError::Api(format!(
+            "eval failed: {} of {} case(s) failed",
+            report.failed, report.total
+        )));
+    }
+    Ok(())
+}
+
+fn doctor_command(args: DoctorArgs) -> Result<()> {
+    use crate::channels::doctor::{CheckStatus, TELEGRAM_API_URL, format_report, run_checks};
+
+    let workspace = resolve_workspace(args.workspace.as_deref())?;
+    let overrides = args.common.to_overrides();
+    let config = load_runtime_config(&workspace, args.common.profile.as_deref(), &overrides);
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| CrabClawError::Network(format!("failed to start runtime: {e}")))?;
+    let checks = rt.block_on(run_checks(&workspace, config, TELEGRAM_API_URL));
+    println!("{}", format_report(&checks));
+
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        return Err(CrabClawError::Config(format!(
+            "doctor found {failed} problem(s)"
+        )));
+    }
+    Ok(())
+}
+
+fn self_update_command(args: SelfUpdateArgs) -> Result<()> {
+    use crate::core::self_update::{check_for_update, install_release};
+
+    let current = env!("CARGO_PKG_VERSION");
+    let Some(release) = check_for_update(&args.releases_url, current)? else {
+        println!("crabclaw {current} is up to date");
+        return Ok(());
+    };
+    if args.check {
+        println!(
+            "crabclaw {} is available (running {current}); run `crabclaw self-update` to install it",
+            release.version()
+        );
+        return Ok(());
+    }
+
+    let target = std::env::current_exe()?;
+    println!("Downloading crabclaw {}...", release.version());
+    install_release(&release, &target)?;
+    println!(
+        "Updated {} from {current} to {}",
+        target.display(),
+        release.version()
+    );
     Ok(())
 }
 
 fn interactive_command(args: InteractiveArgs) -> Result<()> {
-    let workspace = std::env::current_dir().map_err(CrabClawError::Io)?;
+    let workspace = args.session.workspace()?;
+    let session = args.session.session()?;
     let overrides = args.common.to_overrides();
     let config = load_runtime_config(&workspace, args.common.profile.as_deref(), &overrides)?;
-    crate::channels::repl::run_interactive(&config, &workspace)
+    init_scheduler(&workspace, &config);
+    crate::core::auth::spawn_token_refresher(None);
+    crate::channels::repl::run_interactive(&config, &workspace, session)
 }
 
 fn serve_command(args: ServeArgs) -> Result<()> {
-    let workspace = std::env::current_dir().map_err(CrabClawError::Io)?;
+    let workspace = resolve_workspace(args.workspace.as_deref())?;
     let overrides = args.common.to_overrides();
     let config = load_runtime_config(&workspace, args.common.profile.as_deref(), &overrides)?;
+    init_scheduler(&workspace, &config);
     let config = Arc::new(config);
 
     let rt = tokio::runtime::Builder::new_multi_thread()
         .enable_all()
         .build()
         .map_err(|e| CrabClawError::Network(format!("failed to start runtime: {e}")))?;
+    crate::core::auth::spawn_token_refresher(crate::channels::telegram_notify::admin_alert(
+        &config,
+        rt.handle().clone(),
+    ));
+    if !args.no_update_check {
+        crate::core::self_update::spawn_service_check(
+            args.releases_url,
+            crate::channels::telegram_notify::admin_alert(&config, rt.handle().clone()),
+        );
+    }
 
     rt.block_on(async {
         let mut manager =
@@ -229,3 +804,45 @@ fn serve_command(args: ServeArgs) -> Result<()> {
         manager.run().await
     })
 }
+
+fn acp_command(args: AcpArgs) -> Result<()> {
+    let workspace = resolve_workspace(args.workspace.as_deref())?;
+    let overrides = args.common.to_overrides();
+    let profile = args.common.profile.clone();
+    // Fail at startup rather than on the first `session/new`.
+    load_runtime_config(&workspace, profile.as_deref(), &overrides)?;
+    let load_config: crate::channels::acp::ConfigLoader =
+        Box::new(move |workspace| load_runtime_config(workspace, profile.as_deref(), &overrides));
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| CrabClawError::Network(format!("failed to start runtime: {e}")))?;
+    rt.block_on(crate::channels::acp::AcpServer::serve(
+        &workspace,
+        load_config,
+        std::io::BufReader::new(std::io::stdin()),
+        std::io::stdout(),
+    ));
+    Ok(())
+}
+
+/// Configure the global scheduler for a long-running session.
+///
+/// Job state is mirrored into the workspace so `crabclaw schedule` can read it.
+fn init_scheduler(workspace: &std::path::Path, config: &crate::core::config::AppConfig) {
+    use crate::tools::schedule::{RetryPolicy, global_scheduler};
+    use crate::tools::schedule_store::JobStore;
+
+    let scheduler = global_scheduler();
+    scheduler.persist_to(&JobStore::workspace_path(workspace));
+    scheduler.set_retry_policy(RetryPolicy::new(
+        config.schedule_max_retries,
+        std::time::Duration::from_secs(config.schedule_retry_backoff_seconds),
+    ));
+    scheduler.set_full_reports(config.schedule_report);
+    scheduler.set_max_concurrent_agent_jobs(config.schedule_max_concurrent_jobs);
+    scheduler.set_jitter(std::time::Duration::from_secs(
+        config.schedule_jitter_seconds,
+    ));
+}
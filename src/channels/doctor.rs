@@ -0,0 +1,369 @@
+//! `crabclaw doctor`: diagnose the local setup.
+//!
+//! Each check reports ok, a warning or a failure together with the fix to
+//! try. Checks that need a loaded config (provider pings, Telegram, clock
+//! skew) are skipped when the config itself cannot be loaded, since that
+//! failure already names the problem.
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::core::auth::{Provider, load_tokens, validate_api_key};
+use crate::core::config::AppConfig;
+use crate::core::error::{CrabClawError, Result};
+use crate::core::shell::{SHELL_ENV_KEY, ShellKind};
+
+/// Telegram Bot API used to validate `TELEGRAM_TOKEN`.
+pub const TELEGRAM_API_URL: &str = "https://api.telegram.org";
+
+/// Clock offset from a provider's `Date` header that gets a warning. Larger
+/// offsets break OAuth token expiry and request signing.
+const MAX_CLOCK_SKEW_SECS: i64 = 60;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Outcome of one diagnostic check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// What to do about a warning or failure.
+    pub fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: impl Into<String>, detail: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    fn warn(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Warn,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn fail(name: impl Into<String>, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            status: CheckStatus::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    fn from_error(name: impl Into<String>, err: &CrabClawError) -> Self {
+        let fix = err
+            .remediation()
+            .unwrap_or_else(|| "See the error above.".to_string());
+        Self::fail(name, err.to_string(), fix)
+    }
+}
+
+/// Run every check for `workspace`. `config` is the result of loading the
+/// runtime config, so a config error is reported rather than aborting.
+pub async fn run_checks(
+    workspace: &Path,
+    config: Result<AppConfig>,
+    telegram_api: &str,
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+    match &config {
+        Ok(config) => checks.push(check_config(config)),
+        Err(e) => checks.push(Check::from_error("config", e)),
+    }
+    checks.push(check_tape_dir(workspace));
+    checks.push(check_shell().await);
+    if let Ok(config) = &config {
+        checks.extend(check_providers(config).await);
+        if let Some(token) = &config.telegram_token {
+            checks.push(check_telegram(telegram_api, token).await);
+        }
+        checks.push(check_clock(&config.api_base).await);
+    }
+    checks
+}
+
+/// Render checks one per line, with the fix indented below.
+pub fn format_report(checks: &[Check]) -> String {
+    let mut lines = Vec::new();
+    for check in checks {
+        let icon = match check.status {
+            CheckStatus::Ok => "✅",
+            CheckStatus::Warn => "⚠️",
+            CheckStatus::Fail => "❌",
+        };
+        lines.push(format!("{icon} {}: {}", check.name, check.detail));
+        if let Some(fix) = &check.fix {
+            lines.push(format!("   Fix: {fix}"));
+        }
+    }
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+    let warned = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Warn)
+        .count();
+    lines.push(String::new());
+    lines.push(match (failed, warned) {
+        (0, 0) => "All checks passed.".to_string(),
+        (0, w) => format!("No failures, {w} warning(s)."),
+        (f, w) => format!("{f} check(s) failed, {w} warning(s)."),
+    });
+    lines.join("\n")
+}
+
+fn check_config(config: &AppConfig) -> Check {
+    if let Err(e) = crate::llm::models::validate_model_id(&config.model) {
+        return Check::fail(
+            "config",
+            e,
+            "Set MODEL to a prefixed model id, e.g. openai:gpt-4o.",
+        );
+    }
+    Check::ok(
+        "config",
+        format!("profile {}, model {}", config.profile, config.model),
+    )
+}
+
+fn check_tape_dir(workspace: &Path) -> Check {
+    let dir = workspace.join(".crabclaw");
+    let probe = dir.join(".doctor-probe");
+    let result = std::fs::create_dir_all(&dir)
+        .and_then(|()| std::fs::write(&probe, b"ok"))
+        .and_then(|()| std::fs::remove_file(&probe));
+    match result {
+        Ok(()) => Check::ok("tapes", format!("{} is writable", dir.display())),
+        Err(e) => Check::fail(
+            "tapes",
+            format!("cannot write to {}: {e}", dir.display()),
+            "Fix the directory's permissions, or pass --workspace to use another directory.",
+        ),
+    }
+}
+
+async fn check_shell() -> Check {
+    let shell = ShellKind::detect();
+    let (program, _) = shell.argv("exit 0");
+    let status = tokio::process::Command::from(shell.std_command("exit 0"))
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()
+        .await;
+    match status {
+        Ok(status) if status.success() => Check::ok("shell", format!("{program} runs commands")),
+        Ok(status) => Check::fail(
+            "shell",
+            format!("{program} exited with {status}"),
+            format!("Set {SHELL_ENV_KEY} to another shell (sh, powershell, pwsh or cmd)."),
+        ),
+        Err(e) => Check::fail(
+            "shell",
+            format!("cannot start {program}: {e}"),
+            format!("Install it, or set {SHELL_ENV_KEY} to sh, powershell, pwsh or cmd."),
+        ),
+    }
+}
+
+/// One ping per configured provider: the active model's provider, plus
+/// every OpenAI-compatible endpoint that has a key.
+async fn check_providers(config: &AppConfig) -> Vec<Check> {
+    let mut checks = Vec::new();
+    match Provider::from_model(&config.model) {
+        Some(Provider::Codex) => checks.push(check_codex_tokens()),
+        Some(provider) => {
+            checks.push(ping(provider, &config.api_base, &config.api_key).await);
+        }
+        // `responses:` models use the OpenAI API shape.
+        None if config.model.starts_with("responses:") => {
+            checks.push(ping(Provider::OpenAI, &config.api_base, &config.api_key).await);
+        }
+        None => {}
+    }
+    for (name, endpoint) in &config.provider_endpoints {
+        let (Ok(provider), Some(key)) = (Provider::parse(name), &endpoint.api_key) else {
+            continue;
+        };
+        if Provider::from_model(&config.model) != Some(provider) {
+            checks.push(ping(provider, &endpoint.api_base, key).await);
+        }
+    }
+    checks
+}
+
+async fn ping(provider: Provider, api_base: &str, api_key: &str) -> Check {
+    let name = format!("provider {}", provider.name());
+    match validate_api_key(provider, api_base, api_key).await {
+        Ok(()) => Check::ok(name, format!("{api_base} accepted the key")),
+        Err(CrabClawError::Auth(detail)) => Check::fail(
+            name,
+            detail,
+            format!(
+                "Check API_KEY, or run `crabclaw auth login {}`.",
+                provider.name()
+            ),
+        ),
+        Err(e) => Check::from_error(name, &e),
+    }
+}
+
+fn check_codex_tokens() -> Check {
+    match load_tokens() {
+        None => Check::fail(
+            "provider codex",
+            "no OAuth tokens stored",
+            "Run `crabclaw auth login`.",
+        ),
+        Some(tokens) if tokens.is_expired() => Check::warn(
+            "provider codex",
+            "the access token has expired",
+            "Run `crabclaw auth refresh`, or `crabclaw auth login` if that fails.",
+        ),
+        Some(_) => Check::ok("provider codex", "OAuth token is valid"),
+    }
+}
+
+/// Validate a bot token with `getMe`.
+pub async fn check_telegram(api_url: &str, token: &str) -> Check {
+    let url = format!("{}/bot{token}/getMe", api_url.trim_end_matches('/'));
+    let response = match reqwest::Client::new()
+        .get(&url)
+        .timeout(PING_TIMEOUT)
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            return Check::fail(
+                "telegram",
+                format!("cannot reach the Bot API: {}", e.without_url()),
+                "Check your network connection, or set TELEGRAM_PROXY.",
+            );
+        }
+    };
+    let status = response.status();
+    let body: serde_json::Value = response.json().await.unwrap_or_default();
+    if status.is_success() && body["ok"].as_bool() == Some(true) {
+        let username = body["result"]["username"].as_str().unwrap_or("?");
+        return Check::ok("telegram", format!("token belongs to @{username}"));
+    }
+    Check::fail(
+        "telegram",
+        format!(
+            "Bot API rejected the token (HTTP {status}): {}",
+            body["description"].as_str().unwrap_or("no description")
+        ),
+        "Copy the token from @BotFather into TELEGRAM_TOKEN.",
+    )
+}
+
+/// Compare the local clock with the `Date` header of `api_base`.
+async fn check_clock(api_base: &str) -> Check {
+    let response = reqwest::Client::new()
+        .head(api_base)
+        .timeout(PING_TIMEOUT)
+        .send()
+        .await;
+    let server_time = response.ok().and_then(|r| {
+        let date = r.headers().get("date")?.to_str().ok()?.to_string();
+        chrono::DateTime::parse_from_rfc2822(&date).ok()
+    });
+    let Some(server_time) = server_time else {
+        return Check::warn(
+            "clock",
+            format!("{api_base} sent no Date header to compare with"),
+            "Make sure the system clock is synchronized (e.g. with NTP).",
+        );
+    };
+    clock_check(
+        chrono::Utc::now().timestamp() - server_time.timestamp(),
+        api_base,
+    )
+}
+
+fn clock_check(skew_secs: i64, source: &str) -> Check {
+    if skew_secs.abs() <= MAX_CLOCK_SKEW_SECS {
+        return Check::ok("clock", format!("within {}s of {source}", skew_secs.abs()));
+    }
+    let direction = if skew_secs > 0 { "ahead of" } else { "behind" };
+    Check::warn(
+        "clock",
+        format!("{}s {direction} {source}", skew_secs.abs()),
+        "Synchronize the system clock (e.g. enable NTP); token expiry checks depend on it.",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn clock_skew_beyond_a_minute_warns() {
+        assert_eq!(clock_check(5, "api").status, CheckStatus::Ok);
+        let check = clock_check(-300, "api");
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert_eq!(check.detail, "300s behind api");
+    }
+
+    #[test]
+    fn tape_dir_check_creates_the_directory() {
+        let dir = tempdir().unwrap();
+        let check = check_tape_dir(dir.path());
+        assert_eq!(check.status, CheckStatus::Ok, "{check:?}");
+        assert!(dir.path().join(".crabclaw").is_dir());
+        assert!(!dir.path().join(".crabclaw/.doctor-probe").exists());
+    }
+
+    #[tokio::test]
+    async fn telegram_tokens_are_checked_with_get_me() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/bot123:good/getMe")
+            .with_body(r#"{"ok":true,"result":{"id":1,"username":"crab_bot"}}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/bot123:bad/getMe")
+            .with_status(401)
+            .with_body(r#"{"ok":false,"description":"Unauthorized"}"#)
+            .create_async()
+            .await;
+
+        let check = check_telegram(&server.url(), "123:good").await;
+        assert_eq!(check.detail, "token belongs to @crab_bot");
+        let check = check_telegram(&server.url(), "123:bad").await;
+        assert_eq!(check.status, CheckStatus::Fail);
+        assert!(check.detail.contains("Unauthorized"), "{}", check.detail);
+    }
+
+    #[test]
+    fn report_summarizes_failures() {
+        let checks = [
+            Check::ok("config", "profile default"),
+            Check::fail("shell", "cannot start sh", "Install it."),
+        ];
+        let report = format_report(&checks);
+        assert!(report.contains("❌ shell: cannot start sh\n   Fix: Install it."));
+        assert!(report.ends_with("1 check(s) failed, 0 warning(s)."));
+    }
+}
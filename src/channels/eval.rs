@@ -0,0 +1,521 @@
+//! Scenario evaluation (`crabclaw eval <suite.yaml>`).
+//!
+//! Each case sends a prompt through the full agent loop in a fresh session,
+//! then checks the answer: text assertions, the tools called, a JSON Schema,
+//! and a rubric graded by a judge model. The JSON report lists every check,
+//! so a prompt or tool change can be compared against the last run before
+//! it is deployed.
+//!
+//! ```yaml
+//! model: openai:gpt-4o-mini   # optional default for every case
+//! judge: openai:gpt-4o        # model grading `rubric` checks
+//! workspace: fixtures/repo    # relative to the suite file; default: its directory
+//! cases:
+//!   - name: finds-entry-point
+//!     prompt: Which file holds the main function?
+//!     expect:
+//!       contains: [src/main.rs]
+//!       not_contains: [I cannot]
+//!       regex: '\bmain\b'
+//!       tools: [file.search]
+//!       rubric: Names src/main.rs and does not invent other files.
+//!   - name: json-summary
+//!     prompt: List the crate's dependencies.
+//!     expect:
+//!       schema: { type: array, items: { type: string } }
+//! ```
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::core::agent_loop::AgentLoop;
+use crate::core::config::{AppConfig, CliConfigOverrides, load_runtime_config};
+use crate::core::error::{CrabClawError, Result};
+use crate::core::model_runner::ModelRunner;
+use crate::llm::api_types::Message;
+use crate::tape::store::TapeStore;
+use crate::tools::registry::ToolContext;
+
+const JUDGE_PROMPT: &str = "You grade an AI assistant's answer against a rubric. \
+Judge only what the rubric asks for. Reply with JSON only: \
+{\"pass\": true or false, \"reason\": \"one sentence\"}.";
+
+/// A parsed suite file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalSuite {
+    /// Cases run at the same time (default 1).
+    #[serde(default)]
+    pub concurrency: Option<usize>,
+    /// Model for cases that do not name one.
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Model grading `rubric` checks.
+    #[serde(default)]
+    pub judge: Option<String>,
+    /// Workspace for cases that do not name one.
+    #[serde(default)]
+    pub workspace: Option<PathBuf>,
+    pub cases: Vec<EvalCase>,
+}
+
+/// One scenario.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EvalCase {
+    /// Name used in the report and the tape name (default `case-<n>`).
+    #[serde(default)]
+    pub name: Option<String>,
+    pub prompt: String,
+    #[serde(default)]
+    pub workspace: Option<PathBuf>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub expect: Expectations,
+}
+
+/// Checks run against a case's answer; every one must pass.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Expectations {
+    /// Substrings the answer must contain.
+    #[serde(default)]
+    pub contains: Vec<String>,
+    /// Substrings the answer must not contain.
+    #[serde(default)]
+    pub not_contains: Vec<String>,
+    /// Pattern the answer must match.
+    #[serde(default)]
+    pub regex: Option<String>,
+    /// Tools that must have been called.
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// JSON Schema the answer must be a JSON value of.
+    #[serde(default)]
+    pub schema: Option<serde_json::Value>,
+    /// What a good answer does, graded by the judge model.
+    #[serde(default)]
+    pub rubric: Option<String>,
+}
+
+impl Expectations {
+    fn is_empty(&self) -> bool {
+        self.contains.is_empty()
+            && self.not_contains.is_empty()
+            && self.regex.is_none()
+            && self.tools.is_empty()
+            && self.schema.is_none()
+            && self.rubric.is_none()
+    }
+}
+
+/// Outcome of one check.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CheckResult {
+    /// What was checked, e.g. `contains "src/main.rs"`.
+    pub check: String,
+    pub passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Outcome of one case.
+#[derive(Debug, Clone, Serialize)]
+pub struct CaseResult {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub passed: bool,
+    /// Share of checks that passed, 0.0 to 1.0.
+    pub score: f64,
+    pub checks: Vec<CheckResult>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    pub tools: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Aggregated results of a suite run.
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalReport {
+    pub total: usize,
+    pub passed: usize,
+    pub failed: usize,
+    /// Mean case score.
+    pub score: f64,
+    pub results: Vec<CaseResult>,
+}
+
+impl EvalReport {
+    /// One line per case and a total, for the terminal.
+    pub fn summary(&self) -> String {
+        let mut lines: Vec<String> = self
+            .results
+            .iter()
+            .map(|case| {
+                let status = if case.passed { "PASS" } else { "FAIL" };
+                let failed: Vec<&str> = case
+                    .checks
+                    .iter()
+                    .filter(|c| !c.passed)
+                    .map(|c| c.check.as_str())
+                    .chain(case.error.as_deref())
+                    .collect();
+                let mut line = format!("{status} {} ({:.0}%)", case.name, case.score * 100.0);
+                if !failed.is_empty() {
+                    line.push_str(&format!(": {}", failed.join("; ")));
+                }
+                line
+            })
+            .collect();
+        lines.push(format!(
+            "{}/{} case(s) passed, score {:.0}%",
+            self.passed,
+            self.total,
+            self.score * 100.0
+        ));
+        lines.join("\n")
+    }
+}
+
+/// Read and validate a suite file. Workspaces are resolved against the
+/// file's directory and case names are filled in and checked for duplicates.
+pub fn load_eval_suite(path: &Path) -> Result<EvalSuite> {
+    let content = std::fs::read_to_string(path).map_err(CrabClawError::Io)?;
+    let base_dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    parse_eval_suite(&content, base_dir)
+}
+
+fn parse_eval_suite(content: &str, base_dir: &Path) -> Result<EvalSuite> {
+    let mut suite: EvalSuite = serde_norway::from_str(content)
+        .map_err(|e| CrabClawError::Config(format!("invalid eval suite: {e}")))?;
+    if suite.cases.is_empty() {
+        return Err(CrabClawError::Config("eval suite has no cases".to_string()));
+    }
+    let resolve = |dir: Option<PathBuf>, default: &Path| match dir {
+        Some(dir) if dir.is_absolute() => dir,
+        Some(dir) => base_dir.join(dir),
+        None => default.to_path_buf(),
+    };
+    let default_workspace = resolve(suite.workspace.take(), base_dir);
+    let mut names = HashSet::new();
+    for (i, case) in suite.cases.iter_mut().enumerate() {
+        let name = case.name.get_or_insert_with(|| format!("case-{}", i + 1));
+        let invalid =
+            |problem: &str| CrabClawError::Config(format!("eval case '{name}' {problem}"));
+        if !names.insert(name.clone()) {
+            return Err(CrabClawError::Config(format!(
+                "duplicate eval case name '{name}'"
+            )));
+        }
+        if case.prompt.trim().is_empty() {
+            return Err(invalid("has an empty prompt"));
+        }
+        if case.expect.is_empty() {
+            return Err(invalid("has no expectations"));
+        }
+        if let Some(pattern) = &case.expect.regex
+            && let Err(e) = regex::Regex::new(pattern)
+        {
+            return Err(invalid(&format!("has an invalid regex: {e}")));
+        }
+        if case.expect.schema.as_ref().is_some_and(|s| !s.is_object()) {
+            return Err(invalid("has a schema that is not an object"));
+        }
+        if case.expect.rubric.is_some() && suite.judge.is_none() {
+            return Err(invalid("has a rubric but the suite names no judge model"));
+        }
+        case.workspace = Some(resolve(case.workspace.take(), &default_workspace));
+    }
+    if let Some(judge) = &suite.judge {
+        crate::llm::models::validate_model_id(judge)
+            .map_err(|e| CrabClawError::Config(format!("invalid judge model '{judge}': {e}")))?;
+    }
+    Ok(suite)
+}
+
+/// Run every case, at most `concurrency` at a time, and collect the results
+/// in case order. A case's model (or the suite's) takes precedence over
+/// `--model` in `overrides`.
+pub async fn run_eval(
+    suite: &EvalSuite,
+    profile: Option<&str>,
+    overrides: &CliConfigOverrides,
+    concurrency: usize,
+) -> EvalReport {
+    let run_id = chrono::Utc::now().format("%Y%m%dT%H%M%S").to_string();
+    let mut results: Vec<(usize, CaseResult)> =
+        futures_util::stream::iter(suite.cases.iter().enumerate())
+            .map(|(i, case)| {
+                let model = case.model.clone().or_else(|| suite.model.clone());
+                let judge = suite.judge.as_deref();
+                let run_id = &run_id;
+                async move {
+                    let result = run_case(case, model, judge, profile, overrides, run_id).await;
+                    (i, result)
+                }
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect()
+            .await;
+    results.sort_by_key(|(i, _)| *i);
+
+    let results: Vec<CaseResult> = results.into_iter().map(|(_, r)| r).collect();
+    let passed = results.iter().filter(|r| r.passed).count();
+    let score = results.iter().map(|r| r.score).sum::<f64>() / results.len().max(1) as f64;
+    EvalReport {
+        total: results.len(),
+        passed,
+        failed: results.len() - passed,
+        score,
+        results,
+    }
+}
+
+async fn run_case(
+    case: &EvalCase,
+    model: Option<String>,
+    judge: Option<&str>,
+    profile: Option<&str>,
+    overrides: &CliConfigOverrides,
+    run_id: &str,
+) -> CaseResult {
+    let name = case.name.clone().unwrap_or_default();
+    let workspace = case.workspace.clone().unwrap_or_else(|| PathBuf::from("."));
+    let started = Instant::now();
+    let mut result = CaseResult {
+        name: name.clone(),
+        model: model.clone(),
+        passed: false,
+        score: 0.0,
+        checks: Vec::new(),
+        output: None,
+        tools: Vec::new(),
+        error: None,
+        duration_ms: 0,
+    };
+
+    let mut overrides = overrides.clone();
+    if model.is_some() {
+        overrides.model = model;
+    }
+    let outcome = async {
+        let config = load_runtime_config(&workspace, profile, &overrides)?;
+        let session_id = format!("eval:{name}:{run_id}");
+        let mut agent = AgentLoop::open(&config, &workspace, &session_id, None, None)?;
+        info!(case = %name, model = %config.model, "eval.case.start");
+        let loop_result = agent.handle_input(&case.prompt).await;
+        let answer = loop_result.to_reply().unwrap_or_default();
+        let mut checks = check_answer(&case.expect, &answer, &loop_result.invoked_tools);
+        if let (Some(rubric), Some(judge)) = (&case.expect.rubric, judge) {
+            let judge_config = AppConfig {
+                model: judge.to_string(),
+                ..config.clone()
+            };
+            checks.push(
+                grade(
+                    &judge_config,
+                    &workspace,
+                    agent.tape(),
+                    &case.prompt,
+                    &answer,
+                    rubric,
+                )
+                .await,
+            );
+        }
+        Ok::<_, CrabClawError>((config.model.clone(), loop_result, answer, checks))
+    }
+    .await;
+
+    match outcome {
+        Ok((model, loop_result, answer, checks)) => {
+            let passed = checks.iter().filter(|c| c.passed).count();
+            result.model = Some(model);
+            result.score = passed as f64 / checks.len().max(1) as f64;
+            result.passed = loop_result.error.is_none() && passed == checks.len();
+            result.checks = checks;
+            result.output = (!answer.is_empty()).then_some(answer);
+            result.tools = loop_result.invoked_tools;
+            result.error = loop_result.error;
+        }
+        Err(e) => result.error = Some(e.to_string()),
+    }
+    result.duration_ms = started.elapsed().as_millis() as u64;
+    info!(
+        case = %name,
+        passed = result.passed,
+        score = result.score,
+        duration_ms = result.duration_ms,
+        "eval.case.done"
+    );
+    result
+}
+
+/// Run the assertion checks (everything but the rubric).
+fn check_answer(expect: &Expectations, answer: &str, tools: &[String]) -> Vec<CheckResult> {
+    let check = |check: String, passed: bool, detail: Option<String>| CheckResult {
+        check,
+        passed,
+        detail,
+    };
+    let mut checks = Vec::new();
+    for needle in &expect.contains {
+        checks.push(check(
+            format!("contains {needle:?}"),
+            answer.contains(needle.as_str()),
+            None,
+        ));
+    }
+    for needle in &expect.not_contains {
+        checks.push(check(
+            format!("does not contain {needle:?}"),
+            !answer.contains(needle.as_str()),
+            None,
+        ));
+    }
+    if let Some(pattern) = &expect.regex {
+        let matched = regex::Regex::new(pattern).is_ok_and(|re| re.is_match(answer));
+        checks.push(check(format!("matches /{pattern}/"), matched, None));
+    }
+    for tool in &expect.tools {
+        checks.push(check(
+            format!("calls {tool}"),
+            tools.contains(tool),
+            (!tools.contains(tool)).then(|| format!("called: {}", tools.join(", "))),
+        ));
+    }
+    if let Some(schema) = &expect.schema {
+        let parsed = crate::core::structured::parse_output(answer, schema);
+        checks.push(check(
+            "matches the schema".to_string(),
+            parsed.is_ok(),
+            parsed.err(),
+        ));
+    }
+    checks
+}
+
+/// Ask the judge model whether `answer` meets `rubric`.
+async fn grade(
+    config: &AppConfig,
+    workspace: &Path,
+    tape: &TapeStore,
+    prompt: &str,
+    answer: &str,
+    rubric: &str,
+) -> CheckResult {
+    let mut messages = vec![
+        Message::system(JUDGE_PROMPT),
+        Message::user(format!(
+            "Rubric:\n{rubric}\n\nUser prompt:\n{prompt}\n\nAnswer:\n{answer}"
+        )),
+    ];
+    let turn = ModelRunner::new(config, workspace)
+        .run_turn(&mut messages, None, tape, &ToolContext::empty())
+        .await;
+    let verdict = match turn.error {
+        Some(e) => Err(format!("judge failed: {e}")),
+        None => parse_verdict(&turn.assistant_text),
+    };
+    let (passed, detail) = match verdict {
+        Ok((passed, reason)) => (passed, reason),
+        Err(e) => (false, Some(e)),
+    };
+    CheckResult {
+        check: format!("rubric ({})", config.model),
+        passed,
+        detail,
+    }
+}
+
+/// Read the judge's `{"pass": bool, "reason": "..."}` reply.
+fn parse_verdict(text: &str) -> std::result::Result<(bool, Option<String>), String> {
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "pass": { "type": "boolean" },
+            "reason": { "type": "string" }
+        },
+        "required": ["pass"]
+    });
+    let verdict = crate::core::structured::parse_output(text, &schema)
+        .map_err(|e| format!("unreadable judge verdict ({e}): {text}"))?;
+    Ok((
+        verdict["pass"].as_bool().unwrap_or(false),
+        verdict["reason"].as_str().map(String::from),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suites_are_validated() {
+        let base = Path::new("/work");
+        let suite = parse_eval_suite(
+            "judge: openai:gpt-4o\nworkspace: repo\ncases:\n  - prompt: hi\n    expect: { contains: [hello] }\n  - name: b\n    workspace: /abs\n    prompt: x\n    expect: { rubric: polite }\n",
+            base,
+        )
+        .unwrap();
+        assert_eq!(suite.cases[0].name.as_deref(), Some("case-1"));
+        assert_eq!(
+            suite.cases[0].workspace.as_deref(),
+            Some(Path::new("/work/repo"))
+        );
+        assert_eq!(suite.cases[1].workspace.as_deref(), Some(Path::new("/abs")));
+
+        for bad in [
+            "cases: []",
+            "cases:\n  - prompt: x",
+            "cases:\n  - prompt: x\n    expect: { regex: '(' }",
+            "cases:\n  - prompt: x\n    expect: { rubric: polite }",
+            "judge: gpt-4o\ncases:\n  - prompt: x\n    expect: { contains: [a] }",
+            "cases:\n  - prompt: x\n    expect: { equals: a }",
+        ] {
+            assert!(parse_eval_suite(bad, base).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn assertions_report_each_check() {
+        let expect = Expectations {
+            contains: vec!["main.rs".into()],
+            not_contains: vec!["sorry".into()],
+            regex: Some(r"^\[".into()),
+            tools: vec!["file.search".into()],
+            schema: Some(serde_json::json!({"type": "array", "items": {"type": "string"}})),
+            rubric: None,
+        };
+        let checks = check_answer(&expect, r#"["src/main.rs"]"#, &["file.list".to_string()]);
+        let failed: Vec<&str> = checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.check.as_str())
+            .collect();
+        assert_eq!(checks.len(), 5);
+        assert_eq!(failed, ["calls file.search"]);
+        assert_eq!(checks[3].detail.as_deref(), Some("called: file.list"));
+    }
+
+    #[test]
+    fn judge_verdicts_are_parsed() {
+        assert_eq!(
+            parse_verdict("```json\n{\"pass\": true, \"reason\": \"ok\"}\n```"),
+            Ok((true, Some("ok".to_string())))
+        );
+        assert!(parse_verdict("PASS").is_err());
+    }
+}
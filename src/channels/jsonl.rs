@@ -0,0 +1,156 @@
+//! Machine-readable output of `crabclaw run --output jsonl`.
+//!
+//! One JSON object per line on stdout, tagged by `type`, so a UI or script
+//! can drive crabclaw as a subprocess:
+//!
+//! ```text
+//! {"type":"turn_started","session":"cli","prompt":"list the files"}
+//! {"type":"tool_call","id":"call_1","name":"file.list","arguments":"{}"}
+//! {"type":"tool_result","id":"call_1","name":"file.list","result":"src/\n..."}
+//! {"type":"token","text":"There are "}
+//! {"type":"token","text":"two entries."}
+//! {"type":"usage","prompt_tokens":812,"completion_tokens":9,"total_tokens":821}
+//! {"type":"turn_completed","output":"There are two entries.","tool_rounds":1}
+//! ```
+//!
+//! `usage` is only emitted when the provider reported token counts.
+//! `restart` means the stream broke off and the answer starts over, so
+//! tokens received before it should be discarded.
+//! `turn_completed` is always the last line; it carries the output of comma
+//! commands in `command_output` and a failed turn's message in `error`.
+//! Logs go to stderr as usual.
+
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::core::agent_loop::{AgentLoop, LoopResult};
+use crate::core::model_runner::StreamEvent;
+
+/// One line of the event stream.
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event<'a> {
+    TurnStarted {
+        session: &'a str,
+        prompt: &'a str,
+    },
+    Token {
+        text: &'a str,
+    },
+    ToolCall {
+        id: &'a str,
+        name: &'a str,
+        arguments: &'a str,
+    },
+    ToolResult {
+        id: &'a str,
+        name: &'a str,
+        result: &'a str,
+    },
+    Restart,
+    Usage {
+        prompt_tokens: u32,
+        completion_tokens: u32,
+        total_tokens: u32,
+    },
+    TurnCompleted {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        output: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        command_output: Option<&'a str>,
+        tool_rounds: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        error: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        turn_id: Option<&'a str>,
+    },
+}
+
+impl<'a> From<StreamEvent<'a>> for Event<'a> {
+    fn from(event: StreamEvent<'a>) -> Self {
+        match event {
+            StreamEvent::Token(text) => Event::Token { text },
+            StreamEvent::ToolCall(call) => Event::ToolCall {
+                id: &call.id,
+                name: &call.function.name,
+                arguments: &call.function.arguments,
+            },
+            StreamEvent::ToolResult { call, result } => Event::ToolResult {
+                id: &call.id,
+                name: &call.function.name,
+                result,
+            },
+            StreamEvent::Restart => Event::Restart,
+        }
+    }
+}
+
+/// Write `event` as one line and flush, so readers see it at once. A closed
+/// pipe is not an error worth aborting the turn for.
+pub fn emit(out: &mut impl Write, event: &Event<'_>) {
+    let Ok(line) = serde_json::to_string(event) else {
+        return;
+    };
+    let _ = writeln!(out, "{line}").and_then(|()| out.flush());
+}
+
+/// Run one turn of `agent`, reporting it on `out` as it progresses.
+pub async fn run_turn(
+    agent: &mut AgentLoop,
+    session: &str,
+    prompt: &str,
+    out: &mut impl Write,
+) -> LoopResult {
+    emit(out, &Event::TurnStarted { session, prompt });
+    let result = agent
+        .handle_input_events(prompt, |event| emit(out, &event.into()))
+        .await;
+    if let Some(usage) = &result.usage {
+        emit(
+            out,
+            &Event::Usage {
+                prompt_tokens: usage.prompt_tokens,
+                completion_tokens: usage.completion_tokens,
+                total_tokens: usage.total_tokens,
+            },
+        );
+    }
+    emit(
+        out,
+        &Event::TurnCompleted {
+            output: result.assistant_output.as_deref(),
+            command_output: result.immediate_output.as_deref(),
+            tool_rounds: result.tool_rounds,
+            error: result.error.as_deref(),
+            turn_id: result.turn_id.as_deref(),
+        },
+    );
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_tagged_by_type() {
+        let mut out = Vec::new();
+        emit(&mut out, &Event::Token { text: "hi\n" });
+        emit(
+            &mut out,
+            &Event::TurnCompleted {
+                output: Some("hi"),
+                command_output: None,
+                tool_rounds: 0,
+                error: None,
+                turn_id: Some("0a1b2c3d4e5f"),
+            },
+        );
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"type\":\"token\",\"text\":\"hi\\n\"}\n\
+             {\"type\":\"turn_completed\",\"output\":\"hi\",\"tool_rounds\":0,\"turn_id\":\"0a1b2c3d4e5f\"}\n"
+        );
+    }
+}
@@ -1,6 +1,17 @@
+pub mod acp;
+pub mod admin;
+mod agent_cache;
 pub mod base;
+pub mod batch;
 pub mod cli;
+pub mod doctor;
+pub mod eval;
+pub mod jsonl;
 pub mod manager;
+pub mod postprocess;
+pub mod rate_limit;
 pub mod repl;
 pub mod telegram;
-mod telegram_notify;
+mod telegram_inline;
+pub(crate) mod telegram_notify;
+mod telegram_turns;
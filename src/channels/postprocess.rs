@@ -0,0 +1,183 @@
+//! Transforms applied to assistant output before a channel sends it.
+//!
+//! Filters are declared in config as a `;`-separated list and run in order;
+//! write `\;` for a `;` inside a regex or footer:
+//!
+//! ```text
+//! TELEGRAM_OUTPUT_FILTERS="strip:<internal>[\s\S]*?</internal>; max_chars:3000; footer:— sent by crabclaw"
+//! ```
+//!
+//! Only what the channel sends is changed; the tape keeps the model's answer.
+
+use regex::Regex;
+use serde::Serialize;
+
+/// One output transform.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum OutputFilter {
+    /// Remove every match of the regex (`strip:<regex>`).
+    Strip(StripPattern),
+    /// Cut the text to at most this many characters (`max_chars:<n>`).
+    MaxChars(usize),
+    /// Append a paragraph (`footer:<text>`).
+    Footer(String),
+}
+
+/// A `strip:` regex, compiled once when the config is loaded.
+#[derive(Debug, Clone)]
+pub struct StripPattern(Regex);
+
+impl StripPattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Self)
+    }
+}
+
+impl PartialEq for StripPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for StripPattern {}
+
+/// Serializes as the pattern source.
+impl Serialize for StripPattern {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+/// Parse a filter list such as `strip:<b>; max_chars:3000`.
+///
+/// Regexes are compiled here so a bad one is reported at startup.
+pub fn parse_filters(value: &str) -> Result<Vec<OutputFilter>, String> {
+    split_entries(value)
+        .iter()
+        .map(|e| e.trim())
+        .filter(|e| !e.is_empty())
+        .map(|entry| {
+            let (kind, arg) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("'{entry}': expected kind:argument"))?;
+            match kind.trim() {
+                "strip" => StripPattern::new(arg.trim())
+                    .map(OutputFilter::Strip)
+                    .map_err(|e| format!("'{entry}': {e}")),
+                "max_chars" => arg
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .map(OutputFilter::MaxChars)
+                    .ok_or_else(|| format!("'{entry}': expected a positive number")),
+                "footer" => Ok(OutputFilter::Footer(arg.trim().to_string())),
+                other => Err(format!(
+                    "'{entry}': unknown filter '{other}', expected strip, max_chars or footer"
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Split on `;`, keeping each `\;` as a literal `;`.
+fn split_entries(value: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    let mut current = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&';') => {
+                chars.next();
+                current.push(';');
+            }
+            ';' => entries.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    entries.push(current);
+    entries
+}
+
+/// Run `text` through `filters` in order.
+pub fn apply(filters: &[OutputFilter], text: &str) -> String {
+    let mut out = text.to_string();
+    for filter in filters {
+        out = match filter {
+            OutputFilter::Strip(pattern) => pattern.0.replace_all(&out, "").trim().to_string(),
+            OutputFilter::MaxChars(max) => truncate_chars(&out, *max),
+            OutputFilter::Footer(footer) if out.is_empty() => footer.clone(),
+            OutputFilter::Footer(footer) => format!("{out}\n\n{footer}"),
+        };
+    }
+    out
+}
+
+/// Keep at most `max` characters, marking a cut with a trailing `…`.
+fn truncate_chars(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut out: String = text.chars().take(max.saturating_sub(1)).collect();
+    out.truncate(out.trim_end().len());
+    out.push('…');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_filters_in_order() {
+        let filters = parse_filters("strip:<x>.*?</x>; max_chars: 10 ;footer:bye").unwrap();
+        assert_eq!(
+            filters,
+            [
+                OutputFilter::Strip(StripPattern::new("<x>.*?</x>").unwrap()),
+                OutputFilter::MaxChars(10),
+                OutputFilter::Footer("bye".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_filters() {
+        assert!(parse_filters("strip:(").is_err());
+        assert!(parse_filters("max_chars:0").is_err());
+        assert!(parse_filters("shout:yes").is_err());
+        assert!(parse_filters("footer").is_err());
+    }
+
+    #[test]
+    fn escaped_semicolons_stay_in_the_entry() {
+        let filters = parse_filters(r"strip:&nbsp\;; footer:a\; b").unwrap();
+        assert_eq!(
+            filters,
+            [
+                OutputFilter::Strip(StripPattern::new("&nbsp;").unwrap()),
+                OutputFilter::Footer("a; b".to_string()),
+            ]
+        );
+        assert_eq!(apply(&filters, "hi&nbsp;"), "hi\n\na; b");
+    }
+
+    #[test]
+    fn applies_filters_in_order() {
+        let filters = parse_filters(r"strip:<internal>[\s\S]*?</internal>; footer:-- bot").unwrap();
+        assert_eq!(
+            apply(&filters, "<internal>plan\nsteps</internal>\nThe answer."),
+            "The answer.\n\n-- bot"
+        );
+    }
+
+    #[test]
+    fn max_chars_counts_characters() {
+        let filters = [OutputFilter::MaxChars(4)];
+        assert_eq!(apply(&filters, "你好世界"), "你好世界");
+        assert_eq!(apply(&filters, "你好 世界"), "你好…");
+    }
+}
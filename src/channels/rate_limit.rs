@@ -0,0 +1,215 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Sliding window used for the per-minute message limit.
+const WINDOW: Duration = Duration::from_secs(60);
+
+/// Why a message was throttled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Throttle {
+    /// Too many messages in the last minute; retry after the given wait.
+    RateLimited { retry_after: Duration },
+    /// Too many turns already in flight for this user or chat.
+    Busy,
+}
+
+impl Throttle {
+    /// Polite reply sent back to the throttled chat.
+    pub fn message(&self) -> String {
+        match self {
+            Throttle::RateLimited { retry_after } => format!(
+                "\u{23f3} You're sending messages too quickly. Please wait {}s and try again.",
+                retry_after.as_secs().max(1)
+            ),
+            Throttle::Busy => {
+                "\u{23f3} Still working on your previous message \u{2014} please wait for it to finish."
+                    .to_string()
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct KeyState {
+    recent: VecDeque<Instant>,
+    active: u32,
+}
+
+impl KeyState {
+    /// Forget messages older than the window.
+    fn prune(&mut self, now: Instant) {
+        while self
+            .recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= WINDOW)
+        {
+            self.recent.pop_front();
+        }
+    }
+
+    /// No turn in flight and no message in the window, so the key can go.
+    fn is_idle(&self) -> bool {
+        self.active == 0 && self.recent.is_empty()
+    }
+}
+
+/// Per-key flood protection: messages per minute and concurrent turns.
+///
+/// Callers pass every key a message counts against (e.g. `user:<id>` and
+/// `chat:<id>`); the message is admitted only if all keys are under their
+/// limits. A `None` limit disables that check.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_minute: Option<u32>,
+    max_concurrent: Option<u32>,
+    state: Arc<Mutex<HashMap<String, KeyState>>>,
+}
+
+impl RateLimiter {
+    pub fn new(per_minute: Option<u32>, max_concurrent: Option<u32>) -> Self {
+        Self {
+            per_minute,
+            max_concurrent,
+            state: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Admit one message for `keys`, or report why it is throttled.
+    ///
+    /// The returned guard holds a concurrent-turn slot until dropped.
+    pub fn try_acquire(&self, keys: &[String]) -> Result<TurnGuard, Throttle> {
+        self.try_acquire_at(keys, Instant::now())
+    }
+
+    fn try_acquire_at(&self, keys: &[String], now: Instant) -> Result<TurnGuard, Throttle> {
+        let mut state = self.state.lock().unwrap();
+        // Sweep keys that went quiet so one-off senders do not pile up.
+        state.retain(|_, entry| {
+            entry.prune(now);
+            !entry.is_idle()
+        });
+
+        for key in keys {
+            let entry = state.entry(key.clone()).or_default();
+            if let Some(limit) = self.max_concurrent
+                && entry.active >= limit
+            {
+                return Err(Throttle::Busy);
+            }
+            if let Some(limit) = self.per_minute
+                && entry.recent.len() >= limit as usize
+            {
+                let oldest = entry.recent.front().copied().unwrap_or(now);
+                let retry_after = WINDOW.saturating_sub(now.duration_since(oldest));
+                return Err(Throttle::RateLimited { retry_after });
+            }
+        }
+
+        for key in keys {
+            let entry = state.entry(key.clone()).or_default();
+            entry.recent.push_back(now);
+            entry.active += 1;
+        }
+        Ok(TurnGuard {
+            keys: keys.to_vec(),
+            state: Arc::clone(&self.state),
+        })
+    }
+}
+
+/// Releases a concurrent-turn slot when the turn finishes.
+#[derive(Debug)]
+pub struct TurnGuard {
+    keys: Vec<String>,
+    state: Arc<Mutex<HashMap<String, KeyState>>>,
+}
+
+impl Drop for TurnGuard {
+    fn drop(&mut self) {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        for key in &self.keys {
+            if let Some(entry) = state.get_mut(key) {
+                entry.active = entry.active.saturating_sub(1);
+                entry.prune(now);
+                if entry.is_idle() {
+                    state.remove(key);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(user: &str) -> Vec<String> {
+        vec![format!("user:{user}"), "chat:1".to_string()]
+    }
+
+    #[test]
+    fn unlimited_when_disabled() {
+        let limiter = RateLimiter::new(None, None);
+        let guards: Vec<_> = (0..100)
+            .map(|_| limiter.try_acquire(&keys("a")).unwrap())
+            .collect();
+        assert_eq!(guards.len(), 100);
+    }
+
+    #[test]
+    fn per_minute_limit_resets_after_window() {
+        let limiter = RateLimiter::new(Some(2), None);
+        let start = Instant::now();
+        drop(limiter.try_acquire_at(&keys("a"), start).unwrap());
+        drop(limiter.try_acquire_at(&keys("a"), start).unwrap());
+
+        let later = start + Duration::from_secs(20);
+        match limiter.try_acquire_at(&keys("a"), later) {
+            Err(Throttle::RateLimited { retry_after }) => {
+                assert_eq!(retry_after, Duration::from_secs(40));
+            }
+            other => panic!("expected rate limit, got: {other:?}"),
+        }
+
+        assert!(limiter.try_acquire_at(&keys("a"), start + WINDOW).is_ok());
+    }
+
+    #[test]
+    fn concurrent_turns_released_on_drop() {
+        let limiter = RateLimiter::new(None, Some(1));
+        let guard = limiter.try_acquire(&keys("a")).unwrap();
+        assert_eq!(limiter.try_acquire(&keys("a")).unwrap_err(), Throttle::Busy);
+        // Another user in the same chat is also blocked by the chat key.
+        assert_eq!(limiter.try_acquire(&keys("b")).unwrap_err(), Throttle::Busy);
+        drop(guard);
+        assert!(limiter.try_acquire(&keys("a")).is_ok());
+    }
+
+    #[test]
+    fn quiet_keys_are_forgotten() {
+        let limiter = RateLimiter::new(Some(5), Some(1));
+        let start = Instant::now();
+        drop(limiter.try_acquire_at(&keys("a"), start).unwrap());
+        assert_eq!(limiter.state.lock().unwrap().len(), 2);
+
+        let guard = limiter
+            .try_acquire_at(&["user:b".to_string()], start + WINDOW)
+            .unwrap();
+        let state = limiter.state.lock().unwrap();
+        assert_eq!(state.keys().collect::<Vec<_>>(), ["user:b"]);
+        drop(state);
+        drop(guard);
+    }
+
+    #[test]
+    fn throttle_messages_are_polite() {
+        let msg = Throttle::RateLimited {
+            retry_after: Duration::from_millis(300),
+        }
+        .message();
+        assert!(msg.contains("wait 1s"), "got: {msg}");
+        assert!(Throttle::Busy.message().contains("previous message"));
+    }
+}
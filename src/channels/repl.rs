@@ -1,22 +1,77 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use rustyline::DefaultEditor;
+use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
 
 use crate::core::agent_loop::AgentLoop;
 use crate::core::config::AppConfig;
 use crate::core::error::{CrabClawError, Result};
 
+/// Tab completion of `@path` file mentions against the workspace.
+struct MentionCompleter {
+    workspace: PathBuf,
+}
+
+impl Completer for MentionCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(
+            crate::core::mentions::complete_mention(&self.workspace, line, pos)
+                .unwrap_or((pos, Vec::new())),
+        )
+    }
+}
+
+impl Hinter for MentionCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for MentionCompleter {}
+
+impl Validator for MentionCompleter {}
+
+impl Helper for MentionCompleter {}
+
+/// Ask on the terminal whether the model may run `command`.
+fn confirm_command(command: &str) -> bool {
+    use std::io::Write;
+
+    println!();
+    print!("The assistant wants to run `{command}`. Allow? [y/N] ");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
+
 /// Run an interactive REPL session.
 ///
 /// Delegates to `AgentLoop::handle_input_stream` for each user input,
 /// which handles command routing, tool calling, tape recording,
 /// and streaming output.
-pub fn run_interactive(config: &AppConfig, workspace: &Path) -> Result<()> {
-    let mut agent = AgentLoop::open(config, workspace, "default", None, None)?;
+pub fn run_interactive(config: &AppConfig, workspace: &Path, session: &str) -> Result<()> {
+    let mut agent = AgentLoop::open(config, workspace, session, None, None)?;
+    agent.set_command_approver(Arc::new(confirm_command));
 
-    let mut editor = DefaultEditor::new()
+    let mut editor = Editor::<MentionCompleter, DefaultHistory>::new()
         .map_err(|e| CrabClawError::Config(format!("failed to init editor: {e}")))?;
+    editor.set_helper(Some(MentionCompleter {
+        workspace: workspace.to_path_buf(),
+    }));
 
     // Load history from workspace
     let history_path = workspace.join(".crabclaw").join("history.txt");
@@ -25,6 +80,7 @@ pub fn run_interactive(config: &AppConfig, workspace: &Path) -> Result<()> {
     println!("CrabClaw interactive mode");
     println!("  model: {}", config.model);
     println!("  workspace: {}", workspace.display());
+    println!("  session: {session}");
     println!("  Type ,help for commands, ,quit to exit.\n");
 
     let rt = tokio::runtime::Builder::new_current_thread()
@@ -33,6 +89,10 @@ pub fn run_interactive(config: &AppConfig, workspace: &Path) -> Result<()> {
         .map_err(|e| CrabClawError::Network(format!("failed to start runtime: {e}")))?;
 
     loop {
+        // `,workspace switch` changes where `@path` mentions resolve.
+        if let Some(helper) = editor.helper_mut() {
+            helper.workspace = agent.workspace().to_path_buf();
+        }
         let cwd_name = workspace
             .file_name()
             .and_then(|n| n.to_str())
@@ -71,9 +131,12 @@ pub fn run_interactive(config: &AppConfig, workspace: &Path) -> Result<()> {
                     println!("{output}");
                 }
 
-                if result.tool_rounds > 0 {
-                    // Print tool round info for user awareness
-                    println!("  ({} tool round(s))", result.tool_rounds);
+                for path in result.attachments.iter().filter_map(|a| a.path()) {
+                    println!("  [attached: {}]", path.display());
+                }
+
+                if let Some(timing) = &result.timing {
+                    println!("  ({})", timing.footer());
                 }
 
                 if let Some(err) = &result.error {
@@ -1,29 +1,52 @@
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use async_trait::async_trait;
 use teloxide::prelude::*;
-use teloxide::types::{ChatAction, MediaKind, MessageKind, ParseMode};
+use teloxide::types::{ChatAction, InlineQuery, InputFile, MediaKind, MessageKind, ParseMode};
 use tracing::{debug, info, warn};
 
+use crate::channels::admin::{self, AdminCommand};
+use crate::channels::agent_cache::AgentCache;
 use crate::channels::base::{Channel, ChannelResponse};
-use crate::channels::telegram_notify::get_or_create_notifier_sender;
+use crate::channels::postprocess;
+use crate::channels::rate_limit::{RateLimiter, Throttle};
+use crate::channels::telegram_inline::handle_inline_query;
+use crate::channels::telegram_notify::chat_notifier;
+use crate::channels::telegram_turns::TurnTracker;
+use crate::core::agent_loop::{AgentLoop, IdleArchive};
 use crate::core::config::AppConfig;
+use crate::core::reply_context::{self, ReplyTarget};
+use crate::tools::output::ToolOutput;
 
 /// Telegram channel adapter using long polling.
 ///
 /// Aligned with bub's `TelegramChannel`:
 /// - Long polling for updates
 /// - ACL via allow_from (user IDs/usernames) and allow_chats (chat IDs)
+/// - Admin tier (`TELEGRAM_ADMINS`) unlocking `,admin` management commands
+/// - Per-user and per-chat rate limits (messages per minute, concurrent turns)
 /// - Typing indicator during processing
+/// - Edits to the latest prompt supersede its turn and re-run it
+/// - Inline queries (`@bot <question>`) answered by a toolless quick-answer
+///   call, outside any session
 /// - Routes messages through CrabClaw router + model pipeline
 pub struct TelegramChannel {
-    config: Arc<AppConfig>,
+    config: SharedConfig,
     workspace: std::path::PathBuf,
+    limiter: Arc<RateLimiter>,
 }
 
 impl TelegramChannel {
     pub fn new(config: Arc<AppConfig>, workspace: std::path::PathBuf) -> Self {
-        Self { config, workspace }
+        let limiter = Arc::new(RateLimiter::new(
+            config.telegram_rate_limit_per_minute,
+            config.telegram_max_concurrent_turns,
+        ));
+        Self {
+            config: Arc::new(RwLock::new(config)),
+            workspace,
+            limiter,
+        }
     }
 }
 
@@ -34,8 +57,7 @@ impl Channel for TelegramChannel {
     }
 
     async fn start(&mut self) -> crate::core::error::Result<()> {
-        let token = self
-            .config
+        let token = current_config(&self.config)
             .telegram_token
             .as_ref()
             .ok_or_else(|| {
@@ -48,15 +70,51 @@ impl Channel for TelegramChannel {
         let bot = Bot::new(&token);
         let config = Arc::clone(&self.config);
         let workspace = self.workspace.clone();
-
-        let handler = Update::filter_message().endpoint(move |bot: Bot, msg: Message| {
-            let config = Arc::clone(&config);
-            let workspace = workspace.clone();
-            async move {
-                handle_message(bot, msg, config, &workspace).await;
-                respond(())
-            }
-        });
+        let limiter = Arc::clone(&self.limiter);
+
+        let inline_config = Arc::clone(&config);
+        let inline_limiter = Arc::clone(&limiter);
+        let startup = current_config(&config);
+        let turns = TurnContext {
+            config,
+            workspace,
+            limiter,
+            tracker: Arc::new(TurnTracker::default()),
+            workers: Arc::new(tokio::sync::Semaphore::new(startup.telegram_workers)),
+            agents: Arc::new(AgentCache::new(startup.agent_cache_size)),
+        };
+        let edit_turns = turns.clone();
+        let handler = dptree::entry()
+            .branch(
+                Update::filter_message().endpoint(move |bot: Bot, msg: Message| {
+                    spawn_turn(bot, msg, &turns, None);
+                    async { respond(()) }
+                }),
+            )
+            .branch(
+                Update::filter_edited_message().endpoint(move |bot: Bot, msg: Message| {
+                    // Only an edit of the chat's latest prompt, while it runs or
+                    // shortly after, re-runs the turn.
+                    match edit_turns.tracker.supersede(msg.chat.id.0, msg.id.0) {
+                        Some(original) => {
+                            info!(chat_id = msg.chat.id.0, "telegram.edit.supersede");
+                            spawn_turn(bot, msg, &edit_turns, Some(original));
+                        }
+                        None => debug!(chat_id = msg.chat.id.0, "telegram.edit.ignored"),
+                    }
+                    async { respond(()) }
+                }),
+            )
+            .branch(
+                Update::filter_inline_query().endpoint(move |bot: Bot, query: InlineQuery| {
+                    let config = current_config(&inline_config);
+                    let limiter = Arc::clone(&inline_limiter);
+                    async move {
+                        handle_inline_query(bot, query, &config, &limiter).await;
+                        respond(())
+                    }
+                }),
+            );
 
         Dispatcher::builder(bot, handler)
             .enable_ctrlc_handler()
@@ -73,12 +131,136 @@ impl Channel for TelegramChannel {
     }
 }
 
+/// What a message turn task needs, shared by every turn.
+#[derive(Clone)]
+struct TurnContext {
+    config: SharedConfig,
+    workspace: std::path::PathBuf,
+    limiter: Arc<RateLimiter>,
+    tracker: Arc<TurnTracker>,
+    /// Bounds the turns running at once across all chats.
+    workers: Arc<tokio::sync::Semaphore>,
+    agents: Arc<AgentCache>,
+}
+
+/// Run the turn for `msg` as its own task so an edit can abort it.
+///
+/// Turns of one session still run one at a time, in arrival order, under the
+/// session lock; turns of different sessions run side by side, at most
+/// `TELEGRAM_WORKERS` at once. `supersedes` is the original text when `msg`
+/// is an edit.
+///
+/// Flood protection runs here, on arrival, so a throttled message never
+/// queues: its rate-limiter slot is held until the turn finishes, and a
+/// session whose backlog is full is told to wait.
+fn spawn_turn(bot: Bot, msg: Message, ctx: &TurnContext, supersedes: Option<String>) {
+    let chat = msg.chat.id.0;
+    let message_id = msg.id.0;
+    let text = msg.text().or(msg.caption()).unwrap_or_default().to_string();
+    let user = msg.from.as_ref().map(|user| user.id.0);
+    let mut limit_keys = vec![format!("chat:{chat}")];
+    if let Some(user) = user {
+        limit_keys.push(format!("user:{user}"));
+    }
+    // An edit replaces a turn that was already admitted, and whose slot is
+    // only freed once its aborted task is dropped.
+    let limit = match supersedes {
+        Some(_) => None,
+        None => match ctx.limiter.try_acquire(&limit_keys) {
+            Ok(guard) => Some(guard),
+            Err(throttle) => {
+                warn!(chat_id = chat, ?throttle, "telegram.rate_limited");
+                reply_later(bot, msg.chat.id, throttle.message());
+                return;
+            }
+        },
+    };
+    let (_, session) = session_scope(&current_config(&ctx.config), &ctx.workspace, chat, user);
+    let Some(queued) = ctx.tracker.enqueue(&session) else {
+        warn!(chat_id = chat, session = %session, "telegram.backlog_full");
+        reply_later(bot, msg.chat.id, Throttle::Busy.message());
+        return;
+    };
+    let (ready_tx, ready_rx) = tokio::sync::oneshot::channel::<()>();
+    let task_ctx = ctx.clone();
+    let task = tokio::spawn(async move {
+        let _limit = limit;
+        // Wait until the tracker knows this task, so `finished` finds it.
+        let _ = ready_rx.await;
+        let _turn = queued.wait().await;
+        let Ok(_worker) = task_ctx.workers.acquire().await else {
+            return;
+        };
+        handle_message(
+            bot,
+            msg,
+            &task_ctx.config,
+            &task_ctx.workspace,
+            &task_ctx.agents,
+            supersedes,
+        )
+        .await;
+        task_ctx.tracker.finished(chat, message_id);
+    });
+    ctx.tracker
+        .started(chat, message_id, &text, task.abort_handle());
+    let _ = ready_tx.send(());
+}
+
+/// Send `text` to `chat` from a task of its own, for replies that do not
+/// start a turn.
+fn reply_later(bot: Bot, chat: ChatId, text: String) {
+    tokio::spawn(async move {
+        let _ = bot.send_message(chat, text).await;
+    });
+}
+
+/// Aborts a background task when dropped, e.g. when its turn is aborted.
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Channel config, replaceable at runtime by `,admin reload-config`.
+type SharedConfig = Arc<RwLock<Arc<AppConfig>>>;
+
+fn current_config(shared: &SharedConfig) -> Arc<AppConfig> {
+    Arc::clone(&shared.read().unwrap())
+}
+
+/// Telegram slash commands accepted as aliases for internal commands.
+const SLASH_COMMANDS: &[&str] = &["model"];
+
+/// Translate `/model args` (or `/model@bot_name args`) into `,model args`,
+/// with `prefix` in place of the `,`.
+fn slash_alias(text: &str, prefix: char) -> Option<String> {
+    let rest = text.trim_start().strip_prefix('/')?;
+    let (head, args) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let name = head.split('@').next().unwrap_or(head);
+    if !SLASH_COMMANDS.contains(&name) {
+        return None;
+    }
+    let args = args.trim();
+    Some(if args.is_empty() {
+        format!("{prefix}{name}")
+    } else {
+        format!("{prefix}{name} {args}")
+    })
+}
+
 async fn handle_message(
     bot: Bot,
     msg: Message,
-    config: Arc<AppConfig>,
+    shared_config: &SharedConfig,
     workspace: &std::path::Path,
+    agents: &AgentCache,
+    supersedes: Option<String>,
 ) {
+    let config = current_config(shared_config);
+
     // Extract text content from various message types
     let text = match &msg.kind {
         MessageKind::Common(common) => match &common.media_kind {
@@ -117,7 +299,21 @@ async fn handle_message(
         _ => return,
     };
 
+    let prefix = config.telegram_command_prefix;
+    let text = slash_alias(&text, prefix).unwrap_or(text);
     let chat_id = msg.chat.id;
+    // Replies to the bot's own messages carry that message as context.
+    let reply_to = msg
+        .reply_to_message()
+        .filter(|replied| replied.from.as_ref().is_some_and(|u| u.is_bot))
+        .map(|replied| ReplyTarget {
+            message_id: Some(i64::from(replied.id.0)),
+            text: replied
+                .text()
+                .or(replied.caption())
+                .unwrap_or_default()
+                .to_string(),
+        });
 
     // ACL check
     if let Some(user) = msg.from.as_ref() {
@@ -142,91 +338,77 @@ async fn handle_message(
         }
     }
 
-    // Build per-session notifier for schedule jobs (Bub-style context-bound callback)
-    let notifier: Option<crate::tools::schedule::Notifier> = {
-        let tg_token = config.telegram_token.clone().unwrap_or_default();
-        let tg_chat_id = chat_id.0;
-        let sender = get_or_create_notifier_sender(&tg_token, tg_chat_id).await;
-        Some(std::sync::Arc::new(move |text: String| {
-            if sender.send(text).is_err() {
-                warn!(chat_id = tg_chat_id, "telegram.notifier.sender_closed");
+    // Admin commands are handled here and never reach the router or model.
+    // For non-admins `,admin` is not special and is processed like any input.
+    if let Some(user) = msg.from.as_ref()
+        && admin::is_admin(
+            &config.telegram_admins,
+            &user.id.0.to_string(),
+            user.username.as_deref(),
+        )
+        && let Some(command) = admin::parse_admin_command(&text, prefix)
+    {
+        info!(user_id = user.id.0, text = %text, "telegram.admin");
+        let reply = match command {
+            Ok(command) => run_admin_command(command, &bot, shared_config, workspace).await,
+            Err(usage) => usage,
+        };
+        for chunk in split_message(&reply, 4096) {
+            if let Err(e) = bot.send_message(chat_id, chunk).await {
+                warn!("telegram.send.plain_error: {e}");
             }
-        }))
-    };
+        }
+        return;
+    }
+    if let Some(command) = admin::admin_only_command(&text, prefix)
+        && !msg.from.as_ref().is_some_and(|user| {
+            admin::is_admin(
+                &config.telegram_admins,
+                &user.id.0.to_string(),
+                user.username.as_deref(),
+            )
+        })
+    {
+        let _ = bot
+            .send_message(chat_id, format!("Only admins can use {prefix}{command}."))
+            .await;
+        return;
+    }
 
-    // Build per-session agent runner for scheduled agent-mode jobs.
-    // When the job fires, this closure runs the full agent pipeline
-    // (LLM + tools like web.fetch) and sends the result to Telegram.
-    let agent_runner: Option<crate::tools::schedule::AgentRunner> = {
-        let run_config = config.clone();
-        let run_workspace = workspace.to_path_buf();
-        let run_session = format!("telegram:{}", chat_id.0);
-        let tg_token = config.telegram_token.clone().unwrap_or_default();
-        let tg_chat_id = chat_id.0;
-        Some(std::sync::Arc::new(move |prompt: String| {
-            let config = run_config.clone();
-            let workspace = run_workspace.clone();
-            let session_id = run_session.clone();
-            let token = tg_token.clone();
-            let chat = tg_chat_id;
-            Box::pin(async move {
-                info!(
-                    prompt = %prompt,
-                    session_id = %session_id,
-                    "schedule.agent_runner: starting agent execution"
-                );
-
-                // Run the full agent pipeline with the prompt
-                let response =
-                    process_message(&prompt, &config, &workspace, &session_id, None, None).await;
-
-                // Deliver the result to the Telegram chat
-                match response.to_reply() {
-                    Some(reply) => {
-                        info!(
-                            reply_len = reply.len(),
-                            "schedule.agent_runner: delivering result to telegram"
-                        );
-                        let url = format!("https://api.telegram.org/bot{token}/sendMessage");
-                        let client = reqwest::Client::new();
-                        for chunk in split_message(&reply, 4096) {
-                            let html = markdown_to_telegram_html(&chunk);
-                            match client
-                                .post(&url)
-                                .json(&serde_json::json!({
-                                    "chat_id": chat,
-                                    "text": html,
-                                    "parse_mode": "HTML",
-                                }))
-                                .send()
-                                .await
-                            {
-                                Ok(resp) => {
-                                    if !resp.status().is_success() {
-                                        warn!(
-                                            status = %resp.status(),
-                                            "schedule.agent_runner: telegram sendMessage failed"
-                                        );
-                                    }
-                                }
-                                Err(e) => {
-                                    warn!(
-                                        error = %e,
-                                        "schedule.agent_runner: telegram sendMessage error"
-                                    );
-                                }
-                            }
-                        }
-                    }
-                    None => {
-                        warn!("schedule.agent_runner: process_message returned empty response");
-                    }
-                }
-            })
-        }))
+    // Per-user isolation: workspace and tape are scoped to the sender when configured
+    let user_id = msg.from.as_ref().map(|u| u.id.0);
+    let (workspace, session_id) = session_scope(&config, workspace, chat_id.0, user_id);
+    if let Err(e) = std::fs::create_dir_all(&workspace) {
+        warn!(workspace = %workspace.display(), "telegram.workspace.error: {e}");
+        let _ = bot
+            .send_message(chat_id, "Failed to prepare your workspace.")
+            .await;
+        return;
+    }
+    let workspace = workspace.as_path();
+
+    // Reuse the session's live agent loop from earlier messages when there
+    // is one; opening a loop re-reads the tape and rediscovers skills.
+    let mut agent = match agents.take(&session_id, &config) {
+        Some(agent) => Ok(agent),
+        None => open_agent(&config, workspace, &session_id, chat_id.0).await,
     };
+    if let (Ok(agent), Some(hours)) = (agent.as_mut(), config.telegram_session_ttl_hours) {
+        // Months-old context only confuses new questions; start over.
+        let ttl = i64::try_from(hours)
+            .ok()
+            .and_then(chrono::TimeDelta::try_hours)
+            .unwrap_or(chrono::TimeDelta::MAX);
+        match agent.archive_if_idle(ttl, chrono::Utc::now()) {
+            Ok(Some(archived)) => {
+                info!(session_id = %session_id, idle_hours = archived.idle.num_hours(), "telegram.session.expired");
+                let _ = bot.send_message(chat_id, idle_notice(&archived)).await;
+            }
+            Ok(None) => {}
+            Err(e) => warn!("telegram.session.expire_error: {e}"),
+        }
+    }
 
-    let session_id = format!("telegram:{}", chat_id.0);
     info!(
         session_id = %session_id,
         text = %text,
@@ -235,30 +417,34 @@ async fn handle_message(
 
     // Sustained typing indicator — sends every 4 seconds until processing completes
     let bot_clone = bot.clone();
-    let typing_handle = tokio::spawn(async move {
+    let typing = AbortOnDrop(tokio::spawn(async move {
         loop {
             let _ = bot_clone
                 .send_chat_action(chat_id, ChatAction::Typing)
                 .await;
             tokio::time::sleep(std::time::Duration::from_secs(4)).await;
         }
-    });
+    }));
 
     // Process through CrabClaw router + model + tool calling
-    let response = process_message(
-        &text,
-        &config,
-        workspace,
-        &session_id,
-        notifier,
-        agent_runner,
-    )
-    .await;
+    let inbound = Inbound {
+        user: user_id.map(|id| id.to_string()),
+        reply_to,
+        supersedes,
+    };
+    let (response, mut agent) = match agent {
+        Ok(mut agent) => (
+            run_turn(&mut agent, &text, inbound, &config).await,
+            Some(agent),
+        ),
+        Err(e) => (agent_error(e), None),
+    };
 
     // Stop typing indicator
-    typing_handle.abort();
+    drop(typing);
 
     if let Some(reply) = response.to_reply() {
+        let mut sent_ids = Vec::new();
         // Telegram has a 4096 char limit per message.
         // Convert each chunk to HTML independently so tags aren't split across messages.
         for chunk in split_message(&reply, 4096) {
@@ -268,18 +454,210 @@ async fn handle_message(
                 .parse_mode(ParseMode::Html)
                 .await;
 
-            if let Err(e) = send_result {
-                // Fallback: send as plain text if HTML parsing fails
-                warn!("telegram.send.html_error: {e} — retrying without parse_mode");
-                if let Err(e2) = bot.send_message(chat_id, &chunk).await {
-                    warn!("telegram.send.plain_error: {e2}");
+            match send_result {
+                Ok(sent) => sent_ids.push(i64::from(sent.id.0)),
+                Err(e) => {
+                    // Fallback: send as plain text if HTML parsing fails
+                    warn!("telegram.send.html_error: {e} — retrying without parse_mode");
+                    match bot.send_message(chat_id, &chunk).await {
+                        Ok(sent) => sent_ids.push(i64::from(sent.id.0)),
+                        Err(e2) => warn!("telegram.send.plain_error: {e2}"),
+                    }
                 }
             }
         }
+        if let Some(agent) = agent.as_mut()
+            && response.assistant_output.is_some()
+            && !sent_ids.is_empty()
+        {
+            // Record which Telegram messages carry the answer, so a later
+            // reply to them can be traced back (see `core::reply_context`).
+            if let Err(e) = reply_context::link_sent_messages(agent.tape_mut(), &sent_ids) {
+                warn!("telegram.reply_link.error: {e}");
+            }
+        }
+    }
+    send_attachments(&bot, chat_id, &response.attachments).await;
+
+    if let Some(agent) = agent {
+        agents.put(&session_id, &config, agent);
     }
 }
 
-fn acl_allows(
+/// Open the agent loop of `session_id`, wired to deliver schedule
+/// notifications back to `chat_id`.
+async fn open_agent(
+    config: &Arc<AppConfig>,
+    workspace: &std::path::Path,
+    session_id: &str,
+    chat_id: i64,
+) -> crate::core::error::Result<AgentLoop> {
+    // Build per-session notifier for schedule jobs (Bub-style context-bound callback)
+    let notifier: Option<crate::tools::schedule::Notifier> = {
+        let tg_token = config.telegram_token.clone().unwrap_or_default();
+        Some(chat_notifier(&tg_token, chat_id).await)
+    };
+
+    // Build per-session agent runner for scheduled agent-mode jobs.
+    // When the job fires, this closure runs the full agent pipeline
+    // (LLM + tools like web.fetch); the scheduler delivers the result
+    // through the job's notifier.
+    let agent_runner: Option<crate::tools::schedule::AgentRunner> = {
+        let run_config = config.clone();
+        let run_workspace = workspace.to_path_buf();
+        let run_session = session_id.to_string();
+        Some(std::sync::Arc::new(
+            move |job_id: String, prompt: String| {
+                let config = run_config.clone();
+                let workspace = run_workspace.clone();
+                let session_id = run_session.clone();
+                Box::pin(async move {
+                    info!(
+                        job_id = %job_id,
+                        prompt = %prompt,
+                        session_id = %session_id,
+                        "schedule.agent_runner: starting agent execution"
+                    );
+                    run_scheduled_job(&job_id, &prompt, &config, &workspace, &session_id).await
+                })
+            },
+        ))
+    };
+
+    let mut agent = AgentLoop::open_shared(
+        Arc::clone(config),
+        workspace,
+        session_id,
+        notifier,
+        agent_runner,
+    )?;
+    agent.set_origin_chat(chat_id);
+    Ok(agent)
+}
+
+/// Workspace and session ID for a message.
+///
+/// By default sessions are per chat and share the bot's workspace. With
+/// `TELEGRAM_USER_WORKSPACE_ROOT` set, each sender gets `<root>/<user_id>`
+/// as workspace (so `file.*` tools and tapes are private to them) and a
+/// `telegram:<chat_id>:<user_id>` session.
+fn session_scope(
+    config: &AppConfig,
+    workspace: &std::path::Path,
+    chat_id: i64,
+    user_id: Option<u64>,
+) -> (std::path::PathBuf, String) {
+    match (user_workspace_root(config, workspace), user_id) {
+        (Some(root), Some(user_id)) => (
+            root.join(user_id.to_string()),
+            format!("telegram:{chat_id}:{user_id}"),
+        ),
+        _ => (workspace.to_path_buf(), format!("telegram:{chat_id}")),
+    }
+}
+
+fn user_workspace_root(
+    config: &AppConfig,
+    workspace: &std::path::Path,
+) -> Option<std::path::PathBuf> {
+    config
+        .telegram_user_workspace_root
+        .as_deref()
+        .map(|root| workspace.join(root))
+}
+
+/// Sessions in the shared workspace plus every per-user workspace.
+fn all_sessions(config: &AppConfig, workspace: &std::path::Path) -> Vec<admin::SessionSummary> {
+    let mut sessions = admin::session_summaries(workspace);
+    if let Some(root) = user_workspace_root(config, workspace)
+        && let Ok(entries) = std::fs::read_dir(root)
+    {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                sessions.extend(admin::session_summaries(&entry.path()));
+            }
+        }
+    }
+    sessions
+}
+
+/// Execute an admin command and return the reply text.
+async fn run_admin_command(
+    command: AdminCommand,
+    bot: &Bot,
+    shared_config: &SharedConfig,
+    workspace: &std::path::Path,
+) -> String {
+    match command {
+        AdminCommand::Help => admin::admin_help(),
+        AdminCommand::Usage => {
+            admin::format_usage(&all_sessions(&current_config(shared_config), workspace))
+        }
+        AdminCommand::Sessions => {
+            admin::format_sessions(&all_sessions(&current_config(shared_config), workspace))
+        }
+        AdminCommand::Broadcast(message) => {
+            let sessions = all_sessions(&current_config(shared_config), workspace);
+            let chats = admin::telegram_chat_ids(&sessions);
+            let mut delivered = 0;
+            for chat in &chats {
+                match bot.send_message(ChatId(*chat), &message).await {
+                    Ok(_) => delivered += 1,
+                    Err(e) => warn!(chat_id = chat, "telegram.admin.broadcast_error: {e}"),
+                }
+            }
+            format!(
+                "Broadcast delivered to {delivered}/{} chat(s).",
+                chats.len()
+            )
+        }
+        AdminCommand::ReloadConfig => {
+            let profile = current_config(shared_config).profile.clone();
+            match crate::core::config::load_runtime_config(
+                workspace,
+                Some(&profile),
+                &crate::core::config::CliConfigOverrides::default(),
+            ) {
+                Ok(fresh) => {
+                    *shared_config.write().unwrap() = Arc::new(fresh);
+                    "Config reloaded. CLI flags are not re-applied; token, proxy and rate limits take effect after restart.".to_string()
+                }
+                Err(e) => format!("Config reload failed: {e}"),
+            }
+        }
+    }
+}
+
+/// Run a scheduled agent-mode job in a fresh agent loop for `session_id`.
+async fn run_scheduled_job(
+    job_id: &str,
+    prompt: &str,
+    config: &AppConfig,
+    workspace: &std::path::Path,
+    session_id: &str,
+) -> crate::tools::schedule::AgentRunReport {
+    match AgentLoop::open(config, workspace, session_id, None, None) {
+        Ok(mut agent) => {
+            if config.telegram_persona.is_some() {
+                agent.set_default_persona(config.telegram_persona.clone());
+            }
+            agent.set_workspace_switching(config.telegram_user_workspace_root.is_none());
+            agent.set_command_prefix(config.telegram_command_prefix);
+            agent.run_scheduled_job(job_id, prompt).await
+        }
+        Err(e) => {
+            warn!("telegram.agent_loop.error: {e}");
+            crate::tools::schedule::AgentRunReport {
+                job_id: job_id.to_string(),
+                prompt: prompt.to_string(),
+                error: Some(e.user_message()),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+pub(crate) fn acl_allows(
     allow_from: &[String],
     allow_chats: &[String],
     user_id: &str,
@@ -317,34 +695,137 @@ pub async fn process_message(
     notifier: Option<crate::tools::schedule::Notifier>,
     agent_runner: Option<crate::tools::schedule::AgentRunner>,
 ) -> ChannelResponse {
-    let mut agent = match crate::core::agent_loop::AgentLoop::open(
+    process_message_from(
+        text,
+        Inbound::default(),
         config,
         workspace,
         session_id,
         notifier,
         agent_runner,
-    ) {
-        Ok(a) => a,
-        Err(e) => {
-            warn!("telegram.agent_loop.error: {e}");
-            return ChannelResponse {
-                error: Some(format!("{e}")),
-                ..Default::default()
-            };
-        }
+    )
+    .await
+}
+
+/// Quick answer for a Telegram inline query; no tape or tools are involved.
+pub use crate::channels::telegram_inline::quick_answer;
+
+/// Who sent a message and what it replies to.
+#[derive(Debug, Default)]
+struct Inbound {
+    /// Sender ID, for the audit log.
+    user: Option<String>,
+    /// The earlier bot message this one replies to.
+    reply_to: Option<ReplyTarget>,
+    /// Original text of the turn this edited message replaces.
+    supersedes: Option<String>,
+}
+
+/// [`process_message`] with the sender and reply details of [`Inbound`].
+async fn process_message_from(
+    text: &str,
+    inbound: Inbound,
+    config: &AppConfig,
+    workspace: &std::path::Path,
+    session_id: &str,
+    notifier: Option<crate::tools::schedule::Notifier>,
+    agent_runner: Option<crate::tools::schedule::AgentRunner>,
+) -> ChannelResponse {
+    match AgentLoop::open(config, workspace, session_id, notifier, agent_runner) {
+        Ok(mut agent) => run_turn(&mut agent, text, inbound, config).await,
+        Err(e) => agent_error(e),
+    }
+}
+
+/// Notice sent when `TELEGRAM_SESSION_TTL_HOURS` archived an idle session.
+fn idle_notice(archived: &IdleArchive) -> String {
+    let since = match archived.idle.num_days() {
+        days if days >= 2 => format!("{days} days"),
+        _ => format!("{} hours", archived.idle.num_hours()),
     };
+    let mut notice = format!(
+        "No messages for {since}, so the old conversation was archived and this one starts fresh."
+    );
+    let mut kept = Vec::new();
+    if archived.pins > 0 {
+        kept.push(format!("{} pinned note(s)", archived.pins));
+    }
+    if archived.settings {
+        kept.push("your persona, model and workspace choices".to_string());
+    }
+    if !kept.is_empty() {
+        notice.push_str(&format!(" {} carry over.", kept.join(" and ")));
+    }
+    notice
+}
+
+/// Reply for a session whose agent loop could not be opened.
+fn agent_error(e: crate::core::error::CrabClawError) -> ChannelResponse {
+    warn!("telegram.agent_loop.error: {e}");
+    ChannelResponse {
+        error: Some(e.user_message()),
+        ..Default::default()
+    }
+}
+
+/// Run one message through `agent` with the Telegram session settings.
+async fn run_turn(
+    agent: &mut AgentLoop,
+    text: &str,
+    inbound: Inbound,
+    config: &AppConfig,
+) -> ChannelResponse {
+    if config.telegram_persona.is_some() {
+        agent.set_default_persona(config.telegram_persona.clone());
+    }
+    // Isolated users must stay inside their own workspace.
+    agent.set_workspace_switching(config.telegram_user_workspace_root.is_none());
+    agent.set_actor(inbound.user);
+    agent.set_reply_to(inbound.reply_to);
+    if let Some(original) = inbound.supersedes {
+        agent.supersede_last_turn(&original, "edited");
+    }
 
     let result = agent.handle_input(text).await;
 
     ChannelResponse {
         immediate_output: result.immediate_output,
-        assistant_output: result.assistant_output,
+        assistant_output: result
+            .assistant_output
+            .map(|output| postprocess::apply(&config.telegram_output_filters, &output)),
         error: result.error,
+        attachments: result.attachments,
+    }
+}
+
+/// Send the files and images a turn produced (`file.send`).
+async fn send_attachments(bot: &Bot, chat_id: ChatId, attachments: &[ToolOutput]) {
+    for attachment in attachments {
+        let sent = match attachment {
+            ToolOutput::Image { path, caption } => {
+                let request = bot.send_photo(chat_id, InputFile::file(path));
+                match caption {
+                    Some(caption) => request.caption(caption).await,
+                    None => request.await,
+                }
+            }
+            ToolOutput::File { path, caption } => {
+                let request = bot.send_document(chat_id, InputFile::file(path));
+                match caption {
+                    Some(caption) => request.caption(caption).await,
+                    None => request.await,
+                }
+            }
+            _ => continue,
+        };
+        if let Err(e) = sent {
+            warn!("telegram.send.attachment_error: {e}");
+        }
     }
 }
 
 /// Split a long message into chunks that fit Telegram's per-message limit.
-fn split_message(text: &str, max_len: usize) -> Vec<String> {
+pub(crate) fn split_message(text: &str, max_len: usize) -> Vec<String> {
     if max_len == 0 {
         return Vec::new();
     }
@@ -563,6 +1044,60 @@ pub fn markdown_to_telegram_html(text: &str) -> String {
 mod tests {
     use super::*;
 
+    fn test_config(root: Option<&str>) -> AppConfig {
+        crate::core::config::resolve_config(
+            None,
+            &crate::core::config::CliConfigOverrides::default(),
+            &std::collections::HashMap::from([
+                ("API_KEY".to_string(), "key".to_string()),
+                (
+                    "TELEGRAM_USER_WORKSPACE_ROOT".to_string(),
+                    root.unwrap_or_default().to_string(),
+                ),
+            ]),
+            &std::collections::HashMap::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn slash_commands_map_to_internal_commands() {
+        assert_eq!(slash_alias("/model", ',').as_deref(), Some(",model"));
+        assert_eq!(
+            slash_alias("/model@crab_bot", '/').as_deref(),
+            Some("/model")
+        );
+        assert_eq!(
+            slash_alias("/model@crab_bot anthropic:claude-opus-4-5", ',').as_deref(),
+            Some(",model anthropic:claude-opus-4-5")
+        );
+        assert_eq!(slash_alias("/start", ','), None);
+        assert_eq!(slash_alias("model", ','), None);
+    }
+
+    #[test]
+    fn session_scope_is_per_chat_by_default() {
+        let ws = std::path::Path::new("/srv/bot");
+        let (workspace, session) = session_scope(&test_config(None), ws, -100, Some(7));
+        assert_eq!(workspace, ws);
+        assert_eq!(session, "telegram:-100");
+    }
+
+    #[test]
+    fn session_scope_isolates_users_when_root_set() {
+        let ws = std::path::Path::new("/srv/bot");
+        let config = test_config(Some("users"));
+        let (alice_ws, alice) = session_scope(&config, ws, -100, Some(7));
+        let (bob_ws, bob) = session_scope(&config, ws, -100, Some(8));
+        assert_eq!(alice_ws, ws.join("users/7"));
+        assert_eq!(alice, "telegram:-100:7");
+        assert_ne!(alice_ws, bob_ws);
+        assert_ne!(alice, bob);
+
+        let (absolute_ws, _) = session_scope(&test_config(Some("/data/u")), ws, 1, Some(7));
+        assert_eq!(absolute_ws, std::path::Path::new("/data/u/7"));
+    }
+
     #[test]
     fn split_short_message() {
         let chunks = split_message("hello", 4096);
@@ -741,4 +1276,21 @@ mod tests {
             "a &lt; b &amp;&amp; c &gt; d"
         );
     }
+
+    #[test]
+    fn idle_notice_counts_days_or_hours() {
+        let archived = |idle, pins, settings| IdleArchive {
+            idle,
+            pins,
+            settings,
+        };
+        let notice = idle_notice(&archived(chrono::TimeDelta::hours(30), 0, false));
+        assert!(notice.starts_with("No messages for 30 hours"));
+        assert!(notice.ends_with("starts fresh."));
+        let notice = idle_notice(&archived(chrono::TimeDelta::days(45), 2, true));
+        assert!(notice.starts_with("No messages for 45 days"));
+        assert!(notice.ends_with(
+            "2 pinned note(s) and your persona, model and workspace choices carry over."
+        ));
+    }
 }
@@ -0,0 +1,125 @@
+//! Telegram inline queries (`@bot <question>` typed in any chat).
+//!
+//! Inline queries get a short, toolless answer from a single model call:
+//! there is no tape, no agent loop and no command routing, so nothing typed
+//! inline can touch the workspace. The answer is offered as one article
+//! result the user can tap to post into the chat.
+
+use std::time::Duration;
+
+use teloxide::prelude::*;
+use teloxide::types::{
+    InlineQuery, InlineQueryResult, InlineQueryResultArticle, InputMessageContent,
+    InputMessageContentText, ParseMode,
+};
+use tracing::{info, warn};
+
+use crate::channels::rate_limit::RateLimiter;
+use crate::channels::telegram::{acl_allows, markdown_to_telegram_html};
+use crate::core::config::AppConfig;
+use crate::core::error::{CrabClawError, Result};
+use crate::core::utils::safe_truncate;
+use crate::llm::api_types::{ChatRequest, Message};
+
+const QUICK_ANSWER_PROMPT: &str = "You answer quick questions typed inline in a Telegram chat. \
+Reply in a few sentences at most. You have no tools and no conversation history; if the \
+question needs either, say so briefly.";
+
+/// Output cap for quick answers.
+const QUICK_ANSWER_MAX_TOKENS: u32 = 512;
+
+/// Telegram drops answers to inline queries after about 10 seconds.
+const QUICK_ANSWER_TIMEOUT: Duration = Duration::from_secs(9);
+
+/// Queries shorter than this (in characters) are still being typed.
+const MIN_QUERY_CHARS: usize = 3;
+
+/// Seconds Telegram may cache an answer for the same user and query.
+const CACHE_SECS: u32 = 30;
+
+/// Answer `query` with one toolless model call.
+pub async fn quick_answer(query: &str, config: &AppConfig) -> Result<String> {
+    let request = ChatRequest {
+        model: config.model.clone(),
+        messages: vec![
+            Message::system(QUICK_ANSWER_PROMPT),
+            Message::user(query.trim()),
+        ],
+        max_tokens: Some(QUICK_ANSWER_MAX_TOKENS),
+        temperature: None,
+        tools: None,
+        tool_choice: None,
+    };
+    let response = crate::llm::client::send_chat_request(config, &request).await?;
+    response
+        .assistant_content()
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+        .map(ToString::to_string)
+        .ok_or_else(|| CrabClawError::Api("empty answer".to_string()))
+}
+
+/// Handle one inline query: check the ACL and rate limit, then answer with
+/// a single article (or nothing, which Telegram shows as no results).
+pub(crate) async fn handle_inline_query(
+    bot: Bot,
+    query: InlineQuery,
+    config: &AppConfig,
+    limiter: &RateLimiter,
+) {
+    let text = query.query.trim();
+    if text.chars().count() < MIN_QUERY_CHARS {
+        return;
+    }
+    let user_id = query.from.id.0.to_string();
+    // Inline queries carry no chat, so a chat allow-list cannot be satisfied.
+    if !acl_allows(
+        &config.telegram_allow_from,
+        &config.telegram_allow_chats,
+        &user_id,
+        query.from.username.as_deref(),
+        "",
+    ) {
+        warn!(user_id = %user_id, "telegram.inline.acl.deny");
+        return;
+    }
+    let _turn = match limiter.try_acquire(&[format!("user:{user_id}")]) {
+        Ok(guard) => guard,
+        Err(throttle) => {
+            warn!(user_id = %user_id, ?throttle, "telegram.inline.rate_limited");
+            return;
+        }
+    };
+
+    info!(user_id = %user_id, query = %text, "telegram.inline");
+    let answer = match tokio::time::timeout(QUICK_ANSWER_TIMEOUT, quick_answer(text, config)).await
+    {
+        Ok(Ok(answer)) => answer,
+        Ok(Err(e)) => {
+            warn!("telegram.inline.error: {e}");
+            return;
+        }
+        Err(_) => {
+            warn!("telegram.inline.timeout");
+            return;
+        }
+    };
+
+    let message = markdown_to_telegram_html(safe_truncate(&answer, 4000));
+    let article = InlineQueryResultArticle::new(
+        "answer",
+        safe_truncate(text, 64),
+        InputMessageContent::Text(
+            InputMessageContentText::new(message).parse_mode(ParseMode::Html),
+        ),
+    )
+    .description(safe_truncate(&answer, 200));
+    if let Err(e) = bot
+        .answer_inline_query(&query.id, [InlineQueryResult::Article(article)])
+        .cache_time(CACHE_SECS)
+        .is_personal(true)
+        .await
+    {
+        warn!("telegram.inline.answer_error: {e}");
+    }
+}
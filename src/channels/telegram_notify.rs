@@ -4,6 +4,9 @@ use std::sync::OnceLock;
 use tokio::sync::{Mutex, mpsc};
 use tracing::warn;
 
+use super::telegram::{markdown_to_telegram_html, split_message};
+use crate::tools::schedule::Notifier;
+
 type NotifyKey = (String, i64);
 type TelegramNotifySender = mpsc::UnboundedSender<String>;
 
@@ -16,7 +19,8 @@ fn notifier_senders() -> &'static Mutex<HashMap<NotifyKey, TelegramNotifySender>
 
 /// Get or create a per-(token, chat_id) notifier sender backed by an async worker.
 ///
-/// The worker serializes message delivery per key and reuses a single HTTP client.
+/// The worker serializes message delivery per key and reuses a single HTTP
+/// client. Messages are rendered as HTML and split to Telegram's length limit.
 pub async fn get_or_create_notifier_sender(token: &str, chat_id: i64) -> TelegramNotifySender {
     let key = (token.to_string(), chat_id);
 
@@ -41,31 +45,8 @@ pub async fn get_or_create_notifier_sender(token: &str, chat_id: i64) -> Telegra
         let url = format!("https://api.telegram.org/bot{token_owned}/sendMessage");
         let client = reqwest::Client::new();
         while let Some(msg_text) = rx.recv().await {
-            match client
-                .post(&url)
-                .json(&serde_json::json!({
-                    "chat_id": chat_id,
-                    "text": msg_text,
-                }))
-                .send()
-                .await
-            {
-                Ok(resp) => {
-                    if !resp.status().is_success() {
-                        warn!(
-                            chat_id = chat_id,
-                            status = %resp.status(),
-                            "telegram.notifier.send_message_failed"
-                        );
-                    }
-                }
-                Err(e) => {
-                    warn!(
-                        chat_id = chat_id,
-                        error = %e,
-                        "telegram.notifier.send_message_error"
-                    );
-                }
+            for chunk in split_message(&msg_text, 4096) {
+                send_chunk(&client, &url, chat_id, &chunk).await;
             }
         }
 
@@ -75,3 +56,95 @@ pub async fn get_or_create_notifier_sender(token: &str, chat_id: i64) -> Telegra
 
     tx
 }
+
+static CHAT_NOTIFIERS: OnceLock<std::sync::Mutex<HashMap<NotifyKey, Notifier>>> = OnceLock::new();
+
+/// Shared schedule notifier for a chat.
+///
+/// Every session in the chat gets the same callback, so notifications held
+/// during quiet hours are merged into a single digest per chat.
+pub async fn chat_notifier(token: &str, chat_id: i64) -> Notifier {
+    let key = (token.to_string(), chat_id);
+    let notifiers = CHAT_NOTIFIERS.get_or_init(Default::default);
+    if let Some(existing) = notifiers.lock().unwrap().get(&key) {
+        return existing.clone();
+    }
+    let sender = get_or_create_notifier_sender(token, chat_id).await;
+    let notifier: Notifier = std::sync::Arc::new(move |text: String| {
+        if sender.send(text).is_err() {
+            warn!(chat_id = chat_id, "telegram.notifier.sender_closed");
+        }
+    });
+    notifiers
+        .lock()
+        .unwrap()
+        .entry(key)
+        .or_insert(notifier)
+        .clone()
+}
+
+/// Send one message chunk as HTML, falling back to plain text when Telegram
+/// rejects the converted markup.
+async fn send_chunk(client: &reqwest::Client, url: &str, chat_id: i64, chunk: &str) {
+    let html = markdown_to_telegram_html(chunk);
+    let attempts = [
+        serde_json::json!({ "chat_id": chat_id, "text": html, "parse_mode": "HTML" }),
+        serde_json::json!({ "chat_id": chat_id, "text": chunk }),
+    ];
+    for body in attempts {
+        match client.post(url).json(&body).send().await {
+            Ok(resp) if resp.status().is_success() => return,
+            Ok(resp) => {
+                warn!(
+                    chat_id = chat_id,
+                    status = %resp.status(),
+                    "telegram.notifier.send_message_failed"
+                );
+                if resp.status() != reqwest::StatusCode::BAD_REQUEST {
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!(
+                    chat_id = chat_id,
+                    error = %e,
+                    "telegram.notifier.send_message_error"
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Alert callback that messages every numeric Telegram admin ID.
+///
+/// Used by `crabclaw serve` to surface background problems (e.g. a failing
+/// OAuth refresh) to the operator. Usernames in `TELEGRAM_ADMINS` cannot be
+/// messaged directly and are skipped. Returns `None` when Telegram is not
+/// configured or there is no one to notify.
+pub fn admin_alert(
+    config: &crate::core::config::AppConfig,
+    handle: tokio::runtime::Handle,
+) -> Option<crate::core::auth::AuthAlert> {
+    let token = config.telegram_token.clone()?;
+    let chat_ids: Vec<i64> = config
+        .telegram_admins
+        .iter()
+        .filter_map(|admin| admin.trim().parse().ok())
+        .collect();
+    if chat_ids.is_empty() {
+        return None;
+    }
+    Some(std::sync::Arc::new(move |text: String| {
+        let token = token.clone();
+        let chat_ids = chat_ids.clone();
+        handle.spawn(async move {
+            for chat_id in chat_ids {
+                let sender = get_or_create_notifier_sender(&token, chat_id).await;
+                if sender.send(text.clone()).is_err() {
+                    warn!(chat_id = chat_id, "telegram.admin_alert.closed");
+                }
+            }
+        });
+    }))
+}
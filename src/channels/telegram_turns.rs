@@ -0,0 +1,215 @@
+//! In-flight Telegram turns, so an edited prompt can supersede its turn.
+//!
+//! Each incoming message runs as its own task, serialized per session by a
+//! session lock, so one chat's long turn never holds up another chat. The
+//! tracker remembers the latest turn of every chat; when the
+//! user edits that message while the turn is running, or shortly after it
+//! finished, [`TurnTracker::supersede`] aborts the task and hands back the
+//! original text so the edited prompt can replace it.
+//!
+//! A session holds at most [`MAX_QUEUED_TURNS`] turns waiting behind the
+//! running one; its lock is dropped once no turn needs it, and finished
+//! turns are forgotten once their edit window has passed.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::task::AbortHandle;
+
+/// How long after a turn finished an edit still re-runs it.
+pub(crate) const EDIT_WINDOW: Duration = Duration::from_secs(120);
+/// Turns of one session that may wait behind the running one.
+pub(crate) const MAX_QUEUED_TURNS: usize = 3;
+
+#[derive(Debug)]
+struct Turn {
+    message_id: i32,
+    text: String,
+    abort: AbortHandle,
+    finished: Option<Instant>,
+}
+
+/// The turns of one session that are queued or running.
+#[derive(Debug, Default)]
+struct SessionTurns {
+    lock: Arc<tokio::sync::Mutex<()>>,
+    pending: usize,
+}
+
+/// Latest turn per chat, plus the locks that keep a session's turns in order.
+#[derive(Debug, Default)]
+pub(crate) struct TurnTracker {
+    chats: Mutex<HashMap<i64, Turn>>,
+    sessions: Mutex<HashMap<String, SessionTurns>>,
+}
+
+impl TurnTracker {
+    /// Queue a turn of `session`, or `None` when [`MAX_QUEUED_TURNS`] turns
+    /// already wait behind the running one.
+    pub(crate) fn enqueue(self: &Arc<Self>, session: &str) -> Option<QueuedTurn> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let turns = sessions.entry(session.to_string()).or_default();
+        if turns.pending > MAX_QUEUED_TURNS {
+            return None;
+        }
+        turns.pending += 1;
+        Some(QueuedTurn {
+            tracker: Arc::clone(self),
+            session: session.to_string(),
+            lock: Arc::clone(&turns.lock),
+        })
+    }
+
+    /// Record the task running the turn for `message_id`.
+    pub(crate) fn started(&self, chat: i64, message_id: i32, text: &str, abort: AbortHandle) {
+        let now = Instant::now();
+        let mut chats = self.chats.lock().unwrap();
+        chats.retain(|_, turn| {
+            turn.finished
+                .is_none_or(|at| now.duration_since(at) <= EDIT_WINDOW)
+        });
+        chats.insert(
+            chat,
+            Turn {
+                message_id,
+                text: text.to_string(),
+                abort,
+                finished: None,
+            },
+        );
+    }
+
+    /// Note that the turn for `message_id` completed.
+    pub(crate) fn finished(&self, chat: i64, message_id: i32) {
+        let mut chats = self.chats.lock().unwrap();
+        if let Some(turn) = chats.get_mut(&chat)
+            && turn.message_id == message_id
+        {
+            turn.finished = Some(Instant::now());
+        }
+    }
+
+    /// Abort the turn of an edited message and return its original text.
+    ///
+    /// `None` when `message_id` is not the chat's latest turn or the turn
+    /// finished more than [`EDIT_WINDOW`] ago; the edit is then ignored.
+    pub(crate) fn supersede(&self, chat: i64, message_id: i32) -> Option<String> {
+        self.supersede_at(chat, message_id, Instant::now())
+    }
+
+    fn supersede_at(&self, chat: i64, message_id: i32, now: Instant) -> Option<String> {
+        let mut chats = self.chats.lock().unwrap();
+        let turn = chats.get(&chat)?;
+        if turn.message_id != message_id
+            || turn
+                .finished
+                .is_some_and(|at| now.duration_since(at) > EDIT_WINDOW)
+        {
+            return None;
+        }
+        let turn = chats.remove(&chat)?;
+        turn.abort.abort();
+        Some(turn.text)
+    }
+}
+
+/// A session's place in line; the session forgets its lock once the last
+/// queued turn is dropped.
+#[derive(Debug)]
+pub(crate) struct QueuedTurn {
+    tracker: Arc<TurnTracker>,
+    session: String,
+    lock: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl QueuedTurn {
+    /// Wait until the session's earlier turns are done.
+    pub(crate) async fn wait(&self) -> tokio::sync::OwnedMutexGuard<()> {
+        Arc::clone(&self.lock).lock_owned().await
+    }
+}
+
+impl Drop for QueuedTurn {
+    fn drop(&mut self) {
+        let mut sessions = self.tracker.sessions.lock().unwrap();
+        if let Some(turns) = sessions.get_mut(&self.session) {
+            turns.pending = turns.pending.saturating_sub(1);
+            if turns.pending == 0 {
+                sessions.remove(&self.session);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_task() -> tokio::task::JoinHandle<()> {
+        tokio::spawn(std::future::pending::<()>())
+    }
+
+    #[tokio::test]
+    async fn running_turns_are_aborted_on_edit() {
+        let tracker = TurnTracker::default();
+        let task = pending_task();
+        tracker.started(1, 10, "original", task.abort_handle());
+
+        assert_eq!(tracker.supersede(1, 11), None);
+        assert_eq!(tracker.supersede(2, 10), None);
+        assert_eq!(tracker.supersede(1, 10).as_deref(), Some("original"));
+        assert!(task.await.unwrap_err().is_cancelled());
+        // A second edit has nothing left to supersede.
+        assert_eq!(tracker.supersede(1, 10), None);
+    }
+
+    #[tokio::test]
+    async fn finished_turns_can_be_edited_within_the_window() {
+        let tracker = TurnTracker::default();
+        tracker.started(1, 10, "original", pending_task().abort_handle());
+        tracker.finished(1, 10);
+
+        let late = Instant::now() + EDIT_WINDOW + Duration::from_secs(1);
+        assert_eq!(tracker.supersede_at(1, 10, late), None);
+        assert_eq!(tracker.supersede(1, 10).as_deref(), Some("original"));
+    }
+
+    #[tokio::test]
+    async fn sessions_are_serialized_independently() {
+        let tracker = Arc::new(TurnTracker::default());
+        let alice = tracker.enqueue("telegram:-100:7").unwrap();
+        let _running = alice.wait().await;
+
+        let queued = tracker.enqueue("telegram:-100:7").unwrap();
+        assert!(queued.lock.try_lock().is_err());
+        let bob = tracker.enqueue("telegram:-100:8").unwrap();
+        assert!(bob.lock.try_lock().is_ok());
+    }
+
+    #[tokio::test]
+    async fn backlogs_are_bounded_and_idle_sessions_forgotten() {
+        let tracker = Arc::new(TurnTracker::default());
+        let turns: Vec<_> = (0..=MAX_QUEUED_TURNS)
+            .map(|_| tracker.enqueue("telegram:1").unwrap())
+            .collect();
+        assert!(tracker.enqueue("telegram:1").is_none());
+        assert!(tracker.enqueue("telegram:2").is_some());
+
+        drop(turns);
+        assert!(tracker.sessions.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn finished_turns_are_forgotten_after_the_window() {
+        let tracker = TurnTracker::default();
+        tracker.started(1, 10, "old", pending_task().abort_handle());
+        tracker.chats.lock().unwrap().get_mut(&1).unwrap().finished =
+            Some(Instant::now() - EDIT_WINDOW - Duration::from_secs(1));
+        tracker.started(2, 20, "new", pending_task().abort_handle());
+
+        let chats = tracker.chats.lock().unwrap();
+        assert!(!chats.contains_key(&1));
+        assert!(chats.contains_key(&2));
+    }
+}
@@ -4,22 +4,136 @@
 //! for each user message. The loop owns tape, tool_view, and model_runner,
 //! eliminating the duplicated logic across telegram, cli, and repl.
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use tracing::{debug, instrument, warn};
+use tracing::{Span, debug, field, instrument, warn};
 
+use crate::core::command::{CommandKind, detect_command_with_prefix, expand_alias};
 use crate::core::config::AppConfig;
-use crate::core::context::{build_messages, build_system_prompt_with_tools};
+use crate::core::consensus::{self, AskAll, CONSENSUS_EVENT, Panel};
+use crate::core::context::{
+    TurnContext, build_messages, build_system_prompt_with_tools, fit_to_token_budget,
+    format_context_report, format_prompt_preview, format_prompt_tokens, previous_user_message_at,
+    workspace_context_fingerprint,
+};
+use crate::core::dialog::{self, Dialog, Form};
 use crate::core::error::{CrabClawError, Result};
-use crate::core::model_runner::{ModelRunner, ModelTurnResult};
-use crate::core::router::route_user;
+use crate::core::model_runner::{
+    ModelRunner, ModelTurnResult, RESTART_NOTICE, StreamEvent, TOOL_CALL_EVENT, TURN_PARTIAL_EVENT,
+    TURN_TIMING_EVENT, TurnTiming,
+};
+use crate::core::persona::{Persona, find_persona, tape_persona};
+use crate::core::reply_context::{ReplyTarget, reply_context};
+use crate::core::router::{CommandApprover, route_user_with_limits};
+use crate::core::structured::{self, MAX_SCHEMA_RETRIES};
+use crate::core::title::{TITLE_EVENT, TitleMode, fallback_title, generate_title, tape_title};
+use crate::core::trace;
+use crate::llm::api_types::{Message, ToolChoice, ToolDefinition, Usage};
+use crate::llm::json_repair::ARGS_REPAIRED_EVENT;
+use crate::llm::models::capabilities;
 use crate::tape::store::TapeStore;
+use crate::tools::citations::{CitationLog, SOURCES_EVENT, format_sources};
+use crate::tools::external::ExternalTool;
+use crate::tools::output::ToolOutput;
+use crate::tools::output_budget::OutputBudget;
+use crate::tools::pins::{self, PinLog};
 use crate::tools::progressive::ProgressiveToolView;
-use crate::tools::registry::ToolContext;
-use crate::tools::schedule::Notifier;
+use crate::tools::registry::{ToolApprover, ToolContext, ToolRegistry};
+use crate::tools::schedule::{AgentRunReport, Notifier};
+use crate::tools::skills::{self, SkillMetadata};
 
 const ASSISTANT_COMMANDS_ENV_KEY: &str = "CRABCLAW_ENABLE_ASSISTANT_COMMANDS";
 
+/// Tape event recording the token usage of one model turn.
+pub const TURN_USAGE_EVENT: &str = "turn.usage";
+
+/// Tape event recording a model turn that failed (`{"error": "..."}`).
+pub const TURN_ERROR_EVENT: &str = "turn.error";
+
+/// Tape event recording a `,model` switch (`{"model": "provider:name" | null}`).
+pub const MODEL_EVENT: &str = "model.switch";
+
+/// Model selected with `,model` in this session.
+///
+/// `None` when the session never switched; `Some(None)` after a reset.
+pub fn tape_model(tape: &TapeStore) -> Option<Option<String>> {
+    tape.entries()
+        .iter()
+        .rev()
+        .find(|e| e.kind == MODEL_EVENT)
+        .map(|e| {
+            e.payload
+                .get("model")
+                .and_then(|v| v.as_str())
+                .map(ToString::to_string)
+        })
+}
+
+/// Tape event recording a `,debug dump on|off` (`{"dir": "/path" | null}`).
+pub const DUMP_EVENT: &str = "debug.dump";
+
+/// Dump directory chosen with `,debug dump` in this session.
+///
+/// `None` when the session never toggled dumps; `Some(None)` after `off`.
+fn tape_dump_dir(tape: &TapeStore) -> Option<Option<PathBuf>> {
+    tape.entries()
+        .iter()
+        .rev()
+        .find(|e| e.kind == DUMP_EVENT)
+        .map(|e| {
+            e.payload
+                .get("dir")
+                .and_then(|v| v.as_str())
+                .map(PathBuf::from)
+        })
+}
+
+/// Tape event marking a turn replaced by a later one, e.g. after the user
+/// edited the prompt (`{"from": 12, "to": 15, "reason": "edited"}`, entry
+/// IDs inclusive). Superseded messages are left out of the context.
+pub const TURN_SUPERSEDED_EVENT: &str = "turn.superseded";
+
+/// Tape event recording a `,workspace switch` (`{"name": "api" | null}`).
+pub const WORKSPACE_EVENT: &str = "workspace.switch";
+
+/// Workspace selected with `,workspace switch` in this session.
+///
+/// `None` when the session never switched; `Some(None)` after switching
+/// back to the default workspace.
+pub fn tape_workspace(tape: &TapeStore) -> Option<Option<String>> {
+    tape.entries()
+        .iter()
+        .rev()
+        .find(|e| e.kind == WORKSPACE_EVENT)
+        .map(|e| {
+            e.payload
+                .get("name")
+                .and_then(|v| v.as_str())
+                .map(ToString::to_string)
+        })
+}
+
+/// Tape events holding session settings, carried into the fresh tape when
+/// an idle session is archived ([`AgentLoop::archive_if_idle`]).
+const SESSION_SETTING_EVENTS: &[&str] = &[
+    crate::core::persona::PERSONA_EVENT,
+    MODEL_EVENT,
+    WORKSPACE_EVENT,
+    DUMP_EVENT,
+];
+
+/// An idle session archived by [`AgentLoop::archive_if_idle`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleArchive {
+    /// How long the session had been idle.
+    pub idle: chrono::TimeDelta,
+    /// Active pins carried into the fresh tape.
+    pub pins: usize,
+    /// Whether a persona, model, workspace or dump setting was carried.
+    pub settings: bool,
+}
+
 /// Output from one agent loop turn.
 #[derive(Debug, Default)]
 pub struct LoopResult {
@@ -31,8 +145,21 @@ pub struct LoopResult {
     pub exit_requested: bool,
     /// Number of tool-calling rounds executed.
     pub tool_rounds: usize,
+    /// Tool names invoked during the model turn.
+    pub invoked_tools: Vec<String>,
+    /// Token usage reported by the provider (non-streaming turns only).
+    pub usage: Option<Usage>,
     /// Error message if any.
     pub error: Option<String>,
+    /// Parsed answer when the turn ran with an output schema (see
+    /// `core::structured`).
+    pub structured_output: Option<serde_json::Value>,
+    /// How long the model turn took; `None` when the model was not called.
+    pub timing: Option<TurnTiming>,
+    /// Files and images produced by tools (`file.send`) for the user.
+    pub attachments: Vec<ToolOutput>,
+    /// Trace ID of the turn (see `core::trace`).
+    pub turn_id: Option<String>,
 }
 
 impl LoopResult {
@@ -67,15 +194,32 @@ impl LoopResult {
 /// - `telegram::process_message`
 /// - `repl::run_interactive`
 /// - `cli::run_command`
-pub struct AgentLoop<'a> {
-    config: &'a AppConfig,
-    workspace: &'a Path,
+pub struct AgentLoop {
+    config: Arc<AppConfig>,
+    /// Workspace the session was opened in; holds the tape.
+    home: PathBuf,
+    /// Active workspace for tools and prompt context (see `,workspace`).
+    workspace: PathBuf,
+    workspace_switching: bool,
+    /// Prefix marking commands in this session's channel.
+    command_prefix: char,
     tape: TapeStore,
     tool_view: ProgressiveToolView,
     tool_ctx: ToolContext,
+    skills: Vec<SkillMetadata>,
+    default_persona: Option<String>,
+    persona: Option<Persona>,
+    /// One-shot `tool_choice` for the next model turn.
+    next_tool_choice: Option<ToolChoice>,
+    next_output_schema: Option<serde_json::Value>,
+    /// One-shot: the earlier message the next input replies to.
+    reply_to: Option<ReplyTarget>,
+    /// Asks the user before assistant shell commands run, in channels that
+    /// can (`ASSISTANT_COMMAND_CONFIRM`).
+    command_approver: Option<CommandApprover>,
 }
 
-impl<'a> AgentLoop<'a> {
+impl AgentLoop {
     /// Create a new agent loop for a session.
     ///
     /// Opens or creates the tape file for `session_id`.
@@ -84,33 +228,79 @@ impl<'a> AgentLoop<'a> {
     /// `agent_runner` is an optional async callback for running the
     /// full agent pipeline on schedule fire (agent-mode jobs).
     pub fn open(
-        config: &'a AppConfig,
-        workspace: &'a Path,
+        config: &AppConfig,
+        workspace: &Path,
+        session_id: &str,
+        notifier: Option<Notifier>,
+        agent_runner: Option<crate::tools::schedule::AgentRunner>,
+    ) -> Result<Self> {
+        Self::open_shared(
+            Arc::new(config.clone()),
+            workspace,
+            session_id,
+            notifier,
+            agent_runner,
+        )
+    }
+
+    /// [`AgentLoop::open`] with a config shared with the caller, for loops
+    /// kept alive between messages (see `channels::agent_cache`).
+    pub fn open_shared(
+        config: Arc<AppConfig>,
+        workspace: &Path,
         session_id: &str,
         notifier: Option<Notifier>,
         agent_runner: Option<crate::tools::schedule::AgentRunner>,
     ) -> Result<Self> {
         let tape_dir = workspace.join(".crabclaw");
         let tape_name = session_id.replace(':', "_");
-        let tape = TapeStore::open(&tape_dir, &tape_name).map_err(CrabClawError::Io)?;
-
-        // Build tool registry with builtins + workspace skills
-        let mut registry = crate::tools::registry::builtin_registry();
-        crate::tools::registry::register_skills(&mut registry, workspace);
+        let mut tape = TapeStore::open(&tape_dir, &tape_name).map_err(CrabClawError::Io)?;
+        tape.set_sync(config.tape_sync);
+
+        let active = match tape_workspace(&tape).flatten() {
+            Some(name) => registered_workspace(&config, workspace, &name).unwrap_or_else(|| {
+                warn!(%name, "agent_loop.workspace.unregistered");
+                workspace.to_path_buf()
+            }),
+            None => workspace.to_path_buf(),
+        };
+        let WorkspaceTools {
+            registry,
+            skills,
+            external,
+        } = build_registry(&config, &active);
 
         let tool_view = ProgressiveToolView::new(registry);
 
         let tool_ctx = ToolContext {
             notifier,
             agent_runner,
+            citations: CitationLog::default(),
+            pins: PinLog::default(),
+            calendars: config.calendar_urls.clone(),
+            notify: config.notify.clone(),
+            quiet_hours: config.quiet_hours,
+            outputs: OutputBudget::new(config.tool_output_limits.clone()),
+            approver: None,
+            external_tools: external,
         };
 
         let mut loop_instance = Self {
-            config,
-            workspace,
+            home: workspace.to_path_buf(),
+            workspace: active,
+            workspace_switching: true,
+            command_prefix: config.command_prefix,
             tape,
             tool_view,
             tool_ctx,
+            skills,
+            default_persona: config.persona.clone(),
+            config,
+            persona: None,
+            next_tool_choice: None,
+            next_output_schema: None,
+            reply_to: None,
+            command_approver: None,
         };
 
         loop_instance
@@ -125,12 +315,39 @@ impl<'a> AgentLoop<'a> {
     ///
     /// Routes input through the command router, and if the model is needed,
     /// calls the model with the tool-calling loop.
-    #[instrument(skip_all, fields(input_len = text.len()))]
+    #[instrument(skip_all, fields(input_len = text.len(), turn_id = field::Empty))]
     pub async fn handle_input(&mut self, text: &str) -> LoopResult {
+        let turn_id = self.begin_turn();
+        let result = trace::in_turn(turn_id.clone(), self.run_input(text)).await;
+        self.end_turn(&turn_id, result)
+    }
+
+    async fn run_input(&mut self, text: &str) -> LoopResult {
         let mut result = LoopResult::default();
+        let expanded = expand_alias(text, &self.config.command_aliases, self.command_prefix);
+        let text = expanded.as_deref().unwrap_or(text);
+
+        if let Some(output) = self.dialog_command(text) {
+            result.immediate_output = Some(output);
+            return result;
+        }
+        if let Some(output) = self.session_command(text) {
+            result.immediate_output = Some(output);
+            return result;
+        }
+        if let Some(output) = self.ask_all_command(text).await {
+            result.immediate_output = Some(output);
+            return result;
+        }
 
         // 1. Route user input
-        let route = route_user(text, &mut self.tape, self.workspace);
+        let route = route_user_with_limits(
+            text,
+            &mut self.tape,
+            &self.workspace,
+            &self.config.tool_output_limits,
+            self.command_prefix,
+        );
 
         if route.exit_requested {
             result.exit_requested = true;
@@ -150,53 +367,135 @@ impl<'a> AgentLoop<'a> {
             warn!("agent_loop.tape.write.error: {e}");
         }
 
-        // 3. Build tool definitions from progressive view
-        let tool_defs = self.tool_view.tool_definitions();
-        let tools = if tool_defs.is_empty() {
-            None
-        } else {
-            Some(tool_defs)
-        };
-
-        // 4. Build system prompt and messages from tape context
-        let tools_prompt = self.tools_prompt_block();
-        let system_prompt = build_system_prompt_with_tools(
-            self.config.system_prompt.as_deref(),
-            self.workspace,
-            Some(&tools_prompt),
-        );
-        let mut messages = build_messages(
-            &self.tape,
-            Some(&system_prompt),
-            self.config.max_context_messages,
-        );
+        // 3-4. Build tool definitions, system prompt and messages
+        let (tools, mut messages) = self.prepare_request(&route.model_prompt);
+        self.insert_reply_context(&mut messages);
+        let output_schema = self.next_output_schema.take();
+        if let Some(schema) = &output_schema {
+            messages.push(Message::system(structured::schema_instruction(schema)));
+        }
 
         debug!(message_count = messages.len(), "agent_loop.model_request");
+        self.tool_ctx.citations.take();
+        self.tool_ctx.outputs.reset();
 
         // 5. Run model turn with tool calling loop
-        let runner = ModelRunner::new(self.config, self.workspace);
-        let turn_result = runner
+        let mut config = self.persona_config();
+        if let Some(model) = &route.model_override {
+            config.model = model.clone();
+        }
+        let temperature = self.persona.as_ref().and_then(|p| p.temperature);
+        let runner = ModelRunner::new(&config, &self.workspace)
+            .with_temperature(temperature)
+            .with_tool_choice(self.next_tool_choice.take());
+        let mut turn_result = runner
             .run_turn(&mut messages, tools.as_deref(), &self.tape, &self.tool_ctx)
             .await;
 
+        // 5b. Validate a structured answer, sending it back with the errors
+        // until it conforms or the retries run out.
+        let mut structured_output = None;
+        let mut schema_error = None;
+        if let Some(schema) = &output_schema {
+            let retry_runner =
+                ModelRunner::new(&config, &self.workspace).with_temperature(temperature);
+            let mut attempt = 0;
+            while turn_result.error.is_none() {
+                match structured::parse_output(&turn_result.assistant_text, schema) {
+                    Ok(value) => {
+                        structured_output = Some(value);
+                        break;
+                    }
+                    Err(e) if attempt < MAX_SCHEMA_RETRIES => {
+                        attempt += 1;
+                        warn!(attempt, error = %e, "agent_loop.structured_output.retry");
+                        messages.push(Message::assistant(turn_result.assistant_text.clone()));
+                        messages.push(Message::user(structured::retry_prompt(&e)));
+                        let retry = retry_runner
+                            .run_turn(&mut messages, tools.as_deref(), &self.tape, &self.tool_ctx)
+                            .await;
+                        turn_result.absorb(retry);
+                    }
+                    Err(e) => {
+                        schema_error = Some(format!(
+                            "structured output rejected after {} attempt(s): {e}",
+                            attempt + 1
+                        ));
+                        break;
+                    }
+                }
+            }
+        }
+
         // 6. Process result
         self.process_turn_result(&turn_result, &mut result);
+        result.structured_output = structured_output;
+        if let Some(e) = schema_error {
+            result.error.get_or_insert(e);
+        }
+        self.ensure_title(&route.model_prompt, &result).await;
+        self.attach_sources(&mut result);
 
         result
     }
 
     /// Handle one user input message (**streaming**, for CLI / REPL).
     ///
-    /// `on_token` is called for each streamed text chunk from the model.
-    #[instrument(skip_all, fields(input_len = text.len()))]
-    pub async fn handle_input_stream<F>(&mut self, text: &str, on_token: F) -> LoopResult
+    /// `on_token` is called for each streamed text chunk from the model, and
+    /// with [`RESTART_NOTICE`] when a broken stream starts over.
+    pub async fn handle_input_stream<F>(&mut self, text: &str, mut on_token: F) -> LoopResult
     where
         F: FnMut(&str),
+    {
+        self.handle_input_events(text, |event| match event {
+            StreamEvent::Token(token) => on_token(token),
+            StreamEvent::Restart => on_token(RESTART_NOTICE),
+            _ => {}
+        })
+        .await
+    }
+
+    /// Like [`Self::handle_input_stream`], but also reports each tool call
+    /// and its result as it happens (used by `run --output jsonl`).
+    #[instrument(skip_all, fields(input_len = text.len(), turn_id = field::Empty))]
+    pub async fn handle_input_events<F>(&mut self, text: &str, on_event: F) -> LoopResult
+    where
+        F: FnMut(StreamEvent<'_>),
+    {
+        let turn_id = self.begin_turn();
+        let result = trace::in_turn(turn_id.clone(), self.run_input_events(text, on_event)).await;
+        self.end_turn(&turn_id, result)
+    }
+
+    async fn run_input_events<F>(&mut self, text: &str, mut on_event: F) -> LoopResult
+    where
+        F: FnMut(StreamEvent<'_>),
     {
         let mut result = LoopResult::default();
+        let expanded = expand_alias(text, &self.config.command_aliases, self.command_prefix);
+        let text = expanded.as_deref().unwrap_or(text);
+
+        if let Some(output) = self.dialog_command(text) {
+            result.immediate_output = Some(output);
+            return result;
+        }
+        if let Some(output) = self.session_command(text) {
+            result.immediate_output = Some(output);
+            return result;
+        }
+        if let Some(output) = self.ask_all_command(text).await {
+            result.immediate_output = Some(output);
+            return result;
+        }
 
         // 1. Route user input
-        let route = route_user(text, &mut self.tape, self.workspace);
+        let route = route_user_with_limits(
+            text,
+            &mut self.tape,
+            &self.workspace,
+            &self.config.tool_output_limits,
+            self.command_prefix,
+        );
 
         if route.exit_requested {
             result.exit_requested = true;
@@ -216,50 +515,534 @@ impl<'a> AgentLoop<'a> {
             warn!("agent_loop.tape.write.error: {e}");
         }
 
-        // 3. Build tool definitions from progressive view
-        let tool_defs = self.tool_view.tool_definitions();
-        let tools = if tool_defs.is_empty() {
-            None
-        } else {
-            Some(tool_defs)
-        };
-
-        // 4. Build system prompt and messages from tape context
-        let tools_prompt = self.tools_prompt_block();
-        let system_prompt = build_system_prompt_with_tools(
-            self.config.system_prompt.as_deref(),
-            self.workspace,
-            Some(&tools_prompt),
-        );
-        let mut messages = build_messages(
-            &self.tape,
-            Some(&system_prompt),
-            self.config.max_context_messages,
-        );
+        // 3-4. Build tool definitions, system prompt and messages
+        let (tools, mut messages) = self.prepare_request(&route.model_prompt);
+        self.insert_reply_context(&mut messages);
 
         debug!(message_count = messages.len(), "agent_loop.stream_request");
+        self.tool_ctx.citations.take();
+        self.tool_ctx.outputs.reset();
 
         // 5. Run streaming model turn with tool calling loop
-        let runner = ModelRunner::new(self.config, self.workspace);
+        let mut config = self.persona_config();
+        if let Some(model) = &route.model_override {
+            config.model = model.clone();
+        }
+        let runner = ModelRunner::new(&config, &self.workspace)
+            .with_temperature(self.persona.as_ref().and_then(|p| p.temperature))
+            .with_tool_choice(self.next_tool_choice.take());
         let turn_result = runner
             .run_turn_stream(
                 &mut messages,
                 tools.as_deref(),
                 &self.tape,
                 &self.tool_ctx,
-                on_token,
+                &mut on_event,
             )
             .await;
 
         // 6. Process result
         self.process_turn_result(&turn_result, &mut result);
+        self.ensure_title(&route.model_prompt, &result).await;
+        if let Some(sources) = self.attach_sources(&mut result) {
+            on_event(StreamEvent::Token(&format!("\n\n{sources}")));
+        }
 
         result
     }
 
+    /// Start a turn: a new trace ID on the current span and the tape.
+    fn begin_turn(&mut self) -> String {
+        let turn_id = trace::new_turn_id();
+        Span::current().record("turn_id", turn_id.as_str());
+        self.tape.set_turn(Some(turn_id.clone()));
+        turn_id
+    }
+
+    /// End turn `turn_id`, quoting its ID in the error, if any.
+    fn end_turn(&mut self, turn_id: &str, mut result: LoopResult) -> LoopResult {
+        self.tape.set_turn(None);
+        if let Some(error) = &result.error {
+            result.error = Some(trace::tag_error(error, turn_id));
+        }
+        result.turn_id = Some(turn_id.to_string());
+        result
+    }
+
+    /// Answer commands that need the loop's own state, which the stateless
+    /// router does not have: `,prompt.show` / `,prompt.tokens [msg]` and
+    /// `,context` (tools, skills, persona) and `,workspace` (registered
+    /// workspaces).
+    fn session_command(&mut self, text: &str) -> Option<String> {
+        let command = detect_command_with_prefix(text, self.command_prefix)?;
+        if command.kind != CommandKind::Internal {
+            return None;
+        }
+        match command.name.as_str() {
+            "prompt.show" | "prompt.tokens" => {
+                Some(self.prompt_preview(&command.name, &command.args.positional.join(" ")))
+            }
+            "context" => Some(self.context_report()),
+            "workspace" => Some(self.workspace_command(&command.args.positional)),
+            "aliases" => Some(self.list_aliases()),
+            "debug" => Some(self.debug_command(&command.args.positional)),
+            _ => None,
+        }
+    }
+
+    /// `,ask-all`: the prompt, with the session's history, to several
+    /// models at once (see [`crate::core::consensus`]).
+    async fn ask_all_command(&mut self, text: &str) -> Option<String> {
+        let command = detect_command_with_prefix(text, self.command_prefix)?;
+        if command.kind != CommandKind::Internal || command.name != "ask-all" {
+            return None;
+        }
+        let args = command
+            .raw
+            .trim_start_matches(self.command_prefix)
+            .trim_start()
+            .trim_start_matches("ask-all");
+        let request = match AskAll::parse(args) {
+            Ok(request) => request,
+            Err(usage) => return Some(usage),
+        };
+        let models = request
+            .models
+            .unwrap_or_else(|| self.config.consensus_models.clone());
+        if models.is_empty() {
+            return Some(
+                "No models to ask: set CONSENSUS_MODELS=provider:model,... or pass --models="
+                    .to_string(),
+            );
+        }
+        let panel = Panel {
+            models,
+            judge: request
+                .judge
+                .unwrap_or_else(|| self.config.consensus_judge.clone()),
+        };
+
+        if let Err(e) = self.tape.append_message("user", &request.prompt) {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
+        let (_, messages) = self.prepare_request(&request.prompt);
+        let config = self.persona_config();
+        let consensus = consensus::ask_all(
+            &config,
+            &self.workspace,
+            &request.prompt,
+            &messages,
+            &panel,
+            &self.tape,
+            &self.tool_ctx,
+        )
+        .await;
+
+        let output = consensus.format();
+        let payload = serde_json::to_value(&consensus).unwrap_or_default();
+        if let Err(e) = self.tape.append_event(CONSENSUS_EVENT, payload) {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
+        if let Err(e) = self.tape.append_message("assistant", &output) {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
+        Some(output)
+    }
+
+    fn prompt_preview(&mut self, name: &str, sample: &str) -> String {
+        let (tools, mut messages) = self.build_request(sample);
+        if !sample.is_empty() {
+            messages.push(Message::user(sample));
+        }
+        if name == "prompt.show" {
+            format_prompt_preview(&messages, tools.as_deref())
+        } else {
+            let budget = capabilities(&self.config, &self.effective_model()).prompt_budget();
+            format_prompt_tokens(&messages, tools.as_deref(), budget)
+        }
+    }
+
+    fn context_report(&mut self) -> String {
+        let (tools, messages) = self.build_request("");
+        let budget = capabilities(&self.config, &self.effective_model()).prompt_budget();
+        format_context_report(
+            &messages,
+            tools.as_deref(),
+            &self.tools_prompt_block(),
+            &self.tape,
+            budget,
+        )
+    }
+
+    fn workspace_command(&mut self, args: &[String]) -> String {
+        const USAGE: &str = "Usage: ,workspace [list] | ,workspace switch <name|default>";
+        match args.first().map(String::as_str) {
+            None | Some("list") => self.list_workspaces(),
+            Some("switch") => match args.get(1) {
+                Some(name) => self
+                    .switch_workspace(name)
+                    .unwrap_or_else(|e| format!("Error: {e}")),
+                None => USAGE.to_string(),
+            },
+            Some(other) => format!("unknown workspace command: {other}\n{USAGE}"),
+        }
+    }
+
+    /// Start, continue or cancel a multi-step command (see
+    /// [`crate::core::dialog`]). `None` when `text` is left to the rest of
+    /// the loop.
+    fn dialog_command(&mut self, text: &str) -> Option<String> {
+        let open = dialog::open_dialog(&self.tape);
+        let command = detect_command_with_prefix(text, self.command_prefix)
+            .filter(|c| c.kind == CommandKind::Internal);
+        let Some(command) = command else {
+            // A shell command or plain message: the answer, if one is due.
+            let mut dialog = open.filter(|_| !text.trim().starts_with(self.command_prefix))?;
+            let problem = dialog.answer(text).err();
+            return Some(self.advance_dialog(dialog, problem));
+        };
+        if command.name == "cancel" {
+            if open.is_none() {
+                return Some("Nothing to cancel.".to_string());
+            }
+            self.save_dialog(None);
+            return Some("Cancelled.".to_string());
+        }
+        let Some(start) = Form::from_command(&command) else {
+            if open.is_some() {
+                self.save_dialog(None);
+            }
+            return None;
+        };
+        let (form, mut values) = match start {
+            Ok(start) => start,
+            Err(usage) => return Some(usage),
+        };
+        if form == Form::Remind && self.tool_ctx.notifier.is_some() {
+            values
+                .entry("to".to_string())
+                .or_insert_with(|| "here".to_string());
+        }
+        let (dialog, problem) = Dialog::start(form, values);
+        Some(self.advance_dialog(dialog, problem))
+    }
+
+    /// Ask for the next missing field, or run the command once every field
+    /// is filled. `problem` explains why the last answer was not taken.
+    fn advance_dialog(&mut self, mut dialog: Dialog, mut problem: Option<String>) -> String {
+        loop {
+            if let Some(question) = dialog.next_question() {
+                self.save_dialog(Some(&dialog));
+                return match problem {
+                    Some(problem) => format!("{problem}\n{question}"),
+                    None => question,
+                };
+            }
+            match self.finish_dialog(&dialog) {
+                Ok(reply) => {
+                    self.save_dialog(None);
+                    return reply;
+                }
+                Err((field, error)) => {
+                    dialog.values.remove(field);
+                    problem = Some(error);
+                }
+            }
+        }
+    }
+
+    /// Run a filled-in form, or name the field to ask for again.
+    fn finish_dialog(
+        &mut self,
+        dialog: &Dialog,
+    ) -> std::result::Result<String, (&'static str, String)> {
+        match dialog.form {
+            Form::Remind => {
+                let now = match self.config.timezone {
+                    Some(tz) => chrono::Utc::now().with_timezone(&tz).time(),
+                    None => chrono::Local::now().time(),
+                };
+                let after_seconds =
+                    dialog::parse_delay(dialog.value("when"), now).map_err(|e| ("when", e))?;
+                let to = dialog.value("to");
+                let notify_via = if to.eq_ignore_ascii_case("here") {
+                    ""
+                } else {
+                    to
+                };
+                crate::tools::notify::notifier_for(
+                    notify_via,
+                    self.tool_ctx.notifier.clone(),
+                    &self.tool_ctx.notify,
+                )
+                .map_err(|e| ("to", e))?;
+                let args = serde_json::json!({
+                    "message": dialog.value("message"),
+                    "after_seconds": after_seconds,
+                    "notify_via": notify_via,
+                });
+                Ok(crate::tools::registry::execute_tool(
+                    "schedule.add",
+                    &args.to_string(),
+                    &self.tape,
+                    &self.workspace,
+                    &self.tool_ctx,
+                ))
+            }
+            Form::ConfigSet => {
+                let (key, value) = (dialog.value("key"), dialog.value("value"));
+                crate::core::config::save_dotenv_value(
+                    &self.home,
+                    &self.config.profile,
+                    key,
+                    value,
+                )
+                .map_err(|e| ("value", format!("{key} was not saved: {e}")))?;
+                let mut reply = format!(
+                    "Saved {key} to .env.local. New sessions use it; a running bot picks it up with ,admin reload-config."
+                );
+                if std::env::var_os(key).is_some() {
+                    reply.push_str(&format!(
+                        "\nNote: {key} is also set in the environment, which takes precedence."
+                    ));
+                }
+                Ok(reply)
+            }
+        }
+    }
+
+    fn save_dialog(&mut self, dialog: Option<&Dialog>) {
+        if let Err(e) = dialog::save_dialog(&mut self.tape, dialog) {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
+    }
+
+    /// `,debug dump [on|off]`: provider request dumps for this session (see
+    /// [`crate::llm::dump`]).
+    fn debug_command(&mut self, args: &[String]) -> String {
+        let usage = "usage: ,debug dump [on|off]".to_string();
+        let [sub, rest @ ..] = args else {
+            return usage;
+        };
+        if sub != "dump" || rest.len() > 1 {
+            return usage;
+        }
+        let dir = match rest.first().map(String::as_str) {
+            None => None,
+            Some("on") => {
+                Some(Some(self.config.dump_dir.clone().unwrap_or_else(|| {
+                    self.home.join(".crabclaw").join("dumps")
+                })))
+            }
+            Some("off") => Some(None),
+            Some(_) => return usage,
+        };
+        if let Some(dir) = dir {
+            let payload = serde_json::json!({ "dir": dir.map(|d| d.display().to_string()) });
+            if let Err(e) = self.tape.append_event(DUMP_EVENT, payload) {
+                return format!("failed to record dump switch: {e}");
+            }
+        }
+        match self.dump_dir() {
+            Some(dir) => format!("Provider dumps: on, writing to {}", dir.display()),
+            None => "Provider dumps: off".to_string(),
+        }
+    }
+
+    fn list_aliases(&self) -> String {
+        if self.config.command_aliases.is_empty() {
+            return "No aliases defined (set COMMAND_ALIASES=name=expansion;...)".to_string();
+        }
+        let width = self
+            .config
+            .command_aliases
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        let mut lines = vec!["Aliases:".to_string()];
+        for (name, expansion) in &self.config.command_aliases {
+            lines.push(format!("  ,{name:<width$}  → {expansion}"));
+        }
+        lines.join("\n")
+    }
+
+    fn list_workspaces(&self) -> String {
+        let active = tape_workspace(&self.tape).flatten();
+        let marker = |name: Option<&str>| if active.as_deref() == name { "*" } else { " " };
+        let mut lines = vec![
+            "Workspaces:".to_string(),
+            format!("{} default  {}", marker(None), self.home.display()),
+        ];
+        for (name, _) in &self.config.workspaces {
+            let path = registered_workspace(&self.config, &self.home, name).unwrap_or_default();
+            lines.push(format!("{} {name}  {}", marker(Some(name)), path.display()));
+        }
+        if self.config.workspaces.is_empty() {
+            lines.push("(register more with WORKSPACES=name=path,...)".to_string());
+        }
+        lines.join("\n")
+    }
+
+    /// Make `name` the active workspace and re-anchor the tape so the new
+    /// workspace starts with a fresh context window.
+    fn switch_workspace(&mut self, name: &str) -> std::result::Result<String, String> {
+        if !self.workspace_switching {
+            return Err("workspace switching is disabled for this session".to_string());
+        }
+        let (selected, path) = if name == "default" {
+            (None, self.home.clone())
+        } else {
+            let path = registered_workspace(&self.config, &self.home, name)
+                .ok_or_else(|| format!("unknown workspace '{name}' (see ,workspace)"))?;
+            (Some(name), path)
+        };
+        if !path.is_dir() {
+            return Err(format!(
+                "workspace directory {} does not exist",
+                path.display()
+            ));
+        }
+        let record = |e: std::io::Error| format!("failed to record workspace switch: {e}");
+        self.tape
+            .append_event(WORKSPACE_EVENT, serde_json::json!({ "name": selected }))
+            .map_err(record)?;
+        self.tape
+            .anchor(
+                &format!("workspace/{name}"),
+                serde_json::json!({ "workspace": path.display().to_string() }),
+            )
+            .map_err(record)?;
+        self.workspace = path;
+        self.rebuild_tools();
+        Ok(format!(
+            "Switched to workspace '{name}' ({}). Context window reset.",
+            self.workspace.display()
+        ))
+    }
+
+    /// Allow or forbid `,workspace switch` (channels with per-user workspace
+    /// isolation turn it off).
+    pub fn set_workspace_switching(&mut self, enabled: bool) {
+        self.workspace_switching = enabled;
+    }
+
+    /// Use another command prefix than `COMMAND_PREFIX` (Telegram has its
+    /// own, `TELEGRAM_COMMAND_PREFIX`).
+    pub fn set_command_prefix(&mut self, prefix: char) {
+        self.command_prefix = prefix;
+    }
+
+    /// Active workspace of the session.
+    pub fn workspace(&self) -> &Path {
+        &self.workspace
+    }
+
+    /// Name the session after its first answered exchange.
+    async fn ensure_title(&mut self, prompt: &str, result: &LoopResult) {
+        let Some(reply) = result.assistant_output.as_deref().filter(|s| !s.is_empty()) else {
+            return;
+        };
+        if tape_title(&self.tape).is_some() {
+            return;
+        }
+        let title = match self.config.session_titles {
+            TitleMode::Off => return,
+            TitleMode::Message => fallback_title(prompt),
+            TitleMode::Model => {
+                generate_title(
+                    &self.persona_config(),
+                    &self.effective_model(),
+                    prompt,
+                    reply,
+                )
+                .await
+            }
+        };
+        debug!(%title, "agent_loop.session_titled");
+        if let Err(e) = self
+            .tape
+            .append_event(TITLE_EVENT, serde_json::json!({ "title": title }))
+        {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
+    }
+
+    /// Append the web sources used this turn to the answer and record them
+    /// in the tape. Returns the appended section.
+    fn attach_sources(&mut self, result: &mut LoopResult) -> Option<String> {
+        let sources = self.tool_ctx.citations.take();
+        if sources.is_empty() {
+            return None;
+        }
+        let answer = result.assistant_output.as_mut()?;
+        let section = format_sources(&sources);
+        answer.push_str("\n\n");
+        answer.push_str(&section);
+        if let Err(e) = self
+            .tape
+            .append_event(SOURCES_EVENT, serde_json::json!({ "sources": sources }))
+        {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
+        Some(section)
+    }
+
     /// Process the model turn result: record to tape and populate LoopResult.
     fn process_turn_result(&mut self, turn: &ModelTurnResult, result: &mut LoopResult) {
         result.tool_rounds = turn.tool_rounds;
+        result.invoked_tools = turn.invoked_tools.clone();
+        result.usage = turn.usage.clone();
+
+        if let Some(usage) = &turn.usage {
+            let mut payload = serde_json::to_value(usage).unwrap_or_default();
+            if let Some(cost) = capabilities(&self.config, &self.effective_model()).cost_usd(usage)
+            {
+                payload["cost_usd"] = serde_json::json!(cost);
+            }
+            if let Err(e) = self.tape.append_event(TURN_USAGE_EVENT, payload) {
+                warn!("agent_loop.tape.write.error: {e}");
+            }
+        }
+
+        for repair in &turn.repaired_args {
+            let payload = serde_json::to_value(repair).unwrap_or_default();
+            if let Err(e) = self.tape.append_event(ARGS_REPAIRED_EVENT, payload) {
+                warn!("agent_loop.tape.write.error: {e}");
+            }
+        }
+
+        for call in &turn.tool_calls {
+            let payload = serde_json::to_value(call).unwrap_or_default();
+            if let Err(e) = self.tape.append_event(TOOL_CALL_EVENT, payload) {
+                warn!("agent_loop.tape.write.error: {e}");
+            }
+        }
+        result.attachments = turn
+            .tool_calls
+            .iter()
+            .filter_map(|call| call.output.clone())
+            .filter(ToolOutput::is_attachment)
+            .collect();
+
+        for note in self.tool_ctx.pins.take() {
+            if let Err(e) = pins::add(&mut self.tape, &note, "model") {
+                warn!("agent_loop.tape.write.error: {e}");
+            }
+        }
+
+        for partial in &turn.partial_responses {
+            let payload = serde_json::to_value(partial).unwrap_or_default();
+            if let Err(e) = self.tape.append_event(TURN_PARTIAL_EVENT, payload) {
+                warn!("agent_loop.tape.write.error: {e}");
+            }
+        }
+
+        result.timing = Some(turn.timing);
+        let payload = serde_json::to_value(turn.timing).unwrap_or_default();
+        if let Err(e) = self.tape.append_event(TURN_TIMING_EVENT, payload) {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
 
         for tool_name in &turn.invoked_tools {
             self.tool_view.note_selected(tool_name);
@@ -267,6 +1050,10 @@ impl<'a> AgentLoop<'a> {
 
         if let Some(err) = &turn.error {
             result.error = Some(err.clone());
+            let payload = serde_json::json!({ "error": err });
+            if let Err(e) = self.tape.append_event(TURN_ERROR_EVENT, payload) {
+                warn!("agent_loop.tape.write.error: {e}");
+            }
         }
 
         if !turn.assistant_text.is_empty() {
@@ -285,10 +1072,12 @@ impl<'a> AgentLoop<'a> {
             }
 
             // Route assistant output through command detection
-            let assistant_route = crate::core::router::route_assistant(
+            let assistant_route = crate::core::router::route_assistant_with_policy(
                 &turn.assistant_text,
                 &mut self.tape,
-                self.workspace,
+                &self.workspace,
+                &self.config.assistant_commands,
+                self.command_approver.as_ref(),
             );
 
             if assistant_route.has_commands() {
@@ -330,6 +1119,48 @@ impl<'a> AgentLoop<'a> {
         }
     }
 
+    /// Run a scheduled agent-mode job and record a structured report.
+    ///
+    /// The report (prompt, tools called, usage, output, duration) is appended
+    /// to the tape as a `schedule.report` event and returned to the caller.
+    pub async fn run_scheduled_job(&mut self, job_id: &str, prompt: &str) -> AgentRunReport {
+        let started = std::time::Instant::now();
+        let snapshot = crate::core::snapshot::create_before_run(
+            &self.workspace,
+            &format!("before job {job_id}"),
+        );
+        let result = self.handle_input(prompt).await;
+
+        let output = [&result.immediate_output, &result.assistant_output]
+            .into_iter()
+            .flatten()
+            .filter(|s| !s.is_empty())
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let report = AgentRunReport {
+            job_id: job_id.to_string(),
+            prompt: prompt.to_string(),
+            tool_calls: result.invoked_tools,
+            tool_rounds: result.tool_rounds,
+            usage: result.usage,
+            output: (!output.is_empty()).then_some(output),
+            error: result.error,
+            duration_ms: started.elapsed().as_millis() as u64,
+            snapshot,
+        };
+
+        match serde_json::to_value(&report) {
+            Ok(payload) => {
+                if let Err(e) = self.tape.append_event("schedule.report", payload) {
+                    warn!("agent_loop.tape.write.error: {e}");
+                }
+            }
+            Err(e) => warn!("agent_loop.schedule_report.serialize_error: {e}"),
+        }
+        report
+    }
+
     /// Access the tape store (for external recording or inspection).
     pub fn tape(&self) -> &TapeStore {
         &self.tape
@@ -350,6 +1181,408 @@ impl<'a> AgentLoop<'a> {
         Ok(())
     }
 
+    /// Archive the tape and start afresh when its last message is more than
+    /// `ttl` before `now`.
+    ///
+    /// The fresh tape opens with a `handoff` anchor naming the archive file,
+    /// followed by the active pins and the latest persona, model, workspace
+    /// and dump settings, so only the conversation itself is dropped. The
+    /// scratchpad is archived with the tape.
+    pub fn archive_if_idle(
+        &mut self,
+        ttl: chrono::TimeDelta,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Option<IdleArchive>> {
+        let last_message = self
+            .tape
+            .entries()
+            .iter()
+            .rev()
+            .find(|entry| entry.kind == "message")
+            .and_then(|entry| chrono::DateTime::parse_from_rfc3339(&entry.timestamp).ok());
+        let Some(last_message) = last_message else {
+            return Ok(None);
+        };
+        let idle = now.signed_duration_since(last_message);
+        if idle <= ttl {
+            return Ok(None);
+        }
+
+        let entries = self.tape.entries();
+        let settings: Vec<(String, serde_json::Value)> = SESSION_SETTING_EVENTS
+            .iter()
+            .filter_map(|kind| entries.iter().rev().find(|e| e.kind == *kind))
+            .map(|e| (e.kind.clone(), e.payload.clone()))
+            .collect();
+        let pin_ids: Vec<u64> = pins::active_pins(&self.tape)
+            .iter()
+            .map(|pin| pin.id)
+            .collect();
+        let pinned: Vec<serde_json::Value> = entries
+            .iter()
+            .filter(|e| pin_ids.contains(&e.id))
+            .map(|e| e.payload.clone())
+            .collect();
+
+        let entries_before = entries.len();
+        let archive = self.tape.reset(true).map_err(CrabClawError::Io)?;
+        self.tape
+            .anchor(
+                "handoff",
+                serde_json::json!({
+                    "owner": "system",
+                    "type": "handoff",
+                    "reason": "idle",
+                    "idle_hours": idle.num_hours(),
+                    "entries_before": entries_before,
+                    "archive": archive.map(|path| path.display().to_string()),
+                }),
+            )
+            .map_err(CrabClawError::Io)?;
+        for (kind, payload) in &settings {
+            self.tape
+                .append_event(kind, payload.clone())
+                .map_err(CrabClawError::Io)?;
+        }
+        for payload in &pinned {
+            self.tape
+                .append_event(pins::PIN_EVENT, payload.clone())
+                .map_err(CrabClawError::Io)?;
+        }
+        self.tool_view.reset();
+        Ok(Some(IdleArchive {
+            idle,
+            pins: pinned.len(),
+            settings: !settings.is_empty(),
+        }))
+    }
+
+    /// Build tool definitions and the message list for one model request.
+    fn prepare_request(&mut self, prompt: &str) -> (Option<Vec<ToolDefinition>>, Vec<Message>) {
+        self.refresh_workspace_context();
+        self.build_request(prompt)
+    }
+
+    /// [`Self::prepare_request`] without recording context changes on the
+    /// tape, for the read-only previews.
+    fn build_request(&mut self, prompt: &str) -> (Option<Vec<ToolDefinition>>, Vec<Message>) {
+        self.apply_persona();
+
+        let tool_defs = self.tool_view.tool_definitions();
+        let tools = if tool_defs.is_empty() {
+            None
+        } else {
+            Some(tool_defs)
+        };
+
+        let mut tools_prompt = self.tools_prompt_block();
+        if let Some(skills_block) = self.selected_skills_block(prompt) {
+            tools_prompt.push('\n');
+            tools_prompt.push_str(&skills_block);
+        }
+        let base_prompt = self.base_system_prompt();
+        let config_prompt = match &self.persona {
+            Some(persona) if !persona.system_prompt.is_empty() => {
+                Some(persona.system_prompt.as_str())
+            }
+            _ => base_prompt.as_deref(),
+        };
+        let reply_language =
+            crate::core::language::reply_instruction(self.config.reply_language.as_ref(), prompt);
+        let turn = TurnContext {
+            reply_language: reply_language.as_deref(),
+            timezone: self.config.timezone,
+            previous_message_at: previous_user_message_at(&self.tape, prompt),
+        };
+        let system_prompt = build_system_prompt_with_tools(
+            config_prompt,
+            &self.workspace,
+            Some(&tools_prompt),
+            &turn,
+        );
+        let messages = build_messages(
+            &self.tape,
+            Some(&system_prompt),
+            self.config.max_context_messages,
+        );
+        let budget = capabilities(&self.config, &self.effective_model()).prompt_budget();
+        (tools, fit_to_token_budget(messages, budget))
+    }
+
+    /// Let the user approve assistant shell commands when
+    /// `ASSISTANT_COMMAND_CONFIRM` is on; without an approver they are skipped.
+    pub fn set_command_approver(&mut self, approver: CommandApprover) {
+        self.command_approver = Some(approver);
+    }
+
+    /// Ask `approver` before each tool call that runs commands or changes
+    /// files (see `tools::registry::needs_approval`).
+    pub fn set_tool_approver(&mut self, approver: ToolApprover) {
+        self.tool_ctx.approver = Some(approver);
+    }
+
+    /// The Telegram chat this session belongs to, which `telegram:<chat_id>`
+    /// notification targets may name without being in `NOTIFY_TELEGRAM_CHATS`.
+    pub fn set_origin_chat(&mut self, chat_id: i64) {
+        self.tool_ctx.notify.origin_chat = Some(chat_id);
+    }
+
+    /// Record who is driving this session (e.g. a Telegram user ID) so
+    /// audited actions are attributed to them.
+    pub fn set_actor(&mut self, actor: Option<String>) {
+        self.tape.set_actor(actor);
+    }
+
+    /// Set the `tool_choice` for the first request of the next model turn,
+    /// e.g. `ToolChoice::Any` to force a planning step to call a tool.
+    pub fn set_next_tool_choice(&mut self, tool_choice: Option<ToolChoice>) {
+        self.next_tool_choice = tool_choice;
+    }
+
+    /// Ask the next `handle_input` turn for a JSON answer matching `schema`.
+    /// The validated value is returned in `LoopResult::structured_output`;
+    /// streaming turns ignore the schema.
+    pub fn set_next_output_schema(&mut self, schema: Option<serde_json::Value>) {
+        self.next_output_schema = schema;
+    }
+
+    /// Mark the next input as a reply to an earlier chat message. The
+    /// exchange around it is looked up in the tape and added to the prompt
+    /// (see `core::reply_context`).
+    pub fn set_reply_to(&mut self, target: Option<ReplyTarget>) {
+        self.reply_to = target;
+    }
+
+    /// Set the persona used when the session has not picked one with
+    /// `,persona` (channels pass their per-channel default here).
+    pub fn set_default_persona(&mut self, name: Option<String>) {
+        self.default_persona = name;
+    }
+
+    /// Resolve the session persona (tape switch, else channel default) and
+    /// rebuild the tool set if it changed since the last request.
+    fn apply_persona(&mut self) {
+        let name = tape_persona(&self.tape).unwrap_or_else(|| self.default_persona.clone());
+        let persona = name.and_then(|n| {
+            let found = find_persona(&n, &self.workspace);
+            if found.is_none() {
+                warn!(persona = %n, "agent_loop.persona_not_found");
+            }
+            found
+        });
+        if persona != self.persona {
+            debug!(persona = ?persona.as_ref().map(|p| &p.name), "agent_loop.persona_applied");
+            self.persona = persona;
+            self.rebuild_tools();
+        }
+    }
+
+    /// Config for the model runner, with the session (`,model`) or persona
+    /// model override and the session's dump switch applied.
+    fn persona_config(&self) -> AppConfig {
+        let mut config = AppConfig::clone(&self.config);
+        config.model = self.effective_model();
+        config.dump_dir = self.dump_dir();
+        config
+    }
+
+    /// Where this session's provider requests are dumped: the `,debug dump`
+    /// switch, else `CRABCLAW_DUMP_DIR`.
+    fn dump_dir(&self) -> Option<PathBuf> {
+        tape_dump_dir(&self.tape).unwrap_or_else(|| self.config.dump_dir.clone())
+    }
+
+    /// Model used for the next turn: `,model` switch, else the persona's
+    /// model, else the configured one.
+    fn effective_model(&self) -> String {
+        tape_model(&self.tape)
+            .flatten()
+            .or_else(|| self.persona.as_ref().and_then(|p| p.model.clone()))
+            .unwrap_or_else(|| self.config.model.clone())
+    }
+
+    /// Rebuild the registry from the workspace, filtered by the persona's tools.
+    fn rebuild_tools(&mut self) {
+        let WorkspaceTools {
+            mut registry,
+            skills,
+            mut external,
+        } = build_registry(&self.config, &self.workspace);
+        if let Some(persona) = &self.persona {
+            registry.retain(|name| persona.allows_tool(name));
+            external.retain(|tool| persona.allows_tool(&tool.name));
+        }
+        self.tool_view.replace_registry(registry);
+        self.skills = skills;
+        self.tool_ctx.external_tools = external;
+    }
+
+    /// Pick up edits to `.agent/` made while the session is running.
+    ///
+    /// The fingerprint of the files feeding the context (see
+    /// [`workspace_context_fingerprint`]) is kept on the tape. On change the
+    /// skill registry is rebuilt and a `context.changed` event is recorded.
+    fn refresh_workspace_context(&mut self) {
+        let fingerprint = workspace_context_fingerprint(&self.workspace);
+        let previous = self
+            .tape
+            .entries()
+            .iter()
+            .rev()
+            .find(|e| e.kind == "context.loaded" || e.kind == "context.changed")
+            .and_then(|e| e.payload.get("fingerprint"))
+            .and_then(|v| v.as_str())
+            .map(ToString::to_string);
+
+        let event = match previous {
+            Some(prev) if prev == fingerprint => return,
+            Some(prev) => {
+                self.rebuild_tools();
+                debug!("agent_loop.workspace_context_changed");
+                (
+                    "context.changed",
+                    serde_json::json!({ "fingerprint": fingerprint, "previous": prev }),
+                )
+            }
+            None => (
+                "context.loaded",
+                serde_json::json!({ "fingerprint": fingerprint }),
+            ),
+        };
+        if let Err(e) = self.tape.append_event(event.0, event.1) {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
+    }
+
+    /// Mark the latest turn as superseded when it was started by `original`
+    /// (the latest user message contains it), so its messages drop out of
+    /// the context before the replacement runs.
+    pub fn supersede_last_turn(&mut self, original: &str, reason: &str) {
+        let original = original.trim();
+        let Some(from) = self
+            .tape
+            .entries()
+            .iter()
+            .rev()
+            .find(|e| e.kind == "message" && e.payload["role"] == "user")
+            .filter(|e| {
+                !original.is_empty()
+                    && e.payload["content"]
+                        .as_str()
+                        .is_some_and(|c| c.contains(original))
+            })
+            .map(|e| e.id)
+        else {
+            debug!("agent_loop.supersede.no_turn");
+            return;
+        };
+        let to = self.tape.entries().last().map_or(from, |e| e.id);
+        if let Err(e) = self.tape.append_event(
+            TURN_SUPERSEDED_EVENT,
+            serde_json::json!({ "from": from, "to": to, "reason": reason }),
+        ) {
+            warn!("agent_loop.tape.write.error: {e}");
+        }
+    }
+
+    /// Put the context of a pending reply right before the user message.
+    fn insert_reply_context(&mut self, messages: &mut Vec<Message>) {
+        let Some(target) = self.reply_to.take() else {
+            return;
+        };
+        match reply_context(&self.tape, &target) {
+            Some(context) => {
+                let at = messages.len().saturating_sub(1);
+                messages.insert(at, Message::system(context));
+            }
+            None => debug!(message_id = ?target.message_id, "agent_loop.reply_target_not_found"),
+        }
+    }
+
+    /// The profile's `SYSTEM_PROMPT_FILE` followed by `SYSTEM_PROMPT`.
+    fn base_system_prompt(&self) -> Option<String> {
+        let file = self.config.system_prompt_file.as_ref().and_then(|path| {
+            let path = match path.strip_prefix("~") {
+                Ok(rest) => dirs::home_dir().unwrap_or_default().join(rest),
+                Err(_) => self.home.join(path),
+            };
+            std::fs::read_to_string(&path)
+                .inspect_err(|e| {
+                    warn!(path = %path.display(), "agent_loop.system_prompt_file.error: {e}");
+                })
+                .ok()
+        });
+        let parts: Vec<&str> = [file.as_deref(), self.config.system_prompt.as_deref()]
+            .into_iter()
+            .flatten()
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect();
+        (!parts.is_empty()).then(|| parts.join("\n\n"))
+    }
+
+    /// Skills named in the profile's `SKILLS`, in that order.
+    fn profile_skills(&self) -> Vec<SkillMetadata> {
+        if self.config.skills.is_empty() {
+            return Vec::new();
+        }
+        let available = skills::discover_skills(&self.workspace);
+        self.config
+            .skills
+            .iter()
+            .filter_map(|name| {
+                let found = available.iter().find(|s| s.name.eq_ignore_ascii_case(name));
+                if found.is_none() {
+                    warn!(skill = %name, "agent_loop.profile_skill.missing");
+                }
+                found.cloned()
+            })
+            .collect()
+    }
+
+    /// Inject the bodies of the profile's skills and, when auto-selection is
+    /// on, of the skills most relevant to `prompt`.
+    ///
+    /// Records a `skills.selected` tape event naming the auto-selected
+    /// skills. An event is used rather than an anchor so the context window
+    /// is kept.
+    fn selected_skills_block(&mut self, prompt: &str) -> Option<String> {
+        let mut loaded = self.profile_skills();
+        let selected: Vec<SkillMetadata> = match self.config.skill_top_k {
+            Some(top_k) => skills::select_relevant_skills(&self.skills, prompt, top_k)
+                .into_iter()
+                .filter(|s| !loaded.iter().any(|l| l.name == s.name))
+                .collect(),
+            None => Vec::new(),
+        };
+        loaded.extend(selected.iter().cloned());
+        if loaded.is_empty() {
+            return None;
+        }
+
+        let mut lines = vec!["<skills>".to_string()];
+        for skill in &loaded {
+            let body = std::fs::read_to_string(&skill.location).unwrap_or_default();
+            lines.push(format!("  <skill name=\"{}\">", skill.name));
+            lines.push(body.trim().to_string());
+            lines.push("  </skill>".to_string());
+        }
+        lines.push("</skills>".to_string());
+
+        let names: Vec<&str> = selected.iter().map(|s| s.name.as_str()).collect();
+        if !names.is_empty() {
+            debug!(skills = ?names, "agent_loop.skills_selected");
+            if let Err(e) = self
+                .tape
+                .append_event("skills.selected", serde_json::json!({ "skills": names }))
+            {
+                warn!("agent_loop.tape.write.error: {e}");
+            }
+        }
+
+        Some(lines.join("\n"))
+    }
+
     fn tools_prompt_block(&self) -> String {
         let compact = self.tool_view.compact_block();
         let expanded = self.tool_view.expanded_block();
@@ -361,6 +1594,45 @@ impl<'a> AgentLoop<'a> {
     }
 }
 
+/// Path of the workspace registered as `name`, resolved against `home`.
+fn registered_workspace(config: &AppConfig, home: &Path, name: &str) -> Option<PathBuf> {
+    config
+        .workspaces
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, path)| home.join(path))
+}
+
+/// What [`build_registry`] found in a workspace.
+struct WorkspaceTools {
+    registry: ToolRegistry,
+    /// Skills to inject per prompt when skill auto-selection is on.
+    skills: Vec<SkillMetadata>,
+    /// Tools declared in `.agent/tools.json`, for [`ToolContext`].
+    external: Vec<ExternalTool>,
+}
+
+/// Build the tool registry with builtins, `.agent/tools.json` tools and
+/// workspace skills.
+///
+/// With skill auto-selection on, skills are not registered as tools; they
+/// are returned so the loop can inject the relevant ones per prompt.
+fn build_registry(config: &AppConfig, workspace: &Path) -> WorkspaceTools {
+    let mut registry = crate::tools::registry::builtin_registry();
+    let external = crate::tools::registry::register_external_tools(&mut registry, workspace);
+    let skills = if config.skill_top_k.is_some() {
+        skills::discover_skills(workspace)
+    } else {
+        crate::tools::registry::register_skills(&mut registry, workspace);
+        Vec::new()
+    };
+    WorkspaceTools {
+        registry,
+        skills,
+        external,
+    }
+}
+
 fn assistant_commands_enabled() -> bool {
     parse_bool_env(std::env::var(ASSISTANT_COMMANDS_ENV_KEY).ok().as_deref())
 }
@@ -388,11 +1660,56 @@ mod tests {
             api_base: String::new(),
             model: String::new(),
             system_prompt: None,
+            system_prompt_file: None,
+            skills: Vec::new(),
+            reply_language: None,
+            timezone: None,
             telegram_token: None,
             telegram_allow_from: Vec::new(),
             telegram_allow_chats: Vec::new(),
             telegram_proxy: None,
+            telegram_persona: None,
+            telegram_output_filters: Vec::new(),
             max_context_messages: 50,
+            skill_top_k: None,
+            persona: None,
+            schedule_report: false,
+            schedule_max_retries: 2,
+            schedule_retry_backoff_seconds: 30,
+            schedule_max_concurrent_jobs: 2,
+            schedule_jitter_seconds: 0,
+            quiet_hours: None,
+            telegram_rate_limit_per_minute: None,
+            telegram_session_ttl_hours: None,
+            telegram_max_concurrent_turns: None,
+            telegram_workers: 8,
+            agent_cache_size: 0,
+            tape_sync: Default::default(),
+            telegram_admins: vec![],
+            telegram_user_workspace_root: None,
+            model_capabilities: None,
+            session_titles: crate::core::title::TitleMode::Off,
+            workspaces: Vec::new(),
+            command_aliases: Vec::new(),
+            command_prefix: ',',
+            telegram_command_prefix: ',',
+            max_continuations: 2,
+            http_pools: Default::default(),
+            llm_timeouts: Default::default(),
+            llm_retry: Default::default(),
+            llm_cassette: None,
+            dump_dir: None,
+            assistant_commands: Default::default(),
+            circuit_breaker: crate::core::config::CircuitBreakerConfig::disabled(),
+            fallback_model: None,
+            consensus_models: Vec::new(),
+            consensus_judge: None,
+            anthropic_params: None,
+            provider_endpoints: Default::default(),
+            provider_quirks: Vec::new(),
+            calendar_urls: Vec::new(),
+            notify: Default::default(),
+            tool_output_limits: Default::default(),
         }
     }
 
@@ -460,6 +1777,68 @@ mod tests {
         assert!(entries.len() <= 1);
     }
 
+    #[test]
+    fn idle_sessions_are_archived_with_a_handoff_anchor() {
+        let dir = tempdir().unwrap();
+        let config = test_config();
+        let mut loop_ = AgentLoop::open(&config, dir.path(), "test", None, None).unwrap();
+        let ttl = chrono::TimeDelta::hours(24);
+        let now = chrono::Utc::now();
+        assert_eq!(loop_.archive_if_idle(ttl, now).unwrap(), None);
+
+        loop_.tape_mut().append_message("user", "hello").unwrap();
+        assert_eq!(loop_.archive_if_idle(ttl, now).unwrap(), None);
+        let tape = loop_.tape_mut();
+        pins::add(tape, "answer in French", "human").unwrap();
+        let dropped = pins::add(tape, "old constraint", "human").unwrap();
+        pins::remove(tape, dropped).unwrap();
+        tape.append_event(MODEL_EVENT, serde_json::json!({"model": "openai:a"}))
+            .unwrap();
+        tape.append_event(MODEL_EVENT, serde_json::json!({"model": "openai:b"}))
+            .unwrap();
+        tape.append_event(
+            crate::core::persona::PERSONA_EVENT,
+            serde_json::json!({"name": "reviewer"}),
+        )
+        .unwrap();
+        std::fs::write(tape.scratch_path(), "plan").unwrap();
+
+        let later = now + chrono::TimeDelta::days(3);
+        let archived = loop_.archive_if_idle(ttl, later).unwrap().unwrap();
+        assert!(archived.idle > ttl);
+        assert_eq!((archived.pins, archived.settings), (1, true));
+
+        let tape = loop_.tape();
+        let entries = tape.entries();
+        assert!(!entries.iter().any(|entry| entry.kind == "message"));
+        let handoff_at = entries
+            .iter()
+            .position(|e| e.kind == "anchor" && e.payload["state"]["reason"] == "idle")
+            .unwrap();
+        let handoff = &entries[handoff_at].payload["state"];
+        let archive = handoff["archive"].as_str().unwrap();
+        assert!(std::fs::read_to_string(archive).unwrap().contains("hello"));
+        // Pins and settings follow the handoff anchor.
+        assert!(
+            entries[handoff_at + 1..]
+                .iter()
+                .any(|e| e.kind == MODEL_EVENT)
+        );
+        let notes: Vec<String> = pins::active_pins(tape)
+            .into_iter()
+            .map(|p| p.note)
+            .collect();
+        assert_eq!(notes, ["answer in French"]);
+        assert!(
+            pins::active_pins(tape)
+                .iter()
+                .all(|p| p.id > handoff_at as u64)
+        );
+        assert_eq!(tape_model(tape), Some(Some("openai:b".to_string())));
+        assert_eq!(tape_persona(tape), Some(Some("reviewer".to_string())));
+        assert!(!tape.scratch_path().exists());
+    }
+
     #[test]
     fn loop_result_to_reply_combines_parts() {
         let result = LoopResult {
@@ -499,14 +1878,435 @@ mod tests {
             tool_rounds: 1,
             invoked_tools: vec!["file.read".to_string()],
             error: Some("tool iteration limit reached".to_string()),
+            usage: None,
+            repaired_args: Vec::new(),
+            tool_calls: Vec::new(),
+            partial_responses: Vec::new(),
+            timing: TurnTiming {
+                first_token_ms: Some(800),
+                total_ms: 2_000,
+                tool_ms: 500,
+                tool_calls: 1,
+            },
         };
         let mut result = LoopResult::default();
 
         loop_.process_turn_result(&turn, &mut result);
 
+        assert_eq!(
+            result.timing.unwrap().footer(),
+            "0.8s first token · 2.0s total · 1 tool call"
+        );
+        let event = loop_
+            .tape()
+            .entries()
+            .iter()
+            .find(|e| e.kind == TURN_TIMING_EVENT)
+            .unwrap();
+        assert_eq!(event.payload["total_ms"], 2_000);
         assert_eq!(loop_.tool_view.expanded_count(), 1);
         assert_eq!(result.tool_rounds, 1);
         assert!(result.assistant_output.is_none());
         assert!(result.error.is_some());
     }
+
+    #[test]
+    fn skill_auto_selection_injects_matching_skill_and_records_event() {
+        let dir = tempdir().unwrap();
+        let skill_dir = dir.path().join(".agent/skills/docker-deploy");
+        std::fs::create_dir_all(&skill_dir).unwrap();
+        std::fs::write(
+            skill_dir.join("SKILL.md"),
+            "---\nname: docker-deploy\ndescription: Push docker images\n---\nRun docker push.",
+        )
+        .unwrap();
+
+        let mut config = test_config();
+        config.skill_top_k = Some(2);
+        let mut loop_ = AgentLoop::open(&config, dir.path(), "test", None, None).unwrap();
+        assert!(
+            !loop_
+                .tool_view
+                .all_tools()
+                .contains(&"skill.docker-deploy".to_string())
+        );
+
+        let (_, messages) = loop_.prepare_request("deploy with docker");
+        assert!(
+            messages[0]
+                .content
+                .contains("<skill name=\"docker-deploy\">")
+        );
+        assert!(messages[0].content.contains("Run docker push."));
+        let event = loop_.tape().entries().last().unwrap();
+        assert_eq!(event.kind, "skills.selected");
+        assert_eq!(event.payload["skills"][0], "docker-deploy");
+
+        let (_, messages) = loop_.prepare_request("unrelated question");
+        assert!(!messages[0].content.contains("<skills>"));
+    }
+
+    #[test]
+    fn profile_prompt_file_and_skills_are_always_loaded() {
+        let dir = tempdir().unwrap();
+        let skill_dir = dir.path().join(".agent/skills/conventions");
+        std::fs::create_dir_all(&skill_dir).unwrap();
+        std::fs::write(
+            skill_dir.join("SKILL.md"),
+            "---\nname: conventions\ndescription: Team rules\n---\nUse snake_case.",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("work.md"), "You help with work code.").unwrap();
+
+        let mut config = test_config();
+        config.system_prompt = Some("Be brief.".to_string());
+        config.system_prompt_file = Some(PathBuf::from("work.md"));
+        config.skills = vec!["Conventions".to_string(), "missing".to_string()];
+        let mut loop_ = AgentLoop::open(&config, dir.path(), "test", None, None).unwrap();
+
+        let (_, messages) = loop_.prepare_request("unrelated question");
+        let system = &messages[0].content;
+        let file_at = system.find("You help with work code.").unwrap();
+        assert!(file_at < system.find("Be brief.").unwrap());
+        assert!(system.contains("<skill name=\"conventions\">"));
+        assert!(system.contains("Use snake_case."));
+        // Profile skills are not auto-selected, so no event is recorded.
+        assert!(
+            loop_
+                .tape()
+                .entries()
+                .iter()
+                .all(|e| e.kind != "skills.selected")
+        );
+    }
+
+    #[test]
+    fn workspace_context_change_reloads_skills_and_records_event() {
+        let dir = tempdir().unwrap();
+        let config = test_config();
+        let mut loop_ = AgentLoop::open(&config, dir.path(), "test", None, None).unwrap();
+
+        loop_.prepare_request("hello");
+        assert_eq!(
+            loop_.tape().entries().last().unwrap().kind,
+            "context.loaded"
+        );
+        let count = loop_.tape().entries().len();
+        loop_.prepare_request("hello again");
+        assert_eq!(loop_.tape().entries().len(), count);
+
+        let skill_dir = dir.path().join(".agent/skills/late");
+        std::fs::create_dir_all(&skill_dir).unwrap();
+        std::fs::write(skill_dir.join("SKILL.md"), "---\nname: late\n---\n").unwrap();
+
+        loop_.prepare_request("and again");
+        assert_eq!(
+            loop_.tape().entries().last().unwrap().kind,
+            "context.changed"
+        );
+        assert!(
+            loop_
+                .tool_view
+                .all_tools()
+                .contains(&"skill.late".to_string())
+        );
+
+        // Editing only the body of a skill is a change too.
+        let count = loop_.tape().entries().len();
+        std::fs::write(
+            skill_dir.join("SKILL.md"),
+            "---\nname: late\n---\nNew instructions.\n",
+        )
+        .unwrap();
+        loop_.prepare_request("once more");
+        assert_eq!(loop_.tape().entries().len(), count + 1);
+        assert_eq!(
+            loop_.tape().entries().last().unwrap().kind,
+            "context.changed"
+        );
+    }
+
+    #[test]
+    fn persona_switch_changes_prompt_model_and_tools() {
+        let dir = tempdir().unwrap();
+        let personas = dir.path().join(".agent/personas");
+        std::fs::create_dir_all(&personas).unwrap();
+        std::fs::write(
+            personas.join("reader.md"),
+            "---\nname: reader\nmodel: openai:reader-model\ntools: file.read\n---\nOnly read files.",
+        )
+        .unwrap();
+
+        let config = test_config();
+        let mut loop_ = AgentLoop::open(&config, dir.path(), "test", None, None).unwrap();
+        loop_.set_default_persona(Some("reader".to_string()));
+
+        let (tools, messages) = loop_.prepare_request("hi");
+        assert!(messages[0].content.contains("Only read files."));
+        assert_eq!(loop_.tool_view.all_tools(), vec!["file.read".to_string()]);
+        assert_eq!(tools.unwrap().len(), 1);
+        assert_eq!(loop_.persona_config().model, "openai:reader-model");
+
+        loop_
+            .tape_mut()
+            .append_event("persona.switch", serde_json::json!({"name": null}))
+            .unwrap();
+        let (_, messages) = loop_.prepare_request("hi");
+        assert!(!messages[0].content.contains("Only read files."));
+        assert!(loop_.tool_view.all_tools().len() > 1);
+    }
+
+    #[tokio::test]
+    async fn prompt_commands_preview_next_request() {
+        let dir = tempdir().unwrap();
+        let config = test_config();
+        let mut agent = AgentLoop::open(&config, dir.path(), "prompt", None, None).unwrap();
+        agent
+            .tape
+            .append_message("user", "earlier question")
+            .unwrap();
+
+        let shown = agent.handle_input(",prompt.show what next").await;
+        let output = shown.immediate_output.unwrap();
+        assert!(output.contains("<identity>"), "{output}");
+        assert!(output.contains("[user] earlier question\n[user] what next"));
+        assert!(output.contains("=== Tools ("));
+
+        let tokens = agent.handle_input(",prompt.tokens").await;
+        assert!(
+            tokens
+                .immediate_output
+                .unwrap()
+                .contains("system prompt: ~")
+        );
+        // Previews are not recorded as conversation.
+        assert!(
+            !agent
+                .tape
+                .entries()
+                .iter()
+                .any(|e| e.payload["content"] == "what next")
+        );
+
+        // Nor do they record context fingerprints.
+        agent.handle_input(",context").await;
+        assert!(
+            !agent
+                .tape
+                .entries()
+                .iter()
+                .any(|e| e.kind.starts_with("context."))
+        );
+    }
+
+    #[tokio::test]
+    async fn context_command_reports_sections_and_trimmed_messages() {
+        let dir = tempdir().unwrap();
+        let mut config = test_config();
+        config.max_context_messages = 2;
+        let mut agent = AgentLoop::open(&config, dir.path(), "context", None, None).unwrap();
+
+        let output = agent
+            .handle_input(",context")
+            .await
+            .immediate_output
+            .unwrap();
+        assert!(output.contains("tool definitions ("), "{output}");
+        assert!(output.contains("Nothing trimmed"), "{output}");
+
+        agent
+            .tape
+            .append_message("user", "before the anchor")
+            .unwrap();
+        agent.tape.anchor("topic", serde_json::json!({})).unwrap();
+        for text in ["first question", "second question", "third question"] {
+            agent.tape.append_message("user", text).unwrap();
+        }
+        let output = agent
+            .handle_input(",context")
+            .await
+            .immediate_output
+            .unwrap();
+        assert!(output.contains("history (2 message(s))"), "{output}");
+        assert!(
+            output.contains("1 message(s) before anchor 'topic'"),
+            "{output}"
+        );
+        assert!(output.contains("1 older message(s) trimmed"), "{output}");
+        assert!(output.contains("[user] first question"), "{output}");
+        assert!(!output.contains("[user] second question"), "{output}");
+    }
+
+    #[test]
+    fn superseded_turns_leave_the_context() {
+        let dir = tempdir().unwrap();
+        let config = test_config();
+        let mut agent = AgentLoop::open(&config, dir.path(), "edit", None, None).unwrap();
+        agent.tape.append_message("user", "first").unwrap();
+        agent
+            .tape
+            .append_message("assistant", "answer one")
+            .unwrap();
+        agent.tape.append_message("user", "shw me logs").unwrap();
+        agent
+            .tape
+            .append_message("assistant", "which logs?")
+            .unwrap();
+
+        // A text that is not the latest prompt supersedes nothing.
+        agent.supersede_last_turn("first", "edited");
+        assert!(
+            !agent
+                .tape
+                .entries()
+                .iter()
+                .any(|e| e.kind == TURN_SUPERSEDED_EVENT)
+        );
+
+        agent.supersede_last_turn("shw me logs", "edited");
+        agent.tape.append_message("user", "show me logs").unwrap();
+        let (_, messages) = agent.prepare_request("show me logs");
+        let contents: Vec<&str> = messages[1..].iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["first", "answer one", "show me logs"]);
+    }
+
+    #[tokio::test]
+    async fn aliases_expand_before_routing_and_are_listed() {
+        let dir = tempdir().unwrap();
+        let mut config = test_config();
+        config.command_aliases = vec![
+            ("hi".to_string(), ",echo hello".to_string()),
+            ("ti".to_string(), ",tape.info".to_string()),
+        ];
+        let mut agent = AgentLoop::open(&config, dir.path(), "aliases", None, None).unwrap();
+
+        let output = agent.handle_input(",hi there").await.immediate_output;
+        assert_eq!(output.as_deref(), Some("hello there"));
+        let listed = agent
+            .handle_input(",aliases")
+            .await
+            .immediate_output
+            .unwrap();
+        assert!(listed.contains(",hi  → ,echo hello"), "{listed}");
+        assert!(listed.contains(",ti  → ,tape.info"), "{listed}");
+    }
+
+    #[tokio::test]
+    async fn config_set_asks_for_missing_parts_across_turns() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join(".env.local"), "API_KEY=test-key\n").unwrap();
+        let mut agent = AgentLoop::open(&test_config(), dir.path(), "dialog", None, None).unwrap();
+        let mut say = async |text: &str| agent.handle_input(text).await.immediate_output.unwrap();
+
+        assert!(say(",config set").await.starts_with("Which setting?"));
+        assert_eq!(say("timezone").await, "What should TIMEZONE be set to?");
+        let retry = say("Mars/Olympus").await;
+        assert!(retry.contains("TIMEZONE was not saved"), "{retry}");
+        assert!(
+            retry.ends_with("What should TIMEZONE be set to?"),
+            "{retry}"
+        );
+        assert!(say("Asia/Shanghai").await.starts_with("Saved TIMEZONE"));
+        let saved = std::fs::read_to_string(dir.path().join(".env.local")).unwrap();
+        assert_eq!(saved, "API_KEY=test-key\nTIMEZONE=Asia/Shanghai\n");
+
+        let refused = say(",config set TELEGRAM_ADMINS 42").await;
+        assert!(refused.contains("cannot be set from chat"), "{refused}");
+        assert!(
+            refused.ends_with("Which setting? e.g. MODEL or REPLY_LANGUAGE"),
+            "{refused}"
+        );
+        assert_eq!(say(",cancel").await, "Cancelled.");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(".env.local")).unwrap(),
+            saved
+        );
+
+        assert!(say(",remind").await.starts_with("What should I remind"));
+        assert_eq!(say(",cancel").await, "Cancelled.");
+        assert_eq!(say(",cancel").await, "Nothing to cancel.");
+    }
+
+    #[tokio::test]
+    async fn workspace_switch_reanchors_and_persists() {
+        let home = tempdir().unwrap();
+        let other = tempdir().unwrap();
+        let mut config = test_config();
+        config.workspaces = vec![("api".to_string(), other.path().display().to_string())];
+
+        let mut agent = AgentLoop::open(&config, home.path(), "ws", None, None).unwrap();
+        let listed = agent
+            .handle_input(",workspace")
+            .await
+            .immediate_output
+            .unwrap();
+        assert!(listed.contains("* default"), "{listed}");
+        assert!(listed.contains("  api"), "{listed}");
+
+        let out = agent.handle_input(",workspace switch nope").await;
+        assert!(out.immediate_output.unwrap().contains("unknown workspace"));
+
+        let out = agent.handle_input(",workspace switch api").await;
+        assert!(
+            out.immediate_output
+                .unwrap()
+                .starts_with("Switched to workspace 'api'")
+        );
+        assert_eq!(agent.workspace(), other.path());
+        assert_eq!(
+            agent.tape().info().last_anchor.as_deref(),
+            Some("workspace/api")
+        );
+        drop(agent);
+
+        // The tape stays in the home workspace and the switch survives reopening.
+        let mut agent = AgentLoop::open(&config, home.path(), "ws", None, None).unwrap();
+        assert_eq!(agent.workspace(), other.path());
+        agent.handle_input(",workspace switch default").await;
+        assert_eq!(agent.workspace(), home.path());
+
+        agent.set_workspace_switching(false);
+        let out = agent.handle_input(",workspace switch api").await;
+        assert!(out.immediate_output.unwrap().contains("disabled"));
+    }
+
+    #[tokio::test]
+    async fn dump_switch_applies_to_one_session() {
+        let dir = tempdir().unwrap();
+        let config = test_config();
+        let mut debugging = AgentLoop::open(&config, dir.path(), "a", None, None).unwrap();
+        let other = AgentLoop::open(&config, dir.path(), "b", None, None).unwrap();
+
+        let out = debugging.handle_input(",debug dump on").await;
+        assert!(
+            out.immediate_output
+                .unwrap()
+                .starts_with("Provider dumps: on")
+        );
+        let dumps = dir.path().join(".crabclaw").join("dumps");
+        assert_eq!(debugging.persona_config().dump_dir, Some(dumps.clone()));
+        assert_eq!(other.persona_config().dump_dir, None);
+        drop(debugging);
+
+        let mut debugging = AgentLoop::open(&config, dir.path(), "a", None, None).unwrap();
+        assert_eq!(debugging.persona_config().dump_dir, Some(dumps));
+        debugging.handle_input(",debug dump off").await;
+        assert_eq!(debugging.persona_config().dump_dir, None);
+    }
+
+    #[tokio::test]
+    async fn model_switch_overrides_persona_and_config() {
+        let dir = tempdir().unwrap();
+        let mut config = test_config();
+        config.model = "openai:base".to_string();
+        let mut loop_ = AgentLoop::open(&config, dir.path(), "test", None, None).unwrap();
+        assert_eq!(loop_.persona_config().model, "openai:base");
+
+        let result = loop_.handle_input(",model anthropic:claude-opus-4-5").await;
+        assert!(result.immediate_output.unwrap().contains("switched"));
+        assert_eq!(loop_.persona_config().model, "anthropic:claude-opus-4-5");
+
+        loop_.handle_input(",model default").await;
+        assert_eq!(loop_.persona_config().model, "openai:base");
+    }
 }
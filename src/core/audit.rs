@@ -0,0 +1,241 @@
+//! Append-only audit log of shell commands and file mutations.
+//!
+//! Separate from the tape: one JSONL file per day under
+//! `~/.crabclaw/audit/<workspace key>/`, recording who triggered what and
+//! how it ended. Tapes can be reset by users; the audit log is never
+//! rewritten. It lives outside the workspace so the model's file tools
+//! cannot edit it.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use crate::tape::store::TapeStore;
+
+/// Maximum bytes of command output kept per record.
+const MAX_RESULT_BYTES: usize = 500;
+
+/// One audited action.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    /// Channel the session belongs to (`cli`, `telegram`).
+    pub channel: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    pub session: String,
+    /// Agent turn the action belongs to (see `core::trace`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<String>,
+    /// Who issued the action: `human`, `assistant` (comma-command in model
+    /// output) or `tool` (model tool call).
+    pub origin: String,
+    /// `shell`, `shell.exec`, `file.write`, `file.edit`.
+    pub action: String,
+    /// Command line or target path.
+    pub target: String,
+    pub success: bool,
+    pub result: String,
+}
+
+/// Directory holding the daily audit files for a workspace.
+pub fn audit_dir(workspace: &Path) -> PathBuf {
+    audit_root().join(workspace_key(workspace))
+}
+
+/// Name of `workspace`'s audit directory: its file name and a hash of its
+/// canonical path, so workspaces with the same name stay apart.
+pub fn workspace_key(workspace: &Path) -> String {
+    use sha2::{Digest, Sha256};
+
+    let path = workspace
+        .canonicalize()
+        .unwrap_or_else(|_| workspace.to_path_buf());
+    let name: String = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let hash = format!("{:x}", Sha256::digest(path.to_string_lossy().as_bytes()));
+    format!("{name}-{}", &hash[..12])
+}
+
+fn audit_root() -> PathBuf {
+    // Unit tests must not write to the developer's home.
+    if cfg!(test) {
+        std::env::temp_dir().join("crabclaw-test-audit")
+    } else {
+        crate::core::config::user_config_dir().join("audit")
+    }
+}
+
+/// Append a record for an action taken in the session behind `tape`.
+///
+/// Failures are logged and never interrupt the action being audited.
+pub fn record(
+    workspace: &Path,
+    tape: &TapeStore,
+    origin: &str,
+    action: &str,
+    target: &str,
+    success: bool,
+    result: &str,
+) {
+    let session = tape.info().name;
+    let channel = if session.starts_with("telegram_") {
+        "telegram"
+    } else {
+        "cli"
+    };
+    let entry = AuditRecord {
+        timestamp: Utc::now().to_rfc3339(),
+        channel: channel.to_string(),
+        user: tape.actor().map(String::from),
+        session,
+        turn: tape.turn().map(String::from),
+        origin: origin.to_string(),
+        action: action.to_string(),
+        target: target.to_string(),
+        success,
+        result: truncate(result),
+    };
+    if let Err(e) = append(workspace, &entry) {
+        warn!("audit.write.error: {e}");
+    }
+}
+
+fn append(workspace: &Path, entry: &AuditRecord) -> std::io::Result<()> {
+    let dir = audit_dir(workspace);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("{}.jsonl", Utc::now().format("%Y-%m-%d")));
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let line = serde_json::to_string(entry).map_err(std::io::Error::other)?;
+    writeln!(file, "{line}")
+}
+
+fn truncate(text: &str) -> String {
+    let text = text.trim();
+    if text.len() <= MAX_RESULT_BYTES {
+        return text.to_string();
+    }
+    let mut end = MAX_RESULT_BYTES;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &text[..end])
+}
+
+/// The `limit` most recent records, oldest first.
+pub fn tail(workspace: &Path, limit: usize) -> std::io::Result<Vec<AuditRecord>> {
+    let dir = audit_dir(workspace);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut days: Vec<PathBuf> = fs::read_dir(&dir)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    days.sort();
+
+    let mut records = Vec::new();
+    for day in days.iter().rev() {
+        let content = fs::read_to_string(day)?;
+        let mut day_records: Vec<AuditRecord> = content
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        day_records.append(&mut records);
+        records = day_records;
+        if records.len() >= limit {
+            break;
+        }
+    }
+    let skip = records.len().saturating_sub(limit);
+    Ok(records.split_off(skip))
+}
+
+/// One-line rendering for `crabclaw audit tail`.
+pub fn format_record(record: &AuditRecord) -> String {
+    let status = if record.success { "ok" } else { "failed" };
+    let who = match &record.user {
+        Some(user) => format!("{}/{user}", record.channel),
+        None => record.channel.clone(),
+    };
+    let turn = record
+        .turn
+        .as_deref()
+        .map(|turn| format!(" turn={turn}"))
+        .unwrap_or_default();
+    format!(
+        "{} {who} session={}{turn} {} {} {status}: {}",
+        record.timestamp, record.session, record.origin, record.action, record.target
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn records_are_appended_and_tailed() {
+        let ws = tempdir().unwrap();
+        let mut tape = TapeStore::open(&ws.path().join(".crabclaw"), "telegram_-100").unwrap();
+        tape.set_actor(Some("42".to_string()));
+
+        record(ws.path(), &tape, "human", "shell", "ls", true, "Cargo.toml");
+        tape.set_turn(Some("0a1b2c3d4e5f".to_string()));
+        record(
+            ws.path(),
+            &tape,
+            "tool",
+            "file.write",
+            "notes.md",
+            false,
+            "Error writing file",
+        );
+
+        let records = tail(ws.path(), 10).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].channel, "telegram");
+        assert_eq!(records[0].user.as_deref(), Some("42"));
+        assert_eq!(records[0].turn, None);
+        assert_eq!(records[1].turn.as_deref(), Some("0a1b2c3d4e5f"));
+        assert_eq!(records[1].action, "file.write");
+        assert!(!records[1].success);
+
+        let last = tail(ws.path(), 1).unwrap();
+        assert_eq!(last.len(), 1);
+        assert_eq!(last[0].target, "notes.md");
+        assert!(format_record(&last[0]).contains(
+            "telegram/42 session=telegram_-100 turn=0a1b2c3d4e5f tool file.write failed: notes.md"
+        ));
+    }
+
+    #[test]
+    fn long_results_are_truncated() {
+        let long = "é".repeat(400);
+        let short = truncate(&long);
+        assert!(short.len() <= MAX_RESULT_BYTES + '…'.len_utf8());
+        assert!(short.ends_with('…'));
+    }
+
+    #[test]
+    fn tail_without_log_is_empty() {
+        let ws = tempdir().unwrap();
+        assert!(tail(ws.path(), 5).unwrap().is_empty());
+    }
+}
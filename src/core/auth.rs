@@ -10,8 +10,16 @@
 //! 4. User logs in, OpenAI redirects to localhost with auth code
 //! 5. Exchange code for access_token + refresh_token
 //! 6. Store tokens in ~/.crabclaw/auth.json
+//!
+//! API-key providers (OpenAI, Anthropic) are managed alongside: keys are
+//! validated with a cheap `GET /models` ping and stored in
+//! ~/.crabclaw/credentials.json, where config resolution falls back to them
+//! when no `API_KEY` is set.
 
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
 
@@ -29,6 +37,13 @@ const REDIRECT_URI: &str = "http://localhost:1455/auth/callback";
 const SCOPES: &str = "openid profile email offline_access";
 const CALLBACK_PORT: u16 = 1455;
 
+/// Refresh the Codex token this long before it expires.
+const REFRESH_MARGIN_SECS: i64 = 300;
+/// Delay before retrying a failed background refresh.
+const REFRESH_RETRY_SECS: u64 = 60;
+/// Warn this long before a token that cannot be refreshed expires.
+const EXPIRY_WARNING_SECS: i64 = 3600;
+
 /// Stored OAuth tokens.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenData {
@@ -368,9 +383,7 @@ pub fn status() {
         Some(tokens) => {
             println!("✅ Logged in via OAuth");
             if let Some(exp) = tokens.expires_at {
-                let dt = chrono::DateTime::from_timestamp(exp, 0)
-                    .map(|d| d.format("%Y-%m-%d %H:%M:%S UTC").to_string())
-                    .unwrap_or_else(|| "unknown".to_string());
+                let dt = format_expiry(exp);
                 let expired = tokens.is_expired();
                 println!(
                     "   Token expires:  {dt}{}",
@@ -394,6 +407,364 @@ pub fn status() {
     }
 }
 
+// ---------------------------------------------------------------------------
+// Background refresh
+// ---------------------------------------------------------------------------
+
+/// Seconds until `tokens` should be refreshed (0 when already due).
+fn refresh_due_in(tokens: &TokenData) -> Option<u64> {
+    tokens.refresh_token.as_ref()?;
+    let exp = tokens.expires_at?;
+    let due = exp - REFRESH_MARGIN_SECS - chrono::Utc::now().timestamp();
+    Some(due.max(0) as u64)
+}
+
+/// Callback used to surface auth problems to an operator (e.g. Telegram admins).
+pub type AuthAlert = Arc<dyn Fn(String) + Send + Sync>;
+
+fn format_expiry(exp: i64) -> String {
+    chrono::DateTime::from_timestamp(exp, 0)
+        .map(|d| d.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn refresh_failed_alert(error: &CrabClawError, exp: i64) -> String {
+    format!(
+        "⚠ Codex OAuth token refresh failed: {error}\n\
+         The current token expires at {}; codex requests and scheduled jobs \
+         will fail after that. Retrying every minute — run `crabclaw auth login` \
+         on the host if this persists.",
+        format_expiry(exp)
+    )
+}
+
+fn expiry_alert(exp: i64) -> String {
+    format!(
+        "⚠ Codex OAuth token expires at {} and cannot be refreshed. \
+         Run `crabclaw auth login` on the host.",
+        format_expiry(exp)
+    )
+}
+
+/// Keep the Codex OAuth token fresh in long-running sessions.
+///
+/// Refreshes shortly before expiry instead of waiting for a request to fail.
+/// `alert` is called on the first failure of a refresh streak, again if the
+/// token actually expires while refreshes keep failing, and once before
+/// expiry for tokens without a refresh token. Does nothing when no token with
+/// an expiry is stored; stops if the tokens are removed (`crabclaw auth
+/// logout`).
+pub fn spawn_token_refresher(alert: Option<AuthAlert>) {
+    if load_tokens().and_then(|t| t.expires_at).is_none() {
+        return;
+    }
+    let notify = move |text: String| {
+        warn!("auth.refresh.alert: {text}");
+        if let Some(alert) = &alert {
+            alert(text);
+        }
+    };
+    let spawned = std::thread::Builder::new()
+        .name("auth-refresh".to_string())
+        .spawn(move || {
+            let rt = match tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(rt) => rt,
+                Err(e) => {
+                    warn!("auth.refresh.runtime_error: {e}");
+                    return;
+                }
+            };
+            rt.block_on(async {
+                let mut failures = 0u32;
+                let mut expired_alerted = false;
+                loop {
+                    let Some(tokens) = load_tokens() else { return };
+                    let Some(exp) = tokens.expires_at else { return };
+                    let Some(due_in) = refresh_due_in(&tokens) else {
+                        // Not refreshable: warn ahead of expiry, then stop.
+                        let warn_in = exp - EXPIRY_WARNING_SECS - chrono::Utc::now().timestamp();
+                        tokio::time::sleep(Duration::from_secs(warn_in.max(0) as u64)).await;
+                        if load_tokens().is_some_and(|t| t.access_token == tokens.access_token) {
+                            notify(expiry_alert(exp));
+                        }
+                        return;
+                    };
+                    tokio::time::sleep(Duration::from_secs(due_in)).await;
+                    // Another process may have refreshed in the meantime.
+                    let Some(current) = load_tokens() else { return };
+                    if refresh_due_in(&current) != Some(0) {
+                        continue;
+                    }
+                    match refresh_access_token(&current).await {
+                        Ok(_) => {
+                            if failures > 0 {
+                                info!(failures, "auth.refresh.recovered");
+                            }
+                            failures = 0;
+                            expired_alerted = false;
+                        }
+                        Err(e) => {
+                            failures += 1;
+                            warn!(failures, "auth.refresh.error: {e}");
+                            if failures == 1 {
+                                notify(refresh_failed_alert(&e, exp));
+                            } else if current.is_expired() && !expired_alerted {
+                                expired_alerted = true;
+                                notify(format!(
+                                    "⚠ Codex OAuth token has expired and refresh is still \
+                                     failing ({e}). Run `crabclaw auth login` on the host."
+                                ));
+                            }
+                            tokio::time::sleep(Duration::from_secs(REFRESH_RETRY_SECS)).await;
+                        }
+                    }
+                }
+            });
+        });
+    if let Err(e) = spawned {
+        warn!("auth.refresh.spawn_error: {e}");
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Providers and API keys (persisted to ~/.crabclaw/credentials.json)
+// ---------------------------------------------------------------------------
+
+/// A provider managed by `crabclaw auth`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    /// ChatGPT subscription via OAuth (`codex:` models).
+    Codex,
+    /// OpenAI-compatible API key (`openai:` models).
+    OpenAI,
+    /// Anthropic API key (`anthropic:` models).
+    Anthropic,
+    /// xAI API key (`xai:` models).
+    Xai,
+    /// Mistral API key (`mistral:` models).
+    Mistral,
+}
+
+impl Provider {
+    pub const ALL: [Provider; 5] = [
+        Provider::Codex,
+        Provider::OpenAI,
+        Provider::Anthropic,
+        Provider::Xai,
+        Provider::Mistral,
+    ];
+
+    /// Parse a provider name as typed on the command line.
+    pub fn parse(name: &str) -> Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "codex" | "chatgpt" => Ok(Self::Codex),
+            "openai" => Ok(Self::OpenAI),
+            "anthropic" => Ok(Self::Anthropic),
+            "xai" | "grok" => Ok(Self::Xai),
+            "mistral" => Ok(Self::Mistral),
+            other => Err(CrabClawError::Config(format!(
+                "unknown provider '{other}'. Expected one of: codex, openai, anthropic, xai, mistral"
+            ))),
+        }
+    }
+
+    /// Provider implied by a `provider:model` string.
+    pub fn from_model(model: &str) -> Option<Self> {
+        let (prefix, _) = model.split_once(':')?;
+        Self::parse(prefix).ok()
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Codex => "codex",
+            Self::OpenAI => "openai",
+            Self::Anthropic => "anthropic",
+            Self::Xai => "xai",
+            Self::Mistral => "mistral",
+        }
+    }
+
+    /// Environment variable conventionally holding this provider's key.
+    pub fn key_env(self) -> Option<&'static str> {
+        match self {
+            Self::Codex => None,
+            Self::OpenAI => Some("OPENAI_API_KEY"),
+            Self::Anthropic => Some("ANTHROPIC_API_KEY"),
+            Self::Xai => Some("XAI_API_KEY"),
+            Self::Mistral => Some("MISTRAL_API_KEY"),
+        }
+    }
+
+    /// API base used to validate keys when none is given.
+    pub fn default_api_base(self) -> &'static str {
+        match self {
+            Self::Codex => "https://chatgpt.com/backend-api",
+            Self::OpenAI => "https://api.openai.com/v1",
+            Self::Anthropic => "https://api.anthropic.com",
+            Self::Xai => "https://api.x.ai/v1",
+            Self::Mistral => "https://api.mistral.ai/v1",
+        }
+    }
+}
+
+/// Stored API key for one provider.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredKey {
+    api_key: String,
+    /// Unix timestamp of the last successful validation ping.
+    #[serde(default)]
+    validated_at: Option<i64>,
+}
+
+fn credentials_file_path() -> PathBuf {
+    token_file_path().with_file_name("credentials.json")
+}
+
+fn load_credentials() -> BTreeMap<String, StoredKey> {
+    std::fs::read_to_string(credentials_file_path())
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn save_credentials(credentials: &BTreeMap<String, StoredKey>) -> Result<()> {
+    let path = credentials_file_path();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(CrabClawError::Io)?;
+    }
+    let json = serde_json::to_string_pretty(credentials)?;
+    std::fs::write(&path, json).map_err(CrabClawError::Io)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))
+            .map_err(CrabClawError::Io)?;
+    }
+    Ok(())
+}
+
+/// Stored API key for `provider`, if any.
+pub fn load_api_key(provider: Provider) -> Option<String> {
+    load_credentials()
+        .remove(provider.name())
+        .map(|stored| stored.api_key)
+}
+
+/// Store a validated API key for `provider`.
+pub fn save_api_key(provider: Provider, api_key: &str) -> Result<()> {
+    let mut credentials = load_credentials();
+    credentials.insert(
+        provider.name().to_string(),
+        StoredKey {
+            api_key: api_key.to_string(),
+            validated_at: Some(chrono::Utc::now().timestamp()),
+        },
+    );
+    save_credentials(&credentials)
+}
+
+/// Remove the stored API key for `provider`. Returns whether one existed.
+pub fn clear_api_key(provider: Provider) -> Result<bool> {
+    let mut credentials = load_credentials();
+    let existed = credentials.remove(provider.name()).is_some();
+    if existed {
+        save_credentials(&credentials)?;
+    }
+    Ok(existed)
+}
+
+/// Check an API key with a `GET /models` ping against `api_base`.
+pub async fn validate_api_key(provider: Provider, api_base: &str, api_key: &str) -> Result<()> {
+    let base = api_base.trim_end_matches('/');
+    let client = reqwest::Client::new();
+    let request = match provider {
+        Provider::Codex => {
+            return Err(CrabClawError::Auth(
+                "codex uses OAuth; run `crabclaw auth login codex`".to_string(),
+            ));
+        }
+        Provider::OpenAI | Provider::Xai | Provider::Mistral => {
+            client.get(format!("{base}/models")).bearer_auth(api_key)
+        }
+        Provider::Anthropic => client
+            .get(format!("{base}/v1/models"))
+            .header("x-api-key", api_key)
+            .header("anthropic-version", "2023-06-01"),
+    };
+    let resp = request
+        .timeout(Duration::from_secs(15))
+        .send()
+        .await
+        .map_err(|e| CrabClawError::Network(format!("{} ping failed: {e}", provider.name())))?;
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let body = resp.text().await.unwrap_or_default();
+    if status.as_u16() == 401 || status.as_u16() == 403 {
+        return Err(CrabClawError::Auth(format!(
+            "{} rejected the API key (HTTP {status}): {body}",
+            provider.name()
+        )));
+    }
+    Err(CrabClawError::Api(format!(
+        "{} ping failed (HTTP {status}): {body}",
+        provider.name()
+    )))
+}
+
+/// Abbreviate a secret for display (`sk-a…9xyz`).
+pub fn mask_key(key: &str) -> String {
+    let chars: Vec<char> = key.chars().collect();
+    if chars.len() <= 8 {
+        return "…".to_string();
+    }
+    let head: String = chars[..4].iter().collect();
+    let tail: String = chars[chars.len() - 4..].iter().collect();
+    format!("{head}…{tail}")
+}
+
+/// Print the status of every provider.
+pub fn status_all() {
+    status();
+    let credentials = load_credentials();
+    for provider in &Provider::ALL[1..] {
+        println!();
+        match credentials.get(provider.name()) {
+            Some(stored) => {
+                println!(
+                    "✅ {}: API key {}",
+                    provider.name(),
+                    mask_key(&stored.api_key)
+                );
+                if let Some(at) = stored
+                    .validated_at
+                    .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                {
+                    println!("   Last validated: {}", at.format("%Y-%m-%d %H:%M:%S UTC"));
+                }
+            }
+            None => {
+                println!("❌ {}: no stored key", provider.name());
+                println!(
+                    "   Run `crabclaw auth login {}` to add one.",
+                    provider.name()
+                );
+            }
+        }
+        if let Some(var) = provider.key_env()
+            && std::env::var(var).is_ok_and(|v| !v.trim().is_empty())
+        {
+            println!("   {var} is also set in the environment");
+        }
+    }
+    println!();
+    println!("   Credentials file: {}", credentials_file_path().display());
+}
+
 /// Minimal percent-encoding for URL params.
 fn urlencoding(s: &str) -> String {
     s.replace(' ', "%20")
@@ -463,6 +834,81 @@ mod tests {
         assert!(!no_expiry.is_expired());
     }
 
+    #[test]
+    fn provider_parsing() {
+        assert_eq!(Provider::parse("OpenAI").unwrap(), Provider::OpenAI);
+        assert_eq!(Provider::parse("chatgpt").unwrap(), Provider::Codex);
+        assert!(Provider::parse("gemini").is_err());
+        assert_eq!(
+            Provider::from_model("anthropic:claude-sonnet"),
+            Some(Provider::Anthropic)
+        );
+        assert_eq!(Provider::from_model("gpt-4o"), None);
+    }
+
+    #[test]
+    fn mask_key_hides_middle() {
+        assert_eq!(mask_key("sk-abcdefghijkl"), "sk-a…ijkl");
+        assert_eq!(mask_key("short"), "…");
+    }
+
+    #[test]
+    fn refresh_due_only_with_refresh_token_and_expiry() {
+        let mut tokens = TokenData {
+            access_token: "a".to_string(),
+            refresh_token: Some("r".to_string()),
+            id_token: None,
+            expires_at: Some(chrono::Utc::now().timestamp() + 3600),
+        };
+        let due = refresh_due_in(&tokens).unwrap();
+        assert!(due <= 3600 - REFRESH_MARGIN_SECS as u64 && due > 3000);
+
+        tokens.expires_at = Some(0);
+        assert_eq!(refresh_due_in(&tokens), Some(0));
+
+        tokens.refresh_token = None;
+        assert_eq!(refresh_due_in(&tokens), None);
+    }
+
+    #[test]
+    fn alerts_mention_expiry_and_relogin() {
+        let err = CrabClawError::Auth("token refresh failed (HTTP 400)".to_string());
+        let text = refresh_failed_alert(&err, 0);
+        assert!(text.contains("HTTP 400"));
+        assert!(text.contains("1970-01-01 00:00:00 UTC"));
+        assert!(text.contains("crabclaw auth login"));
+        assert!(expiry_alert(0).contains("cannot be refreshed"));
+    }
+
+    #[tokio::test]
+    async fn validate_api_key_pings_provider() {
+        let mut server = mockito::Server::new_async().await;
+        let ok = server
+            .mock("GET", "/v1/models")
+            .match_header("x-api-key", "good")
+            .with_status(200)
+            .with_body(r#"{"data":[]}"#)
+            .create_async()
+            .await;
+        let rejected = server
+            .mock("GET", "/models")
+            .match_header("authorization", "Bearer bad")
+            .with_status(401)
+            .with_body("invalid key")
+            .create_async()
+            .await;
+
+        validate_api_key(Provider::Anthropic, &server.url(), "good")
+            .await
+            .unwrap();
+        let err = validate_api_key(Provider::OpenAI, &server.url(), "bad")
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CrabClawError::Auth(_)), "got: {err}");
+        ok.assert_async().await;
+        rejected.assert_async().await;
+    }
+
     #[test]
     fn urlencoding_works() {
         assert_eq!(urlencoding("hello world"), "hello%20world");
@@ -34,21 +34,32 @@ pub struct ParsedArgs {
 }
 
 impl ParsedArgs {
-    /// Get a keyword argument value by key.
+    /// Get a keyword argument value by key; the last one wins if repeated.
     pub fn get(&self, key: &str) -> Option<&str> {
         self.kwargs
             .iter()
-            .find(|(k, _)| k == key)
+            .rfind(|(k, _)| k == key)
             .map(|(_, v)| v.as_str())
     }
 
+    /// Every value given for a repeated key, in order.
+    pub fn get_all(&self, key: &str) -> Vec<&str> {
+        self.kwargs
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect()
+    }
+
     /// Check if a flag is set.
     pub fn has_flag(&self, flag: &str) -> bool {
         self.flags.iter().any(|f| f == flag)
     }
 }
 
-const INTERNAL_PREFIX: char = ',';
+/// The command prefix unless a channel configures another
+/// (`COMMAND_PREFIX`, `TELEGRAM_COMMAND_PREFIX`).
+pub const DEFAULT_PREFIX: char = ',';
 
 /// Known internal command names.
 const KNOWN_INTERNAL_COMMANDS: &[&str] = &[
@@ -64,26 +75,91 @@ const KNOWN_INTERNAL_COMMANDS: &[&str] = &[
     "skills.describe",
     "anchors",
     "handoff",
+    "pin",
+    "pins",
+    "unpin",
+    "stats",
+    "persona",
+    "model",
+    "retry",
+    "redo",
+    "ask-all",
+    "prompt.show",
+    "prompt.tokens",
+    "context",
+    "workspace",
+    "schedule.history",
+    "export",
+    "snapshot",
+    "aliases",
+    "debug",
+    "remind",
+    "config",
+    "cancel",
 ];
 
+/// Whether `name` is a built-in internal command.
+pub fn is_internal_command(name: &str) -> bool {
+    KNOWN_INTERNAL_COMMANDS.contains(&name)
+}
+
+/// Expand a user-defined alias (`COMMAND_ALIASES`) at the start of `input`.
+///
+/// `,gs -s` with `gs=,git status` becomes `,git status -s`; an expansion
+/// without the `,` prefix is a prompt for the model. Expansions are written
+/// with `,` and take the channel's `prefix` here. Returns `None` when the
+/// input does not start with an alias. Expansions are not expanded again.
+pub fn expand_alias(input: &str, aliases: &[(String, String)], prefix: char) -> Option<String> {
+    let body = input.trim().strip_prefix(prefix)?.trim_start();
+    let (name, rest) = body.split_once(char::is_whitespace).unwrap_or((body, ""));
+    let (_, expansion) = aliases.iter().find(|(alias, _)| alias == name)?;
+    let expansion = match expansion.strip_prefix(DEFAULT_PREFIX) {
+        Some(command) => format!("{prefix}{command}"),
+        None => expansion.clone(),
+    };
+    let rest = rest.trim();
+    Some(if rest.is_empty() {
+        expansion
+    } else {
+        format!("{expansion} {rest}")
+    })
+}
+
+/// Drop the escape from prose that starts with a doubled prefix (`,,` or
+/// `//`), leaving other input unchanged.
+pub fn unescape_prefix(input: &str, prefix: char) -> &str {
+    let trimmed = input.trim_start();
+    match trimmed.strip_prefix(prefix) {
+        Some(rest) if rest.starts_with(prefix) => rest,
+        _ => input,
+    }
+}
+
+/// Detect whether a line of input is a command, using the `,` prefix.
+pub fn detect_command(input: &str) -> Option<DetectedCommand> {
+    detect_command_with_prefix(input, DEFAULT_PREFIX)
+}
+
 /// Detect whether a line of input is a command.
 ///
 /// Rules (aligned with bub):
-/// - Lines starting with `,` are commands.
+/// - Lines starting with `prefix` are commands, except that a doubled
+///   prefix escapes prose (see [`unescape_prefix`]).
 /// - If the first token matches a known internal name → `CommandKind::Internal`.
 /// - Otherwise → `CommandKind::Shell` (arbitrary shell execution).
 /// - All other input is routed to the model.
-pub fn detect_command(input: &str) -> Option<DetectedCommand> {
+pub fn detect_command_with_prefix(input: &str, prefix: char) -> Option<DetectedCommand> {
     let stripped = input.trim();
     if stripped.is_empty() {
         return None;
     }
 
-    if !stripped.starts_with(INTERNAL_PREFIX) {
+    let body = stripped.strip_prefix(prefix)?;
+    if body.starts_with(prefix) {
         return None;
     }
 
-    let body = stripped[1..].trim_start();
+    let body = body.trim_start();
     if body.is_empty() {
         return None;
     }
@@ -93,11 +169,11 @@ pub fn detect_command(input: &str) -> Option<DetectedCommand> {
         return None;
     }
 
-    let name = tokens[0].clone();
+    let name = tokens[0].text.clone();
     let is_internal = KNOWN_INTERNAL_COMMANDS.iter().any(|&cmd| cmd == name);
 
     if is_internal {
-        let args = parse_kv_arguments(&tokens[1..]);
+        let args = parse_tokens(&tokens[1..]);
         Some(DetectedCommand {
             kind: CommandKind::Internal,
             name,
@@ -115,59 +191,156 @@ pub fn detect_command(input: &str) -> Option<DetectedCommand> {
     }
 }
 
-/// Parse tool-style arguments from tokens.
+/// Parse tool-style arguments from a command line.
+///
+/// Grammar, token by token:
+/// - `--` → every later token is positional
+/// - `--key=value` → keyword argument
+/// - `--key value` → keyword argument, unless `value` starts with `--`
+/// - `--flag` → boolean flag
+/// - `key=value` → keyword argument
+/// - anything else, and any token that starts with a quote (`"a=b"`,
+///   `'--x'`) → positional
 ///
-/// Supports:
-/// - `--flag` (boolean flag)
-/// - `--key value` or `--key=value`
-/// - `key=value`
-/// - positional arguments
-pub fn parse_kv_arguments(tokens: &[String]) -> ParsedArgs {
+/// Keys may repeat: [`ParsedArgs::get`] returns the last value and
+/// [`ParsedArgs::get_all`] every value.
+pub fn parse_arguments(input: &str) -> ParsedArgs {
+    parse_tokens(&shell_split(input))
+}
+
+fn parse_tokens(tokens: &[Token]) -> ParsedArgs {
     let mut args = ParsedArgs::default();
     let mut idx = 0;
 
     while idx < tokens.len() {
         let token = &tokens[idx];
+        idx += 1;
 
-        if let Some(rest) = token.strip_prefix("--") {
+        if token.literal {
+            args.positional.push(token.text.clone());
+            continue;
+        }
+
+        if token.text == "--" {
+            args.positional
+                .extend(tokens[idx..].iter().map(|t| t.text.clone()));
+            break;
+        }
+
+        if let Some(rest) = token.text.strip_prefix("--") {
             if let Some((key, value)) = rest.split_once('=') {
                 args.kwargs.push((key.to_string(), value.to_string()));
-                idx += 1;
                 continue;
             }
 
-            if idx + 1 < tokens.len() && !tokens[idx + 1].starts_with("--") {
-                args.kwargs
-                    .push((rest.to_string(), tokens[idx + 1].clone()));
-                idx += 2;
+            if let Some(next) = tokens.get(idx)
+                && (next.literal || !next.text.starts_with("--"))
+            {
+                args.kwargs.push((rest.to_string(), next.text.clone()));
+                idx += 1;
                 continue;
             }
 
             args.flags.push(rest.to_string());
-            idx += 1;
             continue;
         }
 
-        #[allow(clippy::collapsible_if)]
-        if let Some((key, value)) = token.split_once('=') {
-            if !key.is_empty() {
-                args.kwargs.push((key.to_string(), value.to_string()));
-                idx += 1;
-                continue;
-            }
+        if let Some((key, value)) = token.text.split_once('=')
+            && !key.is_empty()
+        {
+            args.kwargs.push((key.to_string(), value.to_string()));
+            continue;
         }
 
-        args.positional.push(token.clone());
-        idx += 1;
+        args.positional.push(token.text.clone());
     }
 
     args
 }
 
+/// How a pipeline stage is joined to the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipeOp {
+    /// `a && b`: run `b` only if `a` succeeded.
+    And,
+    /// `a | b`: hand the output of `a` to `b`.
+    Pipe,
+}
+
+impl fmt::Display for PipeOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PipeOp::And => write!(f, "&&"),
+            PipeOp::Pipe => write!(f, "|"),
+        }
+    }
+}
+
+/// Split a command line into stages on `&&` and `|` outside quotes.
+///
+/// Each stage comes with the operator joining it to the previous one
+/// (`None` for the first). `||` is not a separator and stays in the text.
+pub fn split_pipeline(input: &str) -> Vec<(Option<PipeOp>, String)> {
+    let mut stages = Vec::new();
+    let mut current = String::new();
+    let mut op = None;
+    let mut in_single_quote = false;
+    let mut in_double_quote = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        let quoted = in_single_quote || in_double_quote;
+        match ch {
+            '\\' if !in_single_quote => {
+                current.push(ch);
+                if let Some(next) = chars.next() {
+                    current.push(next);
+                }
+                continue;
+            }
+            '\'' if !in_double_quote => in_single_quote = !in_single_quote,
+            '"' if !in_single_quote => in_double_quote = !in_double_quote,
+            '&' if !quoted && chars.peek() == Some(&'&') => {
+                chars.next();
+                stages.push((op, std::mem::take(&mut current).trim().to_string()));
+                op = Some(PipeOp::And);
+                continue;
+            }
+            '|' if !quoted => {
+                if chars.peek() == Some(&'|') {
+                    chars.next();
+                    current.push_str("||");
+                } else {
+                    stages.push((op, std::mem::take(&mut current).trim().to_string()));
+                    op = Some(PipeOp::Pipe);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        current.push(ch);
+    }
+    stages.push((op, current.trim().to_string()));
+    stages
+}
+
+/// A word of a command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    text: String,
+    /// Starts with a quote or escape, so it is never a flag or `key=value`.
+    literal: bool,
+}
+
 /// Simple shell-like tokenizer that handles basic quoting.
-fn shell_split(input: &str) -> Vec<String> {
+///
+/// Quotes group words and are removed; `""` yields an empty token.
+fn shell_split(input: &str) -> Vec<Token> {
     let mut tokens = Vec::new();
     let mut current = String::new();
+    // Whether a token has started (it may still be empty: `""`).
+    let mut started = false;
+    let mut literal = false;
     let mut in_single_quote = false;
     let mut in_double_quote = false;
     let mut escape_next = false;
@@ -179,6 +352,16 @@ fn shell_split(input: &str) -> Vec<String> {
             continue;
         }
 
+        let quote_or_escape = (ch == '\\' && !in_single_quote)
+            || (ch == '\'' && !in_double_quote)
+            || (ch == '"' && !in_single_quote);
+        if quote_or_escape && !started {
+            literal = true;
+        }
+        if quote_or_escape || !ch.is_whitespace() {
+            started = true;
+        }
+
         if ch == '\\' && !in_single_quote {
             escape_next = true;
             continue;
@@ -195,8 +378,13 @@ fn shell_split(input: &str) -> Vec<String> {
         }
 
         if ch.is_whitespace() && !in_single_quote && !in_double_quote {
-            if !current.is_empty() {
-                tokens.push(std::mem::take(&mut current));
+            if started {
+                tokens.push(Token {
+                    text: std::mem::take(&mut current),
+                    literal,
+                });
+                started = false;
+                literal = false;
             }
             continue;
         }
@@ -204,8 +392,11 @@ fn shell_split(input: &str) -> Vec<String> {
         current.push(ch);
     }
 
-    if !current.is_empty() {
-        tokens.push(current);
+    if started {
+        tokens.push(Token {
+            text: current,
+            literal,
+        });
     }
 
     tokens
@@ -289,24 +480,124 @@ mod tests {
         assert_eq!(cmd.args.positional, vec!["friendly-python"]);
     }
 
+    fn texts(input: &str) -> Vec<String> {
+        shell_split(input).into_iter().map(|t| t.text).collect()
+    }
+
     #[test]
     fn shell_split_handles_quotes() {
-        let tokens = shell_split(r#"hello "world foo" bar"#);
+        let tokens = texts(r#"hello "world foo" bar"#);
         assert_eq!(tokens, vec!["hello", "world foo", "bar"]);
     }
 
     #[test]
     fn shell_split_handles_single_quotes() {
-        let tokens = shell_split("hello 'world foo' bar");
+        let tokens = texts("hello 'world foo' bar");
         assert_eq!(tokens, vec!["hello", "world foo", "bar"]);
     }
 
     #[test]
     fn shell_split_handles_escapes() {
-        let tokens = shell_split(r"hello\ world bar");
+        let tokens = texts(r"hello\ world bar");
         assert_eq!(tokens, vec!["hello world", "bar"]);
     }
 
+    #[test]
+    fn quoted_search_query_stays_one_positional() {
+        let cmd = detect_command(r#",tape.search "error: failed to""#).unwrap();
+        assert_eq!(cmd.args.positional, vec!["error: failed to"]);
+        assert!(cmd.args.kwargs.is_empty());
+    }
+
+    #[test]
+    fn quoted_tokens_are_never_flags_or_kwargs() {
+        let args = parse_arguments(r#""a=b" '--force' \--x "" key="c d""#);
+        assert_eq!(args.positional, vec!["a=b", "--force", "--x", ""]);
+        assert!(args.flags.is_empty());
+        assert_eq!(args.get("key"), Some("c d"));
+    }
+
+    #[test]
+    fn flag_values_repeat_and_double_dash_ends_options() {
+        let args = parse_arguments("--tag=a --tag b --verbose -- --not-a-flag x=1");
+        assert_eq!(args.get_all("tag"), vec!["a", "b"]);
+        assert_eq!(args.get("tag"), Some("b"));
+        assert!(args.has_flag("verbose"));
+        assert_eq!(args.positional, vec!["--not-a-flag", "x=1"]);
+
+        let args = parse_arguments(r#"--name "--literal" --dry-run --limit=5"#);
+        assert_eq!(args.get("name"), Some("--literal"));
+        assert!(args.has_flag("dry-run"));
+        assert_eq!(args.get("limit"), Some("5"));
+        assert!(args.get_all("missing").is_empty());
+    }
+
+    #[test]
+    fn split_pipeline_respects_quotes_and_or() {
+        let stages =
+            split_pipeline(r#"git diff | grep "a|b" && echo 'x && y' || true | summarize"#);
+        assert_eq!(
+            stages,
+            vec![
+                (None, "git diff".to_string()),
+                (Some(PipeOp::Pipe), r#"grep "a|b""#.to_string()),
+                (Some(PipeOp::And), "echo 'x && y' || true".to_string()),
+                (Some(PipeOp::Pipe), "summarize".to_string()),
+            ]
+        );
+        assert_eq!(
+            split_pipeline(r"echo a\|b"),
+            vec![(None, r"echo a\|b".to_string())]
+        );
+        assert_eq!(split_pipeline("cargo test 2>&1").len(), 1);
+    }
+
+    #[test]
+    fn aliases_expand_with_trailing_arguments() {
+        let aliases = vec![
+            ("gs".to_string(), ",git status".to_string()),
+            ("deploy".to_string(), "Use the deploy skill for".to_string()),
+        ];
+        assert_eq!(
+            expand_alias(",gs -s | summarize", &aliases, ',').as_deref(),
+            Some(",git status -s | summarize")
+        );
+        assert_eq!(
+            expand_alias(", gs", &aliases, ',').as_deref(),
+            Some(",git status")
+        );
+        assert_eq!(
+            expand_alias(",deploy staging", &aliases, ',').as_deref(),
+            Some("Use the deploy skill for staging")
+        );
+        assert_eq!(
+            expand_alias("/gs", &aliases, '/').as_deref(),
+            Some("/git status")
+        );
+        assert!(expand_alias(",gsx", &aliases, ',').is_none());
+        assert!(expand_alias("gs", &aliases, ',').is_none());
+        assert!(expand_alias(",gs", &aliases, '/').is_none());
+    }
+
+    #[test]
+    fn other_prefixes_and_doubled_prefix_escape() {
+        let cmd = detect_command_with_prefix("/tape.info", '/').unwrap();
+        assert_eq!(cmd.kind, CommandKind::Internal);
+        assert_eq!(cmd.name, "tape.info");
+        assert_eq!(
+            detect_command_with_prefix("!git status", '!').unwrap().raw,
+            "git status"
+        );
+        assert!(detect_command_with_prefix(",help", '/').is_none());
+        assert!(detect_command("/help").is_none());
+
+        assert!(detect_command(",,well, that worked").is_none());
+        assert_eq!(unescape_prefix(",,well", ','), ",well");
+        assert!(detect_command_with_prefix("//etc/hosts is empty", '/').is_none());
+        assert_eq!(unescape_prefix("//etc/hosts", '/'), "/etc/hosts");
+        assert_eq!(unescape_prefix(",help", ','), ",help");
+    }
+
     #[test]
     fn git_status_detected_as_shell() {
         let cmd = detect_command(",git status").unwrap();
@@ -0,0 +1,263 @@
+//! `,ask-all`: one prompt, several models, side by side.
+//!
+//! The prompt goes to every model in `CONSENSUS_MODELS` at once, with the
+//! session's system prompt and history but no tools. The answers are shown
+//! one after another under the model's name; when a judge is configured
+//! (`CONSENSUS_JUDGE`) it reads them all and writes a merged answer that
+//! notes where they disagree.
+//!
+//! ```text
+//! ,ask-all Is this migration safe to run online?
+//! ,ask-all --models=openai:gpt-4o,mistral:mistral-large-latest --no-judge Which is faster?
+//! ```
+
+use std::path::Path;
+use std::time::Instant;
+
+use futures_util::future::join_all;
+use serde::Serialize;
+
+use crate::core::config::AppConfig;
+use crate::core::model_runner::ModelRunner;
+use crate::llm::api_types::{Message, Usage};
+use crate::tape::store::TapeStore;
+use crate::tools::registry::ToolContext;
+
+/// Tape event recording an `,ask-all` run ([`Consensus`]).
+pub const CONSENSUS_EVENT: &str = "consensus";
+
+const JUDGE_PROMPT: &str = "You are given several answers to the same question from different models. \
+Write one final answer: keep what they agree on, resolve disagreements by reasoning about which answer is right, \
+and say briefly where they differed and why you sided as you did.";
+
+/// A parsed `,ask-all` command line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AskAll {
+    pub prompt: String,
+    /// `--models=a,b`, replacing `CONSENSUS_MODELS`.
+    pub models: Option<Vec<String>>,
+    /// `--judge=<model>`, or `Some(None)` for `--no-judge`.
+    pub judge: Option<Option<String>>,
+}
+
+impl AskAll {
+    /// Parse the text after `,ask-all`. Options come first; everything after
+    /// them is the prompt, verbatim.
+    pub fn parse(args: &str) -> Result<Self, String> {
+        let mut parsed = Self {
+            prompt: String::new(),
+            models: None,
+            judge: None,
+        };
+        let mut rest = args.trim_start();
+        loop {
+            let (token, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if let Some(models) = token.strip_prefix("--models=") {
+                let models: Vec<String> = models
+                    .split(',')
+                    .filter(|m| !m.is_empty())
+                    .map(String::from)
+                    .collect();
+                for model in &models {
+                    crate::llm::models::validate_model_id(model)
+                        .map_err(|e| format!("Invalid model '{model}': {e}"))?;
+                }
+                parsed.models = Some(models);
+            } else if let Some(judge) = token.strip_prefix("--judge=") {
+                crate::llm::models::validate_model_id(judge)
+                    .map_err(|e| format!("Invalid judge model '{judge}': {e}"))?;
+                parsed.judge = Some(Some(judge.to_string()));
+            } else if token == "--no-judge" {
+                parsed.judge = Some(None);
+            } else {
+                break;
+            }
+            rest = tail.trim_start();
+        }
+        parsed.prompt = rest.trim().to_string();
+        if parsed.prompt.is_empty() {
+            return Err(
+                "Usage: ,ask-all [--models=p:a,p:b] [--judge=p:m|--no-judge] <prompt>".to_string(),
+            );
+        }
+        Ok(parsed)
+    }
+}
+
+/// The models asked and the judge that merges their answers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Panel {
+    pub models: Vec<String>,
+    pub judge: Option<String>,
+}
+
+/// One model's answer.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ModelAnswer {
+    pub model: String,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    pub elapsed_ms: u64,
+}
+
+/// The answers of an `,ask-all` run and the judge's merge, if any.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Consensus {
+    pub prompt: String,
+    pub answers: Vec<ModelAnswer>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merged: Option<ModelAnswer>,
+}
+
+impl Consensus {
+    /// Answers under a heading per model, then the merged answer.
+    pub fn format(&self) -> String {
+        let mut sections: Vec<String> = self
+            .answers
+            .iter()
+            .map(|answer| section(&answer.model, answer))
+            .collect();
+        if let Some(merged) = &self.merged {
+            sections.push(section(&format!("Merged by {}", merged.model), merged));
+        }
+        sections.join("\n\n")
+    }
+}
+
+fn section(title: &str, answer: &ModelAnswer) -> String {
+    let secs = answer.elapsed_ms as f64 / 1000.0;
+    let body = match &answer.error {
+        Some(error) => format!("Error: {error}"),
+        None => answer.text.trim().to_string(),
+    };
+    format!("### {title} ({secs:.1}s)\n{body}")
+}
+
+/// Send `messages` to each model of `panel` concurrently, then to its judge
+/// with the answers when at least two of them succeeded.
+pub async fn ask_all(
+    config: &AppConfig,
+    workspace: &Path,
+    prompt: &str,
+    messages: &[Message],
+    panel: &Panel,
+    tape: &TapeStore,
+    tool_ctx: &ToolContext,
+) -> Consensus {
+    let answers = join_all(
+        panel
+            .models
+            .iter()
+            .map(|model| ask_one(config, workspace, model, messages.to_vec(), tape, tool_ctx)),
+    )
+    .await;
+
+    let succeeded: Vec<&ModelAnswer> = answers.iter().filter(|a| a.error.is_none()).collect();
+    let merged = match &panel.judge {
+        Some(judge) if succeeded.len() >= 2 => {
+            let mut request = vec![Message::system(JUDGE_PROMPT)];
+            request.push(Message::user(judge_input(prompt, &succeeded)));
+            Some(ask_one(config, workspace, judge, request, tape, tool_ctx).await)
+        }
+        _ => None,
+    };
+    Consensus {
+        prompt: prompt.to_string(),
+        answers,
+        merged,
+    }
+}
+
+async fn ask_one(
+    config: &AppConfig,
+    workspace: &Path,
+    model: &str,
+    mut messages: Vec<Message>,
+    tape: &TapeStore,
+    tool_ctx: &ToolContext,
+) -> ModelAnswer {
+    let mut config = config.clone();
+    config.model = model.to_string();
+    let started = Instant::now();
+    let turn = ModelRunner::new(&config, workspace)
+        .run_turn(&mut messages, None, tape, tool_ctx)
+        .await;
+    ModelAnswer {
+        model: model.to_string(),
+        text: turn.assistant_text,
+        error: turn.error,
+        usage: turn.usage,
+        elapsed_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+fn judge_input(prompt: &str, answers: &[&ModelAnswer]) -> String {
+    let mut text = format!("Question:\n{prompt}");
+    for (i, answer) in answers.iter().enumerate() {
+        text.push_str(&format!(
+            "\n\nAnswer {} ({}):\n{}",
+            i + 1,
+            answer.model,
+            answer.text.trim()
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_options_before_the_prompt() {
+        let parsed =
+            AskAll::parse("--models=openai:gpt-4o,anthropic:claude-x --no-judge Is it --safe?")
+                .unwrap();
+        assert_eq!(parsed.prompt, "Is it --safe?");
+        assert_eq!(
+            parsed.models.as_deref(),
+            Some(
+                &[
+                    "openai:gpt-4o".to_string(),
+                    "anthropic:claude-x".to_string()
+                ][..]
+            )
+        );
+        assert_eq!(parsed.judge, Some(None));
+
+        let parsed = AskAll::parse("  why?").unwrap();
+        assert_eq!((parsed.models, parsed.judge), (None, None));
+        assert!(AskAll::parse("--judge=o3 hi").is_err());
+        assert!(
+            AskAll::parse("--no-judge")
+                .unwrap_err()
+                .starts_with("Usage")
+        );
+    }
+
+    #[test]
+    fn formats_answers_and_errors() {
+        let answer = |model: &str, text: &str, error: Option<&str>| ModelAnswer {
+            model: model.to_string(),
+            text: text.to_string(),
+            error: error.map(String::from),
+            usage: None,
+            elapsed_ms: 1200,
+        };
+        let consensus = Consensus {
+            prompt: "q".to_string(),
+            answers: vec![
+                answer("openai:gpt-4o", "Yes.\n", None),
+                answer("mistral:mistral-large-latest", "", Some("timeout")),
+            ],
+            merged: Some(answer("openai:o3", "Yes, mostly.", None)),
+        };
+        assert_eq!(
+            consensus.format(),
+            "### openai:gpt-4o (1.2s)\nYes.\n\n### mistral:mistral-large-latest (1.2s)\nError: timeout\n\n### Merged by openai:o3 (1.2s)\nYes, mostly."
+        );
+    }
+}
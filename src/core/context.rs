@@ -1,6 +1,8 @@
-use crate::llm::api_types::Message;
-use crate::tape::store::TapeStore;
-use std::path::Path;
+use chrono::{DateTime, Utc};
+
+use crate::llm::api_types::{Message, ToolDefinition};
+use crate::tape::store::{TapeEntry, TapeStore};
+use std::path::{Path, PathBuf};
 
 /// Build the system prompt from available sources.
 ///
@@ -11,13 +13,28 @@ use std::path::Path;
 /// 4. Context / DateTime
 /// 5. Tools Section
 pub fn build_system_prompt(config_prompt: Option<&str>, workspace: &Path) -> String {
-    build_system_prompt_with_tools(config_prompt, workspace, None)
+    build_system_prompt_with_tools(config_prompt, workspace, None, &TurnContext::default())
+}
+
+/// Per-turn details for the system prompt.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TurnContext<'a> {
+    /// Instruction from [`crate::core::language::reply_instruction`], placed
+    /// last so it outweighs the language of tool results.
+    pub reply_language: Option<&'a str>,
+    /// Zone the current time is given in (`TIMEZONE`); the system zone
+    /// when unset.
+    pub timezone: Option<chrono_tz::Tz>,
+    /// When the user's previous message arrived, so the model can tell how
+    /// much time has passed.
+    pub previous_message_at: Option<DateTime<Utc>>,
 }
 
 pub fn build_system_prompt_with_tools(
     config_prompt: Option<&str>,
     workspace: &Path,
     tools_contract_override: Option<&str>,
+    turn: &TurnContext<'_>,
 ) -> String {
     let mut sections: Vec<String> = Vec::new();
 
@@ -46,8 +63,16 @@ pub fn build_system_prompt_with_tools(
         }
     }
 
+    // 3b. Cross-tool instruction files (AGENTS.md, CLAUDE.md), outermost first
+    for file in discover_instruction_files(workspace) {
+        sections.push(format!(
+            "<project_instructions path=\"{}\">\n{}\n</project_instructions>",
+            file.path.display(),
+            file.content
+        ));
+    }
+
     // 4. Runtime & Workspace Section
-    let workspace_str = workspace.to_string_lossy();
     let runtime_contract = format!(
         "<runtime_contract>\n\
         1) Use tool calls for all actions (file ops, shell, web, tape, skills).\n\
@@ -56,22 +81,20 @@ pub fn build_system_prompt_with_tools(
         4) When enough evidence is collected, return a plain natural language answer.\n\
         </runtime_contract>\n\
         <workspace_context>\n\
-        Current workspace: {}\n\
+        {}\n\
         </workspace_context>",
-        workspace_str
+        workspace_context_lines(workspace).join("\n")
     );
     sections.push(runtime_contract);
 
     // 5. Context / DateTime
-    let datetime = chrono::Local::now()
-        .format("%Y-%m-%d %H:%M:%S %Z")
-        .to_string();
-    let context_section = format!(
-        "<context>\n\
-        Current Date/Time: {}\n\
-        </context>",
-        datetime
-    );
+    let mut context_lines = time_context_lines(Utc::now(), turn);
+    context_lines.push(format!(
+        "OS: {} ({})",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    ));
+    let context_section = format!("<context>\n{}\n</context>", context_lines.join("\n"));
     sections.push(context_section);
 
     // 6. Tools Section
@@ -82,9 +105,258 @@ pub fn build_system_prompt_with_tools(
         .unwrap_or_else(crate::tools::registry::builtin_tools_contract_block);
     sections.push(tools_contract);
 
+    // 7. Reply Language
+    if let Some(instruction) = turn.reply_language {
+        sections.push(format!(
+            "<reply_language>\n{instruction}\n</reply_language>"
+        ));
+    }
+
     sections.join("\n\n")
 }
 
+/// Current date and time in the turn's zone, and how long ago the user's
+/// previous message was.
+fn time_context_lines(now: DateTime<Utc>, turn: &TurnContext<'_>) -> Vec<String> {
+    const FORMAT: &str = "%Y-%m-%d %H:%M:%S %A (UTC%:z)";
+    let (datetime, zone) = match turn.timezone {
+        Some(tz) => (
+            now.with_timezone(&tz).format(FORMAT).to_string(),
+            Some(tz.name().to_string()),
+        ),
+        None => (
+            now.with_timezone(&chrono::Local).format(FORMAT).to_string(),
+            std::env::var("TZ").ok().filter(|tz| !tz.is_empty()),
+        ),
+    };
+    let mut lines = vec![format!("Current Date/Time: {datetime}")];
+    if let Some(zone) = zone {
+        lines.push(format!("Timezone: {zone}"));
+    }
+    if let Some(previous) = turn.previous_message_at {
+        let secs = (now - previous).num_seconds().max(0) as u64;
+        lines.push(format!(
+            "Time since the user's previous message: {}",
+            format_elapsed(secs)
+        ));
+    }
+    lines
+}
+
+/// `42s`, `5m`, `3h 12m` or `2d 4h`.
+fn format_elapsed(secs: u64) -> String {
+    let (days, hours, minutes) = (secs / 86_400, secs / 3_600 % 24, secs / 60 % 60);
+    match (days, hours, minutes) {
+        (0, 0, 0) => format!("{secs}s"),
+        (0, 0, m) => format!("{m}m"),
+        (0, h, m) => format!("{h}h {m}m"),
+        (d, h, _) => format!("{d}d {h}h"),
+    }
+}
+
+/// Comma-separated instruction file names to look for (default
+/// `AGENTS.md,CLAUDE.md`; empty or `off` disables discovery).
+const INSTRUCTION_FILES_ENV_KEY: &str = "CRABCLAW_INSTRUCTION_FILES";
+const DEFAULT_INSTRUCTION_FILES: &[&str] = &["AGENTS.md", "CLAUDE.md"];
+/// Per-file cap on included instructions.
+const MAX_INSTRUCTION_FILE_BYTES: usize = 32 * 1024;
+/// Cap on all included instruction files together.
+const MAX_INSTRUCTION_TOTAL_BYTES: usize = 64 * 1024;
+
+/// An instruction file found in the workspace or one of its parents.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InstructionFile {
+    pub path: PathBuf,
+    pub content: String,
+}
+
+/// Discover `AGENTS.md` / `CLAUDE.md` files from the repository root (the
+/// nearest parent with `.git`, else the filesystem root) down to `workspace`.
+///
+/// Files are returned outermost first so more specific instructions come
+/// later in the prompt. Symlinked or identical files are included once, and
+/// content is capped per file and in total.
+pub fn discover_instruction_files(workspace: &Path) -> Vec<InstructionFile> {
+    discover_instruction_files_named(workspace, &instruction_file_names())
+}
+
+/// Every path an instruction file may be read from, found or not, without
+/// reading any of them.
+pub fn instruction_file_candidates(workspace: &Path) -> Vec<PathBuf> {
+    let names = instruction_file_names();
+    instruction_dirs(workspace)
+        .iter()
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .collect()
+}
+
+/// Instruction file names: `INSTRUCTION_FILES`, else the defaults.
+fn instruction_file_names() -> Vec<String> {
+    match std::env::var(INSTRUCTION_FILES_ENV_KEY) {
+        Ok(value) if value.trim().eq_ignore_ascii_case("off") => Vec::new(),
+        Ok(value) => value
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(ToString::to_string)
+            .collect(),
+        Err(_) => DEFAULT_INSTRUCTION_FILES
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+    }
+}
+
+/// Directories searched for instruction files, outermost first.
+fn instruction_dirs(workspace: &Path) -> Vec<PathBuf> {
+    let workspace = workspace
+        .canonicalize()
+        .unwrap_or_else(|_| workspace.to_path_buf());
+    let mut dirs = Vec::new();
+    for dir in workspace.ancestors() {
+        dirs.push(dir.to_path_buf());
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    dirs.reverse();
+    dirs
+}
+
+fn discover_instruction_files_named(workspace: &Path, names: &[String]) -> Vec<InstructionFile> {
+    if names.is_empty() {
+        return Vec::new();
+    }
+    let mut seen_paths = std::collections::HashSet::new();
+    let mut seen_content = std::collections::HashSet::new();
+    let mut remaining = MAX_INSTRUCTION_TOTAL_BYTES;
+    let mut files = Vec::new();
+    for dir in instruction_dirs(workspace) {
+        for name in names {
+            let path = dir.join(name);
+            let Ok(real) = path.canonicalize() else {
+                continue;
+            };
+            if !seen_paths.insert(real) {
+                continue;
+            }
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let content = content.trim();
+            if content.is_empty() || !seen_content.insert(content.to_string()) {
+                continue;
+            }
+            if remaining == 0 {
+                tracing::warn!(path = %path.display(), "context.instructions.over_budget");
+                continue;
+            }
+            let content = truncate_to_bytes(content, MAX_INSTRUCTION_FILE_BYTES.min(remaining));
+            remaining = remaining.saturating_sub(content.len());
+            files.push(InstructionFile { path, content });
+        }
+    }
+    files
+}
+
+/// Cut `text` to at most `max` bytes on a char boundary, marking the cut.
+fn truncate_to_bytes(text: &str, max: usize) -> String {
+    if text.len() <= max {
+        return text.to_string();
+    }
+    let mut end = max;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n[... truncated]", &text[..end])
+}
+
+/// Marker files that identify a project type, in reporting order.
+const PROJECT_MARKERS: &[(&str, &str)] = &[
+    ("Cargo.toml", "Rust (Cargo)"),
+    ("package.json", "JavaScript/TypeScript (package.json)"),
+    ("pyproject.toml", "Python (pyproject.toml)"),
+    ("requirements.txt", "Python (requirements.txt)"),
+    ("go.mod", "Go (go.mod)"),
+    ("pom.xml", "Java (Maven)"),
+    ("build.gradle", "JVM (Gradle)"),
+    ("build.gradle.kts", "JVM (Gradle)"),
+    ("Gemfile", "Ruby (Bundler)"),
+    ("composer.json", "PHP (Composer)"),
+    ("CMakeLists.txt", "C/C++ (CMake)"),
+];
+
+/// Lines of the `<workspace_context>` block: path, project type and git
+/// branch. Recomputed every turn so the model sees the current branch.
+fn workspace_context_lines(workspace: &Path) -> Vec<String> {
+    let mut lines = vec![format!("Current workspace: {}", workspace.display())];
+    let mut kinds: Vec<&str> = PROJECT_MARKERS
+        .iter()
+        .filter(|(file, _)| workspace.join(file).is_file())
+        .map(|(_, kind)| *kind)
+        .collect();
+    kinds.dedup();
+    if !kinds.is_empty() {
+        lines.push(format!("Project type: {}", kinds.join(", ")));
+    }
+    if let Some(git) = git_summary(workspace) {
+        lines.push(format!("Git: {git}"));
+    }
+    lines
+}
+
+/// One-line git summary for `workspace`, or `None` outside a repository.
+///
+/// Only the branch is read: anything that compares the work tree with the
+/// index (`git status`) runs the clean filters named in the repository's
+/// `.git/config` and `.gitattributes`, which the model can write, and this
+/// runs every turn without the sandbox.
+fn git_summary(workspace: &Path) -> Option<String> {
+    let output = crate::core::shell::git_command(workspace)
+        .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .output()
+        .ok()?;
+    match output.status.code() {
+        Some(0) => Some(format!(
+            "branch {}",
+            String::from_utf8_lossy(&output.stdout).trim()
+        )),
+        // `--quiet` exits with 1 when HEAD is not a branch.
+        Some(1) => Some("detached HEAD".to_string()),
+        _ => None,
+    }
+}
+
+/// Fingerprint of the workspace-provided prompt context.
+///
+/// Hashes the path, modification time and size of every file that feeds the
+/// prompt or the tool set: `.agent/system-prompt.md`, instruction files,
+/// each skill's `SKILL.md`, `.agent/tools.json` and the persona files. Only
+/// metadata is read, so it is cheap enough to compare before every request.
+pub fn workspace_context_fingerprint(workspace: &Path) -> String {
+    use sha2::{Digest, Sha256};
+
+    let mut files = vec![
+        workspace.join(".agent/system-prompt.md"),
+        workspace.join(crate::tools::external::MANIFEST_FILE),
+    ];
+    files.extend(instruction_file_candidates(workspace));
+    files.extend(crate::tools::skills::skill_files(workspace));
+    files.extend(crate::core::persona::persona_files(workspace));
+
+    let mut hasher = Sha256::new();
+    for path in files {
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            continue;
+        };
+        hasher.update(path.to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        hasher.update(format!("{:?} {}", metadata.modified().ok(), metadata.len()).as_bytes());
+        hasher.update(b"\0");
+    }
+    format!("{:x}", hasher.finalize())
+}
+
 /// Build a list of messages from tape entries for multi-turn conversation.
 ///
 /// Aligned with bub's `tape/context.py::_select_messages`:
@@ -92,6 +364,7 @@ pub fn build_system_prompt_with_tools(
 /// - Extracts entries with kind "message"
 /// - Preserves role and content from payload
 /// - Optionally prepends a system prompt
+/// - Adds pinned notes (see [`crate::tools::pins`]) after the system prompt
 pub fn build_messages(
     tape: &TapeStore,
     system_prompt: Option<&str>,
@@ -105,11 +378,17 @@ pub fn build_messages(
             messages.push(Message::system(trimmed));
         }
     }
+    // Pins come from the whole tape, so anchors do not drop them.
+    if let Some(pinned) = crate::tools::pins::pins_message(&crate::tools::pins::active_pins(tape)) {
+        messages.push(Message::system(pinned));
+    }
 
     // Use entries since last anchor for context truncation
+    let entries = tape.entries_since_last_anchor();
+    let superseded = superseded_ranges(entries);
     let mut tape_messages = Vec::new();
-    for entry in tape.entries_since_last_anchor() {
-        if entry.kind != "message" {
+    for entry in entries {
+        if entry.kind != "message" || superseded.iter().any(|range| range.contains(&entry.id)) {
             continue;
         }
 
@@ -150,11 +429,316 @@ pub fn build_messages(
     messages
 }
 
+/// The latest user message that has not been superseded, with its entry ID.
+pub fn last_user_turn(tape: &TapeStore) -> Option<(u64, String)> {
+    let entries = tape.entries();
+    let superseded = superseded_ranges(entries);
+    entries
+        .iter()
+        .rev()
+        .filter(|e| e.kind == "message" && e.payload["role"] == "user")
+        .filter(|e| !superseded.iter().any(|r| r.contains(&e.id)))
+        .find_map(|e| {
+            let content = e.payload["content"].as_str()?;
+            (!content.is_empty()).then(|| (e.id, content.to_string()))
+        })
+}
+
+/// When the user's message before `current` arrived.
+///
+/// `current` is skipped when it is already the latest user message in the
+/// tape, as it is once a turn has recorded it.
+pub fn previous_user_message_at(tape: &TapeStore, current: &str) -> Option<DateTime<Utc>> {
+    let mut user_messages = tape
+        .entries()
+        .iter()
+        .rev()
+        .filter(|e| e.kind == "message" && e.payload["role"] == "user")
+        .peekable();
+    if user_messages
+        .peek()
+        .is_some_and(|e| e.payload["content"] == current)
+    {
+        user_messages.next();
+    }
+    let entry = user_messages.next()?;
+    DateTime::parse_from_rfc3339(&entry.timestamp)
+        .ok()
+        .map(|t| t.with_timezone(&Utc))
+}
+
+/// Entry ID ranges of turns replaced by later ones (`turn.superseded`).
+fn superseded_ranges(entries: &[TapeEntry]) -> Vec<std::ops::RangeInclusive<u64>> {
+    entries
+        .iter()
+        .filter(|e| e.kind == crate::core::agent_loop::TURN_SUPERSEDED_EVENT)
+        .filter_map(|e| Some(e.payload["from"].as_u64()?..=e.payload["to"].as_u64()?))
+        .collect()
+}
+
+/// Drop the oldest conversation messages until the estimated prompt size
+/// fits `budget_tokens` (see [`crate::llm::models::ModelCapabilities::prompt_budget`]).
+///
+/// Leading system messages are always kept, as is the latest message.
+pub fn fit_to_token_budget(messages: Vec<Message>, budget_tokens: usize) -> Vec<Message> {
+    use crate::llm::models::estimate_tokens;
+
+    let total: usize = messages.iter().map(|m| estimate_tokens(&m.content)).sum();
+    if total <= budget_tokens {
+        return messages;
+    }
+    let system_len = messages.iter().take_while(|m| m.role == "system").count();
+    let mut rest = messages;
+    let mut kept = rest.split_off(system_len);
+    let mut size = total;
+    let mut dropped = 0;
+    while kept.len() > 1 && size > budget_tokens {
+        size -= estimate_tokens(&kept.remove(0).content);
+        dropped += 1;
+    }
+    if dropped > 0 {
+        tracing::info!(dropped, budget_tokens, "context.trimmed_to_token_budget");
+        const NOTICE: &str =
+            "Older messages in this session have been truncated to fit the context window.";
+        if !rest.iter().any(|m| m.content == NOTICE) {
+            rest.push(Message::system(NOTICE));
+        }
+    }
+    rest.extend(kept);
+    rest
+}
+
+/// Render the request that would be sent next turn, for `,prompt.show`.
+///
+/// Leading system messages are printed as the system prompt; the rest are
+/// listed with their roles, and tools by name only.
+pub fn format_prompt_preview(messages: &[Message], tools: Option<&[ToolDefinition]>) -> String {
+    let system_len = messages.iter().take_while(|m| m.role == "system").count();
+    let (system, rest) = messages.split_at(system_len);
+    let mut out = String::from("=== System prompt ===\n");
+    for message in system {
+        out.push_str(&message.content);
+        out.push('\n');
+    }
+    out.push_str(&format!("\n=== Messages ({}) ===\n", rest.len()));
+    for message in rest {
+        match (&message.tool_calls, &message.tool_call_id) {
+            (Some(calls), _) => {
+                for call in calls {
+                    out.push_str(&format!(
+                        "[assistant → {}] {}\n",
+                        call.function.name, call.function.arguments
+                    ));
+                }
+            }
+            (None, Some(id)) => out.push_str(&format!("[tool {id}] {}\n", message.content)),
+            (None, None) => out.push_str(&format!("[{}] {}\n", message.role, message.content)),
+        }
+    }
+    let names: Vec<&str> = tools
+        .unwrap_or_default()
+        .iter()
+        .map(|t| t.function.name.as_str())
+        .collect();
+    out.push_str(&format!(
+        "\n=== Tools ({}) ===\n{}",
+        names.len(),
+        names.join(", ")
+    ));
+    out
+}
+
+/// Estimated token counts of the next request, for `,prompt.tokens`.
+pub fn format_prompt_tokens(
+    messages: &[Message],
+    tools: Option<&[ToolDefinition]>,
+    budget: usize,
+) -> String {
+    use crate::llm::models::estimate_tokens;
+
+    let system_len = messages.iter().take_while(|m| m.role == "system").count();
+    let (system, rest) = messages.split_at(system_len);
+    let count = |ms: &[Message]| -> usize {
+        ms.iter()
+            .map(|m| {
+                estimate_tokens(&m.content)
+                    + m.tool_calls
+                        .iter()
+                        .flatten()
+                        .map(|c| estimate_tokens(&c.function.arguments))
+                        .sum::<usize>()
+            })
+            .sum()
+    };
+    let system_tokens = count(system);
+    let message_tokens = count(rest);
+    let tools = tools.unwrap_or_default();
+    let tool_tokens = serde_json::to_string(tools).map_or(0, |json| estimate_tokens(&json));
+    let total = system_tokens + message_tokens + tool_tokens;
+    format!(
+        "~{total} tokens (prompt budget {budget})\n  \
+         system prompt: ~{system_tokens}\n  \
+         messages ({}): ~{message_tokens}\n  \
+         tools ({}): ~{tool_tokens}",
+        rest.len(),
+        tools.len()
+    )
+}
+
+/// Messages kept in the tape as conversation (`message` entries with
+/// content, not superseded), with their roles.
+fn tape_messages(entries: &[TapeEntry]) -> Vec<(&str, &str)> {
+    let superseded = superseded_ranges(entries);
+    entries
+        .iter()
+        .filter(|e| e.kind == "message" && !superseded.iter().any(|r| r.contains(&e.id)))
+        .filter_map(|e| {
+            let content = e.payload.get("content")?.as_str()?;
+            let role = e
+                .payload
+                .get("role")
+                .and_then(|r| r.as_str())
+                .unwrap_or("user");
+            (!content.is_empty()).then_some((role, content))
+        })
+        .collect()
+}
+
+/// What the next request is made of, for `,context`: token estimates per
+/// section and the session messages that will not be sent.
+///
+/// `messages` and `tools` are the prepared request; `tools_block` is the
+/// part of the system prompt describing tools, which is reported separately
+/// from the rest of it, as is any `<skills>` block.
+pub fn format_context_report(
+    messages: &[Message],
+    tools: Option<&[ToolDefinition]>,
+    tools_block: &str,
+    tape: &TapeStore,
+    budget: usize,
+) -> String {
+    use crate::llm::models::estimate_tokens;
+
+    let system_len = messages.iter().take_while(|m| m.role == "system").count();
+    let (system, rest) = messages.split_at(system_len);
+    let system_tokens: usize = system.iter().map(|m| estimate_tokens(&m.content)).sum();
+    let tools_block_tokens = estimate_tokens(tools_block);
+    let skills_tokens: usize = system
+        .iter()
+        .filter_map(|m| {
+            let start = m.content.find("<skills>")?;
+            let end = m.content[start..].find("</skills>")? + start + "</skills>".len();
+            Some(estimate_tokens(&m.content[start..end]))
+        })
+        .sum();
+    let prompt_tokens = system_tokens.saturating_sub(tools_block_tokens + skills_tokens);
+
+    let message_tokens = |m: &Message| {
+        estimate_tokens(&m.content)
+            + m.tool_calls
+                .iter()
+                .flatten()
+                .map(|c| estimate_tokens(&c.function.arguments))
+                .sum::<usize>()
+    };
+    let (results, history): (Vec<&Message>, Vec<&Message>) =
+        rest.iter().partition(|m| m.role == "tool");
+    let history_tokens: usize = history.iter().map(|m| message_tokens(m)).sum();
+    let result_tokens: usize = results.iter().map(|m| message_tokens(m)).sum();
+    let tools = tools.unwrap_or_default();
+    let definition_tokens = serde_json::to_string(tools).map_or(0, |json| estimate_tokens(&json));
+
+    let total = system_tokens + history_tokens + result_tokens + definition_tokens;
+    let percent = (total * 100).checked_div(budget).unwrap_or(0);
+    let mut lines = vec![
+        format!(
+            "Context: ~{total} of {budget} tokens ({percent}%), {} message(s)",
+            messages.len()
+        ),
+        format!("  system prompt: ~{prompt_tokens}"),
+        format!("  tool descriptions: ~{tools_block_tokens}"),
+        format!("  skills: ~{skills_tokens}"),
+        format!("  tool definitions ({}): ~{definition_tokens}", tools.len()),
+        format!(
+            "  history ({} message(s)): ~{history_tokens}",
+            history.len()
+        ),
+        format!("  tool results ({}): ~{result_tokens}", results.len()),
+    ];
+
+    let all = tape_messages(tape.entries());
+    let since_anchor = tape_messages(tape.entries_since_last_anchor());
+    let before_anchor = all.len() - since_anchor.len();
+    let dropped = since_anchor.len().saturating_sub(history.len());
+    if before_anchor == 0 && dropped == 0 {
+        lines.push("Nothing trimmed: every message since the session start is sent.".to_string());
+        return lines.join("\n");
+    }
+
+    lines.push("Not sent:".to_string());
+    if before_anchor > 0 {
+        let anchor = tape.info().last_anchor.unwrap_or_default();
+        lines.push(format!(
+            "  {before_anchor} message(s) before anchor '{anchor}' (see ,anchors)"
+        ));
+    }
+    if dropped > 0 {
+        lines.push(format!(
+            "  {dropped} older message(s) trimmed to fit the context window:"
+        ));
+        const SHOWN: usize = 5;
+        if dropped > SHOWN {
+            lines.push(format!("    ... {} more", dropped - SHOWN));
+        }
+        for (role, content) in &since_anchor[dropped.saturating_sub(SHOWN)..dropped] {
+            let first_line = content.lines().next().unwrap_or_default();
+            let preview = crate::core::utils::safe_truncate(first_line, 80);
+            let ellipsis = if preview.len() < content.len() {
+                "..."
+            } else {
+                ""
+            };
+            lines.push(format!("    [{role}] {preview}{ellipsis}"));
+        }
+    }
+    lines.join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    #[test]
+    fn workspace_context_fingerprint_tracks_every_context_file() {
+        let dir = tempdir().unwrap();
+        let initial = workspace_context_fingerprint(dir.path());
+        assert_eq!(initial, workspace_context_fingerprint(dir.path()));
+
+        std::fs::create_dir_all(dir.path().join(".agent")).unwrap();
+        std::fs::write(dir.path().join(".agent/system-prompt.md"), "Be brief.").unwrap();
+        let with_prompt = workspace_context_fingerprint(dir.path());
+        assert_ne!(initial, with_prompt);
+
+        let skill_dir = dir.path().join(".agent/skills/demo");
+        std::fs::create_dir_all(&skill_dir).unwrap();
+        std::fs::write(skill_dir.join("SKILL.md"), "---\nname: demo\n---\n").unwrap();
+        let with_skill = workspace_context_fingerprint(dir.path());
+        assert_ne!(with_prompt, with_skill);
+
+        std::fs::write(skill_dir.join("SKILL.md"), "---\nname: demo\n---\nBody.\n").unwrap();
+        let with_body = workspace_context_fingerprint(dir.path());
+        assert_ne!(with_skill, with_body);
+
+        std::fs::write(dir.path().join(".agent/tools.json"), "{\"tools\": []}").unwrap();
+        let with_tools = workspace_context_fingerprint(dir.path());
+        assert_ne!(with_body, with_tools);
+
+        std::fs::create_dir_all(dir.path().join(".agent/personas")).unwrap();
+        std::fs::write(dir.path().join(".agent/personas/terse.md"), "Be terse.").unwrap();
+        assert_ne!(with_tools, workspace_context_fingerprint(dir.path()));
+    }
+
     #[test]
     fn empty_tape_no_system_prompt() {
         let dir = tempdir().unwrap();
@@ -297,6 +881,52 @@ mod tests {
         assert!(result.contains("file.read"));
     }
 
+    #[test]
+    fn time_context_uses_the_configured_zone_and_elapsed_time() {
+        let now = DateTime::parse_from_rfc3339("2026-03-01T04:30:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let turn = TurnContext {
+            timezone: Some(chrono_tz::Asia::Shanghai),
+            previous_message_at: Some(now - chrono::Duration::minutes(195)),
+            ..Default::default()
+        };
+        assert_eq!(
+            time_context_lines(now, &turn),
+            [
+                "Current Date/Time: 2026-03-01 12:30:00 Sunday (UTC+08:00)",
+                "Timezone: Asia/Shanghai",
+                "Time since the user's previous message: 3h 15m",
+            ]
+        );
+        assert_eq!(format_elapsed(42), "42s");
+        assert_eq!(format_elapsed(2 * 86_400 + 4 * 3_600 + 59), "2d 4h");
+    }
+
+    #[test]
+    fn previous_user_message_skips_the_current_one() {
+        let dir = tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "test").unwrap();
+        assert!(previous_user_message_at(&tape, "hi").is_none());
+        tape.append_message("user", "first").unwrap();
+        tape.append_message("assistant", "reply").unwrap();
+        let first = previous_user_message_at(&tape, "second").unwrap();
+        tape.append_message("user", "second").unwrap();
+        assert_eq!(previous_user_message_at(&tape, "second"), Some(first));
+    }
+
+    #[test]
+    fn reply_language_instruction_comes_last() {
+        let dir = tempdir().unwrap();
+        let turn = TurnContext {
+            reply_language: Some("Reply in Chinese."),
+            ..Default::default()
+        };
+        let result = build_system_prompt_with_tools(None, dir.path(), None, &turn);
+        assert!(result.ends_with("<reply_language>\nReply in Chinese.\n</reply_language>"));
+        assert!(!build_system_prompt(None, dir.path()).contains("<reply_language>"));
+    }
+
     #[test]
     fn system_prompt_combines_sources() {
         let dir = tempdir().unwrap();
@@ -314,6 +944,136 @@ mod tests {
         assert!(result.contains("<context>"));
     }
 
+    #[test]
+    fn instruction_files_are_discovered_outermost_first() {
+        let root = tempdir().unwrap();
+        std::fs::create_dir(root.path().join(".git")).unwrap();
+        let sub = root.path().join("crates/app");
+        std::fs::create_dir_all(&sub).unwrap();
+        std::fs::write(root.path().join("AGENTS.md"), "Run cargo fmt.").unwrap();
+        std::fs::write(sub.join("AGENTS.md"), "App rules.").unwrap();
+        // Same content under the other name is only included once.
+        std::fs::write(sub.join("CLAUDE.md"), "App rules.\n").unwrap();
+
+        let names = vec!["AGENTS.md".to_string(), "CLAUDE.md".to_string()];
+        let files = discover_instruction_files_named(&sub, &names);
+        let contents: Vec<&str> = files.iter().map(|f| f.content.as_str()).collect();
+        assert_eq!(contents, ["Run cargo fmt.", "App rules."]);
+
+        let prompt = build_system_prompt(None, &sub);
+        let outer = prompt.find("Run cargo fmt.").unwrap();
+        assert!(outer < prompt.find("App rules.").unwrap());
+        assert!(prompt.contains("<project_instructions path="));
+    }
+
+    #[test]
+    fn instruction_files_are_capped() {
+        let root = tempdir().unwrap();
+        std::fs::create_dir(root.path().join(".git")).unwrap();
+        std::fs::write(
+            root.path().join("AGENTS.md"),
+            "é".repeat(MAX_INSTRUCTION_FILE_BYTES),
+        )
+        .unwrap();
+        let files = discover_instruction_files_named(root.path(), &["AGENTS.md".to_string()]);
+        assert!(files[0].content.ends_with("[... truncated]"));
+        assert!(files[0].content.len() <= MAX_INSTRUCTION_FILE_BYTES + 20);
+        assert!(discover_instruction_files_named(root.path(), &[]).is_empty());
+    }
+
+    #[test]
+    fn workspace_context_reports_project_type() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        std::fs::write(dir.path().join("package.json"), "{}").unwrap();
+        let prompt = build_system_prompt(None, dir.path());
+        assert!(
+            prompt.contains("Project type: Rust (Cargo), JavaScript/TypeScript (package.json)")
+        );
+        assert!(prompt.contains(&format!("OS: {}", std::env::consts::OS)));
+        assert!(prompt.contains("Current Date/Time: "));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn git_summary_runs_no_repository_programs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let ws = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(ws.path())
+                .output()
+                .unwrap()
+        };
+        if !git(&["init", "-q"]).status.success() {
+            return;
+        }
+        let hook = ws.path().join("monitor.sh");
+        std::fs::write(&hook, "#!/bin/sh\ntouch \"$(dirname \"$0\")/pwned\"\n").unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::write(ws.path().join(".gitattributes"), "* filter=evil\n").unwrap();
+        std::fs::write(ws.path().join("a.txt"), "a").unwrap();
+        git(&["-c", "filter.evil.clean=cat", "add", "a.txt"]);
+        git(&["symbolic-ref", "HEAD", "refs/heads/feature"]);
+        git(&["config", "core.fsmonitor", hook.to_str().unwrap()]);
+        git(&["config", "filter.evil.clean", hook.to_str().unwrap()]);
+
+        assert_eq!(git_summary(ws.path()).as_deref(), Some("branch feature"));
+        assert!(!ws.path().join("pwned").exists());
+    }
+
+    #[test]
+    fn prompt_preview_and_token_breakdown() {
+        let messages = vec![
+            Message::system("<identity>crab</identity>"),
+            Message::user("hi"),
+            Message::assistant_with_tool_calls(vec![crate::llm::api_types::ToolCall {
+                id: "call_1".to_string(),
+                call_type: "function".to_string(),
+                function: crate::llm::api_types::ToolCallFunction {
+                    name: "file.read".to_string(),
+                    arguments: r#"{"path":"a.txt"}"#.to_string(),
+                },
+            }]),
+            Message::tool("call_1", "contents"),
+        ];
+        let preview = format_prompt_preview(&messages, None);
+        assert!(preview.starts_with("=== System prompt ===\n<identity>crab</identity>"));
+        assert!(preview.contains("=== Messages (3) ===\n[user] hi\n"));
+        assert!(preview.contains(r#"[assistant → file.read] {"path":"a.txt"}"#));
+        assert!(preview.contains("[tool call_1] contents"));
+        assert!(preview.ends_with("=== Tools (0) ===\n"));
+
+        let tokens = format_prompt_tokens(&messages, None, 1000);
+        assert!(tokens.starts_with("~"), "{tokens}");
+        assert!(tokens.contains("prompt budget 1000"));
+        assert!(tokens.contains("messages (3)"));
+    }
+
+    #[test]
+    fn fit_to_token_budget_drops_oldest_messages() {
+        let messages = vec![
+            Message::system("sys"),
+            Message::user("a".repeat(400)),
+            Message::assistant("b".repeat(400)),
+            Message::user("latest"),
+        ];
+        // Everything fits: unchanged.
+        assert_eq!(fit_to_token_budget(messages.clone(), 1_000).len(), 4);
+
+        let trimmed = fit_to_token_budget(messages.clone(), 150);
+        let contents: Vec<&str> = trimmed.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents[0], "sys");
+        assert!(contents[1].contains("truncated"));
+        assert_eq!(contents[2..], ["b".repeat(400).as_str(), "latest"]);
+
+        // The latest message survives even when it alone exceeds the budget.
+        let trimmed = fit_to_token_budget(messages, 1);
+        assert_eq!(trimmed.last().unwrap().content, "latest");
+    }
+
     #[test]
     fn test_max_context_messages_truncation() {
         let dir = tempdir().unwrap();
@@ -0,0 +1,398 @@
+//! Commands that ask for what is missing, across turns.
+//!
+//! `,remind` and `,config set` can be typed in full or with parts left
+//! out. Each missing part is asked for in turn ("When should I remind
+//! you?") and the next plain message is taken as the answer; an answer
+//! that does not parse is asked for again. The open dialog is a `dialog`
+//! event in the tape, so it works the same in every channel and survives
+//! restarts. `,cancel` drops it, as does any other command or
+//! [`DIALOG_TIMEOUT_MINUTES`] without an answer.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, NaiveTime, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::core::command::DetectedCommand;
+use crate::tape::store::TapeStore;
+
+/// Tape event holding the open dialog, or `null` once it is closed.
+pub const DIALOG_EVENT: &str = "dialog";
+/// An unanswered question is dropped after this long.
+pub const DIALOG_TIMEOUT_MINUTES: i64 = 30;
+
+/// Field values by field name.
+pub type Values = BTreeMap<String, String>;
+
+/// A command that can be filled in over several messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Form {
+    /// `,remind`: fields `message`, `when` and `to`.
+    Remind,
+    /// `,config set`: fields `key` and `value`.
+    ConfigSet,
+}
+
+impl Form {
+    /// Fields in the order they are asked for.
+    pub fn fields(self) -> &'static [&'static str] {
+        match self {
+            Self::Remind => &["message", "when", "to"],
+            Self::ConfigSet => &["key", "value"],
+        }
+    }
+
+    fn question(self, field: &str, values: &Values) -> String {
+        match (self, field) {
+            (Self::Remind, "message") => "What should I remind you about?".to_string(),
+            (Self::Remind, "when") => {
+                "When? A delay like 10m or 2h30m, or a time like 18:30.".to_string()
+            }
+            (Self::Remind, _) => "Where should I send it? here, telegram:<chat_id>, desktop, \
+                 webhook[:<url>] or email[:<address>]"
+                .to_string(),
+            (Self::ConfigSet, "key") => "Which setting? e.g. MODEL or REPLY_LANGUAGE".to_string(),
+            (Self::ConfigSet, _) => format!(
+                "What should {} be set to?",
+                values.get("key").map(String::as_str).unwrap_or("it")
+            ),
+        }
+    }
+
+    /// Check an answer for `field` and return the value to keep.
+    fn check(self, field: &str, answer: &str) -> Result<String, String> {
+        let answer = answer.trim();
+        if answer.is_empty() {
+            return Err("That was empty.".to_string());
+        }
+        match (self, field) {
+            (Self::Remind, "when") => parse_delay(answer, NaiveTime::MIN)
+                .map(|_| answer.to_string())
+                .map_err(|e| format!("I could not read '{answer}' as a time: {e}.")),
+            (Self::ConfigSet, "key") => {
+                use crate::core::config::CHAT_SETTABLE_KEYS;
+
+                let key = answer.to_ascii_uppercase();
+                if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+                    Err(format!(
+                        "'{answer}' is not a setting name (letters, digits and _)."
+                    ))
+                } else if !CHAT_SETTABLE_KEYS.contains(&key.as_str()) {
+                    Err(format!(
+                        "{key} cannot be set from chat; edit .env.local by hand. Settable: {}.",
+                        CHAT_SETTABLE_KEYS.join(", ")
+                    ))
+                } else {
+                    Ok(key)
+                }
+            }
+            _ => Ok(answer.to_string()),
+        }
+    }
+
+    /// The form `command` starts, with the fields it already gives, or a
+    /// usage message. `None` when the command has no form.
+    pub fn from_command(command: &DetectedCommand) -> Option<Result<(Self, Values), String>> {
+        let args = &command.args;
+        let mut values = BTreeMap::new();
+        match command.name.as_str() {
+            "remind" => {
+                let mut words = args.positional.as_slice();
+                if let [first, amount, rest @ ..] = words
+                    && matches!(first.as_str(), "in" | "at")
+                {
+                    let mut when = format!("{first} {amount}");
+                    words = rest;
+                    // `in 10 minutes ...`: the unit is a word of its own.
+                    if let [unit, tail @ ..] = rest
+                        && amount.chars().all(|c| c.is_ascii_digit())
+                        && parse_delay(&format!("1{unit}"), NaiveTime::MIN).is_ok()
+                    {
+                        when.push_str(unit);
+                        words = tail;
+                    }
+                    values.insert("when".to_string(), when);
+                }
+                for key in ["when", "to"] {
+                    if let Some(value) = args.get(key) {
+                        values.insert(key.to_string(), value.to_string());
+                    }
+                }
+                if !words.is_empty() {
+                    values.insert("message".to_string(), words.join(" "));
+                }
+                Some(Ok((Self::Remind, values)))
+            }
+            "config" => {
+                let usage = "usage: ,config set [KEY [value]]".to_string();
+                let [sub, rest @ ..] = args.positional.as_slice() else {
+                    return Some(Err(usage));
+                };
+                if sub != "set" {
+                    return Some(Err(usage));
+                }
+                if let Some((key, value)) = args.kwargs.first() {
+                    values.insert("key".to_string(), key.clone());
+                    values.insert("value".to_string(), value.clone());
+                } else if let [key, value @ ..] = rest {
+                    values.insert("key".to_string(), key.clone());
+                    if !value.is_empty() {
+                        values.insert("value".to_string(), value.join(" "));
+                    }
+                }
+                Some(Ok((Self::ConfigSet, values)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A form being filled in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Dialog {
+    pub form: Form,
+    #[serde(default)]
+    pub values: Values,
+    /// Field the last question asked for.
+    #[serde(default)]
+    pub asking: Option<String>,
+}
+
+impl Dialog {
+    /// Start `form` with the values given up front. Values that do not
+    /// check out are left to be asked for; the first problem is returned.
+    pub fn start(form: Form, given: Values) -> (Self, Option<String>) {
+        let mut dialog = Self {
+            form,
+            values: Values::new(),
+            asking: None,
+        };
+        let mut problem = None;
+        for (field, value) in given {
+            match form.check(&field, &value) {
+                Ok(value) => {
+                    dialog.values.insert(field, value);
+                }
+                Err(e) => {
+                    problem.get_or_insert(e);
+                }
+            }
+        }
+        (dialog, problem)
+    }
+
+    /// Take `answer` for the field last asked for.
+    pub fn answer(&mut self, answer: &str) -> Result<(), String> {
+        let Some(field) = self.asking.clone() else {
+            return Ok(());
+        };
+        let value = self.form.check(&field, answer)?;
+        self.values.insert(field, value);
+        Ok(())
+    }
+
+    /// The question for the first missing field, or `None` when every
+    /// field is filled.
+    pub fn next_question(&mut self) -> Option<String> {
+        let field = self
+            .form
+            .fields()
+            .iter()
+            .find(|field| !self.values.contains_key(**field))?;
+        self.asking = Some(field.to_string());
+        Some(self.form.question(field, &self.values))
+    }
+
+    pub fn value(&self, field: &str) -> &str {
+        self.values
+            .get(field)
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+}
+
+/// The dialog open in `tape`, unless it was closed or has timed out.
+pub fn open_dialog(tape: &TapeStore) -> Option<Dialog> {
+    let entry = tape
+        .entries()
+        .iter()
+        .rev()
+        .find(|e| e.kind == DIALOG_EVENT)?;
+    let opened = DateTime::parse_from_rfc3339(&entry.timestamp).ok()?;
+    if Utc::now().signed_duration_since(opened) > chrono::Duration::minutes(DIALOG_TIMEOUT_MINUTES)
+    {
+        return None;
+    }
+    serde_json::from_value(entry.payload.clone()).ok()
+}
+
+/// Record `dialog` as the open dialog, or close it (`None`).
+pub fn save_dialog(tape: &mut TapeStore, dialog: Option<&Dialog>) -> std::io::Result<()> {
+    let payload = serde_json::to_value(dialog).unwrap_or_default();
+    tape.append_event(DIALOG_EVENT, payload).map(|_| ())
+}
+
+/// Longest delay `parse_delay` accepts: one year.
+const MAX_DELAY_SECONDS: u64 = 365 * 86_400;
+
+/// Seconds until `when`: a delay (`10m`, `in 2h30m`, `90 seconds`) of at
+/// most a year, or a time of day (`18:30`, `at 7:05`), the next one after
+/// `now`.
+pub fn parse_delay(when: &str, now: NaiveTime) -> Result<u64, String> {
+    let when = when.trim().to_ascii_lowercase();
+    let at = when.strip_prefix("at ").unwrap_or(&when).trim();
+    if at.contains(':') {
+        let time =
+            NaiveTime::parse_from_str(at, "%H:%M").map_err(|_| "expected HH:MM".to_string())?;
+        let seconds = |t: NaiveTime| i64::from(t.num_seconds_from_midnight());
+        let wait = (seconds(time) - seconds(now)).rem_euclid(86_400);
+        return Ok(if wait == 0 { 86_400 } else { wait as u64 });
+    }
+
+    let delay = when.strip_prefix("in ").unwrap_or(&when).replace(' ', "");
+    let mut total = 0u64;
+    let mut rest = delay.as_str();
+    while !rest.is_empty() {
+        let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        let amount: u64 = rest[..digits]
+            .parse()
+            .map_err(|_| "expected a number followed by s, m, h or d".to_string())?;
+        rest = &rest[digits..];
+        let letters = rest.len() - rest.trim_start_matches(|c: char| c.is_alphabetic()).len();
+        let unit = match &rest[..letters] {
+            "s" | "sec" | "secs" | "second" | "seconds" => 1,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 3_600,
+            "d" | "day" | "days" => 86_400,
+            other => return Err(format!("unknown unit '{other}' (use s, m, h or d)")),
+        };
+        rest = &rest[letters..];
+        total = amount
+            .checked_mul(unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .filter(|&total| total <= MAX_DELAY_SECONDS)
+            .ok_or_else(|| "the delay must be at most 365 days".to_string())?;
+    }
+    if total == 0 {
+        return Err("the delay must be more than zero".to_string());
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::command::detect_command;
+
+    fn time(hh_mm: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(hh_mm, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn delays_and_times_of_day_are_parsed() {
+        let now = time("12:00");
+        assert_eq!(parse_delay("10m", now), Ok(600));
+        assert_eq!(parse_delay("in 2h30m", now), Ok(9_000));
+        assert_eq!(parse_delay("90 seconds", now), Ok(90));
+        assert_eq!(parse_delay("at 18:30", now), Ok(23_400));
+        assert_eq!(parse_delay("7:00", now), Ok(68_400));
+        assert!(parse_delay("soon", now).is_err());
+        assert!(parse_delay("10 fortnights", now).is_err());
+        assert!(parse_delay("25:00", now).is_err());
+    }
+
+    #[test]
+    fn delays_are_bounded() {
+        let now = time("12:00");
+        assert_eq!(parse_delay("365d", now), Ok(MAX_DELAY_SECONDS));
+        assert_eq!(
+            parse_delay("366d", now),
+            Err("the delay must be at most 365 days".to_string())
+        );
+        // Overflows u64 when multiplied, and when added up.
+        assert!(parse_delay("in 999999999999999d", now).is_err());
+        assert!(parse_delay("18446744073709551615s1s", now).is_err());
+    }
+
+    #[test]
+    fn commands_prefill_the_form() {
+        let start = |text: &str| {
+            let command = detect_command(text).unwrap();
+            Form::from_command(&command).unwrap()
+        };
+        let (form, values) = start(",remind in 10m stretch your legs").unwrap();
+        assert_eq!(form, Form::Remind);
+        assert_eq!(values["when"], "in 10m");
+        assert_eq!(values["message"], "stretch your legs");
+
+        let (_, values) = start(",remind in 10 minutes call back").unwrap();
+        assert_eq!(values["when"], "in 10minutes");
+        assert_eq!(values["message"], "call back");
+
+        let (_, values) = start(",config set MODEL=openai:gpt-4o").unwrap();
+        assert_eq!(
+            (values["key"].as_str(), values["value"].as_str()),
+            ("MODEL", "openai:gpt-4o")
+        );
+        let (_, values) = start(",config set reply_language").unwrap();
+        assert_eq!(values.len(), 1);
+        assert!(start(",config get MODEL").is_err());
+    }
+
+    #[test]
+    fn config_set_refuses_security_settings() {
+        for key in [
+            "BASE_URL",
+            "telegram_admins",
+            "TELEGRAM_ALLOW_FROM",
+            "ASSISTANT_COMMAND_CONFIRM",
+        ] {
+            let given = BTreeMap::from([
+                ("key".to_string(), key.to_string()),
+                ("value".to_string(), "x".to_string()),
+            ]);
+            let (dialog, problem) = Dialog::start(Form::ConfigSet, given);
+            let problem = problem.unwrap();
+            assert!(problem.contains("cannot be set from chat"), "{problem}");
+            assert!(!dialog.values.contains_key("key"));
+        }
+    }
+
+    #[test]
+    fn missing_fields_are_asked_for_in_order() {
+        let (mut dialog, problem) = Dialog::start(
+            Form::Remind,
+            BTreeMap::from([("when".to_string(), "someday".to_string())]),
+        );
+        assert!(problem.unwrap().contains("someday"));
+        assert_eq!(
+            dialog.next_question().unwrap(),
+            "What should I remind you about?"
+        );
+        dialog.answer("call mum").unwrap();
+        assert!(dialog.next_question().unwrap().starts_with("When?"));
+        assert!(dialog.answer("whenever").is_err());
+        dialog.answer("18:30").unwrap();
+        assert!(dialog.next_question().unwrap().starts_with("Where"));
+        dialog.answer("here").unwrap();
+        assert_eq!(dialog.next_question(), None);
+        assert_eq!(dialog.value("message"), "call mum");
+    }
+
+    #[test]
+    fn open_dialogs_live_in_the_tape() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "test").unwrap();
+        assert_eq!(open_dialog(&tape), None);
+
+        let (mut dialog, _) = Dialog::start(Form::ConfigSet, BTreeMap::new());
+        dialog.next_question();
+        save_dialog(&mut tape, Some(&dialog)).unwrap();
+        let reopened = TapeStore::open(dir.path(), "test").unwrap();
+        assert_eq!(open_dialog(&reopened), Some(dialog));
+
+        save_dialog(&mut tape, None).unwrap();
+        assert_eq!(open_dialog(&tape), None);
+    }
+}
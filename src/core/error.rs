@@ -1,4 +1,6 @@
+use std::fmt;
 use std::io;
+use std::time::Duration;
 
 use thiserror::Error;
 
@@ -16,12 +18,231 @@ pub enum CrabClawError {
     Auth(String),
     #[error("api error: {0}")]
     Api(String),
-    #[error("rate limited: {0}")]
-    RateLimit(String),
+    /// A provider answered with an HTTP error status.
+    #[error("provider error: {0}")]
+    Provider(ProviderError),
+    /// A provider's circuit is open after repeated failures; nothing was sent.
+    #[error("provider unavailable: {0}")]
+    Unavailable(String),
     #[error("http error: {0}")]
     Http(#[from] reqwest::Error),
 }
 
+impl CrabClawError {
+    /// Whether sending the same request again may succeed: network
+    /// failures, rate limits and overloaded providers.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Network(_) => true,
+            Self::Provider(e) => e.is_retryable(),
+            _ => false,
+        }
+    }
+
+    /// How long the provider asked us to wait before retrying.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Provider(e) => e.retry_after,
+            _ => None,
+        }
+    }
+
+    /// What the user can do about the error, when there is something.
+    pub fn remediation(&self) -> Option<String> {
+        match self {
+            Self::Provider(e) => e.remediation(),
+            Self::Auth(_) => Some(
+                "Check your API key, or run `crabclaw auth login` (codex, openai or anthropic)."
+                    .to_string(),
+            ),
+            Self::Unavailable(_) => Some(
+                "Switch models with ,model, or set FALLBACK_MODEL to use another provider meanwhile."
+                    .to_string(),
+            ),
+            Self::Network(_) | Self::Http(_) => Some(
+                "Check your network connection, proxy settings and the API base URL.".to_string(),
+            ),
+            Self::Config(_) => {
+                Some("Check your environment variables or the .env file.".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Message for channels to show instead of the raw error: a one-line
+    /// summary followed by the remediation hint, if any.
+    pub fn user_message(&self) -> String {
+        let summary = match self {
+            Self::Provider(e) => e.summary(),
+            other => other.to_string(),
+        };
+        match self.remediation() {
+            Some(hint) => format!("{summary}\n{hint}"),
+            None => summary,
+        }
+    }
+}
+
+/// Broad class of a provider error, derived from the HTTP status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderErrorKind {
+    /// 401 / 403: the key or token was rejected.
+    Auth,
+    /// 429.
+    RateLimit,
+    /// 503 / 529: the provider is shedding load.
+    Overloaded,
+    /// Other 5xx.
+    Server,
+    /// 404: usually an unknown model or a wrong base URL.
+    NotFound,
+    /// Other 4xx: the provider rejected the request itself.
+    BadRequest,
+    Other,
+}
+
+impl ProviderErrorKind {
+    fn from_status(status: u16) -> Self {
+        match status {
+            401 | 403 => Self::Auth,
+            429 => Self::RateLimit,
+            503 | 529 => Self::Overloaded,
+            404 => Self::NotFound,
+            500..=599 => Self::Server,
+            400..=499 => Self::BadRequest,
+            _ => Self::Other,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Auth => "authentication failed",
+            Self::RateLimit => "rate limit",
+            Self::Overloaded => "overloaded",
+            Self::Server => "server error",
+            Self::NotFound => "not found",
+            Self::BadRequest => "request rejected",
+            Self::Other => "error",
+        }
+    }
+}
+
+/// HTTP error from an LLM provider, with what is needed to decide on a
+/// retry and to tell the user what to do.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderError {
+    /// Provider prefix of the model (`anthropic`, `openai`, `codex`, ...).
+    pub provider: String,
+    pub status: u16,
+    /// From the `Retry-After` header, when the provider sent one.
+    pub retry_after: Option<Duration>,
+    /// Error message from the response body.
+    pub detail: String,
+}
+
+impl ProviderError {
+    pub fn new(provider: impl Into<String>, status: u16, detail: impl Into<String>) -> Self {
+        Self {
+            provider: provider.into(),
+            status,
+            retry_after: None,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn with_retry_after(mut self, retry_after: Option<Duration>) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    pub fn kind(&self) -> ProviderErrorKind {
+        ProviderErrorKind::from_status(self.status)
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            ProviderErrorKind::RateLimit | ProviderErrorKind::Overloaded
+        )
+    }
+
+    /// Display name of the provider ("Anthropic", "OpenAI", ...).
+    pub fn provider_name(&self) -> String {
+        provider_display_name(&self.provider)
+    }
+
+    /// One line for users, e.g. `Anthropic rate limit (HTTP 429): ...`.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} {} (HTTP {}): {}",
+            self.provider_name(),
+            self.kind().label(),
+            self.status,
+            self.detail
+        )
+    }
+
+    pub fn remediation(&self) -> Option<String> {
+        let hint = match self.kind() {
+            ProviderErrorKind::Auth => match self.provider.as_str() {
+                "codex" | "responses" => "Run `crabclaw auth login` to sign in again.".to_string(),
+                "openai" | "anthropic" => format!(
+                    "Check API_KEY, or run `crabclaw auth login {}`.",
+                    self.provider
+                ),
+                other => format!("Check {}_API_KEY.", other.to_ascii_uppercase()),
+            },
+            ProviderErrorKind::RateLimit => match self.retry_after {
+                Some(wait) => format!(
+                    "Try again in {}s, or switch models with ,model.",
+                    wait.as_secs().max(1)
+                ),
+                None => "Wait a moment and try again, or switch models with ,model.".to_string(),
+            },
+            ProviderErrorKind::Overloaded => {
+                "The provider is overloaded; try again shortly or switch models with ,model."
+                    .to_string()
+            }
+            ProviderErrorKind::Server => {
+                "The provider had an internal error; try again later.".to_string()
+            }
+            ProviderErrorKind::NotFound => "Check MODEL and the API base URL.".to_string(),
+            ProviderErrorKind::BadRequest | ProviderErrorKind::Other => return None,
+        };
+        Some(hint)
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} HTTP {}: {}", self.provider, self.status, self.detail)
+    }
+}
+
+/// Display name for a provider prefix (`anthropic` -> `Anthropic`).
+pub fn provider_display_name(provider: &str) -> String {
+    match provider {
+        "openai" => "OpenAI".to_string(),
+        "anthropic" => "Anthropic".to_string(),
+        "codex" => "Codex".to_string(),
+        "responses" => "OpenAI Responses".to_string(),
+        "xai" => "xAI".to_string(),
+        other => {
+            let mut chars = other.chars();
+            chars
+                .next()
+                .map(|c| c.to_uppercase().chain(chars).collect())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// Parse a `Retry-After` header given in seconds. HTTP dates are rare for
+/// LLM APIs and are ignored.
+pub fn parse_retry_after(value: Option<&str>) -> Option<Duration> {
+    value?.trim().parse::<u64>().ok().map(Duration::from_secs)
+}
+
 pub type Result<T> = std::result::Result<T, CrabClawError>;
 
 #[cfg(test)]
@@ -52,6 +273,37 @@ mod tests {
         assert_eq!(err.to_string(), "api error: rate limited");
     }
 
+    #[test]
+    fn provider_errors_are_classified() {
+        let err = CrabClawError::Provider(
+            ProviderError::new("anthropic", 429, "slow down")
+                .with_retry_after(parse_retry_after(Some("30"))),
+        );
+        assert!(err.is_retryable());
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(30)));
+        assert_eq!(
+            err.to_string(),
+            "provider error: anthropic HTTP 429: slow down"
+        );
+        assert_eq!(
+            err.user_message(),
+            "Anthropic rate limit (HTTP 429): slow down\n\
+             Try again in 30s, or switch models with ,model."
+        );
+
+        let err = CrabClawError::Provider(ProviderError::new("codex", 401, "expired"));
+        assert!(!err.is_retryable());
+        assert!(
+            err.user_message()
+                .ends_with("Run `crabclaw auth login` to sign in again.")
+        );
+        let err = ProviderError::new("mistral", 403, "bad key");
+        assert_eq!(err.remediation().unwrap(), "Check MISTRAL_API_KEY.");
+        assert!(ProviderError::new("openai", 529, "busy").is_retryable());
+        assert!(!ProviderError::new("openai", 500, "oops").is_retryable());
+        assert_eq!(ProviderError::new("openai", 400, "bad").remediation(), None);
+    }
+
     #[test]
     fn io_error_from_conversion() {
         let io_err = io::Error::new(io::ErrorKind::NotFound, "file missing");
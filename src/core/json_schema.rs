@@ -0,0 +1,247 @@
+//! Validation against the subset of JSON Schema that tool and output
+//! schemas use in practice.
+//!
+//! Supported keywords: `type` (a name or a list), `enum`, `const`,
+//! `properties`, `required`, `additionalProperties` (`false` or a schema),
+//! `items`, `minItems` / `maxItems`, `minLength` / `maxLength`, `minimum` /
+//! `maximum`, `anyOf` and `oneOf`. Unknown keywords are ignored, so richer
+//! schemas still validate what they can.
+
+use serde_json::Value;
+
+/// Check `value` against `schema`. Returns one message per violation,
+/// each prefixed with the JSON path of the offending value (`$.items[2]`).
+pub fn validate(value: &Value, schema: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    check(value, schema, "$", &mut errors);
+    errors
+}
+
+fn check(value: &Value, schema: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        // `true` / `{}` accept anything; `false` accepts nothing.
+        if schema == &Value::Bool(false) {
+            errors.push(format!("{path}: no value is allowed here"));
+        }
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let names: Vec<&str> = match expected {
+            Value::String(name) => vec![name.as_str()],
+            Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+            _ => Vec::new(),
+        };
+        if !names.is_empty() && !names.iter().any(|name| has_type(value, name)) {
+            errors.push(format!(
+                "{path}: expected {}, got {}",
+                names.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(options) = schema.get("enum").and_then(Value::as_array)
+        && !options.contains(value)
+    {
+        let allowed: Vec<String> = options.iter().map(Value::to_string).collect();
+        errors.push(format!("{path}: must be one of {}", allowed.join(", ")));
+    }
+    if let Some(expected) = schema.get("const")
+        && expected != value
+    {
+        errors.push(format!("{path}: must be {expected}"));
+    }
+
+    for (keyword, exactly_one) in [("anyOf", false), ("oneOf", true)] {
+        let Some(branches) = schema.get(keyword).and_then(Value::as_array) else {
+            continue;
+        };
+        let matching = branches
+            .iter()
+            .filter(|branch| validate(value, branch).is_empty())
+            .count();
+        if matching == 0 || (exactly_one && matching > 1) {
+            errors.push(format!(
+                "{path}: must match {} of the {keyword} schemas ({matching} matched)",
+                if exactly_one {
+                    "exactly one"
+                } else {
+                    "at least one"
+                }
+            ));
+        }
+    }
+
+    match value {
+        Value::Object(map) => {
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for key in schema
+                .get("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+            {
+                if !map.contains_key(key) {
+                    errors.push(format!("{path}: missing required property '{key}'"));
+                }
+            }
+            for (key, item) in map {
+                let item_path = format!("{path}.{key}");
+                match properties.and_then(|p| p.get(key)) {
+                    Some(item_schema) => check(item, item_schema, &item_path, errors),
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            errors.push(format!("{path}: unexpected property '{key}'"))
+                        }
+                        Some(extra) => check(item, extra, &item_path, errors),
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            let count = items.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(Value::as_u64)
+                && count < min
+            {
+                errors.push(format!(
+                    "{path}: expected at least {min} item(s), got {count}"
+                ));
+            }
+            if let Some(max) = schema.get("maxItems").and_then(Value::as_u64)
+                && count > max
+            {
+                errors.push(format!(
+                    "{path}: expected at most {max} item(s), got {count}"
+                ));
+            }
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    check(item, item_schema, &format!("{path}[{i}]"), errors);
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(Value::as_u64)
+                && len < min
+            {
+                errors.push(format!("{path}: shorter than {min} character(s)"));
+            }
+            if let Some(max) = schema.get("maxLength").and_then(Value::as_u64)
+                && len > max
+            {
+                errors.push(format!("{path}: longer than {max} character(s)"));
+            }
+        }
+        Value::Number(n) => {
+            let n = n.as_f64().unwrap_or_default();
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64)
+                && n < min
+            {
+                errors.push(format!("{path}: must be at least {min}"));
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64)
+                && n > max
+            {
+                errors.push(format!("{path}: must be at most {max}"));
+            }
+        }
+        Value::Bool(_) | Value::Null => {}
+    }
+}
+
+fn has_type(value: &Value, name: &str) -> bool {
+    match name {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        // Unknown type names are not ours to reject.
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "status": { "enum": ["ok", "failed"] },
+                "count": { "type": "integer", "minimum": 0 },
+                "tags": { "type": "array", "items": { "type": "string" }, "maxItems": 2 },
+                "note": { "type": ["string", "null"] }
+            },
+            "required": ["status", "count"],
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn valid_values_pass() {
+        let value = json!({ "status": "ok", "count": 3, "tags": ["a"], "note": null });
+        assert!(validate(&value, &schema()).is_empty());
+        assert!(validate(&json!(2.0), &json!({ "type": "integer" })).is_empty());
+    }
+
+    #[test]
+    fn violations_are_reported_with_paths() {
+        let value = json!({ "status": "maybe", "count": -1, "tags": ["a", 2, "c"], "extra": 1 });
+        let errors = validate(&value, &schema());
+        assert_eq!(
+            errors,
+            vec![
+                "$.count: must be at least 0",
+                "$: unexpected property 'extra'",
+                "$.status: must be one of \"ok\", \"failed\"",
+                "$.tags: expected at most 2 item(s), got 3",
+                "$.tags[1]: expected string, got integer",
+            ]
+        );
+        assert_eq!(
+            validate(&json!({}), &schema()),
+            vec![
+                "$: missing required property 'status'",
+                "$: missing required property 'count'",
+            ]
+        );
+        assert_eq!(
+            validate(&json!("x"), &schema()),
+            vec!["$: expected object, got string"]
+        );
+    }
+
+    #[test]
+    fn any_of_and_one_of() {
+        let schema = json!({ "oneOf": [{ "type": "number" }, { "type": "integer" }] });
+        assert_eq!(validate(&json!(1.5), &schema), Vec::<String>::new());
+        assert_eq!(validate(&json!(1), &schema).len(), 1);
+        let schema = json!({ "anyOf": [{ "type": "string" }, { "type": "null" }] });
+        assert!(validate(&Value::Null, &schema).is_empty());
+        assert_eq!(validate(&json!(true), &schema).len(), 1);
+    }
+}
@@ -0,0 +1,231 @@
+//! Reply-language policy.
+//!
+//! Models tend to drift into English after tool-heavy rounds even when the
+//! user writes in another language. `REPLY_LANGUAGE` adds a standing
+//! instruction to the system prompt: `auto` answers in the language of the
+//! current message (detected by script, and by common words for Latin
+//! script), while any other value names a language to always reply in.
+
+use serde::Serialize;
+
+/// Which language answers should be in.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReplyLanguage {
+    /// The language of the user's message.
+    Auto,
+    /// Always this language, e.g. `Chinese`.
+    Fixed(String),
+}
+
+impl ReplyLanguage {
+    /// Parse `auto` or a language name; `off` (or nothing) means no policy.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        match value.to_ascii_lowercase().as_str() {
+            "" | "off" | "false" | "0" => None,
+            "auto" => Some(Self::Auto),
+            _ => Some(Self::Fixed(value.to_string())),
+        }
+    }
+}
+
+/// Common words that tell Latin-script languages apart.
+const LATIN_WORDS: &[(&str, &[&str])] = &[
+    (
+        "English",
+        &[
+            "the", "is", "are", "and", "what", "how", "please", "can", "you", "with", "this", "my",
+            "to", "of",
+        ],
+    ),
+    (
+        "Spanish",
+        &[
+            "el", "la", "los", "las", "es", "que", "por", "para", "cómo", "qué", "una", "con",
+            "mi", "del",
+        ],
+    ),
+    (
+        "French",
+        &[
+            "le", "les", "est", "une", "des", "pour", "avec", "que", "je", "vous", "comment",
+            "mon", "du", "sur",
+        ],
+    ),
+    (
+        "German",
+        &[
+            "der", "die", "das", "ist", "und", "ich", "nicht", "mit", "wie", "ein", "eine",
+            "bitte", "mein", "zu",
+        ],
+    ),
+    (
+        "Portuguese",
+        &[
+            "o", "os", "é", "não", "uma", "com", "para", "como", "você", "meu", "do", "da", "em",
+        ],
+    ),
+];
+
+/// Best guess at the language of `text`, or `None` when it is unclear.
+///
+/// A non-Latin script wins when its characters are at least a quarter of
+/// the Latin letters, so a Chinese question about `src/main.rs` still reads
+/// as Chinese.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let mut counts: Vec<(&'static str, usize)> = Vec::new();
+    let mut latin = 0;
+    let mut kana = false;
+    for c in text.chars().filter(|c| c.is_alphabetic()) {
+        let script = match c as u32 {
+            0x3040..=0x30FF => {
+                kana = true;
+                "Japanese"
+            }
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => "Chinese",
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => "Korean",
+            0x0400..=0x04FF => "Russian",
+            0x0600..=0x06FF => "Arabic",
+            0x0590..=0x05FF => "Hebrew",
+            0x0370..=0x03FF => "Greek",
+            0x0E00..=0x0E7F => "Thai",
+            0x0900..=0x097F => "Hindi",
+            _ if c.is_ascii_alphabetic() || ('\u{00C0}'..='\u{024F}').contains(&c) => {
+                latin += 1;
+                continue;
+            }
+            _ => continue,
+        };
+        match counts.iter_mut().find(|(s, _)| *s == script) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+    // Japanese mixes kanji with kana; any kana means Japanese.
+    if kana && let Some(chinese) = counts.iter().position(|(s, _)| *s == "Chinese") {
+        let (_, n) = counts.remove(chinese);
+        if let Some((_, japanese)) = counts.iter_mut().find(|(s, _)| *s == "Japanese") {
+            *japanese += n;
+        }
+    }
+    if let Some((script, n)) = counts.into_iter().max_by_key(|(_, n)| *n)
+        && n * 4 >= latin
+    {
+        return Some(script);
+    }
+
+    let lower = text.to_lowercase();
+    let words: Vec<&str> = lower
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|w| !w.is_empty())
+        .collect();
+    LATIN_WORDS
+        .iter()
+        .map(|(language, common)| {
+            let hits = words.iter().filter(|w| common.contains(w)).count();
+            (*language, hits)
+        })
+        .filter(|(_, hits)| *hits > 0)
+        // Ties go to the language listed first.
+        .rev()
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(language, _)| language)
+}
+
+/// `text` without code fences and the `<file>` / `<command>` blocks added
+/// for mentions and command output, which would read as English.
+fn prose(text: &str) -> std::borrow::Cow<'_, str> {
+    static EMBEDDED: std::sync::LazyLock<regex::Regex> = std::sync::LazyLock::new(|| {
+        regex::Regex::new(r"(?s)```.*?(```|$)|<(file|command)\b[^>]*>.*?</(file|command)>").unwrap()
+    });
+    EMBEDDED.replace_all(text, " ")
+}
+
+/// The system prompt instruction for `policy` given the user's `message`.
+pub fn reply_instruction(policy: Option<&ReplyLanguage>, message: &str) -> Option<String> {
+    match policy? {
+        ReplyLanguage::Fixed(language) => Some(format!(
+            "Always reply in {language}, whatever language the user or tool results are in."
+        )),
+        ReplyLanguage::Auto => detect_language(&prose(message)).map(|language| {
+            format!(
+                "The user is writing in {language}. Reply in {language}, also after tool calls, \
+                 unless the user asks for another language."
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_policies() {
+        assert_eq!(ReplyLanguage::parse("auto"), Some(ReplyLanguage::Auto));
+        assert_eq!(ReplyLanguage::parse("off"), None);
+        assert_eq!(
+            ReplyLanguage::parse(" Chinese "),
+            Some(ReplyLanguage::Fixed("Chinese".to_string()))
+        );
+    }
+
+    #[test]
+    fn detects_scripts_despite_code_identifiers() {
+        assert_eq!(
+            detect_language("帮我修复 src/main.rs 里的 bug"),
+            Some("Chinese")
+        );
+        assert_eq!(detect_language("このファイルを読んで"), Some("Japanese"));
+        assert_eq!(detect_language("東京の天気は？"), Some("Japanese"));
+        assert_eq!(detect_language("이 파일을 읽어줘"), Some("Korean"));
+        assert_eq!(detect_language("Привет, как дела?"), Some("Russian"));
+        assert_eq!(
+            detect_language("Tell me about the poems of 李白"),
+            Some("English")
+        );
+    }
+
+    #[test]
+    fn detects_latin_languages_by_common_words() {
+        assert_eq!(
+            detect_language("What is the weather today?"),
+            Some("English")
+        );
+        assert_eq!(
+            detect_language("¿Qué hora es en la oficina?"),
+            Some("Spanish")
+        );
+        assert_eq!(
+            detect_language("Comment je peux lire le fichier?"),
+            Some("French")
+        );
+        assert_eq!(detect_language("Wie spät ist es bitte?"), Some("German"));
+        assert_eq!(detect_language("ok"), None);
+    }
+
+    #[test]
+    fn instructions_follow_the_policy() {
+        assert_eq!(reply_instruction(None, "你好"), None);
+        let auto = ReplyLanguage::Auto;
+        assert!(
+            reply_instruction(Some(&auto), "今天天气怎么样")
+                .unwrap()
+                .contains("Reply in Chinese")
+        );
+        assert_eq!(reply_instruction(Some(&auto), "ok"), None);
+        let mention = "解释一下 @main.rs\n\n<file path=\"main.rs\">\nfn main() { println!(\"hello world\"); }\n</file>";
+        assert!(
+            reply_instruction(Some(&auto), mention)
+                .unwrap()
+                .contains("Reply in Chinese")
+        );
+        let fixed = ReplyLanguage::Fixed("German".to_string());
+        assert!(
+            reply_instruction(Some(&fixed), "hello")
+                .unwrap()
+                .starts_with("Always reply in German")
+        );
+    }
+}
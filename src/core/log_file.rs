@@ -0,0 +1,302 @@
+//! Persistent JSON-lines log file with rotation (`LOG_FILE`).
+//!
+//! Stderr logging is unchanged; the file gets a second copy of every event
+//! as one JSON object per line, including the span fields such as
+//! `turn_id`. The file is rotated when the next event would take it past
+//! `LOG_FILE_MAX_BYTES`, or at the first event of a new UTC day or hour
+//! (`LOG_FILE_ROTATION`). A rotated file is renamed to
+//! `<name>.<YYYYmmddTHHMMSS>` and only the newest `LOG_FILE_KEEP` of them
+//! are kept.
+//!
+//! Read from the process environment only, because logging starts before
+//! any `.env.local` is loaded.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+
+const LOG_FILE_KEY: &str = "LOG_FILE";
+const LOG_FILE_MAX_BYTES_KEY: &str = "LOG_FILE_MAX_BYTES";
+const LOG_FILE_ROTATION_KEY: &str = "LOG_FILE_ROTATION";
+const LOG_FILE_KEEP_KEY: &str = "LOG_FILE_KEEP";
+
+const DEFAULT_MAX_BYTES: u64 = 50 * 1024 * 1024;
+const DEFAULT_KEEP: usize = 7;
+
+/// When a log file is rotated regardless of its size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Never,
+    Hourly,
+    Daily,
+}
+
+impl Rotation {
+    /// Label of the period `time` falls in; a change means rotate.
+    fn period(self, time: DateTime<Utc>) -> Option<String> {
+        match self {
+            Self::Never => None,
+            Self::Hourly => Some(time.format("%Y-%m-%dT%H").to_string()),
+            Self::Daily => Some(time.format("%Y-%m-%d").to_string()),
+        }
+    }
+}
+
+/// Where and how to keep the log file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFileConfig {
+    pub path: PathBuf,
+    /// Size that triggers a rotation (0: no size limit).
+    pub max_bytes: u64,
+    pub rotation: Rotation,
+    /// Rotated files kept next to the live one.
+    pub keep: usize,
+}
+
+impl LogFileConfig {
+    /// Read the settings from the environment; `None` when `LOG_FILE` is
+    /// unset.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        Self::from_vars(|key| std::env::var(key).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Option<Self>, String> {
+        let var = |key: &str| {
+            var(key)
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+        };
+        let Some(path) = var(LOG_FILE_KEY) else {
+            return Ok(None);
+        };
+        let max_bytes = match var(LOG_FILE_MAX_BYTES_KEY) {
+            Some(v) => v
+                .parse()
+                .map_err(|_| format!("invalid {LOG_FILE_MAX_BYTES_KEY} '{v}'"))?,
+            None => DEFAULT_MAX_BYTES,
+        };
+        let rotation = match var(LOG_FILE_ROTATION_KEY)
+            .map(|v| v.to_ascii_lowercase())
+            .as_deref()
+        {
+            None | Some("daily") => Rotation::Daily,
+            Some("hourly") => Rotation::Hourly,
+            Some("never") | Some("size") => Rotation::Never,
+            Some(other) => {
+                return Err(format!(
+                    "invalid {LOG_FILE_ROTATION_KEY} '{other}': expected daily, hourly or never"
+                ));
+            }
+        };
+        let keep = match var(LOG_FILE_KEEP_KEY) {
+            Some(v) => v
+                .parse()
+                .map_err(|_| format!("invalid {LOG_FILE_KEEP_KEY} '{v}'"))?,
+            None => DEFAULT_KEEP,
+        };
+        Ok(Some(Self {
+            path: PathBuf::from(path),
+            max_bytes,
+            rotation,
+            keep,
+        }))
+    }
+}
+
+/// Appends to the log file, rotating it between writes.
+///
+/// `tracing_subscriber` writes each event with a single `write` call, so
+/// an event is never split across two files.
+pub struct RotatingFile {
+    config: LogFileConfig,
+    file: File,
+    len: u64,
+    period: Option<String>,
+}
+
+impl RotatingFile {
+    /// Open (or create) the log file and its directory.
+    pub fn open(config: LogFileConfig) -> io::Result<Self> {
+        if let Some(dir) = config.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let file = open_append(&config.path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata
+            .modified()
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+        Ok(Self {
+            period: config.rotation.period(modified),
+            len: metadata.len(),
+            file,
+            config,
+        })
+    }
+
+    fn needs_rotation(&self, incoming: usize, now: DateTime<Utc>) -> bool {
+        if self.len == 0 {
+            return false;
+        }
+        let too_big =
+            self.config.max_bytes > 0 && self.len + incoming as u64 > self.config.max_bytes;
+        too_big || self.config.rotation.period(now) != self.period
+    }
+
+    fn rotate(&mut self, now: DateTime<Utc>) -> io::Result<()> {
+        self.file.flush()?;
+        let stamp = now.format("%Y%m%dT%H%M%S").to_string();
+        let mut target = suffixed(&self.config.path, &stamp);
+        let mut n = 1;
+        while target.exists() {
+            target = suffixed(&self.config.path, &format!("{stamp}-{n}"));
+            n += 1;
+        }
+        fs::rename(&self.config.path, &target)?;
+        self.file = open_append(&self.config.path)?;
+        self.len = 0;
+        self.prune();
+        Ok(())
+    }
+
+    /// Delete rotated files beyond the newest `keep`.
+    fn prune(&self) {
+        let Some(name) = self.config.path.file_name().and_then(|n| n.to_str()) else {
+            return;
+        };
+        let dir = match self.config.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let prefix = format!("{name}.");
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        let mut rotated: Vec<(std::time::SystemTime, PathBuf)> = entries
+            .flatten()
+            .filter(|e| {
+                e.file_name()
+                    .to_str()
+                    .is_some_and(|n| n.starts_with(&prefix))
+            })
+            .filter_map(|e| Some((e.metadata().ok()?.modified().ok()?, e.path())))
+            .collect();
+        rotated.sort();
+        let excess = rotated.len().saturating_sub(self.config.keep);
+        for (_, path) in rotated.into_iter().take(excess) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = Utc::now();
+        if self.needs_rotation(buf.len(), now) {
+            self.rotate(now)?;
+        }
+        self.period = self.config.rotation.period(now);
+        let written = self.file.write(buf)?;
+        self.len += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{suffix}"));
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn settings_come_from_the_environment() {
+        let vars = |pairs: &[(&str, &str)]| {
+            let map: HashMap<String, String> = pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect();
+            LogFileConfig::from_vars(move |key| map.get(key).cloned())
+        };
+        assert_eq!(vars(&[]), Ok(None));
+        assert_eq!(
+            vars(&[("LOG_FILE", "logs/crabclaw.log")]),
+            Ok(Some(LogFileConfig {
+                path: PathBuf::from("logs/crabclaw.log"),
+                max_bytes: DEFAULT_MAX_BYTES,
+                rotation: Rotation::Daily,
+                keep: DEFAULT_KEEP,
+            }))
+        );
+        let config = vars(&[
+            ("LOG_FILE", "a.log"),
+            ("LOG_FILE_MAX_BYTES", "0"),
+            ("LOG_FILE_ROTATION", "Hourly"),
+            ("LOG_FILE_KEEP", "2"),
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            (config.max_bytes, config.rotation, config.keep),
+            (0, Rotation::Hourly, 2)
+        );
+        assert!(vars(&[("LOG_FILE", "a.log"), ("LOG_FILE_ROTATION", "weekly")]).is_err());
+        assert!(vars(&[("LOG_FILE", "a.log"), ("LOG_FILE_KEEP", "-1")]).is_err());
+    }
+
+    #[test]
+    fn rotates_by_size_and_keeps_the_newest_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs/crabclaw.log");
+        let mut file = RotatingFile::open(LogFileConfig {
+            path: path.clone(),
+            max_bytes: 20,
+            rotation: Rotation::Never,
+            keep: 2,
+        })
+        .unwrap();
+        for i in 0..5 {
+            file.write_all(format!("{{\"event\":{i}}}\n").as_bytes())
+                .unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"event\":4}\n");
+        let rotated: Vec<_> = fs::read_dir(path.parent().unwrap())
+            .unwrap()
+            .flatten()
+            .filter(|e| e.path() != path)
+            .collect();
+        assert_eq!(rotated.len(), 2);
+    }
+
+    #[test]
+    fn rotates_when_the_period_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut file = RotatingFile::open(LogFileConfig {
+            path: dir.path().join("crabclaw.log"),
+            max_bytes: 0,
+            rotation: Rotation::Daily,
+            keep: 7,
+        })
+        .unwrap();
+        let now = Utc::now();
+        assert!(!file.needs_rotation(10, now));
+        file.write_all(b"line\n").unwrap();
+        assert!(!file.needs_rotation(10, now));
+        assert!(file.needs_rotation(10, now + chrono::Duration::days(1)));
+    }
+}
@@ -0,0 +1,175 @@
+//! `@path` file mentions in user input.
+//!
+//! A word starting with `@` that names a file in the workspace is expanded
+//! into a `<file>` block appended to the prompt, so the model sees the file
+//! without a `file.read` round trip. Mentions that do not resolve to a
+//! readable text file (Telegram `@usernames`, e-mail addresses, paths
+//! outside the workspace) are left alone. File contents count against the
+//! tool output limits: each file is cut to the `file.read` limit and all of
+//! them together to the per-turn budget.
+
+use std::path::Path;
+
+use crate::core::config::ToolOutputLimits;
+use crate::core::utils::safe_truncate;
+use crate::tools::file_ops::resolve_safe_path;
+
+/// Files expanded per message; further mentions stay plain text.
+const MAX_MENTIONS: usize = 10;
+
+/// Path completions offered at once.
+const MAX_COMPLETIONS: usize = 50;
+
+/// Characters that end a sentence rather than a path.
+const TRAILING_PUNCTUATION: &[char] = &['.', ',', ';', ':', '!', '?', ')', '"', '\''];
+
+/// Paths mentioned with `@` in `input`, in order and without duplicates.
+pub fn find_mentions(input: &str) -> Vec<&str> {
+    let mut mentions: Vec<&str> = Vec::new();
+    let mut prev = ' ';
+    for (i, c) in input.char_indices() {
+        if c == '@' && (prev.is_whitespace() || prev == '(') {
+            let rest = &input[i + 1..];
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let path = rest[..end].trim_end_matches(TRAILING_PUNCTUATION);
+            if !path.is_empty() && !mentions.contains(&path) {
+                mentions.push(path);
+            }
+        }
+        prev = c;
+    }
+    mentions
+}
+
+/// `input` followed by a `<file>` block for every mentioned workspace file.
+pub fn expand_mentions(input: &str, workspace: &Path, limits: &ToolOutputLimits) -> String {
+    let per_file = limits.for_tool("file.read");
+    let mut remaining = limits.turn_bytes;
+    let mut blocks = Vec::new();
+    for mention in find_mentions(input).into_iter().take(MAX_MENTIONS) {
+        let Some(path) = resolve_safe_path(workspace, mention).filter(|p| p.is_file()) else {
+            continue;
+        };
+        // Binary and unreadable files are skipped like unknown paths.
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        let allowed = per_file.min(remaining);
+        let shown = safe_truncate(&content, allowed);
+        remaining -= shown.len();
+        let note = if shown.len() < content.len() {
+            format!(
+                "\n[truncated: showing {} of {} bytes; call file.read for the whole file]",
+                shown.len(),
+                content.len()
+            )
+        } else {
+            String::new()
+        };
+        blocks.push(format!("<file path=\"{mention}\">\n{shown}{note}\n</file>"));
+        if remaining == 0 {
+            break;
+        }
+    }
+    if blocks.is_empty() {
+        return input.to_string();
+    }
+    format!("{input}\n\n{}", blocks.join("\n\n"))
+}
+
+/// Completions for an `@path` word ending at byte `pos` of `line`.
+///
+/// Returns the byte offset where the path starts (just after the `@`) and
+/// matching workspace paths, with a trailing `/` on directories.
+pub fn complete_mention(workspace: &Path, line: &str, pos: usize) -> Option<(usize, Vec<String>)> {
+    let before = line.get(..pos)?;
+    let word_start = before.rfind(char::is_whitespace).map_or(0, |i| {
+        i + before[i..].chars().next().map_or(1, char::len_utf8)
+    });
+    let partial = before[word_start..].strip_prefix('@')?;
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(i) => (&partial[..=i], &partial[i + 1..]),
+        None => ("", partial),
+    };
+    let dir_path = if dir.is_empty() {
+        workspace.to_path_buf()
+    } else {
+        resolve_safe_path(workspace, dir)?
+    };
+
+    let mut candidates: Vec<String> = std::fs::read_dir(dir_path)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let slash = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{dir}{name}{slash}"))
+        })
+        .collect();
+    candidates.sort();
+    candidates.truncate(MAX_COMPLETIONS);
+    Some((word_start + 1, candidates))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn mentions_need_a_word_boundary() {
+        assert_eq!(
+            find_mentions("look at @src/main.rs, and (@Cargo.toml) @src/main.rs"),
+            vec!["src/main.rs", "Cargo.toml"]
+        );
+        assert!(find_mentions("mail me@example.com").is_empty());
+        assert!(find_mentions("just @").is_empty());
+    }
+
+    #[test]
+    fn workspace_files_are_expanded_within_limits() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello notes").unwrap();
+        std::fs::write(dir.path().join("big.txt"), "x".repeat(100)).unwrap();
+        let limits = ToolOutputLimits {
+            tool_bytes: 40,
+            ..ToolOutputLimits::default()
+        };
+
+        let prompt = expand_mentions(
+            "compare @notes.txt with @big.txt and ask @someone",
+            dir.path(),
+            &limits,
+        );
+        assert!(prompt.starts_with("compare @notes.txt with @big.txt and ask @someone\n\n"));
+        assert!(prompt.contains("<file path=\"notes.txt\">\nhello notes\n</file>"));
+        assert!(prompt.contains("showing 40 of 100 bytes"), "{prompt}");
+        assert!(!prompt.contains("someone\">"));
+
+        let outside = expand_mentions("see @../etc/passwd", dir.path(), &limits);
+        assert_eq!(outside, "see @../etc/passwd");
+    }
+
+    #[test]
+    fn completion_lists_matching_paths() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "").unwrap();
+        std::fs::write(dir.path().join("src/mod.rs"), "").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), "").unwrap();
+
+        let line = "explain @s";
+        let (start, candidates) = complete_mention(dir.path(), line, line.len()).unwrap();
+        assert_eq!(start, 9);
+        assert_eq!(candidates, vec!["secret.txt", "src/"]);
+
+        let line = "explain @src/ma";
+        let (_, candidates) = complete_mention(dir.path(), line, line.len()).unwrap();
+        assert_eq!(candidates, vec!["src/main.rs"]);
+
+        assert!(complete_mention(dir.path(), "no mention", 10).is_none());
+    }
+}
@@ -1,11 +1,28 @@
 pub mod agent_loop;
+pub mod audit;
 pub mod auth;
 pub mod command;
 pub mod config;
+pub mod consensus;
 pub mod context;
+pub mod dialog;
 pub mod error;
 pub mod input;
+pub mod json_schema;
+pub mod language;
+pub mod log_file;
+pub mod mentions;
 pub mod model_runner;
+pub mod persona;
+pub mod reply_context;
 pub mod router;
+pub mod sandbox;
+pub mod self_update;
 pub mod shell;
+pub mod shell_policy;
+pub mod snapshot;
+pub mod stats;
+pub mod structured;
+pub mod title;
+pub mod trace;
 pub mod utils;
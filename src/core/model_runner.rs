@@ -2,17 +2,23 @@
 //!
 //! Replaces the inlined tool-calling loops in process_message, run_interactive,
 //! and run_command. Channels call `run_turn` (non-streaming) or
-//! `run_turn_stream` (streaming with callback) for each model turn.
+//! `run_turn_stream` (streaming with [`StreamEvent`] callback) for each model
+//! turn.
 
 use std::path::Path;
+use std::time::Instant;
 
-use tracing::{debug, info, instrument};
+use serde::Serialize;
+use tracing::{debug, info, instrument, warn};
 
-use crate::core::config::AppConfig;
+use crate::core::config::{AppConfig, StreamRetry};
 use crate::llm::api_types::{
-    ChatRequest, Message, StreamChunk, ToolCall, ToolCallFunction, ToolDefinition,
+    ChatRequest, Message, StreamChunk, ToolCall, ToolCallFunction, ToolChoice, ToolDefinition,
+    Usage, is_truncated,
 };
+use crate::llm::json_repair::{ArgsRepair, repair_json};
 use crate::tape::store::TapeStore;
+use crate::tools::output::ToolOutput;
 use crate::tools::registry::ToolContext;
 
 /// Default maximum tool-calling rounds per turn.
@@ -20,6 +26,108 @@ use crate::tools::registry::ToolContext;
 /// enough headroom without risking runaway loops.
 const DEFAULT_MAX_TOOL_ITERATIONS: usize = 15;
 
+/// Follow-up prompt sent when an answer is cut off by the output token limit
+/// or by a broken stream.
+const CONTINUE_PROMPT: &str =
+    "Continue exactly where you left off. Do not repeat anything you already wrote.";
+
+/// Tape event recording a streamed answer cut off by an error
+/// ([`PartialResponse`]).
+pub const TURN_PARTIAL_EVENT: &str = "turn.partial";
+
+/// Tape event recording one executed tool call ([`ToolCallRecord`]).
+pub const TOOL_CALL_EVENT: &str = "tool.call";
+
+/// Tape event recording how long one turn took ([`TurnTiming`]).
+pub const TURN_TIMING_EVENT: &str = "turn.timing";
+
+/// Longest tool result kept in a `tool.call` event, in bytes.
+const MAX_RECORDED_RESULT_BYTES: usize = 4_000;
+
+/// Progress reported by [`ModelRunner::run_turn_stream`] while a turn runs.
+#[derive(Debug, Clone, Copy)]
+pub enum StreamEvent<'a> {
+    /// A chunk of assistant text.
+    Token(&'a str),
+    /// The model asked for a tool call, which is about to run.
+    ToolCall(&'a ToolCall),
+    /// A tool call finished with this (full) result.
+    ToolResult { call: &'a ToolCall, result: &'a str },
+    /// The stream broke off and is requested again from the start
+    /// (`STREAM_RETRY=restart`); text streamed before this is discarded.
+    Restart,
+}
+
+/// Shown in streamed text when an answer starts over after [`StreamEvent::Restart`].
+pub const RESTART_NOTICE: &str = "\n\n[connection lost, restarting the answer]\n\n";
+
+/// A tool call made during a turn, with its (possibly truncated) result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ToolCallRecord {
+    pub tool: String,
+    pub arguments: String,
+    pub result: String,
+    /// The typed result, for anything other than plain text.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<ToolOutput>,
+}
+
+impl ToolCallRecord {
+    fn new(call: &ToolCall, result: &str, output: ToolOutput) -> Self {
+        Self {
+            tool: call.function.name.clone(),
+            arguments: call.function.arguments.clone(),
+            result: crate::core::utils::safe_truncate(result, MAX_RECORDED_RESULT_BYTES)
+                .to_string(),
+            output: (!matches!(output, ToolOutput::Text(_))).then_some(output),
+        }
+    }
+}
+
+/// Text streamed before the stream failed, kept rather than discarded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PartialResponse {
+    pub text: String,
+    pub error: String,
+    /// Whether a continuation request was sent to finish the answer.
+    pub resumed: bool,
+}
+
+/// How long a turn took, in milliseconds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TurnTiming {
+    /// Until the first streamed chunk, or the first response when the turn
+    /// is not streamed.
+    pub first_token_ms: Option<u64>,
+    /// The whole turn, tool calls included.
+    pub total_ms: u64,
+    /// Spent running tools.
+    pub tool_ms: u64,
+    pub tool_calls: usize,
+}
+
+impl TurnTiming {
+    /// `1.2s first token · 8.4s total · 2 tool calls`
+    pub fn footer(&self) -> String {
+        let secs = |ms: u64| format!("{:.1}s", ms as f64 / 1000.0);
+        let mut parts = Vec::new();
+        if let Some(first) = self.first_token_ms {
+            parts.push(format!("{} first token", secs(first)));
+        }
+        parts.push(format!("{} total", secs(self.total_ms)));
+        match self.tool_calls {
+            0 => {}
+            1 => parts.push("1 tool call".to_string()),
+            n => parts.push(format!("{n} tool calls")),
+        }
+        parts.join(" · ")
+    }
+}
+
+fn elapsed_ms(since: Instant) -> u64 {
+    since.elapsed().as_millis() as u64
+}
+
 /// Result of a single model turn (may include multiple tool-call rounds).
 #[derive(Debug, Default)]
 pub struct ModelTurnResult {
@@ -31,6 +139,40 @@ pub struct ModelTurnResult {
     pub invoked_tools: Vec<String>,
     /// Error if any occurred during the turn.
     pub error: Option<String>,
+    /// Token usage summed over all rounds, when the provider reports it.
+    pub usage: Option<Usage>,
+    /// Tool calls whose malformed arguments were repaired before running.
+    pub repaired_args: Vec<ArgsRepair>,
+    /// Every tool call executed, in order.
+    pub tool_calls: Vec<ToolCallRecord>,
+    /// Streams that broke off mid-answer, in order.
+    pub partial_responses: Vec<PartialResponse>,
+    pub timing: TurnTiming,
+}
+
+impl ModelTurnResult {
+    /// Fold in a follow-up turn on the same input (e.g. a retry): tool
+    /// rounds, tools and usage add up, the answer and error are the
+    /// follow-up's.
+    pub fn absorb(&mut self, next: ModelTurnResult) {
+        self.assistant_text = next.assistant_text;
+        self.error = next.error;
+        self.tool_rounds += next.tool_rounds;
+        self.repaired_args.extend(next.repaired_args);
+        self.tool_calls.extend(next.tool_calls);
+        self.partial_responses.extend(next.partial_responses);
+        self.timing.total_ms += next.timing.total_ms;
+        self.timing.tool_ms += next.timing.tool_ms;
+        self.timing.tool_calls += next.timing.tool_calls;
+        for tool in &next.invoked_tools {
+            push_unique_tool(&mut self.invoked_tools, tool);
+        }
+        if let Some(usage) = &next.usage {
+            self.usage
+                .get_or_insert_with(Usage::default)
+                .accumulate(usage);
+        }
+    }
 }
 
 /// Unified model turn runner with tool-calling loop.
@@ -44,6 +186,8 @@ pub struct ModelRunner<'a> {
     config: &'a AppConfig,
     workspace: &'a Path,
     max_tool_iterations: usize,
+    temperature: Option<f32>,
+    tool_choice: Option<ToolChoice>,
 }
 
 impl<'a> ModelRunner<'a> {
@@ -53,6 +197,8 @@ impl<'a> ModelRunner<'a> {
             config,
             workspace,
             max_tool_iterations: DEFAULT_MAX_TOOL_ITERATIONS,
+            temperature: None,
+            tool_choice: None,
         }
     }
 
@@ -63,10 +209,70 @@ impl<'a> ModelRunner<'a> {
         self
     }
 
+    /// Set the sampling temperature sent with each request.
+    pub fn with_temperature(mut self, temperature: Option<f32>) -> Self {
+        self.temperature = temperature;
+        self
+    }
+
+    /// Set the `tool_choice` for the turn's first request (e.g. `Any` to
+    /// make the model start by calling a tool). Follow-up requests after
+    /// tool results go back to the provider default so the model can answer.
+    pub fn with_tool_choice(mut self, tool_choice: Option<ToolChoice>) -> Self {
+        self.tool_choice = tool_choice;
+        self
+    }
+
+    /// Execute one tool call, or explain to the model why its arguments
+    /// were rejected.
+    fn call_tool(&self, tc: &ToolCall, tape: &TapeStore, tool_ctx: &ToolContext) -> ToolOutput {
+        if let Some(error) =
+            crate::tools::registry::check_tool_arguments(&tc.function.name, &tc.function.arguments)
+        {
+            warn!(tool = %tc.function.name, "model_runner.tool_arguments_invalid");
+            return error.into();
+        }
+        crate::tools::registry::execute_tool_output(
+            &tc.function.name,
+            &tc.function.arguments,
+            tape,
+            self.workspace,
+            tool_ctx,
+        )
+    }
+
+    /// Snapshot the workspace before the turn's first round of tool calls
+    /// that is a large plan ([`crate::core::snapshot::is_large_plan`]), so
+    /// it can be rolled back with `,snapshot restore`.
+    fn snapshot_large_plan(&self, tool_calls: &[ToolCall], snapshotted: &mut bool) {
+        if *snapshotted
+            || !crate::core::snapshot::is_large_plan(
+                tool_calls.iter().map(|tc| tc.function.name.as_str()),
+            )
+        {
+            return;
+        }
+        *snapshotted = true;
+        crate::core::snapshot::create_before_run(
+            self.workspace,
+            &format!("before a plan of {} tool calls", tool_calls.len()),
+        );
+    }
+
     /// Run a **non-streaming** model turn with tool calling loop.
     ///
     /// This is the async path used by Telegram and test harness.
     /// Returns the final assistant text after all tool rounds.
+    /// Tool definitions to send, or `None` when the model cannot call tools.
+    fn request_tools(&self, tools: Option<&[ToolDefinition]>) -> Option<Vec<ToolDefinition>> {
+        let tools = tools?;
+        if !crate::llm::models::capabilities(self.config, &self.config.model).supports_tools {
+            warn!(model = %self.config.model, "model_runner.tools_unsupported");
+            return None;
+        }
+        Some(tools.to_vec())
+    }
+
     #[instrument(skip_all, fields(model = %self.config.model, msg_count = messages.len()))]
     pub async fn run_turn(
         &self,
@@ -76,19 +282,35 @@ impl<'a> ModelRunner<'a> {
         tool_ctx: &ToolContext,
     ) -> ModelTurnResult {
         let mut result = ModelTurnResult::default();
+        let started = Instant::now();
 
-        let tools_vec = tools.map(|t| t.to_vec());
+        let tools_vec = self.request_tools(tools);
+        let mut continuations = 0;
+        let mut snapshotted = false;
 
         for iteration in 0..self.max_tool_iterations {
             let request = ChatRequest {
                 model: self.config.model.clone(),
                 messages: messages.clone(),
                 max_tokens: None,
+                tool_choice: self.tool_choice.clone().filter(|_| iteration == 0),
+                temperature: self.temperature,
                 tools: tools_vec.clone(),
             };
 
             match crate::llm::client::send_chat_request(self.config, &request).await {
                 Ok(chat_response) => {
+                    result
+                        .timing
+                        .first_token_ms
+                        .get_or_insert_with(|| elapsed_ms(started));
+                    if let Some(usage) = &chat_response.usage {
+                        result
+                            .usage
+                            .get_or_insert_with(Usage::default)
+                            .accumulate(usage);
+                    }
+
                     // Check if model wants to call tools
                     if let Some(tool_calls) = chat_response.tool_calls() {
                         info!(
@@ -96,25 +318,37 @@ impl<'a> ModelRunner<'a> {
                             tool_count = tool_calls.len(),
                             "model_runner.tool_calls"
                         );
+                        let mut tool_calls = tool_calls.to_vec();
+                        let truncated = is_truncated(
+                            chat_response
+                                .choices
+                                .first()
+                                .and_then(|c| c.finish_reason.as_deref()),
+                        );
+                        repair_arguments(&mut tool_calls, truncated, &mut result.repaired_args);
 
                         // Append the assistant message with tool_calls to context
-                        messages.push(Message::assistant_with_tool_calls(tool_calls.to_vec()));
+                        messages.push(Message::assistant_with_tool_calls(tool_calls.clone()));
+                        self.snapshot_large_plan(&tool_calls, &mut snapshotted);
 
                         // Execute each tool and append results
-                        for tc in tool_calls {
+                        for tc in &tool_calls {
                             push_unique_tool(&mut result.invoked_tools, &tc.function.name);
-                            let tool_result = crate::tools::registry::execute_tool(
-                                &tc.function.name,
-                                &tc.function.arguments,
-                                tape,
-                                self.workspace,
-                                tool_ctx,
-                            );
+                            let tool_started = Instant::now();
+                            let tool_output = self.call_tool(tc, tape, tool_ctx);
+                            let tool_result = tool_output.for_model();
+                            result.timing.tool_ms += elapsed_ms(tool_started);
+                            result.timing.tool_calls += 1;
                             debug!(
                                 tool = %tc.function.name,
                                 result_len = tool_result.len(),
                                 "model_runner.tool_result"
                             );
+                            result.tool_calls.push(ToolCallRecord::new(
+                                tc,
+                                &tool_result,
+                                tool_output,
+                            ));
                             messages.push(Message::tool(&tc.id, &tool_result));
                         }
 
@@ -123,13 +357,20 @@ impl<'a> ModelRunner<'a> {
                     }
 
                     // No tool calls — we have the final response
-                    if let Some(content) = chat_response.assistant_content() {
-                        result.assistant_text = content.to_string();
+                    let content = chat_response.assistant_content().unwrap_or_default();
+                    result.assistant_text.push_str(content);
+                    let finish_reason = chat_response
+                        .choices
+                        .first()
+                        .and_then(|c| c.finish_reason.as_deref());
+                    if self.continue_truncated(finish_reason, &mut continuations, messages, content)
+                    {
+                        continue;
                     }
                     break;
                 }
                 Err(e) => {
-                    result.error = Some(format!("{e}"));
+                    result.error = Some(e.user_message());
                     break;
                 }
             }
@@ -145,13 +386,15 @@ impl<'a> ModelRunner<'a> {
             ));
         }
 
+        result.timing.total_ms = elapsed_ms(started);
         result
     }
 
     /// Run a **streaming** model turn with tool calling loop.
     ///
-    /// Used by CLI and REPL. Calls `on_token` for each streamed text chunk.
-    /// After all tool rounds, returns the final result.
+    /// Used by CLI and REPL. Calls `on_event` for each streamed text chunk
+    /// and around each tool call. After all tool rounds, returns the final
+    /// result.
     #[instrument(skip_all, fields(model = %self.config.model, msg_count = messages.len()))]
     pub async fn run_turn_stream<F>(
         &self,
@@ -159,19 +402,28 @@ impl<'a> ModelRunner<'a> {
         tools: Option<&[ToolDefinition]>,
         tape: &TapeStore,
         tool_ctx: &ToolContext,
-        mut on_token: F,
+        mut on_event: F,
     ) -> ModelTurnResult
     where
-        F: FnMut(&str),
+        F: FnMut(StreamEvent<'_>),
     {
         let mut result = ModelTurnResult::default();
-        let tools_vec = tools.map(|t| t.to_vec());
+        let started = Instant::now();
+        let tools_vec = self.request_tools(tools);
+        let mut continuations = 0;
+        let mut snapshotted = false;
+        let mut stream_retries = 0;
+        // Leading whitespace to drop from the continuation of an answer
+        // resumed as an assistant prefix.
+        let mut trim_resumed = false;
 
         for iteration in 0..self.max_tool_iterations {
             let request = ChatRequest {
                 model: self.config.model.clone(),
                 messages: messages.clone(),
                 max_tokens: None,
+                tool_choice: self.tool_choice.clone().filter(|_| iteration == 0),
+                temperature: self.temperature,
                 tools: tools_vec.clone(),
             };
 
@@ -182,15 +434,34 @@ impl<'a> ModelRunner<'a> {
                 Ok(mut rx) => {
                     let mut full_content = String::new();
                     let mut tool_calls = Vec::<ToolCall>::new();
+                    let mut finish_reason = None;
+                    let mut interrupted = None;
 
                     while let Some(chunk_res) = rx.recv().await {
                         match chunk_res {
                             Ok(chunk) => match chunk {
                                 StreamChunk::Content(text) => {
-                                    on_token(&text);
-                                    full_content.push_str(&text);
+                                    result
+                                        .timing
+                                        .first_token_ms
+                                        .get_or_insert_with(|| elapsed_ms(started));
+                                    let text = if trim_resumed {
+                                        trim_resumed = text.trim_start().is_empty();
+                                        text.trim_start()
+                                    } else {
+                                        text.as_str()
+                                    };
+                                    if text.is_empty() {
+                                        continue;
+                                    }
+                                    on_event(StreamEvent::Token(text));
+                                    full_content.push_str(text);
                                 }
                                 StreamChunk::ToolCallStart { index, id, name } => {
+                                    result
+                                        .timing
+                                        .first_token_ms
+                                        .get_or_insert_with(|| elapsed_ms(started));
                                     if tool_calls.len() <= index {
                                         tool_calls.resize(
                                             index + 1,
@@ -213,17 +484,60 @@ impl<'a> ModelRunner<'a> {
                                         tool_calls[index].function.arguments.push_str(&text);
                                     }
                                 }
+                                StreamChunk::Finish(reason) => {
+                                    finish_reason = Some(reason);
+                                }
                                 StreamChunk::Done => {
                                     break;
                                 }
                             },
                             Err(e) => {
-                                result.error = Some(format!("{e}"));
-                                return result;
+                                interrupted = Some(e);
+                                break;
                             }
                         }
                     }
 
+                    if let Some(e) = interrupted {
+                        let retry = self.stream_retry(stream_retries, &full_content, &tool_calls);
+                        let error = e.user_message();
+                        if retry != Some(StreamRetry::Restart) && !full_content.is_empty() {
+                            // Keep what was streamed.
+                            result.assistant_text.push_str(&full_content);
+                            result.partial_responses.push(PartialResponse {
+                                text: full_content.clone(),
+                                error: error.clone(),
+                                resumed: retry.is_some(),
+                            });
+                        }
+                        let Some(mode) = retry else {
+                            warn!(retries = stream_retries, "model_runner.stream.interrupted");
+                            result.error = Some(error);
+                            result.timing.total_ms = elapsed_ms(started);
+                            return result;
+                        };
+                        let delay = crate::llm::client::retry_delay(
+                            &e,
+                            self.config.llm_retry.delay(stream_retries),
+                        );
+                        stream_retries += 1;
+                        info!(
+                            retry = stream_retries,
+                            mode = ?mode,
+                            partial_len = full_content.len(),
+                            delay_ms = delay.as_millis() as u64,
+                            error = %e,
+                            "model_runner.stream.retry"
+                        );
+                        tokio::time::sleep(delay).await;
+                        if mode == StreamRetry::Resume {
+                            trim_resumed = self.resume_interrupted(messages, &full_content);
+                        } else if !full_content.is_empty() {
+                            on_event(StreamEvent::Restart);
+                        }
+                        continue;
+                    }
+
                     // If we got tool calls, execute them and loop
                     if !tool_calls.is_empty() {
                         info!(
@@ -231,23 +545,37 @@ impl<'a> ModelRunner<'a> {
                             tool_count = tool_calls.len(),
                             "model_runner.stream.tool_calls"
                         );
+                        repair_arguments(
+                            &mut tool_calls,
+                            is_truncated(finish_reason.as_deref()),
+                            &mut result.repaired_args,
+                        );
 
                         messages.push(Message::assistant_with_tool_calls(tool_calls.clone()));
+                        self.snapshot_large_plan(&tool_calls, &mut snapshotted);
 
                         for tc in &tool_calls {
                             push_unique_tool(&mut result.invoked_tools, &tc.function.name);
-                            let tool_result = crate::tools::registry::execute_tool(
-                                &tc.function.name,
-                                &tc.function.arguments,
-                                tape,
-                                self.workspace,
-                                tool_ctx,
-                            );
+                            on_event(StreamEvent::ToolCall(tc));
+                            let tool_started = Instant::now();
+                            let tool_output = self.call_tool(tc, tape, tool_ctx);
+                            let tool_result = tool_output.for_model();
+                            result.timing.tool_ms += elapsed_ms(tool_started);
+                            result.timing.tool_calls += 1;
                             debug!(
                                 tool = %tc.function.name,
                                 result_len = tool_result.len(),
                                 "model_runner.stream.tool_result"
                             );
+                            on_event(StreamEvent::ToolResult {
+                                call: tc,
+                                result: &tool_result,
+                            });
+                            result.tool_calls.push(ToolCallRecord::new(
+                                tc,
+                                &tool_result,
+                                tool_output,
+                            ));
                             messages.push(Message::tool(&tc.id, &tool_result));
                         }
 
@@ -256,11 +584,19 @@ impl<'a> ModelRunner<'a> {
                     }
 
                     // No tool calls — we have the final response
-                    result.assistant_text = full_content;
+                    result.assistant_text.push_str(&full_content);
+                    if self.continue_truncated(
+                        finish_reason.as_deref(),
+                        &mut continuations,
+                        messages,
+                        &full_content,
+                    ) {
+                        continue;
+                    }
                     break;
                 }
                 Err(e) => {
-                    result.error = Some(format!("{e}"));
+                    result.error = Some(e.user_message());
                     break;
                 }
             }
@@ -276,8 +612,99 @@ impl<'a> ModelRunner<'a> {
             ));
         }
 
+        result.timing.total_ms = elapsed_ms(started);
         result
     }
+
+    /// When an answer was cut off by the output token limit, queue a
+    /// "continue" request after the partial text and return `true`, at most
+    /// `max_continuations` times per turn. The caller stitches the parts.
+    fn continue_truncated(
+        &self,
+        finish_reason: Option<&str>,
+        continuations: &mut u32,
+        messages: &mut Vec<Message>,
+        partial: &str,
+    ) -> bool {
+        if !is_truncated(finish_reason) {
+            return false;
+        }
+        if *continuations >= self.config.max_continuations {
+            warn!(continuations = *continuations, "model_runner.truncated");
+            return false;
+        }
+        *continuations += 1;
+        info!(
+            continuation = *continuations,
+            "model_runner.continue_truncated"
+        );
+        messages.push(Message::assistant(partial));
+        messages.push(Message::user(CONTINUE_PROMPT));
+        true
+    }
+
+    /// How to retry a stream that broke off after `partial` text and
+    /// `tool_calls`, or `None` to give up (`STREAM_RETRY`, `LLM_MAX_RETRIES`).
+    ///
+    /// Only plain text can be resumed; a stream that broke off before any
+    /// text or inside a tool call is restarted.
+    fn stream_retry(
+        &self,
+        retries: u32,
+        partial: &str,
+        tool_calls: &[ToolCall],
+    ) -> Option<StreamRetry> {
+        let policy = self.config.llm_retry;
+        if policy.stream == StreamRetry::Off || retries >= policy.max_retries {
+            return None;
+        }
+        if policy.stream == StreamRetry::Resume && !partial.is_empty() && tool_calls.is_empty() {
+            Some(StreamRetry::Resume)
+        } else {
+            Some(StreamRetry::Restart)
+        }
+    }
+
+    /// Queue the request that continues an answer cut off after `partial`.
+    /// The caller stitches the parts.
+    ///
+    /// Anthropic continues a trailing assistant message as a prefix (without
+    /// trailing whitespace, which it rejects), so the continuation's leading
+    /// whitespace is dropped and `true` returned. Other providers get the
+    /// text back with a "continue" prompt.
+    fn resume_interrupted(&self, messages: &mut Vec<Message>, partial: &str) -> bool {
+        if self.config.model.starts_with("anthropic:") {
+            messages.push(Message::assistant(partial.trim_end()));
+            return partial.trim_end().len() < partial.len();
+        }
+        messages.push(Message::assistant(partial));
+        messages.push(Message::user(CONTINUE_PROMPT));
+        false
+    }
+}
+
+/// Fix almost-JSON tool arguments in place (see `llm::json_repair`), so
+/// both the tool and the assistant message in the history get valid JSON.
+///
+/// Nothing is repaired when the response was `truncated` at the token
+/// limit: the arguments may be cut off anywhere, so the calls fail argument
+/// validation and the model is asked to send them again.
+fn repair_arguments(tool_calls: &mut [ToolCall], truncated: bool, repairs: &mut Vec<ArgsRepair>) {
+    if truncated {
+        return;
+    }
+    for tc in tool_calls {
+        if let Some(repaired) = repair_json(&tc.function.arguments) {
+            warn!(tool = %tc.function.name, "model_runner.tool_arguments_repaired");
+            let original = std::mem::replace(&mut tc.function.arguments, repaired.clone());
+            repairs.push(ArgsRepair {
+                tool: tc.function.name.clone(),
+                call_id: tc.id.clone(),
+                original,
+                repaired,
+            });
+        }
+    }
 }
 
 fn push_unique_tool(tools: &mut Vec<String>, name: &str) {
@@ -298,11 +725,56 @@ mod tests {
             api_base: String::new(),
             model: String::new(),
             system_prompt: None,
+            system_prompt_file: None,
+            skills: Vec::new(),
+            reply_language: None,
+            timezone: None,
             telegram_token: None,
             telegram_allow_from: Vec::new(),
             telegram_allow_chats: Vec::new(),
             telegram_proxy: None,
+            telegram_persona: None,
+            telegram_output_filters: Vec::new(),
             max_context_messages: 50,
+            skill_top_k: None,
+            persona: None,
+            schedule_report: false,
+            schedule_max_retries: 2,
+            schedule_retry_backoff_seconds: 30,
+            schedule_max_concurrent_jobs: 2,
+            schedule_jitter_seconds: 0,
+            quiet_hours: None,
+            telegram_rate_limit_per_minute: None,
+            telegram_session_ttl_hours: None,
+            telegram_max_concurrent_turns: None,
+            telegram_workers: 8,
+            agent_cache_size: 0,
+            tape_sync: Default::default(),
+            telegram_admins: vec![],
+            telegram_user_workspace_root: None,
+            model_capabilities: None,
+            session_titles: crate::core::title::TitleMode::Off,
+            workspaces: Vec::new(),
+            command_aliases: Vec::new(),
+            command_prefix: ',',
+            telegram_command_prefix: ',',
+            max_continuations: 2,
+            http_pools: Default::default(),
+            llm_timeouts: Default::default(),
+            llm_retry: Default::default(),
+            llm_cassette: None,
+            dump_dir: None,
+            assistant_commands: Default::default(),
+            circuit_breaker: crate::core::config::CircuitBreakerConfig::disabled(),
+            fallback_model: None,
+            consensus_models: Vec::new(),
+            consensus_judge: None,
+            anthropic_params: None,
+            provider_endpoints: Default::default(),
+            provider_quirks: Vec::new(),
+            calendar_urls: Vec::new(),
+            notify: Default::default(),
+            tool_output_limits: Default::default(),
         }
     }
 
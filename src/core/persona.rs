@@ -0,0 +1,233 @@
+//! Named personas: a system prompt plus optional model, temperature and
+//! tool allowlist, switchable per session with `,persona <name>`.
+//!
+//! Personas are Markdown files under `.agent/personas/<name>.md` (workspace)
+//! or `~/.config/crabclaw/personas/<name>.md` (user). Frontmatter carries the
+//! settings and the body is the system prompt:
+//!
+//! ```text
+//! ---
+//! name: reviewer
+//! description: Strict code reviewer
+//! model: anthropic:claude-sonnet-4-20250514
+//! temperature: 0.2
+//! tools: file.read, file.search, shell.exec
+//! ---
+//! You review code changes and point out bugs before style.
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::tape::store::TapeStore;
+use crate::tools::skills::{parse_frontmatter, strip_frontmatter};
+
+const PROJECT_PERSONAS_DIR: &str = ".agent/personas";
+const USER_PERSONAS_DIR: &str = ".config/crabclaw/personas";
+
+/// Tape event kind recording a persona switch.
+pub const PERSONA_EVENT: &str = "persona.switch";
+
+/// A named persona definition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Persona {
+    pub name: String,
+    pub description: String,
+    pub system_prompt: String,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    /// Allowed tool names; `None` means all tools. Entries ending in `*`
+    /// match by prefix (e.g. `file.*`).
+    pub tools: Option<Vec<String>>,
+    pub source: String,
+}
+
+impl Persona {
+    /// Whether this persona may use the tool `name`.
+    pub fn allows_tool(&self, name: &str) -> bool {
+        let Some(allowed) = &self.tools else {
+            return true;
+        };
+        allowed
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => pattern == name,
+            })
+    }
+}
+
+/// Discover personas from the workspace and user directories.
+///
+/// Workspace personas override same-named user ones; results are sorted by name.
+pub fn discover_personas(workspace: &Path) -> Vec<Persona> {
+    discover_personas_in_roots(&persona_roots(workspace))
+}
+
+/// Persona files in the workspace and user directories, in priority order.
+pub fn persona_files(workspace: &Path) -> Vec<PathBuf> {
+    persona_roots(workspace)
+        .iter()
+        .flat_map(|(root, _)| markdown_files(root))
+        .collect()
+}
+
+fn persona_roots(workspace: &Path) -> [(PathBuf, &'static str); 2] {
+    let home = dirs::home_dir().unwrap_or_default();
+    [
+        (workspace.join(PROJECT_PERSONAS_DIR), "project"),
+        (home.join(USER_PERSONAS_DIR), "user"),
+    ]
+}
+
+/// `*.md` files directly under `root`, sorted.
+fn markdown_files(root: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "md"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn discover_personas_in_roots(roots: &[(PathBuf, &str)]) -> Vec<Persona> {
+    let mut by_name: std::collections::BTreeMap<String, Persona> =
+        std::collections::BTreeMap::new();
+
+    for (root, source) in roots {
+        for path in markdown_files(root) {
+            if let Some(persona) = read_persona(&path, source) {
+                by_name
+                    .entry(persona.name.to_lowercase())
+                    .or_insert(persona);
+            }
+        }
+    }
+
+    by_name.into_values().collect()
+}
+
+/// Find a persona by case-insensitive name.
+pub fn find_persona(name: &str, workspace: &Path) -> Option<Persona> {
+    let lowered = name.to_lowercase();
+    discover_personas(workspace)
+        .into_iter()
+        .find(|p| p.name.to_lowercase() == lowered)
+}
+
+/// The persona selected in this session's tape, if any.
+///
+/// Returns `Some(None)` when the session explicitly switched back to the
+/// default, and `None` when the tape has no persona switch at all.
+pub fn tape_persona(tape: &TapeStore) -> Option<Option<String>> {
+    tape.entries()
+        .iter()
+        .rev()
+        .find(|e| e.kind == PERSONA_EVENT)
+        .map(|e| {
+            e.payload
+                .get("name")
+                .and_then(|v| v.as_str())
+                .map(ToString::to_string)
+        })
+}
+
+fn read_persona(path: &Path, source: &str) -> Option<Persona> {
+    let content = fs::read_to_string(path).ok()?;
+    let frontmatter = parse_frontmatter(&content);
+
+    let name = frontmatter
+        .get("name")
+        .filter(|n| !n.trim().is_empty())
+        .cloned()
+        .or_else(|| path.file_stem()?.to_str().map(ToString::to_string))?;
+
+    let tools = frontmatter.get("tools").map(|list| {
+        list.split(',')
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect()
+    });
+
+    Some(Persona {
+        name,
+        description: frontmatter.get("description").cloned().unwrap_or_default(),
+        system_prompt: strip_frontmatter(&content).trim().to_string(),
+        model: frontmatter.get("model").filter(|m| !m.is_empty()).cloned(),
+        temperature: frontmatter
+            .get("temperature")
+            .and_then(|t| t.parse::<f32>().ok()),
+        tools,
+        source: source.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_persona(root: &Path, file: &str, content: &str) {
+        let dir = root.join(PROJECT_PERSONAS_DIR);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(file), content).unwrap();
+    }
+
+    #[test]
+    fn reads_persona_settings_and_body() {
+        let dir = tempdir().unwrap();
+        write_persona(
+            dir.path(),
+            "reviewer.md",
+            "---\nname: reviewer\ndescription: Code reviewer\nmodel: openai:gpt-4o\ntemperature: 0.2\ntools: file.*, shell.exec\n---\nReview carefully.\n",
+        );
+
+        let personas =
+            discover_personas_in_roots(&[(dir.path().join(PROJECT_PERSONAS_DIR), "project")]);
+        assert_eq!(personas.len(), 1);
+        let p = &personas[0];
+        assert_eq!(p.name, "reviewer");
+        assert_eq!(p.system_prompt, "Review carefully.");
+        assert_eq!(p.model.as_deref(), Some("openai:gpt-4o"));
+        assert_eq!(p.temperature, Some(0.2));
+        assert!(p.allows_tool("file.read"));
+        assert!(p.allows_tool("shell.exec"));
+        assert!(!p.allows_tool("web.fetch"));
+    }
+
+    #[test]
+    fn file_stem_used_when_name_missing_and_workspace_wins() {
+        let project = tempdir().unwrap();
+        let user = tempdir().unwrap();
+        write_persona(project.path(), "helper.md", "Project helper.");
+        write_persona(user.path(), "helper.md", "User helper.");
+
+        let personas = discover_personas_in_roots(&[
+            (project.path().join(PROJECT_PERSONAS_DIR), "project"),
+            (user.path().join(PROJECT_PERSONAS_DIR), "user"),
+        ]);
+        assert_eq!(personas.len(), 1);
+        assert_eq!(personas[0].name, "helper");
+        assert_eq!(personas[0].system_prompt, "Project helper.");
+        assert!(personas[0].allows_tool("anything"));
+    }
+
+    #[test]
+    fn tape_persona_reads_latest_switch() {
+        let dir = tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "persona").unwrap();
+        assert_eq!(tape_persona(&tape), None);
+
+        tape.append_event(PERSONA_EVENT, serde_json::json!({"name": "reviewer"}))
+            .unwrap();
+        assert_eq!(tape_persona(&tape), Some(Some("reviewer".to_string())));
+
+        tape.append_event(PERSONA_EVENT, serde_json::json!({"name": null}))
+            .unwrap();
+        assert_eq!(tape_persona(&tape), Some(None));
+    }
+}
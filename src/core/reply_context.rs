@@ -0,0 +1,198 @@
+//! Context for replies to earlier assistant messages.
+//!
+//! Channels that support replies record which of their sent messages carry
+//! which assistant tape entry (a `reply.link` event). When the user later
+//! replies to one of them, the linked entry and the turns around it are
+//! looked up in the whole tape, not just the current context window, and
+//! given to the model as an explicit system message. Messages sent before
+//! linking existed are found by matching their text.
+
+use crate::core::utils::safe_truncate;
+use crate::tape::store::{TapeEntry, TapeStore};
+
+/// Tape event linking sent chat messages to an assistant message entry
+/// (`{"entry_id": 12, "message_ids": [345, 346]}`).
+pub const REPLY_LINK_EVENT: &str = "reply.link";
+
+/// Messages shown on each side of the replied-to message.
+const SURROUNDING_MESSAGES: usize = 2;
+
+/// Longest excerpt of one message in the reply context, in bytes.
+const MAX_EXCERPT_BYTES: usize = 1_500;
+
+/// Characters of the replied-to text used to find it by content.
+const MATCH_PREFIX_CHARS: usize = 200;
+
+/// The chat message a user replied to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplyTarget {
+    /// Channel message ID, when the channel has one.
+    pub message_id: Option<i64>,
+    /// Text of the replied-to message as the channel shows it.
+    pub text: String,
+}
+
+/// Link `message_ids` to the latest assistant message in the tape.
+pub fn link_sent_messages(tape: &mut TapeStore, message_ids: &[i64]) -> std::io::Result<()> {
+    let Some(entry_id) = tape
+        .entries()
+        .iter()
+        .rev()
+        .find(|e| is_message(e, Some("assistant")))
+        .map(|e| e.id)
+    else {
+        return Ok(());
+    };
+    tape.append_event(
+        REPLY_LINK_EVENT,
+        serde_json::json!({ "entry_id": entry_id, "message_ids": message_ids }),
+    )?;
+    Ok(())
+}
+
+/// System message describing the exchange around the replied-to message,
+/// or `None` when it cannot be found in the tape.
+pub fn reply_context(tape: &TapeStore, target: &ReplyTarget) -> Option<String> {
+    let entry_id = linked_entry(tape, target).or_else(|| matching_entry(tape, &target.text))?;
+    let messages: Vec<&TapeEntry> = tape
+        .entries()
+        .iter()
+        .filter(|e| is_message(e, None))
+        .collect();
+    let index = messages.iter().position(|e| e.id == entry_id)?;
+    let start = index.saturating_sub(SURROUNDING_MESSAGES);
+    let end = (index + SURROUNDING_MESSAGES + 1).min(messages.len());
+
+    let sent = &messages[index].timestamp;
+    let mut lines = vec![
+        "<reply_context>".to_string(),
+        format!(
+            "The user is replying to an earlier message of yours (sent {}). The exchange around it:",
+            sent.get(..16).unwrap_or(sent).replace('T', " ")
+        ),
+    ];
+    for (i, entry) in messages[start..end].iter().enumerate() {
+        let role = entry.payload["role"].as_str().unwrap_or("user");
+        let content = entry.payload["content"].as_str().unwrap_or_default();
+        let excerpt = safe_truncate(content, MAX_EXCERPT_BYTES);
+        let marker = if start + i == index {
+            ", replied to"
+        } else {
+            ""
+        };
+        let ellipsis = if excerpt.len() < content.len() {
+            " ..."
+        } else {
+            ""
+        };
+        lines.push(format!("[{role}{marker}] {excerpt}{ellipsis}"));
+    }
+    lines.push("</reply_context>".to_string());
+    Some(lines.join("\n"))
+}
+
+fn is_message(entry: &TapeEntry, role: Option<&str>) -> bool {
+    entry.kind == "message"
+        && role.is_none_or(|role| entry.payload["role"].as_str() == Some(role))
+        && entry.payload["content"]
+            .as_str()
+            .is_some_and(|c| !c.is_empty())
+}
+
+fn linked_entry(tape: &TapeStore, target: &ReplyTarget) -> Option<u64> {
+    let message_id = target.message_id?;
+    tape.entries()
+        .iter()
+        .rev()
+        .filter(|e| e.kind == REPLY_LINK_EVENT)
+        .find(|e| {
+            e.payload["message_ids"]
+                .as_array()
+                .is_some_and(|ids| ids.iter().any(|id| id.as_i64() == Some(message_id)))
+        })
+        .and_then(|e| e.payload["entry_id"].as_u64())
+}
+
+/// Latest assistant message containing the start of `text`. Both sides are
+/// reduced to lowercase letters and digits, since channels render Markdown.
+fn matching_entry(tape: &TapeStore, text: &str) -> Option<u64> {
+    let needle: String = normalize(text).chars().take(MATCH_PREFIX_CHARS).collect();
+    if needle.chars().count() < 8 {
+        return None;
+    }
+    tape.entries()
+        .iter()
+        .rev()
+        .filter(|e| is_message(e, Some("assistant")))
+        .find(|e| normalize(e.payload["content"].as_str().unwrap_or_default()).contains(&needle))
+        .map(|e| e.id)
+}
+
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn tape_with_history(dir: &std::path::Path) -> TapeStore {
+        let mut tape = TapeStore::open(dir, "reply").unwrap();
+        for (role, content) in [
+            ("user", "What is the capital of France?"),
+            ("assistant", "The capital of France is **Paris**."),
+            ("user", "And of Italy?"),
+            ("assistant", "Rome."),
+        ] {
+            tape.append_message(role, content).unwrap();
+        }
+        tape
+    }
+
+    #[test]
+    fn linked_messages_resolve_to_their_entry() {
+        let dir = tempdir().unwrap();
+        let mut tape = tape_with_history(dir.path());
+        link_sent_messages(&mut tape, &[41, 42]).unwrap();
+        tape.anchor("handoff", serde_json::json!({})).unwrap();
+        tape.append_message("user", "unrelated").unwrap();
+
+        let target = ReplyTarget {
+            message_id: Some(42),
+            text: String::new(),
+        };
+        let context = reply_context(&tape, &target).unwrap();
+        assert!(
+            context.contains("[assistant, replied to] Rome."),
+            "{context}"
+        );
+        assert!(context.contains("[user] And of Italy?"), "{context}");
+        assert!(context.contains("[user] unrelated"), "{context}");
+        assert!(!context.contains("France?"), "{context}");
+    }
+
+    #[test]
+    fn unlinked_replies_are_matched_by_text() {
+        let dir = tempdir().unwrap();
+        let tape = tape_with_history(dir.path());
+        let target = ReplyTarget {
+            message_id: Some(7),
+            text: "The capital of France is Paris.".to_string(),
+        };
+        let context = reply_context(&tape, &target).unwrap();
+        assert!(
+            context.contains("[assistant, replied to] The capital of France is **Paris**."),
+            "{context}"
+        );
+
+        let target = ReplyTarget {
+            message_id: None,
+            text: "Something never said".to_string(),
+        };
+        assert_eq!(reply_context(&tape, &target), None);
+    }
+}
@@ -1,8 +1,15 @@
 use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use serde::Serialize;
 
-use crate::core::command::{CommandKind, ParsedArgs, detect_command};
+use crate::core::command::{
+    CommandKind, DEFAULT_PREFIX, DetectedCommand, ParsedArgs, PipeOp, detect_command,
+    detect_command_with_prefix, split_pipeline, unescape_prefix,
+};
+use crate::core::config::{AssistantCommandPolicy, ToolOutputLimits};
+use crate::core::stats::SessionStats;
 use crate::tape::store::TapeStore;
 use crate::tools::registry::{ToolRegistry, builtin_registry};
 use crate::tools::skills;
@@ -18,6 +25,8 @@ pub struct UserRouteResult {
     pub immediate_output: String,
     /// Whether the user requested to exit.
     pub exit_requested: bool,
+    /// Model for this turn only (`,redo --model`).
+    pub model_override: Option<String>,
 }
 
 /// Routing outcome for assistant (model) output.
@@ -50,11 +59,30 @@ impl AssistantRouteResult {
 ///
 /// Logic (aligned with bub's `InputRouter.route_user`):
 /// 1. Empty input → ignored
-/// 2. `,` prefix → parse as command, execute internally
+/// 2. `,` prefix → parse as command, execute internally (`,,` escapes prose)
 /// 3. Successful command → return output directly
 /// 4. Unknown command → fallback to model with context
-/// 5. Natural language → send to model
+/// 5. Natural language → send to model, with `@path` mentions expanded
+///    (see [`crate::core::mentions`])
 pub fn route_user(input: &str, tape: &mut TapeStore, workspace: &Path) -> UserRouteResult {
+    route_user_with_limits(
+        input,
+        tape,
+        workspace,
+        &ToolOutputLimits::default(),
+        DEFAULT_PREFIX,
+    )
+}
+
+/// [`route_user`] with the session's tool output limits, which also bound
+/// the files pulled in by `@path` mentions, and the channel's command prefix.
+pub fn route_user_with_limits(
+    input: &str,
+    tape: &mut TapeStore,
+    workspace: &Path,
+    limits: &ToolOutputLimits,
+    prefix: char,
+) -> UserRouteResult {
     let stripped = input.trim();
 
     if stripped.is_empty() {
@@ -63,11 +91,17 @@ pub fn route_user(input: &str, tape: &mut TapeStore, workspace: &Path) -> UserRo
             model_prompt: String::new(),
             immediate_output: String::new(),
             exit_requested: false,
+            model_override: None,
         };
     }
 
-    let Some(command) = detect_command(stripped) else {
+    if let Some(result) = route_pipeline(stripped, tape, workspace, limits, prefix) {
+        return result;
+    }
+
+    let Some(command) = detect_command_with_prefix(stripped, prefix) else {
         // Natural language → route to model
+        let stripped = unescape_prefix(stripped, prefix);
         tape.append_event(
             "route",
             serde_json::json!({"kind": "model", "input": stripped}),
@@ -75,13 +109,57 @@ pub fn route_user(input: &str, tape: &mut TapeStore, workspace: &Path) -> UserRo
         .ok();
         return UserRouteResult {
             enter_model: true,
-            model_prompt: stripped.to_string(),
+            model_prompt: crate::core::mentions::expand_mentions(stripped, workspace, limits),
             immediate_output: String::new(),
             exit_requested: false,
+            model_override: None,
         };
     };
 
-    // Execute internal command
+    if matches!(command.name.as_str(), "retry" | "redo") && command.kind == CommandKind::Internal {
+        return route_retry(&command.name, &command.args, tape);
+    }
+
+    let (result, failure_context) = run_human_command(&command, tape, workspace);
+    if result.exit_requested {
+        return UserRouteResult {
+            enter_model: false,
+            model_prompt: String::new(),
+            immediate_output: String::new(),
+            exit_requested: true,
+            model_override: None,
+        };
+    }
+
+    if result.success {
+        // Success → return output directly, do not enter model.
+        UserRouteResult {
+            enter_model: false,
+            model_prompt: String::new(),
+            immediate_output: result.output,
+            exit_requested: false,
+            model_override: None,
+        }
+    } else {
+        // Failure → structured context for LLM self-correction.
+        UserRouteResult {
+            enter_model: true,
+            model_prompt: failure_context,
+            immediate_output: result.output,
+            exit_requested: false,
+            model_override: None,
+        }
+    }
+}
+
+/// Run a command typed by the user and record it in the tape.
+///
+/// Also returns the context block handed to the model when it failed.
+fn run_human_command(
+    command: &DetectedCommand,
+    tape: &mut TapeStore,
+    workspace: &Path,
+) -> (CommandResult, String) {
     match command.kind {
         CommandKind::Internal => {
             let registry = builtin_registry();
@@ -99,41 +177,28 @@ pub fn route_user(input: &str, tape: &mut TapeStore, workspace: &Path) -> UserRo
             )
             .ok();
 
-            if result.exit_requested {
-                return UserRouteResult {
-                    enter_model: false,
-                    model_prompt: String::new(),
-                    immediate_output: String::new(),
-                    exit_requested: true,
-                };
-            }
-
-            if result.success {
-                UserRouteResult {
-                    enter_model: false,
-                    model_prompt: String::new(),
-                    immediate_output: result.output,
-                    exit_requested: false,
-                }
-            } else {
-                // Failed command falls back to model with context
-                let context = format!(
-                    "<command name=\"{}\" status=\"error\">\n{}\n</command>",
-                    command.name, result.output
-                );
-                UserRouteResult {
-                    enter_model: true,
-                    model_prompt: context,
-                    immediate_output: result.output.clone(),
-                    exit_requested: false,
-                }
-            }
+            let context = format!(
+                "<command name=\"{}\" status=\"error\">\n{}\n</command>",
+                command.name, result.output
+            );
+            (result, context)
         }
         CommandKind::Shell => {
             use crate::core::shell;
 
-            let shell_result = shell::execute_shell(&command.raw, workspace);
+            let shell_result =
+                shell::execute_shell_as(&command.raw, workspace, shell::CommandOrigin::Human);
             let display_output = shell::format_shell_output(&shell_result);
+            let success = shell_result.exit_code == 0 && !shell_result.timed_out;
+            crate::core::audit::record(
+                workspace,
+                tape,
+                "human",
+                "shell",
+                &command.raw,
+                success,
+                &display_output,
+            );
 
             tape.append_event(
                 "command",
@@ -149,26 +214,279 @@ pub fn route_user(input: &str, tape: &mut TapeStore, workspace: &Path) -> UserRo
             )
             .ok();
 
-            if shell_result.exit_code == 0 && !shell_result.timed_out {
-                // Success → return output directly, do not enter model.
-                UserRouteResult {
-                    enter_model: false,
-                    model_prompt: String::new(),
-                    immediate_output: display_output,
-                    exit_requested: false,
-                }
-            } else {
-                // Failure → structured context for LLM self-correction.
-                let context = shell::wrap_failure_context(&command.raw, &shell_result);
-                UserRouteResult {
+            let context = shell::wrap_failure_context(&command.raw, &shell_result);
+            let result = CommandResult {
+                success,
+                output: display_output,
+                exit_requested: false,
+            };
+            (result, context)
+        }
+    }
+}
+
+/// Steps that hand piped output to the model (`,git diff | summarize`),
+/// with the instruction sent along. `ask <question>` uses the question.
+const MODEL_STEPS: &[(&str, &str)] = &[
+    ("summarize", "Summarize the command output below."),
+    ("explain", "Explain what the command output below means."),
+    (
+        "review",
+        "Review the command output below and point out anything wrong or risky.",
+    ),
+    ("ask", "Answer using the command output below."),
+];
+
+/// A step of a pipeline once adjacent shell stages are merged.
+enum Stage {
+    Command(DetectedCommand),
+    /// A model step, with its instruction.
+    Model(String),
+}
+
+/// The instruction of a model step such as `summarize` or `ask why?`.
+fn model_step(text: &str) -> Option<String> {
+    let (name, rest) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    let (name, base) = MODEL_STEPS.iter().find(|(step, _)| *step == name)?;
+    let rest = rest.trim();
+    Some(match (*name, rest.is_empty()) {
+        (_, true) => base.to_string(),
+        ("ask", false) => format!("Using the command output below, answer: {rest}"),
+        (_, false) => format!("{base} {rest}"),
+    })
+}
+
+/// Route a command line chaining stages with `&&` and `|`.
+///
+/// Returns `None` unless the line mixes in an internal command or a model
+/// step: a plain shell pipeline goes to the shell whole. Adjacent shell
+/// stages run as one shell command, `a && b` stops at the first failure
+/// (which falls back to the model like a lone failed command), and a
+/// trailing model step sends the piped output to the model.
+fn route_pipeline(
+    input: &str,
+    tape: &mut TapeStore,
+    workspace: &Path,
+    limits: &ToolOutputLimits,
+    prefix: char,
+) -> Option<UserRouteResult> {
+    let body = input.strip_prefix(prefix)?;
+    if body.starts_with(prefix) {
+        return None;
+    }
+    let parts = split_pipeline(body);
+    if parts.len() < 2 {
+        return None;
+    }
+
+    let fail = |output: String| UserRouteResult {
+        enter_model: false,
+        model_prompt: String::new(),
+        immediate_output: output,
+        exit_requested: false,
+        model_override: None,
+    };
+
+    let mut stages: Vec<(Option<PipeOp>, Stage)> = Vec::new();
+    for (op, text) in parts {
+        let text = text.trim_start_matches(prefix).trim_start();
+        if op == Some(PipeOp::Pipe)
+            && let Some(instruction) = model_step(text)
+        {
+            stages.push((op, Stage::Model(instruction)));
+            continue;
+        }
+        let Some(command) = detect_command_with_prefix(&format!("{prefix}{text}"), prefix) else {
+            return Some(fail(format!("Empty stage in pipeline: {input}")));
+        };
+        if let (Some(op), Some((_, Stage::Command(previous)))) = (op, stages.last_mut())
+            && command.kind == CommandKind::Shell
+            && previous.kind == CommandKind::Shell
+        {
+            previous.raw = format!("{} {op} {}", previous.raw, command.raw);
+            continue;
+        }
+        stages.push((op, Stage::Command(command)));
+    }
+
+    let mixed = stages.len() > 1
+        || stages
+            .iter()
+            .any(|(_, stage)| !matches!(stage, Stage::Command(c) if c.kind == CommandKind::Shell));
+    if !mixed {
+        return None;
+    }
+
+    let steps = MODEL_STEPS
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ");
+    for (index, (op, stage)) in stages.iter().enumerate() {
+        match stage {
+            Stage::Model(_) if index + 1 < stages.len() => {
+                return Some(fail(format!(
+                    "A model step ({steps}) must be the last stage of a pipeline."
+                )));
+            }
+            Stage::Command(command) if *op == Some(PipeOp::Pipe) => {
+                return Some(fail(format!(
+                    "Only shell commands and model steps ({steps}) can take piped input, not ,{}.",
+                    command.name
+                )));
+            }
+            Stage::Command(command) if matches!(command.name.as_str(), "retry" | "redo") => {
+                return Some(fail(format!(
+                    ",{} cannot be used in a pipeline.",
+                    command.name
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    tape.append_event(
+        "route",
+        serde_json::json!({"kind": "pipeline", "input": input}),
+    )
+    .ok();
+
+    let mut shown = Vec::new();
+    let mut piped = String::new();
+    for (index, (_, stage)) in stages.iter().enumerate() {
+        let command = match stage {
+            Stage::Command(command) => command,
+            Stage::Model(instruction) => {
+                return Some(UserRouteResult {
                     enter_model: true,
-                    model_prompt: context,
-                    immediate_output: display_output,
+                    model_prompt: format!("{instruction}\n\n{piped}"),
+                    immediate_output: shown.join("\n\n"),
                     exit_requested: false,
-                }
+                    model_override: None,
+                });
             }
+        };
+
+        let (result, failure_context) = run_human_command(command, tape, workspace);
+        if result.exit_requested {
+            return Some(UserRouteResult {
+                enter_model: false,
+                model_prompt: String::new(),
+                immediate_output: shown.join("\n\n"),
+                exit_requested: true,
+                model_override: None,
+            });
+        }
+        if !result.success {
+            shown.push(result.output);
+            return Some(UserRouteResult {
+                enter_model: true,
+                model_prompt: failure_context,
+                immediate_output: shown.join("\n\n"),
+                exit_requested: false,
+                model_override: None,
+            });
+        }
+
+        let feeds_next = stages
+            .get(index + 1)
+            .is_some_and(|(op, _)| *op == Some(PipeOp::Pipe));
+        if feeds_next {
+            let label = match command.kind {
+                CommandKind::Internal => &command.name,
+                CommandKind::Shell => &command.raw,
+            };
+            piped = format!(
+                "<command name=\"{label}\" status=\"ok\">\n{}\n</command>",
+                crate::core::utils::safe_truncate(&result.output, limits.tool_bytes)
+            );
+        } else {
+            shown.push(result.output);
         }
     }
+
+    Some(UserRouteResult {
+        enter_model: false,
+        model_prompt: String::new(),
+        immediate_output: shown.join("\n\n"),
+        exit_requested: false,
+        model_override: None,
+    })
+}
+
+/// Tape event recording a `,retry` / `,redo` (`{"command": "redo", "from":
+/// 12, "model": "provider:name" | null}`, `from` being the entry ID of the
+/// re-sent prompt).
+pub const TURN_RETRY_EVENT: &str = "turn.retry";
+
+/// `,retry` re-sends the latest prompt; `,redo --model <m>` does the same
+/// with another model for that turn. The previous turn is superseded, so
+/// the rejected answer leaves the context.
+fn route_retry(name: &str, args: &ParsedArgs, tape: &mut TapeStore) -> UserRouteResult {
+    use crate::core::agent_loop::TURN_SUPERSEDED_EVENT;
+
+    let fail = |tape: &mut TapeStore, output: String| {
+        tape.append_event(
+            "command",
+            serde_json::json!({
+                "origin": "human",
+                "kind": "internal",
+                "name": name,
+                "status": "error",
+                "output": output,
+            }),
+        )
+        .ok();
+        UserRouteResult {
+            enter_model: false,
+            model_prompt: String::new(),
+            immediate_output: output,
+            exit_requested: false,
+            model_override: None,
+        }
+    };
+
+    let model = args
+        .get("model")
+        .or_else(|| args.positional.first().map(String::as_str))
+        .map(str::to_string);
+    if name == "redo" && model.is_none() {
+        return fail(tape, "Usage: ,redo --model <provider:name>".to_string());
+    }
+    if let Some(model) = &model
+        && let Err(e) = crate::llm::models::validate_model_id(model)
+    {
+        return fail(tape, format!("Invalid model '{model}': {e}"));
+    }
+    let Some((from, prompt)) = crate::core::context::last_user_turn(tape) else {
+        return fail(
+            tape,
+            "Nothing to retry: no earlier prompt in this session.".to_string(),
+        );
+    };
+
+    let to = tape.entries().last().map_or(from, |e| e.id);
+    tape.append_event(
+        TURN_SUPERSEDED_EVENT,
+        serde_json::json!({ "from": from, "to": to, "reason": name }),
+    )
+    .ok();
+    tape.append_event(
+        TURN_RETRY_EVENT,
+        serde_json::json!({ "command": name, "from": from, "model": model }),
+    )
+    .ok();
+
+    UserRouteResult {
+        enter_model: true,
+        model_prompt: prompt,
+        immediate_output: model
+            .as_ref()
+            .map(|m| format!("Retrying with {m}."))
+            .unwrap_or_default(),
+        exit_requested: false,
+        model_override: model,
+    }
 }
 
 /// Route assistant (model) output through command detection.
@@ -182,10 +500,45 @@ pub fn route_user(input: &str, tape: &mut TapeStore, workspace: &Path) -> UserRo
 /// If any commands were found, their results become `command_blocks`
 /// which should be fed back to the model in the next turn.
 pub fn route_assistant(text: &str, tape: &mut TapeStore, workspace: &Path) -> AssistantRouteResult {
+    route_assistant_with_policy(
+        text,
+        tape,
+        workspace,
+        &AssistantCommandPolicy::default(),
+        None,
+    )
+}
+
+/// Asks the user whether an assistant shell command may run.
+pub type CommandApprover = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// [`route_assistant`] under the session's [`AssistantCommandPolicy`].
+///
+/// Commands inside ``` fences are examples unless `run_fenced` is set; a
+/// fence whose commands ran is dropped from the visible text. Commands past `max_commands` or `max_secs` are skipped, and a shell
+/// command's timeout is cut to the time left. With `confirm_shell`, each
+/// shell command that is not read-only ([`crate::core::shell::is_read_only`])
+/// runs only if `approver` says yes; without an approver (a
+/// channel that cannot ask) they are skipped. Skipped commands still get a
+/// `<command>` block so the model learns why.
+pub fn route_assistant_with_policy(
+    text: &str,
+    tape: &mut TapeStore,
+    workspace: &Path,
+    policy: &AssistantCommandPolicy,
+    approver: Option<&CommandApprover>,
+) -> AssistantRouteResult {
     let mut visible_lines = Vec::new();
     let mut command_blocks = Vec::new();
     let mut exit_requested = false;
     let mut in_fence = false;
+    // Where the open fence's marker sits in `visible_lines`, and whether a
+    // command inside it ran (`run_fenced`).
+    let mut fence_start = 0;
+    let mut fence_ran = false;
+    let started = Instant::now();
+    let budget = Duration::from_secs(policy.max_secs);
+    let mut executed = 0;
 
     for line in text.lines() {
         let stripped = line.trim();
@@ -193,6 +546,18 @@ pub fn route_assistant(text: &str, tape: &mut TapeStore, workspace: &Path) -> As
         // Track code fence boundaries
         if stripped.starts_with("```") {
             in_fence = !in_fence;
+            if policy.run_fenced {
+                if in_fence {
+                    fence_start = visible_lines.len();
+                    fence_ran = false;
+                    visible_lines.push(line.to_string());
+                } else if fence_ran {
+                    visible_lines.remove(fence_start);
+                } else {
+                    visible_lines.push(line.to_string());
+                }
+                continue;
+            }
             if !command_blocks.is_empty() {
                 // Don't add fence markers to visible output when executing commands
                 continue;
@@ -202,7 +567,7 @@ pub fn route_assistant(text: &str, tape: &mut TapeStore, workspace: &Path) -> As
         }
 
         // Skip command detection inside code fences to prevent prompt injection
-        if in_fence {
+        if in_fence && !policy.run_fenced {
             visible_lines.push(line.to_string());
             continue;
         }
@@ -216,12 +581,87 @@ pub fn route_assistant(text: &str, tape: &mut TapeStore, workspace: &Path) -> As
         }
 
         let command = command.unwrap();
+        fence_ran |= in_fence;
+
+        // Skip quit from assistant — model shouldn't be able to quit
+        if command.kind == CommandKind::Internal && command.name == "quit" {
+            visible_lines.push(line.to_string());
+            continue;
+        }
+
+        let remaining = budget.saturating_sub(started.elapsed());
+        let skipped = if let Some(reason) = refused_for_assistant(&command) {
+            Some(reason.to_string())
+        } else if executed >= policy.max_commands {
+            Some(format!(
+                "skipped: the limit of {} commands per turn was reached",
+                policy.max_commands
+            ))
+        } else if remaining.is_zero() {
+            Some(format!(
+                "skipped: the {}s command time budget for this turn is used up",
+                policy.max_secs
+            ))
+        } else if command.kind == CommandKind::Shell
+            && policy.confirm_shell
+            && !crate::core::shell::is_read_only(&command.raw, workspace)
+        {
+            match approver {
+                None => Some(
+                    "skipped: shell commands need user approval, which this channel cannot ask for"
+                        .to_string(),
+                ),
+                Some(approve) if !approve(&command.raw) => {
+                    Some("skipped: the user declined to run this command".to_string())
+                }
+                Some(_) => None,
+            }
+        } else {
+            None
+        };
+        if let Some(reason) = skipped {
+            let name = match command.kind {
+                CommandKind::Internal => &command.name,
+                CommandKind::Shell => &command.raw,
+            };
+            tape.append_event(
+                "command",
+                serde_json::json!({
+                    "origin": "assistant",
+                    "kind": command.kind.to_string(),
+                    "name": name,
+                    "status": "skipped",
+                    "output": reason,
+                }),
+            )
+            .ok();
+            command_blocks.push(format!(
+                "<command name=\"{name}\" status=\"skipped\">\n{reason}\n</command>"
+            ));
+            continue;
+        }
+        executed += 1;
 
         match command.kind {
             CommandKind::Shell => {
                 use crate::core::shell;
 
-                let shell_result = shell::execute_shell(&command.raw, workspace);
+                let timeout = remaining.min(Duration::from_secs(shell::DEFAULT_TIMEOUT_SECS));
+                let shell_result = shell::execute_shell_as_with_timeout(
+                    &command.raw,
+                    workspace,
+                    shell::CommandOrigin::Assistant,
+                    timeout,
+                );
+                crate::core::audit::record(
+                    workspace,
+                    tape,
+                    "assistant",
+                    "shell",
+                    &command.raw,
+                    shell_result.exit_code == 0 && !shell_result.timed_out,
+                    &shell::format_shell_output(&shell_result),
+                );
 
                 tape.append_event(
                     "command",
@@ -249,12 +689,6 @@ pub fn route_assistant(text: &str, tape: &mut TapeStore, workspace: &Path) -> As
                 command_blocks.push(block);
             }
             CommandKind::Internal => {
-                // Skip quit from assistant — model shouldn't be able to quit
-                if command.name == "quit" {
-                    visible_lines.push(line.to_string());
-                    continue;
-                }
-
                 let registry = builtin_registry();
                 let result =
                     execute_internal(&command.name, tape, &command.args, workspace, &registry);
@@ -299,6 +733,25 @@ pub fn route_assistant(text: &str, tape: &mut TapeStore, workspace: &Path) -> As
     }
 }
 
+/// Why `command` may only be typed by the user, or `None` if the model may
+/// run it too. These commands delete or write workspace files without
+/// the approval tools need.
+fn refused_for_assistant(command: &DetectedCommand) -> Option<&'static str> {
+    if command.kind != CommandKind::Internal {
+        return None;
+    }
+    match (
+        command.name.as_str(),
+        command.args.positional.first().map(String::as_str),
+    ) {
+        ("snapshot", Some("restore" | "delete")) => {
+            Some("skipped: only the user can restore or delete snapshots")
+        }
+        ("export", _) => Some("skipped: only the user can export the session"),
+        _ => None,
+    }
+}
+
 #[derive(Debug)]
 struct CommandResult {
     success: bool,
@@ -419,6 +872,12 @@ fn execute_internal(
                 },
             }
         }
+        "pin" | "pins" | "unpin" => execute_pin(name, args, tape),
+        "stats" => CommandResult {
+            success: true,
+            output: SessionStats::from_tape(tape).format(),
+            exit_requested: false,
+        },
         "tools" => execute_tools(registry),
         "tool.describe" => {
             let name = if args.positional.is_empty() {
@@ -432,7 +891,7 @@ fn execute_internal(
             };
             match registry.get(&name) {
                 Some(tool) => {
-                    let params = crate::tools::registry::tool_parameters(&name);
+                    let params = registry.parameters(&name);
                     let params_str = serde_json::to_string_pretty(&params).unwrap_or_default();
                     CommandResult {
                         success: true,
@@ -452,6 +911,26 @@ fn execute_internal(
         }
         "skills" => execute_skills(workspace),
         "skills.describe" => execute_skills_describe(args, workspace),
+        "persona" => execute_persona(args, tape, workspace),
+        "model" => execute_model(args, tape),
+        "schedule.history" => execute_schedule_history(args, tape),
+        "export" => execute_export(args, tape, workspace),
+        "snapshot" => execute_snapshot(args, workspace),
+        // Routed to the model by `route_retry`; assistants cannot retry.
+        "retry" | "redo" => CommandResult {
+            success: false,
+            output: format!(",{name} is only available to users"),
+            exit_requested: false,
+        },
+        // Answered by `AgentLoop`, which knows the session's tools, persona,
+        // registered workspaces, aliases, models, dump settings and open
+        // dialogs.
+        "prompt.show" | "prompt.tokens" | "context" | "workspace" | "aliases" | "ask-all"
+        | "debug" | "remind" | "config" | "cancel" => CommandResult {
+            success: false,
+            output: format!(",{name} is only available in an agent session"),
+            exit_requested: false,
+        },
         _ => CommandResult {
             success: false,
             output: format!("unknown internal command: {name}"),
@@ -471,11 +950,34 @@ Available commands:
   ,tape.search <q>    — Search tape entries by content
   ,anchors            — List all anchors in the tape
   ,handoff [name]     — Create a handoff anchor (resets context window)
+  ,pin <note>         — Pin a note that stays in context for the session
+  ,pins               — List pinned notes
+  ,unpin <id>         — Remove a pinned note
+  ,stats              — Session turns, tool calls, tokens, latency and errors
   ,tools              — List all registered tools
   ,tool.describe <n>  — Show tool details and parameter schema
   ,skills             — List discovered skills
   ,skills.describe <n>— Show full body of a skill
-  ,<shell command>    — Execute a shell command (e.g. ,ls, ,git status)";
+  ,persona [name|off] — List personas, or switch the session persona
+  ,model [p:name|default] — Show or switch the session model
+  ,retry              — Answer the last prompt again, dropping the last answer
+  ,redo --model <p:name> — Retry the last prompt with another model
+  ,ask-all <prompt>   — Ask every CONSENSUS_MODELS model at once and compare
+  ,prompt.show [msg]  — Show the system prompt and messages sent next turn
+  ,prompt.tokens [msg]— Estimate the token count of the next request
+  ,context            — Show what the next request contains and what was trimmed
+  ,workspace [switch <name>] — List workspaces, or switch the session workspace
+  ,schedule.history [id] — Show recent scheduled job runs (limit=N)
+  ,export html [path] — Write the session as a standalone HTML transcript
+  ,snapshot [create [label]|restore <id>|delete <id>|list] — Save or roll back workspace files
+  ,aliases            — List user-defined command aliases (COMMAND_ALIASES)
+  ,debug dump [on|off] — Show or toggle provider request dumps (CRABCLAW_DUMP_DIR)
+  ,remind [in 10m|at 18:30] [msg] — Set a reminder; anything missing is asked for
+  ,config set [KEY [value]] — Save a setting to .env.local, asking for what is missing
+  ,cancel             — Drop the question a command is waiting on
+  ,<shell command>    — Execute a shell command (e.g. ,ls, ,git status)
+  ,a && ,b            — Run ,b only if ,a succeeded
+  ,<cmd> | summarize  — Send the output to the model (also explain, review, ask <question>)";
 
     CommandResult {
         success: true,
@@ -484,6 +986,160 @@ Available commands:
     }
 }
 
+/// `,pin <note>` adds a pinned note, `,pins` lists them and `,unpin <id>`
+/// removes one (see [`crate::tools::pins`]).
+fn execute_pin(name: &str, args: &ParsedArgs, tape: &mut TapeStore) -> CommandResult {
+    use crate::tools::pins;
+
+    let done = |success: bool, output: String| CommandResult {
+        success,
+        output,
+        exit_requested: false,
+    };
+    match name {
+        "pin" => {
+            let note = args.positional.join(" ");
+            if note.trim().is_empty() {
+                return done(false, "Usage: ,pin <note>".to_string());
+            }
+            match pins::add(tape, note.trim(), "human") {
+                Ok(id) => done(true, format!("Pinned #{id}: {}", note.trim())),
+                Err(e) => done(false, format!("Failed to pin: {e}")),
+            }
+        }
+        "unpin" => {
+            let Some(id) = args
+                .positional
+                .first()
+                .and_then(|id| id.trim_start_matches('#').parse::<u64>().ok())
+            else {
+                return done(false, "Usage: ,unpin <id> (see ,pins)".to_string());
+            };
+            match pins::remove(tape, id) {
+                Ok(true) => done(true, format!("Unpinned #{id}.")),
+                Ok(false) => done(false, format!("No pinned note #{id} (see ,pins).")),
+                Err(e) => done(false, format!("Failed to unpin: {e}")),
+            }
+        }
+        _ => done(true, pins::format_pins(&pins::active_pins(tape))),
+    }
+}
+
+/// `,snapshot create [label]`, `,snapshot restore <id>`,
+/// `,snapshot delete <id>` and `,snapshot list` (see [`crate::core::snapshot`]).
+fn execute_snapshot(args: &ParsedArgs, workspace: &Path) -> CommandResult {
+    use crate::core::snapshot;
+
+    let done = |success: bool, output: String| CommandResult {
+        success,
+        output,
+        exit_requested: false,
+    };
+    let usage = "Usage: ,snapshot [create [label]|restore <id>|delete <id>|list]";
+    match args.positional.first().map(String::as_str) {
+        None | Some("list") => done(true, snapshot::format_list(&snapshot::list(workspace))),
+        Some("create") => {
+            let label = args.positional[1..].join(" ");
+            let label = if label.trim().is_empty() {
+                "manual"
+            } else {
+                label.trim()
+            };
+            match snapshot::create(workspace, label) {
+                Ok(s) => done(
+                    true,
+                    format!("Created snapshot {} ({} file(s)).", s.id, s.files.len()),
+                ),
+                Err(e) => done(false, format!("Failed to create snapshot: {e}")),
+            }
+        }
+        Some("restore") => {
+            let Some(id) = args.positional.get(1) else {
+                return done(false, usage.to_string());
+            };
+            match snapshot::restore(workspace, id) {
+                Ok(summary) => done(
+                    true,
+                    format!(
+                        "Restored snapshot {id}: {} file(s) restored, {} removed. Undo with ,snapshot restore {}",
+                        summary.restored, summary.removed, summary.backup
+                    ),
+                ),
+                Err(e) => done(false, format!("Failed to restore snapshot {id}: {e}")),
+            }
+        }
+        Some("delete") => {
+            let Some(id) = args.positional.get(1) else {
+                return done(false, usage.to_string());
+            };
+            match snapshot::delete(workspace, id) {
+                Ok(()) => done(true, format!("Deleted snapshot {id}.")),
+                Err(e) => done(false, format!("Failed to delete snapshot {id}: {e}")),
+            }
+        }
+        Some(_) => done(false, usage.to_string()),
+    }
+}
+
+/// Write the whole tape as an HTML transcript, by default to
+/// `.crabclaw/exports/<tape>-<timestamp>.html` in the workspace.
+fn execute_export(args: &ParsedArgs, tape: &TapeStore, workspace: &Path) -> CommandResult {
+    let usage = || CommandResult {
+        success: false,
+        output: "Usage: ,export html [path]".to_string(),
+        exit_requested: false,
+    };
+    let Some(format) = args.positional.first() else {
+        return usage();
+    };
+    if !format.eq_ignore_ascii_case("html") {
+        return usage();
+    }
+    let name = tape.info().name;
+    let path = match args.positional.get(1) {
+        Some(path) => match crate::tools::file_ops::resolve_safe_path(workspace, path) {
+            Some(path) => path,
+            None => {
+                return CommandResult {
+                    success: false,
+                    output: format!("Refusing to export outside the workspace: {path}"),
+                    exit_requested: false,
+                };
+            }
+        },
+        None => workspace.join(".crabclaw/exports").join(format!(
+            "{name}-{}.html",
+            chrono::Utc::now().format("%Y%m%d-%H%M%S")
+        )),
+    };
+    let title = crate::core::title::tape_title(tape).unwrap_or(name);
+    let html = crate::tape::export::render_html(&title, tape.entries());
+    // Never overwrite: an export is a new file, not a way to replace one.
+    let written = path
+        .parent()
+        .map_or(Ok(()), std::fs::create_dir_all)
+        .and_then(|()| {
+            use std::io::Write;
+            std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)?
+                .write_all(html.as_bytes())
+        });
+    match written {
+        Ok(()) => CommandResult {
+            success: true,
+            output: format!("Exported transcript to {}", path.display()),
+            exit_requested: false,
+        },
+        Err(e) => CommandResult {
+            success: false,
+            output: format!("Failed to write {}: {e}", path.display()),
+            exit_requested: false,
+        },
+    }
+}
+
 fn execute_tape_info(tape: &TapeStore) -> CommandResult {
     let info = tape.info();
     let output = serde_json::to_string_pretty(&TapeInfoDisplay {
@@ -576,6 +1232,144 @@ fn execute_skills(workspace: &Path) -> CommandResult {
     }
 }
 
+fn execute_schedule_history(args: &ParsedArgs, tape: &TapeStore) -> CommandResult {
+    use crate::tools::schedule::global_scheduler;
+    use crate::tools::schedule_store::{format_history, format_job_detail};
+
+    let jobs = global_scheduler().session_jobs(&tape.info().name);
+    if let Some(id) = args.positional.first() {
+        return match jobs.iter().find(|job| &job.id == id) {
+            Some(job) => CommandResult {
+                success: true,
+                output: format_job_detail(job),
+                exit_requested: false,
+            },
+            None => CommandResult {
+                success: false,
+                output: format!("Schedule job not found: {id}"),
+                exit_requested: false,
+            },
+        };
+    }
+    let limit = args.get("limit").and_then(|v| v.parse().ok()).unwrap_or(20);
+    CommandResult {
+        success: true,
+        output: format_history(&jobs, limit),
+        exit_requested: false,
+    }
+}
+
+fn execute_model(args: &ParsedArgs, tape: &mut TapeStore) -> CommandResult {
+    use crate::core::agent_loop::{MODEL_EVENT, tape_model};
+    use crate::llm::models::{describe, lookup, validate_model_id};
+
+    let Some(model) = args.positional.first() else {
+        let output = match tape_model(tape).flatten() {
+            Some(model) => format!(
+                "Model: {model} (session override; `,model default` to reset)\n  {}",
+                describe(&lookup(&model))
+            ),
+            None => "Model: configured default. Switch with `,model <provider:name>`.".to_string(),
+        };
+        return CommandResult {
+            success: true,
+            output,
+            exit_requested: false,
+        };
+    };
+
+    let (selected, output) = if matches!(model.as_str(), "default" | "off") {
+        (None, "Model reset to the configured default.".to_string())
+    } else {
+        if let Err(e) = validate_model_id(model) {
+            return CommandResult {
+                success: false,
+                output: format!("Invalid model '{model}': {e}"),
+                exit_requested: false,
+            };
+        }
+        let output = format!(
+            "Model switched to '{model}' for this session.\n  {}",
+            describe(&lookup(model))
+        );
+        (Some(model.clone()), output)
+    };
+
+    match tape.append_event(MODEL_EVENT, serde_json::json!({ "model": selected })) {
+        Ok(_) => CommandResult {
+            success: true,
+            output,
+            exit_requested: false,
+        },
+        Err(e) => CommandResult {
+            success: false,
+            output: format!("Failed to record model switch: {e}"),
+            exit_requested: false,
+        },
+    }
+}
+
+fn execute_persona(args: &ParsedArgs, tape: &mut TapeStore, workspace: &Path) -> CommandResult {
+    use crate::core::persona::{PERSONA_EVENT, discover_personas, find_persona, tape_persona};
+
+    let Some(name) = args.positional.first() else {
+        let personas = discover_personas(workspace);
+        let active = tape_persona(tape)
+            .flatten()
+            .unwrap_or_else(|| "(channel default)".to_string());
+        if personas.is_empty() {
+            return CommandResult {
+                success: true,
+                output: format!("No personas discovered. Active: {active}"),
+                exit_requested: false,
+            };
+        }
+        let mut lines = vec![format!("Personas ({}), active: {active}", personas.len())];
+        for persona in &personas {
+            lines.push(format!(
+                "  {}: {} [{}]",
+                persona.name, persona.description, persona.source
+            ));
+        }
+        return CommandResult {
+            success: true,
+            output: lines.join("\n"),
+            exit_requested: false,
+        };
+    };
+
+    let (selected, output) = if matches!(name.as_str(), "off" | "default") {
+        (None, "Persona reset to the channel default.".to_string())
+    } else {
+        match find_persona(name, workspace) {
+            Some(persona) => {
+                let output = format!("Persona switched to '{}'.", persona.name);
+                (Some(persona.name), output)
+            }
+            None => {
+                return CommandResult {
+                    success: false,
+                    output: format!("Persona not found: {name}"),
+                    exit_requested: false,
+                };
+            }
+        }
+    };
+
+    match tape.append_event(PERSONA_EVENT, serde_json::json!({ "name": selected })) {
+        Ok(_) => CommandResult {
+            success: true,
+            output,
+            exit_requested: false,
+        },
+        Err(e) => CommandResult {
+            success: false,
+            output: format!("Failed to record persona switch: {e}"),
+            exit_requested: false,
+        },
+    }
+}
+
 fn execute_skills_describe(args: &ParsedArgs, workspace: &Path) -> CommandResult {
     let name = match args.positional.first() {
         Some(n) => n,
@@ -703,6 +1497,7 @@ mod tests {
         assert!(result.model_prompt.contains("</command>"));
     }
 
+    #[cfg(unix)]
     #[test]
     fn shell_command_captures_stderr() {
         let (_dir, mut tape) = make_tape();
@@ -788,6 +1583,271 @@ mod tests {
         assert!(result.model_prompt.contains("skill not found"));
     }
 
+    #[test]
+    fn pipe_into_model_step_sends_output_to_the_model() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let result = route_user(
+            ",echo alpha | tr a-z A-Z | summarize in one line",
+            &mut tape,
+            ws.path(),
+        );
+        assert!(result.enter_model);
+        assert!(result.immediate_output.is_empty());
+        assert!(
+            result
+                .model_prompt
+                .starts_with("Summarize the command output below. in one line")
+        );
+        assert!(
+            result
+                .model_prompt
+                .contains("<command name=\"echo alpha | tr a-z A-Z\" status=\"ok\">\nALPHA\n")
+        );
+
+        let result = route_user(",tape.info | ask how long is it?", &mut tape, ws.path());
+        assert!(result.model_prompt.contains("answer: how long is it?"));
+        assert!(result.model_prompt.contains("<command name=\"tape.info\""));
+    }
+
+    #[test]
+    fn and_chains_stop_at_the_first_failure() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let result = route_user(",tape.info && ,echo done", &mut tape, ws.path());
+        assert!(!result.enter_model);
+        assert!(result.immediate_output.contains("router-test"));
+        assert!(result.immediate_output.ends_with("done"));
+
+        let result = route_user(",tape.search && echo never", &mut tape, ws.path());
+        assert!(result.enter_model);
+        assert!(result.model_prompt.contains("status=\"error\""));
+        assert!(!result.immediate_output.contains("never"));
+    }
+
+    #[test]
+    fn plain_shell_pipelines_and_bad_pipelines() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let result = route_user(",echo a && echo b | tr a-z A-Z", &mut tape, ws.path());
+        assert_eq!(result.immediate_output, "a\nB");
+        let last = tape.entries().last().unwrap();
+        assert_eq!(last.payload["cmd"], "echo a && echo b | tr a-z A-Z");
+
+        let result = route_user(",echo a | summarize | explain", &mut tape, ws.path());
+        assert!(!result.enter_model);
+        assert!(result.immediate_output.contains("must be the last stage"));
+
+        let result = route_user(",echo a | tape.info", &mut tape, ws.path());
+        assert!(result.immediate_output.contains("not ,tape.info"));
+    }
+
+    #[test]
+    fn configured_prefix_marks_commands_and_doubled_prefix_escapes() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let limits = ToolOutputLimits::default();
+        let route = |input: &str, tape: &mut TapeStore| {
+            route_user_with_limits(input, tape, ws.path(), &limits, '/')
+        };
+
+        let result = route("/echo slash", &mut tape);
+        assert!(!result.enter_model);
+        assert_eq!(result.immediate_output, "slash");
+
+        let result = route("/tape.info && /echo chained", &mut tape);
+        assert!(result.immediate_output.ends_with("chained"));
+
+        // A comma is not special in a stage under another prefix.
+        let result = route("/echo first && ,echo second", &mut tape);
+        assert!(
+            !result.immediate_output.contains("Empty stage"),
+            "{}",
+            result.immediate_output
+        );
+        assert!(result.immediate_output.contains("first"));
+
+        let result = route(",echo not a command", &mut tape);
+        assert!(result.enter_model);
+        assert_eq!(result.model_prompt, ",echo not a command");
+
+        let result = route("//etc/hosts looks empty", &mut tape);
+        assert!(result.enter_model);
+        assert_eq!(result.model_prompt, "/etc/hosts looks empty");
+
+        let result = route_user(",,well, hello", &mut tape, ws.path());
+        assert!(result.enter_model);
+        assert_eq!(result.model_prompt, ",well, hello");
+    }
+
+    #[test]
+    fn shell_commands_are_audited() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        route_user(",echo audited", &mut tape, ws.path());
+
+        let records = crate::core::audit::tail(ws.path(), 10).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].origin, "human");
+        assert_eq!(records[0].target, "echo audited");
+        assert!(records[0].success);
+        assert_eq!(records[0].session, "router-test");
+    }
+
+    #[test]
+    fn retry_supersedes_the_last_turn_and_resends_its_prompt() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let result = route_user(",retry", &mut tape, ws.path());
+        assert!(!result.enter_model);
+        assert!(result.immediate_output.starts_with("Nothing to retry"));
+
+        tape.append_message("user", "first").unwrap();
+        tape.append_message("assistant", "one").unwrap();
+        tape.append_message("user", "second").unwrap();
+        tape.append_message("assistant", "bad answer").unwrap();
+
+        let result = route_user(",retry", &mut tape, ws.path());
+        assert!(result.enter_model);
+        assert_eq!(result.model_prompt, "second");
+        assert_eq!(result.model_override, None);
+        let retry = tape
+            .entries()
+            .iter()
+            .find(|e| e.kind == TURN_RETRY_EVENT)
+            .unwrap();
+        assert_eq!(retry.payload["command"], "retry");
+
+        let messages = crate::core::context::build_messages(&tape, None, 50);
+        let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["first", "one"]);
+    }
+
+    #[test]
+    fn redo_needs_a_valid_model() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        tape.append_message("user", "question").unwrap();
+
+        let result = route_user(",redo", &mut tape, ws.path());
+        assert!(!result.enter_model);
+        assert!(result.immediate_output.starts_with("Usage: ,redo"));
+        let result = route_user(",redo --model gpt-4o", &mut tape, ws.path());
+        assert!(result.immediate_output.starts_with("Invalid model"));
+
+        let result = route_user(",redo --model anthropic:claude-x", &mut tape, ws.path());
+        assert!(result.enter_model);
+        assert_eq!(result.model_prompt, "question");
+        assert_eq!(result.model_override.as_deref(), Some("anthropic:claude-x"));
+    }
+
+    #[tokio::test]
+    async fn schedule_history_reads_the_sessions_runs() {
+        let scheduler = crate::tools::schedule::persisted_global_scheduler();
+        let dir = tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "history-command").unwrap();
+        let mut other = TapeStore::open(dir.path(), "history-command-other").unwrap();
+        let ws = workspace();
+        let result = route_user(",schedule.history", &mut tape, ws.path());
+        assert!(result.immediate_output.contains("no job runs recorded"));
+
+        let id = scheduler
+            .schedule(
+                "check feeds",
+                Some(0),
+                None,
+                crate::tools::schedule::JobMode::Reminder,
+                None,
+                None,
+            )
+            .unwrap()
+            .id;
+        scheduler.set_job_session(&id, "history-command");
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+        let result = route_user(",schedule.history", &mut tape, ws.path());
+        assert!(!result.enter_model);
+        assert!(
+            result.immediate_output.starts_with(&format!("{id} ")),
+            "{}",
+            result.immediate_output
+        );
+
+        let result = route_user(&format!(",schedule.history {id}"), &mut tape, ws.path());
+        assert!(result.immediate_output.contains("message:  check feeds"));
+
+        // The history follows the session, not the workspace it is in.
+        let elsewhere = workspace();
+        let result = route_user(",schedule.history", &mut tape, elsewhere.path());
+        assert!(result.immediate_output.starts_with(&format!("{id} ")));
+        let result = route_user(&format!(",schedule.history {id}"), &mut other, ws.path());
+        assert!(result.immediate_output.contains("Schedule job not found"));
+        let result = route_user(",schedule.history", &mut other, ws.path());
+        assert!(result.immediate_output.contains("no job runs recorded"));
+    }
+
+    #[test]
+    fn persona_switch_records_tape_event() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let personas = ws.path().join(".agent/personas");
+        std::fs::create_dir_all(&personas).unwrap();
+        std::fs::write(
+            personas.join("reviewer.md"),
+            "---\nname: reviewer\ndescription: Code reviewer\n---\nReview.",
+        )
+        .unwrap();
+
+        let result = route_user(",persona", &mut tape, ws.path());
+        assert!(result.immediate_output.contains("reviewer: Code reviewer"));
+
+        let result = route_user(",persona Reviewer", &mut tape, ws.path());
+        assert!(!result.enter_model);
+        assert!(result.immediate_output.contains("'reviewer'"));
+        assert_eq!(
+            crate::core::persona::tape_persona(&tape),
+            Some(Some("reviewer".to_string()))
+        );
+
+        let result = route_user(",persona ghost", &mut tape, ws.path());
+        assert!(result.enter_model);
+        assert!(result.immediate_output.contains("Persona not found"));
+
+        route_user(",persona off", &mut tape, ws.path());
+        assert_eq!(crate::core::persona::tape_persona(&tape), Some(None));
+    }
+
+    #[test]
+    fn model_switch_records_tape_event() {
+        use crate::core::agent_loop::tape_model;
+
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+
+        let result = route_user(",model", &mut tape, ws.path());
+        assert!(result.immediate_output.contains("configured default"));
+
+        let result = route_user(",model anthropic:claude-opus-4-5", &mut tape, ws.path());
+        assert!(!result.enter_model);
+        assert!(result.immediate_output.contains("context 200k"));
+        assert_eq!(
+            tape_model(&tape),
+            Some(Some("anthropic:claude-opus-4-5".to_string()))
+        );
+        let result = route_user(",model", &mut tape, ws.path());
+        assert!(result.immediate_output.contains("session override"));
+
+        let result = route_user(",model gpt-4o", &mut tape, ws.path());
+        assert!(result.immediate_output.contains("Invalid model"));
+        assert_eq!(
+            tape_model(&tape),
+            Some(Some("anthropic:claude-opus-4-5".to_string()))
+        );
+
+        route_user(",model default", &mut tape, ws.path());
+        assert_eq!(tape_model(&tape), Some(None));
+    }
+
     #[test]
     fn tape_search_finds_messages() {
         let (_dir, mut tape) = make_tape();
@@ -822,6 +1882,118 @@ mod tests {
         assert!(result.immediate_output.contains("Anchors ("));
     }
 
+    #[test]
+    fn natural_language_expands_file_mentions() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        std::fs::write(ws.path().join("notes.md"), "remember the milk").unwrap();
+
+        let result = route_user("summarize @notes.md please", &mut tape, ws.path());
+        assert!(result.enter_model);
+        assert!(
+            result
+                .model_prompt
+                .starts_with("summarize @notes.md please")
+        );
+        assert!(
+            result
+                .model_prompt
+                .contains("<file path=\"notes.md\">\nremember the milk\n</file>")
+        );
+    }
+
+    #[test]
+    fn export_html_writes_transcript_into_workspace() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        tape.append_message("user", "show <main>").unwrap();
+
+        let result = route_user(",export html out/session.html", &mut tape, ws.path());
+        assert!(!result.enter_model);
+        assert!(result.immediate_output.contains("Exported transcript"));
+        let html = std::fs::read_to_string(ws.path().join("out/session.html")).unwrap();
+        assert!(html.contains("show &lt;main&gt;"));
+
+        let result = route_user(",export pdf", &mut tape, ws.path());
+        assert!(result.immediate_output.contains("Usage: ,export html"));
+
+        for escape in ["../escape.html", "/tmp/escape.html"] {
+            let result = route_user(&format!(",export html {escape}"), &mut tape, ws.path());
+            assert!(
+                result
+                    .immediate_output
+                    .contains("Refusing to export outside")
+            );
+        }
+        assert!(!ws.path().join("../escape.html").exists());
+
+        // Existing files are never overwritten, and the model cannot export.
+        std::fs::write(ws.path().join("main.rs"), "fn main() {}").unwrap();
+        let result = route_user(",export html main.rs", &mut tape, ws.path());
+        assert!(result.immediate_output.contains("Failed to write"));
+        let result = route_assistant(",export html notes.html", &mut tape, ws.path());
+        assert!(result.command_blocks[0].contains("only the user can export"));
+        assert_eq!(
+            std::fs::read_to_string(ws.path().join("main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert!(!ws.path().join("notes.html").exists());
+    }
+
+    #[test]
+    fn snapshot_restore_rolls_back_the_workspace() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        std::fs::write(ws.path().join("plan.md"), "v1").unwrap();
+
+        let created = route_user(",snapshot create before run", &mut tape, ws.path());
+        assert!(created.immediate_output.contains("Created snapshot"));
+        let id = crate::core::snapshot::list(ws.path())[0].id.clone();
+
+        std::fs::write(ws.path().join("plan.md"), "v2").unwrap();
+        let restored = route_user(&format!(",snapshot restore {id}"), &mut tape, ws.path());
+        assert!(
+            restored.immediate_output.contains("1 file(s) restored"),
+            "{}",
+            restored.immediate_output
+        );
+        assert_eq!(
+            std::fs::read_to_string(ws.path().join("plan.md")).unwrap(),
+            "v1"
+        );
+
+        let listed = route_user(",snapshot", &mut tape, ws.path()).immediate_output;
+        assert!(listed.contains("before run"), "{listed}");
+        assert!(
+            listed.contains(&format!("before restoring {id}")),
+            "{listed}"
+        );
+        let missing = route_user(",snapshot restore nope", &mut tape, ws.path());
+        assert!(missing.immediate_output.contains("no snapshot nope"));
+
+        // The model can take snapshots but not restore or delete them.
+        std::fs::write(ws.path().join("plan.md"), "v3").unwrap();
+        let result = route_assistant(
+            &format!(",snapshot restore {id}\n,snapshot delete {id}"),
+            &mut tape,
+            ws.path(),
+        );
+        assert!(
+            result
+                .command_blocks
+                .iter()
+                .all(|b| b.contains("only the user can restore")),
+            "{:?}",
+            result.command_blocks
+        );
+        assert_eq!(
+            std::fs::read_to_string(ws.path().join("plan.md")).unwrap(),
+            "v3"
+        );
+        let deleted = route_user(&format!(",snapshot delete {id}"), &mut tape, ws.path());
+        assert!(deleted.immediate_output.contains("Deleted snapshot"));
+    }
+
     #[test]
     fn handoff_creates_anchor() {
         let (_dir, mut tape) = make_tape();
@@ -839,6 +2011,47 @@ mod tests {
         assert_eq!(last.payload["name"], "checkpoint-1");
     }
 
+    #[test]
+    fn pins_outlive_handoff_until_unpinned() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+
+        let result = route_user(",pin never touch the prod config", &mut tape, ws.path());
+        assert!(result.immediate_output.starts_with("Pinned #"));
+        route_user(",handoff", &mut tape, ws.path());
+
+        let messages = crate::core::context::build_messages(&tape, Some("sys"), 50);
+        assert_eq!(messages[1].role, "system");
+        assert!(
+            messages[1]
+                .content
+                .contains("- never touch the prod config")
+        );
+        let pins = route_user(",pins", &mut tape, ws.path()).immediate_output;
+        assert!(pins.contains("never touch the prod config"), "{pins}");
+
+        let id = crate::tools::pins::active_pins(&tape)[0].id;
+        let result = route_user(&format!(",unpin #{id}"), &mut tape, ws.path());
+        assert_eq!(result.immediate_output, format!("Unpinned #{id}."));
+        assert!(crate::tools::pins::active_pins(&tape).is_empty());
+        let result = route_user(",unpin", &mut tape, ws.path());
+        assert!(result.immediate_output.starts_with("Usage"));
+    }
+
+    #[test]
+    fn stats_counts_failed_commands() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+
+        route_user(",tool.describe nope", &mut tape, ws.path());
+        let stats = route_user(",stats", &mut tape, ws.path()).immediate_output;
+        assert!(stats.starts_with("Session stats:"), "{stats}");
+        assert!(
+            stats.contains("Errors: 0 turn, 1 command, 0 tool"),
+            "{stats}"
+        );
+    }
+
     #[test]
     fn tool_describe_shows_params() {
         let (_dir, mut tape) = make_tape();
@@ -900,6 +2113,96 @@ mod tests {
         assert!(result.command_blocks[0].contains("help"));
     }
 
+    #[test]
+    fn assistant_commands_stop_at_the_per_turn_limit() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let policy = AssistantCommandPolicy {
+            max_commands: 2,
+            ..AssistantCommandPolicy::default()
+        };
+        let result = route_assistant_with_policy(
+            ",echo one\n,quit\n,echo two\n,echo three",
+            &mut tape,
+            ws.path(),
+            &policy,
+            None,
+        );
+        assert_eq!(result.command_blocks.len(), 3);
+        assert!(result.command_blocks[1].contains("two"));
+        assert!(
+            result.command_blocks[2]
+                .starts_with("<command name=\"echo three\" status=\"skipped\">")
+        );
+        assert!(result.command_blocks[2].contains("limit of 2 commands"));
+        let last = tape.entries().last().unwrap();
+        assert_eq!(last.payload["status"], "skipped");
+
+        let policy = AssistantCommandPolicy {
+            max_secs: 1,
+            ..AssistantCommandPolicy::default()
+        };
+        let result = route_assistant_with_policy(
+            ",sleep 3\n,echo late",
+            &mut tape,
+            ws.path(),
+            &policy,
+            None,
+        );
+        assert!(result.command_blocks[0].contains("exit_code"));
+        assert!(result.command_blocks[1].contains("time budget"));
+    }
+
+    #[test]
+    fn fenced_commands_run_only_when_the_policy_says_so() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let text = "Example:\n```\n,echo example\n```\nRunning:\n```sh\n,echo fenced\n```";
+
+        let result = route_assistant(text, &mut tape, ws.path());
+        assert!(!result.has_commands());
+        assert_eq!(result.visible_text, text);
+
+        let policy = AssistantCommandPolicy {
+            run_fenced: true,
+            ..AssistantCommandPolicy::default()
+        };
+        let result = route_assistant_with_policy(text, &mut tape, ws.path(), &policy, None);
+        assert_eq!(result.command_blocks.len(), 2);
+        assert!(result.command_blocks[1].contains("fenced"));
+        assert_eq!(result.visible_text, "Example:\nRunning:");
+
+        let text = "```rust\nfn main() {}\n```";
+        let result = route_assistant_with_policy(text, &mut tape, ws.path(), &policy, None);
+        assert_eq!(result.visible_text, text);
+    }
+
+    #[test]
+    fn assistant_shell_commands_wait_for_approval() {
+        let (_dir, mut tape) = make_tape();
+        let ws = workspace();
+        let policy = AssistantCommandPolicy {
+            confirm_shell: true,
+            ..AssistantCommandPolicy::default()
+        };
+        let text = ",touch approved\n,touch declined\n,tape.info\n,ls";
+        let approver: CommandApprover = Arc::new(|command| command == "touch approved");
+        let result =
+            route_assistant_with_policy(text, &mut tape, ws.path(), &policy, Some(&approver));
+        assert!(result.command_blocks[0].contains("status=\"ok\""));
+        assert!(ws.path().join("approved").exists());
+        assert!(result.command_blocks[1].contains("the user declined"));
+        assert!(!ws.path().join("declined").exists());
+        assert!(result.command_blocks[2].contains("status=\"ok\""));
+        // Read-only commands run without asking.
+        assert!(result.command_blocks[3].contains("approved"));
+
+        let result = route_assistant_with_policy(text, &mut tape, ws.path(), &policy, None);
+        assert!(result.command_blocks[0].contains("cannot ask"));
+        assert!(result.command_blocks[2].contains("status=\"ok\""));
+        assert!(result.command_blocks[3].contains("status=\"ok\""));
+    }
+
     #[test]
     fn assistant_quit_blocked() {
         let (_dir, mut tape) = make_tape();
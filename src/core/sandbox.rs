@@ -0,0 +1,274 @@
+//! Optional sandbox backends for shell commands.
+//!
+//! The `file.*` tools are jailed to the workspace, but a shell escapes that
+//! trivially. Commands can run under firejail, bubblewrap or Docker by
+//! adding `sandbox.json` to the user config directory (`~/.crabclaw`):
+//!
+//! ```json
+//! { "backend": "bubblewrap", "network": false }
+//! ```
+//!
+//! Only the workspace is writable, and it is the only non-system directory
+//! visible: the user's home, with `~/.ssh` and `~/.crabclaw`, stays out of
+//! reach. Network is disabled by default for
+//! model-originated commands; `network` overrides that.
+//!
+//! The file is deliberately not read from the workspace: the model can write
+//! there, and must not be able to switch its own sandbox off.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::core::shell::CommandOrigin;
+
+/// Sandbox configuration file, relative to the user config directory.
+pub const SANDBOX_CONFIG_FILE: &str = "sandbox.json";
+
+/// Top-level directories firejail hides unless they hold the workspace;
+/// bubblewrap never binds them.
+const FIREJAIL_HIDDEN_DIRS: &[&str] = &["/mnt", "/media", "/opt", "/srv", "/var"];
+
+/// Image used by the Docker backend when none is configured.
+const DEFAULT_DOCKER_IMAGE: &str = "alpine:3";
+
+/// Sandbox implementation used to run shell commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxBackend {
+    None,
+    Firejail,
+    #[serde(alias = "bwrap")]
+    Bubblewrap,
+    Docker,
+}
+
+/// Sandbox settings.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct SandboxConfig {
+    pub backend: SandboxBackend,
+    /// Allow network access for model-originated commands (default: off).
+    /// Human-originated commands keep network access unless this is `false`.
+    #[serde(default)]
+    pub network: Option<bool>,
+    /// Docker image (Docker backend only).
+    #[serde(default)]
+    pub image: Option<String>,
+    /// Also sandbox commands typed by the user (default: on).
+    #[serde(default = "default_true")]
+    pub sandbox_human: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl SandboxConfig {
+    /// Load the sandbox configuration in `config_dir`, if any.
+    ///
+    /// A malformed file is an error rather than "no sandbox", so a typo
+    /// cannot silently disable it.
+    pub fn load(config_dir: &Path) -> Result<Option<Self>, String> {
+        let path = config_dir.join(SANDBOX_CONFIG_FILE);
+        let Ok(content) = std::fs::read_to_string(&path) else {
+            return Ok(None);
+        };
+        match serde_json::from_str::<SandboxConfig>(&content) {
+            Ok(config) if config.backend != SandboxBackend::None => Ok(Some(config)),
+            Ok(_) => Ok(None),
+            Err(e) => {
+                warn!(path = %path.display(), "sandbox.config.invalid: {e}");
+                Err(format!("invalid {SANDBOX_CONFIG_FILE}: {e}"))
+            }
+        }
+    }
+
+    /// Whether commands from `origin` run inside the sandbox.
+    pub fn applies_to(&self, origin: CommandOrigin) -> bool {
+        origin.is_model() || self.sandbox_human
+    }
+
+    /// Whether commands from `origin` get network access.
+    pub fn network_for(&self, origin: CommandOrigin) -> bool {
+        match self.network {
+            Some(allowed) => allowed,
+            None => !origin.is_model(),
+        }
+    }
+
+    /// Program and arguments that run `cmd_line` via `/bin/sh -c` inside the
+    /// sandbox, with `workspace` as the working directory.
+    pub fn wrap(
+        &self,
+        cmd_line: &str,
+        workspace: &Path,
+        origin: CommandOrigin,
+    ) -> (String, Vec<String>) {
+        let ws = workspace.display().to_string();
+        let network = self.network_for(origin);
+        let mut args: Vec<String> = Vec::new();
+        let program = match self.backend {
+            SandboxBackend::None => {
+                return (
+                    "/bin/sh".to_string(),
+                    vec!["-c".to_string(), cmd_line.to_string()],
+                );
+            }
+            SandboxBackend::Firejail => {
+                // Whitelisting the workspace empties the directory holding
+                // it (home, /tmp, /srv...); home and /tmp are emptied
+                // otherwise, and other users' homes are hidden by default.
+                let holds_workspace = |dir: &Path| workspace.starts_with(dir);
+                args.extend([
+                    "--quiet".to_string(),
+                    "--noprofile".to_string(),
+                    format!("--whitelist={ws}"),
+                    "--read-only=/".to_string(),
+                    format!("--read-write={ws}"),
+                    "--private-dev".to_string(),
+                ]);
+                if !dirs::home_dir().is_some_and(|home| holds_workspace(&home)) {
+                    args.push("--private".to_string());
+                }
+                if !holds_workspace(Path::new("/tmp")) {
+                    args.push("--private-tmp".to_string());
+                }
+                for dir in FIREJAIL_HIDDEN_DIRS {
+                    if !holds_workspace(Path::new(dir)) {
+                        args.push(format!("--blacklist={dir}"));
+                    }
+                }
+                if !network {
+                    args.push("--net=none".to_string());
+                }
+                args.push("--".to_string());
+                "firejail"
+            }
+            SandboxBackend::Bubblewrap => {
+                for dir in ["/usr", "/bin", "/lib", "/lib64", "/etc"] {
+                    args.extend(["--ro-bind-try".to_string(), dir.into(), dir.into()]);
+                }
+                args.extend([
+                    "--bind".to_string(),
+                    ws.clone(),
+                    ws.clone(),
+                    "--dev".to_string(),
+                    "/dev".to_string(),
+                    "--proc".to_string(),
+                    "/proc".to_string(),
+                    "--tmpfs".to_string(),
+                    "/tmp".to_string(),
+                    "--die-with-parent".to_string(),
+                ]);
+                if !network {
+                    args.push("--unshare-net".to_string());
+                }
+                args.extend(["--chdir".to_string(), ws.clone()]);
+                "bwrap"
+            }
+            SandboxBackend::Docker => {
+                args.extend([
+                    "run".to_string(),
+                    "--rm".to_string(),
+                    "-i".to_string(),
+                    "-v".to_string(),
+                    format!("{ws}:/workspace"),
+                    "-w".to_string(),
+                    "/workspace".to_string(),
+                ]);
+                if !network {
+                    args.extend(["--network".to_string(), "none".to_string()]);
+                }
+                args.push(
+                    self.image
+                        .clone()
+                        .unwrap_or_else(|| DEFAULT_DOCKER_IMAGE.to_string()),
+                );
+                "docker"
+            }
+        };
+        args.extend([
+            "/bin/sh".to_string(),
+            "-c".to_string(),
+            cmd_line.to_string(),
+        ]);
+        (program.to_string(), args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn config(json: &str) -> SandboxConfig {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn load_handles_missing_none_and_invalid() {
+        let ws = tempdir().unwrap();
+        assert_eq!(SandboxConfig::load(ws.path()), Ok(None));
+
+        let path = ws.path().join(SANDBOX_CONFIG_FILE);
+        std::fs::write(&path, r#"{"backend":"none"}"#).unwrap();
+        assert_eq!(SandboxConfig::load(ws.path()), Ok(None));
+
+        std::fs::write(&path, r#"{"backend":"chroot"}"#).unwrap();
+        assert!(SandboxConfig::load(ws.path()).is_err());
+
+        std::fs::write(&path, r#"{"backend":"bwrap"}"#).unwrap();
+        let loaded = SandboxConfig::load(ws.path()).unwrap().unwrap();
+        assert_eq!(loaded.backend, SandboxBackend::Bubblewrap);
+        assert!(loaded.sandbox_human);
+    }
+
+    #[test]
+    fn network_off_for_model_commands_by_default() {
+        let cfg = config(r#"{"backend":"bubblewrap"}"#);
+        assert!(!cfg.network_for(CommandOrigin::Tool));
+        assert!(!cfg.network_for(CommandOrigin::Assistant));
+        assert!(cfg.network_for(CommandOrigin::Human));
+
+        let (program, args) = cfg.wrap("ls", Path::new("/ws"), CommandOrigin::Tool);
+        assert_eq!(program, "bwrap");
+        assert!(args.contains(&"--unshare-net".to_string()));
+        assert!(args.windows(3).any(|w| w == ["--bind", "/ws", "/ws"]));
+        assert_eq!(args[args.len() - 3..], ["/bin/sh", "-c", "ls"]);
+
+        let (_, args) = cfg.wrap("ls", Path::new("/ws"), CommandOrigin::Human);
+        assert!(!args.contains(&"--unshare-net".to_string()));
+    }
+
+    #[test]
+    fn docker_and_firejail_arguments() {
+        let docker = config(r#"{"backend":"docker","image":"rust:1"}"#);
+        let (program, args) = docker.wrap("cargo test", Path::new("/ws"), CommandOrigin::Tool);
+        assert_eq!(program, "docker");
+        assert!(args.contains(&"/ws:/workspace".to_string()));
+        assert!(args.windows(2).any(|w| w == ["--network", "none"]));
+        assert!(args.contains(&"rust:1".to_string()));
+
+        let firejail = config(r#"{"backend":"firejail","network":true}"#);
+        let (program, args) = firejail.wrap("ls", Path::new("/ws"), CommandOrigin::Tool);
+        assert_eq!(program, "firejail");
+        assert!(args.contains(&"--read-write=/ws".to_string()));
+        assert!(args.contains(&"--whitelist=/ws".to_string()));
+        assert!(args.contains(&"--private".to_string()));
+        assert!(args.contains(&"--private-tmp".to_string()));
+        assert!(args.contains(&"--blacklist=/var".to_string()));
+        assert!(!args.contains(&"--net=none".to_string()));
+
+        let (_, args) = firejail.wrap("ls", Path::new("/srv/ws"), CommandOrigin::Tool);
+        assert!(args.contains(&"--whitelist=/srv/ws".to_string()));
+        assert!(!args.contains(&"--blacklist=/srv".to_string()));
+    }
+
+    #[test]
+    fn human_commands_can_skip_sandbox() {
+        let cfg = config(r#"{"backend":"docker","sandbox_human":false}"#);
+        assert!(!cfg.applies_to(CommandOrigin::Human));
+        assert!(cfg.applies_to(CommandOrigin::Assistant));
+    }
+}
@@ -0,0 +1,317 @@
+//! `crabclaw self-update`: replace the running binary with the latest
+//! GitHub release.
+//!
+//! Releases carry one binary per platform (`crabclaw-<arch>-<os>`, with
+//! `.exe` on Windows) and a `SHA256SUMS` file in `sha256sum` format. The
+//! download is only installed when its digest matches that file; it is
+//! written next to the current executable and renamed over it, so a failed
+//! update never leaves a half-written binary behind.
+//!
+//! `crabclaw serve` runs the same check in the background (see
+//! [`spawn_service_check`]): it logs `update.available` and tells the
+//! Telegram admins, but never installs anything by itself.
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::core::error::{CrabClawError, Result};
+
+/// Latest-release endpoint of the upstream repository.
+pub const DEFAULT_RELEASES_URL: &str =
+    "https://api.github.com/repos/jackwener/crabclaw/releases/latest";
+
+/// Release asset listing the SHA-256 digest of every binary.
+const CHECKSUMS_ASSET: &str = "SHA256SUMS";
+
+const UPDATE_USER_AGENT: &str = concat!("crabclaw/", env!("CARGO_PKG_VERSION"));
+
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// How often `crabclaw serve` looks for a newer release.
+pub const SERVICE_CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    /// Release version without the leading `v`.
+    pub fn version(&self) -> &str {
+        self.tag_name.trim_start_matches('v')
+    }
+
+    fn asset(&self, name: &str) -> Result<&ReleaseAsset> {
+        self.assets.iter().find(|a| a.name == name).ok_or_else(|| {
+            CrabClawError::Api(format!("release {} has no asset '{name}'", self.tag_name))
+        })
+    }
+}
+
+/// Name of the release binary for the platform this build runs on.
+pub fn asset_name() -> String {
+    let suffix = if cfg!(windows) { ".exe" } else { "" };
+    format!(
+        "crabclaw-{}-{}{suffix}",
+        std::env::consts::ARCH,
+        std::env::consts::OS
+    )
+}
+
+/// Whether `candidate` is a newer `major.minor.patch` version than
+/// `current`. Pre-release suffixes (`-rc.1`) are never considered newer.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    fn parse(version: &str) -> Option<(u64, u64, u64)> {
+        let mut parts = version.trim_start_matches('v').split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().unwrap_or("0").parse().ok()?;
+        let patch = parts.next().unwrap_or("0").parse().ok()?;
+        Some((major, minor, patch))
+    }
+    match (parse(candidate), parse(current)) {
+        (Some(candidate), Some(current)) => candidate > current,
+        _ => false,
+    }
+}
+
+/// Fetch the latest release description from `releases_url`.
+pub fn latest_release(releases_url: &str) -> Result<Release> {
+    let response = client()?
+        .get(releases_url)
+        .header("Accept", "application/vnd.github+json")
+        .send()?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CrabClawError::Network(format!(
+            "release check failed: HTTP {status}"
+        )));
+    }
+    Ok(response.json()?)
+}
+
+/// The latest release if it is newer than `current`, logging an
+/// `update.available` event when it is.
+pub fn check_for_update(releases_url: &str, current: &str) -> Result<Option<Release>> {
+    let release = latest_release(releases_url)?;
+    if !is_newer(release.version(), current) {
+        return Ok(None);
+    }
+    tracing::info!(
+        current,
+        latest = release.version(),
+        url = %release.html_url,
+        "update.available"
+    );
+    Ok(Some(release))
+}
+
+/// Check for a newer release now and every [`SERVICE_CHECK_INTERVAL`] in a
+/// background thread of a long-running service. `alert` is told once per
+/// new version; failed checks are logged and retried at the next interval.
+pub fn spawn_service_check(releases_url: String, alert: Option<crate::core::auth::AuthAlert>) {
+    let current = env!("CARGO_PKG_VERSION");
+    let spawned = std::thread::Builder::new()
+        .name("update-check".to_string())
+        .spawn(move || {
+            let mut announced = String::new();
+            loop {
+                match check_for_update(&releases_url, current) {
+                    Ok(Some(release)) if release.version() != announced => {
+                        announced = release.version().to_string();
+                        if let Some(alert) = &alert {
+                            alert(update_notice(&release, current));
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!("update.check_error: {e}"),
+                }
+                std::thread::sleep(SERVICE_CHECK_INTERVAL);
+            }
+        });
+    if let Err(e) = spawned {
+        tracing::warn!("update.check_thread_error: {e}");
+    }
+}
+
+/// Admin alert for a newer `release`.
+pub fn update_notice(release: &Release, current: &str) -> String {
+    let mut notice = format!(
+        "crabclaw {} is available (running {current}). Run `crabclaw self-update` on the host to install it.",
+        release.version()
+    );
+    if !release.html_url.is_empty() {
+        notice.push_str(&format!("\n{}", release.html_url));
+    }
+    notice
+}
+
+/// Download this platform's binary from `release`, verify it against the
+/// release checksums and install it at `target`.
+pub fn install_release(release: &Release, target: &Path) -> Result<()> {
+    let name = asset_name();
+    let binary = release.asset(&name)?;
+    let sums = release.asset(CHECKSUMS_ASSET)?;
+
+    let client = client()?;
+    let sums =
+        String::from_utf8_lossy(&download(&client, &sums.browser_download_url)?).into_owned();
+    let bytes = download(&client, &binary.browser_download_url)?;
+    verify_checksum(&bytes, &sums, &name)?;
+    replace_binary(target, &bytes)
+}
+
+/// Check `bytes` against the `sha256sum`-format entry for `name` in `sums`.
+pub fn verify_checksum(bytes: &[u8], sums: &str, name: &str) -> Result<()> {
+    let expected = sums
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .find(|(_, file)| file.trim().trim_start_matches('*') == name)
+        .map(|(digest, _)| digest.to_ascii_lowercase())
+        .ok_or_else(|| {
+            CrabClawError::Api(format!("{CHECKSUMS_ASSET} has no entry for '{name}'"))
+        })?;
+    let actual = format!("{:x}", Sha256::digest(bytes));
+    if actual != expected {
+        return Err(CrabClawError::Api(format!(
+            "checksum mismatch for '{name}': expected {expected}, got {actual}"
+        )));
+    }
+    Ok(())
+}
+
+/// Write `bytes` beside `target` and rename the file over it.
+///
+/// Windows cannot overwrite a running executable but can rename it, so the
+/// old binary is moved aside to `<name>.old` first.
+pub fn replace_binary(target: &Path, bytes: &[u8]) -> Result<()> {
+    let staged = target.with_extension("update");
+    std::fs::write(&staged, bytes)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o755))?;
+    }
+    install_staged(&staged, target, cfg!(windows))
+}
+
+/// Rename `staged` over `target`, first moving `target` to `<name>.old` when
+/// `move_aside` is set. If the final rename fails the old binary is put back,
+/// so a failed update never leaves `target` missing.
+fn install_staged(staged: &Path, target: &Path, move_aside: bool) -> Result<()> {
+    let old = target.with_extension("old");
+    if move_aside {
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(target, &old)?;
+    }
+    if let Err(e) = std::fs::rename(staged, target) {
+        let _ = std::fs::remove_file(staged);
+        if move_aside {
+            let _ = std::fs::rename(&old, target);
+        }
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn client() -> Result<reqwest::blocking::Client> {
+    Ok(reqwest::blocking::Client::builder()
+        .timeout(DOWNLOAD_TIMEOUT)
+        .user_agent(UPDATE_USER_AGENT)
+        .build()?)
+}
+
+fn download(client: &reqwest::blocking::Client, url: &str) -> Result<Vec<u8>> {
+    let response = client.get(url).send()?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(CrabClawError::Network(format!(
+            "download of {url} failed: HTTP {status}"
+        )));
+    }
+    Ok(response.bytes()?.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn update_checks_report_only_newer_releases() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/latest")
+            .with_status(200)
+            .with_body(r#"{"tag_name":"v1.2.0","html_url":"https://example.com/r","assets":[]}"#)
+            .create();
+        let url = format!("{}/latest", server.url());
+
+        let release = check_for_update(&url, "1.1.0").unwrap().unwrap();
+        assert_eq!(
+            update_notice(&release, "1.1.0"),
+            "crabclaw 1.2.0 is available (running 1.1.0). Run `crabclaw self-update` on the host to install it.\nhttps://example.com/r"
+        );
+        assert!(check_for_update(&url, "1.2.0").unwrap().is_none());
+        assert!(check_for_update(&format!("{}/missing", server.url()), "1.1.0").is_err());
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(is_newer("v0.10.0", "0.9.3"));
+        assert!(is_newer("1.0", "0.9.9"));
+        assert!(!is_newer("v0.1.0", "0.1.0"));
+        assert!(!is_newer("0.2.0-rc.1", "0.1.0"));
+        assert!(!is_newer("nightly", "0.1.0"));
+    }
+
+    #[test]
+    fn checksums_are_matched_by_asset_name() {
+        let bytes = b"binary";
+        let digest = format!("{:x}", Sha256::digest(bytes));
+        let sums = format!("0000  crabclaw-other\n{digest} *crabclaw-x86_64-linux\n");
+
+        assert!(verify_checksum(bytes, &sums, "crabclaw-x86_64-linux").is_ok());
+        let err = verify_checksum(b"tampered", &sums, "crabclaw-x86_64-linux").unwrap_err();
+        assert!(err.to_string().contains("checksum mismatch"), "{err}");
+        let err = verify_checksum(bytes, &sums, "crabclaw-aarch64-macos").unwrap_err();
+        assert!(err.to_string().contains("no entry"), "{err}");
+    }
+
+    #[test]
+    fn binaries_are_replaced_in_place() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("crabclaw");
+        std::fs::write(&target, b"old").unwrap();
+
+        replace_binary(&target, b"new").unwrap();
+        assert_eq!(std::fs::read(&target).unwrap(), b"new");
+        assert!(!dir.path().join("crabclaw.update").exists());
+    }
+
+    #[test]
+    fn failed_install_restores_the_moved_binary() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("crabclaw");
+        std::fs::write(&target, b"old").unwrap();
+
+        // The staged file is missing, so the rename over `target` fails
+        // after the running binary was moved aside.
+        let staged = dir.path().join("crabclaw.update");
+        assert!(install_staged(&staged, &target, true).is_err());
+        assert_eq!(std::fs::read(&target).unwrap(), b"old");
+        assert!(!dir.path().join("crabclaw.old").exists());
+    }
+}
@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 /// Result of executing a shell command.
@@ -11,11 +11,152 @@ pub struct ShellResult {
 }
 
 /// Default timeout for shell commands (30 seconds).
-const DEFAULT_TIMEOUT_SECS: u64 = 30;
+pub(crate) const DEFAULT_TIMEOUT_SECS: u64 = 30;
+
+/// Who issued a shell command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandOrigin {
+    /// Typed by the user (`,git status`).
+    Human,
+    /// Comma-command found in assistant output.
+    Assistant,
+    /// `shell.exec` tool call.
+    Tool,
+}
+
+impl CommandOrigin {
+    /// Whether the command came from the model rather than the user.
+    pub fn is_model(self) -> bool {
+        !matches!(self, CommandOrigin::Human)
+    }
+}
+
+/// Environment variable overriding the command interpreter
+/// (`sh`, `powershell`, `pwsh`, `cmd`).
+pub const SHELL_ENV_KEY: &str = "CRABCLAW_SHELL";
+
+/// Interpreter used to run command lines.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellKind {
+    /// POSIX `sh -c` (default outside Windows).
+    Sh,
+    /// Windows PowerShell (default on Windows).
+    PowerShell,
+    /// PowerShell 7+ (`pwsh`).
+    Pwsh,
+    /// `cmd.exe /C`.
+    Cmd,
+}
+
+impl ShellKind {
+    /// Parse a `CRABCLAW_SHELL` value.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sh" | "bash" | "posix" => Some(Self::Sh),
+            "powershell" => Some(Self::PowerShell),
+            "pwsh" => Some(Self::Pwsh),
+            "cmd" | "cmd.exe" => Some(Self::Cmd),
+            _ => None,
+        }
+    }
+
+    /// Interpreter for this platform, honouring `CRABCLAW_SHELL`.
+    pub fn detect() -> Self {
+        std::env::var(SHELL_ENV_KEY)
+            .ok()
+            .and_then(|v| Self::parse(&v))
+            .unwrap_or(if cfg!(windows) {
+                Self::PowerShell
+            } else {
+                Self::Sh
+            })
+    }
+
+    /// Program and arguments that run `cmd_line`.
+    pub fn argv(self, cmd_line: &str) -> (String, Vec<String>) {
+        let powershell = |program: &str| {
+            (
+                program.to_string(),
+                vec![
+                    "-NoLogo".to_string(),
+                    "-NoProfile".to_string(),
+                    "-NonInteractive".to_string(),
+                    "-Command".to_string(),
+                    cmd_line.to_string(),
+                ],
+            )
+        };
+        match self {
+            Self::Sh => {
+                let program = if cfg!(windows) { "sh" } else { "/bin/sh" };
+                (
+                    program.to_string(),
+                    vec!["-c".to_string(), cmd_line.to_string()],
+                )
+            }
+            Self::PowerShell => powershell("powershell"),
+            Self::Pwsh => powershell("pwsh"),
+            // `/S` makes cmd.exe strip exactly the outer quotes that
+            // `std_command` puts around the line.
+            Self::Cmd => (
+                "cmd".to_string(),
+                vec!["/S".to_string(), "/C".to_string(), cmd_line.to_string()],
+            ),
+        }
+    }
+
+    /// `arg` quoted as a single literal word for this interpreter.
+    ///
+    /// cmd.exe expands `%VAR%` even inside quotes, so each `%` is put
+    /// outside them and escaped with `^` (`"a"^%"b"`).
+    pub fn quote(self, arg: &str) -> String {
+        match self {
+            Self::Sh => format!("'{}'", arg.replace('\'', r"'\''")),
+            Self::PowerShell | Self::Pwsh => format!("'{}'", arg.replace('\'', "''")),
+            Self::Cmd => format!("\"{}\"", arg.replace('"', "\"\"").replace('%', "\"^%\"")),
+        }
+    }
+
+    /// The process running `cmd_line`.
+    ///
+    /// cmd.exe parses the text after `/C` itself and does not understand
+    /// the `\"` escaping `Command::args` applies on Windows, so the line is
+    /// handed over unescaped.
+    pub(crate) fn std_command(self, cmd_line: &str) -> std::process::Command {
+        let (program, args) = self.argv(cmd_line);
+        let mut command = std::process::Command::new(program);
+        #[cfg(windows)]
+        if self == Self::Cmd {
+            use std::os::windows::process::CommandExt;
+
+            command
+                .args(["/S", "/C"])
+                .raw_arg(format!("\"{cmd_line}\""));
+            return command;
+        }
+        command.args(args);
+        command
+    }
+}
+
+/// Working directory to hand to the interpreter.
+///
+/// `canonicalize` on Windows yields verbatim paths (`\\?\C:\...`), which
+/// `cmd.exe` rejects as a current directory; strip that prefix.
+fn shell_dir(workspace: &Path) -> PathBuf {
+    let display = workspace.to_string_lossy();
+    if let Some(unc) = display.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{unc}"))
+    } else if let Some(local) = display.strip_prefix(r"\\?\") {
+        PathBuf::from(local)
+    } else {
+        workspace.to_path_buf()
+    }
+}
 
 /// Execute an arbitrary shell command asynchronously.
 ///
-/// Uses `/bin/sh -c` to run the command. Captures stdout, stderr, and exit code.
+/// Uses the platform interpreter ([`ShellKind::detect`]) to run the command. Captures stdout, stderr, and exit code.
 /// Enforces a default timeout of 30 seconds.
 /// Preferred in async contexts (channels, tool calling loop).
 pub async fn execute_shell_async(cmd_line: &str, workspace: &Path) -> ShellResult {
@@ -33,10 +174,8 @@ pub async fn execute_shell_async_with_timeout(
     workspace: &Path,
     timeout: Duration,
 ) -> ShellResult {
-    let mut child = match tokio::process::Command::new("/bin/sh")
-        .arg("-c")
-        .arg(cmd_line)
-        .current_dir(workspace)
+    let mut child = match tokio::process::Command::from(ShellKind::detect().std_command(cmd_line))
+        .current_dir(shell_dir(workspace))
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()
@@ -105,7 +244,7 @@ pub async fn execute_shell_async_with_timeout(
 
 /// Execute an arbitrary shell command synchronously.
 ///
-/// Uses `/bin/sh -c` to run the command. Captures stdout, stderr, and exit code.
+/// Uses the platform interpreter ([`ShellKind::detect`]) to run the command. Captures stdout, stderr, and exit code.
 /// Enforces a default timeout of 30 seconds.
 /// For use in sync contexts (router command dispatch).
 pub fn execute_shell(cmd_line: &str, workspace: &Path) -> ShellResult {
@@ -122,10 +261,122 @@ pub fn execute_shell_with_timeout(
     workspace: &Path,
     timeout: Duration,
 ) -> ShellResult {
-    let mut child = match std::process::Command::new("/bin/sh")
-        .arg("-c")
-        .arg(cmd_line)
-        .current_dir(workspace)
+    let command = ShellKind::detect().std_command(cmd_line);
+    run_with_timeout(command, workspace, timeout, "shell")
+}
+
+/// Execute a command on behalf of `origin`.
+///
+/// Commands refused by the shell policy (`~/.crabclaw/shell-policy.json`
+/// plus the built-in model denylist) fail without running, and model-originated
+/// commands only inherit the policy's environment passthrough list. Runs
+/// inside the sandbox (`~/.crabclaw/sandbox.json`) when one is configured
+/// and applies to `origin`.
+pub fn execute_shell_as(cmd_line: &str, workspace: &Path, origin: CommandOrigin) -> ShellResult {
+    execute_shell_as_with_timeout(
+        cmd_line,
+        workspace,
+        origin,
+        Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+    )
+}
+
+/// [`execute_shell_as`] with a custom timeout, for long-running tools such
+/// as cargo builds.
+pub fn execute_shell_as_with_timeout(
+    cmd_line: &str,
+    workspace: &Path,
+    origin: CommandOrigin,
+    timeout: Duration,
+) -> ShellResult {
+    match command_as(cmd_line, workspace, origin) {
+        Ok((command, label)) => run_with_timeout(command, workspace, timeout, &label),
+        Err(reason) => ShellResult {
+            stdout: String::new(),
+            stderr: reason,
+            exit_code: -1,
+            timed_out: false,
+        },
+    }
+}
+
+/// The process [`execute_shell_as`] starts for `cmd_line`: checked against
+/// the shell policy, wrapped in the sandbox and with the environment
+/// scrubbed for `origin`. Returns it with a label for logs, or the refusal
+/// reason. For callers that talk to the process instead of waiting for it.
+pub fn command_as(
+    cmd_line: &str,
+    workspace: &Path,
+    origin: CommandOrigin,
+) -> Result<(std::process::Command, String), String> {
+    use crate::core::sandbox::SandboxConfig;
+    use crate::core::shell_policy::ShellPolicy;
+
+    let config_dir = crate::core::config::user_config_dir();
+    let policy =
+        ShellPolicy::load(&config_dir).map_err(|e| format!("refusing to run command: {e}"))?;
+    policy.check(cmd_line, origin)?;
+    let sandbox = SandboxConfig::load(&config_dir)
+        .map_err(|e| format!("refusing to run command: {e}"))?
+        .filter(|s| s.applies_to(origin));
+
+    let (mut command, label) = match sandbox {
+        Some(sandbox) => {
+            let (program, args) = sandbox.wrap(cmd_line, workspace, origin);
+            let mut command = std::process::Command::new(&program);
+            command.args(&args);
+            (command, format!("sandbox ({program})"))
+        }
+        None => (
+            ShellKind::detect().std_command(cmd_line),
+            "shell".to_string(),
+        ),
+    };
+    if let Some(env) = policy.env_for(origin, std::env::vars()) {
+        command.env_clear().envs(env);
+    }
+    command.current_dir(shell_dir(workspace));
+    Ok((command, label))
+}
+
+/// A `git` process for crabclaw's own bookkeeping in `workspace` (context
+/// summary, snapshots). Runs without a sandbox, so fsmonitor and hooks
+/// configured in the repository's `.git/config` are disabled and the
+/// environment is scrubbed like a model command's.
+pub fn git_command(workspace: &Path) -> std::process::Command {
+    use crate::core::shell_policy::ShellPolicy;
+
+    let policy = ShellPolicy::load(&crate::core::config::user_config_dir()).unwrap_or_default();
+    let mut command = std::process::Command::new("git");
+    command
+        .args([
+            "-c",
+            "core.fsmonitor=false",
+            "-c",
+            "core.hooksPath=/dev/null",
+        ])
+        .current_dir(shell_dir(workspace))
+        .env_clear()
+        .envs(
+            policy
+                .env_for(CommandOrigin::Tool, std::env::vars())
+                .unwrap_or_default(),
+        )
+        .env("GIT_OPTIONAL_LOCKS", "0")
+        .stdin(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null());
+    command
+}
+
+/// Spawn `command` in `workspace` and collect its output, killing it on timeout.
+fn run_with_timeout(
+    mut command: std::process::Command,
+    workspace: &Path,
+    timeout: Duration,
+    label: &str,
+) -> ShellResult {
+    let mut child = match command
+        .current_dir(shell_dir(workspace))
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()
@@ -134,31 +385,38 @@ pub fn execute_shell_with_timeout(
         Err(e) => {
             return ShellResult {
                 stdout: String::new(),
-                stderr: format!("failed to spawn shell: {e}"),
+                stderr: format!("failed to spawn {label}: {e}"),
                 exit_code: -1,
                 timed_out: false,
             };
         }
     };
 
+    // Drain both pipes while waiting: a child writing more than the pipe
+    // buffer would otherwise block forever.
+    let drain = |pipe: Option<Box<dyn std::io::Read + Send>>| {
+        std::thread::spawn(move || {
+            use std::io::Read;
+
+            let mut bytes = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut bytes);
+            }
+            String::from_utf8_lossy(&bytes).into_owned()
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
+
     // Wait with timeout using polling.
     let start = std::time::Instant::now();
     loop {
         match child.try_wait() {
-            Ok(Some(_status)) => {
-                // Process finished; collect output.
-                let output = child.wait_with_output().unwrap_or_else(|e| {
-                    // Child already exited — should not fail, but handle gracefully.
-                    std::process::Output {
-                        status: std::process::ExitStatus::default(),
-                        stdout: Vec::new(),
-                        stderr: format!("unexpected error collecting output: {e}").into_bytes(),
-                    }
-                });
+            Ok(Some(status)) => {
                 return ShellResult {
-                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                    exit_code: output.status.code().unwrap_or(-1),
+                    stdout: stdout.join().unwrap_or_default(),
+                    stderr: stderr.join().unwrap_or_default(),
+                    exit_code: status.code().unwrap_or(-1),
                     timed_out: false,
                 };
             }
@@ -166,6 +424,8 @@ pub fn execute_shell_with_timeout(
                 if start.elapsed() >= timeout {
                     let _ = child.kill();
                     let _ = child.wait(); // Reap the process.
+                    // The readers are left behind: grandchildren may still
+                    // hold the pipes open.
                     return ShellResult {
                         stdout: String::new(),
                         stderr: format!("command timed out after {}s", timeout.as_secs()),
@@ -203,6 +463,475 @@ pub fn format_shell_output(result: &ShellResult) -> String {
     }
 }
 
+/// Programs that only read, with the options they may be given. An entry
+/// ending in `=` takes a value, attached (`-n5`, `--lines=5`) or as the
+/// next word.
+const READ_ONLY_PROGRAMS: &[(&str, &[&str])] = &[
+    (
+        "ls",
+        &[
+            "-l",
+            "-a",
+            "-A",
+            "-h",
+            "-R",
+            "-t",
+            "-r",
+            "-S",
+            "-1",
+            "-d",
+            "-F",
+            "-i",
+            "-s",
+            "--all",
+            "--color",
+            "--color=",
+            "--human-readable",
+            "--recursive",
+        ],
+    ),
+    (
+        "cat",
+        &["-n", "-b", "-A", "-E", "-T", "-v", "-s", "--number"],
+    ),
+    ("head", &["-n=", "-c=", "-q", "-v", "--lines=", "--bytes="]),
+    ("tail", &["-n=", "-c=", "-q", "-v", "--lines=", "--bytes="]),
+    (
+        "wc",
+        &[
+            "-l", "-w", "-c", "-m", "-L", "--lines", "--words", "--bytes", "--chars",
+        ],
+    ),
+    ("pwd", &["-L", "-P"]),
+    ("echo", &["-n", "-e", "-E"]),
+    (
+        "grep",
+        &[
+            "-i",
+            "-n",
+            "-r",
+            "-R",
+            "-l",
+            "-L",
+            "-c",
+            "-v",
+            "-w",
+            "-x",
+            "-E",
+            "-F",
+            "-P",
+            "-H",
+            "-h",
+            "-o",
+            "-s",
+            "-q",
+            "-I",
+            "-e=",
+            "-f=",
+            "-A=",
+            "-B=",
+            "-C=",
+            "-m=",
+            "--include=",
+            "--exclude=",
+            "--exclude-dir=",
+            "--color",
+            "--color=",
+            "--recursive",
+            "--ignore-case",
+            "--line-number",
+            "--count",
+            "--files-with-matches",
+            "--invert-match",
+            "--word-regexp",
+            "--fixed-strings",
+            "--extended-regexp",
+            "--regexp=",
+            "--file=",
+            "--max-count=",
+            "--context=",
+            "--after-context=",
+            "--before-context=",
+        ],
+    ),
+    (
+        "rg",
+        &[
+            "-i",
+            "-n",
+            "-N",
+            "-l",
+            "-c",
+            "-v",
+            "-w",
+            "-x",
+            "-F",
+            "-S",
+            "-s",
+            "-o",
+            "-u",
+            "-U",
+            "-e=",
+            "-f=",
+            "-g=",
+            "-t=",
+            "-T=",
+            "-A=",
+            "-B=",
+            "-C=",
+            "-m=",
+            "--glob=",
+            "--type=",
+            "--type-not=",
+            "--hidden",
+            "--files",
+            "--count",
+            "--ignore-case",
+            "--smart-case",
+            "--line-number",
+            "--files-with-matches",
+            "--fixed-strings",
+            "--word-regexp",
+            "--invert-match",
+            "--no-ignore",
+            "--json",
+            "--max-count=",
+            "--context=",
+            "--regexp=",
+            "--color=",
+        ],
+    ),
+    (
+        "tree",
+        &[
+            "-a",
+            "-d",
+            "-f",
+            "-i",
+            "-C",
+            "-h",
+            "-s",
+            "-L=",
+            "-I=",
+            "-P=",
+            "--gitignore",
+            "--noreport",
+            "--dirsfirst",
+        ],
+    ),
+    (
+        "file",
+        &["-b", "-i", "-L", "--brief", "--mime", "--mime-type"],
+    ),
+    ("stat", &["-c=", "-f", "-L", "-t", "--format=", "--printf="]),
+    (
+        "du",
+        &[
+            "-h",
+            "-s",
+            "-a",
+            "-c",
+            "-k",
+            "-m",
+            "-d=",
+            "--max-depth=",
+            "--summarize",
+        ],
+    ),
+    ("df", &["-h", "-H", "-T", "-i", "-k", "--human-readable"]),
+    ("which", &["-a"]),
+    ("whoami", &[]),
+    (
+        "date",
+        &[
+            "-u",
+            "-R",
+            "-I",
+            "-d=",
+            "-r=",
+            "--date=",
+            "--iso-8601",
+            "--iso-8601=",
+            "--utc",
+        ],
+    ),
+    (
+        "uname",
+        &["-a", "-s", "-r", "-m", "-n", "-o", "-v", "-p", "-i"],
+    ),
+    (
+        "diff",
+        &[
+            "-u",
+            "-U=",
+            "-r",
+            "-N",
+            "-q",
+            "-i",
+            "-w",
+            "-b",
+            "-B",
+            "-y",
+            "--brief",
+            "--unified",
+            "--unified=",
+            "--recursive",
+            "--color",
+            "--color=",
+        ],
+    ),
+    (
+        "sort",
+        &[
+            "-u",
+            "-r",
+            "-n",
+            "-f",
+            "-b",
+            "-h",
+            "-V",
+            "-g",
+            "-M",
+            "-s",
+            "-c",
+            "-C",
+            "-z",
+            "-k=",
+            "-t=",
+            "--unique",
+            "--reverse",
+            "--numeric-sort",
+            "--key=",
+            "--field-separator=",
+        ],
+    ),
+    (
+        "cut",
+        &[
+            "-d=",
+            "-f=",
+            "-c=",
+            "-b=",
+            "-s",
+            "--delimiter=",
+            "--fields=",
+            "--complement",
+        ],
+    ),
+    ("basename", &["-a", "-z", "-s="]),
+    ("dirname", &["-z"]),
+    ("realpath", &["-e", "-m", "-s", "-q", "--relative-to="]),
+    ("readlink", &["-f", "-e", "-m", "-n"]),
+];
+
+/// Tests and actions `find` may be given. Its options are whole words
+/// (`-name`), never clusters.
+const FIND_OPTIONS: &[&str] = &[
+    "-name",
+    "-iname",
+    "-path",
+    "-ipath",
+    "-regex",
+    "-type",
+    "-maxdepth",
+    "-mindepth",
+    "-size",
+    "-mtime",
+    "-mmin",
+    "-newer",
+    "-empty",
+    "-print",
+    "-print0",
+    "-not",
+    "-and",
+    "-or",
+    "-a",
+    "-o",
+];
+
+/// Read-only git subcommands with the options they may be given.
+///
+/// Subcommands that compare the work tree or render blobs (`status`,
+/// `diff`, `show`, `log`, `blame`, `ls-files`, `describe`) are left out:
+/// they run the `filter.<x>.clean`, `diff.<x>.textconv` and `gpg.program`
+/// commands named in `.git/config` and `.gitattributes`, which the model
+/// can write.
+const READ_ONLY_GIT: &[(&str, &[&str])] = &[
+    (
+        "rev-parse",
+        &[
+            "--short",
+            "--short=",
+            "--abbrev-ref",
+            "--show-toplevel",
+            "--git-dir",
+            "--verify",
+        ],
+    ),
+    (
+        "shortlog",
+        &["-s", "-n", "-e", "--summary", "--numbered", "--email"],
+    ),
+    (
+        "grep",
+        &[
+            "-n",
+            "-i",
+            "-l",
+            "-c",
+            "-w",
+            "-v",
+            "-E",
+            "-F",
+            "-I",
+            "-e=",
+            "--cached",
+            "--untracked",
+            "--line-number",
+            "--ignore-case",
+            "--count",
+        ],
+    ),
+];
+
+/// Options `git branch` may be given and still only list branches.
+const GIT_BRANCH_LIST: &[&str] = &[
+    "-a",
+    "-r",
+    "-v",
+    "--all",
+    "--remotes",
+    "--list",
+    "--show-current",
+];
+
+/// Whether `cmd_line` only reads `workspace`, so it can run without asking
+/// the user even when shell commands need approval.
+///
+/// Every command of a `|`, `&&`, `||` or `;` chain must be read-only, and
+/// every argument that is not an option must name a path inside the
+/// workspace. Options must be on the program's allowlist, so unknown or
+/// abbreviated ones (`sort --compress-program`) need approval. Redirections, command substitution, background jobs, `~`,
+/// brace expansion and leading environment assignments are never
+/// read-only. The check is conservative: quoting is ignored, so
+/// `grep "a > b"` still needs approval.
+pub fn is_read_only(cmd_line: &str, workspace: &Path) -> bool {
+    if cmd_line.trim().is_empty()
+        || ['>', '<', '`', '\n', '$', '~', '{', '}']
+            .iter()
+            .any(|c| cmd_line.contains(*c))
+    {
+        return false;
+    }
+    let chained = cmd_line
+        .replace("&&", ";")
+        .replace("||", ";")
+        .replace('|', ";");
+    if chained.contains('&') {
+        return false;
+    }
+    chained.split(';').all(|segment| {
+        let words: Vec<&str> = segment.split_whitespace().collect();
+        !words.is_empty()
+            && is_read_only_command(&words)
+            && words[1..].iter().all(|w| stays_in_workspace(w, workspace))
+    })
+}
+
+/// Whether `words` run a read-only program with allowed options only.
+///
+/// git counts only when run as `git -c core.fsmonitor=false <sub>`: reading
+/// the index would otherwise start the fsmonitor program the repository's
+/// `.git/config` names.
+fn is_read_only_command(words: &[&str]) -> bool {
+    match words[0] {
+        "git" => {
+            let ["git", "-c", "core.fsmonitor=false", sub, args @ ..] = words else {
+                return false;
+            };
+            if *sub == "branch" {
+                // Listing only: `git branch foo` creates a branch.
+                return args.iter().all(|w| w.starts_with('-'))
+                    && options_allowed(args, GIT_BRANCH_LIST);
+            }
+            READ_ONLY_GIT
+                .iter()
+                .find(|(name, _)| name == sub)
+                .is_some_and(|(_, allowed)| options_allowed(args, allowed))
+        }
+        "find" => words[1..]
+            .iter()
+            .all(|w| !w.starts_with('-') || FIND_OPTIONS.contains(w)),
+        program => READ_ONLY_PROGRAMS
+            .iter()
+            .find(|(name, _)| *name == program)
+            .is_some_and(|(_, allowed)| options_allowed(&words[1..], allowed)),
+    }
+}
+
+/// Every option in `args` is on `allowed`. Long options must match
+/// exactly; short options may be clustered (`-la`), and the rest of a
+/// cluster after one that takes a value is that value (`-n5`). Words that
+/// are not options are left to [`stays_in_workspace`].
+fn options_allowed(args: &[&str], allowed: &[&str]) -> bool {
+    let allows = |option: &str| allowed.contains(&option);
+    args.iter().all(|arg| {
+        if let Some(long) = arg.strip_prefix("--") {
+            if long.is_empty() {
+                return true;
+            }
+            return match long.split_once('=') {
+                Some((name, _)) => allows(&format!("--{name}=")),
+                None => allows(arg) || allows(&format!("{arg}=")),
+            };
+        }
+        let Some(cluster) = arg.strip_prefix('-') else {
+            return true;
+        };
+        for letter in cluster.chars() {
+            if allows(&format!("-{letter}=")) {
+                return true;
+            }
+            if !allows(&format!("-{letter}")) {
+                return false;
+            }
+        }
+        true
+    })
+}
+
+/// Whether the paths `word` may name stay inside `workspace`: the word
+/// itself, a `--option=value` value, or the value attached to a short
+/// option. Quotes and backslashes are dropped first, as the shell would.
+fn stays_in_workspace(word: &str, workspace: &Path) -> bool {
+    let word: String = word
+        .chars()
+        .filter(|c| !matches!(c, '\'' | '"' | '\\'))
+        .collect();
+    let path = if let Some(long) = word.strip_prefix("--") {
+        match long.split_once('=') {
+            Some((_, value)) => value,
+            None => return true,
+        }
+    } else if let Some(short) = word.strip_prefix('-') {
+        // `-la` is a cluster of flags; `-f/etc/passwd` carries a path.
+        match short.get(1..) {
+            Some(value) if value.contains('/') || value.contains("..") => value,
+            _ => return true,
+        }
+    } else {
+        word.as_str()
+    };
+    if path.is_empty() {
+        return true;
+    }
+    // A glob such as `.*` can expand to `..`.
+    let hidden_glob = path
+        .split('/')
+        .any(|part| part.starts_with('.') && part.contains(['*', '?', '[']));
+    !hidden_glob && crate::tools::file_ops::resolve_safe_path(workspace, path).is_some()
+}
+
 /// Wrap a failed command result into a structured XML context block for the LLM.
 pub fn wrap_failure_context(cmd_line: &str, result: &ShellResult) -> String {
     let output = format_shell_output(result);
@@ -217,6 +946,98 @@ mod tests {
     use super::*;
     use tempfile::tempdir;
 
+    #[test]
+    fn read_only_commands_are_recognized() {
+        let ws = tempdir().unwrap();
+        let is_read_only = |cmd: &str| is_read_only(cmd, ws.path());
+        for cmd in [
+            "ls -la",
+            "cat Cargo.toml | grep version",
+            "git -c core.fsmonitor=false rev-parse --abbrev-ref HEAD && git -c core.fsmonitor=false shortlog -sn",
+            "git -c core.fsmonitor=false branch -a",
+            "git -c core.fsmonitor=false grep -n TODO",
+            "find . -name '*.rs'",
+            "rg TODO src; wc -l README.md",
+            "sort -u -k2 data.txt",
+            "head -n5 src/main.rs",
+        ] {
+            assert!(is_read_only(cmd), "{cmd}");
+        }
+        for cmd in [
+            "",
+            "rm -rf target",
+            "echo hi > notes.txt",
+            "cat $(ls)",
+            "git checkout main",
+            "git -c core.fsmonitor=false branch feature",
+            "git -c core.fsmonitor=false diff --no-ext-diff --output=patch.diff",
+            "cargo build",
+            "cargo check",
+            "cargo clippy --fix",
+            "find . -name '*.tmp' -delete",
+            "sort -o out.txt in.txt",
+            "ls && rm x",
+            "sleep 5 & ls",
+            "FOO=1 ls",
+        ] {
+            assert!(!is_read_only(cmd), "{cmd}");
+        }
+    }
+
+    #[test]
+    fn read_only_bypasses_are_refused() {
+        let ws = tempdir().unwrap();
+        for cmd in [
+            // Attached and clustered short options that write.
+            "sort -oout.txt in.txt",
+            "sort -uo out in",
+            "tree -ofile",
+            "date -us 2020-01-01",
+            // Options that run programs, including abbreviated ones.
+            "sort --compress-program=./x.sh -S1 f",
+            "sort --compress=./x.sh f",
+            "rg --hostname-bin=./x.sh TODO",
+            "rg --pre=./x.sh TODO",
+            "find . -fprint out",
+            // git grep can open matches in any program.
+            "git -c core.fsmonitor=false grep -Ovim TODO",
+            "git -c core.fsmonitor=false grep -iO vim TODO",
+            "git -c core.fsmonitor=false grep --open-files-in-pager=vim TODO",
+            // .git/config can name programs for fsmonitor and diff.
+            "git status",
+            "git diff --stat",
+            "git -c core.fsmonitor=false diff",
+            "git -c core.fsmonitor=false diff --ext-diff --no-ext-diff",
+            // Filters, textconv and signature checks run configured programs.
+            "git -c core.fsmonitor=false status",
+            "git -c core.fsmonitor=false diff --no-ext-diff --stat",
+            "git -c core.fsmonitor=false show --stat",
+            "git -c core.fsmonitor=false log --oneline",
+            "git -c core.fsmonitor=false blame src/main.rs",
+            "git -c core.fsmonitor=false ls-files -m",
+            "git -c core.fsmonitor=false describe --dirty",
+            "git -c core.pager=./x.sh log",
+            "git -c core.fsmonitor=false -c core.fsmonitor=./x.sh status",
+            // Reads outside the workspace.
+            "cat ~/.ssh/id_rsa",
+            "cat /etc/shadow",
+            "cat '/etc/shadow'",
+            "cat \"/etc\"/shadow",
+            "cat ../secret",
+            "cat .\\./secret",
+            "cat {/etc/shadow,x}",
+            "ls .*/",
+            "grep -f/etc/shadow x",
+            "grep --file=../secret x",
+            "find / -name passwd",
+            // Builds run build scripts and proc macros.
+            "cargo check --all-targets",
+            "cargo clippy",
+        ] {
+            assert!(!is_read_only(cmd, ws.path()), "{cmd}");
+        }
+    }
+
     // --- Async tests ---
 
     #[tokio::test]
@@ -251,6 +1072,7 @@ mod tests {
         assert!(!result.timed_out);
     }
 
+    #[cfg(unix)]
     #[test]
     fn captures_stderr() {
         let dir = tempdir().unwrap();
@@ -268,6 +1090,7 @@ mod tests {
         assert!(!result.timed_out);
     }
 
+    #[cfg(unix)]
     #[test]
     fn mixed_stdout_and_stderr() {
         let dir = tempdir().unwrap();
@@ -286,6 +1109,7 @@ mod tests {
         assert!(result.stderr.contains("timed out"));
     }
 
+    #[cfg(unix)]
     #[test]
     fn runs_in_workspace_directory() {
         let dir = tempdir().unwrap();
@@ -298,6 +1122,121 @@ mod tests {
         assert_eq!(output_path, canonical);
     }
 
+    #[test]
+    fn execute_as_without_sandbox_runs_directly() {
+        let dir = tempdir().unwrap();
+        let result = execute_shell_as("echo direct", dir.path(), CommandOrigin::Tool);
+        assert_eq!(result.exit_code, 0);
+        assert_eq!(result.stdout.trim(), "direct");
+    }
+
+    #[test]
+    fn execute_as_ignores_sandbox_config_in_the_workspace() {
+        // The model can write the workspace, so it must not be able to
+        // configure (or break) its own sandbox from there.
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".agent")).unwrap();
+        std::fs::write(dir.path().join(".agent/sandbox.json"), "{not json").unwrap();
+        std::fs::write(
+            dir.path().join(crate::core::sandbox::SANDBOX_CONFIG_FILE),
+            "{",
+        )
+        .unwrap();
+        let result = execute_shell_as("echo direct", dir.path(), CommandOrigin::Tool);
+        assert_eq!(result.exit_code, 0, "stderr: {}", result.stderr);
+        assert_eq!(result.stdout.trim(), "direct");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn workspace_policy_cannot_widen_env_passthrough() {
+        // cargo sets CARGO_PKG_NAME for the test process.
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".agent")).unwrap();
+        std::fs::write(
+            dir.path().join(".agent/shell-policy.json"),
+            r#"{"env":["CARGO_PKG_NAME"]}"#,
+        )
+        .unwrap();
+        let command = "echo \"[$CARGO_PKG_NAME]\"";
+        let result = execute_shell_as(command, dir.path(), CommandOrigin::Tool);
+        assert_eq!(result.stdout.trim(), "[]");
+        let result = execute_shell_as(command, dir.path(), CommandOrigin::Human);
+        assert_eq!(result.stdout.trim(), "[crabclaw]");
+    }
+
+    #[test]
+    fn execute_as_ignores_shell_policy_in_the_workspace() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".agent")).unwrap();
+        std::fs::write(
+            dir.path().join(".agent/shell-policy.json"),
+            r#"{"use_defaults":false}"#,
+        )
+        .unwrap();
+        let result = execute_shell_as("git push -f", dir.path(), CommandOrigin::Tool);
+        assert_eq!(result.exit_code, -1);
+        assert!(
+            result.stderr.contains("refused by shell policy"),
+            "got: {}",
+            result.stderr
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_runs_in_workspace_directory() {
+        let dir = tempdir().unwrap();
+        let result = execute_shell("(Get-Location).Path", dir.path());
+        assert_eq!(result.exit_code, 0, "stderr: {}", result.stderr);
+        let output_path = PathBuf::from(result.stdout.trim()).canonicalize().unwrap();
+        assert_eq!(output_path, dir.path().canonicalize().unwrap());
+    }
+
+    #[test]
+    fn shell_kind_argv() {
+        assert_eq!(ShellKind::parse("PowerShell"), Some(ShellKind::PowerShell));
+        assert_eq!(ShellKind::parse("cmd.exe"), Some(ShellKind::Cmd));
+        assert_eq!(ShellKind::parse("fish"), None);
+
+        let (program, args) = ShellKind::Cmd.argv("dir");
+        assert_eq!(program, "cmd");
+        assert_eq!(args, ["/S", "/C", "dir"]);
+        assert_eq!(
+            ShellKind::Cmd.quote(r#"say "hi" 100%PATH%"#),
+            r#""say ""hi"" 100"^%"PATH"^%"""#
+        );
+        let (program, args) = ShellKind::Pwsh.argv("ls");
+        assert_eq!(program, "pwsh");
+        assert_eq!(args[args.len() - 2..], ["-Command", "ls"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn output_larger_than_pipe_buffer_does_not_block() {
+        let dir = tempdir().unwrap();
+        let result = execute_shell_with_timeout(
+            "head -c 300000 /dev/zero | tr '\\0' x",
+            dir.path(),
+            Duration::from_secs(10),
+        );
+        assert!(!result.timed_out, "{}", result.stderr);
+        assert_eq!(result.stdout.len(), 300_000);
+    }
+
+    #[test]
+    fn shell_dir_strips_verbatim_prefix() {
+        assert_eq!(
+            shell_dir(Path::new(r"\\?\C:\work\repo")),
+            PathBuf::from(r"C:\work\repo")
+        );
+        assert_eq!(
+            shell_dir(Path::new(r"\\?\UNC\server\share")),
+            PathBuf::from(r"\\server\share")
+        );
+        assert_eq!(shell_dir(Path::new("/tmp/ws")), PathBuf::from("/tmp/ws"));
+    }
+
     #[test]
     fn format_output_stdout_only() {
         let result = ShellResult {
@@ -355,4 +1294,33 @@ mod tests {
         assert!(ctx.contains("file not found"));
         assert!(ctx.contains("</command>"));
     }
+
+    #[test]
+    fn policy_refusal_is_reported_without_running() {
+        let dir = tempdir().unwrap();
+        let result = execute_shell_as(
+            "git push --force && touch ran",
+            dir.path(),
+            CommandOrigin::Tool,
+        );
+        assert_ne!(result.exit_code, 0);
+        assert!(result.stderr.contains("refused by shell policy"));
+        assert!(!dir.path().join("ran").exists());
+
+        let ctx = wrap_failure_context("git push --force", &result);
+        assert!(ctx.contains("deny rule"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn model_commands_get_scrubbed_env() {
+        // `cargo test` exports CARGO_PKG_NAME to the test process.
+        let dir = tempdir().unwrap();
+        let cmd = "echo \"pkg=$CARGO_PKG_NAME path=${PATH:+set}\"";
+        let tool = execute_shell_as(cmd, dir.path(), CommandOrigin::Tool);
+        assert_eq!(tool.stdout.trim(), "pkg= path=set");
+
+        let human = execute_shell_as(cmd, dir.path(), CommandOrigin::Human);
+        assert_eq!(human.stdout.trim(), "pkg=crabclaw path=set");
+    }
 }
@@ -0,0 +1,477 @@
+//! Workspace snapshots for `,snapshot` and unattended agent runs.
+//!
+//! A snapshot copies the workspace's files to
+//! `.crabclaw/snapshots/<id>/files` with a `manifest.json` listing them. In a
+//! git repository the files are those `git ls-files` reports (tracked and
+//! untracked, minus ignored ones); elsewhere the workspace is walked,
+//! skipping hidden and build directories. Restoring copies the files back
+//! and deletes files created since, after taking a snapshot of the current
+//! state so the restore itself can be undone.
+//!
+//! Snapshots are taken automatically before agent-mode scheduled jobs,
+//! batch tasks and model rounds that make several changes at once
+//! ([`LARGE_PLAN_CHANGES`]). Automatic snapshots are pruned to the newest
+//! [`MAX_AUTOMATIC_SNAPSHOTS`]; snapshots the user takes are only removed by
+//! `,snapshot delete`. Paths running through a symlink are never copied or
+//! deleted, so neither side of a copy can leave its directory.
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::core::error::Result;
+
+/// Automatic snapshots kept per workspace; older ones are pruned when a new
+/// one is taken.
+pub const MAX_AUTOMATIC_SNAPSHOTS: usize = 20;
+
+/// Tool calls that change the workspace (file writes and edits, commands)
+/// in one model round that make it a large plan, snapshotted before it runs.
+pub const LARGE_PLAN_CHANGES: usize = 3;
+
+/// Largest workspace (total file size) a snapshot copies.
+pub const MAX_SNAPSHOT_BYTES: u64 = 200 * 1024 * 1024;
+
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Directories never included when walking a workspace without git.
+const SKIP_DIRS: &[&str] = &["target", "node_modules", "__pycache__", "dist", "build"];
+
+/// Description of one snapshot, stored as its `manifest.json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub label: String,
+    pub created_at: String,
+    /// Workspace-relative paths, with `/` separators.
+    pub files: Vec<String>,
+    pub bytes: u64,
+    /// Taken before an unattended run rather than by the user.
+    #[serde(default)]
+    pub automatic: bool,
+}
+
+/// What [`restore`] changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreSummary {
+    pub restored: usize,
+    pub removed: usize,
+    /// Snapshot of the state before the restore.
+    pub backup: String,
+}
+
+fn snapshots_dir(workspace: &Path) -> PathBuf {
+    workspace.join(".crabclaw").join("snapshots")
+}
+
+/// Copy the workspace's files into a new snapshot kept until deleted.
+pub fn create(workspace: &Path, label: &str) -> Result<Snapshot> {
+    take(workspace, label, false)
+}
+
+fn take(workspace: &Path, label: &str, automatic: bool) -> Result<Snapshot> {
+    let files = workspace_files(workspace);
+    let bytes: u64 = files
+        .iter()
+        .filter_map(|f| std::fs::metadata(workspace.join(f)).ok())
+        .map(|m| m.len())
+        .sum();
+    if bytes > MAX_SNAPSHOT_BYTES {
+        return Err(io::Error::other(format!(
+            "workspace has {} MB of files; snapshots are limited to {} MB",
+            bytes / (1024 * 1024),
+            MAX_SNAPSHOT_BYTES / (1024 * 1024)
+        ))
+        .into());
+    }
+
+    let root = snapshots_dir(workspace);
+    std::fs::create_dir_all(&root)?;
+    let (id, dir) = new_snapshot_dir(&root)?;
+    let copied = copy_files(workspace, &dir.join("files"), &files);
+    let snapshot = Snapshot {
+        id,
+        label: label.to_string(),
+        created_at: chrono::Local::now().to_rfc3339(),
+        files,
+        bytes,
+        automatic,
+    };
+    let written = copied.and_then(|()| {
+        let manifest = serde_json::to_string_pretty(&snapshot)?;
+        std::fs::write(dir.join(MANIFEST_FILE), manifest)?;
+        Ok(())
+    });
+    if let Err(e) = written {
+        let _ = std::fs::remove_dir_all(&dir);
+        return Err(e);
+    }
+    info!(id = %snapshot.id, files = snapshot.files.len(), "snapshot.created");
+    Ok(snapshot)
+}
+
+/// Snapshot before an unattended run, logging instead of failing. Prunes
+/// old automatic snapshots.
+pub fn create_before_run(workspace: &Path, label: &str) -> Option<String> {
+    let taken = take(workspace, label, true);
+    prune(workspace);
+    match taken {
+        Ok(snapshot) => Some(snapshot.id),
+        Err(e) => {
+            warn!("snapshot.create.error: {e}");
+            None
+        }
+    }
+}
+
+/// Whether a model round calling the tools `names` is a large plan: at
+/// least [`LARGE_PLAN_CHANGES`] of the calls change files or run commands.
+pub fn is_large_plan<'a>(names: impl IntoIterator<Item = &'a str>) -> bool {
+    names
+        .into_iter()
+        .filter(|name| {
+            matches!(
+                *name,
+                "file.write" | "file.edit" | "shell.exec" | "archive.extract"
+            )
+        })
+        .count()
+        >= LARGE_PLAN_CHANGES
+}
+
+/// Snapshots of the workspace, oldest first.
+pub fn list(workspace: &Path) -> Vec<Snapshot> {
+    let Ok(entries) = std::fs::read_dir(snapshots_dir(workspace)) else {
+        return Vec::new();
+    };
+    let mut snapshots: Vec<Snapshot> = entries
+        .flatten()
+        .filter_map(|e| std::fs::read_to_string(e.path().join(MANIFEST_FILE)).ok())
+        .filter_map(|json| serde_json::from_str(&json).ok())
+        .collect();
+    // Ids from the same second get a `-2`, `-3`... suffix.
+    snapshots.sort_by(|a, b| {
+        (&a.created_at, a.id.len(), &a.id).cmp(&(&b.created_at, b.id.len(), &b.id))
+    });
+    snapshots
+}
+
+/// Put the workspace back to snapshot `id`.
+pub fn restore(workspace: &Path, id: &str) -> Result<RestoreSummary> {
+    let Some(snapshot) = list(workspace).into_iter().find(|s| s.id == id) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("no snapshot {id}")).into());
+    };
+    if let Some(bad) = snapshot.files.iter().find(|f| !is_relative(f)) {
+        return Err(io::Error::other(format!("snapshot {id} lists unsafe path {bad}")).into());
+    }
+    let source = snapshots_dir(workspace).join(id).join("files");
+    for file in &snapshot.files {
+        check_no_symlinks(&source, file)?;
+        check_no_symlinks(workspace, file)?;
+    }
+    let backup = take(workspace, &format!("before restoring {id}"), false)?;
+
+    let mut removed = 0;
+    for file in &backup.files {
+        if !snapshot.files.contains(file) {
+            check_no_symlinks(workspace, file)?;
+            std::fs::remove_file(workspace.join(file))?;
+            removed += 1;
+        }
+    }
+    copy_files(&source, workspace, &snapshot.files)?;
+    info!(id, removed, "snapshot.restored");
+    Ok(RestoreSummary {
+        restored: snapshot.files.len(),
+        removed,
+        backup: backup.id,
+    })
+}
+
+/// Remove snapshot `id`.
+pub fn delete(workspace: &Path, id: &str) -> Result<()> {
+    if !list(workspace).iter().any(|s| s.id == id) {
+        return Err(io::Error::new(io::ErrorKind::NotFound, format!("no snapshot {id}")).into());
+    }
+    std::fs::remove_dir_all(snapshots_dir(workspace).join(id))?;
+    info!(id, "snapshot.deleted");
+    Ok(())
+}
+
+/// Text for `,snapshot list`.
+pub fn format_list(snapshots: &[Snapshot]) -> String {
+    if snapshots.is_empty() {
+        return "No snapshots. Create one with ,snapshot create [label]".to_string();
+    }
+    let mut lines = vec!["Snapshots:".to_string()];
+    for s in snapshots.iter().rev() {
+        lines.push(format!(
+            "  {}  {} file(s), {} KB  {}{}",
+            s.id,
+            s.files.len(),
+            s.bytes.div_ceil(1024),
+            s.label,
+            if s.automatic { " (auto)" } else { "" }
+        ));
+    }
+    lines.join("\n")
+}
+
+/// A fresh snapshot directory named after the current time.
+fn new_snapshot_dir(root: &Path) -> Result<(String, PathBuf)> {
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S").to_string();
+    for n in 1.. {
+        let id = if n == 1 {
+            stamp.clone()
+        } else {
+            format!("{stamp}-{n}")
+        };
+        let dir = root.join(&id);
+        match std::fs::create_dir(&dir) {
+            Ok(()) => return Ok((id, dir)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    unreachable!("unbounded loop")
+}
+
+fn copy_files(from: &Path, to: &Path, files: &[String]) -> Result<()> {
+    for file in files {
+        check_no_symlinks(from, file)?;
+        check_no_symlinks(to, file)?;
+        let target = to.join(file);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::copy(from.join(file), &target)?;
+    }
+    Ok(())
+}
+
+/// Refuse `root/relative` when any part of it below `root` is a symlink.
+/// Checking stops at the first part that does not exist yet.
+fn check_no_symlinks(root: &Path, relative: &str) -> Result<()> {
+    let mut path = root.to_path_buf();
+    for part in Path::new(relative).components() {
+        path.push(part);
+        match std::fs::symlink_metadata(&path) {
+            Ok(meta) if meta.file_type().is_symlink() => {
+                return Err(io::Error::other(format!(
+                    "refusing to follow symlink {}",
+                    path.display()
+                ))
+                .into());
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
+
+/// Delete automatic snapshots beyond the newest [`MAX_AUTOMATIC_SNAPSHOTS`].
+fn prune(workspace: &Path) {
+    let snapshots: Vec<Snapshot> = list(workspace)
+        .into_iter()
+        .filter(|s| s.automatic)
+        .collect();
+    let excess = snapshots.len().saturating_sub(MAX_AUTOMATIC_SNAPSHOTS);
+    for old in &snapshots[..excess] {
+        if let Err(e) = std::fs::remove_dir_all(snapshots_dir(workspace).join(&old.id)) {
+            warn!(id = %old.id, "snapshot.prune.error: {e}");
+        }
+    }
+}
+
+fn is_relative(path: &str) -> bool {
+    Path::new(path)
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+}
+
+/// Regular files of the workspace, relative and sorted, never including
+/// `.crabclaw/`.
+fn workspace_files(workspace: &Path) -> Vec<String> {
+    let mut files = git_files(workspace).unwrap_or_else(|| {
+        let mut files = Vec::new();
+        walk(workspace, workspace, &mut files);
+        files
+    });
+    files.retain(|f| {
+        !f.starts_with(".crabclaw/")
+            && std::fs::symlink_metadata(workspace.join(f)).is_ok_and(|m| m.is_file())
+    });
+    files.sort();
+    files.dedup();
+    files
+}
+
+fn git_files(workspace: &Path) -> Option<Vec<String>> {
+    let output = crate::core::shell::git_command(workspace)
+        .args([
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .split('\0')
+            .filter(|f| !f.is_empty())
+            .map(String::from)
+            .collect(),
+    )
+}
+
+fn walk(workspace: &Path, dir: &Path, files: &mut Vec<String>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(kind) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        if kind.is_dir() {
+            if !name.starts_with('.') && !SKIP_DIRS.contains(&name.as_str()) {
+                walk(workspace, &path, files);
+            }
+        } else if kind.is_file()
+            && let Ok(relative) = path.strip_prefix(workspace)
+        {
+            let parts: Vec<_> = relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .collect();
+            files.push(parts.join("/"));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restore_undoes_changes_and_new_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let ws = dir.path();
+        std::fs::create_dir(ws.join("src")).unwrap();
+        std::fs::write(ws.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(ws.join("notes.txt"), "keep").unwrap();
+        std::fs::create_dir(ws.join("target")).unwrap();
+        std::fs::write(ws.join("target/out.bin"), "build output").unwrap();
+
+        let snapshot = create(ws, "before edit").unwrap();
+        assert_eq!(snapshot.files, ["notes.txt", "src/main.rs"]);
+
+        std::fs::write(ws.join("src/main.rs"), "broken").unwrap();
+        std::fs::remove_file(ws.join("notes.txt")).unwrap();
+        std::fs::write(ws.join("src/new.rs"), "new").unwrap();
+
+        let summary = restore(ws, &snapshot.id).unwrap();
+        assert_eq!((summary.restored, summary.removed), (2, 1));
+        assert_eq!(
+            std::fs::read_to_string(ws.join("src/main.rs")).unwrap(),
+            "fn main() {}"
+        );
+        assert_eq!(
+            std::fs::read_to_string(ws.join("notes.txt")).unwrap(),
+            "keep"
+        );
+        assert!(!ws.join("src/new.rs").exists());
+        assert!(ws.join("target/out.bin").exists());
+
+        // The state before the restore was kept and can be restored in turn.
+        restore(ws, &summary.backup).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(ws.join("src/main.rs")).unwrap(),
+            "broken"
+        );
+        assert!(ws.join("src/new.rs").exists());
+    }
+
+    #[test]
+    fn list_and_prune() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let manual = create(dir.path(), "mine").unwrap();
+        for i in 0..MAX_AUTOMATIC_SNAPSHOTS + 2 {
+            create_before_run(dir.path(), &format!("run {i}")).unwrap();
+        }
+        let snapshots = list(dir.path());
+        assert_eq!(snapshots.len(), MAX_AUTOMATIC_SNAPSHOTS + 1);
+        assert_eq!(snapshots[0].id, manual.id);
+        assert_eq!(
+            snapshots.last().unwrap().label,
+            format!("run {}", MAX_AUTOMATIC_SNAPSHOTS + 1)
+        );
+        let listed = format_list(&snapshots);
+        assert!(listed.contains("1 file(s), 1 KB  run 2 (auto)"), "{listed}");
+        assert!(listed.ends_with("1 file(s), 1 KB  mine"), "{listed}");
+        assert!(restore(dir.path(), "missing").is_err());
+
+        delete(dir.path(), &manual.id).unwrap();
+        assert!(list(dir.path()).iter().all(|s| s.automatic));
+        assert!(delete(dir.path(), &manual.id).is_err());
+    }
+
+    #[test]
+    fn large_plans_count_changes_only() {
+        assert!(is_large_plan(["file.write", "file.edit", "shell.exec"]));
+        assert!(!is_large_plan(["file.write", "file.read", "file.edit"]));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn restore_refuses_symlinked_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = tempfile::tempdir().unwrap();
+        let ws = dir.path();
+        std::fs::create_dir(ws.join("src")).unwrap();
+        std::fs::write(ws.join("src/main.rs"), "fn main() {}").unwrap();
+        let snapshot = create(ws, "before").unwrap();
+
+        std::fs::remove_dir_all(ws.join("src")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), ws.join("src")).unwrap();
+        let err = restore(ws, &snapshot.id).unwrap_err();
+        assert!(err.to_string().contains("symlink"), "{err}");
+        assert!(!outside.path().join("main.rs").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn git_files_ignores_repository_fsmonitor() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            std::process::Command::new("git")
+                .args(args)
+                .current_dir(dir.path())
+                .output()
+                .unwrap()
+        };
+        if !git(&["init", "-q"]).status.success() {
+            return;
+        }
+        let hook = dir.path().join("monitor.sh");
+        std::fs::write(&hook, "#!/bin/sh\ntouch \"$(dirname \"$0\")/pwned\"\n").unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+        git(&["config", "core.fsmonitor", hook.to_str().unwrap()]);
+
+        assert_eq!(git_files(dir.path()).unwrap(), vec!["monitor.sh"]);
+        assert!(!dir.path().join("pwned").exists());
+    }
+}
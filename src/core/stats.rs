@@ -0,0 +1,207 @@
+//! Session statistics for `,stats`.
+//!
+//! Everything is read back from the tape: `turn.timing` marks each model
+//! turn, `turn.usage` carries tokens and estimated cost, `tool.call` names
+//! each tool run, and failed turns, commands and tool calls count as errors.
+
+use std::collections::BTreeMap;
+
+use crate::core::agent_loop::{TURN_ERROR_EVENT, TURN_USAGE_EVENT};
+use crate::core::model_runner::{TOOL_CALL_EVENT, TURN_TIMING_EVENT};
+use crate::llm::api_types::Usage;
+use crate::tape::store::TapeStore;
+
+/// Aggregated activity of one session tape.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionStats {
+    pub user_messages: usize,
+    /// Model turns (one per `turn.timing` event).
+    pub turns: usize,
+    /// Tool calls per tool name.
+    pub tool_calls: BTreeMap<String, usize>,
+    pub usage: Usage,
+    pub cost_usd: f64,
+    /// Sum and count of first-token latencies (streamed turns only).
+    first_token_ms: (u64, usize),
+    total_ms: u64,
+    pub turn_errors: usize,
+    pub command_errors: usize,
+    pub tool_errors: usize,
+}
+
+impl SessionStats {
+    pub fn from_tape(tape: &TapeStore) -> Self {
+        let mut stats = Self::default();
+        for entry in tape.entries() {
+            let payload = &entry.payload;
+            match entry.kind.as_str() {
+                "message" if payload["role"] == "user" => stats.user_messages += 1,
+                TURN_TIMING_EVENT => {
+                    stats.turns += 1;
+                    stats.total_ms += payload["total_ms"].as_u64().unwrap_or(0);
+                    if let Some(first) = payload["first_token_ms"].as_u64() {
+                        stats.first_token_ms.0 += first;
+                        stats.first_token_ms.1 += 1;
+                    }
+                }
+                TURN_USAGE_EVENT => {
+                    if let Ok(usage) = serde_json::from_value::<Usage>(payload.clone()) {
+                        stats.usage.accumulate(&usage);
+                    }
+                    stats.cost_usd += payload["cost_usd"].as_f64().unwrap_or(0.0);
+                }
+                TOOL_CALL_EVENT => {
+                    let tool = payload["tool"].as_str().unwrap_or("unknown");
+                    *stats.tool_calls.entry(tool.to_string()).or_default() += 1;
+                    if payload["result"]
+                        .as_str()
+                        .is_some_and(|r| r.starts_with("Error"))
+                    {
+                        stats.tool_errors += 1;
+                    }
+                }
+                TURN_ERROR_EVENT => stats.turn_errors += 1,
+                "command" if payload["status"] == "error" => stats.command_errors += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+
+    /// Mean turn duration in milliseconds.
+    pub fn average_turn_ms(&self) -> Option<u64> {
+        (self.turns > 0).then(|| self.total_ms / self.turns as u64)
+    }
+
+    /// Mean time to the first token in milliseconds.
+    pub fn average_first_token_ms(&self) -> Option<u64> {
+        let (sum, count) = self.first_token_ms;
+        (count > 0).then(|| sum / count as u64)
+    }
+
+    pub fn format(&self) -> String {
+        let secs = |ms: u64| format!("{:.1}s", ms as f64 / 1000.0);
+        let tool_total: usize = self.tool_calls.values().sum();
+        let mut lines = vec![
+            "Session stats:".to_string(),
+            format!(
+                "  Turns: {} ({} user message(s))",
+                self.turns, self.user_messages
+            ),
+        ];
+        if self.tool_calls.is_empty() {
+            lines.push("  Tool calls: 0".to_string());
+        } else {
+            let mut by_tool: Vec<_> = self.tool_calls.iter().collect();
+            by_tool.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            let by_tool: Vec<String> = by_tool
+                .into_iter()
+                .map(|(tool, n)| format!("{tool} {n}"))
+                .collect();
+            lines.push(format!(
+                "  Tool calls: {tool_total} ({})",
+                by_tool.join(", ")
+            ));
+        }
+        let cost = if self.cost_usd > 0.0 {
+            format!(" (~${:.4})", self.cost_usd)
+        } else {
+            String::new()
+        };
+        lines.push(format!(
+            "  Tokens: {} prompt + {} completion = {}{cost}",
+            self.usage.prompt_tokens, self.usage.completion_tokens, self.usage.total_tokens
+        ));
+        if let Some(total) = self.average_turn_ms() {
+            let first = self
+                .average_first_token_ms()
+                .map(|ms| format!("{} first token · ", secs(ms)))
+                .unwrap_or_default();
+            lines.push(format!(
+                "  Average latency: {first}{} per turn",
+                secs(total)
+            ));
+        }
+        lines.push(format!(
+            "  Errors: {} turn, {} command, {} tool",
+            self.turn_errors, self.command_errors, self.tool_errors
+        ));
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn aggregates_tape_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "stats").unwrap();
+        tape.append_message("user", "hi").unwrap();
+        tape.append_event(
+            TURN_USAGE_EVENT,
+            json!({"prompt_tokens": 100, "completion_tokens": 20, "total_tokens": 120, "cost_usd": 0.0015}),
+        )
+        .unwrap();
+        for (tool, result) in [
+            ("shell.exec", "ok"),
+            ("fs.read", "fn main() {}"),
+            ("shell.exec", "Error: no command provided."),
+        ] {
+            tape.append_event(
+                TOOL_CALL_EVENT,
+                json!({"tool": tool, "arguments": "{}", "result": result}),
+            )
+            .unwrap();
+        }
+        tape.append_event(
+            TURN_TIMING_EVENT,
+            json!({"first_token_ms": 800, "total_ms": 3000, "tool_ms": 100, "tool_calls": 3}),
+        )
+        .unwrap();
+        tape.append_message("user", "again").unwrap();
+        tape.append_event(TURN_ERROR_EVENT, json!({"error": "timeout"}))
+            .unwrap();
+        tape.append_event(
+            TURN_TIMING_EVENT,
+            json!({"first_token_ms": null, "total_ms": 1000, "tool_ms": 0, "tool_calls": 0}),
+        )
+        .unwrap();
+        tape.append_event("command", json!({"name": "git", "status": "error"}))
+            .unwrap();
+
+        let stats = SessionStats::from_tape(&tape);
+        assert_eq!(stats.turns, 2);
+        assert_eq!(stats.user_messages, 2);
+        assert_eq!(stats.tool_calls["shell.exec"], 2);
+        assert_eq!(stats.usage.total_tokens, 120);
+        assert_eq!(stats.average_turn_ms(), Some(2000));
+        assert_eq!(stats.average_first_token_ms(), Some(800));
+        assert_eq!(
+            (stats.turn_errors, stats.command_errors, stats.tool_errors),
+            (1, 1, 1)
+        );
+
+        let text = stats.format();
+        assert!(text.contains("Turns: 2 (2 user message(s))"), "{text}");
+        assert!(
+            text.contains("Tool calls: 3 (shell.exec 2, fs.read 1)"),
+            "{text}"
+        );
+        assert!(text.contains("= 120 (~$0.0015)"), "{text}");
+        assert!(text.contains("0.8s first token · 2.0s per turn"), "{text}");
+        assert!(text.contains("Errors: 1 turn, 1 command, 1 tool"), "{text}");
+    }
+
+    #[test]
+    fn empty_session() {
+        let dir = tempfile::tempdir().unwrap();
+        let tape = TapeStore::open(dir.path(), "empty").unwrap();
+        let text = SessionStats::from_tape(&tape).format();
+        assert!(text.contains("Turns: 0"));
+        assert!(text.contains("Tool calls: 0"));
+        assert!(!text.contains("Average latency"));
+    }
+}
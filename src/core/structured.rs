@@ -0,0 +1,115 @@
+//! Structured (JSON) answers for automation callers.
+//!
+//! A caller hands the agent loop a JSON Schema with
+//! `AgentLoop::set_next_output_schema`. The next turn then runs with an
+//! extra system instruction asking for a single JSON value, the answer is
+//! parsed and validated, and a non-conforming answer is sent back to the
+//! model with the validation errors, up to [`MAX_SCHEMA_RETRIES`] times.
+//! The parsed value ends up in `LoopResult::structured_output`.
+
+use serde_json::Value;
+
+use crate::core::json_schema;
+
+/// Follow-up attempts after the first answer fails validation.
+pub const MAX_SCHEMA_RETRIES: usize = 2;
+
+/// System instruction describing the expected answer.
+pub fn schema_instruction(schema: &Value) -> String {
+    format!(
+        "Your final answer must be a single JSON value that matches this JSON Schema. \
+Reply with the JSON only: no explanation and no Markdown fences.\n\n{}",
+        serde_json::to_string_pretty(schema).unwrap_or_else(|_| schema.to_string())
+    )
+}
+
+/// Follow-up message asking the model to fix a rejected answer.
+pub fn retry_prompt(error: &str) -> String {
+    format!(
+        "Your answer was rejected: {error}\nReply again with only the corrected JSON value \
+matching the schema."
+    )
+}
+
+/// Parse the model's answer and validate it against `schema`.
+pub fn parse_output(text: &str, schema: &Value) -> Result<Value, String> {
+    let value = extract_json(text).ok_or_else(|| "the answer is not valid JSON".to_string())?;
+    let errors = json_schema::validate(&value, schema);
+    if errors.is_empty() {
+        Ok(value)
+    } else {
+        Err(format!(
+            "the JSON does not match the schema: {}",
+            errors.join("; ")
+        ))
+    }
+}
+
+/// Find the JSON value in an answer: the whole text, the body of a fenced
+/// code block, or the outermost `{...}` / `[...]` span.
+fn extract_json(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(value) = serde_json::from_str(text) {
+        return Some(value);
+    }
+    if let Some(start) = text.find("```") {
+        let body = &text[start + 3..];
+        let body = body.split_once('\n').map_or(body, |(_, rest)| rest);
+        if let Some(end) = body.find("```")
+            && let Ok(value) = serde_json::from_str(body[..end].trim())
+        {
+            return Some(value);
+        }
+    }
+    ['{', '['].into_iter().find_map(|open| {
+        let close = if open == '{' { '}' } else { ']' };
+        let start = text.find(open)?;
+        let end = text.rfind(close)?;
+        (end > start)
+            .then(|| serde_json::from_str(&text[start..=end]).ok())
+            .flatten()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_is_found_in_fences_and_prose() {
+        assert_eq!(extract_json(" {\"a\": 1} "), Some(json!({ "a": 1 })));
+        assert_eq!(
+            extract_json("Here you go:\n```json\n{\"a\": [1, 2]}\n```\nDone."),
+            Some(json!({ "a": [1, 2] }))
+        );
+        assert_eq!(
+            extract_json("Result: {\"ok\": true} as requested"),
+            Some(json!({ "ok": true }))
+        );
+        assert_eq!(extract_json("[1, 2]"), Some(json!([1, 2])));
+        assert_eq!(extract_json("no json here"), None);
+    }
+
+    #[test]
+    fn answers_are_validated() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "count": { "type": "integer" } },
+            "required": ["count"]
+        });
+        assert_eq!(
+            parse_output("{\"count\": 2}", &schema),
+            Ok(json!({ "count": 2 }))
+        );
+        let err = parse_output("{\"count\": \"two\"}", &schema).unwrap_err();
+        assert!(
+            err.contains("$.count: expected integer, got string"),
+            "{err}"
+        );
+        assert_eq!(
+            parse_output("two", &schema).unwrap_err(),
+            "the answer is not valid JSON"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! Short session titles generated after the first exchange.
+//!
+//! The title is stored as a `session.title` tape event so session listings
+//! can show what a conversation is about instead of only `telegram_123456`.
+//! `SESSION_TITLES` picks how it is made: `model` (default, one short extra
+//! request), `message` (first words of the first message) or `off`.
+
+use serde::Serialize;
+
+use crate::core::config::AppConfig;
+use crate::llm::api_types::{ChatRequest, Message};
+use crate::llm::client::send_chat_request;
+use crate::tape::store::TapeStore;
+
+/// Tape event holding the session title (`{"title": "..."}`).
+pub const TITLE_EVENT: &str = "session.title";
+
+/// Longest title kept, in characters.
+const MAX_TITLE_CHARS: usize = 60;
+
+/// Words taken from the first message when the model cannot name the session.
+const FALLBACK_WORDS: usize = 6;
+
+/// Conversation excerpt sent to the model, in characters per side.
+const EXCERPT_CHARS: usize = 1_000;
+
+const TITLE_PROMPT: &str = "Write a title of at most six words for this conversation. \
+Reply with the title only, without quotes or trailing punctuation.";
+
+/// How sessions get their title.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TitleMode {
+    /// Ask the session's model for a short title.
+    #[default]
+    Model,
+    /// Use the first words of the first message.
+    Message,
+    Off,
+}
+
+impl TitleMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "model" | "on" | "true" | "1" => Some(Self::Model),
+            "message" => Some(Self::Message),
+            "off" | "false" | "0" => Some(Self::Off),
+            _ => None,
+        }
+    }
+}
+
+/// Title recorded in the tape, if any.
+pub fn tape_title(tape: &TapeStore) -> Option<String> {
+    tape.entries()
+        .iter()
+        .rev()
+        .find(|e| e.kind == TITLE_EVENT)
+        .and_then(|e| e.payload.get("title")?.as_str().map(ToString::to_string))
+}
+
+/// Ask `model` for a title; falls back to the first words of `prompt` when
+/// the request fails or returns nothing usable.
+pub async fn generate_title(config: &AppConfig, model: &str, prompt: &str, reply: &str) -> String {
+    let request = ChatRequest {
+        model: model.to_string(),
+        messages: vec![
+            Message::system(TITLE_PROMPT),
+            Message::user(format!(
+                "User: {}\n\nAssistant: {}",
+                excerpt(prompt),
+                excerpt(reply)
+            )),
+        ],
+        max_tokens: Some(24),
+        tool_choice: None,
+        temperature: None,
+        tools: None,
+    };
+    send_chat_request(config, &request)
+        .await
+        .ok()
+        .and_then(|response| response.assistant_content().and_then(clean_title))
+        .unwrap_or_else(|| fallback_title(prompt))
+}
+
+/// Normalize a model-written title: first line, no quotes or trailing
+/// punctuation, capped at [`MAX_TITLE_CHARS`].
+pub fn clean_title(raw: &str) -> Option<String> {
+    let line = raw.lines().map(str::trim).find(|l| !l.is_empty())?;
+    let line = line.strip_prefix("Title:").unwrap_or(line);
+    let title = line
+        .trim()
+        .trim_matches(|c: char| matches!(c, '"' | '\'' | '*' | '`' | '#'))
+        .trim_end_matches(['.', '!', '?', ':'])
+        .trim();
+    if title.is_empty() {
+        return None;
+    }
+    Some(truncate(title))
+}
+
+/// Title made from the first words of the user's message.
+pub fn fallback_title(prompt: &str) -> String {
+    let words: Vec<&str> = prompt.split_whitespace().take(FALLBACK_WORDS).collect();
+    if words.is_empty() {
+        return "Untitled".to_string();
+    }
+    truncate(&words.join(" "))
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_TITLE_CHARS {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(MAX_TITLE_CHARS - 1).collect();
+    format!("{}…", cut.trim_end())
+}
+
+fn excerpt(text: &str) -> String {
+    text.chars().take(EXCERPT_CHARS).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn titles_are_cleaned_and_capped() {
+        assert_eq!(
+            clean_title("\"Debugging the Telegram bot.\"\nextra").as_deref(),
+            Some("Debugging the Telegram bot")
+        );
+        assert_eq!(
+            clean_title("Title: Rust lifetimes").as_deref(),
+            Some("Rust lifetimes")
+        );
+        assert_eq!(clean_title("  \n \"\" "), None);
+        let long = clean_title(&"word ".repeat(40)).unwrap();
+        assert!(long.ends_with('…'));
+        assert!(long.chars().count() <= MAX_TITLE_CHARS);
+    }
+
+    #[test]
+    fn fallback_uses_first_words() {
+        assert_eq!(
+            fallback_title("how do I   set up cron jobs on my server today"),
+            "how do I set up cron"
+        );
+        assert_eq!(fallback_title("   "), "Untitled");
+    }
+
+    #[test]
+    fn title_modes_parse() {
+        assert_eq!(TitleMode::parse("Model"), Some(TitleMode::Model));
+        assert_eq!(TitleMode::parse("message"), Some(TitleMode::Message));
+        assert_eq!(TitleMode::parse("off"), Some(TitleMode::Off));
+        assert_eq!(TitleMode::parse("llm"), None);
+    }
+
+    #[test]
+    fn latest_title_event_wins() {
+        let dir = tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "s").unwrap();
+        assert_eq!(tape_title(&tape), None);
+        tape.append_event(TITLE_EVENT, serde_json::json!({"title": "First"}))
+            .unwrap();
+        tape.append_event(TITLE_EVENT, serde_json::json!({"title": "Second"}))
+            .unwrap();
+        assert_eq!(tape_title(&tape).as_deref(), Some("Second"));
+    }
+}
@@ -0,0 +1,63 @@
+//! Per-turn trace IDs.
+//!
+//! Every agent turn gets a short random ID that ties together what the turn
+//! did: the `turn_id` field of its tracing span, the `turn` of each tape
+//! entry and audit record written during it, and the `X-Client-Request-Id`
+//! header of its OpenAI-compatible and Responses API requests. Turn errors
+//! end with `(turn <id>)`, so a user can quote the ID when reporting a
+//! problem and the matching logs can be found with `grep <id>`.
+
+use std::future::Future;
+
+/// Request header carrying the turn ID to providers that log it.
+pub const TURN_ID_HEADER: &str = "X-Client-Request-Id";
+
+tokio::task_local! {
+    static TURN_ID: String;
+}
+
+/// A new turn ID: 12 lowercase hex digits.
+pub fn new_turn_id() -> String {
+    format!("{:012x}", rand::random::<u64>() & 0xffff_ffff_ffff)
+}
+
+/// Run `future` as part of turn `id`, making it visible to
+/// [`current_turn_id`].
+pub async fn in_turn<F: Future>(id: String, future: F) -> F::Output {
+    TURN_ID.scope(id, future).await
+}
+
+/// ID of the turn the current task is running, if any.
+pub fn current_turn_id() -> Option<String> {
+    TURN_ID.try_with(Clone::clone).ok()
+}
+
+/// Add the current turn ID, if any, as [`TURN_ID_HEADER`].
+pub fn tag_request(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match current_turn_id() {
+        Some(id) => builder.header(TURN_ID_HEADER, id),
+        None => builder,
+    }
+}
+
+/// `error` with the turn ID appended, for messages shown to users.
+pub fn tag_error(error: &str, id: &str) -> String {
+    format!("{error} (turn {id})")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn turn_ids_are_scoped_to_the_turn() {
+        let id = new_turn_id();
+        assert_eq!(id.len(), 12);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+
+        assert_eq!(current_turn_id(), None);
+        let seen = in_turn(id.clone(), async { current_turn_id() }).await;
+        assert_eq!(seen.as_deref(), Some(id.as_str()));
+        assert_eq!(current_turn_id(), None);
+    }
+}
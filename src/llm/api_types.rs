@@ -111,7 +111,84 @@ pub struct ChatRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<ToolDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+}
+
+/// Whether the model may, must or must not call tools on a request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolChoice {
+    /// The model decides (the provider default).
+    Auto,
+    /// The model must call at least one tool.
+    Any,
+    /// The model must answer in text.
+    None,
+    /// The model must call the named tool.
+    Tool(String),
+}
+
+impl ToolChoice {
+    /// Parse `auto`, `any` (or `required`), `none` or `tool:<name>`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim() {
+            "auto" => Some(Self::Auto),
+            "any" | "required" => Some(Self::Any),
+            "none" => Some(Self::None),
+            other => other
+                .strip_prefix("tool:")
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(|name| Self::Tool(name.to_string())),
+        }
+    }
+
+    /// Anthropic form: `{"type": "auto" | "any" | "none" | "tool", "name"?}`.
+    pub fn to_anthropic(&self) -> serde_json::Value {
+        match self {
+            Self::Auto => serde_json::json!({"type": "auto"}),
+            Self::Any => serde_json::json!({"type": "any"}),
+            Self::None => serde_json::json!({"type": "none"}),
+            Self::Tool(name) => serde_json::json!({"type": "tool", "name": name}),
+        }
+    }
+
+    /// Whether this choice forces a tool call.
+    pub fn forces_tool(&self) -> bool {
+        matches!(self, Self::Any | Self::Tool(_))
+    }
+}
+
+/// Serializes in the OpenAI chat completions form.
+impl Serialize for ToolChoice {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        match self {
+            Self::Auto => serializer.serialize_str("auto"),
+            Self::Any => serializer.serialize_str("required"),
+            Self::None => serializer.serialize_str("none"),
+            Self::Tool(name) => serde_json::json!({"type": "function", "function": {"name": name}})
+                .serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ToolChoice {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::parse(&value).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "invalid tool_choice '{value}': expected auto, any, none or tool:<name>"
+            ))
+        })
+    }
 }
 
 /// A single choice returned by the API.
@@ -133,8 +210,14 @@ impl Choice {
     }
 }
 
+/// Whether a finish reason means the output token limit cut the answer off
+/// (`length` for OpenAI-compatible APIs, `max_tokens` for Anthropic).
+pub fn is_truncated(finish_reason: Option<&str>) -> bool {
+    matches!(finish_reason, Some("length" | "max_tokens"))
+}
+
 /// Token usage statistics.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Usage {
     #[serde(default)]
     pub prompt_tokens: u32,
@@ -144,6 +227,15 @@ pub struct Usage {
     pub total_tokens: u32,
 }
 
+impl Usage {
+    /// Add another response's usage to this running total.
+    pub fn accumulate(&mut self, other: &Usage) {
+        self.prompt_tokens += other.prompt_tokens;
+        self.completion_tokens += other.completion_tokens;
+        self.total_tokens += other.total_tokens;
+    }
+}
+
 /// Response body from the chat completions endpoint.
 ///
 /// All fields are optional or defaulted to handle non-standard API providers
@@ -211,6 +303,8 @@ pub enum StreamChunk {
     },
     /// A chunk of JSON arguments for an ongoing tool call
     ToolCallArgument { index: usize, text: String },
+    /// Why the model stopped (`stop`, `length`, `max_tokens`, ...)
+    Finish(String),
     /// The stream has finished normally
     Done,
 }
@@ -276,9 +370,66 @@ pub struct AnthropicRequest {
     pub messages: Vec<AnthropicMessage>,
     pub max_tokens: u32,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub system: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<AnthropicToolDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AnthropicMetadata>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thinking: Option<AnthropicThinking>,
+}
+
+/// Request metadata; `user_id` lets Anthropic attribute abuse reports.
+#[derive(Debug, Clone, Serialize)]
+pub struct AnthropicMetadata {
+    pub user_id: String,
+}
+
+/// Extended thinking settings (`{"type": "enabled", "budget_tokens": N}`).
+#[derive(Debug, Clone, Serialize)]
+pub struct AnthropicThinking {
+    #[serde(rename = "type")]
+    pub thinking_type: String,
+    pub budget_tokens: u32,
+}
+
+/// Extra Anthropic request parameters from the `ANTHROPIC_PARAMS` config
+/// value, a JSON object such as
+/// `{"top_k": 40, "user_id": "ops", "thinking_budget": 2048, "tool_choice": "auto"}`.
+///
+/// `temperature` applies only when the caller did not set one (personas do).
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnthropicParams {
+    pub temperature: Option<f32>,
+    pub top_k: Option<u32>,
+    pub top_p: Option<f32>,
+    pub user_id: Option<String>,
+    /// Token budget for extended thinking; must be at least 1024.
+    pub thinking_budget: Option<u32>,
+    pub tool_choice: Option<ToolChoice>,
+}
+
+impl AnthropicParams {
+    pub fn parse(json: &str) -> std::result::Result<Self, String> {
+        let params: Self = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        if let Some(budget) = params.thinking_budget
+            && budget < 1024
+        {
+            return Err(format!(
+                "thinking_budget must be at least 1024, got {budget}"
+            ));
+        }
+        Ok(params)
+    }
 }
 
 /// A message in Anthropic format with structured content blocks.
@@ -489,6 +640,9 @@ pub enum AnthropicStreamBlock {
     Text { text: String },
     #[serde(rename = "tool_use")]
     ToolUse { id: String, name: String },
+    /// Thinking and other blocks that are not surfaced.
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -498,6 +652,8 @@ pub enum AnthropicStreamDelta {
     TextDelta { text: String },
     #[serde(rename = "input_json_delta")]
     InputJsonDelta { partial_json: String },
+    #[serde(other)]
+    Other,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -588,6 +744,8 @@ mod tests {
             model: "gpt-4".to_string(),
             messages: vec![Message::system("You are helpful."), Message::user("Hello")],
             max_tokens: Some(1024),
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
@@ -608,6 +766,8 @@ mod tests {
             model: "gpt-4".to_string(),
             messages: vec![Message::user("Hi")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
         let json = serde_json::to_value(&req).expect("serialize");
@@ -739,4 +899,40 @@ mod tests {
         assert_eq!(usage.completion_tokens, 0);
         assert_eq!(usage.total_tokens, 42);
     }
+
+    #[test]
+    fn length_finish_reasons_mean_truncation() {
+        assert!(is_truncated(Some("length")));
+        assert!(is_truncated(Some("max_tokens")));
+        assert!(!is_truncated(Some("stop")));
+        assert!(!is_truncated(None));
+    }
+
+    #[test]
+    fn tool_choice_serializes_per_provider() {
+        let tool = ToolChoice::parse("tool:file.read").unwrap();
+        assert_eq!(
+            serde_json::to_value(&tool).unwrap(),
+            serde_json::json!({"type": "function", "function": {"name": "file.read"}})
+        );
+        assert_eq!(
+            tool.to_anthropic(),
+            serde_json::json!({"type": "tool", "name": "file.read"})
+        );
+        let any = ToolChoice::parse("required").unwrap();
+        assert_eq!(serde_json::to_value(&any).unwrap(), "required");
+        assert_eq!(any.to_anthropic(), serde_json::json!({"type": "any"}));
+        assert_eq!(ToolChoice::parse("tool:"), None);
+    }
+
+    #[test]
+    fn anthropic_params_are_validated() {
+        let params =
+            AnthropicParams::parse(r#"{"top_k": 40, "user_id": "ops", "tool_choice": "any"}"#)
+                .unwrap();
+        assert_eq!(params.top_k, Some(40));
+        assert_eq!(params.tool_choice, Some(ToolChoice::Any));
+        assert!(AnthropicParams::parse(r#"{"thinking_budget": 100}"#).is_err());
+        assert!(AnthropicParams::parse(r#"{"top_z": 1}"#).is_err());
+    }
 }
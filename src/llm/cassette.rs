@@ -0,0 +1,332 @@
+//! Record and replay provider HTTP traffic.
+//!
+//! With `LLM_CASSETTE=<file>` every model request goes through the
+//! cassette (see `llm::http::send`).
+//! In record mode (`LLM_CASSETTE_MODE=record`) requests reach the provider
+//! as usual and each exchange is appended to the file: method, path and
+//! JSON body of the request, then status, content type and body of the
+//! response. Streamed bodies are stored chunk by chunk as they arrived.
+//! In replay mode (the default) nothing leaves the process: each request
+//! is answered with the first unused recording with the same method and
+//! path, preferring one whose body matches too, and streamed bodies are
+//! replayed with their original chunk boundaries.
+//!
+//! Credentials are never written: request headers are not recorded.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::core::error::{CrabClawError, Result};
+
+/// Response headers kept in a recording.
+const KEPT_HEADERS: [&str; 2] = ["content-type", "retry-after"];
+
+/// Whether `LLM_CASSETTE` is written or read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// The cassette file and what to do with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CassetteConfig {
+    pub path: PathBuf,
+    pub mode: CassetteMode,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Cassette {
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    /// Path and query, without the host, so a recording replays against any
+    /// `BASE_URL`.
+    path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    /// Body as received; one element per network chunk.
+    chunks: Vec<String>,
+}
+
+/// Recordings appended in this process, by file.
+static RECORDINGS: OnceLock<Mutex<HashMap<PathBuf, Cassette>>> = OnceLock::new();
+/// Loaded cassettes, by file.
+static REPLAYS: OnceLock<Mutex<HashMap<PathBuf, Replay>>> = OnceLock::new();
+
+/// A loaded cassette and which of its interactions were replayed.
+struct Replay {
+    cassette: Cassette,
+    used: Vec<bool>,
+}
+
+/// Answer `request` from the cassette, or send it and record the exchange.
+pub(crate) async fn exchange(
+    cassette: &CassetteConfig,
+    client: &reqwest::Client,
+    request: reqwest::Request,
+    on_error: impl Fn(reqwest::Error) -> CrabClawError,
+) -> Result<reqwest::Response> {
+    let recorded = recorded_request(&request);
+    match cassette.mode {
+        CassetteMode::Replay => replay(&cassette.path, &recorded),
+        CassetteMode::Record => {
+            let response = client.execute(request).await.map_err(on_error)?;
+            Ok(record(&cassette.path, recorded, response))
+        }
+    }
+}
+
+fn recorded_request(request: &reqwest::Request) -> RecordedRequest {
+    let url = request.url();
+    let path = match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    };
+    let body = request.body().and_then(|b| b.as_bytes()).map(|bytes| {
+        serde_json::from_slice(bytes)
+            .unwrap_or_else(|_| Value::String(String::from_utf8_lossy(bytes).into_owned()))
+    });
+    RecordedRequest {
+        method: request.method().to_string(),
+        path,
+        body,
+    }
+}
+
+fn replay(path: &Path, request: &RecordedRequest) -> Result<reqwest::Response> {
+    let mut replays = REPLAYS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if !replays.contains_key(path) {
+        let cassette = load(path)?;
+        let used = vec![false; cassette.interactions.len()];
+        replays.insert(path.to_path_buf(), Replay { cassette, used });
+    }
+    let Replay { cassette, used } = replays.get_mut(path).expect("cassette just loaded");
+
+    let unused = |i: &usize| {
+        let recorded = &cassette.interactions[*i].request;
+        !used[*i] && recorded.method == request.method && recorded.path == request.path
+    };
+    let index = (0..used.len())
+        .filter(unused)
+        .find(|&i| cassette.interactions[i].request.body == request.body)
+        .or_else(|| (0..used.len()).find(unused))
+        .ok_or_else(|| {
+            CrabClawError::Config(format!(
+                "cassette {} has no unused recording for {} {}",
+                path.display(),
+                request.method,
+                request.path
+            ))
+        })?;
+    used[index] = true;
+    debug!(cassette = %path.display(), index, path = %request.path, "cassette.replay");
+    response_from(&cassette.interactions[index].response)
+}
+
+fn load(path: &Path) -> Result<Cassette> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        CrabClawError::Config(format!("cannot read cassette {}: {e}", path.display()))
+    })?;
+    serde_json::from_str(&content)
+        .map_err(|e| CrabClawError::Config(format!("invalid cassette {}: {e}", path.display())))
+}
+
+fn response_from(recorded: &RecordedResponse) -> Result<reqwest::Response> {
+    let mut builder = http::Response::builder().status(recorded.status);
+    for (name, value) in &recorded.headers {
+        builder = builder.header(name, value);
+    }
+    let chunks: Vec<std::result::Result<String, std::io::Error>> =
+        recorded.chunks.iter().cloned().map(Ok).collect();
+    let body = reqwest::Body::wrap_stream(futures_util::stream::iter(chunks));
+    let response = builder
+        .body(body)
+        .map_err(|e| CrabClawError::Config(format!("invalid recorded response: {e}")))?;
+    Ok(reqwest::Response::from(response))
+}
+
+/// Pass `response` through, writing the exchange to the cassette once its
+/// body has been read or dropped. Bodies cut short by a transport error are
+/// not recorded.
+fn record(path: &Path, request: RecordedRequest, response: reqwest::Response) -> reqwest::Response {
+    let path = path.to_path_buf();
+    let status = response.status().as_u16();
+    let headers = KEPT_HEADERS
+        .iter()
+        .filter_map(|&name| {
+            let value = response.headers().get(name)?.to_str().ok()?;
+            Some((name.to_string(), value.to_string()))
+        })
+        .collect();
+    crate::llm::http::tee(response, move |chunks, complete| {
+        if !complete {
+            return;
+        }
+        let interaction = Interaction {
+            request,
+            response: RecordedResponse {
+                status,
+                headers,
+                chunks: utf8_chunks(&chunks),
+            },
+        };
+        save(&path, interaction);
+    })
+}
+
+/// Body chunks as text, moving a UTF-8 sequence split between two chunks
+/// into the later one.
+fn utf8_chunks(chunks: &[Vec<u8>]) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut pending = Vec::new();
+    for chunk in chunks {
+        pending.extend_from_slice(chunk);
+        let valid = match std::str::from_utf8(&pending) {
+            Ok(text) => text.len(),
+            Err(e) => e.valid_up_to(),
+        };
+        if valid > 0 {
+            let rest = pending.split_off(valid);
+            let text = std::mem::replace(&mut pending, rest);
+            texts.push(String::from_utf8(text).expect("checked UTF-8 prefix"));
+        }
+    }
+    if !pending.is_empty() {
+        texts.push(String::from_utf8_lossy(&pending).into_owned());
+    }
+    texts
+}
+
+fn save(path: &Path, interaction: Interaction) {
+    let mut recordings = RECORDINGS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    let cassette = recordings.entry(path.to_path_buf()).or_default();
+    cassette.interactions.push(interaction);
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        let _ = std::fs::create_dir_all(dir);
+    }
+    let written = serde_json::to_string_pretty(&*cassette)
+        .map_err(|e| e.to_string())
+        .and_then(|json| std::fs::write(path, json + "\n").map_err(|e| e.to_string()));
+    match written {
+        Ok(()) => debug!(cassette = %path.display(), "cassette.record"),
+        Err(e) => tracing::warn!(cassette = %path.display(), error = %e, "cassette.write_failed"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::config::AppConfig;
+    use crate::llm::api_types::{ChatRequest, Message, StreamChunk};
+
+    fn config(api_base: &str, cassette: &Path, mode: &str) -> AppConfig {
+        let env: HashMap<String, String> = [
+            ("API_KEY", "test-key"),
+            ("BASE_URL", api_base),
+            ("MODEL", "anthropic:test-model"),
+            ("LLM_CASSETTE", &cassette.display().to_string()),
+            ("LLM_CASSETTE_MODE", mode),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        crate::core::config::resolve_config(None, &Default::default(), &env, &HashMap::new())
+            .unwrap()
+    }
+
+    async fn stream_text(config: &AppConfig) -> Result<String> {
+        let request = ChatRequest {
+            model: config.model.clone(),
+            messages: vec![Message::user("hi")],
+            max_tokens: None,
+            temperature: None,
+            tools: None,
+            tool_choice: None,
+        };
+        let mut rx = crate::llm::client::send_chat_request_stream(config, &request).await?;
+        let mut text = String::new();
+        while let Some(chunk) = rx.recv().await {
+            match chunk? {
+                StreamChunk::Content(delta) => text.push_str(&delta),
+                StreamChunk::Done => break,
+                _ => {}
+            }
+        }
+        Ok(text)
+    }
+
+    #[tokio::test]
+    async fn recorded_streams_replay_without_the_provider() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fixtures/anthropic.json");
+        let mut server = mockito::Server::new_async().await;
+        let body = concat!(
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"usage\":{\"input_tokens\":3}}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Grüß \"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"dich\"}}\n\n",
+            "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+        );
+        let mock = server
+            .mock("POST", "/v1/messages")
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let recording = config(&server.url(), &path, "record");
+        assert_eq!(stream_text(&recording).await.unwrap(), "Grüß dich");
+        mock.assert_async().await;
+        let saved = load(&path).unwrap();
+        assert_eq!(saved.interactions.len(), 1);
+        assert_eq!(saved.interactions[0].request.path, "/v1/messages");
+        assert_eq!(saved.interactions[0].response.chunks.concat(), body);
+        assert!(!std::fs::read_to_string(&path).unwrap().contains("test-key"));
+
+        let replaying = config("http://127.0.0.1:9", &path, "replay");
+        assert_eq!(stream_text(&replaying).await.unwrap(), "Grüß dich");
+        let err = stream_text(&replaying).await.unwrap_err().to_string();
+        assert!(
+            err.contains("no unused recording for POST /v1/messages"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn split_utf8_sequences_are_kept_whole() {
+        let bytes = "aü".as_bytes();
+        assert_eq!(
+            utf8_chunks(&[bytes[..2].to_vec(), bytes[2..].to_vec()]),
+            ["a", "ü"]
+        );
+    }
+}
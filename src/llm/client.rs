@@ -1,40 +1,88 @@
-use std::sync::OnceLock;
 use std::time::Duration;
 
 use tracing::{debug, info, instrument, warn};
 
 use crate::core::config::AppConfig;
-use crate::core::error::{CrabClawError, Result};
+use crate::core::error::{CrabClawError, ProviderError, Result, parse_retry_after};
+use crate::core::trace;
 use crate::llm::api_types::{
-    AnthropicRequest, ApiErrorBody, ChatRequest, ChatResponse, StreamChunk,
+    AnthropicMetadata, AnthropicParams, AnthropicRequest, AnthropicThinking, ApiErrorBody,
+    ChatRequest, ChatResponse, StreamChunk, ToolChoice,
 };
+use crate::llm::health;
+use crate::llm::quirks::{self, ProviderQuirks, StreamNormalizer};
+use crate::llm::sse::SseParser;
 use futures_util::StreamExt;
 use tokio::sync::mpsc;
 
-/// Global shared HTTP client for connection pooling across all requests.
-static HTTP_CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+/// Pooled HTTP client for `provider` (see `llm::http`).
+fn http_client(config: &AppConfig, provider: &str) -> Result<reqwest::Client> {
+    crate::llm::http::provider_client(config, provider)
+}
 
-fn get_http_client() -> &'static reqwest::Client {
-    HTTP_CLIENT.get_or_init(|| {
-        reqwest::Client::builder()
-            .timeout(Duration::from_secs(DEFAULT_TIMEOUT_SECS))
-            .pool_max_idle_per_host(5)
-            .build()
-            .expect("failed to build HTTP client")
-    })
+/// Send a non-streaming request, bounded by the whole-request timeout.
+async fn send_request(
+    config: &AppConfig,
+    builder: reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    let timeout = Duration::from_secs(config.llm_timeouts.request_secs);
+    let builder = builder.timeout(timeout);
+    crate::llm::http::send(config, builder, |e| request_error(e, timeout)).await
 }
 
-/// Non-standard error response (e.g. GLM returns HTTP 200 with error JSON).
-#[derive(Debug, serde::Deserialize)]
-struct NonStandardError {
-    code: Option<i32>,
-    msg: Option<String>,
-    success: Option<bool>,
+/// Send a streaming request, waiting at most the first-byte timeout for the
+/// response headers. The body is read with [`next_stream_chunk`].
+async fn send_streaming_request(
+    config: &AppConfig,
+    builder: reqwest::RequestBuilder,
+) -> Result<reqwest::Response> {
+    let first_byte = Duration::from_secs(config.llm_timeouts.first_byte_secs);
+    let send = crate::llm::http::send(config, builder, |e| request_error(e, first_byte));
+    match tokio::time::timeout(first_byte, send).await {
+        Ok(result) => result,
+        Err(_) => Err(CrabClawError::Network(format!(
+            "no response within {}s",
+            first_byte.as_secs()
+        ))),
+    }
 }
 
-const DEFAULT_TIMEOUT_SECS: u64 = 60;
-const MAX_RETRIES: usize = 3;
-const INITIAL_RETRY_DELAY_MS: u64 = 1000;
+/// Next body chunk of a streaming response; an error when the stream stays
+/// quiet for longer than `idle`, `None` at the end of the body.
+async fn next_stream_chunk<S, B>(stream: &mut S, idle: Duration) -> Option<Result<B>>
+where
+    S: futures_util::Stream<Item = reqwest::Result<B>> + Unpin,
+{
+    match tokio::time::timeout(idle, stream.next()).await {
+        Ok(chunk) => {
+            chunk.map(|r| r.map_err(|e| CrabClawError::Network(format!("stream error: {e}"))))
+        }
+        Err(_) => Some(Err(CrabClawError::Network(format!(
+            "stream idle for {}s",
+            idle.as_secs()
+        )))),
+    }
+}
+
+fn request_error(e: reqwest::Error, timeout: Duration) -> CrabClawError {
+    if e.is_timeout() {
+        CrabClawError::Network(format!("request timed out after {}s", timeout.as_secs()))
+    } else if e.is_connect() {
+        CrabClawError::Network(format!("connection failed: {e}"))
+    } else {
+        CrabClawError::Network(format!("request failed: {e}"))
+    }
+}
+
+/// Longest `Retry-After` wait honoured before a retry.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Wait before the next attempt: the `backoff` delay, or the provider's
+/// `Retry-After` when that is longer (capped at [`MAX_RETRY_AFTER`]).
+pub(crate) fn retry_delay(err: &CrabClawError, backoff: Duration) -> Duration {
+    err.retry_after()
+        .map_or(backoff, |wait| wait.min(MAX_RETRY_AFTER).max(backoff))
+}
 
 fn merged_system_prompt(messages: &[crate::llm::api_types::Message]) -> Option<String> {
     let mut combined = String::new();
@@ -84,41 +132,64 @@ fn codex_response_to_stream_chunks(resp: &ChatResponse) -> Vec<StreamChunk> {
     out
 }
 
+/// Send a request for the Responses API providers (`codex:` and `responses:`
+/// models); `None` for other providers.
+async fn send_responses_api_request(
+    config: &AppConfig,
+    request: &ChatRequest,
+) -> Option<Result<ChatResponse>> {
+    let system_prompt = merged_system_prompt(&request.messages);
+    if let Some(model) = request.model.strip_prefix("codex:") {
+        let result =
+            crate::llm::codex::send_codex_request(config, model, request, system_prompt.as_deref())
+                .await;
+        Some(result)
+    } else if let Some(model) = request.model.strip_prefix("responses:") {
+        let result = crate::llm::codex::send_responses_request(
+            config,
+            model,
+            request,
+            system_prompt.as_deref(),
+        )
+        .await;
+        Some(result)
+    } else {
+        None
+    }
+}
+
 /// Send a chat completion request, automatically choosing the provider SDK
 /// based on the model prefix (`provider:model`).
 ///
-/// Retries on 429 (rate limit) and network errors with exponential backoff.
+/// Retries rate limits, overloaded providers and network errors with
+/// exponential backoff per `LLM_MAX_RETRIES` and `LLM_RETRY_DELAY_MS` (see
+/// [`CrabClawError::is_retryable`]).
+/// Providers that keep failing are skipped (see [`health`]).
 #[instrument(skip_all, fields(model = %request.model))]
 pub async fn send_chat_request(config: &AppConfig, request: &ChatRequest) -> Result<ChatResponse> {
-    let mut delay_ms = INITIAL_RETRY_DELAY_MS;
+    let retry = config.llm_retry;
 
-    for attempt in 0..=MAX_RETRIES {
-        let result = if let Some(codex_model) = request.model.strip_prefix("codex:") {
-            let system_prompt = merged_system_prompt(&request.messages);
-            crate::llm::codex::send_codex_request(codex_model, request, system_prompt.as_deref())
-                .await
+    for attempt in 0..=retry.max_retries {
+        let request = &*health::route(config, request)?;
+        let result = if let Some(result) = send_responses_api_request(config, request).await {
+            result
         } else if let Some(anthropic_model) = request.model.strip_prefix("anthropic:") {
             send_anthropic_request(config, request, anthropic_model).await
-        } else if request.model.strip_prefix("openai:").is_some() {
-            send_openai_request(config, request).await
+        } else if let Some(target) = openai_target(config, request)? {
+            send_openai_request(config, target).await
         } else {
             return Err(CrabClawError::Config(format!(
-                "MODEL '{}' must have a provider prefix: openai:<model>, anthropic:<model>, or codex:<model>",
+                "MODEL '{}' must have a provider prefix: openai:, anthropic:, codex:, responses:, xai: or mistral:",
                 request.model
             )));
         };
+        health::record(config, &request.model, result.as_ref().err());
 
         match &result {
-            Err(CrabClawError::RateLimit(_)) if attempt < MAX_RETRIES => {
-                warn!(attempt = attempt + 1, delay_ms, "rate limited, retrying");
-                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                delay_ms *= 2;
-                continue;
-            }
-            Err(CrabClawError::Network(_)) if attempt < MAX_RETRIES => {
-                warn!(attempt = attempt + 1, delay_ms, "network error, retrying");
-                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                delay_ms *= 2;
+            Err(e) if e.is_retryable() && attempt < retry.max_retries => {
+                let delay = retry_delay(e, retry.delay(attempt));
+                warn!(attempt = attempt + 1, delay_secs = delay.as_secs(), error = %e, "retrying");
+                tokio::time::sleep(delay).await;
                 continue;
             }
             _ => return result,
@@ -130,25 +201,23 @@ pub async fn send_chat_request(config: &AppConfig, request: &ChatRequest) -> Res
 
 /// Send a chat completion request as a stream.
 ///
-/// Retries on 429 (rate limit) and network errors with exponential backoff.
+/// Retries rate limits, overloaded providers and network errors with
+/// exponential backoff per `LLM_MAX_RETRIES` and `LLM_RETRY_DELAY_MS` (see
+/// [`CrabClawError::is_retryable`]).
+/// Providers that keep failing are skipped (see [`health`]).
 #[instrument(skip_all, fields(model = %request.model))]
 pub async fn send_chat_request_stream(
     config: &AppConfig,
     request: &ChatRequest,
 ) -> Result<mpsc::UnboundedReceiver<Result<StreamChunk>>> {
-    let mut delay_ms = INITIAL_RETRY_DELAY_MS;
-
-    for attempt in 0..=MAX_RETRIES {
-        // Codex models use the Responses API; wrap in a non-streaming adapter
-        if let Some(codex_model) = request.model.strip_prefix("codex:") {
-            let system_prompt = merged_system_prompt(&request.messages);
-            let result = crate::llm::codex::send_codex_request(
-                codex_model,
-                request,
-                system_prompt.as_deref(),
-            )
-            .await
-            .map(|resp| {
+    let retry = config.llm_retry;
+
+    for attempt in 0..=retry.max_retries {
+        let request = &*health::route(config, request)?;
+        // Responses API models (codex, responses); wrap in a non-streaming adapter
+        if let Some(result) = send_responses_api_request(config, request).await {
+            health::record(config, &request.model, result.as_ref().err());
+            let result = result.map(|resp| {
                 let (tx, rx) = mpsc::unbounded_channel();
                 for chunk in codex_response_to_stream_chunks(&resp) {
                     let _ = tx.send(Ok(chunk));
@@ -157,16 +226,10 @@ pub async fn send_chat_request_stream(
             });
 
             match &result {
-                Err(CrabClawError::RateLimit(_)) if attempt < MAX_RETRIES => {
-                    warn!(attempt = attempt + 1, delay_ms, "rate limited, retrying");
-                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                    delay_ms *= 2;
-                    continue;
-                }
-                Err(CrabClawError::Network(_)) if attempt < MAX_RETRIES => {
-                    warn!(attempt = attempt + 1, delay_ms, "network error, retrying");
-                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                    delay_ms *= 2;
+                Err(e) if e.is_retryable() && attempt < retry.max_retries => {
+                    let delay = retry_delay(e, retry.delay(attempt));
+                    warn!(attempt = attempt + 1, delay_secs = delay.as_secs(), error = %e, "retrying");
+                    tokio::time::sleep(delay).await;
                     continue;
                 }
                 _ => return result,
@@ -175,26 +238,21 @@ pub async fn send_chat_request_stream(
 
         let result = if let Some(anthropic_model) = request.model.strip_prefix("anthropic:") {
             send_anthropic_request_stream(config, request, anthropic_model).await
-        } else if request.model.strip_prefix("openai:").is_some() {
-            send_openai_request_stream(config, request).await
+        } else if let Some(target) = openai_target(config, request)? {
+            send_openai_request_stream(config, target).await
         } else {
             return Err(CrabClawError::Config(format!(
-                "MODEL '{}' must have a provider prefix: openai:<model>, anthropic:<model>, or codex:<model>",
+                "MODEL '{}' must have a provider prefix: openai:, anthropic:, codex:, responses:, xai: or mistral:",
                 request.model
             )));
         };
+        health::record(config, &request.model, result.as_ref().err());
 
         match &result {
-            Err(CrabClawError::RateLimit(_)) if attempt < MAX_RETRIES => {
-                warn!(attempt = attempt + 1, delay_ms, "rate limited, retrying");
-                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                delay_ms *= 2;
-                continue;
-            }
-            Err(CrabClawError::Network(_)) if attempt < MAX_RETRIES => {
-                warn!(attempt = attempt + 1, delay_ms, "network error, retrying");
-                tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                delay_ms *= 2;
+            Err(e) if e.is_retryable() && attempt < retry.max_retries => {
+                let delay = retry_delay(e, retry.delay(attempt));
+                warn!(attempt = attempt + 1, delay_secs = delay.as_secs(), error = %e, "retrying");
+                tokio::time::sleep(delay).await;
                 continue;
             }
             _ => return result,
@@ -204,13 +262,25 @@ pub async fn send_chat_request_stream(
     unreachable!()
 }
 
-async fn send_anthropic_request(
+/// Translate a chat request into the Anthropic messages format, applying
+/// `ANTHROPIC_PARAMS`.
+///
+/// Extended thinking is only requested when the conversation ends with a
+/// user message and no tool call is forced: Anthropic rejects forced tool
+/// use with thinking, and tool-result follow-ups would have to echo the
+/// earlier thinking blocks, which are not kept. While thinking is on,
+/// `temperature` and `top_k` are dropped and `max_tokens` is raised above
+/// the thinking budget.
+fn build_anthropic_request(
     config: &AppConfig,
     request: &ChatRequest,
     model: &str,
-) -> Result<ChatResponse> {
-    let url = format!("{}/v1/messages", config.api_base.trim_end_matches('/'));
-    debug!(url = %url, model = %model, "sending anthropic chat request");
+) -> Result<AnthropicRequest> {
+    let params = match &config.anthropic_params {
+        Some(json) => AnthropicParams::parse(json)
+            .map_err(|e| CrabClawError::Config(format!("invalid ANTHROPIC_PARAMS: {e}")))?,
+        None => AnthropicParams::default(),
+    };
 
     let mut system_text = String::new();
     for msg in &request.messages {
@@ -229,18 +299,54 @@ async fn send_anthropic_request(
             .map(crate::llm::api_types::AnthropicToolDefinition::from)
             .collect::<Vec<_>>()
     });
+    let tool_choice = tools
+        .as_ref()
+        .and(request.tool_choice.as_ref().or(params.tool_choice.as_ref()));
+
+    let ends_with_user = request.messages.last().is_some_and(|m| m.role == "user");
+    let thinking = params
+        .thinking_budget
+        .filter(|_| ends_with_user && !tool_choice.is_some_and(ToolChoice::forces_tool));
+
+    let mut max_tokens = request.max_tokens.unwrap_or(4096);
+    if let Some(budget) = thinking {
+        max_tokens = max_tokens.max(budget + 1024);
+    }
 
-    let anth_req = AnthropicRequest {
+    Ok(AnthropicRequest {
         model: model.to_string(),
         messages,
-        max_tokens: request.max_tokens.unwrap_or(4096),
+        max_tokens,
+        temperature: request
+            .temperature
+            .or(params.temperature)
+            .filter(|_| thinking.is_none()),
         system: if system_text.is_empty() {
             None
         } else {
             Some(system_text)
         },
         tools,
-    };
+        tool_choice: tool_choice.map(ToolChoice::to_anthropic),
+        top_k: params.top_k.filter(|_| thinking.is_none()),
+        top_p: params.top_p,
+        metadata: params.user_id.map(|user_id| AnthropicMetadata { user_id }),
+        thinking: thinking.map(|budget_tokens| AnthropicThinking {
+            thinking_type: "enabled".to_string(),
+            budget_tokens,
+        }),
+    })
+}
+
+async fn send_anthropic_request(
+    config: &AppConfig,
+    request: &ChatRequest,
+    model: &str,
+) -> Result<ChatResponse> {
+    let url = format!("{}/v1/messages", config.api_base.trim_end_matches('/'));
+    debug!(url = %url, model = %model, "sending anthropic chat request");
+
+    let anth_req = build_anthropic_request(config, request, model)?;
 
     info!(
         model = %anth_req.model,
@@ -250,27 +356,18 @@ async fn send_anthropic_request(
         "anthropic.request"
     );
 
-    let client = get_http_client();
+    let client = http_client(config, "anthropic")?;
 
-    let response = client
+    let request_builder = client
         .post(&url)
         .header("x-api-key", &config.api_key)
         .header("anthropic-version", "2023-06-01")
         .header("Content-Type", "application/json")
-        .json(&anth_req)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                CrabClawError::Network(format!("request timed out after {DEFAULT_TIMEOUT_SECS}s"))
-            } else if e.is_connect() {
-                CrabClawError::Network(format!("connection failed: {e}"))
-            } else {
-                CrabClawError::Network(format!("request failed: {e}"))
-            }
-        })?;
+        .json(&anth_req);
+    let response = send_request(config, request_builder).await?;
 
     let status = response.status();
+    let retry_after = retry_after_header(&response);
     debug!(status = %status, "received anthropic response");
 
     let body = response
@@ -295,7 +392,7 @@ async fn send_anthropic_request(
         return Ok(chat_resp);
     }
 
-    handle_error_response(status, &body)
+    Err(error_response("anthropic", status, retry_after, &body))
 }
 
 async fn send_anthropic_request_stream(
@@ -306,206 +403,129 @@ async fn send_anthropic_request_stream(
     let url = format!("{}/v1/messages", config.api_base.trim_end_matches('/'));
     debug!(url = %url, model = %model, "sending anthropic chat streaming request");
 
-    let mut system_text = String::new();
-    for msg in &request.messages {
-        if msg.role == "system" {
-            if !system_text.is_empty() {
-                system_text.push('\n');
-            }
-            system_text.push_str(&msg.content);
-        }
-    }
-
-    let messages = crate::llm::api_types::convert_messages_for_anthropic(&request.messages);
-
-    let tools = request.tools.as_ref().map(|ts| {
-        ts.iter()
-            .map(crate::llm::api_types::AnthropicToolDefinition::from)
-            .collect::<Vec<_>>()
-    });
-
-    let anth_req = AnthropicRequest {
-        model: model.to_string(),
-        messages,
-        max_tokens: request.max_tokens.unwrap_or(4096),
-        system: if system_text.is_empty() {
-            None
-        } else {
-            Some(system_text)
-        },
-        tools,
-    };
+    let anth_req = build_anthropic_request(config, request, model)?;
 
-    let client = get_http_client();
+    let client = http_client(config, "anthropic")?;
 
     let mut json_val = serde_json::to_value(&anth_req).map_err(CrabClawError::from)?;
     if let Some(obj) = json_val.as_object_mut() {
         obj.insert("stream".to_string(), serde_json::Value::Bool(true));
     }
 
-    let response = client
+    let request_builder = client
         .post(&url)
         .header("x-api-key", &config.api_key)
         .header("anthropic-version", "2023-06-01")
         .header("Content-Type", "application/json")
-        .json(&json_val)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                CrabClawError::Network(format!("request timed out after {DEFAULT_TIMEOUT_SECS}s"))
-            } else if e.is_connect() {
-                CrabClawError::Network(format!("connection failed: {e}"))
-            } else {
-                CrabClawError::Network(format!("request failed: {e}"))
-            }
-        })?;
+        .json(&json_val);
+    let response = send_streaming_request(config, request_builder).await?;
 
     let status = response.status();
     debug!(status = %status, "received anthropic stream response headers");
 
     if !status.is_success() {
+        let retry_after = retry_after_header(&response);
         let body = response
             .text()
             .await
             .unwrap_or_else(|_| "Failed to read error body".to_string());
-        return Err(handle_error_response(status, &body).unwrap_err());
+        return Err(error_response("anthropic", status, retry_after, &body));
     }
 
     let (tx, rx) = mpsc::unbounded_channel();
 
+    let idle = Duration::from_secs(config.llm_timeouts.stream_idle_secs);
     tokio::spawn(async move {
         let mut stream = response.bytes_stream();
-        let mut buffer = String::new();
+        let mut parser = SseParser::new();
 
-        use crate::llm::api_types::{
-            AnthropicStreamBlock, AnthropicStreamDelta, AnthropicStreamEvent,
-        };
-
-        while let Some(chunk_res) = stream.next().await {
-            match chunk_res {
-                Ok(bytes) => {
-                    let text = String::from_utf8_lossy(&bytes);
-                    buffer.push_str(&text);
-
-                    while let Some(pos) = buffer.find("\n\n") {
-                        let event_str = buffer[..pos].to_string();
-                        buffer.drain(..pos + 2);
-
-                        // Anthropic sends:
-                        // event: message_start
-                        // data: {"type": ...}
-                        // We can just look for "data: " and parse it.
-                        for line in event_str.lines() {
-                            let line = line.trim();
-                            if let Some(data) = line.strip_prefix("data: ") {
-                                let data = data.trim();
-                                if data == "[DONE]" {
-                                    continue; // Anthropic usually doesn't send this, but just in case
-                                }
-
-                                match serde_json::from_str::<AnthropicStreamEvent>(data) {
-                                    Ok(event) => match event {
-                                        AnthropicStreamEvent::ContentBlockStart {
-                                            index,
-                                            content_block,
-                                        } => match content_block {
-                                            AnthropicStreamBlock::Text { text } => {
-                                                if !text.is_empty() {
-                                                    let _ = tx.send(Ok(StreamChunk::Content(text)));
-                                                }
-                                            }
-                                            AnthropicStreamBlock::ToolUse { id, name } => {
-                                                let _ = tx.send(Ok(StreamChunk::ToolCallStart {
-                                                    index,
-                                                    id,
-                                                    name,
-                                                }));
-                                            }
-                                        },
-                                        AnthropicStreamEvent::ContentBlockDelta {
-                                            index,
-                                            delta,
-                                        } => match delta {
-                                            AnthropicStreamDelta::TextDelta { text } => {
-                                                if !text.is_empty() {
-                                                    let _ = tx.send(Ok(StreamChunk::Content(text)));
-                                                }
-                                            }
-                                            AnthropicStreamDelta::InputJsonDelta {
-                                                partial_json,
-                                            } => {
-                                                let _ =
-                                                    tx.send(Ok(StreamChunk::ToolCallArgument {
-                                                        index,
-                                                        text: partial_json,
-                                                    }));
-                                            }
-                                        },
-                                        AnthropicStreamEvent::MessageStop => {
-                                            let _ = tx.send(Ok(StreamChunk::Done));
-                                            return;
-                                        }
-                                        AnthropicStreamEvent::Error { error } => {
-                                            let _ = tx.send(Err(CrabClawError::Api(format!(
-                                                "anthropic stream error: {}",
-                                                error.message
-                                            ))));
-                                            return;
-                                        }
-                                        _ => {} // Ignore MessageStart, Ping, etc.
-                                    },
-                                    Err(e) => {
-                                        debug!(error = %e, data = %data, "failed to parse anthropic SSE chunk");
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        while let Some(chunk_res) = next_stream_chunk(&mut stream, idle).await {
+            let bytes = match chunk_res {
+                Ok(bytes) => bytes,
                 Err(e) => {
-                    let _ = tx.send(Err(CrabClawError::Network(format!("stream error: {e}"))));
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            };
+            for event in parser.push(&bytes) {
+                if event
+                    .json_payloads()
+                    .into_iter()
+                    .any(|data| forward_anthropic_event(data, &tx))
+                {
                     return;
                 }
             }
         }
+        if let Some(event) = parser.finish()
+            && event
+                .json_payloads()
+                .into_iter()
+                .any(|data| forward_anthropic_event(data, &tx))
+        {
+            return;
+        }
         let _ = tx.send(Ok(StreamChunk::Done));
     });
 
     Ok(rx)
 }
 
-async fn send_openai_request(config: &AppConfig, request: &ChatRequest) -> Result<ChatResponse> {
-    let url = format!("{}/chat/completions", config.api_base.trim_end_matches('/'));
-    let model = request
-        .model
-        .strip_prefix("openai:")
-        .unwrap_or(&request.model);
-    debug!(url = %url, model = %model, "sending openai chat request");
+/// A Chat Completions request ready to send: `openai:` models go to
+/// `BASE_URL` with `API_KEY`, the OpenAI-compatible prefixes in
+/// `llm::providers` to their own endpoint with their own key.
+struct OpenAiTarget {
+    provider: &'static str,
+    url: String,
+    api_key: String,
+    request: ChatRequest,
+    quirks: ProviderQuirks,
+}
 
+/// Resolve the Chat Completions target for `request`, or `None` when the
+/// model is not on an OpenAI-compatible provider.
+fn openai_target(config: &AppConfig, request: &ChatRequest) -> Result<Option<OpenAiTarget>> {
     let mut api_request = request.clone();
-    api_request.model = model.to_string();
+    let (provider, api_base, api_key) = if let Some(model) = request.model.strip_prefix("openai:") {
+        api_request.model = model.to_string();
+        ("openai", config.api_base.clone(), config.api_key.clone())
+    } else if let Some((provider, model)) = crate::llm::providers::compat_provider(&request.model) {
+        let (api_base, api_key) = config.provider_endpoint(provider.name)?;
+        api_request.model = model.to_string();
+        provider.prepare_messages(&mut api_request.messages);
+        (provider.name, api_base, api_key)
+    } else {
+        return Ok(None);
+    };
+    Ok(Some(OpenAiTarget {
+        provider,
+        url: format!("{}/chat/completions", api_base.trim_end_matches('/')),
+        api_key,
+        request: api_request,
+        quirks: quirks::for_api_base(config, &api_base),
+    }))
+}
 
-    let client = get_http_client();
+async fn send_openai_request(config: &AppConfig, target: OpenAiTarget) -> Result<ChatResponse> {
+    let OpenAiTarget {
+        provider,
+        url,
+        api_key,
+        request: api_request,
+        quirks,
+    } = target;
+    debug!(url = %url, model = %api_request.model, provider, "sending openai chat request");
 
-    let response = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", config.api_key))
+    let client = http_client(config, provider)?;
+
+    let request_builder = trace::tag_request(client.post(&url))
+        .header("Authorization", format!("Bearer {api_key}"))
         .header("Content-Type", "application/json")
-        .json(&api_request)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                CrabClawError::Network(format!("request timed out after {DEFAULT_TIMEOUT_SECS}s"))
-            } else if e.is_connect() {
-                CrabClawError::Network(format!("connection failed: {e}"))
-            } else {
-                CrabClawError::Network(format!("request failed: {e}"))
-            }
-        })?;
+        .json(&api_request);
+    let response = send_request(config, request_builder).await?;
 
     let status = response.status();
+    let retry_after = retry_after_header(&response);
     debug!(status = %status, "received openai response");
 
     let body = response
@@ -515,210 +535,259 @@ async fn send_openai_request(config: &AppConfig, request: &ChatRequest) -> Resul
     debug!(body = %body, "raw response body");
 
     if status.is_success() {
-        #[allow(clippy::collapsible_if)]
-        if let Ok(ns_err) = serde_json::from_str::<NonStandardError>(&body) {
-            if ns_err.success == Some(false) || ns_err.code.is_some_and(|c| c >= 400) {
-                let msg = ns_err
-                    .msg
-                    .unwrap_or_else(|| "unknown API error".to_string());
-                let code = ns_err.code.unwrap_or(0);
-                warn!(code = code, msg = %msg, "non-standard API error in 200 response");
-                return Err(CrabClawError::Api(format!(
-                    "API error (code {code}): {msg}"
-                )));
-            }
-        }
-
-        let chat_response: ChatResponse = serde_json::from_str(&body)?;
+        let mut value: serde_json::Value = serde_json::from_str(&body)?;
+        quirks.check_ok_body(&value)?;
+        quirks.normalize_response(&mut value);
+        let chat_response: ChatResponse = serde_json::from_value(value)?;
         return Ok(chat_response);
     }
 
-    handle_error_response(status, &body)
+    Err(error_response(provider, status, retry_after, &body))
 }
 
 async fn send_openai_request_stream(
     config: &AppConfig,
-    request: &ChatRequest,
+    target: OpenAiTarget,
 ) -> Result<mpsc::UnboundedReceiver<Result<StreamChunk>>> {
-    let url = format!("{}/chat/completions", config.api_base.trim_end_matches('/'));
-    let model = request
-        .model
-        .strip_prefix("openai:")
-        .unwrap_or(&request.model);
-    debug!(url = %url, model = %model, "sending openai chat streaming request");
+    let OpenAiTarget {
+        provider,
+        url,
+        api_key,
+        request: api_request,
+        quirks,
+    } = target;
+    debug!(url = %url, model = %api_request.model, provider, "sending openai chat streaming request");
 
-    let client = get_http_client();
+    let client = http_client(config, provider)?;
 
-    let mut api_request = request.clone();
-    api_request.model = model.to_string();
     let mut json_val = serde_json::to_value(&api_request).map_err(CrabClawError::from)?;
     if let Some(obj) = json_val.as_object_mut() {
         obj.insert("stream".to_string(), serde_json::Value::Bool(true));
     }
 
-    let response = client
-        .post(&url)
-        .header("Authorization", format!("Bearer {}", config.api_key))
+    let request_builder = trace::tag_request(client.post(&url))
+        .header("Authorization", format!("Bearer {api_key}"))
         .header("Content-Type", "application/json")
-        .json(&json_val)
-        .send()
-        .await
-        .map_err(|e| {
-            if e.is_timeout() {
-                CrabClawError::Network(format!("request timed out after {DEFAULT_TIMEOUT_SECS}s"))
-            } else if e.is_connect() {
-                CrabClawError::Network(format!("connection failed: {e}"))
-            } else {
-                CrabClawError::Network(format!("request failed: {e}"))
-            }
-        })?;
+        .json(&json_val);
+    let response = send_streaming_request(config, request_builder).await?;
 
     let status = response.status();
     debug!(status = %status, "received openai stream response headers");
 
     if !status.is_success() {
+        let retry_after = retry_after_header(&response);
         let body = response
             .text()
             .await
             .unwrap_or_else(|_| "Failed to read error body".to_string());
-        return Err(handle_error_response(status, &body).unwrap_err());
+        return Err(error_response(provider, status, retry_after, &body));
     }
 
     let (tx, rx) = mpsc::unbounded_channel();
 
+    let idle = Duration::from_secs(config.llm_timeouts.stream_idle_secs);
     tokio::spawn(async move {
         let mut stream = response.bytes_stream();
-        let mut buffer = String::new();
-
-        while let Some(chunk_res) = stream.next().await {
-            match chunk_res {
-                Ok(bytes) => {
-                    let text = String::from_utf8_lossy(&bytes);
-                    buffer.push_str(&text);
-
-                    // Process double-newline separated SSE events
-                    while let Some(pos) = buffer.find("\n\n") {
-                        let event = buffer[..pos].to_string();
-                        buffer.drain(..pos + 2);
-
-                        for line in event.lines() {
-                            let line = line.trim();
-                            if line.is_empty() {
-                                continue;
-                            }
-                            if let Some(data) = line.strip_prefix("data: ") {
-                                let data = data.trim();
-                                if data == "[DONE]" {
-                                    let _ = tx.send(Ok(StreamChunk::Done));
-                                    return;
-                                }
-
-                                match serde_json::from_str::<crate::llm::api_types::ChatStreamChunk>(
-                                    data,
-                                ) {
-                                    Ok(parsed) => {
-                                        if let Some(choice) = parsed.choices.first() {
-                                            if let Some(content) = choice
-                                                .delta
-                                                .content
-                                                .as_ref()
-                                                .filter(|c| !c.is_empty())
-                                            {
-                                                let _ = tx.send(Ok(StreamChunk::Content(
-                                                    content.clone(),
-                                                )));
-                                            }
-                                            if let Some(tool_calls) = &choice.delta.tool_calls {
-                                                for tc in tool_calls {
-                                                    if let Some(id) = &tc.id {
-                                                        // It's the start of a tool call
-                                                        if let Some(name) = tc
-                                                            .function
-                                                            .as_ref()
-                                                            .and_then(|f| f.name.as_ref())
-                                                        {
-                                                            let _ = tx.send(Ok(
-                                                                StreamChunk::ToolCallStart {
-                                                                    index: tc.index,
-                                                                    id: id.clone(),
-                                                                    name: name.clone(),
-                                                                },
-                                                            ));
-                                                        }
-                                                    }
-                                                    #[allow(clippy::collapsible_if)]
-                                                    if let Some(args) = tc
-                                                        .function
-                                                        .as_ref()
-                                                        .and_then(|f| f.arguments.as_ref())
-                                                    {
-                                                        if !args.is_empty() {
-                                                            let _ = tx.send(Ok(
-                                                                StreamChunk::ToolCallArgument {
-                                                                    index: tc.index,
-                                                                    text: args.clone(),
-                                                                },
-                                                            ));
-                                                        }
-                                                    }
-                                                }
-                                            }
-                                        }
-                                    }
-                                    Err(e) => {
-                                        // Some providers send weird pings or format differently, optionally warn
-                                        debug!(error = %e, data = %data, "failed to parse SSE chunk");
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
+        let mut parser = SseParser::new();
+        let mut normalizer = quirks.stream();
+
+        while let Some(chunk_res) = next_stream_chunk(&mut stream, idle).await {
+            let bytes = match chunk_res {
+                Ok(bytes) => bytes,
                 Err(e) => {
-                    let _ = tx.send(Err(CrabClawError::Network(format!("stream error: {e}"))));
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            };
+            for event in parser.push(&bytes) {
+                if event
+                    .json_payloads()
+                    .into_iter()
+                    .any(|data| forward_openai_event(data, &mut normalizer, &tx))
+                {
                     return;
                 }
             }
         }
+        if let Some(event) = parser.finish()
+            && event
+                .json_payloads()
+                .into_iter()
+                .any(|data| forward_openai_event(data, &mut normalizer, &tx))
+        {
+            return;
+        }
         let _ = tx.send(Ok(StreamChunk::Done));
     });
 
     Ok(rx)
 }
 
-fn handle_error_response(status: reqwest::StatusCode, body_text: &str) -> Result<ChatResponse> {
-    let detail = serde_json::from_str::<ApiErrorBody>(body_text)
-        .ok()
-        .and_then(|b| b.error)
-        .map(|e| e.message)
-        .unwrap_or_else(|| body_text.to_string());
+type StreamSender = mpsc::UnboundedSender<Result<StreamChunk>>;
+
+/// Forward one Anthropic SSE `data` payload as stream chunks.
+///
+/// Returns `true` once the stream is finished (`message_stop` or an error).
+fn forward_anthropic_event(data: &str, tx: &StreamSender) -> bool {
+    use crate::llm::api_types::{AnthropicStreamBlock, AnthropicStreamDelta, AnthropicStreamEvent};
 
-    match status.as_u16() {
-        401 | 403 => {
-            warn!(status = %status, "authentication failure");
-            Err(CrabClawError::Auth(format!("HTTP {status}: {detail}")))
+    let data = data.trim();
+    if data == "[DONE]" {
+        return false; // Anthropic usually doesn't send this, but just in case
+    }
+    let event = match serde_json::from_str::<AnthropicStreamEvent>(data) {
+        Ok(event) => event,
+        Err(e) => {
+            debug!(error = %e, data = %data, "failed to parse anthropic SSE chunk");
+            return false;
         }
-        429 => {
-            warn!(status = %status, "rate limited");
-            Err(CrabClawError::RateLimit(format!("HTTP {status}: {detail}")))
+    };
+    match event {
+        AnthropicStreamEvent::ContentBlockStart {
+            index,
+            content_block,
+        } => match content_block {
+            AnthropicStreamBlock::Text { text } => {
+                if !text.is_empty() {
+                    let _ = tx.send(Ok(StreamChunk::Content(text)));
+                }
+            }
+            AnthropicStreamBlock::ToolUse { id, name } => {
+                let _ = tx.send(Ok(StreamChunk::ToolCallStart { index, id, name }));
+            }
+            AnthropicStreamBlock::Other => {}
+        },
+        AnthropicStreamEvent::ContentBlockDelta { index, delta } => match delta {
+            AnthropicStreamDelta::TextDelta { text } => {
+                if !text.is_empty() {
+                    let _ = tx.send(Ok(StreamChunk::Content(text)));
+                }
+            }
+            AnthropicStreamDelta::InputJsonDelta { partial_json } => {
+                let _ = tx.send(Ok(StreamChunk::ToolCallArgument {
+                    index,
+                    text: partial_json,
+                }));
+            }
+            AnthropicStreamDelta::Other => {}
+        },
+        AnthropicStreamEvent::MessageDelta { delta, .. } => {
+            if let Some(reason) = delta.stop_reason {
+                let _ = tx.send(Ok(StreamChunk::Finish(reason)));
+            }
         }
-        s if (500..600).contains(&s) => {
-            warn!(status = %status, "server error");
-            Err(CrabClawError::Api(format!(
-                "server error (HTTP {status}): {detail}"
-            )))
+        AnthropicStreamEvent::MessageStop => {
+            let _ = tx.send(Ok(StreamChunk::Done));
+            return true;
         }
-        _ => {
-            warn!(status = %status, "unexpected status");
-            Err(CrabClawError::Api(format!("HTTP {status}: {detail}")))
+        AnthropicStreamEvent::Error { error } => {
+            let _ = tx.send(Err(CrabClawError::Api(format!(
+                "anthropic stream error: {}",
+                error.message
+            ))));
+            return true;
         }
+        _ => {} // Ignore MessageStart, Ping, etc.
     }
+    false
+}
+
+/// Forward one OpenAI-compatible SSE `data` payload as stream chunks.
+///
+/// Chunks are normalized for the endpoint's quirks first. Returns `true`
+/// once the stream is finished (`[DONE]` or an error).
+fn forward_openai_event(data: &str, normalizer: &mut StreamNormalizer, tx: &StreamSender) -> bool {
+    let data = data.trim();
+    if data == "[DONE]" {
+        let _ = tx.send(Ok(StreamChunk::Done));
+        return true;
+    }
+    let parsed = serde_json::from_str::<serde_json::Value>(data).and_then(|mut value| {
+        if let Err(e) = normalizer.check_ok_body(&value) {
+            return Ok(Err(e));
+        }
+        normalizer.normalize(&mut value);
+        serde_json::from_value::<crate::llm::api_types::ChatStreamChunk>(value).map(Ok)
+    });
+    let parsed = match parsed {
+        Ok(Ok(parsed)) => parsed,
+        Ok(Err(e)) => {
+            let _ = tx.send(Err(e));
+            return true;
+        }
+        Err(e) => {
+            // Some providers send weird pings or format differently, optionally warn
+            debug!(error = %e, data = %data, "failed to parse SSE chunk");
+            return false;
+        }
+    };
+    let Some(choice) = parsed.choices.first() else {
+        return false;
+    };
+    if let Some(content) = choice.delta.content.as_ref().filter(|c| !c.is_empty()) {
+        let _ = tx.send(Ok(StreamChunk::Content(content.clone())));
+    }
+    for tc in choice.delta.tool_calls.iter().flatten() {
+        // An id marks the start of a tool call
+        if let Some(id) = &tc.id
+            && let Some(name) = tc.function.as_ref().and_then(|f| f.name.as_ref())
+        {
+            let _ = tx.send(Ok(StreamChunk::ToolCallStart {
+                index: tc.index,
+                id: id.clone(),
+                name: name.clone(),
+            }));
+        }
+        if let Some(args) = tc
+            .function
+            .as_ref()
+            .and_then(|f| f.arguments.as_ref())
+            .filter(|a| !a.is_empty())
+        {
+            let _ = tx.send(Ok(StreamChunk::ToolCallArgument {
+                index: tc.index,
+                text: args.clone(),
+            }));
+        }
+    }
+    if let Some(reason) = &choice.finish_reason {
+        let _ = tx.send(Ok(StreamChunk::Finish(reason.clone())));
+    }
+    false
+}
+
+fn retry_after_header(response: &reqwest::Response) -> Option<Duration> {
+    parse_retry_after(
+        response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|v| v.to_str().ok()),
+    )
+}
+
+/// Turn an HTTP error response from `provider` into a [`ProviderError`],
+/// using the message from a JSON error body when there is one.
+fn error_response(
+    provider: &str,
+    status: reqwest::StatusCode,
+    retry_after: Option<Duration>,
+    body_text: &str,
+) -> CrabClawError {
+    let detail = serde_json::from_str::<ApiErrorBody>(body_text)
+        .ok()
+        .and_then(|b| b.error)
+        .map(|e| e.message)
+        .unwrap_or_else(|| body_text.to_string());
+    let error = ProviderError::new(provider, status.as_u16(), detail).with_retry_after(retry_after);
+    warn!(provider, status = %status, kind = ?error.kind(), "provider error response");
+    CrabClawError::Provider(error)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::config::AppConfig;
+    use crate::core::error::ProviderErrorKind;
     use crate::llm::api_types::{
         ChatRequest, Choice, Message, StreamChunk, ToolCall, ToolCallFunction,
     };
@@ -731,11 +800,56 @@ mod tests {
             api_base: api_base.to_string(),
             model: "openai:test-model".to_string(),
             system_prompt: None,
+            system_prompt_file: None,
+            skills: Vec::new(),
+            reply_language: None,
+            timezone: None,
             telegram_token: None,
             telegram_allow_from: vec![],
             telegram_allow_chats: vec![],
             telegram_proxy: None,
+            telegram_persona: None,
+            telegram_output_filters: Vec::new(),
             max_context_messages: 50,
+            skill_top_k: None,
+            persona: None,
+            schedule_report: false,
+            schedule_max_retries: 2,
+            schedule_retry_backoff_seconds: 30,
+            schedule_max_concurrent_jobs: 2,
+            schedule_jitter_seconds: 0,
+            quiet_hours: None,
+            telegram_rate_limit_per_minute: None,
+            telegram_session_ttl_hours: None,
+            telegram_max_concurrent_turns: None,
+            telegram_workers: 8,
+            agent_cache_size: 0,
+            tape_sync: Default::default(),
+            telegram_admins: vec![],
+            telegram_user_workspace_root: None,
+            model_capabilities: None,
+            session_titles: crate::core::title::TitleMode::Off,
+            workspaces: Vec::new(),
+            command_aliases: Vec::new(),
+            command_prefix: ',',
+            telegram_command_prefix: ',',
+            max_continuations: 2,
+            http_pools: Default::default(),
+            llm_timeouts: Default::default(),
+            llm_retry: Default::default(),
+            llm_cassette: None,
+            dump_dir: None,
+            assistant_commands: Default::default(),
+            circuit_breaker: crate::core::config::CircuitBreakerConfig::disabled(),
+            fallback_model: None,
+            consensus_models: Vec::new(),
+            consensus_judge: None,
+            anthropic_params: None,
+            provider_endpoints: Default::default(),
+            provider_quirks: Vec::new(),
+            calendar_urls: Vec::new(),
+            notify: Default::default(),
+            tool_output_limits: Default::default(),
         }
     }
 
@@ -774,13 +888,18 @@ mod tests {
             model: "openai:test".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
         let err = send_chat_request(&config, &request).await.unwrap_err();
         match err {
-            CrabClawError::Auth(msg) => assert!(msg.contains("401"), "msg: {msg}"),
-            other => panic!("expected Auth error, got: {other}"),
+            CrabClawError::Provider(e) => {
+                assert_eq!(e.status, 401);
+                assert_eq!(e.kind(), ProviderErrorKind::Auth);
+            }
+            other => panic!("expected Provider error, got: {other}"),
         }
         mock.assert_async().await;
     }
@@ -801,13 +920,18 @@ mod tests {
             model: "openai:test".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
         let err = send_chat_request(&config, &request).await.unwrap_err();
         match err {
-            CrabClawError::Api(msg) => assert!(msg.contains("500"), "msg: {msg}"),
-            other => panic!("expected Api error, got: {other}"),
+            CrabClawError::Provider(e) => {
+                assert_eq!(e.status, 500);
+                assert_eq!(e.kind(), ProviderErrorKind::Server);
+            }
+            other => panic!("expected Provider error, got: {other}"),
         }
         mock.assert_async().await;
     }
@@ -820,6 +944,7 @@ mod tests {
         let mock = server
             .mock("POST", "/chat/completions")
             .with_status(429)
+            .with_header("retry-after", "1")
             .with_body(r#"{"error": {"message": "Rate limit exceeded"}}"#)
             .expect(4)
             .create_async()
@@ -830,17 +955,79 @@ mod tests {
             model: "openai:test".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
         let err = send_chat_request(&config, &request).await.unwrap_err();
         match err {
-            CrabClawError::RateLimit(msg) => assert!(msg.contains("429"), "msg: {msg}"),
-            other => panic!("expected RateLimit error, got: {other}"),
+            CrabClawError::Provider(e) => {
+                assert_eq!(e.status, 429);
+                assert_eq!(e.kind(), ProviderErrorKind::RateLimit);
+                assert_eq!(e.retry_after, Some(Duration::from_secs(1)));
+                assert_eq!(e.detail, "Rate limit exceeded");
+            }
+            other => panic!("expected Provider error, got: {other}"),
         }
         mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn open_circuit_fails_fast_then_uses_the_fallback_model() {
+        let mut server = mockito::Server::new_async().await;
+        let failing = server
+            .mock("POST", "/chat/completions")
+            .with_status(503)
+            .with_body(r#"{"error": {"message": "down"}}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let mut fallback_server = mockito::Server::new_async().await;
+        let fallback = fallback_server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJsonString(
+                r#"{"model": "small"}"#.to_string(),
+            ))
+            .with_status(200)
+            .with_body(
+                r#"{"choices": [{"index": 0, "message": {"role": "assistant", "content": "from mistral"}}]}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut config = test_config(&server.url());
+        config.circuit_breaker.failures = 1;
+        let request = ChatRequest {
+            model: "openai:test".to_string(),
+            messages: vec![Message::user("hello")],
+            max_tokens: None,
+            tool_choice: None,
+            temperature: None,
+            tools: None,
+        };
+
+        // The first failure opens the circuit, so no retries are sent.
+        let err = send_chat_request(&config, &request).await.unwrap_err();
+        assert!(matches!(err, CrabClawError::Unavailable(_)), "{err}");
+        assert!(err.to_string().contains("OpenAI failed 1 times"), "{err}");
+        failing.assert_async().await;
+
+        config.fallback_model = Some("mistral:small".to_string());
+        config.provider_endpoints.insert(
+            "mistral".to_string(),
+            crate::core::config::ProviderEndpoint {
+                api_base: fallback_server.url(),
+                api_key: Some("m-key".to_string()),
+            },
+        );
+        let resp = send_chat_request(&config, &request).await.unwrap();
+        assert_eq!(resp.assistant_content(), Some("from mistral"));
+        fallback.assert_async().await;
+        failing.assert_async().await;
+    }
+
     // Successful response
     #[tokio::test]
     async fn successful_response_returns_chat_response() {
@@ -872,6 +1059,8 @@ mod tests {
             model: "openai:test".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: Some(100),
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
@@ -898,13 +1087,18 @@ mod tests {
             model: "openai:test".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
         let err = send_chat_request(&config, &request).await.unwrap_err();
         match err {
-            CrabClawError::Auth(msg) => assert!(msg.contains("403"), "msg: {msg}"),
-            other => panic!("expected Auth error, got: {other}"),
+            CrabClawError::Provider(e) => {
+                assert_eq!(e.status, 403);
+                assert_eq!(e.kind(), ProviderErrorKind::Auth);
+            }
+            other => panic!("expected Provider error, got: {other}"),
         }
         mock.assert_async().await;
     }
@@ -924,13 +1118,18 @@ mod tests {
             model: "openai:test".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
         let err = send_chat_request(&config, &request).await.unwrap_err();
         match err {
-            CrabClawError::Api(msg) => assert!(msg.contains("418"), "msg: {msg}"),
-            other => panic!("expected Api error, got: {other}"),
+            CrabClawError::Provider(e) => {
+                assert_eq!(e.status, 418);
+                assert_eq!(e.kind(), ProviderErrorKind::BadRequest);
+            }
+            other => panic!("expected Provider error, got: {other}"),
         }
         mock.assert_async().await;
     }
@@ -951,6 +1150,8 @@ mod tests {
             model: "openai:test".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
@@ -977,13 +1178,18 @@ mod tests {
             model: "openai:test".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
         let err = send_chat_request(&config, &request).await.unwrap_err();
         match err {
-            CrabClawError::Api(msg) => assert!(msg.contains("500"), "msg: {msg}"),
-            other => panic!("expected Api error, got: {other}"),
+            CrabClawError::Provider(e) => {
+                assert_eq!(e.status, 500);
+                assert_eq!(e.kind(), ProviderErrorKind::Server);
+            }
+            other => panic!("expected Provider error, got: {other}"),
         }
         mock.assert_async().await;
     }
@@ -1010,6 +1216,8 @@ mod tests {
             model: "openai:test-model".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
@@ -1029,6 +1237,116 @@ mod tests {
         mock.assert_async().await;
     }
 
+    /// Raw HTTP server that answers one request with `response` and then
+    /// holds the connection open without sending anything else.
+    async fn stalling_server(response: &'static str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 8192];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket.write_all(response.as_bytes()).await;
+            tokio::time::sleep(Duration::from_secs(30)).await;
+        });
+        format!("http://{addr}")
+    }
+
+    fn stream_request() -> ChatRequest {
+        ChatRequest {
+            model: "openai:test-model".to_string(),
+            messages: vec![Message::user("hello")],
+            max_tokens: None,
+            tool_choice: None,
+            temperature: None,
+            tools: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn stream_fails_when_headers_do_not_arrive() {
+        let base = stalling_server("").await;
+        let mut config = test_config(&base);
+        config.llm_timeouts.first_byte_secs = 1;
+
+        let target = openai_target(&config, &stream_request()).unwrap().unwrap();
+        let err = send_openai_request_stream(&config, target)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("no response within 1s"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn stream_fails_when_idle_between_chunks() {
+        let base = stalling_server(concat!(
+            "HTTP/1.1 200 OK\r\n",
+            "content-type: text/event-stream\r\n",
+            "transfer-encoding: chunked\r\n\r\n",
+            "45\r\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"},\"finish_reason\":null}]}\n\n",
+            "\r\n",
+        ))
+        .await;
+        let mut config = test_config(&base);
+        config.llm_timeouts.stream_idle_secs = 1;
+
+        let mut rx = send_chat_request_stream(&config, &stream_request())
+            .await
+            .expect("headers arrive");
+        assert_eq!(
+            rx.recv().await.unwrap().unwrap(),
+            StreamChunk::Content("Hi".to_string())
+        );
+        let err = rx.recv().await.unwrap().unwrap_err();
+        assert!(err.to_string().contains("stream idle for 1s"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn stream_handles_crlf_separators_and_comments() {
+        let mut server = mockito::Server::new_async().await;
+        let body = concat!(
+            ": proxy keep-alive\r\n\r\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"},\"finish_reason\":null}]}\r\n\r\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\r\n\r\n",
+            "data: [DONE]\r\n\r\n"
+        );
+        server
+            .mock("POST", "/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let config = test_config(&server.url());
+        let request = ChatRequest {
+            model: "openai:test-model".to_string(),
+            messages: vec![Message::user("hello")],
+            max_tokens: None,
+            tool_choice: None,
+            temperature: None,
+            tools: None,
+        };
+
+        let rx = send_chat_request_stream(&config, &request)
+            .await
+            .expect("stream request should succeed");
+        let chunks = tokio::time::timeout(Duration::from_secs(5), collect_stream_chunks(rx))
+            .await
+            .expect("CRLF stream should not hang");
+
+        assert_eq!(
+            chunks,
+            vec![
+                StreamChunk::Content("Hi".to_string()),
+                StreamChunk::Finish("stop".to_string()),
+                StreamChunk::Done
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn openai_stream_tool_calls_and_arguments() {
         let mut server = mockito::Server::new_async().await;
@@ -1052,6 +1370,8 @@ mod tests {
             model: "openai:test-model".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
@@ -1082,6 +1402,74 @@ mod tests {
         mock.assert_async().await;
     }
 
+    #[tokio::test]
+    async fn openai_stream_applies_endpoint_quirks() {
+        let mut server = mockito::Server::new_async().await;
+        // Qwen-style: both calls at index 0, continuations with empty ids.
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_a\",\"function\":{\"name\":\"file.read\",\"arguments\":\"\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"\",\"function\":{\"name\":\"\",\"arguments\":\"{}\"}}]}}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"tool_calls\":[{\"index\":0,\"id\":\"call_b\",\"function\":{\"name\":\"file.list\",\"arguments\":\"{}\"}}]}}]}\n\n",
+            "data: {\"code\":500,\"msg\":\"internal error\",\"success\":false}\n\n",
+        );
+
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .with_status(200)
+            .with_header("content-type", "text/event-stream")
+            .with_body(body)
+            .create_async()
+            .await;
+
+        let mut config = test_config(&server.url());
+        config.provider_quirks = vec![("127.0.0.1".to_string(), crate::llm::quirks::QWEN)];
+        let request = ChatRequest {
+            model: "openai:qwen-plus".to_string(),
+            messages: vec![Message::user("hello")],
+            max_tokens: None,
+            tool_choice: None,
+            temperature: None,
+            tools: None,
+        };
+
+        let mut rx = send_chat_request_stream(&config, &request)
+            .await
+            .expect("stream request should succeed");
+        let mut chunks = Vec::new();
+        let error = loop {
+            match rx.recv().await.expect("stream should end with the error") {
+                Ok(chunk) => chunks.push(chunk),
+                Err(e) => break e,
+            }
+        };
+
+        assert_eq!(
+            chunks,
+            vec![
+                StreamChunk::ToolCallStart {
+                    index: 0,
+                    id: "call_a".to_string(),
+                    name: "file.read".to_string()
+                },
+                StreamChunk::ToolCallArgument {
+                    index: 0,
+                    text: "{}".to_string()
+                },
+                StreamChunk::ToolCallStart {
+                    index: 1,
+                    id: "call_b".to_string(),
+                    name: "file.list".to_string()
+                },
+                StreamChunk::ToolCallArgument {
+                    index: 1,
+                    text: "{}".to_string()
+                },
+            ]
+        );
+        assert!(error.to_string().contains("internal error"), "{error}");
+        mock.assert_async().await;
+    }
+
     #[tokio::test]
     async fn openai_stream_ignores_malformed_events_and_recovers() {
         let mut server = mockito::Server::new_async().await;
@@ -1104,6 +1492,8 @@ mod tests {
             model: "openai:test-model".to_string(),
             messages: vec![Message::user("hello")],
             max_tokens: None,
+            tool_choice: None,
+            temperature: None,
             tools: None,
         };
 
@@ -1179,4 +1569,136 @@ mod tests {
             ]
         );
     }
+
+    fn anthropic_chat(tool_choice: Option<ToolChoice>) -> ChatRequest {
+        ChatRequest {
+            model: "claude-test".to_string(),
+            messages: vec![Message::system("be brief"), Message::user("plan it")],
+            max_tokens: None,
+            temperature: Some(0.2),
+            tools: Some(vec![crate::llm::api_types::ToolDefinition {
+                tool_type: "function".to_string(),
+                function: crate::llm::api_types::FunctionDefinition {
+                    name: "file.read".to_string(),
+                    description: "Read a file".to_string(),
+                    parameters: serde_json::json!({"type": "object"}),
+                },
+            }]),
+            tool_choice,
+        }
+    }
+
+    #[test]
+    fn anthropic_request_applies_configured_params() {
+        let mut config = test_config("http://unused");
+        config.anthropic_params =
+            Some(r#"{"top_k": 40, "top_p": 0.9, "user_id": "ops", "tool_choice": "auto"}"#.into());
+
+        let body = serde_json::to_value(
+            build_anthropic_request(&config, &anthropic_chat(None), "claude-test").unwrap(),
+        )
+        .unwrap();
+        assert_eq!(body["tool_choice"], serde_json::json!({"type": "auto"}));
+        assert_eq!(body["top_k"], 40);
+        assert_eq!(body["metadata"], serde_json::json!({"user_id": "ops"}));
+        assert_eq!(body["system"], "be brief");
+        assert!(body.get("thinking").is_none());
+
+        // A per-request choice wins over the configured one.
+        let body = serde_json::to_value(
+            build_anthropic_request(
+                &config,
+                &anthropic_chat(Some(ToolChoice::Any)),
+                "claude-test",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(body["tool_choice"], serde_json::json!({"type": "any"}));
+    }
+
+    #[test]
+    fn anthropic_thinking_adjusts_sampling_and_is_skipped_when_tools_are_forced() {
+        let mut config = test_config("http://unused");
+        config.anthropic_params = Some(r#"{"top_k": 40, "thinking_budget": 8000}"#.into());
+
+        let req = build_anthropic_request(&config, &anthropic_chat(None), "claude-test").unwrap();
+        let body = serde_json::to_value(&req).unwrap();
+        assert_eq!(
+            body["thinking"],
+            serde_json::json!({"type": "enabled", "budget_tokens": 8000})
+        );
+        assert_eq!(req.max_tokens, 9024);
+        assert!(req.temperature.is_none());
+        assert!(req.top_k.is_none());
+
+        let forced = build_anthropic_request(
+            &config,
+            &anthropic_chat(Some(ToolChoice::Any)),
+            "claude-test",
+        )
+        .unwrap();
+        assert!(forced.thinking.is_none());
+        assert_eq!(forced.top_k, Some(40));
+
+        let mut after_tool = anthropic_chat(None);
+        after_tool
+            .messages
+            .push(Message::tool("call_1", "contents"));
+        let req = build_anthropic_request(&config, &after_tool, "claude-test").unwrap();
+        assert!(req.thinking.is_none());
+    }
+
+    #[test]
+    fn mistral_target_uses_provider_endpoint_and_short_tool_ids() {
+        let mut config = test_config("http://openai.invalid");
+        config.provider_endpoints.insert(
+            "mistral".to_string(),
+            crate::core::config::ProviderEndpoint {
+                api_base: "https://api.mistral.ai/v1/".to_string(),
+                api_key: Some("m-key".to_string()),
+            },
+        );
+        let mut request = anthropic_chat(None);
+        request.model = "mistral:mistral-large-latest".to_string();
+        request
+            .messages
+            .push(Message::assistant_with_tool_calls(vec![ToolCall {
+                id: "toolu_01ABCDEF".to_string(),
+                call_type: "function".to_string(),
+                function: ToolCallFunction {
+                    name: "file.read".to_string(),
+                    arguments: "{}".to_string(),
+                },
+            }]));
+        request
+            .messages
+            .push(Message::tool("toolu_01ABCDEF", "contents"));
+
+        let target = openai_target(&config, &request).unwrap().unwrap();
+        assert_eq!(target.provider, "mistral");
+        assert_eq!(target.url, "https://api.mistral.ai/v1/chat/completions");
+        assert_eq!(target.api_key, "m-key");
+        assert_eq!(target.request.model, "mistral-large-latest");
+        let call_id = &target.request.messages[2].tool_calls.as_ref().unwrap()[0].id;
+        assert_eq!(call_id.len(), 9);
+        assert_eq!(
+            target.request.messages[3].tool_call_id.as_ref(),
+            Some(call_id)
+        );
+
+        // Without a provider key, API_KEY is only borrowed for the MODEL provider.
+        config
+            .provider_endpoints
+            .get_mut("mistral")
+            .unwrap()
+            .api_key = None;
+        assert!(openai_target(&config, &request).is_err());
+        config.model = "mistral:mistral-small".to_string();
+        let target = openai_target(&config, &request).unwrap().unwrap();
+        assert_eq!(target.api_key, "test-key");
+
+        request.model = "anthropic:claude".to_string();
+        assert!(openai_target(&config, &request).unwrap().is_none());
+    }
 }
@@ -1,16 +1,20 @@
 #![allow(clippy::collapsible_if)]
-//! OpenAI Codex Responses API provider.
+//! OpenAI Responses API providers.
 //!
 //! Codex models use `https://chatgpt.com/backend-api/codex/responses`
 //! with OAuth tokens from ChatGPT subscription (not API keys).
-//! This is a completely different format from Chat Completions.
+//! `responses:` models use the public Responses API at `{BASE_URL}/responses`
+//! with the regular API key. Both share the request format and the SSE /
+//! function-call parsing below, which is a completely different format from
+//! Chat Completions.
 
 use serde::{Deserialize, Serialize};
 use tracing::{debug, info};
 
 use crate::core::auth;
-use crate::core::error::{CrabClawError, Result};
-use crate::llm::api_types::{ChatRequest, Message, ToolCall, ToolCallFunction};
+use crate::core::config::AppConfig;
+use crate::core::error::{CrabClawError, ProviderError, Result, parse_retry_after};
+use crate::llm::api_types::{ChatRequest, Message, ToolCall, ToolCallFunction, ToolChoice};
 
 const CODEX_RESPONSES_URL: &str = "https://chatgpt.com/backend-api/codex/responses";
 const DEFAULT_INSTRUCTIONS: &str = "You are CrabClaw, a concise and helpful coding assistant.";
@@ -25,13 +29,18 @@ fn decode_tool_name(name: &str) -> String {
     name.replace("__", ".")
 }
 
-fn classify_codex_http_error(status: reqwest::StatusCode, body: &str) -> CrabClawError {
-    let message = format!("Codex API error (HTTP {status}): {body}");
-    match status.as_u16() {
-        429 => CrabClawError::RateLimit(message),
-        401 | 403 => CrabClawError::Auth(message),
-        _ => CrabClawError::Api(message),
-    }
+/// Map an HTTP error from `api` ("Codex", "OpenAI Responses") to a
+/// [`ProviderError`].
+fn classify_http_error(
+    api: &str,
+    status: reqwest::StatusCode,
+    retry_after: Option<std::time::Duration>,
+    body: &str,
+) -> CrabClawError {
+    let provider = if api == "Codex" { "codex" } else { "responses" };
+    CrabClawError::Provider(
+        ProviderError::new(provider, status.as_u16(), body).with_retry_after(retry_after),
+    )
 }
 
 // ---------------------------------------------------------------------------
@@ -42,13 +51,17 @@ fn classify_codex_http_error(status: reqwest::StatusCode, body: &str) -> CrabCla
 struct ResponsesRequest {
     model: String,
     input: Vec<serde_json::Value>,
-    instructions: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instructions: Option<String>,
     store: bool,
     stream: bool,
-    text: TextOptions,
-    reasoning: ReasoningOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<TextOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reasoning: Option<ReasoningOptions>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     include: Vec<String>,
-    tool_choice: String,
+    tool_choice: serde_json::Value,
     parallel_tool_calls: bool,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     tools: Vec<CodexToolDef>,
@@ -101,6 +114,7 @@ struct ParsedCodexResponse {
 
 /// Send a Codex Responses API request using OAuth tokens.
 pub async fn send_codex_request(
+    config: &AppConfig,
     model: &str,
     request: &ChatRequest,
     system_prompt: Option<&str>,
@@ -130,84 +144,125 @@ pub async fn send_codex_request(
             )
         })?;
 
-    // Build Responses API input
     let instructions = system_prompt
         .filter(|s| !s.trim().is_empty())
         .map(|s| s.to_string())
         .unwrap_or_else(|| DEFAULT_INSTRUCTIONS.to_string());
-
-    let input = build_responses_input(&request.messages);
-    let tools = convert_tools(&request.tools);
-    let effort = resolve_reasoning_effort(model);
-
-    let body = ResponsesRequest {
-        model: model.to_string(),
-        input,
-        instructions,
-        store: false,
-        stream: true,
-        text: TextOptions {
-            verbosity: "medium".to_string(),
-        },
-        reasoning: ReasoningOptions {
-            effort,
-            summary: "auto".to_string(),
-        },
-        include: vec!["reasoning.encrypted_content".to_string()],
-        tool_choice: if tools.is_empty() {
-            "none".to_string()
-        } else {
-            "auto".to_string()
-        },
-        parallel_tool_calls: true,
-        tools,
-    };
+    let mut body = build_request_body(model, request, Some(instructions));
+    body.text = Some(TextOptions {
+        verbosity: "medium".to_string(),
+    });
+    body.reasoning = Some(ReasoningOptions {
+        effort: resolve_reasoning_effort(model),
+        summary: "auto".to_string(),
+    });
+    body.include = vec!["reasoning.encrypted_content".to_string()];
 
     info!(
         "codex.request model={model} input_count={} tools_count={} instructions_len={}",
         body.input.len(),
         body.tools.len(),
-        body.instructions.len()
+        body.instructions.as_deref().map_or(0, str::len)
     );
 
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(120))
-        .build()
-        .map_err(|e| CrabClawError::Network(format!("failed to build HTTP client: {e}")))?;
+    let client = crate::llm::http::provider_client(config, "codex")?;
 
-    let response = client
+    let builder = client
         .post(CODEX_RESPONSES_URL)
         .header("Authorization", format!("Bearer {access_token}"))
         .header("chatgpt-account-id", &account_id)
         .header("OpenAI-Beta", "responses=experimental")
-        .header("originator", "pi")
+        .header("originator", "pi");
+    let parsed = send_and_parse(config, builder, &body, "Codex").await?;
+
+    info!(
+        "codex.response text_len={} tool_calls={}",
+        parsed.text.len(),
+        parsed.tool_calls.len()
+    );
+
+    Ok(into_chat_response(parsed))
+}
+
+/// Send a request to the public OpenAI Responses API (`{BASE_URL}/responses`)
+/// with the configured API key, for `responses:` models.
+///
+/// Reasoning options are left to the API defaults since non-reasoning models
+/// reject them.
+pub async fn send_responses_request(
+    config: &AppConfig,
+    model: &str,
+    request: &ChatRequest,
+    system_prompt: Option<&str>,
+) -> Result<crate::llm::api_types::ChatResponse> {
+    let url = format!("{}/responses", config.api_base.trim_end_matches('/'));
+    let instructions = system_prompt
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.to_string());
+    let body = build_request_body(model, request, instructions);
+
+    info!(
+        "responses.request model={model} input_count={} tools_count={}",
+        body.input.len(),
+        body.tools.len()
+    );
+
+    let client = crate::llm::http::provider_client(config, "responses")?;
+    let builder = client
+        .post(&url)
+        .header("Authorization", format!("Bearer {}", config.api_key));
+    let parsed = send_and_parse(config, builder, &body, "OpenAI Responses").await?;
+
+    info!(
+        "responses.response text_len={} tool_calls={}",
+        parsed.text.len(),
+        parsed.tool_calls.len()
+    );
+
+    Ok(into_chat_response(parsed))
+}
+
+/// POST `body` as a streamed Responses request and parse the event stream.
+async fn send_and_parse(
+    config: &AppConfig,
+    builder: reqwest::RequestBuilder,
+    body: &ResponsesRequest,
+    api: &str,
+) -> Result<ParsedCodexResponse> {
+    let builder = crate::core::trace::tag_request(builder)
         .header("accept", "text/event-stream")
         .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| CrabClawError::Network(format!("codex request failed: {e}")))?;
+        .json(body)
+        .timeout(std::time::Duration::from_secs(
+            config.llm_timeouts.request_secs,
+        ));
+    let response = crate::llm::http::send(config, builder, |e| {
+        CrabClawError::Network(format!("{api} request failed: {e}"))
+    })
+    .await?;
 
     if !response.status().is_success() {
         let status = response.status();
+        let retry_after = parse_retry_after(
+            response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|v| v.to_str().ok()),
+        );
         let body = response.text().await.unwrap_or_default();
-        return Err(classify_codex_http_error(status, &body));
+        return Err(classify_http_error(api, status, retry_after, &body));
     }
 
     // Parse SSE response
     let body_text = response
         .text()
         .await
-        .map_err(|e| CrabClawError::Network(format!("failed to read codex response: {e}")))?;
+        .map_err(|e| CrabClawError::Network(format!("failed to read {api} response: {e}")))?;
 
-    let parsed = parse_sse_response(&body_text)?;
-
-    info!(
-        "codex.response text_len={} tool_calls={}",
-        parsed.text.len(),
-        parsed.tool_calls.len()
-    );
+    parse_sse_response(&body_text)
+}
 
+fn into_chat_response(parsed: ParsedCodexResponse) -> crate::llm::api_types::ChatResponse {
     let tool_calls = if parsed.tool_calls.is_empty() {
         None
     } else {
@@ -219,7 +274,7 @@ pub async fn send_codex_request(
         "stop"
     };
 
-    Ok(crate::llm::api_types::ChatResponse {
+    crate::llm::api_types::ChatResponse {
         id: None,
         choices: vec![crate::llm::api_types::Choice {
             index: 0,
@@ -232,13 +287,42 @@ pub async fn send_codex_request(
             finish_reason: Some(finish_reason.to_string()),
         }],
         usage: None,
-    })
+    }
 }
 
 // ---------------------------------------------------------------------------
 // Helpers
 // ---------------------------------------------------------------------------
 
+fn build_request_body(
+    model: &str,
+    request: &ChatRequest,
+    instructions: Option<String>,
+) -> ResponsesRequest {
+    let tools = convert_tools(&request.tools);
+    let tool_choice = match &request.tool_choice {
+        _ if tools.is_empty() => serde_json::json!("none"),
+        Some(ToolChoice::Tool(name)) => {
+            serde_json::json!({"type": "function", "name": encode_tool_name(name)})
+        }
+        Some(choice) => serde_json::to_value(choice).unwrap_or_else(|_| "auto".into()),
+        None => serde_json::json!("auto"),
+    };
+    ResponsesRequest {
+        model: model.to_string(),
+        input: build_responses_input(&request.messages),
+        instructions,
+        store: false,
+        stream: true,
+        text: None,
+        reasoning: None,
+        include: Vec::new(),
+        tool_choice,
+        parallel_tool_calls: true,
+        tools,
+    }
+}
+
 fn build_responses_input(messages: &[Message]) -> Vec<serde_json::Value> {
     let mut input: Vec<serde_json::Value> = Vec::new();
     for msg in messages {
@@ -437,152 +521,141 @@ fn parse_sse_response(body: &str) -> Result<ParsedCodexResponse> {
     let mut fn_call_args: std::collections::HashMap<u64, (String, String, String)> =
         std::collections::HashMap::new(); // index -> (name, call_id, arguments_buf)
 
-    for chunk in body.split("\n\n") {
-        for line in chunk.lines() {
-            let data = match line.strip_prefix("data:") {
-                Some(d) => d.trim(),
-                None => continue,
-            };
-            if data.is_empty() || data == "[DONE]" {
-                continue;
-            }
-            let event: serde_json::Value = match serde_json::from_str(data) {
-                Ok(v) => v,
-                Err(_) => continue,
-            };
-
-            // Check for errors
-            let event_type = event.get("type").and_then(|v| v.as_str());
-            if event_type == Some("error") || event_type == Some("response.failed") {
-                let msg = event
-                    .get("message")
-                    .or_else(|| {
-                        event
-                            .get("response")
-                            .and_then(|r| r.get("error"))
-                            .and_then(|e| e.get("message"))
-                    })
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("unknown error");
-                return Err(CrabClawError::Api(format!("Codex stream error: {msg}")));
-            }
+    let sse_events = crate::llm::sse::parse_events(body);
+    for data in sse_events.iter().flat_map(|e| e.json_payloads()) {
+        let data = data.trim();
+        if data.is_empty() || data == "[DONE]" {
+            continue;
+        }
+        let event: serde_json::Value = match serde_json::from_str(data) {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+
+        // Check for errors
+        let event_type = event.get("type").and_then(|v| v.as_str());
+        if event_type == Some("error") || event_type == Some("response.failed") {
+            let msg = event
+                .get("message")
+                .or_else(|| {
+                    event
+                        .get("response")
+                        .and_then(|r| r.get("error"))
+                        .and_then(|e| e.get("message"))
+                })
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown error");
+            return Err(CrabClawError::Api(format!("Codex stream error: {msg}")));
+        }
 
-            let output_index = event
-                .get("output_index")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0);
-
-            match event_type {
-                // --- Text deltas ---
-                Some("response.output_text.delta") => {
-                    if let Some(delta) = event.get("delta").and_then(|v| v.as_str()) {
-                        saw_text_delta = true;
-                        text_delta_buf.push_str(delta);
-                    }
+        let output_index = event
+            .get("output_index")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+
+        match event_type {
+            // --- Text deltas ---
+            Some("response.output_text.delta") => {
+                if let Some(delta) = event.get("delta").and_then(|v| v.as_str()) {
+                    saw_text_delta = true;
+                    text_delta_buf.push_str(delta);
                 }
-                Some("response.output_text.done") if !saw_text_delta => {
-                    if let Some(text) = event.get("text").and_then(|v| v.as_str()) {
-                        if !text.is_empty() {
-                            fallback.text = text.to_string();
-                        }
+            }
+            Some("response.output_text.done") if !saw_text_delta => {
+                if let Some(text) = event.get("text").and_then(|v| v.as_str()) {
+                    if !text.is_empty() {
+                        fallback.text = text.to_string();
                     }
                 }
+            }
 
-                // --- Function call events ---
-                Some("response.output_item.added") => {
-                    // A new output item is being added; if it's a function_call, track it
-                    if let Some(item) = event.get("item") {
-                        if item.get("type").and_then(|v| v.as_str()) == Some("function_call") {
-                            let name = item.get("name").and_then(|v| v.as_str()).unwrap_or("");
-                            let call_id =
-                                item.get("call_id").and_then(|v| v.as_str()).unwrap_or("");
-                            fn_call_args.insert(
-                                output_index,
-                                (name.to_string(), call_id.to_string(), String::new()),
-                            );
-                            debug!(
-                                "codex: function_call item added idx={output_index} name={name}"
-                            );
-                        }
+            // --- Function call events ---
+            Some("response.output_item.added") => {
+                // A new output item is being added; if it's a function_call, track it
+                if let Some(item) = event.get("item") {
+                    if item.get("type").and_then(|v| v.as_str()) == Some("function_call") {
+                        let name = item.get("name").and_then(|v| v.as_str()).unwrap_or("");
+                        let call_id = item.get("call_id").and_then(|v| v.as_str()).unwrap_or("");
+                        fn_call_args.insert(
+                            output_index,
+                            (name.to_string(), call_id.to_string(), String::new()),
+                        );
+                        debug!("codex: function_call item added idx={output_index} name={name}");
                     }
                 }
-                Some("response.function_call_arguments.delta") => {
-                    if let Some(delta) = event.get("delta").and_then(|v| v.as_str()) {
-                        if let Some(entry) = fn_call_args.get_mut(&output_index) {
-                            entry.2.push_str(delta);
-                        }
+            }
+            Some("response.function_call_arguments.delta") => {
+                if let Some(delta) = event.get("delta").and_then(|v| v.as_str()) {
+                    if let Some(entry) = fn_call_args.get_mut(&output_index) {
+                        entry.2.push_str(delta);
                     }
                 }
-                Some("response.function_call_arguments.done") => {
-                    // Finalize this function call
-                    let name = event.get("name").and_then(|v| v.as_str()).unwrap_or("");
-                    let call_id = event.get("call_id").and_then(|v| v.as_str()).unwrap_or("");
-                    let arguments = event
-                        .get("arguments")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("{}");
-
-                    // Prefer the "done" event's fields, fall back to tracked deltas
-                    let final_name = if name.is_empty() {
-                        fn_call_args
-                            .get(&output_index)
-                            .map(|e| e.0.as_str())
-                            .unwrap_or("")
-                    } else {
-                        name
-                    };
-                    let final_call_id = if call_id.is_empty() {
-                        fn_call_args
-                            .get(&output_index)
-                            .map(|e| e.1.as_str())
-                            .unwrap_or("")
-                    } else {
-                        call_id
-                    };
-                    let final_args = if arguments != "{}" {
-                        arguments.to_string()
-                    } else {
-                        fn_call_args
-                            .get(&output_index)
-                            .map(|e| e.2.clone())
-                            .unwrap_or_else(|| "{}".to_string())
-                    };
-
-                    if !final_name.is_empty() {
-                        debug!(
-                            "codex: function_call done name={final_name} call_id={final_call_id}"
-                        );
-                        fallback.tool_calls.push(ToolCall {
-                            id: final_call_id.to_string(),
-                            call_type: "function".to_string(),
-                            function: ToolCallFunction {
-                                name: decode_tool_name(final_name),
-                                arguments: final_args,
-                            },
-                        });
-                    }
-                    fn_call_args.remove(&output_index);
+            }
+            Some("response.function_call_arguments.done") => {
+                // Finalize this function call
+                let name = event.get("name").and_then(|v| v.as_str()).unwrap_or("");
+                let call_id = event.get("call_id").and_then(|v| v.as_str()).unwrap_or("");
+                let arguments = event
+                    .get("arguments")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or("{}");
+
+                // Prefer the "done" event's fields, fall back to tracked deltas
+                let final_name = if name.is_empty() {
+                    fn_call_args
+                        .get(&output_index)
+                        .map(|e| e.0.as_str())
+                        .unwrap_or("")
+                } else {
+                    name
+                };
+                let final_call_id = if call_id.is_empty() {
+                    fn_call_args
+                        .get(&output_index)
+                        .map(|e| e.1.as_str())
+                        .unwrap_or("")
+                } else {
+                    call_id
+                };
+                let final_args = if arguments != "{}" {
+                    arguments.to_string()
+                } else {
+                    fn_call_args
+                        .get(&output_index)
+                        .map(|e| e.2.clone())
+                        .unwrap_or_else(|| "{}".to_string())
+                };
+
+                if !final_name.is_empty() {
+                    debug!("codex: function_call done name={final_name} call_id={final_call_id}");
+                    fallback.tool_calls.push(ToolCall {
+                        id: final_call_id.to_string(),
+                        call_type: "function".to_string(),
+                        function: ToolCallFunction {
+                            name: decode_tool_name(final_name),
+                            arguments: final_args,
+                        },
+                    });
                 }
+                fn_call_args.remove(&output_index);
+            }
 
-                // --- Completed / done ---
-                Some("response.completed" | "response.done") => {
-                    if let Some(resp) = event.get("response") {
-                        if let Ok(parsed) =
-                            serde_json::from_value::<ResponsesResponse>(resp.clone())
-                        {
-                            let extracted = extract_from_response(&parsed);
-                            if fallback.text.is_empty() {
-                                fallback.text = extracted.text;
-                            }
-                            // Merge tool calls from completed event if we didn't get them via SSE
-                            if fallback.tool_calls.is_empty() && !extracted.tool_calls.is_empty() {
-                                fallback.tool_calls = extracted.tool_calls;
-                            }
+            // --- Completed / done ---
+            Some("response.completed" | "response.done") => {
+                if let Some(resp) = event.get("response") {
+                    if let Ok(parsed) = serde_json::from_value::<ResponsesResponse>(resp.clone()) {
+                        let extracted = extract_from_response(&parsed);
+                        if fallback.text.is_empty() {
+                            fallback.text = extracted.text;
+                        }
+                        // Merge tool calls from completed event if we didn't get them via SSE
+                        if fallback.tool_calls.is_empty() && !extracted.tool_calls.is_empty() {
+                            fallback.tool_calls = extracted.tool_calls;
                         }
                     }
                 }
-                _ => {}
             }
+            _ => {}
         }
     }
 
@@ -621,6 +694,7 @@ fn parse_sse_response(body: &str) -> Result<ParsedCodexResponse> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::error::ProviderErrorKind;
 
     #[test]
     fn extract_account_id_from_valid_jwt() {
@@ -716,38 +790,51 @@ mod tests {
 
     #[test]
     fn classify_codex_http_error_maps_429_to_rate_limit() {
-        let err = classify_codex_http_error(reqwest::StatusCode::TOO_MANY_REQUESTS, "slow down");
-        match err {
-            CrabClawError::RateLimit(msg) => {
-                assert!(msg.contains("429"));
-                assert!(msg.contains("slow down"));
-            }
-            other => panic!("expected RateLimit error, got: {other}"),
-        }
+        let err = classify_http_error(
+            "Codex",
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            Some(std::time::Duration::from_secs(30)),
+            "slow down",
+        );
+        assert!(err.is_retryable());
+        assert_eq!(
+            err.user_message(),
+            "Codex rate limit (HTTP 429): slow down\nTry again in 30s, or switch models with ,model."
+        );
     }
 
     #[test]
     fn classify_codex_http_error_maps_401_to_auth() {
-        let err = classify_codex_http_error(reqwest::StatusCode::UNAUTHORIZED, "invalid token");
-        match err {
-            CrabClawError::Auth(msg) => {
-                assert!(msg.contains("401"));
-                assert!(msg.contains("invalid token"));
+        let err = classify_http_error(
+            "Codex",
+            reqwest::StatusCode::UNAUTHORIZED,
+            None,
+            "invalid token",
+        );
+        match &err {
+            CrabClawError::Provider(e) => {
+                assert_eq!(e.kind(), ProviderErrorKind::Auth);
+                assert_eq!(e.detail, "invalid token");
             }
-            other => panic!("expected Auth error, got: {other}"),
+            other => panic!("expected Provider error, got: {other}"),
         }
+        assert!(err.user_message().contains("crabclaw auth login"));
     }
 
     #[test]
-    fn classify_codex_http_error_maps_500_to_api() {
-        let err =
-            classify_codex_http_error(reqwest::StatusCode::INTERNAL_SERVER_ERROR, "internal error");
+    fn classify_responses_http_error_maps_500_to_server() {
+        let err = classify_http_error(
+            "OpenAI Responses",
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+            None,
+            "internal error",
+        );
         match err {
-            CrabClawError::Api(msg) => {
-                assert!(msg.contains("500"));
-                assert!(msg.contains("internal error"));
+            CrabClawError::Provider(e) => {
+                assert_eq!(e.provider, "responses");
+                assert_eq!(e.kind(), ProviderErrorKind::Server);
             }
-            other => panic!("expected Api error, got: {other}"),
+            other => panic!("expected Provider error, got: {other}"),
         }
     }
 
@@ -819,4 +906,38 @@ mod tests {
     fn convert_tools_empty() {
         assert!(convert_tools(&None).is_empty());
     }
+
+    #[test]
+    fn request_body_maps_tool_choice_and_omits_codex_options() {
+        let request = ChatRequest {
+            model: "responses:o3".to_string(),
+            messages: vec![Message::user("hi")],
+            max_tokens: None,
+            tool_choice: Some(ToolChoice::Tool("file.read".to_string())),
+            temperature: None,
+            tools: Some(vec![crate::llm::api_types::ToolDefinition {
+                tool_type: "function".to_string(),
+                function: crate::llm::api_types::FunctionDefinition {
+                    name: "file.read".to_string(),
+                    description: "Read".to_string(),
+                    parameters: serde_json::json!({}),
+                },
+            }]),
+        };
+        let body = serde_json::to_value(build_request_body("o3", &request, None)).unwrap();
+        assert_eq!(
+            body["tool_choice"],
+            serde_json::json!({"type": "function", "name": "file__read"})
+        );
+        for key in ["instructions", "reasoning", "text", "include"] {
+            assert!(body.get(key).is_none(), "{key} should be omitted");
+        }
+
+        let request = ChatRequest {
+            tools: None,
+            ..request
+        };
+        let body = serde_json::to_value(build_request_body("o3", &request, None)).unwrap();
+        assert_eq!(body["tool_choice"], "none");
+    }
 }
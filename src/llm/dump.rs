@@ -0,0 +1,219 @@
+//! Provider request and response dumps (`CRABCLAW_DUMP_DIR`).
+//!
+//! While dumps are on, each model request is written to
+//! `<dir>/<YYYYmmddTHHMMSS>-<seq>[-<turn>].request.txt`: method, URL,
+//! headers and the body exactly as serialized. Its response goes to the
+//! matching `.response.txt` once the body has been read: status, headers
+//! and the raw body, event streams included. Credential headers and every
+//! configured API key or token are replaced with `[redacted]`.
+//!
+//! Dumps are on when the request's config has a `dump_dir`: from
+//! `CRABCLAW_DUMP_DIR`, or set for one session by `,debug dump on|off`
+//! (see `AgentLoop`).
+
+use std::cmp::Reverse;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::{debug, warn};
+
+use crate::core::config::AppConfig;
+
+const REDACTED: &str = "[redacted]";
+
+/// Configured secrets shorter than this are placeholders, not credentials,
+/// and are left alone so they do not mangle the dump.
+const MIN_SECRET_LEN: usize = 8;
+
+static SEQUENCE: AtomicU64 = AtomicU64::new(1);
+
+/// One dumped exchange.
+pub(crate) struct Dump {
+    /// Path without the `.request.txt` / `.response.txt` suffix.
+    stem: PathBuf,
+    secrets: Vec<String>,
+}
+
+impl Dump {
+    /// Write the request to `dir`; `None` when it cannot be written.
+    pub(crate) fn request(
+        dir: &Path,
+        config: &AppConfig,
+        request: &reqwest::Request,
+    ) -> Option<Self> {
+        let seq = SEQUENCE.fetch_add(1, Ordering::Relaxed);
+        let mut name = format!("{}-{seq:04}", chrono::Utc::now().format("%Y%m%dT%H%M%S"));
+        if let Some(turn) = crate::core::trace::current_turn_id() {
+            name.push('-');
+            name.push_str(&turn);
+        }
+        let dump = Self {
+            stem: dir.join(name),
+            secrets: secrets(config),
+        };
+
+        let mut text = format!("{} {}\n", request.method(), request.url());
+        push_headers(&mut text, request.headers());
+        if let Some(body) = request.body().and_then(|b| b.as_bytes()) {
+            text.push('\n');
+            text.push_str(&String::from_utf8_lossy(body));
+            text.push('\n');
+        }
+        let written = std::fs::create_dir_all(dir).and_then(|()| dump.write("request", &text));
+        if let Err(e) = written {
+            warn!(dir = %dir.display(), error = %e, "dump.write_failed");
+            return None;
+        }
+        Some(dump)
+    }
+
+    /// Note that the request got no response.
+    pub(crate) fn failed(&self, error: &reqwest::Error) {
+        let _ = self.write("response", &format!("request failed: {error}\n"));
+    }
+
+    /// Pass `response` through, writing it once its body has been read or
+    /// dropped.
+    pub(crate) fn response(self, response: reqwest::Response) -> reqwest::Response {
+        let mut head = format!("HTTP {}\n", response.status());
+        push_headers(&mut head, response.headers());
+        crate::llm::http::tee(response, move |chunks, complete| {
+            let mut text = head;
+            text.push('\n');
+            text.push_str(&String::from_utf8_lossy(&chunks.concat()));
+            if !complete {
+                text.push_str("\n[body cut short by a transport error]\n");
+            }
+            if let Err(e) = self.write("response", &text) {
+                warn!(dump = %self.stem.display(), error = %e, "dump.write_failed");
+            }
+        })
+    }
+
+    fn write(&self, kind: &str, text: &str) -> io::Result<()> {
+        let mut path = self.stem.clone().into_os_string();
+        path.push(format!(".{kind}.txt"));
+        let path = PathBuf::from(path);
+        std::fs::write(&path, redact(text, &self.secrets))?;
+        debug!(dump = %path.display(), "dump.write");
+        Ok(())
+    }
+}
+
+fn push_headers(text: &mut String, headers: &reqwest::header::HeaderMap) {
+    for (name, value) in headers {
+        let value = if is_sensitive_header(name.as_str()) {
+            REDACTED.into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+        text.push_str(&format!("{name}: {value}\n"));
+    }
+}
+
+fn is_sensitive_header(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    ["auth", "key", "token", "secret", "cookie"]
+        .iter()
+        .any(|word| name.contains(word))
+}
+
+/// Credentials from the config, longest first so a key containing another
+/// is replaced whole.
+fn secrets(config: &AppConfig) -> Vec<String> {
+    let mut secrets: Vec<String> = std::iter::once(config.api_key.clone())
+        .chain(config.telegram_token.clone())
+        .chain(config.notify.telegram_token.clone())
+        .chain(
+            config
+                .provider_endpoints
+                .values()
+                .filter_map(|endpoint| endpoint.api_key.clone()),
+        )
+        .map(|secret| secret.trim().to_string())
+        .filter(|secret| secret.len() >= MIN_SECRET_LEN)
+        .collect();
+    secrets.sort_by_key(|secret| Reverse(secret.len()));
+    secrets.dedup();
+    secrets
+}
+
+fn redact(text: &str, secrets: &[String]) -> String {
+    secrets.iter().fold(text.to_string(), |text, secret| {
+        text.replace(secret, REDACTED)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(api_base: &str, dump_dir: &Path) -> AppConfig {
+        let env: HashMap<String, String> = [
+            ("API_KEY", "sk-secret-123456"),
+            ("BASE_URL", api_base),
+            ("MODEL", "openai:test-model"),
+            ("CRABCLAW_DUMP_DIR", &dump_dir.display().to_string()),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        crate::core::config::resolve_config(None, &Default::default(), &env, &HashMap::new())
+            .unwrap()
+    }
+
+    #[test]
+    fn credentials_are_redacted() {
+        assert!(is_sensitive_header("Authorization"));
+        assert!(is_sensitive_header("x-api-key"));
+        assert!(!is_sensitive_header("content-type"));
+        let secrets = vec!["sk-secret-123456".to_string()];
+        assert_eq!(
+            redact("{\"key\":\"sk-secret-123456\"}", &secrets),
+            "{\"key\":\"[redacted]\"}"
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_and_responses_are_dumped() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut server = mockito::Server::new_async().await;
+        let body = "{\"id\":\"1\",\"echo\":\"sk-secret-123456\"}";
+        server
+            .mock("POST", "/v1/echo")
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .create_async()
+            .await;
+        let config = config(&server.url(), dir.path());
+        let builder = reqwest::Client::new()
+            .post(format!("{}/v1/echo", server.url()))
+            .bearer_auth(&config.api_key)
+            .body("{\"b\":null,\"a\":1}");
+
+        let response = crate::llm::http::send(&config, builder, |e| {
+            crate::core::error::CrabClawError::Network(e.to_string())
+        })
+        .await
+        .unwrap();
+        assert_eq!(response.text().await.unwrap(), body);
+
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        files.sort();
+        assert_eq!(files.len(), 2);
+        let request = std::fs::read_to_string(&files[0]).unwrap();
+        let response = std::fs::read_to_string(&files[1]).unwrap();
+        assert!(files[0].to_string_lossy().ends_with(".request.txt"));
+        assert!(request.starts_with("POST http://"), "{request}");
+        assert!(request.contains("authorization: [redacted]"), "{request}");
+        assert!(request.contains("{\"b\":null,\"a\":1}"), "{request}");
+        assert!(response.starts_with("HTTP 200 OK\n"), "{response}");
+        assert!(response.contains("\"echo\":\"[redacted]\""), "{response}");
+        assert!(!request.contains("sk-secret") && !response.contains("sk-secret"));
+    }
+}
@@ -0,0 +1,223 @@
+//! Provider health: recent failures per provider endpoint and a circuit
+//! breaker on top of them.
+//!
+//! Every attempt in `llm::client` reports its outcome here. Once a provider
+//! has failed `CIRCUIT_BREAKER_FAILURES` times within the window, its circuit
+//! opens: requests go to `FALLBACK_MODEL` when that provider is healthy, and
+//! fail fast otherwise, instead of each message sitting through the whole
+//! retry budget during an outage. After the cooldown requests are let
+//! through again; the first failure re-opens the circuit, the first success
+//! closes it.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+
+use crate::core::config::{AppConfig, CircuitBreakerConfig};
+use crate::core::error::{CrabClawError, ProviderErrorKind, Result, provider_display_name};
+use crate::llm::api_types::ChatRequest;
+
+/// Recent failures of one provider endpoint.
+#[derive(Debug, Default)]
+struct ProviderHealth {
+    failures: VecDeque<Instant>,
+    open_until: Option<Instant>,
+    /// The cooldown is over and requests are let through again, but one
+    /// more failure re-opens the circuit.
+    half_open: bool,
+}
+
+impl ProviderHealth {
+    /// How much longer the circuit stays open at `now`, if it is open.
+    fn open_for(&mut self, now: Instant) -> Option<Duration> {
+        let until = self.open_until?;
+        if now < until {
+            return Some(until - now);
+        }
+        self.open_until = None;
+        self.half_open = true;
+        None
+    }
+
+    /// Count a failure; returns whether it opened the circuit.
+    fn record_failure(&mut self, now: Instant, breaker: &CircuitBreakerConfig) -> bool {
+        if breaker.failures == 0 {
+            return false;
+        }
+        let window = Duration::from_secs(breaker.window_secs);
+        self.failures.push_back(now);
+        while self
+            .failures
+            .front()
+            .is_some_and(|&at| now.duration_since(at) > window)
+        {
+            self.failures.pop_front();
+        }
+        if self.open_until.is_some() || !(self.half_open || self.failures.len() >= breaker.failures)
+        {
+            return false;
+        }
+        self.open_until = Some(now + Duration::from_secs(breaker.cooldown_secs));
+        self.half_open = false;
+        true
+    }
+
+    fn record_success(&mut self) {
+        self.failures.clear();
+        self.open_until = None;
+        self.half_open = false;
+    }
+}
+
+static HEALTH: OnceLock<Mutex<HashMap<(String, String), ProviderHealth>>> = OnceLock::new();
+
+fn with_health<T>(key: (String, String), f: impl FnOnce(&mut ProviderHealth) -> T) -> T {
+    let mut health = HEALTH
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    f(health.entry(key).or_default())
+}
+
+/// Health is tracked per provider and API base, so two endpoints of the same
+/// provider do not share a circuit.
+fn endpoint_key(config: &AppConfig, model: &str) -> (String, String) {
+    let provider = model.split_once(':').map_or(model, |(p, _)| p);
+    let api_base = config
+        .provider_endpoints
+        .get(provider)
+        .map_or(&config.api_base, |endpoint| &endpoint.api_base);
+    (
+        provider.to_string(),
+        api_base.trim_end_matches('/').to_string(),
+    )
+}
+
+/// Failures that suggest the provider is down rather than that the request
+/// was wrong.
+fn is_outage(err: &CrabClawError) -> bool {
+    match err {
+        CrabClawError::Provider(e) => e.is_retryable() || e.kind() == ProviderErrorKind::Server,
+        other => other.is_retryable(),
+    }
+}
+
+/// The request to send now: `request` itself while its provider is healthy,
+/// a copy for `FALLBACK_MODEL` while its circuit is open, or an
+/// [`CrabClawError::Unavailable`] error when there is nothing to fall back to.
+pub fn route<'a>(config: &AppConfig, request: &'a ChatRequest) -> Result<Cow<'a, ChatRequest>> {
+    if config.circuit_breaker.failures == 0 {
+        return Ok(Cow::Borrowed(request));
+    }
+    let now = Instant::now();
+    let key = endpoint_key(config, &request.model);
+    let Some(remaining) = with_health(key.clone(), |h| h.open_for(now)) else {
+        return Ok(Cow::Borrowed(request));
+    };
+    if let Some(fallback) = config.fallback_model.as_deref()
+        && endpoint_key(config, fallback) != key
+        && with_health(endpoint_key(config, fallback), |h| h.open_for(now)).is_none()
+    {
+        info!(from = %request.model, to = fallback, "llm.circuit_open.fallback");
+        let mut request = request.clone();
+        request.model = fallback.to_string();
+        return Ok(Cow::Owned(request));
+    }
+    Err(CrabClawError::Unavailable(format!(
+        "{} failed {} times in the last {}s; not sending requests to it for another {}s",
+        provider_display_name(&key.0),
+        config.circuit_breaker.failures,
+        config.circuit_breaker.window_secs,
+        remaining.as_secs().max(1)
+    )))
+}
+
+/// Record the outcome of one attempt for `model`'s provider. Errors that do
+/// not point at an outage (bad requests, auth) are ignored.
+pub fn record(config: &AppConfig, model: &str, error: Option<&CrabClawError>) {
+    let key = endpoint_key(config, model);
+    match error {
+        None => with_health(key, ProviderHealth::record_success),
+        Some(e) if is_outage(e) => {
+            let breaker = &config.circuit_breaker;
+            if with_health(key.clone(), |h| h.record_failure(Instant::now(), breaker)) {
+                warn!(
+                    provider = %key.0,
+                    api_base = %key.1,
+                    cooldown_secs = breaker.cooldown_secs,
+                    error = %e,
+                    "llm.circuit_opened"
+                );
+            }
+        }
+        Some(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::error::ProviderError;
+
+    fn breaker() -> CircuitBreakerConfig {
+        CircuitBreakerConfig {
+            failures: 3,
+            window_secs: 60,
+            cooldown_secs: 30,
+        }
+    }
+
+    #[test]
+    fn failures_within_the_window_open_the_circuit() {
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+        let mut health = ProviderHealth::default();
+        assert!(!health.record_failure(at(0), &breaker()));
+        assert!(!health.record_failure(at(10), &breaker()));
+        // The first failure has left the window by now.
+        assert!(!health.record_failure(at(65), &breaker()));
+        assert_eq!(health.open_for(at(65)), None);
+        assert!(health.record_failure(at(70), &breaker()));
+        assert_eq!(health.open_for(at(80)), Some(Duration::from_secs(20)));
+
+        // After the cooldown one failure is enough to re-open it...
+        assert_eq!(health.open_for(at(100)), None);
+        assert!(health.record_failure(at(101), &breaker()));
+        assert!(health.open_for(at(102)).is_some());
+
+        // ...and a success closes it.
+        assert_eq!(health.open_for(at(140)), None);
+        health.record_success();
+        assert!(!health.record_failure(at(141), &breaker()));
+        assert_eq!(health.open_for(at(141)), None);
+    }
+
+    #[test]
+    fn zero_failures_disables_the_breaker() {
+        let off = CircuitBreakerConfig {
+            failures: 0,
+            ..breaker()
+        };
+        let now = Instant::now();
+        let mut health = ProviderHealth::default();
+        for _ in 0..10 {
+            assert!(!health.record_failure(now, &off));
+        }
+        assert_eq!(health.open_for(now), None);
+    }
+
+    #[test]
+    fn only_outages_count_as_failures() {
+        let provider = |status| CrabClawError::Provider(ProviderError::new("openai", status, "x"));
+        assert!(is_outage(&provider(429)));
+        assert!(is_outage(&provider(503)));
+        assert!(is_outage(&provider(500)));
+        assert!(is_outage(&CrabClawError::Network("reset".into())));
+        assert!(!is_outage(&provider(400)));
+        assert!(!is_outage(&provider(401)));
+        assert!(!is_outage(&CrabClawError::Config("bad".into())));
+    }
+}
@@ -0,0 +1,176 @@
+//! Pooled HTTP clients for the model providers.
+//!
+//! Building a `reqwest::Client` sets up a fresh connection pool (and TLS
+//! state), so one client is built per provider and pool setting and shared
+//! by every request to that provider. Clients only carry the connect
+//! timeout; request and streaming timeouts are applied per request (see
+//! `LlmTimeouts`).
+//!
+//! Every provider request is sent with [`send`], the one place requests can
+//! be replayed from a cassette (`llm::cassette`) or dumped (`llm::dump`).
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use futures_util::StreamExt;
+
+use crate::core::config::{AppConfig, HttpPoolConfig};
+use crate::core::error::{CrabClawError, Result};
+use crate::llm::{cassette, dump};
+
+type ClientKey = (String, HttpPoolConfig, Duration);
+
+static CLIENTS: OnceLock<Mutex<HashMap<ClientKey, reqwest::Client>>> = OnceLock::new();
+
+/// Shared client for `provider`.
+pub fn provider_client(config: &AppConfig, provider: &str) -> Result<reqwest::Client> {
+    let pool = config.http_pool(provider);
+    let connect_timeout = Duration::from_secs(config.llm_timeouts.connect_secs);
+    let key = (provider.to_string(), pool, connect_timeout);
+    let mut clients = CLIENTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    if let Some(client) = clients.get(&key) {
+        return Ok(client.clone());
+    }
+    let client = build_client(&pool, connect_timeout)?;
+    clients.insert(key, client.clone());
+    Ok(client)
+}
+
+fn build_client(pool: &HttpPoolConfig, connect_timeout: Duration) -> Result<reqwest::Client> {
+    let idle_timeout =
+        (pool.idle_timeout_secs > 0).then(|| Duration::from_secs(pool.idle_timeout_secs));
+    let mut builder = reqwest::Client::builder()
+        .connect_timeout(connect_timeout)
+        .pool_idle_timeout(idle_timeout)
+        .pool_max_idle_per_host(pool.max_idle_per_host);
+    if let Some(secs) = pool.http2_keepalive_secs {
+        builder = builder
+            .http2_keep_alive_interval(Duration::from_secs(secs))
+            .http2_keep_alive_while_idle(true)
+            .tcp_keepalive(Duration::from_secs(secs));
+    }
+    builder
+        .build()
+        .map_err(|e| CrabClawError::Network(format!("failed to build HTTP client: {e}")))
+}
+
+/// Send `builder`, through the cassette when `LLM_CASSETTE` is set and
+/// written to `config.dump_dir` when it is set. Transport errors are
+/// turned into crate errors with `on_error`.
+pub async fn send(
+    config: &AppConfig,
+    builder: reqwest::RequestBuilder,
+    on_error: impl Fn(reqwest::Error) -> CrabClawError,
+) -> Result<reqwest::Response> {
+    let dump_dir = config.dump_dir.as_deref();
+    if config.llm_cassette.is_none() && dump_dir.is_none() {
+        return builder.send().await.map_err(on_error);
+    }
+    let (client, request) = builder.build_split();
+    let request = request.map_err(&on_error)?;
+    let dump = dump_dir.and_then(|dir| dump::Dump::request(dir, config, &request));
+    let on_error = |e: reqwest::Error| {
+        if let Some(dump) = &dump {
+            dump.failed(&e);
+        }
+        on_error(e)
+    };
+    let response = match &config.llm_cassette {
+        Some(cassette) => cassette::exchange(cassette, &client, request, &on_error).await?,
+        None => client.execute(request).await.map_err(&on_error)?,
+    };
+    Ok(match dump {
+        Some(dump) => dump.response(response),
+        None => response,
+    })
+}
+
+/// Pass `response` through unchanged, handing its body chunks to `on_end`
+/// once the body has been read to the end or dropped. The flag is false
+/// when a transport error cut the body short.
+pub(crate) fn tee<F>(response: reqwest::Response, on_end: F) -> reqwest::Response
+where
+    F: FnOnce(Vec<Vec<u8>>, bool) + Send + 'static,
+{
+    let mut builder = http::Response::builder().status(response.status());
+    for (name, value) in response.headers() {
+        builder = builder.header(name, value);
+    }
+    let tee = Tee {
+        chunks: Vec::new(),
+        complete: true,
+        on_end: Some(on_end),
+    };
+    let stream = futures_util::stream::unfold(
+        (response.bytes_stream(), tee),
+        |(mut upstream, mut tee)| async move {
+            match upstream.next().await {
+                Some(Ok(bytes)) => {
+                    tee.chunks.push(bytes.to_vec());
+                    Some((Ok(bytes), (upstream, tee)))
+                }
+                Some(Err(e)) => {
+                    tee.complete = false;
+                    Some((Err(e), (upstream, tee)))
+                }
+                None => None,
+            }
+        },
+    );
+    let response = builder
+        .body(reqwest::Body::wrap_stream(stream))
+        .expect("status and headers come from a valid response");
+    reqwest::Response::from(response)
+}
+
+/// Collects a response body and hands it on when dropped.
+struct Tee<F: FnOnce(Vec<Vec<u8>>, bool)> {
+    chunks: Vec<Vec<u8>>,
+    complete: bool,
+    on_end: Option<F>,
+}
+
+impl<F: FnOnce(Vec<Vec<u8>>, bool)> Drop for Tee<F> {
+    fn drop(&mut self) {
+        if let Some(on_end) = self.on_end.take() {
+            on_end(std::mem::take(&mut self.chunks), self.complete);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clients_are_cached_per_provider_and_pool() {
+        let mut config = crate::core::config::resolve_config(
+            None,
+            &Default::default(),
+            &HashMap::from([("API_KEY".to_string(), "key".to_string())]),
+            &HashMap::new(),
+        )
+        .unwrap();
+        let pool = HttpPoolConfig {
+            idle_timeout_secs: 0,
+            max_idle_per_host: 1,
+            http2_keepalive_secs: Some(15),
+        };
+        config.http_pools.insert("codex".to_string(), pool);
+
+        config.llm_timeouts.connect_secs = 7;
+        let timeout = Duration::from_secs(7);
+        provider_client(&config, "codex").unwrap();
+        provider_client(&config, "codex").unwrap();
+        let clients = CLIENTS.get().unwrap().lock().unwrap();
+        let cached = clients
+            .keys()
+            .filter(|(provider, p, t)| provider == "codex" && *p == pool && *t == timeout)
+            .count();
+        assert_eq!(cached, 1);
+    }
+}
@@ -0,0 +1,163 @@
+//! Tolerant repair of malformed tool-call arguments.
+//!
+//! Some providers (GLM-style OpenAI-compatible endpoints in particular)
+//! occasionally emit tool arguments that are almost JSON: a trailing comma,
+//! closing brackets missing after the last complete value, or the whole
+//! thing wrapped in a Markdown fence. [`repair_json`] fixes those cases and
+//! only returns a result when it actually parses, so valid or hopeless input
+//! is left for the schema check to report.
+//!
+//! Arguments that end inside a string, after a key or after a number may
+//! have been cut off mid-value (e.g. at `max_tokens`), so they are never
+//! completed: running a half-written command or file body is worse than
+//! asking the model again.
+
+use serde::Serialize;
+
+/// A tool call whose arguments were rewritten before execution, recorded in
+/// the tape as a `tool.args_repaired` event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ArgsRepair {
+    pub tool: String,
+    pub call_id: String,
+    pub original: String,
+    pub repaired: String,
+}
+
+/// Tape event for [`ArgsRepair`] records.
+pub const ARGS_REPAIRED_EVENT: &str = "tool.args_repaired";
+
+/// Repaired JSON for `text`, or `None` when it already parses (or is empty)
+/// or cannot be repaired.
+pub fn repair_json(text: &str) -> Option<String> {
+    if text.trim().is_empty() || serde_json::from_str::<serde_json::Value>(text).is_ok() {
+        return None;
+    }
+    let body = strip_fence(text.trim());
+    let repaired = close_structure(body)?;
+    serde_json::from_str::<serde_json::Value>(&repaired)
+        .is_ok()
+        .then_some(repaired)
+}
+
+fn strip_fence(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    let rest = rest.split_once('\n').map_or(rest, |(_, body)| body);
+    rest.trim_end().strip_suffix("```").unwrap_or(rest).trim()
+}
+
+/// Drop trailing commas and close open containers in order, or `None` when
+/// the text stops before its last value is complete.
+fn close_structure(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len() + 8);
+    let mut closers: Vec<char> = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in text.chars() {
+        if in_string {
+            out.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => closers.push('}'),
+            '[' => closers.push(']'),
+            '}' | ']' => {
+                drop_trailing_comma(&mut out);
+                if closers.last() == Some(&c) {
+                    closers.pop();
+                }
+            }
+            _ => {}
+        }
+        out.push(c);
+    }
+
+    if in_string {
+        return None;
+    }
+    drop_trailing_comma(&mut out);
+    if !closers.is_empty() && !ends_with_complete_value(&out) {
+        return None;
+    }
+    while let Some(closer) = closers.pop() {
+        drop_trailing_comma(&mut out);
+        out.push(closer);
+    }
+    Some(out)
+}
+
+/// Whether `out` ends with a value that cannot be the prefix of a longer
+/// one: a string, a closed container or a literal. Numbers may be cut off.
+fn ends_with_complete_value(out: &str) -> bool {
+    let out = out.trim_end();
+    out.ends_with(['"', '}', ']'])
+        || ["true", "false", "null"]
+            .iter()
+            .any(|literal| out.ends_with(literal))
+}
+
+fn drop_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end().len();
+    if out[..trimmed].ends_with(',') {
+        out.truncate(trimmed - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_defects_are_repaired() {
+        let cases = [
+            (r#"{"path": "a.txt",}"#, r#"{"path": "a.txt"}"#),
+            (r#"{"items": [1, 2, ], }"#, r#"{"items": [1, 2]}"#),
+            (r#"{"command": "ls -la","#, r#"{"command": "ls -la"}"#),
+            (r#"{"a": {"b": ["x", "y""#, r#"{"a": {"b": ["x", "y"]}}"#),
+            (r#"{"a": [1, {"ok": true}"#, r#"{"a": [1, {"ok": true}]}"#),
+            (
+                "```json\n{\"path\": \"a.txt\"}\n```",
+                r#"{"path": "a.txt"}"#,
+            ),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(repair_json(input).as_deref(), Some(expected), "{input}");
+        }
+    }
+
+    #[test]
+    fn truncated_values_are_not_completed() {
+        for input in [
+            r#"{"command": "rm -rf /path/part"#,
+            r#"{"path": "a.txt", "content": "first half\"#,
+            r#"{"path":"#,
+            r#"{"count": 12"#,
+            r#"{"items": ["#,
+            "```json\n{\"command\": \"ls -la",
+        ] {
+            assert_eq!(repair_json(input), None, "{input}");
+        }
+    }
+
+    #[test]
+    fn valid_and_hopeless_input_is_left_alone() {
+        assert_eq!(repair_json(r#"{"path": "a,}"}"#), None);
+        assert_eq!(repair_json(""), None);
+        assert_eq!(repair_json("not json at all"), None);
+        // Commas inside strings are not touched.
+        assert_eq!(
+            repair_json(r#"{"text": "a,]", }"#).as_deref(),
+            Some(r#"{"text": "a,]"}"#)
+        );
+    }
+}
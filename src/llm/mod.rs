@@ -1,3 +1,12 @@
 pub mod api_types;
+pub mod cassette;
 pub mod client;
 pub mod codex;
+pub mod dump;
+pub mod health;
+pub mod http;
+pub mod json_repair;
+pub mod models;
+pub mod providers;
+pub mod quirks;
+pub mod sse;
@@ -0,0 +1,313 @@
+//! Capability table for known models.
+//!
+//! Looked up by model name (provider prefix and any `vendor/` path
+//! stripped, longest prefix wins) and adjusted by the `MODEL_CAPABILITIES`
+//! config override:
+//!
+//! ```bash
+//! MODEL_CAPABILITIES='{"openai:llama3.1-8b":{"context_window":8192,"supports_tools":false}}'
+//! ```
+//!
+//! Costs are list prices in USD per million tokens and only approximate.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::core::config::AppConfig;
+use crate::core::error::{CrabClawError, Result};
+use crate::llm::api_types::Usage;
+
+/// What a model can do and what it costs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ModelCapabilities {
+    /// Total tokens (prompt + output) the model accepts.
+    pub context_window: u32,
+    /// Maximum tokens in one response.
+    pub max_output: u32,
+    pub supports_tools: bool,
+    pub supports_vision: bool,
+    /// USD per million prompt tokens, when billed per token.
+    pub input_cost_per_mtok: Option<f64>,
+    /// USD per million completion tokens, when billed per token.
+    pub output_cost_per_mtok: Option<f64>,
+}
+
+/// Partial override from `MODEL_CAPABILITIES`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CapabilityOverride {
+    pub context_window: Option<u32>,
+    pub max_output: Option<u32>,
+    pub supports_tools: Option<bool>,
+    pub supports_vision: Option<bool>,
+    pub input_cost_per_mtok: Option<f64>,
+    pub output_cost_per_mtok: Option<f64>,
+}
+
+/// Capabilities assumed for models missing from the table.
+pub const DEFAULT_CAPABILITIES: ModelCapabilities = ModelCapabilities {
+    context_window: 128_000,
+    max_output: 4_096,
+    supports_tools: true,
+    supports_vision: false,
+    input_cost_per_mtok: None,
+    output_cost_per_mtok: None,
+};
+
+const fn caps(
+    context_window: u32,
+    max_output: u32,
+    supports_vision: bool,
+    input: f64,
+    output: f64,
+) -> ModelCapabilities {
+    ModelCapabilities {
+        context_window,
+        max_output,
+        supports_tools: true,
+        supports_vision,
+        input_cost_per_mtok: Some(input),
+        output_cost_per_mtok: Some(output),
+    }
+}
+
+/// Known models, keyed by name prefix.
+const KNOWN_MODELS: &[(&str, ModelCapabilities)] = &[
+    ("gpt-3.5-turbo", caps(16_385, 4_096, false, 0.50, 1.50)),
+    ("gpt-4o", caps(128_000, 16_384, true, 2.50, 10.00)),
+    ("gpt-4o-mini", caps(128_000, 16_384, true, 0.15, 0.60)),
+    ("gpt-4.1", caps(1_047_576, 32_768, true, 2.00, 8.00)),
+    ("gpt-4.1-mini", caps(1_047_576, 32_768, true, 0.40, 1.60)),
+    ("gpt-4.1-nano", caps(1_047_576, 32_768, true, 0.10, 0.40)),
+    ("gpt-5", caps(400_000, 128_000, true, 1.25, 10.00)),
+    ("gpt-5-mini", caps(400_000, 128_000, true, 0.25, 2.00)),
+    ("gpt-5-nano", caps(400_000, 128_000, true, 0.05, 0.40)),
+    ("o3", caps(200_000, 100_000, true, 2.00, 8.00)),
+    ("o4-mini", caps(200_000, 100_000, true, 1.10, 4.40)),
+    ("claude-3-5-haiku", caps(200_000, 8_192, true, 0.80, 4.00)),
+    ("claude-haiku-4", caps(200_000, 64_000, true, 1.00, 5.00)),
+    ("claude-sonnet-4", caps(200_000, 64_000, true, 3.00, 15.00)),
+    ("claude-opus-4", caps(200_000, 32_000, true, 15.00, 75.00)),
+    ("claude-opus-4-5", caps(200_000, 64_000, true, 5.00, 25.00)),
+];
+
+/// Strip the `provider:` prefix and any `vendor/` path.
+fn bare_name(model: &str) -> &str {
+    let name = model.split_once(':').map_or(model, |(_, name)| name);
+    name.rsplit('/').next().unwrap_or(name)
+}
+
+/// Built-in capabilities for `model` (`provider:name`).
+pub fn lookup(model: &str) -> ModelCapabilities {
+    let name = bare_name(model);
+    let mut found = KNOWN_MODELS
+        .iter()
+        .filter(|(prefix, _)| name.starts_with(prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(DEFAULT_CAPABILITIES, |(_, caps)| *caps);
+    if model.starts_with("codex:") {
+        // Billed through the ChatGPT subscription, not per token.
+        found.input_cost_per_mtok = None;
+        found.output_cost_per_mtok = None;
+    }
+    found
+}
+
+/// Parse a `MODEL_CAPABILITIES` value: a JSON object keyed by model name
+/// (with or without provider prefix).
+pub fn parse_overrides(json: &str) -> Result<BTreeMap<String, CapabilityOverride>> {
+    serde_json::from_str(json)
+        .map_err(|e| CrabClawError::Config(format!("invalid MODEL_CAPABILITIES: {e}")))
+}
+
+/// Capabilities for `model`, with config overrides applied.
+pub fn capabilities(config: &AppConfig, model: &str) -> ModelCapabilities {
+    let mut caps = lookup(model);
+    let overrides = config
+        .model_capabilities
+        .as_deref()
+        .and_then(|json| parse_overrides(json).ok())
+        .unwrap_or_default();
+    for key in [bare_name(model), model] {
+        if let Some(o) = overrides.get(key) {
+            caps.apply(o);
+        }
+    }
+    caps
+}
+
+impl ModelCapabilities {
+    fn apply(&mut self, o: &CapabilityOverride) {
+        if let Some(v) = o.context_window {
+            self.context_window = v;
+        }
+        if let Some(v) = o.max_output {
+            self.max_output = v;
+        }
+        if let Some(v) = o.supports_tools {
+            self.supports_tools = v;
+        }
+        if let Some(v) = o.supports_vision {
+            self.supports_vision = v;
+        }
+        if o.input_cost_per_mtok.is_some() {
+            self.input_cost_per_mtok = o.input_cost_per_mtok;
+        }
+        if o.output_cost_per_mtok.is_some() {
+            self.output_cost_per_mtok = o.output_cost_per_mtok;
+        }
+    }
+
+    /// Tokens available for the prompt once room for a full response is kept.
+    pub fn prompt_budget(&self) -> usize {
+        self.context_window.saturating_sub(self.max_output) as usize
+    }
+
+    /// Cost of `usage` in USD, when the model is billed per token.
+    pub fn cost_usd(&self, usage: &Usage) -> Option<f64> {
+        let input = self.input_cost_per_mtok?;
+        let output = self.output_cost_per_mtok?;
+        Some(
+            (f64::from(usage.prompt_tokens) * input + f64::from(usage.completion_tokens) * output)
+                / 1_000_000.0,
+        )
+    }
+
+    /// Refuse image input for models without vision support.
+    pub fn require_vision(&self, model: &str) -> Result<()> {
+        if self.supports_vision {
+            return Ok(());
+        }
+        Err(CrabClawError::Config(format!(
+            "model '{model}' does not accept images; switch to a vision model or set \
+             \"supports_vision\": true for it in MODEL_CAPABILITIES"
+        )))
+    }
+}
+
+/// Provider prefixes understood by the LLM client.
+pub const PROVIDER_PREFIXES: &[&str] = &[
+    "openai",
+    "anthropic",
+    "codex",
+    "responses",
+    "xai",
+    "mistral",
+];
+
+/// Check that `model` has the `provider:name` form the client can route.
+pub fn validate_model_id(model: &str) -> std::result::Result<(), String> {
+    match model.split_once(':') {
+        Some((provider, name)) if PROVIDER_PREFIXES.contains(&provider) && !name.is_empty() => {
+            Ok(())
+        }
+        _ => Err(format!(
+            "model must be <provider>:<name> with provider one of: {}",
+            PROVIDER_PREFIXES.join(", ")
+        )),
+    }
+}
+
+/// One-line summary such as `context 200k, output 64k, tools, vision`.
+pub fn describe(caps: &ModelCapabilities) -> String {
+    let mut parts = vec![
+        format!("context {}k", caps.context_window / 1000),
+        format!("output {}k", caps.max_output / 1000),
+    ];
+    if caps.supports_tools {
+        parts.push("tools".to_string());
+    }
+    if caps.supports_vision {
+        parts.push("vision".to_string());
+    }
+    if let (Some(input), Some(output)) = (caps.input_cost_per_mtok, caps.output_cost_per_mtok) {
+        parts.push(format!("${input}/${output} per Mtok"));
+    }
+    parts.join(", ")
+}
+
+/// Rough token count for budgeting (about four bytes per token).
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_uses_longest_prefix_and_strips_provider() {
+        assert_eq!(
+            lookup("openai:gpt-4o-mini-2024-07-18").input_cost_per_mtok,
+            Some(0.15)
+        );
+        assert_eq!(lookup("openai:gpt-4o").input_cost_per_mtok, Some(2.50));
+        assert_eq!(
+            lookup("openai:anthropic/claude-sonnet-4.5").max_output,
+            64_000
+        );
+        assert!(!lookup("openai:gpt-3.5-turbo").supports_vision);
+        assert_eq!(lookup("openai:my-local-model"), DEFAULT_CAPABILITIES);
+    }
+
+    #[test]
+    fn codex_models_have_no_token_cost() {
+        let caps = lookup("codex:gpt-5-codex");
+        assert_eq!(caps.context_window, 400_000);
+        assert!(caps.cost_usd(&Usage::default()).is_none());
+    }
+
+    #[test]
+    fn cost_is_computed_per_million_tokens() {
+        let usage = Usage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 100_000,
+            total_tokens: 1_100_000,
+        };
+        let cost = lookup("anthropic:claude-sonnet-4")
+            .cost_usd(&usage)
+            .unwrap();
+        assert!((cost - 4.5).abs() < 1e-9, "got {cost}");
+    }
+
+    #[test]
+    fn overrides_parse_and_apply() {
+        let overrides =
+            parse_overrides(r#"{"llama3":{"context_window":8192,"supports_tools":false}}"#)
+                .unwrap();
+        let mut caps = lookup("openai:llama3");
+        caps.apply(&overrides["llama3"]);
+        assert_eq!(caps.context_window, 8192);
+        assert!(!caps.supports_tools);
+        assert_eq!(caps.prompt_budget(), 8192 - 4096);
+
+        assert!(parse_overrides(r#"{"x":{"context":1}}"#).is_err());
+        assert!(parse_overrides("not json").is_err());
+    }
+
+    #[test]
+    fn model_ids_need_known_provider() {
+        assert!(validate_model_id("anthropic:claude-opus-4-5").is_ok());
+        assert!(validate_model_id("gpt-4o").is_err());
+        assert!(validate_model_id("gemini:pro").is_err());
+        assert!(validate_model_id("openai:").is_err());
+        assert_eq!(
+            describe(&lookup("anthropic:claude-sonnet-4")),
+            "context 200k, output 64k, tools, vision, $3/$15 per Mtok"
+        );
+    }
+
+    #[test]
+    fn vision_is_required_for_images() {
+        assert!(
+            lookup("openai:gpt-4o")
+                .require_vision("openai:gpt-4o")
+                .is_ok()
+        );
+        let err = lookup("openai:gpt-3.5-turbo")
+            .require_vision("openai:gpt-3.5-turbo")
+            .unwrap_err();
+        assert!(err.to_string().contains("does not accept images"));
+    }
+}
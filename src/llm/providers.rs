@@ -0,0 +1,143 @@
+//! OpenAI-compatible providers with their own model prefix.
+//!
+//! `xai:` and `mistral:` models are sent through the Chat Completions client,
+//! but to the provider's own endpoint with the provider's own key instead of
+//! `BASE_URL` / `API_KEY`, so they can be mixed with other providers in one
+//! session. Provider-specific request rewrites live here too.
+
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use crate::llm::api_types::Message;
+
+/// An OpenAI-compatible provider reached through its own prefix.
+#[derive(Debug)]
+pub struct CompatProvider {
+    /// Model prefix and name used for config keys (`XAI_API_KEY`).
+    pub name: &'static str,
+    /// Endpoint used unless `<NAME>_BASE_URL` is set.
+    pub default_api_base: &'static str,
+    /// Tool call ids must be exactly 9 alphanumeric characters.
+    pub short_tool_call_ids: bool,
+}
+
+pub const COMPAT_PROVIDERS: &[CompatProvider] = &[
+    CompatProvider {
+        name: "xai",
+        default_api_base: "https://api.x.ai/v1",
+        short_tool_call_ids: false,
+    },
+    CompatProvider {
+        name: "mistral",
+        default_api_base: "https://api.mistral.ai/v1",
+        short_tool_call_ids: true,
+    },
+];
+
+impl CompatProvider {
+    /// Config key for the API key (`XAI_API_KEY`).
+    pub fn key_env(&self) -> String {
+        format!("{}_API_KEY", self.name.to_ascii_uppercase())
+    }
+
+    /// Config key overriding the endpoint (`XAI_BASE_URL`).
+    pub fn base_url_env(&self) -> String {
+        format!("{}_BASE_URL", self.name.to_ascii_uppercase())
+    }
+
+    /// Apply the provider's request quirks to the outgoing messages.
+    pub fn prepare_messages(&self, messages: &mut [Message]) {
+        if self.short_tool_call_ids {
+            shorten_tool_call_ids(messages);
+        }
+    }
+}
+
+/// Provider for `model` and the model name without its prefix.
+pub fn compat_provider(model: &str) -> Option<(&'static CompatProvider, &str)> {
+    let (prefix, name) = model.split_once(':')?;
+    let provider = COMPAT_PROVIDERS.iter().find(|p| p.name == prefix)?;
+    Some((provider, name))
+}
+
+/// Rewrite tool call ids that are not 9 alphanumeric characters (ids from
+/// other providers, e.g. `call_abc…` or `toolu_…`, in the session history).
+/// The same id always maps to the same replacement, so assistant tool calls
+/// stay paired with their tool results.
+pub fn shorten_tool_call_ids(messages: &mut [Message]) {
+    let valid = |id: &str| id.len() == 9 && id.chars().all(|c| c.is_ascii_alphanumeric());
+    for msg in messages.iter_mut() {
+        for call in msg.tool_calls.iter_mut().flatten() {
+            if !valid(&call.id) {
+                call.id = short_id(&call.id);
+            }
+        }
+        if let Some(id) = msg.tool_call_id.as_mut()
+            && !valid(id)
+        {
+            *id = short_id(id);
+        }
+    }
+}
+
+fn short_id(id: &str) -> String {
+    const ALPHABET: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    let mut n = hasher.finish();
+    (0..9)
+        .map(|_| {
+            let c = ALPHABET[(n % 62) as usize] as char;
+            n /= 62;
+            c
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::api_types::{ToolCall, ToolCallFunction};
+
+    #[test]
+    fn prefixes_resolve_to_providers() {
+        let (provider, model) = compat_provider("mistral:mistral-large-latest").unwrap();
+        assert_eq!(provider.name, "mistral");
+        assert_eq!(model, "mistral-large-latest");
+        assert_eq!(
+            compat_provider("xai:grok-4").unwrap().0.key_env(),
+            "XAI_API_KEY"
+        );
+        assert!(compat_provider("openai:gpt-4o").is_none());
+    }
+
+    #[test]
+    fn foreign_tool_call_ids_are_shortened_consistently() {
+        let call = |id: &str| ToolCall {
+            id: id.to_string(),
+            call_type: "function".to_string(),
+            function: ToolCallFunction {
+                name: "file.read".to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        let mut messages = vec![
+            Message::assistant_with_tool_calls(vec![call("call_abc123def456"), call("aB3dE6gH9")]),
+            Message::tool("call_abc123def456", "one"),
+            Message::tool("aB3dE6gH9", "two"),
+        ];
+        shorten_tool_call_ids(&mut messages);
+
+        let ids: Vec<&str> = messages[0]
+            .tool_calls
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|c| c.id.as_str())
+            .collect();
+        assert_eq!(ids[0].len(), 9);
+        assert!(ids[0].chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(messages[1].tool_call_id.as_deref(), Some(ids[0]));
+        assert_eq!(ids[1], "aB3dE6gH9");
+        assert_eq!(messages[2].tool_call_id.as_deref(), Some("aB3dE6gH9"));
+    }
+}
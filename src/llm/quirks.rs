@@ -0,0 +1,446 @@
+//! Response quirks of non-standard OpenAI-compatible providers.
+//!
+//! GLM, Qwen, Doubao and Moonshot speak the Chat Completions protocol with
+//! small deviations. Rather than special-casing them in the client, each
+//! endpoint gets a [`ProviderQuirks`] preset, picked from its `api_base`
+//! (built-in host matches, overridable with `PROVIDER_QUIRKS`), and response
+//! bodies and stream chunks are normalized to the standard shape before
+//! they are parsed.
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::core::config::AppConfig;
+use crate::core::error::{CrabClawError, Result};
+
+/// Deviations to expect from one endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ProviderQuirks {
+    /// Preset name, as used in `PROVIDER_QUIRKS`.
+    pub name: &'static str,
+    /// HTTP 200 bodies can be errors: `{"code":1214,"msg":"...","success":false}`
+    /// or `{"error":{"message":"..."}}`.
+    pub error_in_ok: bool,
+    /// Tool call `arguments` can be a JSON object instead of a string, and
+    /// stream chunks can carry `message` instead of `delta`.
+    pub alternate_fields: bool,
+    /// Tool calls can come without an `id`, or with an empty one.
+    pub missing_tool_call_ids: bool,
+    /// Streamed tool calls can all use index 0; a new id starts the next call.
+    pub non_incremental_indexes: bool,
+}
+
+/// Standard OpenAI behaviour. The error-in-200 check stays on: it only
+/// fires on bodies that are no chat response at all.
+pub const STANDARD: ProviderQuirks = ProviderQuirks {
+    name: "standard",
+    error_in_ok: true,
+    alternate_fields: false,
+    missing_tool_call_ids: false,
+    non_incremental_indexes: false,
+};
+
+/// Zhipu GLM (`open.bigmodel.cn`, `api.z.ai`).
+pub const GLM: ProviderQuirks = ProviderQuirks {
+    name: "glm",
+    error_in_ok: true,
+    alternate_fields: true,
+    missing_tool_call_ids: true,
+    non_incremental_indexes: false,
+};
+
+/// Alibaba Qwen through DashScope's compatible mode.
+pub const QWEN: ProviderQuirks = ProviderQuirks {
+    name: "qwen",
+    error_in_ok: true,
+    alternate_fields: false,
+    missing_tool_call_ids: true,
+    non_incremental_indexes: true,
+};
+
+/// ByteDance Doubao on Volcano Engine Ark.
+pub const DOUBAO: ProviderQuirks = ProviderQuirks {
+    name: "doubao",
+    error_in_ok: true,
+    alternate_fields: true,
+    missing_tool_call_ids: true,
+    non_incremental_indexes: false,
+};
+
+/// Moonshot Kimi.
+pub const MOONSHOT: ProviderQuirks = ProviderQuirks {
+    name: "moonshot",
+    error_in_ok: true,
+    alternate_fields: true,
+    missing_tool_call_ids: false,
+    non_incremental_indexes: true,
+};
+
+pub const PRESETS: &[ProviderQuirks] = &[STANDARD, GLM, QWEN, DOUBAO, MOONSHOT];
+
+/// `api_base` fragments recognized without configuration.
+const KNOWN_HOSTS: &[(&str, ProviderQuirks)] = &[
+    ("bigmodel.cn", GLM),
+    ("api.z.ai", GLM),
+    ("dashscope", QWEN),
+    ("volces.com", DOUBAO),
+    ("moonshot.", MOONSHOT),
+];
+
+/// Preset by name.
+pub fn preset(name: &str) -> Option<ProviderQuirks> {
+    PRESETS
+        .iter()
+        .find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+        .copied()
+}
+
+/// Parse `PROVIDER_QUIRKS`: `;`-separated `<api_base fragment>=<preset>`
+/// entries, e.g. `llm.internal:8000=qwen;bigmodel.cn=standard`.
+pub fn parse_overrides(value: &str) -> std::result::Result<Vec<(String, ProviderQuirks)>, String> {
+    value
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let (fragment, name) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("'{entry}' is not <api_base>=<preset>"))?;
+            let fragment = fragment.trim();
+            if fragment.is_empty() {
+                return Err(format!("'{entry}' has an empty api_base"));
+            }
+            let quirks = preset(name).ok_or_else(|| {
+                let names: Vec<&str> = PRESETS.iter().map(|p| p.name).collect();
+                format!(
+                    "unknown preset '{}' (expected {})",
+                    name.trim(),
+                    names.join(", ")
+                )
+            })?;
+            Ok((fragment.to_string(), quirks))
+        })
+        .collect()
+}
+
+/// Quirks of the endpoint at `api_base`: the first matching
+/// `PROVIDER_QUIRKS` entry, then the built-in hosts, else [`STANDARD`].
+pub fn for_api_base(config: &AppConfig, api_base: &str) -> ProviderQuirks {
+    config
+        .provider_quirks
+        .iter()
+        .map(|(fragment, quirks)| (fragment.as_str(), *quirks))
+        .chain(KNOWN_HOSTS.iter().copied())
+        .find(|(fragment, _)| api_base.contains(fragment))
+        .map_or(STANDARD, |(_, quirks)| quirks)
+}
+
+impl ProviderQuirks {
+    /// Turn an error reported in an HTTP 200 body into an error.
+    pub fn check_ok_body(&self, body: &Value) -> Result<()> {
+        if !self.error_in_ok || body.get("choices").is_some() {
+            return Ok(());
+        }
+        if body["success"] == false || body["code"].as_i64().is_some_and(|c| c >= 400) {
+            let code = body["code"].as_i64().unwrap_or(0);
+            let msg = body["msg"]
+                .as_str()
+                .or_else(|| body["message"].as_str())
+                .unwrap_or("unknown API error");
+            tracing::warn!(
+                code,
+                msg,
+                quirks = self.name,
+                "non-standard API error in 200 response"
+            );
+            return Err(CrabClawError::Api(format!(
+                "API error (code {code}): {msg}"
+            )));
+        }
+        if let Some(message) = body["error"]["message"].as_str() {
+            tracing::warn!(
+                msg = message,
+                quirks = self.name,
+                "API error in 200 response"
+            );
+            return Err(CrabClawError::Api(format!("API error: {message}")));
+        }
+        Ok(())
+    }
+
+    /// Normalize a non-streaming response body in place.
+    pub fn normalize_response(&self, body: &mut Value) {
+        let Some(choices) = body.get_mut("choices").and_then(Value::as_array_mut) else {
+            return;
+        };
+        for choice in choices {
+            let calls = choice
+                .pointer_mut("/message/tool_calls")
+                .and_then(Value::as_array_mut);
+            for call in calls.into_iter().flatten() {
+                let Some(call) = call.as_object_mut() else {
+                    continue;
+                };
+                if self.missing_tool_call_ids && !has_text(call, "id") {
+                    call.insert("id".into(), Value::String(new_tool_call_id()));
+                }
+                if self.alternate_fields {
+                    stringify_arguments(call);
+                }
+            }
+        }
+    }
+
+    /// State for normalizing the chunks of one stream.
+    pub fn stream(&self) -> StreamNormalizer {
+        StreamNormalizer {
+            quirks: *self,
+            ids: Vec::new(),
+            current: 0,
+        }
+    }
+}
+
+/// Normalizes the chunks of one streamed response (see
+/// [`ProviderQuirks::stream`]).
+#[derive(Debug)]
+pub struct StreamNormalizer {
+    quirks: ProviderQuirks,
+    /// Ids of the tool calls started so far, by index.
+    ids: Vec<String>,
+    /// Index of the tool call being streamed.
+    current: usize,
+}
+
+impl StreamNormalizer {
+    /// Error reported in a chunk, see [`ProviderQuirks::check_ok_body`].
+    pub fn check_ok_body(&self, chunk: &Value) -> Result<()> {
+        self.quirks.check_ok_body(chunk)
+    }
+
+    /// Normalize one parsed `data` payload in place.
+    pub fn normalize(&mut self, chunk: &mut Value) {
+        let Some(choices) = chunk.get_mut("choices").and_then(Value::as_array_mut) else {
+            return;
+        };
+        for choice in choices {
+            let Some(choice) = choice.as_object_mut() else {
+                continue;
+            };
+            if self.quirks.alternate_fields
+                && !choice.contains_key("delta")
+                && let Some(message) = choice.remove("message")
+            {
+                choice.insert("delta".into(), message);
+            }
+            let calls = choice
+                .get_mut("delta")
+                .and_then(|d| d.get_mut("tool_calls"))
+                .and_then(Value::as_array_mut);
+            for call in calls.into_iter().flatten() {
+                if let Some(call) = call.as_object_mut() {
+                    self.normalize_tool_call(call);
+                }
+            }
+        }
+    }
+
+    fn normalize_tool_call(&mut self, call: &mut Map<String, Value>) {
+        // Continuation chunks sometimes repeat the fields as "".
+        if !has_text(call, "id") {
+            call.remove("id");
+        }
+        let name = call
+            .get_mut("function")
+            .and_then(Value::as_object_mut)
+            .and_then(|f| {
+                if !has_text(f, "name") {
+                    f.remove("name");
+                }
+                f.get("name").cloned()
+            });
+        if self.quirks.alternate_fields {
+            stringify_arguments(call);
+        }
+
+        let id = call.get("id").and_then(Value::as_str).map(str::to_string);
+        let starts = match &id {
+            Some(id) => !self.ids.contains(id),
+            None => self.quirks.missing_tool_call_ids && name.is_some(),
+        };
+        let index = call
+            .get("index")
+            .and_then(Value::as_u64)
+            .map(|i| i as usize);
+        if starts {
+            let id = id.unwrap_or_else(new_tool_call_id);
+            let index = match index {
+                Some(index) if !self.quirks.non_incremental_indexes => index,
+                _ => self.ids.len(),
+            };
+            call.insert("id".into(), Value::String(id.clone()));
+            call.insert("index".into(), index.into());
+            if self.ids.len() <= index {
+                self.ids.resize(index + 1, String::new());
+            }
+            self.ids[index] = id;
+            self.current = index;
+        } else {
+            let index = match (index, &id) {
+                (Some(index), _) if !self.quirks.non_incremental_indexes => index,
+                (_, Some(id)) => self
+                    .ids
+                    .iter()
+                    .position(|known| known == id)
+                    .unwrap_or(self.current),
+                (_, None) => self.current,
+            };
+            call.insert("index".into(), index.into());
+            self.current = index;
+        }
+    }
+}
+
+fn has_text(map: &Map<String, Value>, key: &str) -> bool {
+    map.get(key)
+        .and_then(Value::as_str)
+        .is_some_and(|s| !s.is_empty())
+}
+
+/// `function.arguments` given as a JSON object becomes its string form.
+fn stringify_arguments(call: &mut Map<String, Value>) {
+    if let Some(arguments) = call
+        .get_mut("function")
+        .and_then(|f| f.get_mut("arguments"))
+        .filter(|a| a.is_object())
+    {
+        *arguments = Value::String(arguments.to_string());
+    }
+}
+
+fn new_tool_call_id() -> String {
+    use rand::Rng;
+    let suffix: String = rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(24)
+        .map(char::from)
+        .collect();
+    format!("call_{suffix}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn presets_are_picked_by_api_base() {
+        let mut config = test_config();
+        assert_eq!(for_api_base(&config, "https://api.openai.com/v1"), STANDARD);
+        assert_eq!(
+            for_api_base(&config, "https://open.bigmodel.cn/api/paas/v4"),
+            GLM
+        );
+        assert_eq!(
+            for_api_base(&config, "https://dashscope.aliyuncs.com/compatible-mode/v1"),
+            QWEN
+        );
+        assert_eq!(
+            for_api_base(&config, "https://ark.cn-beijing.volces.com/api/v3"),
+            DOUBAO
+        );
+        assert_eq!(
+            for_api_base(&config, "https://api.moonshot.cn/v1"),
+            MOONSHOT
+        );
+
+        config.provider_quirks =
+            parse_overrides("llm.internal:8000=qwen; bigmodel.cn=standard").unwrap();
+        assert_eq!(for_api_base(&config, "http://llm.internal:8000/v1"), QWEN);
+        assert_eq!(
+            for_api_base(&config, "https://open.bigmodel.cn/api/paas/v4"),
+            STANDARD
+        );
+        assert!(parse_overrides("example.com").is_err());
+        assert!(parse_overrides("example.com=deepseek").is_err());
+    }
+
+    fn test_config() -> AppConfig {
+        let mut env = std::collections::HashMap::new();
+        env.insert("API_KEY".to_string(), "key".to_string());
+        crate::core::config::resolve_config(
+            None,
+            &Default::default(),
+            &env,
+            &std::collections::HashMap::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn errors_in_ok_bodies_are_reported() {
+        let glm = json!({"code": 1214, "msg": "model not found", "success": false});
+        let err = STANDARD.check_ok_body(&glm).unwrap_err();
+        assert!(err.to_string().contains("code 1214"), "{err}");
+        let nested = json!({"error": {"message": "quota exceeded"}});
+        assert!(DOUBAO.check_ok_body(&nested).is_err());
+        let ok = json!({"choices": [], "code": 0});
+        assert!(STANDARD.check_ok_body(&ok).is_ok());
+    }
+
+    #[test]
+    fn responses_get_ids_and_string_arguments() {
+        let mut body = json!({"choices": [{"message": {"role": "assistant", "content": "",
+            "tool_calls": [{"function": {"name": "file.read", "arguments": {"path": "a.txt"}}}]}}]});
+        GLM.normalize_response(&mut body);
+        let call = &body["choices"][0]["message"]["tool_calls"][0];
+        assert!(call["id"].as_str().unwrap().starts_with("call_"));
+        assert_eq!(call["function"]["arguments"], r#"{"path":"a.txt"}"#);
+
+        let response: crate::llm::api_types::ChatResponse = serde_json::from_value(body).unwrap();
+        assert_eq!(response.tool_calls().unwrap()[0].function.name, "file.read");
+    }
+
+    #[test]
+    fn non_incremental_stream_indexes_are_renumbered() {
+        let mut stream = QWEN.stream();
+        let mut chunks = [
+            json!({"choices": [{"delta": {"tool_calls": [{"index": 0, "id": "call_a", "function": {"name": "file.read", "arguments": ""}}]}}]}),
+            json!({"choices": [{"delta": {"tool_calls": [{"index": 0, "id": "", "function": {"name": "", "arguments": "{}"}}]}}]}),
+            json!({"choices": [{"delta": {"tool_calls": [{"index": 0, "id": "call_b", "function": {"name": "file.list", "arguments": ""}}]}}]}),
+            json!({"choices": [{"delta": {"tool_calls": [{"index": 0, "function": {"arguments": "{}"}}]}}]}),
+        ];
+        for chunk in &mut chunks {
+            stream.normalize(chunk);
+        }
+        let call = |i: usize| &chunks[i]["choices"][0]["delta"]["tool_calls"][0];
+        assert_eq!(call(0)["index"], 0);
+        assert_eq!(call(1)["index"], 0);
+        assert!(call(1).get("id").is_none());
+        assert!(call(1)["function"].get("name").is_none());
+        assert_eq!(call(2)["index"], 1);
+        assert_eq!(call(3)["index"], 1);
+    }
+
+    #[test]
+    fn stream_fills_missing_ids_and_alternate_fields() {
+        let mut stream = GLM.stream();
+        let mut chunk = json!({"choices": [{"message": {"tool_calls": [
+            {"function": {"name": "file.read", "arguments": {"path": "a"}}}
+        ]}}]});
+        stream.normalize(&mut chunk);
+        let call = &chunk["choices"][0]["delta"]["tool_calls"][0];
+        assert!(call["id"].as_str().unwrap().starts_with("call_"));
+        assert_eq!(call["index"], 0);
+        assert_eq!(call["function"]["arguments"], r#"{"path":"a"}"#);
+
+        // Standard providers are left alone.
+        let mut stream = STANDARD.stream();
+        let mut chunk = json!({"choices": [{"delta": {"tool_calls": [
+            {"index": 1, "id": "call_x", "function": {"name": "file.read", "arguments": ""}}
+        ]}}]});
+        let before = chunk.clone();
+        stream.normalize(&mut chunk);
+        assert_eq!(chunk, before);
+    }
+}
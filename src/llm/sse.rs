@@ -0,0 +1,207 @@
+//! Server-sent events parsing shared by the OpenAI, Anthropic and Codex
+//! providers.
+//!
+//! Follows the WHATWG event stream format rather than splitting on `"\n\n"`:
+//! lines may end in CRLF, LF or CR; an event may carry several `data:` lines
+//! (joined with `\n`); `event:` and `id:` fields are kept; `:` comment lines
+//! and unknown fields are ignored. Bytes are buffered until a full line is
+//! available, so multi-byte UTF-8 characters split across network chunks
+//! decode correctly.
+
+/// One dispatched server-sent event.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SseEvent {
+    /// The `event:` field, if the server named the event.
+    pub event: Option<String>,
+    /// All `data:` lines of the event, joined with `\n`.
+    pub data: String,
+    /// The `id:` field, if present.
+    pub id: Option<String>,
+}
+
+impl SseEvent {
+    /// JSON documents carried by the event.
+    ///
+    /// Per the spec all `data:` lines form one payload, but some servers put
+    /// one JSON document per `data:` line with no blank line in between; when
+    /// the joined data is not valid JSON, each line is returned on its own.
+    pub fn json_payloads(&self) -> Vec<&str> {
+        if !self.data.contains('\n')
+            || serde_json::from_str::<serde::de::IgnoredAny>(&self.data).is_ok()
+        {
+            return vec![self.data.as_str()];
+        }
+        self.data.lines().filter(|l| !l.trim().is_empty()).collect()
+    }
+}
+
+/// Incremental event stream parser: feed it network chunks with
+/// [`SseParser::push`] and call [`SseParser::finish`] at end of stream.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: Vec<u8>,
+    event: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl SseParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Feed a chunk of the response body and return the events it completes.
+    pub fn push(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        self.buffer.extend_from_slice(bytes);
+        let mut events = Vec::new();
+        let mut start = 0;
+        let mut i = 0;
+        while i < self.buffer.len() {
+            let terminator = match self.buffer[i] {
+                b'\n' => 1,
+                // A trailing CR may be the first half of a CRLF split across
+                // chunks; wait for the next byte before deciding.
+                b'\r' if i + 1 == self.buffer.len() => break,
+                b'\r' if self.buffer[i + 1] == b'\n' => 2,
+                b'\r' => 1,
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            };
+            let line = String::from_utf8_lossy(&self.buffer[start..i]).into_owned();
+            if let Some(event) = self.process_line(&line) {
+                events.push(event);
+            }
+            i += terminator;
+            start = i;
+        }
+        self.buffer.drain(..start);
+        events
+    }
+
+    /// End of stream: dispatch an event left without its closing blank line.
+    ///
+    /// The spec discards such events, but several proxies close the
+    /// connection right after the last `data:` line.
+    pub fn finish(&mut self) -> Option<SseEvent> {
+        if !self.buffer.is_empty() {
+            let rest = std::mem::take(&mut self.buffer);
+            let line = String::from_utf8_lossy(&rest);
+            let line = line.strip_suffix('\r').unwrap_or(&line).to_string();
+            if let Some(event) = self.process_line(&line) {
+                return Some(event);
+            }
+        }
+        self.dispatch()
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => self.data.push(value.to_string()),
+            "event" => self.event = Some(value.to_string()),
+            "id" if !value.contains('\0') => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<SseEvent> {
+        let event = self.event.take();
+        let id = self.id.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        Some(SseEvent {
+            event,
+            data: std::mem::take(&mut self.data).join("\n"),
+            id,
+        })
+    }
+}
+
+/// Parse a complete event stream body.
+pub fn parse_events(body: &str) -> Vec<SseEvent> {
+    let mut parser = SseParser::new();
+    let mut events = parser.push(body.as_bytes());
+    events.extend(parser.finish());
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(events: &[SseEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.data.as_str()).collect()
+    }
+
+    #[test]
+    fn crlf_separated_events_are_dispatched() {
+        let events = parse_events("data: {\"a\":1}\r\n\r\ndata: {\"b\":2}\r\n\r\n");
+        assert_eq!(data(&events), ["{\"a\":1}", "{\"b\":2}"]);
+    }
+
+    #[test]
+    fn bare_cr_and_lf_also_end_lines() {
+        let events = parse_events("data: one\r\rdata: two\n\n");
+        assert_eq!(data(&events), ["one", "two"]);
+    }
+
+    #[test]
+    fn multiple_data_lines_join_with_newline() {
+        let events = parse_events("data: {\"text\":\ndata: \"hi\"}\n\n");
+        assert_eq!(data(&events), ["{\"text\":\n\"hi\"}"]);
+    }
+
+    #[test]
+    fn json_per_data_line_is_split_when_needed() {
+        let events = parse_events("data: {\"a\":1}\ndata: [DONE]\n\ndata: {\"b\":\ndata: 2}\n\n");
+        assert_eq!(events[0].json_payloads(), ["{\"a\":1}", "[DONE]"]);
+        assert_eq!(events[1].json_payloads(), ["{\"b\":\n2}"]);
+    }
+
+    #[test]
+    fn event_id_fields_and_comments() {
+        let events = parse_events(
+            ": keep-alive\n\nevent: message_start\nid: 7\ndata:{\"x\":1}\nretry: 100\n\n",
+        );
+        assert_eq!(
+            events,
+            [SseEvent {
+                event: Some("message_start".to_string()),
+                data: "{\"x\":1}".to_string(),
+                id: Some("7".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn chunks_split_mid_line_mid_crlf_and_mid_utf8() {
+        let body = "data: héllo\r\n\r\ndata: [DONE]\r\n\r\n".as_bytes();
+        let mut parser = SseParser::new();
+        let mut events = Vec::new();
+        for chunk in body.chunks(1) {
+            events.extend(parser.push(chunk));
+        }
+        events.extend(parser.finish());
+        assert_eq!(data(&events), ["héllo", "[DONE]"]);
+    }
+
+    #[test]
+    fn unterminated_final_event_is_flushed() {
+        let events = parse_events("data: first\n\ndata: last");
+        assert_eq!(data(&events), ["first", "last"]);
+        assert!(parse_events("event: ping\n\n").is_empty());
+    }
+}
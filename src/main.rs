@@ -1,30 +1,64 @@
-use tracing_subscriber::{EnvFilter, fmt};
+use std::sync::Mutex;
 
-fn main() {
-    let filter =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("crabclaw=info"));
+use crabclaw::core::log_file::{LogFileConfig, RotatingFile};
+use tracing_subscriber::{EnvFilter, Layer, fmt, layer::SubscriberExt, util::SubscriberInitExt};
+
+fn env_filter() -> EnvFilter {
+    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("crabclaw=info"))
+}
 
+fn main() {
     let json_mode = std::env::var("LOG_FORMAT")
         .map(|v| v.eq_ignore_ascii_case("json"))
         .unwrap_or(false);
 
-    if json_mode {
-        fmt()
+    // Logs go to stderr so stdout stays clean for answers and for the
+    // `run --output jsonl` event stream.
+    let stderr_layer = if json_mode {
+        fmt::layer()
             .json()
-            .with_env_filter(filter)
+            .with_writer(std::io::stderr)
             .with_target(true)
             .with_current_span(true)
-            .init();
+            .boxed()
     } else {
-        fmt()
+        fmt::layer()
             .compact()
-            .with_env_filter(filter)
+            .with_writer(std::io::stderr)
             .with_target(true)
-            .init();
-    }
+            .boxed()
+    };
+
+    // Optional persistent copy as JSON lines (see `core::log_file`).
+    let file_layer = match LogFileConfig::from_env().and_then(|config| {
+        config
+            .map(|config| {
+                let path = config.path.display().to_string();
+                RotatingFile::open(config).map_err(|e| format!("cannot open LOG_FILE {path}: {e}"))
+            })
+            .transpose()
+    }) {
+        Ok(file) => file.map(|file| {
+            fmt::layer()
+                .json()
+                .with_writer(Mutex::new(file))
+                .with_target(true)
+                .with_current_span(true)
+                .with_filter(env_filter())
+        }),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
+
+    tracing_subscriber::registry()
+        .with(stderr_layer.with_filter(env_filter()))
+        .with(file_layer)
+        .init();
 
     if let Err(err) = crabclaw::channels::cli::run() {
-        eprintln!("{err}");
+        eprintln!("{}", err.user_message());
         std::process::exit(1);
     }
 }
@@ -0,0 +1,332 @@
+//! Standalone HTML transcripts of a tape (`,export html`).
+//!
+//! The page has no external assets: styles are inlined, tool calls and
+//! command output sit in collapsible `<details>` sections, and fenced code
+//! blocks get a small built-in highlighter (keywords, strings, comments and
+//! numbers) so the file can be mailed or attached to a ticket as is.
+
+use crate::tape::store::TapeEntry;
+
+const STYLE: &str = "\
+body{font:15px/1.5 -apple-system,'Segoe UI',sans-serif;max-width:860px;margin:2em auto;padding:0 1em;color:#1f2328;background:#fff}
+header{border-bottom:1px solid #d0d7de;margin-bottom:1.5em}
+header p{color:#656d76;margin:.2em 0 1em}
+.msg{border-radius:8px;padding:.6em 1em;margin:1em 0;white-space:pre-wrap;overflow-wrap:anywhere}
+.msg .role{font-size:12px;font-weight:600;text-transform:uppercase;color:#656d76;white-space:normal}
+.user{background:#ddf4ff}
+.assistant{background:#f6f8fa}
+.superseded{opacity:.5}
+details{border:1px solid #d0d7de;border-radius:6px;margin:.5em 0;padding:.3em .8em;font-size:13px}
+summary{cursor:pointer;font-family:ui-monospace,monospace}
+.anchor{text-align:center;color:#656d76;font-size:12px;margin:1.5em 0;border-top:1px dashed #d0d7de}
+pre{background:#0d1117;color:#e6edf3;padding:.8em;border-radius:6px;overflow-x:auto;white-space:pre;font:13px/1.45 ui-monospace,monospace}
+.kw{color:#ff7b72}.str{color:#a5d6ff}.com{color:#8b949e;font-style:italic}.num{color:#79c0ff}";
+
+const KEYWORDS: &[&str] = &[
+    "as",
+    "async",
+    "await",
+    "break",
+    "case",
+    "class",
+    "const",
+    "continue",
+    "def",
+    "do",
+    "elif",
+    "else",
+    "enum",
+    "export",
+    "false",
+    "fn",
+    "for",
+    "from",
+    "func",
+    "function",
+    "if",
+    "impl",
+    "import",
+    "in",
+    "interface",
+    "let",
+    "loop",
+    "match",
+    "mod",
+    "mut",
+    "new",
+    "nil",
+    "None",
+    "null",
+    "pub",
+    "return",
+    "self",
+    "static",
+    "struct",
+    "super",
+    "switch",
+    "trait",
+    "True",
+    "true",
+    "False",
+    "type",
+    "use",
+    "var",
+    "where",
+    "while",
+    "with",
+    "yield",
+];
+
+/// Languages whose line comments start with `#` rather than `//`.
+const HASH_COMMENT_LANGS: &[&str] = &[
+    "bash", "py", "python", "rb", "ruby", "sh", "shell", "toml", "yaml", "yml", "zsh",
+];
+
+/// Render `entries` as a standalone HTML page titled `title`.
+pub fn render_html(title: &str, entries: &[TapeEntry]) -> String {
+    let superseded = superseded_ids(entries);
+    let messages = entries.iter().filter(|e| e.kind == "message").count();
+    let mut body = String::new();
+    for entry in entries {
+        match entry.kind.as_str() {
+            "message" => {
+                let role = entry.payload["role"].as_str().unwrap_or("user");
+                let content = entry.payload["content"].as_str().unwrap_or_default();
+                if content.is_empty() || role == "system" {
+                    continue;
+                }
+                let class = if superseded.contains(&entry.id) {
+                    format!("msg {role} superseded")
+                } else {
+                    format!("msg {role}")
+                };
+                body.push_str(&format!(
+                    "<div class=\"{class}\"><div class=\"role\">{role} · {}</div>{}</div>\n",
+                    escape(&short_time(&entry.timestamp)),
+                    render_content(content)
+                ));
+            }
+            crate::core::model_runner::TOOL_CALL_EVENT => {
+                let tool = entry.payload["tool"].as_str().unwrap_or("tool");
+                let arguments = entry.payload["arguments"].as_str().unwrap_or_default();
+                let arguments = serde_json::from_str::<serde_json::Value>(arguments)
+                    .and_then(|v| serde_json::to_string_pretty(&v))
+                    .unwrap_or_else(|_| arguments.to_string());
+                let result = entry.payload["result"].as_str().unwrap_or_default();
+                body.push_str(&format!(
+                    "<details><summary>tool: {}</summary><pre>{}</pre><pre>{}</pre></details>\n",
+                    escape(tool),
+                    highlight(&arguments, "json"),
+                    escape(result)
+                ));
+            }
+            "command" => {
+                let label = entry.payload["cmd"]
+                    .as_str()
+                    .or_else(|| entry.payload["name"].as_str())
+                    .unwrap_or("command");
+                let output = ["output", "stdout", "stderr"]
+                    .iter()
+                    .filter_map(|key| entry.payload[key].as_str())
+                    .filter(|s| !s.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                body.push_str(&format!(
+                    "<details><summary>,{}</summary><pre>{}</pre></details>\n",
+                    escape(label),
+                    escape(&output)
+                ));
+            }
+            "anchor" => {
+                let name = entry.payload["name"].as_str().unwrap_or("anchor");
+                body.push_str(&format!("<div class=\"anchor\">{}</div>\n", escape(name)));
+            }
+            _ => {}
+        }
+    }
+
+    let exported = chrono::Utc::now().format("%Y-%m-%d %H:%M UTC");
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+<title>{title}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n\
+<header><h1>{title}</h1><p>{messages} message(s), exported {exported} by crabclaw</p></header>\n\
+{body}</body>\n</html>\n",
+        title = escape(title),
+    )
+}
+
+/// Entry IDs covered by `turn.superseded` events.
+fn superseded_ids(entries: &[TapeEntry]) -> Vec<u64> {
+    entries
+        .iter()
+        .filter(|e| e.kind == crate::core::agent_loop::TURN_SUPERSEDED_EVENT)
+        .filter_map(|e| Some(e.payload["from"].as_u64()?..=e.payload["to"].as_u64()?))
+        .flatten()
+        .collect()
+}
+
+fn short_time(timestamp: &str) -> String {
+    timestamp.get(..16).unwrap_or(timestamp).replace('T', " ")
+}
+
+/// Escape text and turn fenced code blocks into highlighted `<pre>` blocks.
+fn render_content(content: &str) -> String {
+    let mut out = String::new();
+    let mut rest = content;
+    while let Some(start) = rest.find("```") {
+        out.push_str(&escape(&rest[..start]));
+        let after = &rest[start + 3..];
+        let (lang, code_start) = match after.find('\n') {
+            Some(i) => (after[..i].trim(), &after[i + 1..]),
+            None => ("", after),
+        };
+        match code_start.find("```") {
+            Some(end) => {
+                let code = code_start[..end].trim_end_matches('\n');
+                out.push_str(&format!("<pre>{}</pre>", highlight(code, lang)));
+                rest = code_start[end + 3..].trim_start_matches('\n');
+            }
+            None => {
+                out.push_str(&escape(&rest[start..]));
+                rest = "";
+            }
+        }
+    }
+    out.push_str(&escape(rest));
+    out
+}
+
+/// Wrap keywords, strings, comments and numbers of `code` in spans.
+fn highlight(code: &str, lang: &str) -> String {
+    let lang = lang.to_ascii_lowercase();
+    let hash_comments = HASH_COMMENT_LANGS.contains(&lang.as_str());
+    // Rust lifetimes (`'a`) are not strings.
+    let single_quotes = lang != "rust" && lang != "rs";
+    let chars: Vec<char> = code.chars().collect();
+    let mut out = String::with_capacity(code.len() * 2);
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let line_comment =
+            (c == '/' && chars.get(i + 1) == Some(&'/')) || (c == '#' && hash_comments);
+        if line_comment {
+            let end = chars[i..]
+                .iter()
+                .position(|&c| c == '\n')
+                .map_or(chars.len(), |p| i + p);
+            span(&mut out, "com", &chars[i..end]);
+            i = end;
+        } else if c == '"' || c == '`' || (c == '\'' && single_quotes) {
+            let mut end = i + 1;
+            while end < chars.len() && chars[end] != c && chars[end] != '\n' {
+                end += if chars[end] == '\\' { 2 } else { 1 };
+            }
+            let end = (end + 1).min(chars.len());
+            span(&mut out, "str", &chars[i..end]);
+            i = end;
+        } else if c.is_ascii_digit() && (i == 0 || !is_ident(chars[i - 1])) {
+            let end = chars[i..]
+                .iter()
+                .position(|&c| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .map_or(chars.len(), |p| i + p);
+            span(&mut out, "num", &chars[i..end]);
+            i = end;
+        } else if is_ident(c) {
+            let end = chars[i..]
+                .iter()
+                .position(|&c| !is_ident(c))
+                .map_or(chars.len(), |p| i + p);
+            let word: String = chars[i..end].iter().collect();
+            if KEYWORDS.contains(&word.as_str()) {
+                span(&mut out, "kw", &chars[i..end]);
+            } else {
+                out.push_str(&escape(&word));
+            }
+            i = end;
+        } else {
+            out.push_str(&escape(&c.to_string()));
+            i += 1;
+        }
+    }
+    out
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn span(out: &mut String, class: &str, text: &[char]) {
+    let text: String = text.iter().collect();
+    out.push_str(&format!("<span class=\"{class}\">{}</span>", escape(&text)));
+}
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entry(id: u64, kind: &str, payload: serde_json::Value) -> TapeEntry {
+        TapeEntry {
+            id,
+            kind: kind.to_string(),
+            payload,
+            timestamp: "2026-03-01T10:15:00Z".to_string(),
+            turn: None,
+        }
+    }
+
+    #[test]
+    fn transcript_escapes_text_and_folds_tool_calls() {
+        let entries = [
+            entry(1, "message", json!({"role": "user", "content": "why <b>?"})),
+            entry(
+                2,
+                "tool.call",
+                json!({"tool": "file.read", "arguments": "{\"path\":\"a.rs\"}", "result": "x < y"}),
+            ),
+            entry(
+                3,
+                "message",
+                json!({"role": "assistant", "content": "Try:\n```rust\nlet x = \"hi\"; // note\n```\ndone"}),
+            ),
+        ];
+        let html = render_html("Debug <session>", &entries);
+
+        assert!(html.contains("<title>Debug &lt;session&gt;</title>"));
+        assert!(html.contains("why &lt;b&gt;?"));
+        assert!(html.contains("<details><summary>tool: file.read</summary>"));
+        assert!(html.contains("x &lt; y"));
+        assert!(html.contains("<span class=\"kw\">let</span>"));
+        assert!(html.contains("<span class=\"str\">&quot;hi&quot;</span>"));
+        assert!(html.contains("<span class=\"com\">// note</span>"));
+        assert!(html.contains("</pre>done"));
+        assert!(!html.contains("<script"));
+    }
+
+    #[test]
+    fn highlighter_handles_hash_comments_and_numbers() {
+        let html = highlight("x = 42 # answer", "python");
+        assert_eq!(
+            html,
+            "x = <span class=\"num\">42</span> <span class=\"com\"># answer</span>"
+        );
+        // `#` is not a comment in Rust attributes.
+        assert!(!highlight("#[test]", "rust").contains("com"));
+    }
+}
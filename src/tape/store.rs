@@ -1,9 +1,11 @@
-use std::fs::{self, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 /// A single entry in the append-only tape.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -12,6 +14,9 @@ pub struct TapeEntry {
     pub kind: String,
     pub payload: serde_json::Value,
     pub timestamp: String,
+    /// Agent turn that wrote the entry (see `core::trace`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub turn: Option<String>,
 }
 
 /// Summary information about the tape.
@@ -24,17 +29,75 @@ pub struct TapeInfo {
     pub entries_since_last_anchor: usize,
 }
 
+/// When appended entries are forced to disk with `fsync` (`TAPE_FSYNC`).
+///
+/// Every entry reaches the OS as soon as it is appended, so other readers
+/// always see it; the policy only decides how much a power loss or kernel
+/// crash can take with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TapeSync {
+    /// Leave flushing to the OS.
+    Never,
+    /// Sync after every entry.
+    Always,
+    /// Sync at most once per interval, at the end of each agent turn, and
+    /// when the tape is closed.
+    Interval(Duration),
+}
+
+impl Default for TapeSync {
+    fn default() -> Self {
+        Self::Interval(Duration::from_secs(5))
+    }
+}
+
+impl TapeSync {
+    /// Parse `always`, `never` or an interval in seconds.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "always" | "0" => Some(Self::Always),
+            "never" | "off" => Some(Self::Never),
+            secs => secs
+                .parse::<u64>()
+                .ok()
+                .map(|secs| Self::Interval(Duration::from_secs(secs))),
+        }
+    }
+}
+
+/// Open append handle plus what has not been synced yet.
+struct TapeWriter {
+    file: File,
+    /// File length after our last write; anything else means the file was
+    /// replaced or written by someone else and the handle is reopened.
+    len: u64,
+    /// Entries written since the last sync.
+    unsynced: usize,
+    last_sync: Instant,
+}
+
 /// Append-only JSONL tape store for session recording.
 ///
 /// Aligned with bub's `FileTapeStore` + `TapeService`:
 /// - Entries are persisted as one JSON object per line.
 /// - IDs are monotonically increasing.
 /// - Anchors mark semantic boundaries in the session.
+/// - The file stays open between appends; each entry goes out in a single
+///   write, synced according to [`TapeSync`]. A torn last line left by a
+///   crash is cut off before the first append.
 pub struct TapeStore {
     name: String,
     path: PathBuf,
     entries: Vec<TapeEntry>,
     next_id: u64,
+    writer: Option<TapeWriter>,
+    sync: TapeSync,
+    /// Who is driving the session (e.g. a Telegram user ID). Used for audit
+    /// records; not persisted.
+    actor: Option<String>,
+    /// Agent turn in progress, stamped on appended entries.
+    turn: Option<String>,
 }
 
 impl TapeStore {
@@ -49,9 +112,30 @@ impl TapeStore {
             path,
             entries,
             next_id,
+            writer: None,
+            sync: TapeSync::default(),
+            actor: None,
+            turn: None,
         })
     }
 
+    /// Choose when appended entries are synced to disk.
+    pub fn set_sync(&mut self, sync: TapeSync) {
+        self.sync = sync;
+    }
+
+    /// Sync everything appended so far to disk.
+    pub fn sync(&mut self) -> std::io::Result<()> {
+        if let Some(writer) = self.writer.as_mut()
+            && writer.unsynced > 0
+        {
+            writer.file.sync_data()?;
+            writer.unsynced = 0;
+            writer.last_sync = Instant::now();
+        }
+        Ok(())
+    }
+
     /// Append an event entry.
     pub fn append_event(
         &mut self,
@@ -63,6 +147,7 @@ impl TapeStore {
             kind: kind.to_string(),
             payload,
             timestamp: Utc::now().to_rfc3339(),
+            turn: self.turn.clone(),
         };
         self.append_entry(entry)
     }
@@ -85,6 +170,47 @@ impl TapeStore {
         self.append_event("anchor", payload)
     }
 
+    /// Set who is driving the session.
+    pub fn set_actor(&mut self, actor: Option<String>) {
+        self.actor = actor;
+    }
+
+    /// Who is driving the session, if known.
+    pub fn actor(&self) -> Option<&str> {
+        self.actor.as_deref()
+    }
+
+    /// Set the agent turn in progress; `None` when it ends.
+    ///
+    /// A turn's entries are synced when it ends (unless syncing is off), so
+    /// a session that then goes quiet does not leave them waiting for the
+    /// next append.
+    pub fn set_turn(&mut self, turn: Option<String>) {
+        let ended = turn.is_none() && self.turn.is_some();
+        self.turn = turn;
+        if ended
+            && self.sync != TapeSync::Never
+            && let Err(e) = self.sync()
+        {
+            warn!(tape = %self.name, "tape.sync.error: {e}");
+        }
+    }
+
+    /// The agent turn in progress, if any.
+    pub fn turn(&self) -> Option<&str> {
+        self.turn.as_deref()
+    }
+
+    /// The session's scratchpad file (`scratch.*` tools), next to the tape.
+    pub fn scratch_path(&self) -> PathBuf {
+        self.path.with_extension("scratch.md")
+    }
+
+    /// The JSONL file backing this tape.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     /// Get all entries.
     pub fn entries(&self) -> &[TapeEntry] {
         &self.entries
@@ -146,10 +272,23 @@ impl TapeStore {
         }
     }
 
-    /// Reset the tape, optionally archiving the old data.
+    /// Reset the tape, optionally archiving the old data. An archived
+    /// tape's scratchpad is kept next to it as `<tape>.scratch.md.<stamp>.bak`.
     pub fn reset(&mut self, archive: bool) -> std::io::Result<Option<PathBuf>> {
+        self.writer = None;
+        let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+        let scratch = self.scratch_path();
+        if scratch.exists() {
+            if archive {
+                fs::rename(
+                    &scratch,
+                    self.path.with_extension(format!("scratch.md.{stamp}.bak")),
+                )?;
+            } else {
+                fs::remove_file(&scratch)?;
+            }
+        }
         let archive_path = if archive && self.path.exists() {
-            let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
             let archive = self.path.with_extension(format!("jsonl.{stamp}.bak"));
             fs::rename(&self.path, &archive)?;
             Some(archive)
@@ -179,14 +318,37 @@ impl TapeStore {
     }
 
     fn append_entry(&mut self, entry: TapeEntry) -> std::io::Result<&TapeEntry> {
-        let mut file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.path)?;
-
-        let line = serde_json::to_string(&entry)
+        let mut line = serde_json::to_string(&entry)
             .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
-        writeln!(file, "{line}")?;
+        line.push('\n');
+
+        let on_disk = fs::metadata(&self.path).map(|m| m.len()).ok();
+        if self.writer.as_ref().is_none_or(|w| Some(w.len) != on_disk) {
+            self.sync()?;
+            let file = open_for_append(&self.path)?;
+            self.writer = Some(TapeWriter {
+                len: file.metadata()?.len(),
+                file,
+                unsynced: 0,
+                last_sync: Instant::now(),
+            });
+        }
+        let writer = self.writer.as_mut().unwrap();
+        if let Err(e) = writer.file.write_all(line.as_bytes()) {
+            // Reopen (and repair) on the next append.
+            self.writer = None;
+            return Err(e);
+        }
+        writer.len += line.len() as u64;
+        writer.unsynced += 1;
+        let due = match self.sync {
+            TapeSync::Never => false,
+            TapeSync::Always => true,
+            TapeSync::Interval(interval) => writer.last_sync.elapsed() >= interval,
+        };
+        if due {
+            self.sync()?;
+        }
 
         self.next_id = entry.id + 1;
         self.entries.push(entry);
@@ -217,6 +379,62 @@ impl TapeStore {
     }
 }
 
+impl Drop for TapeStore {
+    fn drop(&mut self) {
+        if self.sync != TapeSync::Never
+            && let Err(e) = self.sync()
+        {
+            warn!(tape = %self.name, "tape.sync.error: {e}");
+        }
+    }
+}
+
+/// Open `path` for appending, first cutting off a last line that a crash
+/// left without its newline; appending after it would corrupt the next
+/// entry as well.
+fn open_for_append(path: &Path) -> std::io::Result<File> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+    let len = file.metadata()?.len();
+    if len == 0 {
+        return Ok(file);
+    }
+    let mut last = [0u8];
+    file.seek(SeekFrom::Start(len - 1))?;
+    file.read_exact(&mut last)?;
+    if last[0] == b'\n' {
+        return Ok(file);
+    }
+
+    // Find the end of the last complete line.
+    let mut keep = 0;
+    let mut offset = 0;
+    file.seek(SeekFrom::Start(0))?;
+    let mut reader = BufReader::new(&file);
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        offset += read as u64;
+        if buf.ends_with(b"\n") {
+            keep = offset;
+        }
+    }
+    warn!(
+        path = %path.display(),
+        dropped_bytes = len - keep,
+        "tape.truncated_tail.repaired"
+    );
+    file.set_len(keep)?;
+    Ok(file)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -293,12 +511,16 @@ mod tests {
         let mut tape = TapeStore::open(dir.path(), "archive").unwrap();
 
         tape.append_message("user", "hello").unwrap();
+        std::fs::write(tape.scratch_path(), "notes").unwrap();
 
-        let archive = tape.reset(true).unwrap();
-        assert!(archive.is_some());
-        assert!(archive.unwrap().exists());
+        let archive = tape.reset(true).unwrap().unwrap();
+        assert!(archive.exists());
         // Only bootstrap anchor after reset
         assert_eq!(tape.entries().len(), 1);
+        // The scratchpad is archived with the tape.
+        assert!(!tape.scratch_path().exists());
+        let scratch = archive.to_string_lossy().replace(".jsonl.", ".scratch.md.");
+        assert_eq!(std::fs::read_to_string(scratch).unwrap(), "notes");
     }
 
     #[test]
@@ -345,6 +567,7 @@ mod tests {
             kind: "message".to_string(),
             payload: serde_json::json!({"role": "user", "content": "hi"}),
             timestamp: "2026-01-01T00:00:00Z".to_string(),
+            turn: None,
         })
         .unwrap();
         let valid2 = serde_json::to_string(&TapeEntry {
@@ -352,6 +575,7 @@ mod tests {
             kind: "message".to_string(),
             payload: serde_json::json!({"role": "assistant", "content": "hello"}),
             timestamp: "2026-01-01T00:00:01Z".to_string(),
+            turn: None,
         })
         .unwrap();
         std::fs::write(&path, format!("{valid1}\nNOT_VALID_JSON\n{valid2}\n")).unwrap();
@@ -465,4 +689,81 @@ mod tests {
         let since = tape.entries_since_last_anchor();
         assert_eq!(since.len(), 1);
     }
+
+    #[test]
+    fn torn_last_line_is_cut_before_appending() {
+        let dir = tempdir().unwrap();
+        {
+            let mut tape = TapeStore::open(dir.path(), "torn").unwrap();
+            tape.append_message("user", "kept").unwrap();
+        }
+        let path = dir.path().join("torn.jsonl");
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"id":2,"kind":"mess"#).unwrap();
+
+        let mut tape = TapeStore::open(dir.path(), "torn").unwrap();
+        assert_eq!(tape.entries().len(), 1);
+        tape.append_message("user", "after the crash").unwrap();
+        drop(tape);
+
+        let tape = TapeStore::open(dir.path(), "torn").unwrap();
+        let contents: Vec<_> = tape
+            .entries()
+            .iter()
+            .map(|e| e.payload["content"].as_str().unwrap())
+            .collect();
+        assert_eq!(contents, vec!["kept", "after the crash"]);
+        assert_eq!(tape.entries()[1].id, 2);
+    }
+
+    #[test]
+    fn interval_sync_catches_up_when_the_turn_ends() {
+        let dir = tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "quiet").unwrap();
+        tape.set_sync(TapeSync::Interval(Duration::from_secs(3600)));
+        tape.append_message("user", "first").unwrap();
+        tape.set_turn(Some("turn".to_string()));
+        tape.append_message("assistant", "last words").unwrap();
+        assert_eq!(tape.writer.as_ref().unwrap().unsynced, 2);
+
+        tape.set_turn(None);
+        assert_eq!(tape.writer.as_ref().unwrap().unsynced, 0);
+
+        // Syncing stays off when it is off.
+        tape.set_sync(TapeSync::Never);
+        tape.set_turn(Some("next".to_string()));
+        tape.append_message("user", "unsynced").unwrap();
+        tape.set_turn(None);
+        assert_eq!(tape.writer.as_ref().unwrap().unsynced, 1);
+    }
+
+    #[test]
+    fn appends_follow_a_tape_replaced_on_disk() {
+        let dir = tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "shared").unwrap();
+        tape.set_sync(TapeSync::Always);
+        tape.append_message("user", "one").unwrap();
+
+        // Another process resets the session while this store is open.
+        TapeStore::open(dir.path(), "shared")
+            .unwrap()
+            .reset(false)
+            .unwrap();
+        tape.append_message("user", "two").unwrap();
+
+        let reopened = TapeStore::open(dir.path(), "shared").unwrap();
+        assert_eq!(reopened.entries().len(), 2);
+        assert_eq!(reopened.entries()[1].payload["content"], "two");
+    }
+
+    #[test]
+    fn sync_policy_parses() {
+        assert_eq!(TapeSync::parse("always"), Some(TapeSync::Always));
+        assert_eq!(TapeSync::parse(" OFF "), Some(TapeSync::Never));
+        assert_eq!(
+            TapeSync::parse("2"),
+            Some(TapeSync::Interval(Duration::from_secs(2)))
+        );
+        assert_eq!(TapeSync::parse("soon"), None);
+    }
 }
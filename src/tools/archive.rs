@@ -0,0 +1,445 @@
+//! `archive.list` and `archive.extract` for zip and tar(.gz) files in the
+//! workspace, typically documents uploaded through Telegram.
+//!
+//! Entry names come from untrusted files, so extraction only writes plain
+//! files and directories whose names are relative paths without `..`;
+//! absolute names, links and device entries are skipped. Existing files and
+//! symlinks are never overwritten, nothing is written through a symlink
+//! already in the destination, and the total size and file count are capped
+//! while decompressing rather than trusting the sizes an archive declares.
+
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+
+use crate::tools::file_ops::{format_size, resolve_safe_path};
+
+/// Entries listed by `archive.list` before the listing is cut off.
+const MAX_LIST_ENTRIES: usize = 500;
+/// Bytes written by one `archive.extract` call.
+pub const MAX_EXTRACT_BYTES: u64 = 100 * 1024 * 1024;
+/// Files written by one `archive.extract` call.
+pub const MAX_EXTRACT_FILES: usize = 5_000;
+/// Skipped entries named in the extraction summary.
+const MAX_REPORTED_SKIPS: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveKind {
+    /// Kind and name without the archive extension, from the file name.
+    fn detect(path: &Path) -> Option<(Self, String)> {
+        let name = path.file_name()?.to_string_lossy().to_string();
+        let lower = name.to_ascii_lowercase();
+        let (kind, ext) = [
+            (Self::TarGz, ".tar.gz"),
+            (Self::TarGz, ".tgz"),
+            (Self::Tar, ".tar"),
+            (Self::Zip, ".zip"),
+        ]
+        .into_iter()
+        .find(|(_, ext)| lower.ends_with(ext))?;
+        Some((kind, name[..name.len() - ext.len()].to_string()))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    File,
+    Dir,
+    /// Symlinks, hard links, devices and other special entries.
+    Other,
+}
+
+/// List the entries of a workspace archive with their sizes.
+pub fn list_archive(workspace: &Path, path: &str) -> String {
+    let (archive, kind, _) = match open_target(workspace, path) {
+        Ok(target) => target,
+        Err(e) => return e,
+    };
+    let mut lines = Vec::new();
+    let mut total = 0usize;
+    let mut bytes = 0u64;
+    let result = for_each_entry(&archive, kind, |name, entry_kind, size, _| {
+        total += 1;
+        bytes += size;
+        if lines.len() < MAX_LIST_ENTRIES {
+            lines.push(match entry_kind {
+                EntryKind::Dir => format!("{}/", name.trim_end_matches('/')),
+                EntryKind::File => format!("{name} ({})", format_size(size)),
+                EntryKind::Other => format!("{name} (link or special file)"),
+            });
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        return format!("Error reading archive {path}: {e}");
+    }
+    if lines.is_empty() {
+        return format!("Archive {path} is empty.");
+    }
+    let mut output = format!(
+        "{path}: {total} entr{}, {} uncompressed\n{}",
+        if total == 1 { "y" } else { "ies" },
+        format_size(bytes),
+        lines.join("\n")
+    );
+    if total > lines.len() {
+        output.push_str(&format!(
+            "\n[listing truncated: {} more entries]",
+            total - lines.len()
+        ));
+    }
+    output
+}
+
+/// Extract a workspace archive into `dest` (default: a directory named
+/// after the archive, next to it).
+pub fn extract_archive(workspace: &Path, path: &str, dest: &str) -> String {
+    let (archive, kind, stem) = match open_target(workspace, path) {
+        Ok(target) => target,
+        Err(e) => return e,
+    };
+    let dest_dir = if dest.trim().is_empty() {
+        archive.with_file_name(&stem)
+    } else {
+        match resolve_safe_path(workspace, dest) {
+            Some(p) => p,
+            None => return format!("Access denied: path escapes workspace: {dest}"),
+        }
+    };
+    if let Err(e) = std::fs::create_dir_all(&dest_dir) {
+        return format!("Error creating {}: {e}", dest_dir.display());
+    }
+    let dest_dir = dest_dir.canonicalize().unwrap_or(dest_dir);
+
+    let mut files = 0usize;
+    let mut written = 0u64;
+    let mut skipped: Vec<String> = Vec::new();
+    let result = for_each_entry(&archive, kind, |name, entry_kind, _, reader| {
+        let Some(relative) = safe_entry_path(name) else {
+            skipped.push(format!("{name} (unsafe path)"));
+            return Ok(());
+        };
+        let target = dest_dir.join(&relative);
+        match entry_kind {
+            EntryKind::Other => {
+                skipped.push(format!("{name} (link or special file)"));
+                Ok(())
+            }
+            EntryKind::Dir => {
+                if !stays_inside(&dest_dir, &target) {
+                    skipped.push(format!("{name} (unsafe path)"));
+                    return Ok(());
+                }
+                std::fs::create_dir_all(&target).map_err(|e| e.to_string())
+            }
+            EntryKind::File => {
+                // `symlink_metadata` so a dangling symlink counts as existing.
+                if target.symlink_metadata().is_ok() {
+                    skipped.push(format!("{name} (already exists)"));
+                    return Ok(());
+                }
+                if files >= MAX_EXTRACT_FILES {
+                    return Err(format!("archive has more than {MAX_EXTRACT_FILES} files"));
+                }
+                let parent = target.parent().unwrap_or(&dest_dir);
+                if !stays_inside(&dest_dir, parent) {
+                    skipped.push(format!("{name} (unsafe path)"));
+                    return Ok(());
+                }
+                std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                let remaining = MAX_EXTRACT_BYTES - written;
+                let mut out = std::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&target)
+                    .map_err(|e| format!("{name}: {e}"))?;
+                let copied = std::io::copy(&mut reader.take(remaining + 1), &mut out)
+                    .map_err(|e| format!("{name}: {e}"))?;
+                if copied > remaining {
+                    drop(out);
+                    let _ = std::fs::remove_file(&target);
+                    return Err(format!(
+                        "extracted data exceeds the {} limit",
+                        format_size(MAX_EXTRACT_BYTES)
+                    ));
+                }
+                files += 1;
+                written += copied;
+                Ok(())
+            }
+        }
+    });
+
+    let shown = dest_dir
+        .strip_prefix(
+            workspace
+                .canonicalize()
+                .unwrap_or_else(|_| workspace.to_path_buf()),
+        )
+        .unwrap_or(&dest_dir)
+        .display()
+        .to_string();
+    let mut output = match result {
+        Ok(()) => format!(
+            "Extracted {files} file(s) ({}) from {path} to {shown}/",
+            format_size(written)
+        ),
+        Err(e) => format!(
+            "Error: extraction of {path} stopped after {files} file(s) ({}) in {shown}/: {e}",
+            format_size(written)
+        ),
+    };
+    if !skipped.is_empty() {
+        output.push_str(&format!("\nSkipped {} entr", skipped.len()));
+        output.push_str(if skipped.len() == 1 { "y:" } else { "ies:" });
+        for skip in skipped.iter().take(MAX_REPORTED_SKIPS) {
+            output.push_str(&format!("\n- {skip}"));
+        }
+        if skipped.len() > MAX_REPORTED_SKIPS {
+            output.push_str(&format!(
+                "\n- ... {} more",
+                skipped.len() - MAX_REPORTED_SKIPS
+            ));
+        }
+    }
+    output
+}
+
+/// Resolve `path` to an existing archive file in the workspace.
+fn open_target(workspace: &Path, path: &str) -> Result<(PathBuf, ArchiveKind, String), String> {
+    let Some(archive) = resolve_safe_path(workspace, path) else {
+        return Err(format!("Access denied: path escapes workspace: {path}"));
+    };
+    if !archive.is_file() {
+        return Err(format!("File not found: {path}"));
+    }
+    let Some((kind, stem)) = ArchiveKind::detect(&archive) else {
+        return Err(format!(
+            "Error: unsupported archive {path} (expected .zip, .tar, .tar.gz or .tgz)"
+        ));
+    };
+    Ok((archive, kind, stem))
+}
+
+/// Call `f` with the name, kind, declared size and contents of every entry.
+fn for_each_entry<F>(archive: &Path, kind: ArchiveKind, mut f: F) -> Result<(), String>
+where
+    F: FnMut(&str, EntryKind, u64, &mut dyn Read) -> Result<(), String>,
+{
+    let file = BufReader::new(File::open(archive).map_err(|e| e.to_string())?);
+    match kind {
+        ArchiveKind::Zip => {
+            let mut zip = zip::ZipArchive::new(file).map_err(|e| e.to_string())?;
+            for i in 0..zip.len() {
+                let mut entry = zip.by_index(i).map_err(|e| e.to_string())?;
+                let name = entry.name().to_string();
+                let entry_kind = if entry.is_dir() {
+                    EntryKind::Dir
+                } else if entry.is_symlink() {
+                    EntryKind::Other
+                } else {
+                    EntryKind::File
+                };
+                let size = entry.size();
+                f(&name, entry_kind, size, &mut entry)?;
+            }
+        }
+        ArchiveKind::Tar => tar_entries(tar::Archive::new(file), f)?,
+        ArchiveKind::TarGz => {
+            tar_entries(tar::Archive::new(flate2::read::GzDecoder::new(file)), f)?
+        }
+    }
+    Ok(())
+}
+
+fn tar_entries<R: Read, F>(mut archive: tar::Archive<R>, mut f: F) -> Result<(), String>
+where
+    F: FnMut(&str, EntryKind, u64, &mut dyn Read) -> Result<(), String>,
+{
+    for entry in archive.entries().map_err(|e| e.to_string())? {
+        let mut entry = entry.map_err(|e| e.to_string())?;
+        let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+        let entry_type = entry.header().entry_type();
+        let entry_kind = if entry_type.is_dir() {
+            EntryKind::Dir
+        } else if entry_type.is_file() || entry_type.is_contiguous() {
+            EntryKind::File
+        } else {
+            EntryKind::Other
+        };
+        let size = entry.size();
+        f(&name, entry_kind, size, &mut entry)?;
+    }
+    Ok(())
+}
+
+/// Whether `path` resolves inside `dest_dir` once its deepest existing
+/// ancestor is canonicalized, so a symlinked directory already in the
+/// destination cannot lead outside. A dangling symlink fails the check.
+fn stays_inside(dest_dir: &Path, path: &Path) -> bool {
+    let mut existing = path;
+    while existing.symlink_metadata().is_err() {
+        match existing.parent() {
+            Some(parent) => existing = parent,
+            None => return false,
+        }
+    }
+    existing
+        .canonicalize()
+        .is_ok_and(|p| p.starts_with(dest_dir))
+}
+
+/// `name` as a relative path of plain components, or `None` if it is
+/// absolute or climbs out with `..`.
+fn safe_entry_path(name: &str) -> Option<PathBuf> {
+    let name = name.replace('\\', "/");
+    let mut path = PathBuf::new();
+    for component in Path::new(&name).components() {
+        match component {
+            Component::Normal(part) => path.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return None;
+    }
+    Some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, data) in files {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    fn write_tar_gz(path: &Path, files: &[(&str, &[u8])]) {
+        let gz = flate2::write::GzEncoder::new(
+            File::create(path).unwrap(),
+            flate2::Compression::default(),
+        );
+        let mut tar = tar::Builder::new(gz);
+        for (name, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            // `append_data` rejects `..`, so write the raw name like a
+            // hostile archive would.
+            header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            header.set_cksum();
+            tar.append(&header, *data).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap();
+    }
+
+    #[test]
+    fn zip_entries_are_listed_and_extracted() {
+        let dir = tempdir().unwrap();
+        write_zip(
+            &dir.path().join("report.zip"),
+            &[("docs/a.txt", b"alpha"), ("b.txt", b"bravo")],
+        );
+
+        let listing = list_archive(dir.path(), "report.zip");
+        assert!(listing.starts_with("report.zip: 2 entries"), "{listing}");
+        assert!(listing.contains("docs/a.txt (5 B)"));
+
+        let output = extract_archive(dir.path(), "report.zip", "");
+        assert!(output.starts_with("Extracted 2 file(s)"), "{output}");
+        let extracted = dir.path().join("report");
+        assert_eq!(
+            std::fs::read_to_string(extracted.join("docs/a.txt")).unwrap(),
+            "alpha"
+        );
+
+        // A second run leaves the existing files alone.
+        let output = extract_archive(dir.path(), "report.zip", "report");
+        assert!(output.contains("Extracted 0 file(s)"), "{output}");
+        assert!(output.contains("b.txt (already exists)"));
+    }
+
+    #[test]
+    fn traversal_entries_are_skipped() {
+        let dir = tempdir().unwrap();
+        let ws = dir.path().join("ws");
+        std::fs::create_dir(&ws).unwrap();
+        write_tar_gz(
+            &ws.join("evil.tar.gz"),
+            &[("../escape.txt", b"owned"), ("ok.txt", b"fine")],
+        );
+
+        let output = extract_archive(&ws, "evil.tar.gz", "out");
+        assert!(output.starts_with("Extracted 1 file(s)"), "{output}");
+        assert!(output.contains("../escape.txt (unsafe path)"));
+        assert!(!dir.path().join("escape.txt").exists());
+        assert!(ws.join("out/ok.txt").exists());
+
+        let output = extract_archive(&ws, "evil.tar.gz", "../elsewhere");
+        assert!(output.starts_with("Access denied"), "{output}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_in_the_destination_are_not_followed() {
+        let dir = tempdir().unwrap();
+        let ws = dir.path().join("ws");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(ws.join("out")).unwrap();
+        std::fs::create_dir(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, ws.join("out/link")).unwrap();
+        std::os::unix::fs::symlink(outside.join("planted.txt"), ws.join("out/dangling.txt"))
+            .unwrap();
+
+        let mut zip = zip::ZipWriter::new(File::create(ws.join("evil.zip")).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.add_directory("link/sub/", options).unwrap();
+        zip.start_file("dangling.txt", options).unwrap();
+        zip.write_all(b"owned").unwrap();
+        zip.start_file("link/deeper/x.txt", options).unwrap();
+        zip.write_all(b"owned").unwrap();
+        zip.finish().unwrap();
+
+        let output = extract_archive(&ws, "evil.zip", "out");
+        assert!(output.starts_with("Extracted 0 file(s)"), "{output}");
+        assert!(output.contains("link/sub/ (unsafe path)"), "{output}");
+        assert!(output.contains("dangling.txt (already exists)"), "{output}");
+        assert!(
+            output.contains("link/deeper/x.txt (unsafe path)"),
+            "{output}"
+        );
+        assert_eq!(std::fs::read_dir(&outside).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn entry_paths_must_stay_relative() {
+        assert_eq!(safe_entry_path("a/./b.txt"), Some(PathBuf::from("a/b.txt")));
+        assert!(safe_entry_path("/etc/passwd").is_none());
+        assert!(safe_entry_path("a/../../b").is_none());
+        assert!(safe_entry_path("..\\win.ini").is_none());
+        assert!(safe_entry_path("./").is_none());
+    }
+
+    #[test]
+    fn unsupported_files_are_rejected() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hi").unwrap();
+        assert!(list_archive(dir.path(), "notes.txt").contains("unsupported archive"));
+        assert!(list_archive(dir.path(), "missing.zip").starts_with("File not found"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Helpers for binary and non-UTF-8 files read by `file.read`.
+
+/// Bytes inspected to decide whether a file is binary.
+pub const SNIFF_BYTES: usize = 8_000;
+
+/// Default and maximum number of bytes in a `file.read` hexdump.
+pub const DEFAULT_HEXDUMP_BYTES: usize = 256;
+pub const MAX_HEXDUMP_BYTES: usize = 4_096;
+
+/// Whether `head` (the start of a file) looks binary. Like git, a NUL byte
+/// in the first few kilobytes means binary; legacy text encodings such as
+/// GBK or Latin-1 never contain one.
+pub fn looks_binary(head: &[u8]) -> bool {
+    head[..head.len().min(SNIFF_BYTES)].contains(&0)
+}
+
+/// MIME type from the file's magic bytes, falling back to its extension.
+pub fn guess_mime(head: &[u8], extension: &str) -> &'static str {
+    const MAGIC: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"BZh", "application/x-bzip2"),
+        (b"\xfd7zXZ\x00", "application/x-xz"),
+        (b"7z\xbc\xaf\x27\x1c", "application/x-7z-compressed"),
+        (b"\x7fELF", "application/x-elf"),
+        (b"MZ", "application/vnd.microsoft.portable-executable"),
+        (b"\x00asm", "application/wasm"),
+        (b"SQLite format 3\x00", "application/vnd.sqlite3"),
+        (b"OggS", "audio/ogg"),
+        (b"ID3", "audio/mpeg"),
+        (b"fLaC", "audio/flac"),
+    ];
+    if let Some((_, mime)) = MAGIC.iter().find(|(magic, _)| head.starts_with(magic)) {
+        return mime;
+    }
+    if head.len() >= 12 && &head[..4] == b"RIFF" {
+        match &head[8..12] {
+            b"WEBP" => return "image/webp",
+            b"WAVE" => return "audio/wav",
+            _ => {}
+        }
+    }
+    if head.len() >= 12 && &head[4..8] == b"ftyp" {
+        return "video/mp4";
+    }
+    match extension.to_ascii_lowercase().as_str() {
+        "txt" | "md" | "log" => "text/plain",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "tar" => "application/x-tar",
+        "mp3" => "audio/mpeg",
+        "ico" => "image/x-icon",
+        "ttf" => "font/ttf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ if looks_binary(head) => "application/octet-stream",
+        _ => "text/plain",
+    }
+}
+
+/// Classic `hexdump -C` layout of `bytes`, with offsets starting at `base`.
+pub fn hexdump(bytes: &[u8], base: u64) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut hex = String::with_capacity(50);
+            for (j, byte) in chunk.iter().enumerate() {
+                if j == 8 {
+                    hex.push(' ');
+                }
+                hex.push_str(&format!("{byte:02x} "));
+            }
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}  {hex:<49} |{ascii}|", base + (i * 16) as u64)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Decode `bytes` with the encoding named `label` (`gbk`, `latin1`,
+/// `shift_jis`, ... as understood by browsers).
+pub fn decode(bytes: &[u8], label: &str) -> Result<String, String> {
+    let encoding = encoding_rs::Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| format!("unknown encoding '{label}'"))?;
+    let (text, _, had_errors) = encoding.decode(bytes);
+    let text = text.into_owned();
+    if had_errors {
+        Ok(format!(
+            "[some bytes are not valid {}; they are shown as \u{fffd}]\n{text}",
+            encoding.name()
+        ))
+    } else {
+        Ok(text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn magic_bytes_win_over_extensions() {
+        assert_eq!(guess_mime(b"\x89PNG\r\n\x1a\n....", "txt"), "image/png");
+        assert_eq!(guess_mime(b"RIFF\0\0\0\0WEBPVP8 ", ""), "image/webp");
+        assert_eq!(guess_mime(b"a,b\n1,2\n", "csv"), "text/csv");
+        assert_eq!(guess_mime(b"\0\x01\x02", "bin"), "application/octet-stream");
+    }
+
+    #[test]
+    fn hexdump_matches_hexdump_c() {
+        let dump = hexdump(b"Hello, world!\n\x00\x01\xff", 16);
+        let lines: Vec<&str> = dump.lines().collect();
+        assert_eq!(
+            lines[0],
+            "00000010  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 01  |Hello, world!...|"
+        );
+        assert_eq!(lines[1], format!("00000020  {:<49} |.|", "ff "));
+    }
+
+    #[test]
+    fn legacy_encodings_are_decoded() {
+        assert_eq!(decode(b"caf\xe9", "latin1").unwrap(), "café");
+        assert_eq!(decode(b"\xc4\xe3\xba\xc3", "gbk").unwrap(), "你好");
+        assert!(decode(b"x", "klingon").is_err());
+    }
+}
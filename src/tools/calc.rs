@@ -0,0 +1,1232 @@
+//! `calc`: exact arithmetic, unit conversion and date math for the model.
+//!
+//! Three kinds of expression are understood:
+//!
+//! - arithmetic such as `1249.99 * 12 * 1.0825` or `15% of 80`. `+ - * /`,
+//!   `%`, `^` and `!` use exact decimals, so `0.1 + 0.2` is `0.3`.
+//!   Division that does not terminate is rounded to `precision` decimal
+//!   places. `sqrt`, `ln`, `sin` and the like fall back to floating point
+//!   and are marked `≈`;
+//! - unit conversion, `<expr> <unit> to <unit>` (`5.2 km to mi`,
+//!   `72 F to C`, `3 GiB in MB`), with exact conversion factors;
+//! - date math on `YYYY-MM-DD[ HH:MM]`, `today`, `now`, `tomorrow` and
+//!   `yesterday`: `2026-03-01 + 45 days`, `2026-12-25 - today`,
+//!   `now + 1h30m`. Months and years are calendar months.
+
+use std::cmp::Ordering;
+
+use bigdecimal::num_bigint::BigInt;
+use bigdecimal::{BigDecimal, One, RoundingMode, Signed, ToPrimitive, Zero};
+use chrono::{Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime};
+
+/// Decimal places kept by non-terminating divisions by default.
+pub const DEFAULT_PRECISION: u32 = 20;
+/// Largest accepted `precision`.
+const MAX_PRECISION: u32 = 200;
+/// Digits a single value may have before or after the point (guards
+/// `9^9^9` and `0.1^5000000000`).
+const MAX_DIGITS: usize = 2_000;
+/// Largest argument of `!`.
+const MAX_FACTORIAL: u64 = 500;
+/// Longest accepted expression, in characters.
+const MAX_EXPRESSION_CHARS: usize = 2_000;
+/// Deepest nesting of parentheses, function calls, signs and exponents the
+/// recursive parser follows before giving up (guards the stack against
+/// `((((...` and `----...`).
+const MAX_DEPTH: usize = 64;
+
+/// Evaluate `expression` and describe the result.
+pub fn evaluate(expression: &str, precision: u32) -> String {
+    let expression = expression.trim();
+    if expression.is_empty() {
+        return "Error: 'expression' argument is required.".to_string();
+    }
+    if expression.chars().count() > MAX_EXPRESSION_CHARS {
+        return format!("Error: expression is longer than {MAX_EXPRESSION_CHARS} characters");
+    }
+    let precision = precision.min(MAX_PRECISION);
+    let result = if is_date_expression(expression) {
+        date_math(expression, Local::now().naive_local())
+    } else if let Some(conversion) = convert_units(expression, precision) {
+        conversion
+    } else {
+        let mut calc = Calculator::new(expression, precision);
+        calc.run().map(|value| calc.describe(value))
+    };
+    match result {
+        Ok(result) => format!("{expression} {result}"),
+        Err(e) => format!("Error: {e}"),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Exact decimals
+
+/// Parse `123`, `-0.5`, `1_000` or `2.5e3`.
+fn parse_decimal(text: &str) -> Option<BigDecimal> {
+    let text = text.replace('_', "");
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((m, e)) => (m, e.parse::<i64>().ok()?),
+        None => (text.as_str(), 0),
+    };
+    if exponent.unsigned_abs() > MAX_DIGITS as u64 {
+        return None;
+    }
+    let unsigned = mantissa.strip_prefix('-').unwrap_or(mantissa);
+    let (int, frac) = unsigned.split_once('.').unwrap_or((unsigned, ""));
+    if (int.is_empty() && frac.is_empty())
+        || !int.chars().chain(frac.chars()).all(|c| c.is_ascii_digit())
+    {
+        return None;
+    }
+    let digits = BigInt::parse_bytes(format!("{int}{frac}").as_bytes(), 10)?;
+    let digits = if mantissa.starts_with('-') {
+        -digits
+    } else {
+        digits
+    };
+    Some(BigDecimal::new(digits, frac.len() as i64 - exponent).normalized())
+}
+
+fn pow10(n: u64) -> BigInt {
+    BigInt::from(10u32).pow(n as u32)
+}
+
+/// `a / b` rounded half away from zero to `places` decimals, and whether
+/// rounding happened.
+fn div(a: &BigDecimal, b: &BigDecimal, places: u32) -> Result<(BigDecimal, bool), String> {
+    if b.is_zero() {
+        return Err("division by zero".to_string());
+    }
+    // (x / 10^sa) / (y / 10^sb) = x * 10^(places + sb - sa) / y / 10^places
+    let (x, sa) = a.as_bigint_and_exponent();
+    let (y, sb) = b.as_bigint_and_exponent();
+    let exponent = i64::from(places) + sb - sa;
+    let (numerator, denominator) = if exponent >= 0 {
+        (x * pow10(exponent as u64), y)
+    } else {
+        (x, y * pow10(exponent.unsigned_abs()))
+    };
+    let mut quotient = &numerator / &denominator;
+    let remainder = &numerator % &denominator;
+    if remainder.abs() * 2 >= denominator.abs() {
+        quotient += if numerator.is_negative() != denominator.is_negative() {
+            -1
+        } else {
+            1
+        };
+    }
+    let value = BigDecimal::new(quotient, i64::from(places)).normalized();
+    Ok((value, !remainder.is_zero()))
+}
+
+/// Remainder with the sign of `a`, like `%` on integers.
+fn rem(a: &BigDecimal, b: &BigDecimal) -> Result<BigDecimal, String> {
+    if b.is_zero() {
+        return Err("division by zero".to_string());
+    }
+    let (x, sa) = a.as_bigint_and_exponent();
+    let (y, sb) = b.as_bigint_and_exponent();
+    let scale = sa.max(sb);
+    let x = x * pow10((scale - sa) as u64);
+    let y = y * pow10((scale - sb) as u64);
+    Ok(BigDecimal::new(x % y, scale).normalized())
+}
+
+fn pow(base: &BigDecimal, exponent: i64, places: u32) -> Result<(BigDecimal, bool), String> {
+    let mut result = BigDecimal::one();
+    let mut base = base.clone();
+    let mut n = exponent.unsigned_abs();
+    while n > 0 {
+        if n & 1 == 1 {
+            result = (&result * &base).normalized();
+        }
+        n >>= 1;
+        if n > 0 {
+            base = (&base * &base).normalized();
+        }
+        check_size(&result)?;
+        check_size(&base)?;
+    }
+    if exponent < 0 {
+        div(&BigDecimal::one(), &result, places)
+    } else {
+        Ok((result, false))
+    }
+}
+
+/// Refuse values with more than [`MAX_DIGITS`] digits before or after the
+/// decimal point.
+fn check_size(value: &BigDecimal) -> Result<(), String> {
+    let (_, scale) = value.as_bigint_and_exponent();
+    if value.digits() as i64 - scale > MAX_DIGITS as i64 {
+        Err("result is too large".to_string())
+    } else if scale > MAX_DIGITS as i64 {
+        Err("result has too many decimal places".to_string())
+    } else {
+        Ok(())
+    }
+}
+
+fn to_i64(value: &BigDecimal) -> Option<i64> {
+    value.is_integer().then(|| value.to_i64()).flatten()
+}
+
+/// `value` without trailing zeros or scientific notation.
+fn plain(value: &BigDecimal) -> String {
+    value.normalized().to_plain_string()
+}
+
+// ---------------------------------------------------------------------------
+// Arithmetic
+
+#[derive(Debug, Clone)]
+enum Value {
+    Exact(BigDecimal),
+    Approx(f64),
+}
+
+impl Value {
+    fn to_f64(&self) -> f64 {
+        match self {
+            Value::Exact(d) => d.to_f64().unwrap_or(f64::NAN),
+            Value::Approx(x) => *x,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Num(String),
+    Ident(String),
+    Op(char),
+    Open,
+    Close,
+    Comma,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit()
+            || (c == '.' && chars.get(i + 1).is_some_and(char::is_ascii_digit))
+        {
+            let start = i;
+            while i < chars.len()
+                && (chars[i].is_ascii_digit() || chars[i] == '.' || chars[i] == '_')
+            {
+                i += 1;
+            }
+            // Exponent: `2.5e3`, `1e-6`.
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '-' || chars[j] == '+') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push(Token::Num(
+                chars[start..i]
+                    .iter()
+                    .collect::<String>()
+                    .replace("e+", "e"),
+            ));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(
+                chars[start..i].iter().collect::<String>().to_lowercase(),
+            ));
+        } else {
+            i += 1;
+            tokens.push(match c {
+                '(' => Token::Open,
+                ')' => Token::Close,
+                ',' => Token::Comma,
+                '*' if chars.get(i) == Some(&'*') => {
+                    i += 1;
+                    Token::Op('^')
+                }
+                '×' => Token::Op('*'),
+                '÷' => Token::Op('/'),
+                '−' => Token::Op('-'),
+                '+' | '-' | '*' | '/' | '%' | '^' | '!' => Token::Op(c),
+                _ => return Err(format!("unexpected character '{c}'")),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+struct Calculator {
+    input: String,
+    tokens: Vec<Token>,
+    pos: usize,
+    precision: u32,
+    /// A division was rounded.
+    rounded: bool,
+    /// Nesting level of the [`Calculator::unary`] calls in progress.
+    depth: usize,
+}
+
+impl Calculator {
+    fn new(input: &str, precision: u32) -> Self {
+        Self {
+            input: input.to_string(),
+            tokens: Vec::new(),
+            pos: 0,
+            precision,
+            rounded: false,
+            depth: 0,
+        }
+    }
+
+    fn run(&mut self) -> Result<Value, String> {
+        self.tokens = tokenize(&self.input)?;
+        self.pos = 0;
+        let value = self.expr()?;
+        match self.tokens.get(self.pos) {
+            None => Ok(value),
+            Some(token) => Err(format!(
+                "unexpected {} in expression",
+                describe_token(token)
+            )),
+        }
+    }
+
+    fn describe(&self, value: Value) -> String {
+        match value {
+            Value::Exact(d) if self.rounded => {
+                format!(
+                    "≈ {} (rounded to {} decimal places)",
+                    plain(&d),
+                    self.precision
+                )
+            }
+            Value::Exact(d) => format!("= {}", plain(&d)),
+            Value::Approx(x) => format!("≈ {}", format_float(x)),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn expr(&mut self) -> Result<Value, String> {
+        let mut value = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = self.binary(op, value, rhs)?;
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<Value, String> {
+        let mut value = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(op @ ('*' | '/' | '%'))) => *op,
+                Some(Token::Ident(word)) if word == "of" => '*',
+                _ => break,
+            };
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = self.binary(op, value, rhs)?;
+        }
+        Ok(value)
+    }
+
+    /// Every recursion of the parser passes through here, so this is where
+    /// the nesting depth is counted.
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.depth >= MAX_DEPTH {
+            return Err("expression nested too deeply".to_string());
+        }
+        self.depth += 1;
+        let value = self.signed();
+        self.depth -= 1;
+        value
+    }
+
+    fn signed(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                let value = self.unary()?;
+                Ok(match value {
+                    Value::Exact(d) => Value::Exact(-d),
+                    Value::Approx(x) => Value::Approx(-x),
+                })
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<Value, String> {
+        let base = self.postfix()?;
+        if self.peek() == Some(&Token::Op('^')) {
+            self.pos += 1;
+            let exponent = self.unary()?;
+            return self.binary('^', base, exponent);
+        }
+        Ok(base)
+    }
+
+    fn postfix(&mut self) -> Result<Value, String> {
+        let mut value = self.primary()?;
+        loop {
+            match self.peek() {
+                Some(Token::Op('!')) => {
+                    self.pos += 1;
+                    value = factorial(&value)?;
+                }
+                // `15%` is a percentage unless an operand follows (`7 % 3`).
+                Some(Token::Op('%'))
+                    if !matches!(
+                        self.tokens.get(self.pos + 1),
+                        Some(Token::Num(_) | Token::Open)
+                    ) && !matches!(self.tokens.get(self.pos + 1), Some(Token::Ident(w)) if w != "of") =>
+                {
+                    self.pos += 1;
+                    value = self.binary('/', value, Value::Exact(BigDecimal::from(100)))?;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    fn primary(&mut self) -> Result<Value, String> {
+        let token = self
+            .peek()
+            .cloned()
+            .ok_or_else(|| "expression ends unexpectedly".to_string())?;
+        self.pos += 1;
+        match token {
+            Token::Num(text) => parse_decimal(&text)
+                .map(Value::Exact)
+                .ok_or_else(|| format!("invalid number '{text}'")),
+            Token::Open => {
+                let value = self.expr()?;
+                self.expect_close()?;
+                Ok(value)
+            }
+            Token::Ident(name) if self.peek() == Some(&Token::Open) => {
+                self.pos += 1;
+                let mut args = Vec::new();
+                if self.peek() != Some(&Token::Close) {
+                    args.push(self.expr()?);
+                    while self.peek() == Some(&Token::Comma) {
+                        self.pos += 1;
+                        args.push(self.expr()?);
+                    }
+                }
+                self.expect_close()?;
+                self.function(&name, args)
+            }
+            Token::Ident(name) => match name.as_str() {
+                "pi" | "π" => Ok(Value::Approx(std::f64::consts::PI)),
+                "e" => Ok(Value::Approx(std::f64::consts::E)),
+                _ => Err(format!("unknown name '{name}'")),
+            },
+            other => Err(format!("unexpected {}", describe_token(&other))),
+        }
+    }
+
+    fn expect_close(&mut self) -> Result<(), String> {
+        if self.peek() == Some(&Token::Close) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err("missing ')'".to_string())
+        }
+    }
+
+    fn binary(&mut self, op: char, lhs: Value, rhs: Value) -> Result<Value, String> {
+        if let (Value::Exact(a), Value::Exact(b)) = (&lhs, &rhs) {
+            let exact = match op {
+                '+' => Some(a + b),
+                '-' => Some(a - b),
+                '*' => Some(a * b),
+                '/' => {
+                    let (q, rounded) = div(a, b, self.precision)?;
+                    self.rounded |= rounded;
+                    Some(q)
+                }
+                '%' => Some(rem(a, b)?),
+                '^' => match to_i64(b) {
+                    Some(n) => {
+                        let (p, rounded) = pow(a, n, self.precision)?;
+                        self.rounded |= rounded;
+                        Some(p)
+                    }
+                    None => None,
+                },
+                _ => None,
+            };
+            if let Some(value) = exact {
+                let value = value.normalized();
+                check_size(&value)?;
+                return Ok(Value::Exact(value));
+            }
+        }
+        let (a, b) = (lhs.to_f64(), rhs.to_f64());
+        let result = match op {
+            '+' => a + b,
+            '-' => a - b,
+            '*' => a * b,
+            '/' if b == 0.0 => return Err("division by zero".to_string()),
+            '/' => a / b,
+            '%' => a % b,
+            _ => a.powf(b),
+        };
+        approx(result)
+    }
+
+    fn function(&mut self, name: &str, args: Vec<Value>) -> Result<Value, String> {
+        let arity = |n: usize| {
+            if args.len() == n {
+                Ok(())
+            } else {
+                Err(format!("{name}() takes {n} argument(s)"))
+            }
+        };
+        match name {
+            "abs" => {
+                arity(1)?;
+                Ok(match &args[0] {
+                    Value::Exact(d) => Value::Exact(d.abs()),
+                    Value::Approx(x) => Value::Approx(x.abs()),
+                })
+            }
+            "round" | "floor" | "ceil" => {
+                let places = match args.get(1) {
+                    Some(Value::Exact(d)) if name == "round" => to_i64(d)
+                        .filter(|n| (0..=i64::from(MAX_PRECISION)).contains(n))
+                        .ok_or("round() places must be a whole number from 0 to 200")?
+                        as u32,
+                    None => 0,
+                    Some(_) => return Err(format!("{name}() takes 1 argument")),
+                };
+                match &args.first() {
+                    Some(Value::Exact(d)) => {
+                        let (scale, mode) = match name {
+                            "round" => (i64::from(places), RoundingMode::HalfUp),
+                            "floor" => (0, RoundingMode::Floor),
+                            _ => (0, RoundingMode::Ceiling),
+                        };
+                        Ok(Value::Exact(d.with_scale_round(scale, mode).normalized()))
+                    }
+                    Some(Value::Approx(x)) => {
+                        let factor = 10f64.powi(places as i32);
+                        approx(match name {
+                            "round" => (x * factor).round() / factor,
+                            "floor" => x.floor(),
+                            _ => x.ceil(),
+                        })
+                    }
+                    None => Err(format!("{name}() needs an argument")),
+                }
+            }
+            "min" | "max" => {
+                if args.is_empty() {
+                    return Err(format!("{name}() needs at least one argument"));
+                }
+                let pick = |a: Value, b: Value| -> Value {
+                    let ordering = match (&a, &b) {
+                        (Value::Exact(x), Value::Exact(y)) => x.cmp(y),
+                        _ => a
+                            .to_f64()
+                            .partial_cmp(&b.to_f64())
+                            .unwrap_or(Ordering::Equal),
+                    };
+                    let keep_a = if name == "min" {
+                        ordering != Ordering::Greater
+                    } else {
+                        ordering != Ordering::Less
+                    };
+                    if keep_a { a } else { b }
+                };
+                Ok(args.into_iter().reduce(pick).expect("non-empty"))
+            }
+            _ => {
+                arity(1)?;
+                let x = args[0].to_f64();
+                let result = match name {
+                    "sqrt" if x < 0.0 => return Err("sqrt() of a negative number".to_string()),
+                    "sqrt" => x.sqrt(),
+                    "cbrt" => x.cbrt(),
+                    "ln" if x <= 0.0 => return Err("ln() needs a positive number".to_string()),
+                    "ln" => x.ln(),
+                    "log" | "log10" if x <= 0.0 => {
+                        return Err(format!("{name}() needs a positive number"));
+                    }
+                    "log" | "log10" => x.log10(),
+                    "log2" => x.log2(),
+                    "exp" => x.exp(),
+                    "sin" => x.sin(),
+                    "cos" => x.cos(),
+                    "tan" => x.tan(),
+                    "asin" => x.asin(),
+                    "acos" => x.acos(),
+                    "atan" => x.atan(),
+                    _ => return Err(format!("unknown function '{name}'")),
+                };
+                approx(result)
+            }
+        }
+    }
+}
+
+fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Num(n) => format!("number '{n}'"),
+        Token::Ident(name) => format!("'{name}'"),
+        Token::Op(op) => format!("'{op}'"),
+        Token::Open => "'('".to_string(),
+        Token::Close => "')'".to_string(),
+        Token::Comma => "','".to_string(),
+    }
+}
+
+fn approx(x: f64) -> Result<Value, String> {
+    if x.is_finite() {
+        Ok(Value::Approx(x))
+    } else {
+        Err("result is not a finite number".to_string())
+    }
+}
+
+fn factorial(value: &Value) -> Result<Value, String> {
+    let n = match value {
+        Value::Exact(d) => to_i64(d).filter(|n| *n >= 0),
+        Value::Approx(_) => None,
+    }
+    .ok_or("factorial needs a non-negative whole number")?;
+    if n as u64 > MAX_FACTORIAL {
+        return Err(format!("factorial is limited to {MAX_FACTORIAL}!"));
+    }
+    let mut result = BigDecimal::one();
+    for i in 2..=n as u64 {
+        result *= BigDecimal::from(i);
+    }
+    Ok(Value::Exact(result))
+}
+
+/// Up to 12 significant decimals without float noise.
+fn format_float(x: f64) -> String {
+    if x != 0.0 && (x.abs() >= 1e15 || x.abs() < 1e-6) {
+        return format!("{x:e}");
+    }
+    let text = format!("{x:.12}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0" } else { text }.to_string()
+}
+
+// ---------------------------------------------------------------------------
+// Units
+
+/// A unit: names, dimension, and its size in the dimension's base unit as
+/// an exact fraction.
+struct Unit {
+    names: &'static [&'static str],
+    dimension: &'static str,
+    numerator: &'static str,
+    denominator: &'static str,
+}
+
+const fn unit(
+    names: &'static [&'static str],
+    dimension: &'static str,
+    numerator: &'static str,
+    denominator: &'static str,
+) -> Unit {
+    Unit {
+        names,
+        dimension,
+        numerator,
+        denominator,
+    }
+}
+
+const UNITS: &[Unit] = &[
+    // Length, in metres.
+    unit(
+        &["m", "meter", "meters", "metre", "metres"],
+        "length",
+        "1",
+        "1",
+    ),
+    unit(
+        &["km", "kilometer", "kilometers", "kilometre", "kilometres"],
+        "length",
+        "1000",
+        "1",
+    ),
+    unit(&["cm", "centimeter", "centimeters"], "length", "0.01", "1"),
+    unit(&["mm", "millimeter", "millimeters"], "length", "0.001", "1"),
+    unit(
+        &["um", "µm", "micrometer", "micron"],
+        "length",
+        "0.000001",
+        "1",
+    ),
+    unit(
+        &["nm", "nanometer", "nanometers"],
+        "length",
+        "0.000000001",
+        "1",
+    ),
+    unit(&["mi", "mile", "miles"], "length", "1609.344", "1"),
+    unit(&["yd", "yard", "yards"], "length", "0.9144", "1"),
+    unit(&["ft", "foot", "feet"], "length", "0.3048", "1"),
+    unit(&["in", "inch", "inches"], "length", "0.0254", "1"),
+    unit(&["nmi", "nautical_mile"], "length", "1852", "1"),
+    // Mass, in kilograms.
+    unit(
+        &["kg", "kilogram", "kilograms", "kilo", "kilos"],
+        "mass",
+        "1",
+        "1",
+    ),
+    unit(&["g", "gram", "grams"], "mass", "0.001", "1"),
+    unit(&["mg", "milligram", "milligrams"], "mass", "0.000001", "1"),
+    unit(&["t", "tonne", "tonnes"], "mass", "1000", "1"),
+    unit(&["lb", "lbs", "pound", "pounds"], "mass", "0.45359237", "1"),
+    unit(&["oz", "ounce", "ounces"], "mass", "0.028349523125", "1"),
+    unit(&["st", "stone"], "mass", "6.35029318", "1"),
+    // Time, in seconds.
+    unit(&["s", "sec", "secs", "second", "seconds"], "time", "1", "1"),
+    unit(&["ms", "millisecond", "milliseconds"], "time", "0.001", "1"),
+    unit(
+        &["us", "µs", "microsecond", "microseconds"],
+        "time",
+        "0.000001",
+        "1",
+    ),
+    unit(&["min", "mins", "minute", "minutes"], "time", "60", "1"),
+    unit(&["h", "hr", "hrs", "hour", "hours"], "time", "3600", "1"),
+    unit(&["d", "day", "days"], "time", "86400", "1"),
+    unit(&["wk", "week", "weeks"], "time", "604800", "1"),
+    // Volume, in litres.
+    unit(
+        &["l", "L", "liter", "liters", "litre", "litres"],
+        "volume",
+        "1",
+        "1",
+    ),
+    unit(
+        &["ml", "mL", "milliliter", "milliliters"],
+        "volume",
+        "0.001",
+        "1",
+    ),
+    unit(&["cl", "cL"], "volume", "0.01", "1"),
+    unit(&["dl", "dL"], "volume", "0.1", "1"),
+    unit(&["m3", "m³"], "volume", "1000", "1"),
+    unit(&["cm3", "cm³", "cc"], "volume", "0.001", "1"),
+    unit(&["gal", "gallon", "gallons"], "volume", "3.785411784", "1"),
+    unit(&["impgal", "imperial_gallon"], "volume", "4.54609", "1"),
+    unit(&["qt", "quart", "quarts"], "volume", "0.946352946", "1"),
+    unit(&["pt", "pint", "pints"], "volume", "0.473176473", "1"),
+    unit(&["cup", "cups"], "volume", "0.2365882365", "1"),
+    unit(&["floz", "fl_oz"], "volume", "0.0295735295625", "1"),
+    unit(
+        &["tbsp", "tablespoon", "tablespoons"],
+        "volume",
+        "0.01478676478125",
+        "1",
+    ),
+    unit(
+        &["tsp", "teaspoon", "teaspoons"],
+        "volume",
+        "0.00492892159375",
+        "1",
+    ),
+    // Area, in square metres.
+    unit(&["m2", "m²", "sqm"], "area", "1", "1"),
+    unit(&["km2", "km²"], "area", "1000000", "1"),
+    unit(&["cm2", "cm²"], "area", "0.0001", "1"),
+    unit(&["ha", "hectare", "hectares"], "area", "10000", "1"),
+    unit(&["acre", "acres"], "area", "4046.8564224", "1"),
+    unit(&["ft2", "ft²", "sqft"], "area", "0.09290304", "1"),
+    unit(&["in2", "in²"], "area", "0.00064516", "1"),
+    unit(&["mi2", "mi²"], "area", "2589988.110336", "1"),
+    // Speed, in metres per second.
+    unit(&["m/s", "mps"], "speed", "1", "1"),
+    unit(&["km/h", "kmh", "kph"], "speed", "1000", "3600"),
+    unit(&["mph", "mi/h"], "speed", "1609.344", "3600"),
+    unit(&["kn", "kt", "knot", "knots"], "speed", "1852", "3600"),
+    unit(&["ft/s", "fps"], "speed", "0.3048", "1"),
+    // Data, in bytes.
+    unit(&["B", "byte", "bytes"], "data", "1", "1"),
+    unit(&["bit", "bits"], "data", "1", "8"),
+    unit(&["KB", "kB"], "data", "1000", "1"),
+    unit(&["MB"], "data", "1000000", "1"),
+    unit(&["GB"], "data", "1000000000", "1"),
+    unit(&["TB"], "data", "1000000000000", "1"),
+    unit(&["PB"], "data", "1000000000000000", "1"),
+    unit(&["KiB"], "data", "1024", "1"),
+    unit(&["MiB"], "data", "1048576", "1"),
+    unit(&["GiB"], "data", "1073741824", "1"),
+    unit(&["TiB"], "data", "1099511627776", "1"),
+    unit(&["Mbit", "Mb"], "data", "1000000", "8"),
+    unit(&["Gbit", "Gb"], "data", "1000000000", "8"),
+    // Energy, in joules.
+    unit(&["J", "joule", "joules"], "energy", "1", "1"),
+    unit(&["kJ"], "energy", "1000", "1"),
+    unit(&["cal"], "energy", "4.184", "1"),
+    unit(&["kcal", "Cal"], "energy", "4184", "1"),
+    unit(&["Wh"], "energy", "3600", "1"),
+    unit(&["kWh"], "energy", "3600000", "1"),
+    // Power, in watts.
+    unit(&["W", "watt", "watts"], "power", "1", "1"),
+    unit(&["kW"], "power", "1000", "1"),
+    unit(&["hp"], "power", "745.69987158227022", "1"),
+    // Pressure, in pascals.
+    unit(&["Pa"], "pressure", "1", "1"),
+    unit(&["kPa"], "pressure", "1000", "1"),
+    unit(&["bar"], "pressure", "100000", "1"),
+    unit(&["psi"], "pressure", "4.4482216152605", "0.00064516"),
+    unit(&["atm"], "pressure", "101325", "1"),
+    unit(&["mmHg"], "pressure", "133.322387415", "1"),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Temperature {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+fn temperature(name: &str) -> Option<Temperature> {
+    match name.trim_start_matches('°').to_ascii_lowercase().as_str() {
+        "c" | "degc" | "celsius" => Some(Temperature::Celsius),
+        "f" | "degf" | "fahrenheit" => Some(Temperature::Fahrenheit),
+        "k" | "kelvin" => Some(Temperature::Kelvin),
+        _ => None,
+    }
+}
+
+fn find_unit(name: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|u| u.names.contains(&name)).or_else(|| {
+        UNITS
+            .iter()
+            .find(|u| u.names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+    })
+}
+
+/// `<expr> <unit> to <unit>`, or `None` if `expression` is not a
+/// conversion.
+fn convert_units(expression: &str, precision: u32) -> Option<Result<String, String>> {
+    let pattern = regex::Regex::new(
+        r"^(?P<value>.*?[\d)])\s*(?P<from>[^\s\d().+*/^%-][^\s]*)\s+(?:to|in|as|into)\s+(?P<to>\S+)$",
+    )
+    .expect("valid regex");
+    let caps = pattern.captures(expression)?;
+    let (from, to) = (&caps["from"], &caps["to"]);
+    let from_temp = temperature(from);
+    let to_temp = temperature(to);
+    let from_unit = find_unit(from);
+    let to_unit = find_unit(to);
+    if from_temp.is_none() && from_unit.is_none() {
+        return None;
+    }
+
+    let mut calc = Calculator::new(&caps["value"], precision);
+    let value = match calc.run() {
+        Ok(Value::Exact(d)) => d,
+        Ok(Value::Approx(x)) => match parse_decimal(&format_float(x)) {
+            Some(d) => {
+                calc.rounded = true;
+                d
+            }
+            None => return Some(Err("value is out of range".to_string())),
+        },
+        Err(e) => return Some(Err(e)),
+    };
+
+    let result = match (from_temp, to_temp, from_unit, to_unit) {
+        (Some(a), Some(b), _, _) => convert_temperature(&value, a, b, precision),
+        (_, _, Some(a), Some(b)) if a.dimension == b.dimension => {
+            let numerator = value * exact(a.numerator) * exact(b.denominator);
+            let denominator = exact(a.denominator) * exact(b.numerator);
+            div(&numerator, &denominator, precision)
+        }
+        (_, _, Some(a), Some(b)) => {
+            return Some(Err(format!(
+                "cannot convert {from} ({}) to {to} ({})",
+                a.dimension, b.dimension
+            )));
+        }
+        _ => return Some(Err(format!("unknown unit '{to}'"))),
+    };
+    Some(result.map(|(converted, rounded)| {
+        let sign = if rounded || calc.rounded { "≈" } else { "=" };
+        format!("{sign} {} {to}", plain(&converted))
+    }))
+}
+
+fn exact(text: &str) -> BigDecimal {
+    parse_decimal(text).expect("valid unit factor")
+}
+
+fn convert_temperature(
+    value: &BigDecimal,
+    from: Temperature,
+    to: Temperature,
+    precision: u32,
+) -> Result<(BigDecimal, bool), String> {
+    let offset = exact("273.15");
+    let (nine, five, thirty_two) = (exact("9"), exact("5"), exact("32"));
+    let mut rounded = false;
+    let kelvin = match from {
+        Temperature::Kelvin => value.clone(),
+        Temperature::Celsius => value + &offset,
+        Temperature::Fahrenheit => {
+            let (celsius, r) = div(&((value - &thirty_two) * &five), &nine, precision)?;
+            rounded |= r;
+            celsius + &offset
+        }
+    };
+    let result = match to {
+        Temperature::Kelvin => kelvin,
+        Temperature::Celsius => kelvin - &offset,
+        Temperature::Fahrenheit => {
+            let (scaled, r) = div(&((kelvin - &offset) * &nine), &five, precision)?;
+            rounded |= r;
+            scaled + &thirty_two
+        }
+    };
+    Ok((result, rounded))
+}
+
+// ---------------------------------------------------------------------------
+// Dates
+
+fn is_date_expression(expression: &str) -> bool {
+    let date = regex::Regex::new(r"\b\d{4}-\d{2}-\d{2}\b").expect("valid regex");
+    date.is_match(expression)
+        || expression.split(|c: char| !c.is_alphanumeric()).any(|w| {
+            matches!(
+                w.to_ascii_lowercase().as_str(),
+                "today" | "now" | "tomorrow" | "yesterday"
+            )
+        })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateTerm {
+    /// A point in time and whether a time of day was given.
+    Moment(NaiveDateTime, bool),
+    /// Calendar months plus an exact duration.
+    Span(u32, Duration),
+}
+
+fn date_math(expression: &str, now: NaiveDateTime) -> Result<String, String> {
+    // Split on `+`/`-` between terms, not inside `2026-03-01`.
+    let terms = regex::Regex::new(r"\s+([+-])\s+").expect("valid regex");
+    let mut result: Option<DateTerm> = None;
+    let mut sign = '+';
+    let mut last = 0;
+    let mut pieces: Vec<(char, &str)> = Vec::new();
+    for caps in terms.captures_iter(expression) {
+        let m = caps.get(0).expect("match");
+        pieces.push((sign, &expression[last..m.start()]));
+        sign = caps[1].chars().next().unwrap_or('+');
+        last = m.end();
+    }
+    pieces.push((sign, &expression[last..]));
+
+    for (sign, text) in pieces {
+        let term = parse_date_term(text.trim(), now)?;
+        result = Some(match (result, sign, term) {
+            (None, '+', term) => term,
+            (None, _, _) => return Err("an expression cannot start with '-'".to_string()),
+            (Some(DateTerm::Moment(at, timed)), sign, DateTerm::Span(months, duration)) => {
+                let shifted = if sign == '+' {
+                    at.checked_add_months(Months::new(months))
+                        .and_then(|d| d.checked_add_signed(duration))
+                } else {
+                    at.checked_sub_months(Months::new(months))
+                        .and_then(|d| d.checked_sub_signed(duration))
+                };
+                let timed = timed || duration.num_seconds() % 86_400 != 0;
+                DateTerm::Moment(shifted.ok_or("date is out of range")?, timed)
+            }
+            (Some(DateTerm::Moment(a, _)), '-', DateTerm::Moment(b, _)) => DateTerm::Span(0, a - b),
+            (Some(DateTerm::Span(m1, d1)), sign, DateTerm::Span(m2, d2)) => {
+                let too_long = "duration is out of range";
+                if sign == '+' {
+                    DateTerm::Span(
+                        m1.checked_add(m2).ok_or(too_long)?,
+                        d1.checked_add(&d2).ok_or(too_long)?,
+                    )
+                } else if m2 == 0 {
+                    DateTerm::Span(m1, d1.checked_sub(&d2).ok_or(too_long)?)
+                } else {
+                    return Err("cannot subtract months from a duration".to_string());
+                }
+            }
+            (Some(DateTerm::Span(..)), _, DateTerm::Moment(..)) => {
+                return Err("put the date first, e.g. 2026-03-01 + 2 weeks".to_string());
+            }
+            (Some(DateTerm::Moment(..)), _, DateTerm::Moment(..)) => {
+                return Err("dates can only be subtracted from each other".to_string());
+            }
+        });
+    }
+
+    Ok(match result.ok_or("empty expression")? {
+        DateTerm::Moment(at, timed) => format!("= {}", format_moment(at, timed)),
+        DateTerm::Span(months, duration) => format!("= {}", format_span(months, duration)),
+    })
+}
+
+fn parse_date_term(text: &str, now: NaiveDateTime) -> Result<DateTerm, String> {
+    let today = now.date().and_time(NaiveTime::MIN);
+    match text.to_ascii_lowercase().as_str() {
+        "now" => return Ok(DateTerm::Moment(now, true)),
+        "today" => return Ok(DateTerm::Moment(today, false)),
+        "tomorrow" => return Ok(DateTerm::Moment(today + Duration::days(1), false)),
+        "yesterday" => return Ok(DateTerm::Moment(today - Duration::days(1), false)),
+        _ => {}
+    }
+    for format in [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(at) = NaiveDateTime::parse_from_str(text, format) {
+            return Ok(DateTerm::Moment(at, true));
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Ok(DateTerm::Moment(date.and_time(NaiveTime::MIN), false));
+    }
+    parse_span(text)
+}
+
+/// `45 days`, `3 weeks 2 days`, `1h30m`, `2 months`.
+fn parse_span(text: &str) -> Result<DateTerm, String> {
+    let part = regex::Regex::new(r"(?i)(\d+)\s*([a-z]+)").expect("valid regex");
+    let too_far = || format!("'{text}' is too long a duration");
+    let mut months = 0u32;
+    let mut duration = Duration::zero();
+    for caps in part.captures_iter(text) {
+        let n: i64 = caps[1]
+            .parse()
+            .map_err(|_| format!("invalid number in '{text}'"))?;
+        let unit = caps[2].to_ascii_lowercase();
+        let step = match unit.as_str() {
+            "y" | "yr" | "yrs" | "year" | "years" => {
+                let years = u32::try_from(n).ok().and_then(|n| n.checked_mul(12));
+                months = years
+                    .and_then(|n| months.checked_add(n))
+                    .ok_or_else(too_far)?;
+                continue;
+            }
+            "mo" | "mon" | "month" | "months" => {
+                let n = u32::try_from(n).ok();
+                months = n.and_then(|n| months.checked_add(n)).ok_or_else(too_far)?;
+                continue;
+            }
+            "w" | "wk" | "wks" | "week" | "weeks" => Duration::try_weeks(n),
+            "d" | "day" | "days" => Duration::try_days(n),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Duration::try_hours(n),
+            "m" | "min" | "mins" | "minute" | "minutes" => Duration::try_minutes(n),
+            "s" | "sec" | "secs" | "second" | "seconds" => Duration::try_seconds(n),
+            _ => return Err(format!("unknown time unit '{}'", &caps[2])),
+        };
+        duration = step
+            .and_then(|step| duration.checked_add(&step))
+            .ok_or_else(too_far)?;
+    }
+    let significant = text.chars().filter(|c| !c.is_whitespace()).count();
+    let matched: usize = part
+        .find_iter(text)
+        .map(|m| m.as_str().chars().filter(|c| !c.is_whitespace()).count())
+        .sum();
+    if matched == 0 || matched != significant {
+        return Err(format!(
+            "cannot read '{text}' as a date (YYYY-MM-DD, today, now) or a duration (45 days, 1h30m)"
+        ));
+    }
+    Ok(DateTerm::Span(months, duration))
+}
+
+fn format_moment(at: NaiveDateTime, timed: bool) -> String {
+    let weekday = at.format("%A");
+    if timed {
+        format!("{} ({weekday})", at.format("%Y-%m-%d %H:%M"))
+    } else {
+        format!("{} ({weekday})", at.date())
+    }
+}
+
+fn format_span(months: u32, duration: Duration) -> String {
+    let mut parts = Vec::new();
+    if months > 0 {
+        let (years, months) = (months / 12, months % 12);
+        if years > 0 {
+            parts.push(plural(years as i64, "year"));
+        }
+        if months > 0 {
+            parts.push(plural(months as i64, "month"));
+        }
+    }
+    let negative = duration < Duration::zero();
+    let total = duration.num_seconds().abs();
+    let (days, rest) = (total / 86_400, total % 86_400);
+    let (hours, minutes, seconds) = (rest / 3_600, rest % 3_600 / 60, rest % 60);
+    if days > 0 {
+        parts.push(plural(days, "day"));
+    }
+    for (n, unit) in [(hours, "hour"), (minutes, "minute"), (seconds, "second")] {
+        if n > 0 {
+            parts.push(plural(n, unit));
+        }
+    }
+    if parts.is_empty() {
+        return "0 days".to_string();
+    }
+    let mut text = parts.join(" ");
+    if negative {
+        text = format!("-{text} (in the past)");
+    }
+    if months == 0 && days >= 7 && rest == 0 {
+        let (weeks, extra) = (days / 7, days % 7);
+        let mut weeks_text = plural(weeks, "week");
+        if extra > 0 {
+            weeks_text.push(' ');
+            weeks_text.push_str(&plural(extra, "day"));
+        }
+        text.push_str(&format!(" ({weeks_text})"));
+    } else if months == 0 && days > 0 && rest > 0 {
+        text.push_str(&format!(
+            " ({} hours)",
+            format_float(total as f64 / 3_600.0)
+        ));
+    }
+    text
+}
+
+fn plural(n: i64, unit: &str) -> String {
+    if n == 1 {
+        format!("1 {unit}")
+    } else {
+        format!("{n} {unit}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn calc(expression: &str) -> String {
+        evaluate(expression, DEFAULT_PRECISION)
+    }
+
+    #[test]
+    fn arithmetic_is_exact() {
+        assert_eq!(calc("0.1 + 0.2"), "0.1 + 0.2 = 0.3");
+        assert_eq!(
+            calc("1249.99 * 12 * 1.0825"),
+            "1249.99 * 12 * 1.0825 = 16237.3701"
+        );
+        assert_eq!(calc("2^100"), "2^100 = 1267650600228229401496703205376");
+        assert_eq!(calc("-(3 - 5) * 2 ** 3"), "-(3 - 5) * 2 ** 3 = 16");
+        assert_eq!(calc("7 % 3"), "7 % 3 = 1");
+        assert_eq!(calc("15% of 80"), "15% of 80 = 12");
+        assert_eq!(calc("200 * 7.5%"), "200 * 7.5% = 15");
+        assert_eq!(calc("20!"), "20! = 2432902008176640000");
+        assert_eq!(calc("round(2.675, 2)"), "round(2.675, 2) = 2.68");
+        assert_eq!(
+            calc("floor(-2.5) + ceil(1.2)"),
+            "floor(-2.5) + ceil(1.2) = -1"
+        );
+        assert_eq!(calc("max(3, 1e3, 7)"), "max(3, 1e3, 7) = 1000");
+    }
+
+    #[test]
+    fn rounding_and_floats_are_marked() {
+        assert_eq!(
+            calc("1/3"),
+            "1/3 ≈ 0.33333333333333333333 (rounded to 20 decimal places)"
+        );
+        assert_eq!(
+            evaluate("2/3", 4),
+            "2/3 ≈ 0.6667 (rounded to 4 decimal places)"
+        );
+        assert_eq!(calc("sqrt(2)"), "sqrt(2) ≈ 1.414213562373");
+        assert_eq!(calc("1/0"), "Error: division by zero");
+        assert!(calc("2 +").starts_with("Error: expression ends"));
+        assert!(calc("9^9^9").contains("too large"));
+        assert!(calc("0.1^5000000000").contains("too many decimal places"));
+        let nested = format!("{}1{}", "(".repeat(400), ")".repeat(400));
+        assert_eq!(calc(&nested), "Error: expression nested too deeply");
+        assert_eq!(
+            calc(&format!("{}1", "-".repeat(1_000))),
+            "Error: expression nested too deeply"
+        );
+        assert!(calc(&format!("{}1", "-".repeat(20_000))).contains("longer than"));
+    }
+
+    #[test]
+    fn units_convert_with_exact_factors() {
+        assert_eq!(calc("5 km to mi"), "5 km to mi ≈ 3.10685596118666984809 mi");
+        assert_eq!(calc("6 ft in m"), "6 ft in m = 1.8288 m");
+        assert_eq!(calc("3 GiB to MB"), "3 GiB to MB = 3221.225472 MB");
+        assert_eq!(
+            calc("100 km/h to m/s"),
+            "100 km/h to m/s ≈ 27.77777777777777777778 m/s"
+        );
+        assert_eq!(calc("72 °F to C"), "72 °F to C ≈ 22.22222222222222222222 C");
+        assert_eq!(calc("(2 + 3) lb to g"), "(2 + 3) lb to g = 2267.96185 g");
+        assert_eq!(
+            calc("5 kg to m"),
+            "Error: cannot convert kg (mass) to m (length)"
+        );
+    }
+
+    #[test]
+    fn dates_add_and_subtract() {
+        let now = NaiveDate::from_ymd_opt(2026, 3, 10)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap();
+        let math = |e: &str| date_math(e, now).unwrap();
+        assert_eq!(math("2026-03-01 + 45 days"), "= 2026-04-15 (Wednesday)");
+        assert_eq!(math("2026-01-31 + 1 month"), "= 2026-02-28 (Saturday)");
+        assert_eq!(math("2026-12-25 - today"), "= 290 days (41 weeks 3 days)");
+        assert_eq!(math("now + 1h45m"), "= 2026-03-10 11:15 (Tuesday)");
+        assert_eq!(math("tomorrow - 2 weeks"), "= 2026-02-25 (Wednesday)");
+        assert_eq!(
+            math("2026-03-01 09:00 - 2026-02-27 18:30"),
+            "= 1 day 14 hours 30 minutes (38.5 hours)"
+        );
+        assert!(date_math("2026-03-01 + 3 fortnights", now).is_err());
+        assert!(date_math("2026-01-01 + 99999999999999 days", now).is_err());
+        assert!(date_math("2026-01-01 + 400000000 years", now).is_err());
+        assert!(date_math("2026-01-01 + 5000000000 months", now).is_err());
+        assert!(is_date_expression("today + 3 days"));
+        assert!(!is_date_expression("3 days to hours"));
+    }
+}
@@ -0,0 +1,557 @@
+//! `rust.check`, `rust.clippy` and `rust.test`: cargo with structured output.
+//!
+//! Cargo runs with `--message-format=json`, so compiler diagnostics arrive as
+//! one JSON object per line and are reduced to `file:line:column` entries
+//! with their primary label and suggestions. Test results are read from the
+//! libtest text that shares stdout with those objects. Long lists are capped
+//! so a broken build costs a few hundred tokens rather than the whole
+//! rendered stderr.
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use crate::core::shell::{CommandOrigin, ShellResult, execute_shell_as_with_timeout};
+use crate::tools::file_ops::resolve_safe_path;
+
+/// Builds of a cold workspace can take several minutes.
+const CARGO_TIMEOUT: Duration = Duration::from_secs(600);
+/// Diagnostics listed before the rest are counted only.
+const MAX_DIAGNOSTICS: usize = 30;
+/// Failed tests whose output is shown.
+const MAX_FAILURES: usize = 10;
+/// Output lines kept per failed test.
+const MAX_FAILURE_LINES: usize = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CargoCommand {
+    Check,
+    Clippy,
+    Test,
+}
+
+impl CargoCommand {
+    /// The command for a `rust.*` tool name.
+    pub fn from_tool(name: &str) -> Option<Self> {
+        match name {
+            "rust.check" => Some(Self::Check),
+            "rust.clippy" => Some(Self::Clippy),
+            "rust.test" => Some(Self::Test),
+            _ => None,
+        }
+    }
+
+    fn subcommand(self) -> &'static str {
+        match self {
+            Self::Check => "check",
+            Self::Clippy => "clippy",
+            Self::Test => "test",
+        }
+    }
+}
+
+/// Arguments of a `rust.*` tool call.
+#[derive(Debug, Clone, Default)]
+pub struct CargoRequest {
+    /// Directory holding `Cargo.toml`, relative to the workspace.
+    pub dir: Option<String>,
+    /// `-p <package>`.
+    pub package: Option<String>,
+    /// Test name filter (`rust.test` only).
+    pub filter: Option<String>,
+}
+
+impl CargoRequest {
+    pub fn from_args(args: &Value) -> Self {
+        let text = |key: &str| {
+            args[key]
+                .as_str()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(str::to_string)
+        };
+        Self {
+            dir: text("path"),
+            package: text("package"),
+            filter: text("filter"),
+        }
+    }
+
+    fn command_line(&self, command: CargoCommand, workspace: &Path) -> Result<String, String> {
+        // Arguments go through the shell: keep them to plain identifiers.
+        let plain = |value: &str| {
+            value
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.'))
+        };
+        let mut line = format!("cargo {} --message-format=json", command.subcommand());
+        if let Some(dir) = &self.dir {
+            let path = resolve_safe_path(workspace, dir)
+                .ok_or_else(|| format!("Access denied: path escapes workspace: {dir}"))?;
+            let manifest = path.join("Cargo.toml");
+            if !manifest.is_file() {
+                return Err(format!("Error: no Cargo.toml in {dir}."));
+            }
+            let manifest = manifest.to_string_lossy().replace('\'', r"'\''");
+            line.push_str(&format!(" --manifest-path '{manifest}'"));
+        } else if !workspace.join("Cargo.toml").is_file() {
+            return Err(
+                "Error: no Cargo.toml at the workspace root; pass 'path' to the crate directory."
+                    .to_string(),
+            );
+        }
+        match &self.package {
+            Some(package) if !plain(package) => {
+                return Err(format!("Error: invalid package name '{package}'."));
+            }
+            Some(package) => line.push_str(&format!(" -p {package}")),
+            None => {}
+        }
+        match command {
+            CargoCommand::Check | CargoCommand::Clippy => line.push_str(" --all-targets"),
+            CargoCommand::Test => {
+                line.push_str(" --no-fail-fast");
+                match &self.filter {
+                    Some(filter) if !plain(filter) => {
+                        return Err(format!("Error: invalid test filter '{filter}'."));
+                    }
+                    Some(filter) => line.push_str(&format!(" {filter}")),
+                    None => {}
+                }
+            }
+        }
+        Ok(line)
+    }
+}
+
+/// Run a `rust.*` tool and summarise the result; the flag is cargo's success.
+pub fn run(command: CargoCommand, workspace: &Path, request: &CargoRequest) -> (String, bool) {
+    let line = match request.command_line(command, workspace) {
+        Ok(line) => line,
+        Err(e) => return (e, false),
+    };
+    let result =
+        execute_shell_as_with_timeout(&line, workspace, CommandOrigin::Tool, CARGO_TIMEOUT);
+    let ok = result.exit_code == 0 && !result.timed_out;
+    (summarize(command, &line, &result), ok)
+}
+
+fn summarize(command: CargoCommand, line: &str, result: &ShellResult) -> String {
+    if result.timed_out {
+        return format!(
+            "Error: `{line}` timed out after {}s.",
+            CARGO_TIMEOUT.as_secs()
+        );
+    }
+    let build = parse_build(&result.stdout);
+    let name = format!("cargo {}", command.subcommand());
+    let mut out = Vec::new();
+
+    let tests = (command == CargoCommand::Test).then(|| parse_tests(&result.stdout));
+    let status = match (&tests, build.success) {
+        (_, Some(false)) => "build failed".to_string(),
+        (Some(tests), _) if tests.binaries > 0 => tests.summary(),
+        _ if result.exit_code != 0 => "failed".to_string(),
+        _ => "ok".to_string(),
+    };
+    out.push(format!(
+        "{name}: {status} — {} errors, {} warnings",
+        build.errors(),
+        build.warnings()
+    ));
+    out.extend(build.render());
+
+    if let Some(tests) = &tests {
+        out.extend(tests.render());
+    }
+    // Cargo's own failures (bad manifest, missing toolchain) only reach stderr.
+    if build.success.is_none() && result.exit_code != 0 {
+        let errors: Vec<&str> = result
+            .stderr
+            .lines()
+            .filter(|l| l.starts_with("error"))
+            .take(10)
+            .collect();
+        out.extend(errors.iter().map(|l| l.to_string()));
+    }
+    out.join("\n")
+}
+
+/// One compiler diagnostic reduced to what the agent needs to act on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Diagnostic {
+    level: String,
+    code: Option<String>,
+    message: String,
+    location: Option<String>,
+    label: Option<String>,
+    help: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+struct Build {
+    diagnostics: Vec<Diagnostic>,
+    success: Option<bool>,
+}
+
+impl Build {
+    fn errors(&self) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.level == "error")
+            .count()
+    }
+
+    fn warnings(&self) -> usize {
+        self.diagnostics.len() - self.errors()
+    }
+
+    fn render(&self) -> Vec<String> {
+        let mut sorted: Vec<&Diagnostic> = self.diagnostics.iter().collect();
+        sorted.sort_by_key(|d| d.level != "error");
+        let mut out = Vec::new();
+        for diagnostic in sorted.iter().take(MAX_DIAGNOSTICS) {
+            let code = diagnostic
+                .code
+                .as_ref()
+                .map(|c| format!("[{c}]"))
+                .unwrap_or_default();
+            let location = diagnostic
+                .location
+                .as_ref()
+                .map(|l| format!(" {l}:"))
+                .unwrap_or_default();
+            out.push(format!(
+                "{}{code}{location} {}",
+                diagnostic.level, diagnostic.message
+            ));
+            if let Some(label) = &diagnostic.label {
+                out.push(format!("    {label}"));
+            }
+            for help in &diagnostic.help {
+                out.push(format!("    {help}"));
+            }
+        }
+        if sorted.len() > MAX_DIAGNOSTICS {
+            out.push(format!(
+                "[{} more diagnostics not shown]",
+                sorted.len() - MAX_DIAGNOSTICS
+            ));
+        }
+        out
+    }
+}
+
+/// Collect `compiler-message` and `build-finished` lines from cargo's JSON
+/// stream, dropping duplicates (a crate checked as lib and as test target
+/// reports the same warning twice) and rustc's closing summaries.
+fn parse_build(stdout: &str) -> Build {
+    let mut build = Build::default();
+    for line in stdout.lines().filter(|l| l.starts_with('{')) {
+        let Ok(value) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        match value["reason"].as_str() {
+            Some("build-finished") => build.success = value["success"].as_bool(),
+            Some("compiler-message") => {
+                if let Some(diagnostic) = diagnostic(&value["message"])
+                    && !build.diagnostics.contains(&diagnostic)
+                {
+                    build.diagnostics.push(diagnostic);
+                }
+            }
+            _ => {}
+        }
+    }
+    build
+}
+
+fn diagnostic(message: &Value) -> Option<Diagnostic> {
+    let level = message["level"].as_str()?;
+    if !matches!(level, "error" | "warning") {
+        return None;
+    }
+    let text = message["message"].as_str()?;
+    let spans = message["spans"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or(&[]);
+    // "aborting due to 2 previous errors", "3 warnings emitted".
+    if spans.is_empty() && (text.starts_with("aborting due to") || text.ends_with("emitted")) {
+        return None;
+    }
+    let primary = spans
+        .iter()
+        .find(|s| s["is_primary"].as_bool() == Some(true))
+        .or(spans.first());
+    let location = primary.map(|s| {
+        format!(
+            "{}:{}:{}",
+            s["file_name"].as_str().unwrap_or("?"),
+            s["line_start"],
+            s["column_start"]
+        )
+    });
+    let label = primary
+        .and_then(|s| s["label"].as_str())
+        .map(str::to_string);
+    let help = message["children"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+        .iter()
+        .filter(|c| c["level"].as_str() == Some("help"))
+        .take(2)
+        .filter_map(|child| {
+            let text = child["message"].as_str()?;
+            let replacement = child["spans"]
+                .as_array()
+                .and_then(|spans| spans.first())
+                .and_then(|s| s["suggested_replacement"].as_str());
+            Some(match replacement {
+                Some(replacement) => format!("help: {text}: `{replacement}`"),
+                None => format!("help: {text}"),
+            })
+        })
+        .collect();
+    Some(Diagnostic {
+        level: level.to_string(),
+        code: message["code"]["code"].as_str().map(str::to_string),
+        message: text.lines().next().unwrap_or(text).to_string(),
+        location,
+        label,
+        help,
+    })
+}
+
+#[derive(Debug, Default)]
+struct Tests {
+    binaries: usize,
+    passed: usize,
+    failed: usize,
+    ignored: usize,
+    /// Failed test names with their captured output.
+    failures: Vec<(String, Vec<String>)>,
+}
+
+impl Tests {
+    fn summary(&self) -> String {
+        format!(
+            "{} failed, {} passed, {} ignored ({} test binaries)",
+            self.failed, self.passed, self.ignored, self.binaries
+        )
+    }
+
+    fn render(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        for (name, output) in self.failures.iter().take(MAX_FAILURES) {
+            out.push(format!("FAILED {name}"));
+            out.extend(output.iter().map(|l| format!("    {l}")));
+        }
+        if self.failures.len() > MAX_FAILURES {
+            out.push(format!(
+                "[{} more failed tests not shown]",
+                self.failures.len() - MAX_FAILURES
+            ));
+        }
+        out
+    }
+}
+
+/// Read libtest's text output: `test result:` totals and the
+/// `---- name stdout ----` sections of failed tests, without backtraces.
+fn parse_tests(stdout: &str) -> Tests {
+    let mut tests = Tests::default();
+    let mut current: Option<(String, Vec<String>)> = None;
+    let mut in_backtrace = false;
+    for line in stdout.lines().filter(|l| !l.starts_with('{')) {
+        if let Some(totals) = line.strip_prefix("test result: ") {
+            tests.binaries += 1;
+            for part in totals.split(';') {
+                let mut words = part.split_whitespace().rev();
+                let (Some(label), Some(count)) = (words.next(), words.next()) else {
+                    continue;
+                };
+                let count: usize = count.parse().unwrap_or(0);
+                match label {
+                    "passed" => tests.passed += count,
+                    "failed" => tests.failed += count,
+                    "ignored" => tests.ignored += count,
+                    _ => {}
+                }
+            }
+            continue;
+        }
+        if let Some(name) = line
+            .strip_prefix("---- ")
+            .and_then(|rest| rest.strip_suffix(" stdout ----"))
+        {
+            tests.failures.extend(current.take());
+            current = Some((name.to_string(), Vec::new()));
+            in_backtrace = false;
+            continue;
+        }
+        let Some((_, output)) = current.as_mut() else {
+            continue;
+        };
+        if line == "failures:" {
+            tests.failures.extend(current.take());
+            continue;
+        }
+        if line.starts_with("stack backtrace:") {
+            in_backtrace = true;
+            continue;
+        }
+        if in_backtrace {
+            in_backtrace = !line.starts_with("note: ");
+            continue;
+        }
+        let hint = line.starts_with("note: run with `RUST_BACKTRACE");
+        if !line.trim().is_empty() && !hint && output.len() < MAX_FAILURE_LINES {
+            output.push(line.to_string());
+        }
+    }
+    tests.failures.extend(current);
+    tests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUILD_FAILED: &str = r#"{"reason":"compiler-artifact","package_id":"dep"}
+{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","code":{"code":"E0308"},"spans":[{"file_name":"src/lib.rs","line_start":3,"column_start":17,"is_primary":true,"label":"expected `u8`, found `&str`"},{"file_name":"src/lib.rs","line_start":3,"column_start":12,"is_primary":false,"label":"expected due to this"}],"children":[]}}
+{"reason":"compiler-message","message":{"level":"warning","message":"unused variable: `unused`","code":{"code":"unused_variables"},"spans":[{"file_name":"src/lib.rs","line_start":2,"column_start":9,"is_primary":true,"label":null}],"children":[{"level":"note","message":"on by default","spans":[]},{"level":"help","message":"if this is intentional, prefix it with an underscore","spans":[{"suggested_replacement":"_unused"}]}]}}
+{"reason":"compiler-message","message":{"level":"warning","message":"unused variable: `unused`","code":{"code":"unused_variables"},"spans":[{"file_name":"src/lib.rs","line_start":2,"column_start":9,"is_primary":true,"label":null}],"children":[{"level":"note","message":"on by default","spans":[]},{"level":"help","message":"if this is intentional, prefix it with an underscore","spans":[{"suggested_replacement":"_unused"}]}]}}
+{"reason":"compiler-message","message":{"level":"error","message":"aborting due to 1 previous error","code":null,"spans":[],"children":[]}}
+{"reason":"compiler-message","message":{"level":"failure-note","message":"For more information about this error, try `rustc --explain E0308`.","code":null,"spans":[],"children":[]}}
+{"reason":"build-finished","success":false}
+"#;
+
+    const TEST_RUN: &str = r#"{"reason":"build-finished","success":true}
+
+running 3 tests
+test tests::bad ... FAILED
+test tests::ok ... ok
+test tests::skipped ... ignored
+
+failures:
+
+---- tests::bad stdout ----
+
+thread 'tests::bad' (28731) panicked at src/lib.rs:11:16:
+assertion `left == right` failed: math
+  left: 2
+ right: 3
+stack backtrace:
+   0: __rustc::rust_begin_unwind
+             at /rustc/library/std/src/panicking.rs:689:5
+note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.
+
+
+failures:
+    tests::bad
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out; finished in 0.02s
+
+
+running 0 tests
+
+test result: ok. 0 passed; 0 failed; 0 ignored; 0 measured; 0 filtered out; finished in 0.00s
+"#;
+
+    fn shell(stdout: &str, exit_code: i32) -> ShellResult {
+        ShellResult {
+            stdout: stdout.to_string(),
+            stderr: String::new(),
+            exit_code,
+            timed_out: false,
+        }
+    }
+
+    #[test]
+    fn compiler_messages_are_deduplicated_and_condensed() {
+        let out = summarize(
+            CargoCommand::Check,
+            "cargo check",
+            &shell(BUILD_FAILED, 101),
+        );
+        assert_eq!(
+            out,
+            "cargo check: build failed — 1 errors, 1 warnings\n\
+             error[E0308] src/lib.rs:3:17: mismatched types\n    \
+             expected `u8`, found `&str`\n\
+             warning[unused_variables] src/lib.rs:2:9: unused variable: `unused`\n    \
+             help: if this is intentional, prefix it with an underscore: `_unused`"
+        );
+    }
+
+    #[test]
+    fn test_failures_keep_panic_output_without_backtraces() {
+        let out = summarize(CargoCommand::Test, "cargo test", &shell(TEST_RUN, 101));
+        assert_eq!(
+            out,
+            "cargo test: 1 failed, 1 passed, 1 ignored (2 test binaries) — 0 errors, 0 warnings\n\
+             FAILED tests::bad\n    \
+             thread 'tests::bad' (28731) panicked at src/lib.rs:11:16:\n    \
+             assertion `left == right` failed: math\n    \
+             \x20 left: 2\n    \
+             \x20right: 3"
+        );
+    }
+
+    #[test]
+    fn cargo_errors_and_timeouts_are_reported() {
+        let mut result = shell("", 101);
+        result.stderr =
+            "error: failed to parse manifest at `/ws/Cargo.toml`\n\nCaused by:\n  x".into();
+        assert_eq!(
+            summarize(CargoCommand::Clippy, "cargo clippy", &result),
+            "cargo clippy: failed — 0 errors, 0 warnings\nerror: failed to parse manifest at `/ws/Cargo.toml`"
+        );
+        result.timed_out = true;
+        assert!(
+            summarize(CargoCommand::Test, "cargo test", &result).contains("timed out after 600s")
+        );
+    }
+
+    #[test]
+    fn command_lines_validate_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let request = CargoRequest::default();
+        assert!(
+            request
+                .command_line(CargoCommand::Check, dir.path())
+                .unwrap_err()
+                .contains("no Cargo.toml")
+        );
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]").unwrap();
+        let request = CargoRequest {
+            package: Some("core-lib".into()),
+            filter: Some("tools::calc".into()),
+            ..CargoRequest::default()
+        };
+        assert_eq!(
+            request
+                .command_line(CargoCommand::Test, dir.path())
+                .unwrap(),
+            "cargo test --message-format=json -p core-lib --no-fail-fast tools::calc"
+        );
+        assert_eq!(
+            request
+                .command_line(CargoCommand::Clippy, dir.path())
+                .unwrap(),
+            "cargo clippy --message-format=json -p core-lib --all-targets"
+        );
+        let request = CargoRequest {
+            filter: Some("x; rm -rf /".into()),
+            ..CargoRequest::default()
+        };
+        assert!(
+            request
+                .command_line(CargoCommand::Test, dir.path())
+                .is_err()
+        );
+    }
+}
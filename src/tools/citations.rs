@@ -0,0 +1,103 @@
+//! Source tracking for answers built from web content.
+//!
+//! Each successful `web.fetch` / `web.search` call records its URL in the
+//! turn's [`CitationLog`] and labels the tool output with the source number,
+//! so the model can cite `[1]` inline. After the turn the agent loop appends
+//! a numbered "Sources" section to the answer and records the list in the
+//! tape as a `turn.sources` event.
+
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+/// Tape event listing the sources cited by one answer.
+pub const SOURCES_EVENT: &str = "turn.sources";
+
+/// One web source consulted during a turn.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Citation {
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+/// Sources recorded during the current turn, shared between tool calls.
+#[derive(Debug, Clone, Default)]
+pub struct CitationLog {
+    sources: Arc<Mutex<Vec<Citation>>>,
+}
+
+impl CitationLog {
+    /// Record a source and return its 1-based number. A URL already in the
+    /// log keeps its number; a title seen later fills in a missing one.
+    pub fn record(&self, url: &str, title: Option<String>) -> usize {
+        let mut sources = self.sources.lock().unwrap();
+        if let Some(pos) = sources.iter().position(|c| c.url == url) {
+            if sources[pos].title.is_none() {
+                sources[pos].title = title;
+            }
+            return pos + 1;
+        }
+        sources.push(Citation {
+            url: url.to_string(),
+            title,
+        });
+        sources.len()
+    }
+
+    /// Drain the recorded sources, leaving the log empty for the next turn.
+    pub fn take(&self) -> Vec<Citation> {
+        std::mem::take(&mut *self.sources.lock().unwrap())
+    }
+}
+
+/// Title of a fetched page: its first Markdown heading, if any.
+pub fn page_title(content: &str) -> Option<String> {
+    content
+        .lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("# "))
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+}
+
+/// Numbered "Sources" section appended to an answer.
+pub fn format_sources(sources: &[Citation]) -> String {
+    let mut lines = vec!["Sources:".to_string()];
+    for (i, source) in sources.iter().enumerate() {
+        match &source.title {
+            Some(title) => lines.push(format!("[{}] {title} — {}", i + 1, source.url)),
+            None => lines.push(format!("[{}] {}", i + 1, source.url)),
+        }
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_urls_keep_their_number() {
+        let log = CitationLog::default();
+        assert_eq!(log.record("https://a.example", None), 1);
+        assert_eq!(log.record("https://b.example", Some("B".into())), 2);
+        assert_eq!(log.record("https://a.example", Some("A".into())), 1);
+
+        let sources = log.take();
+        assert_eq!(
+            format_sources(&sources),
+            "Sources:\n[1] A — https://a.example\n[2] B — https://b.example"
+        );
+        assert!(log.take().is_empty());
+    }
+
+    #[test]
+    fn page_title_uses_first_heading() {
+        assert_eq!(
+            page_title("intro\n# Release notes \n## Details").as_deref(),
+            Some("Release notes")
+        );
+        assert_eq!(page_title("## Only a subheading"), None);
+    }
+}
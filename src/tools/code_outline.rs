@@ -0,0 +1,436 @@
+//! `code.outline`: the symbol structure of a source file with line ranges.
+//!
+//! The file is parsed with its tree-sitter grammar and the declarations are
+//! read off the syntax tree: functions, types, impls, classes and their
+//! methods, with the lines each one spans. Bodies of functions are not
+//! descended into, so local helpers and closures stay out of the outline.
+
+use std::path::Path;
+
+use tree_sitter::{Language, Node, Parser};
+
+use crate::tools::binary;
+use crate::tools::file_ops::resolve_safe_path;
+
+/// Files larger than this are not outlined.
+const MAX_FILE_BYTES: u64 = 4 * 1024 * 1024;
+/// Symbols listed before the rest are summarised.
+const MAX_SYMBOLS: usize = 400;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lang {
+    Rust,
+    Python,
+    Go,
+    JavaScript,
+    TypeScript,
+    Tsx,
+    Java,
+    CSharp,
+    C,
+    Cpp,
+}
+
+impl Lang {
+    fn from_extension(extension: &str) -> Option<Self> {
+        Some(match extension.to_ascii_lowercase().as_str() {
+            "rs" => Self::Rust,
+            "py" | "pyi" => Self::Python,
+            "go" => Self::Go,
+            "js" | "jsx" | "mjs" | "cjs" => Self::JavaScript,
+            "ts" | "mts" | "cts" => Self::TypeScript,
+            "tsx" => Self::Tsx,
+            "java" => Self::Java,
+            "cs" => Self::CSharp,
+            "c" => Self::C,
+            "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" => Self::Cpp,
+            _ => return None,
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Rust => "rust",
+            Self::Python => "python",
+            Self::Go => "go",
+            Self::JavaScript => "javascript",
+            Self::TypeScript | Self::Tsx => "typescript",
+            Self::Java => "java",
+            Self::CSharp => "c#",
+            Self::C => "c",
+            Self::Cpp => "c++",
+        }
+    }
+
+    fn grammar(self) -> Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::Go => tree_sitter_go::LANGUAGE.into(),
+            Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            Self::Tsx => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::Java => tree_sitter_java::LANGUAGE.into(),
+            Self::CSharp => tree_sitter_c_sharp::LANGUAGE.into(),
+            Self::C => tree_sitter_c::LANGUAGE.into(),
+            Self::Cpp => tree_sitter_cpp::LANGUAGE.into(),
+        }
+    }
+}
+
+/// One declaration in the outline; lines are 0-based and inclusive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Symbol {
+    kind: &'static str,
+    name: String,
+    start: usize,
+    end: usize,
+    depth: usize,
+}
+
+/// Outline a workspace file.
+pub fn outline_file(workspace: &Path, file_path: &str) -> String {
+    let Some(path) = resolve_safe_path(workspace, file_path) else {
+        return format!("Access denied: path escapes workspace: {file_path}");
+    };
+    if !path.is_file() {
+        return format!("File not found: {file_path}");
+    }
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let Some(lang) = Lang::from_extension(extension) else {
+        return format!(
+            "Error: no outline support for '.{extension}' files (rust, python, go, js/ts, java, c#, c/c++)."
+        );
+    };
+    let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+    if size > MAX_FILE_BYTES {
+        return format!("Error: {file_path} is too large to outline ({size} bytes).");
+    }
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) => return format!("Error reading file: {e}"),
+    };
+    if binary::looks_binary(&bytes[..bytes.len().min(binary::SNIFF_BYTES)]) {
+        return format!("Error: {file_path} is a binary file.");
+    }
+    let source = String::from_utf8_lossy(&bytes);
+    match outline(&source, lang) {
+        Ok(symbols) => render(file_path, lang, source.lines().count(), &symbols),
+        Err(e) => format!("Error: could not parse {file_path}: {e}"),
+    }
+}
+
+fn render(file_path: &str, lang: Lang, line_count: usize, symbols: &[Symbol]) -> String {
+    let mut out = format!(
+        "{file_path} ({}, {line_count} lines, {} symbols)\n",
+        lang.name(),
+        symbols.len()
+    );
+    if symbols.is_empty() {
+        out.push_str("No declarations found.");
+        return out;
+    }
+    for symbol in symbols.iter().take(MAX_SYMBOLS) {
+        out.push_str(&format!(
+            "{}{} {}  L{}-{}\n",
+            "  ".repeat(symbol.depth),
+            symbol.kind,
+            symbol.name,
+            symbol.start + 1,
+            symbol.end + 1
+        ));
+    }
+    if symbols.len() > MAX_SYMBOLS {
+        out.push_str(&format!("[{} more symbols]\n", symbols.len() - MAX_SYMBOLS));
+    }
+    out.push_str("Read a range with file.read start_line/end_line.");
+    out
+}
+
+fn outline(source: &str, lang: Lang) -> Result<Vec<Symbol>, String> {
+    let mut parser = Parser::new();
+    parser
+        .set_language(&lang.grammar())
+        .map_err(|e| e.to_string())?;
+    let tree = parser
+        .parse(source, None)
+        .ok_or_else(|| "parser gave up".to_string())?;
+
+    // Walk with an explicit stack: deeply nested expressions would overflow
+    // a recursive one. Each entry carries the enclosing symbol, if any.
+    let mut symbols: Vec<Symbol> = Vec::new();
+    let mut stack: Vec<(Node, Option<usize>)> = vec![(tree.root_node(), None)];
+    while let Some((node, parent)) = stack.pop() {
+        let parent_kind = parent.map(|p| symbols[p].kind);
+        let mut enclosing = parent;
+        if let Some((kind, name)) = declaration(lang, node, source) {
+            let kind = match (kind, parent_kind) {
+                ("function", Some(p)) if !matches!(p, "mod" | "namespace") => "method",
+                _ => kind,
+            };
+            symbols.push(Symbol {
+                kind,
+                name,
+                start: node.start_position().row,
+                end: node.end_position().row,
+                depth: parent.map_or(0, |p| symbols[p].depth + 1),
+            });
+            if is_callable(kind) {
+                continue;
+            }
+            enclosing = Some(symbols.len() - 1);
+        }
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        stack.extend(children.into_iter().rev().map(|child| (child, enclosing)));
+    }
+    Ok(symbols)
+}
+
+fn is_callable(kind: &str) -> bool {
+    matches!(kind, "function" | "method" | "constructor")
+}
+
+/// The outline kind and name of `node`, if it is a declaration.
+fn declaration(lang: Lang, node: Node, source: &str) -> Option<(&'static str, String)> {
+    let text = |node: Node| node.utf8_text(source.as_bytes()).unwrap_or("").to_string();
+    let name = || node.child_by_field_name("name").map(text);
+    let kind = match lang {
+        Lang::Rust => match node.kind() {
+            "function_item" | "function_signature_item" => "function",
+            "struct_item" => "struct",
+            "enum_item" => "enum",
+            "union_item" => "union",
+            "trait_item" => "trait",
+            "type_item" => "type",
+            "mod_item" => "mod",
+            "const_item" => "const",
+            "static_item" => "static",
+            "macro_definition" => "macro",
+            "impl_item" => {
+                let target = text(node.child_by_field_name("type")?);
+                let name = match node.child_by_field_name("trait") {
+                    Some(the_trait) => format!("{} for {target}", text(the_trait)),
+                    None => target,
+                };
+                return Some(("impl", name));
+            }
+            _ => return None,
+        },
+        Lang::Python => match node.kind() {
+            "function_definition" => "function",
+            "class_definition" => "class",
+            _ => return None,
+        },
+        Lang::Go => match node.kind() {
+            "function_declaration" => "function",
+            "method_declaration" => {
+                let receiver = node
+                    .child_by_field_name("receiver")?
+                    .named_child(0)?
+                    .child_by_field_name("type")?;
+                let receiver = text(receiver);
+                let receiver = receiver.trim_start_matches('*');
+                let receiver = receiver.split('[').next().unwrap_or(receiver);
+                return Some(("method", format!("{receiver}.{}", name()?)));
+            }
+            "type_spec" | "type_alias" => match node.child_by_field_name("type")?.kind() {
+                "struct_type" => "struct",
+                "interface_type" => "interface",
+                _ => "type",
+            },
+            _ => return None,
+        },
+        Lang::JavaScript | Lang::TypeScript | Lang::Tsx => match node.kind() {
+            "function_declaration" | "generator_function_declaration" => "function",
+            "class_declaration" | "abstract_class_declaration" => "class",
+            "interface_declaration" => "interface",
+            "enum_declaration" => "enum",
+            "type_alias_declaration" => "type",
+            "internal_module" | "module" => "namespace",
+            "method_definition" | "method_signature" | "abstract_method_signature" => "method",
+            "variable_declarator" => match node.child_by_field_name("value")?.kind() {
+                "arrow_function" | "function_expression" | "function" => "function",
+                _ => return None,
+            },
+            _ => return None,
+        },
+        Lang::Java | Lang::CSharp => match node.kind() {
+            "class_declaration" => "class",
+            "interface_declaration" | "annotation_type_declaration" => "interface",
+            "enum_declaration" => "enum",
+            "record_declaration" => "record",
+            "struct_declaration" => "struct",
+            "namespace_declaration" | "file_scoped_namespace_declaration" => "namespace",
+            "method_declaration" => "method",
+            "constructor_declaration" => "constructor",
+            _ => return None,
+        },
+        Lang::C | Lang::Cpp => match node.kind() {
+            "function_definition" => {
+                let mut declarator = node.child_by_field_name("declarator")?;
+                while let Some(inner) = declarator.child_by_field_name("declarator") {
+                    declarator = inner;
+                }
+                return Some(("function", text(declarator)));
+            }
+            "struct_specifier" | "class_specifier" | "enum_specifier" | "union_specifier" => {
+                node.child_by_field_name("body")?;
+                let kind = match node.kind() {
+                    "struct_specifier" => "struct",
+                    "enum_specifier" => "enum",
+                    "union_specifier" => "union",
+                    _ => "class",
+                };
+                // `typedef struct { ... } Name;` names the type after the body.
+                let name = name().or_else(|| {
+                    let typedef = node.parent().filter(|p| p.kind() == "type_definition")?;
+                    typedef.child_by_field_name("declarator").map(text)
+                })?;
+                return Some((kind, name));
+            }
+            "namespace_definition" => "namespace",
+            _ => return None,
+        },
+    };
+    Some((kind, name()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(source: &str, lang: Lang) -> Vec<String> {
+        outline(source, lang)
+            .unwrap()
+            .into_iter()
+            .map(|s| {
+                format!(
+                    "{}{} {} {}-{}",
+                    "  ".repeat(s.depth),
+                    s.kind,
+                    s.name,
+                    s.start + 1,
+                    s.end + 1
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn outlines_rust_items_with_nesting() {
+        let source = r#"//! Docs mentioning fn fake() {
+use std::fmt;
+
+pub struct Config {
+    name: String,
+}
+
+impl<T: Into<String>> From<T> for Config {
+    fn from(value: T) -> Self {
+        let s = "} fn nope() {";
+        let c = '{';
+        Config { name: value.into() }
+    }
+}
+
+pub(crate) async fn run<'a>(
+    config: &'a Config,
+) -> Result<(), String>
+where
+    'a: 'static,
+{
+    Ok(())
+}
+
+pub trait Named {
+    fn name(&self) -> &str;
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn works() {}
+}
+"#;
+        assert_eq!(
+            lines(source, Lang::Rust),
+            vec![
+                "struct Config 4-6",
+                "impl From<T> for Config 8-14",
+                "  method from 9-13",
+                "function run 16-23",
+                "trait Named 25-27",
+                "  method name 26-26",
+                "mod tests 30-33",
+                "  function works 32-32",
+            ]
+        );
+    }
+
+    #[test]
+    fn outlines_python_by_indentation() {
+        let source = "import os\n\nclass Store:\n    \"\"\"Holds things.\n\ndef not_real():\n    \"\"\"\n\n    def get(self,\n            key):\n        def helper():\n            pass\n        return key\n\n\nasync def main():\n    pass\n";
+        assert_eq!(
+            lines(source, Lang::Python),
+            vec![
+                "class Store 3-13",
+                "  method get 9-13",
+                "function main 16-17"
+            ]
+        );
+    }
+
+    #[test]
+    fn outlines_go_typescript_and_java() {
+        let go = "package main\n\ntype Server struct {\n\taddr string\n}\n\ntype ID int\n\nfunc (s *Server) Start() error {\n\treturn nil\n}\n\nfunc main() {\n}\n";
+        assert_eq!(
+            lines(go, Lang::Go),
+            vec![
+                "struct Server 3-5",
+                "type ID 7-7",
+                "method Server.Start 9-11",
+                "function main 13-14",
+            ]
+        );
+
+        let ts = "export interface Opts {\n  a: string;\n}\n\nexport class Client {\n  private url = `${base}/{x}`;\n  constructor(url: string) {\n    if (url) {\n    }\n  }\n  async fetch<T>(path: string): Promise<T> {\n    return get(path);\n  }\n}\n\nexport const handler = async (req) => {\n  return 1;\n};\n";
+        assert_eq!(
+            lines(ts, Lang::TypeScript),
+            vec![
+                "interface Opts 1-3",
+                "class Client 5-14",
+                "  method constructor 7-10",
+                "  method fetch 11-13",
+                "function handler 16-18",
+            ]
+        );
+
+        let java = "package x;\n\n@Service\npublic class Users {\n    private final Map<String, List<User>> cache = new HashMap<>();\n\n    public Users(Repo repo) {\n        this.repo = repo;\n    }\n\n    public Map<String, List<User>> byTeam(String team) {\n        return cache.get(team);\n    }\n}\n";
+        assert_eq!(
+            lines(java, Lang::Java),
+            vec![
+                "class Users 3-14",
+                "  constructor Users 7-9",
+                "  method byTeam 11-13",
+            ]
+        );
+    }
+
+    #[test]
+    fn outline_file_reports_ranges_and_rejects_unknown_types() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("lib.c"),
+            "#include <stdio.h>\n\nint add(int a, int b);\n\nstatic int add(int a, int b) {\n    return a + b;\n}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello").unwrap();
+
+        let out = outline_file(dir.path(), "lib.c");
+        assert!(out.starts_with("lib.c (c, 7 lines, 1 symbols)"), "{out}");
+        assert!(out.contains("function add  L5-7"), "{out}");
+        assert!(outline_file(dir.path(), "notes.txt").starts_with("Error: no outline support"));
+        assert!(outline_file(dir.path(), "../x.rs").starts_with("Access denied"));
+    }
+}
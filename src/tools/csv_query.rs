@@ -0,0 +1,697 @@
+//! `csv.query`: filter, aggregate and sample CSV files in the workspace.
+//!
+//! The model asks a question of the data instead of reading the file:
+//! `filters` keep matching rows (`amount > 100`, `status == paid`,
+//! `name ~ smith`), `group_by` with `aggregates` (`count`, `sum:amount`,
+//! `avg:amount`, ...) summarizes them, and only `head`/`tail` rows of the
+//! result come back, capped at [`MAX_ROWS`]. Values that parse as numbers
+//! compare and sort numerically. The delimiter (`,`, `;`, tab or `|`) is
+//! detected from the header line unless given.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::tools::documents::markdown_table;
+use crate::tools::file_ops::resolve_safe_path;
+
+/// Rows returned when neither `head` nor `tail` is given.
+pub const DEFAULT_ROWS: usize = 20;
+/// Most rows returned by one query.
+pub const MAX_ROWS: usize = 200;
+/// Files larger than this are not loaded.
+const MAX_FILE_BYTES: u64 = 100 * 1024 * 1024;
+/// Columns described in the result header.
+const MAX_DESCRIBED_COLUMNS: usize = 50;
+
+/// A `csv.query` request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CsvQuery {
+    pub path: String,
+    pub columns: Vec<String>,
+    pub filters: Vec<String>,
+    pub group_by: Vec<String>,
+    pub aggregates: Vec<String>,
+    /// Column to sort by; a leading `-` sorts descending.
+    pub sort_by: Option<String>,
+    pub head: Option<usize>,
+    pub tail: Option<usize>,
+    pub delimiter: Option<char>,
+}
+
+impl CsvQuery {
+    /// Query from tool call arguments. List parameters accept a JSON array
+    /// or a comma-separated string (filters: an array or a single string).
+    pub fn from_args(args: &serde_json::Value) -> Result<Self, String> {
+        let path = args["path"].as_str().unwrap_or_default().trim().to_string();
+        if path.is_empty() {
+            return Err("'path' argument is required.".to_string());
+        }
+        let filters = match &args["filters"] {
+            serde_json::Value::String(s) if !s.trim().is_empty() => vec![s.clone()],
+            value => string_list(value, false),
+        };
+        let delimiter = match args["delimiter"].as_str().unwrap_or_default() {
+            "" => None,
+            "\\t" | "tab" => Some('\t'),
+            d if d.chars().count() == 1 => d.chars().next(),
+            d => return Err(format!("delimiter must be a single character, got '{d}'")),
+        };
+        let query = CsvQuery {
+            path,
+            columns: string_list(&args["columns"], true),
+            filters,
+            group_by: string_list(&args["group_by"], true),
+            aggregates: string_list(&args["aggregates"], true),
+            sort_by: args["sort_by"]
+                .as_str()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(String::from),
+            head: args["head"].as_u64().map(|n| n as usize),
+            tail: args["tail"].as_u64().map(|n| n as usize),
+            delimiter,
+        };
+        if query.head.is_some() && query.tail.is_some() {
+            return Err("use either 'head' or 'tail', not both.".to_string());
+        }
+        Ok(query)
+    }
+}
+
+fn string_list(value: &serde_json::Value, split_commas: bool) -> Vec<String> {
+    match value {
+        serde_json::Value::Array(items) => items
+            .iter()
+            .filter_map(|v| v.as_str())
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
+        serde_json::Value::String(s) if split_commas => s
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Run `query` and describe the result as a markdown table.
+pub fn run_query(workspace: &Path, query: &CsvQuery) -> String {
+    match execute(workspace, query) {
+        Ok(output) => output,
+        Err(e) => format!("Error: {e}"),
+    }
+}
+
+fn execute(workspace: &Path, query: &CsvQuery) -> Result<String, String> {
+    let path = &query.path;
+    let file = resolve_safe_path(workspace, path)
+        .ok_or_else(|| format!("path escapes workspace: {path}"))?;
+    let size = std::fs::metadata(&file)
+        .ok()
+        .filter(|m| m.is_file())
+        .ok_or_else(|| format!("file not found: {path}"))?
+        .len();
+    if size > MAX_FILE_BYTES {
+        return Err(format!(
+            "{path} is {size} bytes; csv.query loads files up to {MAX_FILE_BYTES} bytes"
+        ));
+    }
+    let bytes = std::fs::read(&file).map_err(|e| format!("reading {path}: {e}"))?;
+    let text = String::from_utf8_lossy(&bytes);
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    let delimiter = query.delimiter.unwrap_or_else(|| detect_delimiter(text));
+    let mut records = parse_csv(text, delimiter).into_iter();
+    let headers = header_names(records.next().unwrap_or_default());
+    if headers.is_empty() {
+        return Err(format!("{path} is empty"));
+    }
+    let rows: Vec<Vec<String>> = records
+        .filter(|r| !(r.len() == 1 && r[0].is_empty()))
+        .map(|mut r| {
+            r.resize(headers.len(), String::new());
+            r
+        })
+        .collect();
+    let table = Table {
+        headers: &headers,
+        rows: &rows,
+    };
+
+    let filters = query
+        .filters
+        .iter()
+        .map(|f| Filter::parse(f, &table))
+        .collect::<Result<Vec<_>, _>>()?;
+    let matched: Vec<&Vec<String>> = rows
+        .iter()
+        .filter(|row| filters.iter().all(|f| f.matches(row)))
+        .collect();
+
+    let (out_headers, mut out_rows) = if query.group_by.is_empty() && query.aggregates.is_empty() {
+        let columns = if query.columns.is_empty() {
+            (0..headers.len()).collect()
+        } else {
+            table.columns(&query.columns)?
+        };
+        let out_headers: Vec<String> = columns.iter().map(|&i| headers[i].clone()).collect();
+        let mut selected = matched.clone();
+        if let Some(sort_by) = &query.sort_by {
+            let (name, descending) = sort_key(sort_by);
+            let column = table.column(name)?;
+            selected.sort_by(|a, b| ordered(compare(&a[column], &b[column]), descending));
+        }
+        let out_rows: Vec<Vec<String>> = selected
+            .iter()
+            .map(|row| columns.iter().map(|&i| row[i].clone()).collect())
+            .collect();
+        (out_headers, out_rows)
+    } else {
+        aggregate(&table, &matched, query)?
+    };
+
+    let total = out_rows.len();
+    let (range, shown) = match query.tail {
+        Some(n) => {
+            let n = n.clamp(1, MAX_ROWS).min(total);
+            let rows: Vec<_> = out_rows.drain(total - n..).collect();
+            (format!("last {n}"), rows)
+        }
+        None => {
+            let n = query
+                .head
+                .unwrap_or(DEFAULT_ROWS)
+                .clamp(1, MAX_ROWS)
+                .min(total);
+            out_rows.truncate(n);
+            (format!("first {n}"), out_rows)
+        }
+    };
+
+    let mut summary = format!(
+        "{path}: {} rows, {} columns: {}",
+        rows.len(),
+        headers.len(),
+        table.describe_columns()
+    );
+    if !filters.is_empty() {
+        summary.push_str(&format!("\n{} rows match the filters", matched.len()));
+    }
+    let noun = if query.group_by.is_empty() && query.aggregates.is_empty() {
+        "rows"
+    } else {
+        "result rows"
+    };
+    if total == 0 {
+        summary.push_str(&format!("\nNo {noun}."));
+        return Ok(summary);
+    }
+    summary.push_str(&format!("\nShowing {range} of {total} {noun}"));
+    let mut table_rows = vec![out_headers];
+    table_rows.extend(shown);
+    Ok(format!("{summary}\n\n{}", markdown_table(&table_rows)))
+}
+
+struct Table<'a> {
+    headers: &'a [String],
+    rows: &'a [Vec<String>],
+}
+
+impl Table<'_> {
+    /// Index of column `name`, matched exactly and then case-insensitively.
+    fn column(&self, name: &str) -> Result<usize, String> {
+        let name = name.trim();
+        self.headers
+            .iter()
+            .position(|h| h == name)
+            .or_else(|| {
+                self.headers
+                    .iter()
+                    .position(|h| h.eq_ignore_ascii_case(name))
+            })
+            .ok_or_else(|| {
+                format!(
+                    "unknown column '{name}'; columns are: {}",
+                    self.headers.join(", ")
+                )
+            })
+    }
+
+    fn columns(&self, names: &[String]) -> Result<Vec<usize>, String> {
+        names.iter().map(|n| self.column(n)).collect()
+    }
+
+    /// `name (number)` / `name (text)` for every column.
+    fn describe_columns(&self) -> String {
+        let mut described: Vec<String> = self
+            .headers
+            .iter()
+            .enumerate()
+            .take(MAX_DESCRIBED_COLUMNS)
+            .map(|(i, name)| {
+                let mut values = self
+                    .rows
+                    .iter()
+                    .map(|r| r[i].trim())
+                    .filter(|v| !v.is_empty());
+                let numeric =
+                    values.clone().next().is_some() && values.all(|v| number(v).is_some());
+                format!("{name} ({})", if numeric { "number" } else { "text" })
+            })
+            .collect();
+        if self.headers.len() > MAX_DESCRIBED_COLUMNS {
+            described.push(format!(
+                "... {} more",
+                self.headers.len() - MAX_DESCRIBED_COLUMNS
+            ));
+        }
+        described.join(", ")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Contains,
+    NotContains,
+}
+
+/// One `column op value` condition.
+struct Filter {
+    column: usize,
+    op: Op,
+    value: String,
+}
+
+impl Filter {
+    fn parse(expr: &str, table: &Table) -> Result<Self, String> {
+        const OPS: &[(&str, Op)] = &[
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            ("!~", Op::NotContains),
+            ("=", Op::Eq),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+            ("~", Op::Contains),
+        ];
+        let (at, symbol, op) = expr
+            .char_indices()
+            .find_map(|(i, _)| {
+                OPS.iter()
+                    .find(|(symbol, _)| expr[i..].starts_with(symbol))
+                    .map(|(symbol, op)| (i, *symbol, *op))
+            })
+            .ok_or_else(|| {
+                format!("filter '{expr}' has no operator (use ==, !=, >, >=, <, <=, ~ or !~)")
+            })?;
+        let column = table.column(&expr[..at])?;
+        let value = expr[at + symbol.len()..].trim();
+        let value = ["\"", "'"]
+            .iter()
+            .find_map(|q| value.strip_prefix(q).and_then(|v| v.strip_suffix(q)))
+            .unwrap_or(value);
+        Ok(Filter {
+            column,
+            op,
+            value: value.to_string(),
+        })
+    }
+
+    fn matches(&self, row: &[String]) -> bool {
+        let cell = row[self.column].trim();
+        match self.op {
+            Op::Contains => contains_ignore_case(cell, &self.value),
+            Op::NotContains => !contains_ignore_case(cell, &self.value),
+            op => {
+                let ordering = compare(cell, &self.value);
+                match op {
+                    Op::Eq => ordering == Ordering::Equal,
+                    Op::Ne => ordering != Ordering::Equal,
+                    Op::Gt => ordering == Ordering::Greater,
+                    Op::Ge => ordering != Ordering::Less,
+                    Op::Lt => ordering == Ordering::Less,
+                    _ => ordering != Ordering::Greater,
+                }
+            }
+        }
+    }
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+/// Grouped aggregates of `rows` as `(headers, rows)`.
+fn aggregate(
+    table: &Table,
+    rows: &[&Vec<String>],
+    query: &CsvQuery,
+) -> Result<(Vec<String>, Vec<Vec<String>>), String> {
+    let group_columns = table.columns(&query.group_by)?;
+    let aggregates = if query.aggregates.is_empty() {
+        vec![Aggregate::Count(None)]
+    } else {
+        query
+            .aggregates
+            .iter()
+            .map(|a| Aggregate::parse(a, table))
+            .collect::<Result<Vec<_>, _>>()?
+    };
+
+    let mut groups: BTreeMap<Vec<String>, Vec<&Vec<String>>> = BTreeMap::new();
+    for row in rows {
+        let key = group_columns.iter().map(|&i| row[i].clone()).collect();
+        groups.entry(key).or_default().push(row);
+    }
+    if groups.is_empty() && group_columns.is_empty() {
+        groups.insert(Vec::new(), Vec::new());
+    }
+
+    let mut headers: Vec<String> = group_columns
+        .iter()
+        .map(|&i| table.headers[i].clone())
+        .collect();
+    headers.extend(aggregates.iter().map(|a| a.label(table.headers)));
+    let mut out: Vec<Vec<String>> = groups
+        .into_iter()
+        .map(|(mut key, members)| {
+            key.extend(aggregates.iter().map(|a| a.apply(&members)));
+            key
+        })
+        .collect();
+
+    if let Some(sort_by) = &query.sort_by {
+        let (name, descending) = sort_key(sort_by);
+        let column = headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "cannot sort by '{name}'; result columns are: {}",
+                    headers.join(", ")
+                )
+            })?;
+        out.sort_by(|a, b| ordered(compare(&a[column], &b[column]), descending));
+    }
+    Ok((headers, out))
+}
+
+enum Aggregate {
+    /// Rows, or non-empty values of a column.
+    Count(Option<usize>),
+    Distinct(usize),
+    Sum(usize),
+    Avg(usize),
+    Min(usize),
+    Max(usize),
+}
+
+impl Aggregate {
+    /// `count`, `sum:amount` or `sum(amount)`.
+    fn parse(spec: &str, table: &Table) -> Result<Self, String> {
+        let spec = spec.trim();
+        let (function, column) = match spec.split_once(':') {
+            Some((f, c)) => (f, Some(c)),
+            None => match spec.split_once('(') {
+                Some((f, c)) => (f, Some(c.trim_end_matches(')'))),
+                None => (spec, None),
+            },
+        };
+        let column = column
+            .map(str::trim)
+            .filter(|c| !c.is_empty() && *c != "*")
+            .map(|c| table.column(c))
+            .transpose()?;
+        let function = function.trim().to_ascii_lowercase();
+        let need = |column: Option<usize>| {
+            column
+                .ok_or_else(|| format!("aggregate '{spec}' needs a column, e.g. {function}:amount"))
+        };
+        Ok(match function.as_str() {
+            "count" => Aggregate::Count(column),
+            "distinct" | "count_distinct" => Aggregate::Distinct(need(column)?),
+            "sum" => Aggregate::Sum(need(column)?),
+            "avg" | "mean" => Aggregate::Avg(need(column)?),
+            "min" => Aggregate::Min(need(column)?),
+            "max" => Aggregate::Max(need(column)?),
+            _ => {
+                return Err(format!(
+                    "unknown aggregate '{spec}' (count, distinct, sum, avg, min, max)"
+                ));
+            }
+        })
+    }
+
+    fn label(&self, headers: &[String]) -> String {
+        let (name, column) = match self {
+            Aggregate::Count(None) => return "count".to_string(),
+            Aggregate::Count(Some(c)) => ("count", c),
+            Aggregate::Distinct(c) => ("distinct", c),
+            Aggregate::Sum(c) => ("sum", c),
+            Aggregate::Avg(c) => ("avg", c),
+            Aggregate::Min(c) => ("min", c),
+            Aggregate::Max(c) => ("max", c),
+        };
+        format!("{name}({})", headers[*column])
+    }
+
+    fn apply(&self, rows: &[&Vec<String>]) -> String {
+        let values = |c: usize| {
+            rows.iter()
+                .map(move |r| r[c].trim())
+                .filter(|v| !v.is_empty())
+        };
+        let numbers = |c: usize| values(c).filter_map(number).collect::<Vec<f64>>();
+        match self {
+            Aggregate::Count(None) => rows.len().to_string(),
+            Aggregate::Count(Some(c)) => values(*c).count().to_string(),
+            Aggregate::Distinct(c) => {
+                let mut seen: Vec<&str> = values(*c).collect();
+                seen.sort_unstable();
+                seen.dedup();
+                seen.len().to_string()
+            }
+            Aggregate::Sum(c) => format_number(numbers(*c).iter().sum()),
+            Aggregate::Avg(c) => {
+                let numbers = numbers(*c);
+                if numbers.is_empty() {
+                    String::new()
+                } else {
+                    format_number(numbers.iter().sum::<f64>() / numbers.len() as f64)
+                }
+            }
+            Aggregate::Min(c) => values(*c)
+                .min_by(|a, b| compare(a, b))
+                .unwrap_or_default()
+                .to_string(),
+            Aggregate::Max(c) => values(*c)
+                .max_by(|a, b| compare(a, b))
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+}
+
+fn sort_key(spec: &str) -> (&str, bool) {
+    match spec.strip_prefix('-') {
+        Some(name) => (name.trim(), true),
+        None => (spec.trim(), false),
+    }
+}
+
+fn ordered(ordering: Ordering, descending: bool) -> Ordering {
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+/// Numeric comparison when both values are numbers, text otherwise.
+fn compare(a: &str, b: &str) -> Ordering {
+    match (number(a), number(b)) {
+        (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+        _ => a.trim().cmp(b.trim()),
+    }
+}
+
+/// `value` as a number, allowing thousands separators (`1,234.5`).
+fn number(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if value.is_empty() {
+        return None;
+    }
+    value
+        .parse::<f64>()
+        .ok()
+        .or_else(|| value.replace(',', "").parse().ok())
+        .filter(|n: &f64| n.is_finite())
+}
+
+fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        let text = format!("{n:.4}");
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    }
+}
+
+/// Unique header names; blank or repeated names get a `column_N` form.
+fn header_names(record: Vec<String>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::with_capacity(record.len());
+    for (i, name) in record.into_iter().enumerate() {
+        let name = name.trim().to_string();
+        if name.is_empty() || names.contains(&name) {
+            names.push(format!("column_{}", i + 1));
+        } else {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// The candidate delimiter occurring most often in the header line.
+fn detect_delimiter(text: &str) -> char {
+    let header = text.lines().next().unwrap_or_default();
+    [',', ';', '\t', '|']
+        .into_iter()
+        .max_by_key(|&d| (header.matches(d).count(), d == ','))
+        .unwrap_or(',')
+}
+
+/// RFC 4180 records: quoted fields may contain delimiters, newlines and
+/// doubled quotes.
+fn parse_csv(text: &str, delimiter: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                c => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c if c == delimiter => record.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    const SALES: &str = "region,product,amount,note\n\
+        EU,tea,\"1,200\",\"first, big\"\n\
+        US,tea,300,\n\
+        EU,coffee,50.5,\"said \"\"hi\"\"\"\n\
+        US,coffee,700,late\n";
+
+    fn query(args: serde_json::Value) -> String {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("sales.csv"), SALES).unwrap();
+        let mut args = args;
+        args["path"] = json!("sales.csv");
+        run_query(dir.path(), &CsvQuery::from_args(&args).unwrap())
+    }
+
+    #[test]
+    fn rows_are_filtered_selected_and_sorted() {
+        let output = query(json!({
+            "columns": "product, amount",
+            "filters": ["amount > 100"],
+            "sort_by": "-amount",
+        }));
+        assert!(
+            output.starts_with("sales.csv: 4 rows, 4 columns: region (text), product (text), amount (number), note (text)"),
+            "{output}"
+        );
+        assert!(output.contains("3 rows match the filters\nShowing first 3 of 3 rows"));
+        assert!(output.ends_with(
+            "| product | amount |\n| --- | --- |\n| tea | 1,200 |\n| coffee | 700 |\n| tea | 300 |"
+        ));
+
+        let output = query(json!({"filters": "note ~ HI", "columns": ["note"]}));
+        assert!(
+            output.ends_with("| note |\n| --- |\n| said \"hi\" |"),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn groups_are_aggregated() {
+        let output = query(json!({
+            "group_by": "region",
+            "aggregates": ["count", "sum:amount", "avg(amount)", "max:product"],
+        }));
+        assert!(
+            output.contains("Showing first 2 of 2 result rows"),
+            "{output}"
+        );
+        assert!(output.ends_with(
+            "| region | count | sum(amount) | avg(amount) | max(product) |\n\
+             | --- | --- | --- | --- | --- |\n\
+             | EU | 2 | 1250.5 | 625.25 | tea |\n\
+             | US | 2 | 1000 | 500 | tea |"
+        ));
+
+        let output = query(json!({"aggregates": "distinct:product", "filters": ["region == EU"]}));
+        assert!(
+            output.ends_with("| distinct(product) |\n| --- |\n| 2 |"),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn samples_and_errors() {
+        let output = query(json!({"tail": 1, "columns": "region"}));
+        assert!(output.ends_with("Showing last 1 of 4 rows\n\n| region |\n| --- |\n| US |"));
+
+        assert!(query(json!({"columns": "price"})).starts_with("Error: unknown column 'price'"));
+        assert!(query(json!({"filters": ["amount"]})).contains("has no operator"));
+        assert!(query(json!({"aggregates": "sum"})).contains("needs a column"));
+        assert!(CsvQuery::from_args(&json!({"path": "a.csv", "head": 1, "tail": 1})).is_err());
+    }
+
+    #[test]
+    fn csv_parsing_follows_rfc_4180() {
+        let records = parse_csv("a;b\r\n\"x;\ny\";2\n", ';');
+        assert_eq!(records, vec![vec!["a", "b"], vec!["x;\ny", "2"]]);
+        assert_eq!(detect_delimiter("a\tb\tc\n1,2\t3"), '\t');
+        assert_eq!(
+            header_names(vec!["id".into(), "".into(), "id".into()]),
+            vec!["id", "column_2", "column_3"]
+        );
+    }
+}
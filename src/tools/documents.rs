@@ -0,0 +1,645 @@
+//! `doc.extract`: PDF, DOCX and XLSX files as plain text and markdown.
+//!
+//! PDFs are read with the `pdf-extract` crate through [`crate::tools::pdf`],
+//! one `## Page N` section per page. Only the text layer is read: scanned
+//! pages come back empty, a page the parser fails on is left empty rather
+//! than failing the whole file, and encrypted PDFs are rejected unless they
+//! open with the empty password. DOCX paragraphs become markdown, with
+//! headings, list items and tables. Each XLSX sheet becomes a markdown
+//! table under its name. `pages` selects PDF pages or XLSX sheets
+//! (`1-3,5`), and the text is cut to `max_chars` with a note on how to see
+//! more.
+
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use crate::tools::file_ops::resolve_safe_path;
+use crate::tools::pdf;
+
+/// Characters returned when `max_chars` is not given.
+pub const DEFAULT_MAX_CHARS: usize = 20_000;
+/// Largest accepted `max_chars`.
+const MAX_CHARS_LIMIT: usize = 200_000;
+/// Documents larger than this are not opened.
+const MAX_DOCUMENT_BYTES: u64 = 50 * 1024 * 1024;
+/// Decompressed size accepted for one XML part of a DOCX or XLSX file.
+const MAX_PART_BYTES: u64 = 64 * 1024 * 1024;
+/// Rows rendered per XLSX sheet.
+const MAX_SHEET_ROWS: usize = 5_000;
+
+/// Extract the text of the workspace document at `path`.
+pub fn extract_document(workspace: &Path, path: &str, pages: &str, max_chars: usize) -> String {
+    let Some(file) = resolve_safe_path(workspace, path) else {
+        return format!("Access denied: path escapes workspace: {path}");
+    };
+    let size = match std::fs::metadata(&file) {
+        Ok(meta) if meta.is_file() => meta.len(),
+        _ => return format!("File not found: {path}"),
+    };
+    if size > MAX_DOCUMENT_BYTES {
+        return format!(
+            "Error: {path} is {size} bytes; doc.extract reads files up to {MAX_DOCUMENT_BYTES} bytes."
+        );
+    }
+    let bytes = match std::fs::read(&file) {
+        Ok(bytes) => bytes,
+        Err(e) => return format!("Error reading file: {e}"),
+    };
+    let extension = file
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+
+    let result = if bytes.starts_with(b"%PDF") {
+        pdf::extract_pages(&bytes).and_then(|texts| {
+            let selected = select(pages, texts.len(), "page")?;
+            let empty = selected.iter().all(|&i| texts[i].is_empty());
+            let body = selected
+                .iter()
+                .map(|&i| format!("## Page {}\n\n{}", i + 1, texts[i]))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let mut header = format!("{path}: PDF, {} page(s)", texts.len());
+            if empty {
+                header.push_str("\n[no text found; the pages may be scanned images]");
+            }
+            Ok((header, body))
+        })
+    } else if extension == "docx" {
+        docx_text(&bytes).map(|body| {
+            let mut header = format!("{path}: DOCX");
+            if !pages.trim().is_empty() {
+                header.push_str("\n[DOCX files have no fixed pages; showing the whole document]");
+            }
+            (header, body)
+        })
+    } else if extension == "xlsx" {
+        xlsx_sheets(&bytes).and_then(|sheets| {
+            let selected = select(pages, sheets.len(), "sheet")?;
+            let body = selected
+                .iter()
+                .map(|&i| format!("## Sheet: {}\n\n{}", sheets[i].0, sheets[i].1))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            Ok((format!("{path}: XLSX, {} sheet(s)", sheets.len()), body))
+        })
+    } else {
+        return format!("Error: unsupported document {path} (expected .pdf, .docx or .xlsx)");
+    };
+
+    match result {
+        Ok((header, body)) => {
+            let limit = max_chars.clamp(1, MAX_CHARS_LIMIT);
+            let total = body.chars().count();
+            match body.char_indices().nth(limit) {
+                Some((cut, _)) => format!(
+                    "{header}\n\n{}\n\n[truncated at {limit} of {total} characters; select pages or raise max_chars to see more]",
+                    &body[..cut]
+                ),
+                None => format!("{header}\n\n{body}"),
+            }
+        }
+        Err(e) => format!("Error reading {path}: {e}"),
+    }
+}
+
+/// Zero-based indices selected by a `1-3,5` style `spec` out of `count`.
+fn select(spec: &str, count: usize, unit: &str) -> Result<Vec<usize>, String> {
+    let spec = spec.trim();
+    if spec.is_empty() || spec.eq_ignore_ascii_case("all") {
+        return Ok((0..count).collect());
+    }
+    let number = |s: &str| -> Result<usize, String> {
+        let n: usize = s
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid {unit} selection '{spec}'"))?;
+        if n == 0 || n > count {
+            return Err(format!("{unit} {n} is out of range (1-{count})"));
+        }
+        Ok(n)
+    };
+    let mut selected = Vec::new();
+    for part in spec.split(',') {
+        let (start, end) = match part.split_once('-') {
+            Some((start, end)) if end.trim().is_empty() => (number(start)?, count),
+            Some((start, end)) => (number(start)?, number(end)?),
+            None => {
+                let n = number(part)?;
+                (n, n)
+            }
+        };
+        for n in start..=end {
+            if !selected.contains(&(n - 1)) {
+                selected.push(n - 1);
+            }
+        }
+    }
+    Ok(selected)
+}
+
+/// Text of the zip member `name`, if present.
+fn zip_part(zip: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Option<String> {
+    let entry = zip.by_name(name).ok()?;
+    let mut text = String::new();
+    entry.take(MAX_PART_BYTES).read_to_string(&mut text).ok()?;
+    Some(text)
+}
+
+fn open_zip(bytes: &[u8]) -> Result<zip::ZipArchive<Cursor<&[u8]>>, String> {
+    zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| format!("not a valid Office file: {e}"))
+}
+
+/// DOCX body as markdown.
+fn docx_text(bytes: &[u8]) -> Result<String, String> {
+    let mut zip = open_zip(bytes)?;
+    let xml = zip_part(&mut zip, "word/document.xml").ok_or("word/document.xml is missing")?;
+
+    let mut blocks: Vec<String> = Vec::new();
+    let mut paragraph = String::new();
+    let mut prefix = String::new();
+    let mut in_text = false;
+    // Open tables, innermost last: rows of cells.
+    let mut tables: Vec<Vec<Vec<String>>> = Vec::new();
+    for event in XmlEvents::new(&xml) {
+        match event {
+            Xml::Open("p", _) => {
+                paragraph.clear();
+                prefix.clear();
+            }
+            Xml::Open("pStyle", attrs) | Xml::Empty("pStyle", attrs) => {
+                let style = attr(attrs, "val").unwrap_or_default().to_ascii_lowercase();
+                let level = match style.strip_prefix("heading") {
+                    Some(n) => n.trim().parse::<usize>().unwrap_or(1).clamp(1, 6),
+                    None if style == "title" => 1,
+                    None => 0,
+                };
+                if level > 0 {
+                    prefix = format!("{} ", "#".repeat(level));
+                }
+            }
+            Xml::Open("numPr", _) if prefix.is_empty() => prefix = "- ".to_string(),
+            Xml::Open("t", _) => in_text = true,
+            Xml::Close("t") => in_text = false,
+            Xml::Text(text) if in_text => paragraph.push_str(&unescape(text)),
+            // `<w:tab/>` in a run is a tab; with `w:pos` it is a tab stop.
+            Xml::Empty("tab", attrs) if attr(attrs, "pos").is_none() => paragraph.push('\t'),
+            Xml::Empty("br" | "cr", _) => paragraph.push('\n'),
+            Xml::Open("tbl", _) => tables.push(Vec::new()),
+            Xml::Open("tr", _) => {
+                if let Some(table) = tables.last_mut() {
+                    table.push(Vec::new());
+                }
+            }
+            Xml::Open("tc", _) => {
+                if let Some(row) = tables.last_mut().and_then(|t| t.last_mut()) {
+                    row.push(String::new());
+                }
+            }
+            Xml::Close("p") => {
+                let text = paragraph.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                let line = format!("{prefix}{text}");
+                match tables
+                    .last_mut()
+                    .and_then(|t| t.last_mut())
+                    .and_then(|r| r.last_mut())
+                {
+                    Some(cell) => push_cell_text(cell, text),
+                    None => blocks.push(line),
+                }
+            }
+            Xml::Close("tbl") => {
+                let table = markdown_table(&tables.pop().unwrap_or_default());
+                match tables
+                    .last_mut()
+                    .and_then(|t| t.last_mut())
+                    .and_then(|r| r.last_mut())
+                {
+                    Some(cell) => push_cell_text(cell, &table),
+                    None if !table.is_empty() => blocks.push(table),
+                    None => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    // Consecutive list items read better without blank lines between them.
+    let mut out = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            let list = block.starts_with("- ") && blocks[i - 1].starts_with("- ");
+            out.push_str(if list { "\n" } else { "\n\n" });
+        }
+        out.push_str(block);
+    }
+    Ok(out)
+}
+
+fn push_cell_text(cell: &mut String, text: &str) {
+    if !cell.is_empty() {
+        cell.push(' ');
+    }
+    cell.push_str(text);
+}
+
+/// XLSX sheets as `(name, markdown table)` in workbook order.
+fn xlsx_sheets(bytes: &[u8]) -> Result<Vec<(String, String)>, String> {
+    let mut zip = open_zip(bytes)?;
+    let workbook = zip_part(&mut zip, "xl/workbook.xml").ok_or("xl/workbook.xml is missing")?;
+    let rels = zip_part(&mut zip, "xl/_rels/workbook.xml.rels").unwrap_or_default();
+    let shared = zip_part(&mut zip, "xl/sharedStrings.xml")
+        .map(|xml| shared_strings(&xml))
+        .unwrap_or_default();
+
+    let targets: Vec<(String, String)> = XmlEvents::new(&rels)
+        .filter_map(|event| match event {
+            Xml::Empty("Relationship", attrs) | Xml::Open("Relationship", attrs) => {
+                Some((attr(attrs, "Id")?, attr(attrs, "Target")?))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let mut sheets = Vec::new();
+    for (index, event) in XmlEvents::new(&workbook)
+        .filter(|e| matches!(e, Xml::Empty("sheet", _) | Xml::Open("sheet", _)))
+        .enumerate()
+    {
+        let (Xml::Empty(_, attrs) | Xml::Open(_, attrs)) = event else {
+            continue;
+        };
+        let name = attr(attrs, "name").unwrap_or_else(|| format!("Sheet{}", index + 1));
+        let part = attr(attrs, "r:id")
+            .and_then(|id| targets.iter().find(|(rid, _)| *rid == id))
+            .map(|(_, target)| match target.strip_prefix('/') {
+                Some(absolute) => absolute.to_string(),
+                None => format!("xl/{target}"),
+            })
+            .unwrap_or_else(|| format!("xl/worksheets/sheet{}.xml", index + 1));
+        let rows = zip_part(&mut zip, &part)
+            .map(|xml| sheet_rows(&xml, &shared))
+            .unwrap_or_default();
+        let mut table = markdown_table(&rows);
+        if rows.len() >= MAX_SHEET_ROWS {
+            table.push_str(&format!(
+                "\n\n[only the first {MAX_SHEET_ROWS} rows are shown]"
+            ));
+        }
+        if table.is_empty() {
+            table = "(empty)".to_string();
+        }
+        sheets.push((name, table));
+    }
+    if sheets.is_empty() {
+        return Err("workbook has no sheets".to_string());
+    }
+    Ok(sheets)
+}
+
+fn shared_strings(xml: &str) -> Vec<String> {
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    let mut in_text = false;
+    for event in XmlEvents::new(xml) {
+        match event {
+            Xml::Open("si", _) => current.clear(),
+            Xml::Close("si") => strings.push(std::mem::take(&mut current)),
+            Xml::Empty("si", _) => strings.push(String::new()),
+            Xml::Open("t", _) => in_text = true,
+            Xml::Close("t") => in_text = false,
+            Xml::Text(text) if in_text => current.push_str(&unescape(text)),
+            _ => {}
+        }
+    }
+    strings
+}
+
+fn sheet_rows(xml: &str, shared: &[String]) -> Vec<Vec<String>> {
+    let mut rows: Vec<Vec<String>> = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    let mut column = 0usize;
+    let mut cell_type = String::new();
+    let mut value = String::new();
+    let mut in_value = false;
+    for event in XmlEvents::new(xml) {
+        match event {
+            Xml::Open("row", _) => row.clear(),
+            Xml::Close("row") | Xml::Empty("row", _) => {
+                rows.push(std::mem::take(&mut row));
+                if rows.len() >= MAX_SHEET_ROWS {
+                    break;
+                }
+            }
+            Xml::Open("c", attrs) | Xml::Empty("c", attrs) => {
+                column = attr(attrs, "r")
+                    .and_then(|r| column_index(&r))
+                    .unwrap_or(row.len());
+                cell_type = attr(attrs, "t").unwrap_or_default();
+                value.clear();
+            }
+            Xml::Open("v" | "t", _) => in_value = true,
+            Xml::Close("v" | "t") => in_value = false,
+            Xml::Text(text) if in_value => value.push_str(&unescape(text)),
+            Xml::Close("c") => {
+                let text = match cell_type.as_str() {
+                    "s" => value
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| shared.get(i).cloned())
+                        .unwrap_or_default(),
+                    "b" => if value.trim() == "1" { "TRUE" } else { "FALSE" }.to_string(),
+                    _ => value.clone(),
+                };
+                if row.len() <= column {
+                    row.resize(column + 1, String::new());
+                }
+                row[column] = text;
+            }
+            _ => {}
+        }
+    }
+    // Drop trailing empty rows left by formatting.
+    while rows.last().is_some_and(|r| r.iter().all(String::is_empty)) {
+        rows.pop();
+    }
+    rows
+}
+
+/// Zero-based column of a cell reference such as `C7` or `AA10`.
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: String = reference
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .collect();
+    if letters.is_empty() {
+        return None;
+    }
+    let n = letters.chars().fold(0usize, |acc, c| {
+        acc * 26 + (c.to_ascii_uppercase() as usize - 'A' as usize + 1)
+    });
+    Some(n - 1)
+}
+
+/// Markdown table with the first row as header.
+pub(crate) fn markdown_table(rows: &[Vec<String>]) -> String {
+    let width = rows.iter().map(Vec::len).max().unwrap_or(0);
+    if width == 0 {
+        return String::new();
+    }
+    let line = |row: &[String]| {
+        let cells: Vec<String> = (0..width)
+            .map(|i| {
+                row.get(i)
+                    .map(|c| c.replace('|', "\\|").replace('\n', " "))
+                    .unwrap_or_default()
+            })
+            .collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = vec![line(&rows[0]), format!("|{}", " --- |".repeat(width))];
+    lines.extend(rows[1..].iter().map(|r| line(r)));
+    lines.join("\n")
+}
+
+/// Events of a tag-level XML scan. Names have their namespace prefix
+/// removed; attributes are left raw for [`attr`].
+#[derive(Debug, PartialEq)]
+enum Xml<'a> {
+    Open(&'a str, &'a str),
+    Close(&'a str),
+    Empty(&'a str, &'a str),
+    Text(&'a str),
+}
+
+struct XmlEvents<'a> {
+    rest: &'a str,
+}
+
+impl<'a> XmlEvents<'a> {
+    fn new(xml: &'a str) -> Self {
+        Self { rest: xml }
+    }
+}
+
+impl<'a> Iterator for XmlEvents<'a> {
+    type Item = Xml<'a>;
+
+    fn next(&mut self) -> Option<Xml<'a>> {
+        loop {
+            if self.rest.is_empty() {
+                return None;
+            }
+            if !self.rest.starts_with('<') {
+                let end = self.rest.find('<').unwrap_or(self.rest.len());
+                let text = &self.rest[..end];
+                self.rest = &self.rest[end..];
+                return Some(Xml::Text(text));
+            }
+            if let Some(cdata) = self.rest.strip_prefix("<![CDATA[") {
+                let end = cdata.find("]]>").unwrap_or(cdata.len());
+                self.rest = cdata.get(end + 3..).unwrap_or("");
+                return Some(Xml::Text(&cdata[..end]));
+            }
+            let end = self.rest.find('>')?;
+            let tag = &self.rest[1..end];
+            self.rest = &self.rest[end + 1..];
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if let Some(name) = tag.strip_prefix('/') {
+                return Some(Xml::Close(local_name(name.trim())));
+            }
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag, false),
+            };
+            let (name, attrs) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            let name = local_name(name);
+            return Some(if empty {
+                Xml::Empty(name, attrs)
+            } else {
+                Xml::Open(name, attrs)
+            });
+        }
+    }
+}
+
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Value of attribute `key` in raw `attrs`. A key without a prefix also
+/// matches a prefixed attribute (`val` finds `w:val`).
+fn attr(attrs: &str, key: &str) -> Option<String> {
+    let mut rest = attrs;
+    while let Some(eq) = rest.find('=') {
+        let name = rest[..eq].trim();
+        let after = rest[eq + 1..].trim_start();
+        let quote = after.chars().next()?;
+        let end = after[1..].find(quote)? + 1;
+        if name == key || (!key.contains(':') && local_name(name) == key) {
+            return Some(unescape(&after[1..end]));
+        }
+        rest = &after[end + 1..];
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let Some(semi) = rest.find(';').filter(|&i| i <= 10) else {
+            out.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..semi];
+        let decoded = match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(str::parse))
+                .and_then(Result::ok)
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::tempdir;
+
+    fn office_file(path: &Path, parts: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, xml) in parts {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default())
+                .unwrap();
+            zip.write_all(xml.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn docx_becomes_markdown() {
+        let dir = tempdir().unwrap();
+        office_file(
+            &dir.path().join("report.docx"),
+            &[(
+                "word/document.xml",
+                r#"<?xml version="1.0"?><w:document xmlns:w="x"><w:body>
+<w:p><w:pPr><w:pStyle w:val="Heading1"/></w:pPr><w:r><w:t>Summary</w:t></w:r></w:p>
+<w:p><w:r><w:t xml:space="preserve">Sales &amp; </w:t></w:r><w:r><w:t>costs</w:t></w:r></w:p>
+<w:p><w:pPr><w:numPr><w:ilvl w:val="0"/></w:numPr></w:pPr><w:r><w:t>one</w:t></w:r></w:p>
+<w:p><w:pPr><w:numPr><w:ilvl w:val="0"/></w:numPr></w:pPr><w:r><w:t>two</w:t></w:r></w:p>
+<w:tbl><w:tr><w:tc><w:p><w:r><w:t>Region</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>Total</w:t></w:r></w:p></w:tc></w:tr>
+<w:tr><w:tc><w:p><w:r><w:t>EU</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>12|5</w:t></w:r></w:p></w:tc></w:tr></w:tbl>
+</w:body></w:document>"#,
+            )],
+        );
+
+        let text = extract_document(dir.path(), "report.docx", "", DEFAULT_MAX_CHARS);
+        assert_eq!(
+            text,
+            "report.docx: DOCX\n\n# Summary\n\nSales & costs\n\n- one\n- two\n\n\
+             | Region | Total |\n| --- | --- |\n| EU | 12\\|5 |"
+        );
+    }
+
+    #[test]
+    fn xlsx_sheets_are_selected_and_rendered() {
+        let dir = tempdir().unwrap();
+        office_file(
+            &dir.path().join("data.xlsx"),
+            &[
+                (
+                    "xl/workbook.xml",
+                    r#"<workbook><sheets><sheet name="Q1" sheetId="1" r:id="rId1"/><sheet name="Q2" sheetId="2" r:id="rId2"/></sheets></workbook>"#,
+                ),
+                (
+                    "xl/_rels/workbook.xml.rels",
+                    r#"<Relationships><Relationship Id="rId1" Target="worksheets/sheet1.xml"/><Relationship Id="rId2" Target="/xl/worksheets/other.xml"/></Relationships>"#,
+                ),
+                (
+                    "xl/sharedStrings.xml",
+                    r#"<sst><si><t>Item</t></si><si><r><t>Pri</t></r><r><t>ce</t></r></si></sst>"#,
+                ),
+                (
+                    "xl/worksheets/sheet1.xml",
+                    r#"<worksheet><sheetData><row r="1"><c r="A1" t="s"><v>0</v></c><c r="C1" t="s"><v>1</v></c></row><row r="2"><c r="A2" t="inlineStr"><is><t>tea</t></is></c><c r="C2"><v>3.5</v></c></row></sheetData></worksheet>"#,
+                ),
+                (
+                    "xl/worksheets/other.xml",
+                    r#"<worksheet><sheetData><row r="1"><c r="A1" t="b"><v>1</v></c></row></sheetData></worksheet>"#,
+                ),
+            ],
+        );
+
+        let all = extract_document(dir.path(), "data.xlsx", "", DEFAULT_MAX_CHARS);
+        assert!(all.starts_with("data.xlsx: XLSX, 2 sheet(s)"), "{all}");
+        assert!(all.contains(
+            "## Sheet: Q1\n\n| Item |  | Price |\n| --- | --- | --- |\n| tea |  | 3.5 |"
+        ));
+        assert!(all.contains("## Sheet: Q2\n\n| TRUE |"));
+
+        let second = extract_document(dir.path(), "data.xlsx", "2", DEFAULT_MAX_CHARS);
+        assert!(!second.contains("Q1") && second.contains("Q2"), "{second}");
+        let bad = extract_document(dir.path(), "data.xlsx", "3", DEFAULT_MAX_CHARS);
+        assert!(bad.contains("sheet 3 is out of range (1-2)"), "{bad}");
+    }
+
+    #[test]
+    fn pdf_pages_are_selected_and_bounded() {
+        let dir = tempdir().unwrap();
+        let pdf = crate::tools::pdf::tests::sample_pdf(&["first page", "second page", "third"]);
+        std::fs::write(dir.path().join("r.pdf"), pdf).unwrap();
+
+        let text = extract_document(dir.path(), "r.pdf", "2-", DEFAULT_MAX_CHARS);
+        assert_eq!(
+            text,
+            "r.pdf: PDF, 3 page(s)\n\n## Page 2\n\nsecond page\n\n## Page 3\n\nthird"
+        );
+        let cut = extract_document(dir.path(), "r.pdf", "1", 12);
+        assert!(
+            cut.contains("## Page 1\n\nf\n\n[truncated at 12 of 21 characters"),
+            "{cut}"
+        );
+    }
+
+    #[test]
+    fn selections_and_entities_parse() {
+        assert_eq!(select("1-2, 4, 2", 5, "page").unwrap(), vec![0, 1, 3]);
+        assert!(select("0", 5, "page").is_err());
+        assert!(select("x", 5, "page").is_err());
+        assert_eq!(unescape("a &lt;b&gt; &#233;&#x4F60; & c"), "a <b> é你 & c");
+        assert_eq!(column_index("AB12"), Some(27));
+    }
+}
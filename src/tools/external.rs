@@ -0,0 +1,317 @@
+//! Command-backed tools declared in `.agent/tools.json`.
+//!
+//! A lighter way to give the model a simple wrapper than writing a skill:
+//!
+//! ```json
+//! {
+//!   "tools": [
+//!     {
+//!       "name": "jira.issue",
+//!       "description": "Show a Jira issue",
+//!       "parameters": {
+//!         "type": "object",
+//!         "properties": { "key": { "type": "string" } },
+//!         "required": ["key"]
+//!       },
+//!       "command": "jira issue view {{key}} --plain",
+//!       "timeout_secs": 60
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! The manifest is checked when it is loaded: names must be unique and not
+//! shadow built-in or skill tools, `parameters` must be an object schema,
+//! and every `{{placeholder}}` in `command` must be one of its properties.
+//! Arguments are validated against `parameters` before the command runs.
+//! Each placeholder is replaced with the shell-quoted argument (an empty
+//! string when it was left out), and the command runs like `shell.exec`:
+//! under the shell policy and sandbox, and recorded in the audit log. An
+//! approver, when the channel has one, is shown that expanded command.
+//!
+//! The manifest is read when the tool registry is built, not on every call;
+//! editing it while a session runs rebuilds the registry (see
+//! `AgentLoop::refresh_workspace_context`).
+
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::core::shell::{self, CommandOrigin, ShellKind};
+use crate::tape::store::TapeStore;
+
+/// Workspace file declaring the tools.
+pub const MANIFEST_FILE: &str = ".agent/tools.json";
+/// Registry source of declared tools.
+pub const SOURCE: &str = "workspace";
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    #[serde(default)]
+    tools: Vec<ExternalTool>,
+}
+
+/// One declared tool.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExternalTool {
+    pub name: String,
+    pub description: String,
+    /// JSON Schema of the arguments (default: no arguments).
+    #[serde(default = "no_parameters")]
+    pub parameters: Value,
+    /// Command line with `{{argument}}` placeholders.
+    pub command: String,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn no_parameters() -> Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+fn default_timeout_secs() -> u64 {
+    shell::DEFAULT_TIMEOUT_SECS
+}
+
+/// Tools declared in the workspace's manifest; empty when there is none.
+pub fn load(workspace: &Path) -> Result<Vec<ExternalTool>, String> {
+    let Ok(raw) = std::fs::read_to_string(workspace.join(MANIFEST_FILE)) else {
+        return Ok(Vec::new());
+    };
+    let manifest: Manifest =
+        serde_json::from_str(&raw).map_err(|e| format!("invalid {MANIFEST_FILE}: {e}"))?;
+    let builtins: Vec<&str> = crate::tools::registry::builtin_tool_specs()
+        .iter()
+        .map(|spec| spec.name)
+        .collect();
+    let mut seen = std::collections::HashSet::new();
+    for tool in &manifest.tools {
+        let problem = if let Err(problem) = check(tool) {
+            Some(problem)
+        } else if builtins.contains(&tool.name.as_str()) || tool.name.starts_with("skill.") {
+            Some("the name is taken by a built-in or skill tool".to_string())
+        } else if !seen.insert(tool.name.as_str()) {
+            Some("the name is declared twice".to_string())
+        } else {
+            None
+        };
+        if let Some(problem) = problem {
+            return Err(format!(
+                "invalid {MANIFEST_FILE}: tool '{}': {problem}",
+                tool.name
+            ));
+        }
+    }
+    Ok(manifest.tools)
+}
+
+fn check(tool: &ExternalTool) -> Result<(), String> {
+    let valid_name = !tool.name.is_empty()
+        && tool
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if !valid_name {
+        return Err("names may only use letters, digits, '.', '_' and '-'".to_string());
+    }
+    if tool.description.trim().is_empty() {
+        return Err("description is empty".to_string());
+    }
+    if tool.command.trim().is_empty() {
+        return Err("command is empty".to_string());
+    }
+    if tool.parameters.get("type").and_then(Value::as_str) != Some("object") {
+        return Err("parameters must be a schema with \"type\": \"object\"".to_string());
+    }
+    let properties = tool.parameters.get("properties").and_then(Value::as_object);
+    for placeholder in placeholders(&tool.command) {
+        if !properties.is_some_and(|p| p.contains_key(placeholder)) {
+            return Err(format!(
+                "command uses {{{{{placeholder}}}}}, which is not in parameters.properties"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Names inside `{{...}}` in `command`, in order.
+fn placeholders(command: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = command;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        names.push(rest[start + 2..start + 2 + len].trim());
+        rest = &rest[start + 2 + len + 2..];
+    }
+    names
+}
+
+impl ExternalTool {
+    /// Command line for `args`, each placeholder replaced with the quoted
+    /// argument.
+    pub fn render(&self, args: &Value, shell: ShellKind) -> String {
+        let mut command = String::new();
+        let mut rest = self.command.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            let value = match args.get(rest[start + 2..start + 2 + len].trim()) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => s.clone(),
+                Some(other) => other.to_string(),
+            };
+            command.push_str(&rest[..start]);
+            command.push_str(&shell.quote(&value));
+            rest = &rest[start + 2 + len + 2..];
+        }
+        command.push_str(rest);
+        command
+    }
+
+    /// What the approver is shown for a call: the command line `args`
+    /// expand to, like `shell.exec`'s `command`, and the arguments.
+    pub fn approval_args(&self, args: &str) -> String {
+        let value: Value = serde_json::from_str(args).unwrap_or_else(|_| serde_json::json!({}));
+        serde_json::json!({
+            "command": self.render(&value, ShellKind::detect()),
+            "arguments": value,
+        })
+        .to_string()
+    }
+
+    /// Validate `args` and run the command.
+    pub fn run(&self, args: &str, tape: &TapeStore, workspace: &Path) -> String {
+        if let Some(error) =
+            crate::tools::registry::argument_error(&self.name, args, &self.parameters)
+        {
+            return error;
+        }
+        let value: Value = serde_json::from_str(args).unwrap_or_else(|_| serde_json::json!({}));
+        let command = self.render(&value, ShellKind::detect());
+        let result = shell::execute_shell_as_with_timeout(
+            &command,
+            workspace,
+            CommandOrigin::Tool,
+            Duration::from_secs(self.timeout_secs),
+        );
+        let output = shell::format_shell_output(&result);
+        let ok = result.exit_code == 0 && !result.timed_out;
+        crate::core::audit::record(workspace, tape, "tool", &self.name, &command, ok, &output);
+        if ok {
+            output
+        } else {
+            shell::wrap_failure_context(&command, &result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_manifest(workspace: &Path, json: &str) {
+        std::fs::create_dir_all(workspace.join(".agent")).unwrap();
+        std::fs::write(workspace.join(MANIFEST_FILE), json).unwrap();
+    }
+
+    #[test]
+    fn manifests_are_checked_when_loaded() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(load(dir.path()), Ok(Vec::new()));
+
+        let tool = |name: &str, command: &str| {
+            format!(
+                r#"{{"tools": [{{"name": "{name}", "description": "d", "command": "{command}",
+                "parameters": {{"type": "object", "properties": {{"q": {{"type": "string"}}}}}}}}]}}"#
+            )
+        };
+        write_manifest(dir.path(), &tool("grep.todo", "grep -rn {{q}} ."));
+        let tools = load(dir.path()).unwrap();
+        assert_eq!(tools[0].name, "grep.todo");
+        assert_eq!(tools[0].timeout_secs, shell::DEFAULT_TIMEOUT_SECS);
+
+        for (name, command, problem) in [
+            (
+                "grep.todo",
+                "grep {{pattern}}",
+                "not in parameters.properties",
+            ),
+            ("shell.exec", "ls", "taken by a built-in"),
+            ("bad name", "ls", "names may only use"),
+        ] {
+            write_manifest(dir.path(), &tool(name, command));
+            let error = load(dir.path()).unwrap_err();
+            assert!(error.contains(problem), "{error}");
+        }
+        write_manifest(dir.path(), r#"{"tools": [{"name": "a"}]}"#);
+        assert!(
+            load(dir.path())
+                .unwrap_err()
+                .starts_with("invalid .agent/tools.json")
+        );
+    }
+
+    #[test]
+    fn arguments_are_quoted_into_the_command() {
+        let tool = ExternalTool {
+            name: "say".to_string(),
+            description: "d".to_string(),
+            parameters: no_parameters(),
+            command: "echo {{text}} {{ count }} {{missing}}".to_string(),
+            timeout_secs: 5,
+        };
+        let args = serde_json::json!({"text": "it's $HOME", "count": 3});
+        assert_eq!(
+            tool.render(&args, ShellKind::Sh),
+            r#"echo 'it'\''s $HOME' '3' ''"#
+        );
+        assert_eq!(
+            tool.render(&args, ShellKind::Pwsh),
+            "echo 'it''s $HOME' '3' ''"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn declared_tools_run_through_the_registry() {
+        use crate::tools::registry::{ToolContext, execute_tool};
+
+        let dir = tempfile::tempdir().unwrap();
+        write_manifest(
+            dir.path(),
+            r#"{"tools": [{
+                "name": "greet",
+                "description": "Greet someone",
+                "parameters": {
+                    "type": "object",
+                    "properties": {"who": {"type": "string"}},
+                    "required": ["who"]
+                },
+                "command": "printf 'hello %s' {{who}}"
+            }]}"#,
+        );
+        let tape = TapeStore::open(dir.path(), "test").unwrap();
+        let ctx = ToolContext {
+            external_tools: load(dir.path()).unwrap(),
+            ..ToolContext::empty()
+        };
+
+        let output = execute_tool(
+            "greet",
+            r#"{"who": "a; rm -rf b"}"#,
+            &tape,
+            dir.path(),
+            &ctx,
+        );
+        assert!(output.contains("hello a; rm -rf b"), "{output}");
+        let output = execute_tool("greet", "{}", &tape, dir.path(), &ctx);
+        assert!(output.contains("invalid arguments for greet"), "{output}");
+    }
+}
@@ -1,5 +1,8 @@
 use std::path::{Path, PathBuf};
 
+use crate::tools::binary;
+use crate::tools::search_index::{self, SearchIndex};
+
 /// Resolve a path relative to the workspace, preventing path traversal.
 ///
 /// Returns `None` if the resolved path escapes the workspace directory.
@@ -54,39 +57,161 @@ fn normalize_path(path: &Path) -> PathBuf {
     components.iter().collect()
 }
 
+/// How `file.read` presents a file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadMode {
+    /// Text for text files, metadata for binary ones.
+    #[default]
+    Auto,
+    /// Size, MIME type and kind only.
+    Metadata,
+    /// Metadata plus a bounded hexdump.
+    Hexdump,
+}
+
+impl ReadMode {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "" | "auto" | "text" => Some(Self::Auto),
+            "metadata" | "meta" => Some(Self::Metadata),
+            "hexdump" | "hex" => Some(Self::Hexdump),
+            _ => None,
+        }
+    }
+}
+
+/// Options of `file.read` beyond the path.
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    pub mode: ReadMode,
+    /// Encoding of a non-UTF-8 text file (`gbk`, `latin1`, ...).
+    pub encoding: Option<String>,
+    /// First byte of the hexdump.
+    pub offset: u64,
+    /// Hexdump length in bytes (default and cap in `tools::binary`).
+    pub length: Option<usize>,
+    /// First line of text to return (1-based).
+    pub start_line: Option<usize>,
+    /// Last line of text to return (1-based, inclusive).
+    pub end_line: Option<usize>,
+}
+
 /// Read a file's content from the workspace.
 pub fn read_file(workspace: &Path, file_path: &str) -> String {
-    match resolve_safe_path(workspace, file_path) {
-        Some(path) => {
-            if !path.exists() {
-                return format!("File not found: {file_path}");
+    read_file_with(workspace, file_path, &ReadOptions::default())
+}
+
+/// Read a file as text, or describe it when it is binary.
+///
+/// Binary files (see [`binary::looks_binary`]) get their metadata instead
+/// of mojibake; text that is not UTF-8 is decoded lossily with a hint to
+/// pass an `encoding`.
+pub fn read_file_with(workspace: &Path, file_path: &str, options: &ReadOptions) -> String {
+    use std::io::{Read, Seek, SeekFrom};
+
+    let Some(path) = resolve_safe_path(workspace, file_path) else {
+        return format!("Access denied: path escapes workspace: {file_path}");
+    };
+    if !path.exists() {
+        return format!("File not found: {file_path}");
+    }
+    if !path.is_file() {
+        return format!("Not a file: {file_path}");
+    }
+    let mut file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) => return format!("Error reading file: {e}"),
+    };
+    let size = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut head = Vec::with_capacity(binary::SNIFF_BYTES);
+    if let Err(e) = (&mut file)
+        .take(binary::SNIFF_BYTES as u64)
+        .read_to_end(&mut head)
+    {
+        return format!("Error reading file: {e}");
+    }
+    let is_binary = binary::looks_binary(&head);
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let metadata = format!(
+        "File: {file_path}\nSize: {size} bytes\nType: {} ({})",
+        binary::guess_mime(&head, extension),
+        if is_binary { "binary" } else { "text" }
+    );
+
+    match options.mode {
+        ReadMode::Metadata => metadata,
+        ReadMode::Hexdump => {
+            let length = options
+                .length
+                .unwrap_or(binary::DEFAULT_HEXDUMP_BYTES)
+                .min(binary::MAX_HEXDUMP_BYTES);
+            let mut bytes = Vec::with_capacity(length);
+            let read = file
+                .seek(SeekFrom::Start(options.offset))
+                .and_then(|_| (&mut file).take(length as u64).read_to_end(&mut bytes));
+            if let Err(e) = read {
+                return format!("Error reading file: {e}");
             }
-            if !path.is_file() {
-                return format!("Not a file: {file_path}");
+            let end = options.offset + bytes.len() as u64;
+            let more = if end < size {
+                format!(
+                    "\n[{} more bytes; pass offset={end} to continue]",
+                    size - end
+                )
+            } else {
+                String::new()
+            };
+            format!(
+                "{metadata}\nBytes {}..{end}:\n{}{more}",
+                options.offset,
+                binary::hexdump(&bytes, options.offset)
+            )
+        }
+        ReadMode::Auto if is_binary && options.encoding.is_none() => format!(
+            "{metadata}\n[binary file not shown; use mode=\"hexdump\" to inspect its bytes]"
+        ),
+        ReadMode::Auto => {
+            // Large files are cut to the tool output budget in `execute_tool`.
+            let mut bytes = head;
+            if let Err(e) = file.read_to_end(&mut bytes) {
+                return format!("Error reading file: {e}");
             }
-            match std::fs::read_to_string(&path) {
-                Ok(content) => {
-                    // Truncate large files to prevent context overflow
-                    const MAX_CHARS: usize = 50_000;
-                    if content.len() > MAX_CHARS {
-                        let truncated = crate::core::utils::safe_truncate(&content, MAX_CHARS);
-                        format!(
-                            "{}\n\n[... truncated, showing first {} of {} bytes]",
-                            truncated,
-                            truncated.len(),
-                            content.len()
-                        )
-                    } else {
-                        content
-                    }
-                }
-                Err(e) => format!("Error reading file: {e}"),
+            let text = match &options.encoding {
+                Some(label) => match binary::decode(&bytes, label) {
+                    Ok(text) => text,
+                    Err(e) => return format!("Error: {e}"),
+                },
+                None => match String::from_utf8(bytes) {
+                    Ok(text) => text,
+                    Err(e) => format!(
+                        "[not valid UTF-8; invalid bytes are shown as \u{fffd}. Pass encoding (e.g. \"gbk\", \"latin1\") to decode it]\n{}",
+                        String::from_utf8_lossy(e.as_bytes())
+                    ),
+                },
+            };
+            if options.start_line.is_none() && options.end_line.is_none() {
+                return text;
             }
+            line_range(&text, options.start_line, options.end_line)
         }
-        None => format!("Access denied: path escapes workspace: {file_path}"),
     }
 }
 
+/// Lines `start..=end` (1-based) of `text`, headed by the range shown.
+fn line_range(text: &str, start: Option<usize>, end: Option<usize>) -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    let total = lines.len();
+    let start = start.unwrap_or(1).max(1);
+    let end = end.unwrap_or(total).min(total);
+    if start > end {
+        return format!("Error: line range {start}-{end} is empty (file has {total} lines).");
+    }
+    format!(
+        "[lines {start}-{end} of {total}]\n{}",
+        lines[start - 1..end].join("\n")
+    )
+}
+
 /// Write content to a file in the workspace.
 pub fn write_file(workspace: &Path, file_path: &str, content: &str) -> String {
     match resolve_safe_path(workspace, file_path) {
@@ -221,6 +346,11 @@ pub fn search_files(workspace: &Path, query: &str, path: &str) -> String {
         return "Error: query cannot be empty.".to_string();
     }
 
+    // Canonical, like the paths `resolve_safe_path` returns, so that the
+    // index keys files by the same relative path however they are reached.
+    let workspace = &workspace
+        .canonicalize()
+        .unwrap_or_else(|_| workspace.to_path_buf());
     let search_root = if path.trim().is_empty() {
         workspace.to_path_buf()
     } else {
@@ -237,8 +367,8 @@ pub fn search_files(workspace: &Path, query: &str, path: &str) -> String {
     let query_lower = query.to_lowercase();
     let mut results = Vec::new();
     const MAX_RESULTS: usize = 50;
-    const MAX_DEPTH: usize = 10;
 
+    let mut index = SearchIndex::load(workspace);
     search_recursive(
         workspace,
         &search_root,
@@ -246,8 +376,13 @@ pub fn search_files(workspace: &Path, query: &str, path: &str) -> String {
         &mut results,
         MAX_RESULTS,
         0,
-        MAX_DEPTH,
+        &mut index,
     );
+    // A walk cut short by the result cap did not see every file.
+    if results.len() < MAX_RESULTS {
+        index.prune(&relative_path(workspace, &search_root));
+    }
+    index.save();
 
     if results.is_empty() {
         format!("No matches found for: {query}")
@@ -265,6 +400,101 @@ pub fn search_files(workspace: &Path, query: &str, path: &str) -> String {
     }
 }
 
+/// Default and maximum depth of `file.tree`.
+pub const DEFAULT_TREE_DEPTH: usize = 3;
+const MAX_TREE_DEPTH: usize = 10;
+
+/// Entries shown by `file.tree` before the listing is cut off.
+const MAX_TREE_ENTRIES: usize = 500;
+
+/// Depth-limited tree of a workspace directory with file sizes.
+///
+/// Honours `.gitignore` (also outside git repositories), skips hidden
+/// entries and the build directories in `SKIP_DIRS`. Directories at the
+/// depth limit are marked with `…`.
+pub fn tree_directory(workspace: &Path, dir_path: &str, depth: usize) -> String {
+    let target = if dir_path.trim().is_empty() {
+        workspace.to_path_buf()
+    } else {
+        match resolve_safe_path(workspace, dir_path) {
+            Some(p) => p,
+            None => return format!("Access denied: path escapes workspace: {dir_path}"),
+        }
+    };
+    if !target.is_dir() {
+        return format!("Directory not found: {dir_path}");
+    }
+    let depth = depth.clamp(1, MAX_TREE_DEPTH);
+
+    let walker = ignore::WalkBuilder::new(&target)
+        .max_depth(Some(depth))
+        .hidden(true)
+        .require_git(false)
+        .filter_entry(|e| {
+            !(e.file_type().is_some_and(|t| t.is_dir())
+                && SKIP_DIRS.contains(&e.file_name().to_string_lossy().as_ref()))
+        })
+        .sort_by_file_name(|a, b| a.cmp(b))
+        .build();
+
+    let root = target
+        .strip_prefix(
+            workspace
+                .canonicalize()
+                .unwrap_or_else(|_| workspace.to_path_buf()),
+        )
+        .ok()
+        .filter(|p| !p.as_os_str().is_empty())
+        .map_or_else(|| ".".to_string(), |p| p.display().to_string());
+    let mut lines = vec![format!("{root}/")];
+    let (mut dirs, mut files, mut bytes) = (0usize, 0usize, 0u64);
+    let mut truncated = false;
+    for entry in walker.flatten().filter(|e| e.depth() > 0) {
+        if dirs + files >= MAX_TREE_ENTRIES {
+            truncated = true;
+            break;
+        }
+        let indent = "  ".repeat(entry.depth());
+        let name = entry.file_name().to_string_lossy();
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            dirs += 1;
+            let more = if entry.depth() == depth { " …" } else { "" };
+            lines.push(format!("{indent}{name}/{more}"));
+        } else {
+            files += 1;
+            let size = entry.metadata().map(|m| m.len()).unwrap_or(0);
+            bytes += size;
+            lines.push(format!("{indent}{name}  ({})", format_size(size)));
+        }
+    }
+    lines.push(String::new());
+    lines.push(format!(
+        "{dirs} director{}, {files} file(s), {} (depth {depth})",
+        if dirs == 1 { "y" } else { "ies" },
+        format_size(bytes)
+    ));
+    if truncated {
+        lines.push(format!(
+            "[... capped at {MAX_TREE_ENTRIES} entries; use a subdirectory or a smaller depth]"
+        ));
+    }
+    lines.join("\n")
+}
+
+pub(crate) fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KB", "MB", "GB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
 /// Directories to skip during search.
 const SKIP_DIRS: &[&str] = &[
     ".git",
@@ -278,6 +508,16 @@ const SKIP_DIRS: &[&str] = &[
     "build",
 ];
 
+/// Directory levels `file.search` descends below its root.
+const MAX_SEARCH_DEPTH: usize = 10;
+
+fn relative_path(workspace: &Path, file: &Path) -> String {
+    file.strip_prefix(workspace)
+        .unwrap_or(file)
+        .display()
+        .to_string()
+}
+
 fn search_recursive(
     workspace: &Path,
     dir: &Path,
@@ -285,15 +525,15 @@ fn search_recursive(
     results: &mut Vec<String>,
     max: usize,
     depth: usize,
-    max_depth: usize,
+    index: &mut SearchIndex,
 ) {
-    if results.len() >= max || depth > max_depth {
+    if results.len() >= max || depth > MAX_SEARCH_DEPTH {
         return;
     }
 
     // If it's a file, search it directly
     if dir.is_file() {
-        search_file(workspace, dir, query, results, max);
+        search_file(workspace, dir, query, results, max, index);
         return;
     }
 
@@ -319,14 +559,21 @@ fn search_recursive(
         }
 
         if path.is_dir() {
-            search_recursive(workspace, &path, query, results, max, depth + 1, max_depth);
+            search_recursive(workspace, &path, query, results, max, depth + 1, index);
         } else if path.is_file() {
-            search_file(workspace, &path, query, results, max);
+            search_file(workspace, &path, query, results, max, index);
         }
     }
 }
 
-fn search_file(workspace: &Path, file: &Path, query: &str, results: &mut Vec<String>, max: usize) {
+fn search_file(
+    workspace: &Path,
+    file: &Path,
+    query: &str,
+    results: &mut Vec<String>,
+    max: usize,
+    index: &mut SearchIndex,
+) {
     // Skip likely binary files by extension
     let ext = file.extension().and_then(|e| e.to_str()).unwrap_or("");
     const BINARY_EXTS: &[&str] = &[
@@ -336,18 +583,24 @@ fn search_file(workspace: &Path, file: &Path, query: &str, results: &mut Vec<Str
     if BINARY_EXTS.contains(&ext) {
         return;
     }
+    // Session scratchpads (`tools::scratch`) are the model's notes, not workspace content.
+    if file.to_string_lossy().ends_with(".scratch.md") {
+        return;
+    }
+    if file.starts_with(workspace.join(search_index::INDEX_DIR)) {
+        return;
+    }
+
+    let rel_path = relative_path(workspace, file);
+    if !index.may_contain(&rel_path, file, query) {
+        return;
+    }
 
     let content = match std::fs::read_to_string(file) {
         Ok(c) => c,
         Err(_) => return, // Skip binary/unreadable files
     };
 
-    let rel_path = file
-        .strip_prefix(workspace)
-        .unwrap_or(file)
-        .display()
-        .to_string();
-
     for (line_num, line) in content.lines().enumerate() {
         if results.len() >= max {
             return;
@@ -411,6 +664,68 @@ mod tests {
         assert_eq!(content, "world");
     }
 
+    #[test]
+    fn binary_files_get_metadata_and_hexdumps() {
+        let dir = tempdir().unwrap();
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR";
+        std::fs::write(dir.path().join("logo.png"), png).unwrap();
+
+        let content = read_file(dir.path(), "logo.png");
+        assert!(content.contains("Size: 16 bytes"), "{content}");
+        assert!(content.contains("Type: image/png (binary)"), "{content}");
+        assert!(content.contains("mode=\"hexdump\""), "{content}");
+
+        let options = ReadOptions {
+            mode: ReadMode::Hexdump,
+            offset: 8,
+            length: Some(4),
+            ..ReadOptions::default()
+        };
+        let content = read_file_with(dir.path(), "logo.png", &options);
+        assert!(
+            content.contains("Bytes 8..12:\n00000008  00 00 00 0d"),
+            "{content}"
+        );
+        assert!(content.contains("[4 more bytes; pass offset=12 to continue]"));
+    }
+
+    #[test]
+    fn legacy_encoded_text_is_decoded_on_request() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("menu.txt"), b"caf\xe9").unwrap();
+
+        let content = read_file(dir.path(), "menu.txt");
+        assert!(content.starts_with("[not valid UTF-8"), "{content}");
+        assert!(content.ends_with("caf\u{fffd}"), "{content}");
+
+        let options = ReadOptions {
+            encoding: Some("latin1".to_string()),
+            ..ReadOptions::default()
+        };
+        assert_eq!(read_file_with(dir.path(), "menu.txt", &options), "café");
+    }
+
+    #[test]
+    fn read_line_range() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "one\ntwo\nthree\nfour\n").unwrap();
+
+        let options = ReadOptions {
+            start_line: Some(2),
+            end_line: Some(3),
+            ..ReadOptions::default()
+        };
+        assert_eq!(
+            read_file_with(dir.path(), "a.rs", &options),
+            "[lines 2-3 of 4]\ntwo\nthree"
+        );
+        let options = ReadOptions {
+            start_line: Some(9),
+            ..ReadOptions::default()
+        };
+        assert!(read_file_with(dir.path(), "a.rs", &options).starts_with("Error: line range"));
+    }
+
     #[test]
     fn read_nonexistent_file() {
         let dir = tempdir().unwrap();
@@ -487,6 +802,37 @@ mod tests {
         assert!(result.contains("Access denied"));
     }
 
+    #[test]
+    fn tree_respects_depth_and_gitignore() {
+        let dir = tempdir().unwrap();
+        let ws = dir.path();
+        std::fs::write(ws.join(".gitignore"), "*.log\n").unwrap();
+        std::fs::write(ws.join("Cargo.toml"), "x".repeat(2048)).unwrap();
+        std::fs::write(ws.join("debug.log"), "").unwrap();
+        std::fs::create_dir_all(ws.join("src/tools/deep")).unwrap();
+        std::fs::write(ws.join("src/main.rs"), "fn main() {}").unwrap();
+        std::fs::write(ws.join("src/tools/deep/x.rs"), "").unwrap();
+        std::fs::create_dir_all(ws.join("target/debug")).unwrap();
+
+        let tree = tree_directory(ws, "", 2);
+        assert!(tree.starts_with("./\n"), "{tree}");
+        assert!(tree.contains("\n  Cargo.toml  (2.0 KB)"), "{tree}");
+        assert!(tree.contains("\n    main.rs  (12 B)"), "{tree}");
+        assert!(tree.contains("\n    tools/ …"), "{tree}");
+        assert!(!tree.contains("debug.log"), "{tree}");
+        assert!(!tree.contains("target"), "{tree}");
+        assert!(!tree.contains("x.rs"), "{tree}");
+        assert!(
+            tree.ends_with("2 directories, 2 file(s), 2.0 KB (depth 2)"),
+            "{tree}"
+        );
+
+        let sub = tree_directory(ws, "src/tools", 5);
+        assert!(sub.starts_with("src/tools/\n"), "{sub}");
+        assert!(sub.contains("\n    x.rs  (0 B)"), "{sub}");
+        assert!(tree_directory(ws, "../", 2).contains("Access denied"));
+    }
+
     #[test]
     fn empty_path_rejected() {
         let dir = tempdir().unwrap();
@@ -514,6 +860,37 @@ mod tests {
         assert!(result.contains("No matches"));
     }
 
+    #[test]
+    fn search_skips_session_scratchpads() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(".crabclaw")).unwrap();
+        std::fs::write(dir.path().join(".crabclaw/cli.scratch.md"), "plan: hello").unwrap();
+        let result = search_files(dir.path(), "hello", ".crabclaw");
+        assert!(result.contains("No matches"), "{result}");
+    }
+
+    #[test]
+    fn search_index_follows_workspace_changes() {
+        let dir = tempdir().unwrap();
+        std::fs::write(dir.path().join("a.rs"), "fn hello_world() {}").unwrap();
+        std::fs::write(dir.path().join("b.rs"), "fn goodbye() {}").unwrap();
+
+        let first = search_files(dir.path(), "hello", "");
+        assert!(first.contains("1 match"), "{first}");
+        assert!(dir.path().join(".crabclaw/index/search.json").exists());
+        // Searching the index directory itself does not find the index.
+        let own = search_files(dir.path(), "a.rs", ".crabclaw");
+        assert!(own.contains("No matches"), "{own}");
+
+        // Same results from the index, then edits and deletions show up.
+        assert_eq!(search_files(dir.path(), "hello", ""), first);
+        std::fs::write(dir.path().join("b.rs"), "fn goodbye() { hello() }").unwrap();
+        std::fs::remove_file(dir.path().join("a.rs")).unwrap();
+        let result = search_files(dir.path(), "hello", "");
+        assert!(result.contains("1 match"), "{result}");
+        assert!(result.contains("b.rs:1"), "{result}");
+    }
+
     #[test]
     fn search_empty_query_rejected() {
         let dir = tempdir().unwrap();
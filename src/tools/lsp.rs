@@ -0,0 +1,973 @@
+//! `lsp.*` tools: code intelligence from a language server.
+//!
+//! One server process per (workspace, server) is started on first use and
+//! kept running, so its index survives between tool calls. Files are synced
+//! with full-text `didOpen`/`didChange` notifications before each request,
+//! and `publishDiagnostics` notifications are collected as they arrive.
+//!
+//! Servers come from built-in defaults for common languages, overridden or
+//! extended by `~/.crabclaw/lsp.json`:
+//!
+//! ```json
+//! { "python": { "command": "pylsp", "args": [], "extensions": ["py"] } }
+//! ```
+//!
+//! Server definitions are never read from the workspace, which the model can
+//! write to, and servers start like `shell.exec` commands: under the shell
+//! policy and sandbox, with a scrubbed environment.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{Sender, channel};
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+use crate::core::shell::{self, CommandOrigin, ShellKind};
+use crate::tools::file_ops::resolve_safe_path;
+
+/// Server overrides, relative to the user config directory.
+const CONFIG_FILE: &str = "lsp.json";
+/// Servers can take a while to start and index a project.
+const INITIALIZE_TIMEOUT: Duration = Duration::from_secs(60);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long `lsp.diagnostics` waits for the server to publish.
+const DIAGNOSTICS_TIMEOUT: Duration = Duration::from_secs(20);
+/// Publications closer together than this are treated as one batch.
+const DIAGNOSTICS_SETTLE: Duration = Duration::from_millis(1500);
+/// Locations listed before the rest are summarised.
+const MAX_LOCATIONS: usize = 100;
+
+/// A language server and the file extensions it handles.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ServerConfig {
+    #[serde(skip)]
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+}
+
+fn default_servers() -> Vec<ServerConfig> {
+    let server = |name: &str, command: &str, args: &[&str], extensions: &[&str]| ServerConfig {
+        name: name.to_string(),
+        command: command.to_string(),
+        args: args.iter().map(|a| a.to_string()).collect(),
+        extensions: extensions.iter().map(|e| e.to_string()).collect(),
+    };
+    vec![
+        server("rust", "rust-analyzer", &[], &["rs"]),
+        server("python", "pyright-langserver", &["--stdio"], &["py", "pyi"]),
+        server("go", "gopls", &[], &["go"]),
+        server(
+            "typescript",
+            "typescript-language-server",
+            &["--stdio"],
+            &["ts", "tsx", "js", "jsx", "mjs", "cjs"],
+        ),
+        server(
+            "c",
+            "clangd",
+            &[],
+            &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"],
+        ),
+    ]
+}
+
+/// Built-in servers with the `lsp.json` entries in `config_dir` layered over
+/// them by name.
+pub fn load_servers(config_dir: &Path) -> Result<Vec<ServerConfig>, String> {
+    let mut servers = default_servers();
+    let path = config_dir.join(CONFIG_FILE);
+    let Ok(raw) = std::fs::read_to_string(&path) else {
+        return Ok(servers);
+    };
+    let overrides: HashMap<String, ServerConfig> =
+        serde_json::from_str(&raw).map_err(|e| format!("invalid {CONFIG_FILE}: {e}"))?;
+    let mut overrides: Vec<_> = overrides.into_iter().collect();
+    overrides.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, mut server) in overrides {
+        server.name = name;
+        // Extensions claimed by the override move away from the defaults.
+        for other in &mut servers {
+            other.extensions.retain(|e| !server.extensions.contains(e));
+        }
+        match servers.iter_mut().find(|s| s.name == server.name) {
+            Some(existing) => {
+                if server.extensions.is_empty() {
+                    server.extensions = std::mem::take(&mut existing.extensions);
+                }
+                *existing = server;
+            }
+            None => servers.push(server),
+        }
+    }
+    Ok(servers)
+}
+
+/// `server` as a command line, with only the words that need it quoted so
+/// the shell policy sees plain program names.
+fn command_line(server: &ServerConfig, shell: ShellKind) -> String {
+    std::iter::once(&server.command)
+        .chain(&server.args)
+        .map(|word| {
+            let plain = !word.is_empty()
+                && word
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c));
+            if plain {
+                word.clone()
+            } else {
+                shell.quote(word)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn server_for<'a>(servers: &'a [ServerConfig], path: &Path) -> Option<&'a ServerConfig> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    servers.iter().find(|s| s.extensions.contains(&extension))
+}
+
+fn language_id(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "rs" => "rust",
+        "py" | "pyi" => "python",
+        "go" => "go",
+        "ts" | "mts" | "cts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" | "mjs" | "cjs" => "javascript",
+        "jsx" => "javascriptreact",
+        "c" | "h" => "c",
+        "cc" | "cpp" | "cxx" | "hpp" | "hh" => "cpp",
+        _ => "plaintext",
+    }
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let encoded: Vec<String> = path
+        .to_string_lossy()
+        .split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect();
+    format!("file://{}", encoded.join("/"))
+}
+
+fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+    Some(PathBuf::from(urlencoding::decode(path).ok()?.into_owned()))
+}
+
+/// Read one `Content-Length` framed message; `None` at end of stream.
+fn read_message(reader: &mut impl BufRead) -> std::io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let mut body = vec![0; length.unwrap_or(0)];
+    reader.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body).unwrap_or(Value::Null)))
+}
+
+fn write_message(writer: &mut dyn Write, message: &Value) -> std::io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+    writer.flush()
+}
+
+type Writer = Arc<Mutex<Box<dyn Write + Send>>>;
+
+#[derive(Default)]
+struct Shared {
+    pending: HashMap<u64, Sender<Result<Value, String>>>,
+    /// Latest diagnostics per document URI, with the publication number.
+    diagnostics: HashMap<String, (u64, Vec<Value>)>,
+    publications: u64,
+    closed: bool,
+}
+
+/// A JSON-RPC connection to one language server.
+pub struct LspClient {
+    writer: Writer,
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+    next_id: AtomicU64,
+    /// Open documents: URI to (version, text sent).
+    documents: Mutex<HashMap<String, (i64, String)>>,
+    child: Option<Mutex<Child>>,
+}
+
+impl LspClient {
+    /// Start `server` with `root` as its workspace and complete the handshake.
+    ///
+    /// The server runs as a tool command: refused by the shell policy like
+    /// any other, and inside the sandbox when one is configured.
+    pub fn spawn(server: &ServerConfig, root: &Path) -> Result<Self, String> {
+        let not_started = |e: String| {
+            format!(
+                "could not start language server '{}' ({}): {e}. Install it or configure another in ~/.crabclaw/{CONFIG_FILE}.",
+                server.name, server.command
+            )
+        };
+        let line = command_line(server, ShellKind::detect());
+        let (mut command, _) = shell::command_as(&line, root, CommandOrigin::Tool)?;
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| not_started(e.to_string()))?;
+        let stdin = child.stdin.take().ok_or("server stdin unavailable")?;
+        let stdout = child.stdout.take().ok_or("server stdout unavailable")?;
+        let mut client = Self::connect(stdout, stdin);
+        client.child = Some(Mutex::new(child));
+        client.initialize(root).map_err(not_started)?;
+        Ok(client)
+    }
+
+    /// Wrap an already connected server stream and start reading from it.
+    fn connect(reader: impl Read + Send + 'static, writer: impl Write + Send + 'static) -> Self {
+        let writer: Writer = Arc::new(Mutex::new(Box::new(writer)));
+        let shared = Arc::new((Mutex::new(Shared::default()), Condvar::new()));
+        let thread_writer = writer.clone();
+        let thread_shared = shared.clone();
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                dispatch(message, &thread_writer, &thread_shared);
+            }
+            let (lock, condvar) = &*thread_shared;
+            let mut shared = lock.lock().unwrap();
+            shared.closed = true;
+            for (_, sender) in shared.pending.drain() {
+                let _ = sender.send(Err("language server exited".to_string()));
+            }
+            condvar.notify_all();
+        });
+        Self {
+            writer,
+            shared,
+            next_id: AtomicU64::new(1),
+            documents: Mutex::new(HashMap::new()),
+            child: None,
+        }
+    }
+
+    fn initialize(&self, root: &Path) -> Result<(), String> {
+        let root_uri = path_to_uri(root);
+        let name = root
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let params = json!({
+            "processId": std::process::id(),
+            "rootUri": root_uri,
+            "rootPath": root.to_string_lossy(),
+            "workspaceFolders": [{ "uri": root_uri, "name": name }],
+            "capabilities": {
+                "general": { "positionEncodings": ["utf-16"] },
+                "workspace": { "configuration": true, "workspaceFolders": true },
+                "textDocument": {
+                    "synchronization": { "didSave": true },
+                    "definition": { "linkSupport": true },
+                    "references": {},
+                    "publishDiagnostics": { "relatedInformation": false }
+                }
+            }
+        });
+        self.request_with_timeout("initialize", params, INITIALIZE_TIMEOUT)?;
+        self.notify("initialized", json!({}))
+    }
+
+    fn is_closed(&self) -> bool {
+        self.shared.0.lock().unwrap().closed
+    }
+
+    fn notify(&self, method: &str, params: Value) -> Result<(), String> {
+        let message = json!({ "jsonrpc": "2.0", "method": method, "params": params });
+        write_message(&mut **self.writer.lock().unwrap(), &message)
+            .map_err(|e| format!("language server write failed: {e}"))
+    }
+
+    pub fn request(&self, method: &str, params: Value) -> Result<Value, String> {
+        self.request_with_timeout(method, params, REQUEST_TIMEOUT)
+    }
+
+    fn request_with_timeout(
+        &self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value, String> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = channel();
+        {
+            let mut shared = self.shared.0.lock().unwrap();
+            if shared.closed {
+                return Err("language server exited".to_string());
+            }
+            shared.pending.insert(id, sender);
+        }
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = write_message(&mut **self.writer.lock().unwrap(), &message) {
+            self.shared.0.lock().unwrap().pending.remove(&id);
+            return Err(format!("language server write failed: {e}"));
+        }
+        match receiver.recv_timeout(timeout) {
+            Ok(result) => result,
+            Err(_) => {
+                self.shared.0.lock().unwrap().pending.remove(&id);
+                Err(format!(
+                    "{method} timed out after {}s (the server may still be indexing)",
+                    timeout.as_secs()
+                ))
+            }
+        }
+    }
+
+    /// Send the file's current text to the server. Returns its URI and
+    /// whether the server's copy changed (or was just opened).
+    fn sync_document(&self, path: &Path, text: &str) -> Result<(String, bool), String> {
+        let uri = path_to_uri(path);
+        let mut documents = self.documents.lock().unwrap();
+        match documents.get_mut(&uri) {
+            Some((_, sent)) if sent == text => return Ok((uri, false)),
+            Some((version, sent)) => {
+                *version += 1;
+                *sent = text.to_string();
+                self.notify(
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": { "uri": uri, "version": version },
+                        "contentChanges": [{ "text": text }]
+                    }),
+                )?;
+            }
+            None => {
+                documents.insert(uri.clone(), (1, text.to_string()));
+                self.notify(
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": language_id(path),
+                            "version": 1,
+                            "text": text
+                        }
+                    }),
+                )?;
+            }
+        }
+        // Servers that check on save (rust-analyzer's cargo check) need this.
+        self.notify(
+            "textDocument/didSave",
+            json!({ "textDocument": { "uri": uri }, "text": text }),
+        )?;
+        Ok((uri, true))
+    }
+
+    /// Diagnostics for `uri` published after publication number `after`,
+    /// waiting until they settle; the last known set on timeout.
+    fn wait_diagnostics(&self, uri: &str, after: u64, timeout: Duration) -> Option<Vec<Value>> {
+        let (lock, condvar) = &*self.shared;
+        let deadline = Instant::now() + timeout;
+        let mut shared = lock.lock().unwrap();
+        let mut settle_until: Option<Instant> = None;
+        let mut seen = after;
+        loop {
+            if let Some((number, _)) = shared.diagnostics.get(uri)
+                && *number > seen
+            {
+                seen = *number;
+                settle_until = Some(Instant::now() + DIAGNOSTICS_SETTLE);
+            }
+            let now = Instant::now();
+            let wake = settle_until.map_or(deadline, |s| s.min(deadline));
+            if now >= wake || shared.closed {
+                break;
+            }
+            shared = condvar.wait_timeout(shared, wake - now).unwrap().0;
+        }
+        shared.diagnostics.get(uri).map(|(_, list)| list.clone())
+    }
+
+    fn publications(&self) -> u64 {
+        self.shared.0.lock().unwrap().publications
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        if let Some(child) = &self.child {
+            let _ = self.request_with_timeout("shutdown", Value::Null, Duration::from_secs(2));
+            let _ = self.notify("exit", Value::Null);
+            let mut child = child.lock().unwrap();
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+/// Route one message from the server: responses to their waiting request,
+/// diagnostics into the store, and minimal answers to server requests.
+fn dispatch(message: Value, writer: &Writer, shared: &Arc<(Mutex<Shared>, Condvar)>) {
+    let method = message["method"].as_str();
+    let id = &message["id"];
+    match (method, id.is_null()) {
+        (None, false) => {
+            let Some(id) = id.as_u64() else {
+                return;
+            };
+            let result = match &message["error"] {
+                Value::Null => Ok(message["result"].clone()),
+                error => Err(format!(
+                    "language server error: {}",
+                    error["message"].as_str().unwrap_or("unknown error")
+                )),
+            };
+            if let Some(sender) = shared.0.lock().unwrap().pending.remove(&id) {
+                let _ = sender.send(result);
+            }
+        }
+        (Some(method), false) => {
+            let result = match method {
+                "workspace/configuration" => {
+                    let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                    Value::Array(vec![Value::Null; items])
+                }
+                _ => Value::Null,
+            };
+            let reply = json!({ "jsonrpc": "2.0", "id": id, "result": result });
+            let _ = write_message(&mut **writer.lock().unwrap(), &reply);
+        }
+        (Some("textDocument/publishDiagnostics"), true) => {
+            let params = &message["params"];
+            let Some(uri) = params["uri"].as_str() else {
+                return;
+            };
+            let list = params["diagnostics"]
+                .as_array()
+                .cloned()
+                .unwrap_or_default();
+            let (lock, condvar) = &**shared;
+            let mut shared = lock.lock().unwrap();
+            shared.publications += 1;
+            let number = shared.publications;
+            shared.diagnostics.insert(uri.to_string(), (number, list));
+            condvar.notify_all();
+        }
+        _ => {}
+    }
+}
+
+type ClientKey = (PathBuf, String);
+
+/// Running servers, shared by every session on the same workspace.
+static CLIENTS: OnceLock<Mutex<HashMap<ClientKey, Arc<LspClient>>>> = OnceLock::new();
+
+fn client_for(root: &Path, server: &ServerConfig) -> Result<Arc<LspClient>, String> {
+    let clients = CLIENTS.get_or_init(|| Mutex::new(HashMap::new()));
+    let key = (root.to_path_buf(), server.name.clone());
+    if let Some(client) = clients.lock().unwrap().get(&key)
+        && !client.is_closed()
+    {
+        return Ok(client.clone());
+    }
+    // Started outside the lock: initialization can take a while.
+    let client = Arc::new(LspClient::spawn(server, root)?);
+    clients.lock().unwrap().insert(key, client.clone());
+    Ok(client)
+}
+
+/// A file resolved for a language server request.
+struct Target {
+    root: PathBuf,
+    path: PathBuf,
+    text: String,
+    client: Arc<LspClient>,
+}
+
+fn open_target(workspace: &Path, file_path: &str) -> Result<Target, String> {
+    let path = resolve_safe_path(workspace, file_path)
+        .ok_or_else(|| format!("Access denied: path escapes workspace: {file_path}"))?;
+    if !path.is_file() {
+        return Err(format!("File not found: {file_path}"));
+    }
+    let servers =
+        load_servers(&crate::core::config::user_config_dir()).map_err(|e| format!("Error: {e}"))?;
+    let server = server_for(&servers, &path).ok_or_else(|| {
+        format!(
+            "Error: no language server configured for {file_path} (add one to ~/.crabclaw/{CONFIG_FILE})."
+        )
+    })?;
+    let text = std::fs::read_to_string(&path).map_err(|e| format!("Error reading file: {e}"))?;
+    let root = workspace
+        .canonicalize()
+        .unwrap_or_else(|_| workspace.to_path_buf());
+    let client = client_for(&root, server).map_err(|e| format!("Error: {e}"))?;
+    Ok(Target {
+        root,
+        path,
+        text,
+        client,
+    })
+}
+
+/// Where in a file to ask about: a 1-based line plus a 1-based column or a
+/// symbol on that line.
+#[derive(Debug, Clone, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: Option<usize>,
+    pub symbol: Option<String>,
+}
+
+impl Position {
+    /// The LSP position (0-based line, UTF-16 character offset).
+    fn resolve(&self, text: &str) -> Result<Value, String> {
+        let line_count = text.lines().count();
+        let Some(line) = self.line.checked_sub(1).and_then(|n| text.lines().nth(n)) else {
+            return Err(format!(
+                "Error: line {} is outside the file (1-{line_count}).",
+                self.line
+            ));
+        };
+        let byte = match (&self.symbol, self.column) {
+            (Some(symbol), _) => find_symbol(line, symbol).ok_or_else(|| {
+                format!("Error: '{symbol}' does not appear on line {}.", self.line)
+            })?,
+            (None, Some(column)) => line
+                .char_indices()
+                .nth(column.saturating_sub(1))
+                .map_or(line.len(), |(byte, _)| byte),
+            (None, None) => return Err("Error: pass 'column' or 'symbol'.".to_string()),
+        };
+        let character: usize = line[..byte].chars().map(char::len_utf16).sum();
+        Ok(json!({ "line": self.line - 1, "character": character }))
+    }
+}
+
+/// Byte offset of `symbol` in `line`, preferring a whole-word occurrence.
+fn find_symbol(line: &str, symbol: &str) -> Option<usize> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut fallback = None;
+    for (byte, _) in line.match_indices(symbol) {
+        let before = line[..byte].chars().next_back();
+        let after = line[byte + symbol.len()..].chars().next();
+        if !before.is_some_and(is_word) && !after.is_some_and(is_word) {
+            return Some(byte);
+        }
+        fallback.get_or_insert(byte);
+    }
+    fallback
+}
+
+/// `lsp.definition`: where the symbol at a position is defined.
+pub fn definition(workspace: &Path, file_path: &str, position: &Position) -> String {
+    locate(
+        workspace,
+        file_path,
+        position,
+        "textDocument/definition",
+        json!({}),
+    )
+    .unwrap_or_else(|e| e)
+}
+
+/// `lsp.references`: every use of the symbol at a position.
+pub fn references(
+    workspace: &Path,
+    file_path: &str,
+    position: &Position,
+    include_declaration: bool,
+) -> String {
+    let context = json!({ "context": { "includeDeclaration": include_declaration } });
+    locate(
+        workspace,
+        file_path,
+        position,
+        "textDocument/references",
+        context,
+    )
+    .unwrap_or_else(|e| e)
+}
+
+fn locate(
+    workspace: &Path,
+    file_path: &str,
+    position: &Position,
+    method: &str,
+    extra: Value,
+) -> Result<String, String> {
+    let target = open_target(workspace, file_path)?;
+    let lsp_position = position.resolve(&target.text)?;
+    let (uri, _) = target
+        .client
+        .sync_document(&target.path, &target.text)
+        .map_err(|e| format!("Error: {e}"))?;
+    let mut params = json!({ "textDocument": { "uri": uri }, "position": lsp_position });
+    if let (Some(params), Some(extra)) = (params.as_object_mut(), extra.as_object()) {
+        params.extend(extra.clone());
+    }
+    let result = target
+        .client
+        .request(method, params)
+        .map_err(|e| format!("Error: {e}"))?;
+    Ok(format_locations(&target.root, &result))
+}
+
+/// Render `Location | Location[] | LocationLink[] | null` as
+/// `path:line:column: source line`.
+fn format_locations(root: &Path, result: &Value) -> String {
+    let items = match result {
+        Value::Array(items) => items.clone(),
+        Value::Null => Vec::new(),
+        single => vec![single.clone()],
+    };
+    if items.is_empty() {
+        return "No results (the server may still be indexing the workspace).".to_string();
+    }
+    let mut files: HashMap<PathBuf, Vec<String>> = HashMap::new();
+    let mut lines = Vec::new();
+    for item in items.iter().take(MAX_LOCATIONS) {
+        let uri = item["targetUri"].as_str().or(item["uri"].as_str());
+        let range = match &item["targetSelectionRange"] {
+            Value::Null => &item["range"],
+            range => range,
+        };
+        let Some(path) = uri.and_then(uri_to_path) else {
+            continue;
+        };
+        let line = range["start"]["line"].as_u64().unwrap_or(0) as usize;
+        let character = range["start"]["character"].as_u64().unwrap_or(0) as usize;
+        let source = files.entry(path.clone()).or_insert_with(|| {
+            std::fs::read_to_string(&path)
+                .map(|text| text.lines().map(str::to_string).collect())
+                .unwrap_or_default()
+        });
+        let text = source.get(line).map(String::as_str).unwrap_or("");
+        lines.push(format!(
+            "{}:{}:{}: {}",
+            display_path(root, &path),
+            line + 1,
+            utf16_to_column(text, character),
+            text.trim()
+        ));
+    }
+    if items.len() > MAX_LOCATIONS {
+        lines.push(format!("[{} more]", items.len() - MAX_LOCATIONS));
+    }
+    lines.join("\n")
+}
+
+fn display_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// 1-based character column of a UTF-16 offset into `line`.
+fn utf16_to_column(line: &str, offset: usize) -> usize {
+    let mut units = 0;
+    for (index, c) in line.chars().enumerate() {
+        if units >= offset {
+            return index + 1;
+        }
+        units += c.len_utf16();
+    }
+    line.chars().count() + 1
+}
+
+/// `lsp.diagnostics`: errors and warnings the server reports for a file.
+pub fn diagnostics(workspace: &Path, file_path: &str) -> String {
+    let target = match open_target(workspace, file_path) {
+        Ok(target) => target,
+        Err(e) => return e,
+    };
+    let before = target.client.publications();
+    let (uri, changed) = match target.client.sync_document(&target.path, &target.text) {
+        Ok(synced) => synced,
+        Err(e) => return format!("Error: {e}"),
+    };
+    let timeout = if changed {
+        DIAGNOSTICS_TIMEOUT
+    } else {
+        Duration::ZERO
+    };
+    let Some(list) = target.client.wait_diagnostics(&uri, before, timeout) else {
+        return format!(
+            "No diagnostics published for {file_path} within {}s.",
+            DIAGNOSTICS_TIMEOUT.as_secs()
+        );
+    };
+    format_diagnostics(file_path, &target.text, &list)
+}
+
+fn format_diagnostics(file_path: &str, text: &str, list: &[Value]) -> String {
+    if list.is_empty() {
+        return format!("No problems reported for {file_path}.");
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let mut sorted: Vec<&Value> = list.iter().collect();
+    sorted.sort_by_key(|d| {
+        (
+            d["severity"].as_u64().unwrap_or(1),
+            d["range"]["start"]["line"].as_u64().unwrap_or(0),
+        )
+    });
+    let mut out = Vec::new();
+    for diagnostic in sorted {
+        let severity = match diagnostic["severity"].as_u64() {
+            Some(2) => "warning",
+            Some(3) => "info",
+            Some(4) => "hint",
+            _ => "error",
+        };
+        let code = match &diagnostic["code"] {
+            Value::String(code) => format!("[{code}]"),
+            Value::Number(code) => format!("[{code}]"),
+            _ => String::new(),
+        };
+        let source = diagnostic["source"]
+            .as_str()
+            .map(|s| format!(" ({s})"))
+            .unwrap_or_default();
+        let line = diagnostic["range"]["start"]["line"].as_u64().unwrap_or(0) as usize;
+        let character = diagnostic["range"]["start"]["character"]
+            .as_u64()
+            .unwrap_or(0) as usize;
+        let column = utf16_to_column(lines.get(line).copied().unwrap_or(""), character);
+        let message = diagnostic["message"].as_str().unwrap_or("").trim();
+        let message = message.lines().next().unwrap_or("");
+        out.push(format!(
+            "{file_path}:{}:{column}: {severity}{code}: {message}{source}",
+            line + 1
+        ));
+    }
+    out.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scripted server on in-process pipes: answers `initialize`,
+    /// definition and references requests, and publishes one diagnostic
+    /// when a document is opened.
+    fn fake_server(uri_path: &str) -> LspClient {
+        let (client_reader, mut server_writer) = std::io::pipe().unwrap();
+        let (server_reader, client_writer) = std::io::pipe().unwrap();
+        let target = format!("file://{uri_path}");
+        std::thread::spawn(move || {
+            let mut reader = BufReader::new(server_reader);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                let id = message["id"].clone();
+                let reply = match message["method"].as_str().unwrap_or("") {
+                    "initialize" => {
+                        json!({ "jsonrpc": "2.0", "id": id, "result": { "capabilities": {} } })
+                    }
+                    "textDocument/definition" => json!({
+                        "jsonrpc": "2.0", "id": id,
+                        "result": { "uri": target, "range": {
+                            "start": { "line": 0, "character": 3 },
+                            "end": { "line": 0, "character": 7 } } }
+                    }),
+                    "textDocument/references" => json!({
+                        "jsonrpc": "2.0", "id": id,
+                        "error": { "code": -32801, "message": "content modified" }
+                    }),
+                    "textDocument/didOpen" => {
+                        let uri = message["params"]["textDocument"]["uri"].clone();
+                        // Ask for configuration first, like real servers do.
+                        let request = json!({ "jsonrpc": "2.0", "id": 99, "method": "workspace/configuration",
+                            "params": { "items": [{}, {}] } });
+                        write_message(&mut server_writer, &request).unwrap();
+                        let answer = loop {
+                            let next = read_message(&mut reader).unwrap().unwrap();
+                            if next["id"] == 99 {
+                                break next;
+                            }
+                        };
+                        assert_eq!(answer["result"], json!([null, null]));
+                        json!({ "jsonrpc": "2.0", "method": "textDocument/publishDiagnostics",
+                            "params": { "uri": uri, "diagnostics": [{
+                                "range": { "start": { "line": 1, "character": 4 }, "end": { "line": 1, "character": 5 } },
+                                "severity": 1, "code": "E0308", "source": "rustc",
+                                "message": "mismatched types\nexpected u8" }] } })
+                    }
+                    _ => continue,
+                };
+                write_message(&mut server_writer, &reply).unwrap();
+            }
+        });
+        let client = LspClient::connect(client_reader, client_writer);
+        client.initialize(Path::new("/ws")).unwrap();
+        client
+    }
+
+    #[test]
+    fn client_round_trips_requests_and_collects_diagnostics() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("main.rs");
+        std::fs::write(&file, "fn main() {\n    let x: u8 = \"a\";\n}\n").unwrap();
+        let client = fake_server(&file.to_string_lossy());
+
+        let result = client
+            .request("textDocument/definition", json!({}))
+            .unwrap();
+        assert_eq!(
+            format_locations(dir.path(), &result),
+            "main.rs:1:4: fn main() {"
+        );
+        let error = client
+            .request("textDocument/references", json!({}))
+            .unwrap_err();
+        assert_eq!(error, "language server error: content modified");
+
+        let text = std::fs::read_to_string(&file).unwrap();
+        let before = client.publications();
+        let (uri, changed) = client.sync_document(&file, &text).unwrap();
+        assert!(changed);
+        let list = client
+            .wait_diagnostics(&uri, before, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(
+            format_diagnostics("main.rs", &text, &list),
+            "main.rs:2:5: error[E0308]: mismatched types (rustc)"
+        );
+        assert!(!client.sync_document(&file, &text).unwrap().1);
+    }
+
+    #[test]
+    fn positions_resolve_symbols_to_utf16_offsets() {
+        let text = "let café = 1;\nlet total = café + subtotal;\n";
+        let position = Position {
+            line: 2,
+            symbol: Some("café".to_string()),
+            ..Position::default()
+        };
+        assert_eq!(
+            position.resolve(text).unwrap(),
+            json!({ "line": 1, "character": 12 })
+        );
+        let position = Position {
+            line: 2,
+            symbol: Some("total".to_string()),
+            ..Position::default()
+        };
+        // `total` as a whole word, not inside `subtotal`.
+        assert_eq!(position.resolve(text).unwrap()["character"], 4);
+        let position = Position {
+            line: 1,
+            column: Some(6),
+            ..Position::default()
+        };
+        assert_eq!(position.resolve(text).unwrap()["character"], 5);
+        assert!(
+            Position {
+                line: 9,
+                column: Some(1),
+                ..Position::default()
+            }
+            .resolve(text)
+            .unwrap_err()
+            .contains("outside the file")
+        );
+        assert_eq!(utf16_to_column("let café = 1;", 9), 10);
+    }
+
+    #[test]
+    fn user_config_overrides_default_servers() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join(CONFIG_FILE),
+            r#"{"python": {"command": "pylsp"}, "deno": {"command": "deno", "args": ["lsp"], "extensions": ["ts"]}}"#,
+        )
+        .unwrap();
+        let servers = load_servers(dir.path()).unwrap();
+        let python = server_for(&servers, Path::new("a.py")).unwrap();
+        assert_eq!(
+            (python.name.as_str(), python.command.as_str()),
+            ("python", "pylsp")
+        );
+        assert_eq!(
+            server_for(&servers, Path::new("a.ts")).unwrap().name,
+            "deno"
+        );
+        assert_eq!(
+            server_for(&servers, Path::new("a.js")).unwrap().name,
+            "typescript"
+        );
+        assert!(server_for(&servers, Path::new("notes.txt")).is_none());
+        assert_eq!(
+            uri_to_path(&path_to_uri(Path::new("/tmp/my dir/a.rs"))).unwrap(),
+            PathBuf::from("/tmp/my dir/a.rs")
+        );
+    }
+
+    #[test]
+    fn missing_server_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = ServerConfig {
+            name: "rust".to_string(),
+            command: "crabclaw-no-such-server".to_string(),
+            args: Vec::new(),
+            extensions: vec!["rs".to_string()],
+        };
+        let error = LspClient::spawn(&server, dir.path()).err().unwrap();
+        assert!(
+            error.starts_with("could not start language server 'rust'"),
+            "{error}"
+        );
+        assert!(diagnostics(dir.path(), "../a.rs").starts_with("Access denied"));
+    }
+
+    #[test]
+    fn workspace_server_definitions_are_ignored() {
+        // The model can write `.agent/lsp.json`; a server it declares there
+        // must never be started.
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join(".agent")).unwrap();
+        std::fs::write(
+            dir.path().join(".agent/lsp.json"),
+            r#"{"evil": {"command": "touch", "args": ["pwned"], "extensions": ["xyz"]}}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("a.xyz"), "x\n").unwrap();
+        let out = diagnostics(dir.path(), "a.xyz");
+        assert!(out.contains("no language server configured"), "{out}");
+        assert!(!dir.path().join("pwned").exists());
+
+        let server = ServerConfig {
+            name: "x".to_string(),
+            command: "my server".to_string(),
+            args: vec!["--stdio".to_string(), "a'b".to_string()],
+            extensions: Vec::new(),
+        };
+        assert_eq!(
+            command_line(&server, ShellKind::Sh),
+            r#"'my server' --stdio 'a'\''b'"#
+        );
+    }
+}
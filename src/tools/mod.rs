@@ -1,6 +1,27 @@
+pub mod archive;
+pub mod binary;
+pub mod calc;
+pub mod calendar;
+pub mod cargo;
+pub mod citations;
+pub mod code_outline;
+pub mod csv_query;
+pub mod documents;
+pub mod external;
 pub mod file_ops;
+pub mod lsp;
+pub mod notify;
+pub mod output;
+pub mod output_budget;
+pub mod pdf;
+pub mod pins;
 pub mod progressive;
+pub mod quiet_hours;
 pub mod registry;
 pub mod schedule;
+pub mod schedule_store;
+pub mod scratch;
+pub mod search_index;
 pub mod skills;
+pub mod watch;
 pub mod web;
@@ -0,0 +1,153 @@
+//! Typed tool results.
+//!
+//! Most tools answer with prose, but some return data or a file for the
+//! user. [`ToolOutput`] keeps that distinction: the model gets a rendering
+//! suited to the kind (pretty-printed JSON, a note about the attachment),
+//! channels send files and images along with the reply, and `tool.call`
+//! tape events keep the typed payload.
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+/// Largest file `file.send` attaches (Telegram's bot upload limit).
+pub const MAX_ATTACHMENT_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Result of one tool call.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "lowercase")]
+pub enum ToolOutput {
+    /// Prose or command output, given to the model as-is.
+    Text(String),
+    /// Structured data, given to the model pretty-printed.
+    Json(serde_json::Value),
+    /// A workspace file to send to the user.
+    File {
+        path: PathBuf,
+        caption: Option<String>,
+    },
+    /// An image to send to the user.
+    Image {
+        path: PathBuf,
+        caption: Option<String>,
+    },
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl ToolOutput {
+    /// An attachment for `path`: an image when the extension says so.
+    pub fn attachment(path: PathBuf, caption: Option<String>) -> Self {
+        if is_image(&path) {
+            Self::Image { path, caption }
+        } else {
+            Self::File { path, caption }
+        }
+    }
+
+    /// What the model sees as the tool result.
+    pub fn for_model(&self) -> String {
+        match self {
+            Self::Text(text) => text.clone(),
+            Self::Json(value) => {
+                serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+            }
+            Self::File { path, caption } | Self::Image { path, caption } => {
+                let kind = if matches!(self, Self::Image { .. }) {
+                    "image"
+                } else {
+                    "file"
+                };
+                let caption = caption
+                    .as_deref()
+                    .map(|c| format!(" with caption \"{c}\""))
+                    .unwrap_or_default();
+                format!(
+                    "Attached {kind} {}{caption}. The user receives it with your reply.",
+                    path.display()
+                )
+            }
+        }
+    }
+
+    /// Whether this is a file or image for the user.
+    pub fn is_attachment(&self) -> bool {
+        matches!(self, Self::File { .. } | Self::Image { .. })
+    }
+
+    /// Path of a file or image attachment.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::File { path, .. } | Self::Image { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| {
+        matches!(
+            e.to_ascii_lowercase().as_str(),
+            "png" | "jpg" | "jpeg" | "gif" | "webp"
+        )
+    })
+}
+
+/// `file.send`: attach a workspace file to the reply.
+pub fn send_file(workspace: &Path, path: &str, caption: Option<String>) -> ToolOutput {
+    let Some(resolved) = crate::tools::file_ops::resolve_safe_path(workspace, path) else {
+        return format!("Error: path '{path}' is outside the workspace.").into();
+    };
+    match std::fs::metadata(&resolved) {
+        Ok(meta) if !meta.is_file() => format!("Error: '{path}' is not a file.").into(),
+        Ok(meta) if meta.len() > MAX_ATTACHMENT_BYTES => format!(
+            "Error: '{path}' is {} MB; attachments are limited to {} MB.",
+            meta.len() / (1024 * 1024),
+            MAX_ATTACHMENT_BYTES / (1024 * 1024)
+        )
+        .into(),
+        Ok(_) => ToolOutput::attachment(resolved, caption),
+        Err(e) => format!("Error: cannot read '{path}': {e}").into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_each_kind_for_the_model() {
+        assert_eq!(ToolOutput::from("ok".to_string()).for_model(), "ok");
+        let json = ToolOutput::Json(serde_json::json!({"entries": 3}));
+        assert_eq!(json.for_model(), "{\n  \"entries\": 3\n}");
+        let image = ToolOutput::attachment(PathBuf::from("plot.PNG"), Some("Sales".into()));
+        assert!(matches!(image, ToolOutput::Image { .. }));
+        assert_eq!(
+            image.for_model(),
+            "Attached image plot.PNG with caption \"Sales\". The user receives it with your reply."
+        );
+        assert_eq!(
+            serde_json::to_value(&image).unwrap(),
+            serde_json::json!({"type": "image", "value": {"path": "plot.PNG", "caption": "Sales"}})
+        );
+    }
+
+    #[test]
+    fn send_file_stays_inside_the_workspace() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("report.pdf"), b"%PDF").unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+
+        let output = send_file(dir.path(), "report.pdf", None);
+        assert!(matches!(output, ToolOutput::File { .. }), "{output:?}");
+        assert!(output.is_attachment());
+        for bad in ["../etc/passwd", "sub", "missing.txt"] {
+            let output = send_file(dir.path(), bad, None);
+            assert!(output.for_model().starts_with("Error"), "{bad}: {output:?}");
+        }
+    }
+}
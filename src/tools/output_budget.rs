@@ -0,0 +1,228 @@
+//! Size limits for tool results returned to the model.
+//!
+//! Every tool result passes through [`OutputBudget::apply`] in
+//! `execute_tool`. A result over the tool's limit, or over what is left of
+//! the turn's budget, is cut at a character boundary and the full text is
+//! kept so the model can read the rest with `tool.output` (`id`, `offset`,
+//! `limit`). The agent loop resets the budget at the start of every turn.
+
+use std::sync::{Arc, Mutex};
+
+use crate::core::config::ToolOutputLimits;
+use crate::core::utils::safe_truncate;
+
+/// Tool that pages through truncated results.
+pub const PAGING_TOOL: &str = "tool.output";
+
+/// Bytes a result may always show, even when the turn budget is spent, so
+/// the model still sees errors and the paging hint.
+const MIN_OUTPUT_BYTES: usize = 2_000;
+
+/// Truncated results kept per turn; older ones are dropped first.
+const MAX_STORED_OUTPUTS: usize = 20;
+
+#[derive(Debug, Default)]
+struct TurnState {
+    used: usize,
+    next_id: usize,
+    stored: Vec<(String, String)>,
+}
+
+/// Output limits and usage for the current turn, shared between tool calls.
+#[derive(Debug, Clone, Default)]
+pub struct OutputBudget {
+    limits: ToolOutputLimits,
+    state: Arc<Mutex<TurnState>>,
+}
+
+impl OutputBudget {
+    pub fn new(limits: ToolOutputLimits) -> Self {
+        Self {
+            limits,
+            state: Arc::default(),
+        }
+    }
+
+    /// Start a new turn: usage goes back to zero and stored results are
+    /// dropped.
+    pub fn reset(&self) {
+        *self.state.lock().unwrap() = TurnState::default();
+    }
+
+    /// Bytes of tool output used so far this turn.
+    pub fn used(&self) -> usize {
+        self.state.lock().unwrap().used
+    }
+
+    /// Fit one result of `tool` into the budget.
+    pub fn apply(&self, tool: &str, output: String) -> String {
+        let mut state = self.state.lock().unwrap();
+        let (allowed, turn_limited) = self.allowance(tool, &state);
+        if output.len() <= allowed {
+            state.used += output.len();
+            return output;
+        }
+
+        let shown = safe_truncate(&output, allowed).to_string();
+        state.used += shown.len();
+        state.next_id += 1;
+        let id = format!("out-{}", state.next_id);
+        let notice = format!(
+            "[output truncated: showing bytes 0-{} of {}{}. Call {PAGING_TOOL} with \
+{{\"id\": \"{id}\", \"offset\": {}}} to read more.]",
+            shown.len(),
+            output.len(),
+            if turn_limited {
+                "; this turn's tool output budget is nearly spent"
+            } else {
+                ""
+            },
+            shown.len()
+        );
+        if state.stored.len() >= MAX_STORED_OUTPUTS {
+            state.stored.remove(0);
+        }
+        state.stored.push((id, output));
+        format!("{shown}\n\n{notice}")
+    }
+
+    /// Return part of a truncated result, starting at byte `offset`.
+    pub fn page(&self, id: &str, offset: usize, limit: Option<usize>) -> String {
+        let mut state = self.state.lock().unwrap();
+        let Some(full) = state
+            .stored
+            .iter()
+            .find(|(stored, _)| stored == id)
+            .map(|(_, text)| text.clone())
+        else {
+            return format!(
+                "Error: no stored output '{id}' (truncated outputs are kept for the current turn only)."
+            );
+        };
+        if offset >= full.len() {
+            return format!(
+                "Error: offset {offset} is past the end of '{id}' ({} bytes).",
+                full.len()
+            );
+        }
+
+        let (allowed, _) = self.allowance(PAGING_TOOL, &state);
+        let allowed = limit.filter(|&n| n > 0).map_or(allowed, |n| n.min(allowed));
+        let start = (0..=offset)
+            .rev()
+            .find(|&i| full.is_char_boundary(i))
+            .unwrap_or(0);
+        let chunk = safe_truncate(&full[start..], allowed);
+        // A limit inside the first character still returns that character.
+        let chunk = if chunk.is_empty() {
+            full[start..]
+                .chars()
+                .next()
+                .map_or("", |c| &full[start..start + c.len_utf8()])
+        } else {
+            chunk
+        };
+        let end = start + chunk.len();
+        state.used += chunk.len();
+
+        let mut out = chunk.to_string();
+        if end < full.len() {
+            out.push_str(&format!(
+                "\n\n[showing bytes {start}-{end} of {}. Call {PAGING_TOOL} with \
+{{\"id\": \"{id}\", \"offset\": {end}}} for the next part.]",
+                full.len()
+            ));
+        } else {
+            out.push_str(&format!(
+                "\n\n[showing bytes {start}-{end} of {}; end of output.]",
+                full.len()
+            ));
+        }
+        out
+    }
+
+    /// Bytes the next result of `tool` may use, and whether the turn budget
+    /// (rather than the tool's own limit) is the constraint.
+    fn allowance(&self, tool: &str, state: &TurnState) -> (usize, bool) {
+        let tool_limit = self.limits.for_tool(tool);
+        let remaining = self
+            .limits
+            .turn_bytes
+            .saturating_sub(state.used)
+            .max(MIN_OUTPUT_BYTES);
+        if remaining < tool_limit {
+            (remaining, true)
+        } else {
+            (tool_limit, false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn budget(tool_bytes: usize, turn_bytes: usize) -> OutputBudget {
+        OutputBudget::new(ToolOutputLimits {
+            tool_bytes,
+            per_tool: BTreeMap::from([("web.fetch".to_string(), 5_000)]),
+            turn_bytes,
+        })
+    }
+
+    #[test]
+    fn small_outputs_pass_through_and_count() {
+        let budget = budget(3_000, 10_000);
+        assert_eq!(budget.apply("file.read", "hello".to_string()), "hello");
+        assert_eq!(budget.used(), 5);
+        budget.reset();
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[test]
+    fn large_outputs_are_truncated_and_paged() {
+        let budget = budget(3_000, 100_000);
+        let text: String = (0..1_000).map(|i| format!("{i:04}\n")).collect();
+        let out = budget.apply("shell.exec", text.clone());
+        assert!(out.starts_with(&text[..3_000]));
+        assert!(out.contains("showing bytes 0-3000 of 5000"));
+        assert!(out.contains(r#"{"id": "out-1", "offset": 3000}"#));
+
+        let page = budget.page("out-1", 3_000, None);
+        assert!(page.starts_with(&text[3_000..]));
+        assert!(page.ends_with("end of output.]"));
+
+        let page = budget.page("out-1", 100, Some(10));
+        assert!(page.starts_with(&text[100..110]));
+        assert!(page.contains(r#""offset": 110}"#));
+        assert!(budget.page("out-2", 0, None).starts_with("Error"));
+        assert!(budget.page("out-1", 9_999, None).starts_with("Error"));
+    }
+
+    #[test]
+    fn per_tool_limits_override_the_default() {
+        let budget = budget(3_000, 100_000);
+        let out = budget.apply("web.fetch", "x".repeat(4_000));
+        assert_eq!(out, "x".repeat(4_000));
+    }
+
+    #[test]
+    fn turn_budget_shrinks_later_outputs() {
+        let budget = budget(50_000, 10_000);
+        budget.apply("file.read", "a".repeat(9_000));
+        let out = budget.apply("file.read", "b".repeat(5_000));
+        assert!(out.starts_with(&"b".repeat(MIN_OUTPUT_BYTES)));
+        assert!(!out.contains(&"b".repeat(MIN_OUTPUT_BYTES + 1)));
+        assert!(out.contains("budget is nearly spent"));
+    }
+
+    #[test]
+    fn truncation_respects_char_boundaries() {
+        let budget = budget(2_000, 100_000);
+        let out = budget.apply("file.read", "é".repeat(1_500));
+        assert!(out.starts_with(&"é".repeat(1_000)));
+        let page = budget.page("out-1", 2_001, Some(1));
+        assert!(page.starts_with('é'));
+    }
+}
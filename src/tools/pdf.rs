@@ -0,0 +1,167 @@
+//! PDF text extraction for `doc.extract`, on top of `pdf-extract`.
+//!
+//! Each page is extracted on its own, so one page the parser cannot handle
+//! comes back empty instead of losing the rest of the document.
+//! `pdf-extract` panics on some malformed input; those panics are caught
+//! and treated the same way. Encrypted files are rejected unless they open
+//! with the empty password, and scanned pages without a text layer come
+//! back empty.
+
+use std::panic::{AssertUnwindSafe, catch_unwind};
+
+use pdf_extract::{Document, PlainTextOutput};
+
+/// Text of every page of `bytes`, in page order.
+pub fn extract_pages(bytes: &[u8]) -> Result<Vec<String>, String> {
+    if !bytes.starts_with(b"%PDF") {
+        return Err("not a PDF file".to_string());
+    }
+    let loaded = catch_unwind(|| Document::load_mem(bytes));
+    let mut doc = match loaded {
+        Ok(Ok(doc)) => doc,
+        Ok(Err(e)) => return Err(format!("cannot read PDF: {e}")),
+        Err(_) => return Err("cannot read PDF (damaged file?)".to_string()),
+    };
+    if doc.is_encrypted() && doc.decrypt("").is_err() {
+        return Err("encrypted PDFs are not supported".to_string());
+    }
+    let pages: Vec<u32> = doc.get_pages().into_keys().collect();
+    if pages.is_empty() {
+        return Err("no pages found".to_string());
+    }
+    Ok(pages
+        .into_iter()
+        .map(|page| page_text(&doc, page))
+        .collect())
+}
+
+fn page_text(doc: &Document, page: u32) -> String {
+    let mut text = String::new();
+    let extracted = catch_unwind(AssertUnwindSafe(|| {
+        let mut output = PlainTextOutput::new(&mut text);
+        pdf_extract::output_doc_page(doc, &mut output, page)
+    }));
+    if !matches!(extracted, Ok(Ok(()))) {
+        return String::new();
+    }
+    text.lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+
+    /// A small PDF with one page per entry of `pages`, the first page's
+    /// content compressed.
+    pub(crate) fn sample_pdf(pages: &[&str]) -> Vec<u8> {
+        let mut objects: Vec<(String, Option<Vec<u8>>)> = Vec::new();
+        let first_page = 4;
+        let kids: Vec<String> = (0..pages.len())
+            .map(|i| format!("{} 0 R", first_page + i * 2))
+            .collect();
+        objects.push(("<< /Type /Catalog /Pages 2 0 R >>".into(), None));
+        objects.push((
+            format!(
+                "<< /Type /Pages /Kids [{}] /Count {} /MediaBox [0 0 612 792] >>",
+                kids.join(" "),
+                pages.len()
+            ),
+            None,
+        ));
+        objects.push((
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>".into(),
+            None,
+        ));
+        for (i, text) in pages.iter().enumerate() {
+            let content = text
+                .lines()
+                .map(|line| format!("({}) Tj T*", line.replace('(', "\\(").replace(')', "\\)")))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let content = format!("BT /F1 12 Tf 72 720 Td 14 TL\n{content}\nET");
+            let (filter, data) = if i == 0 {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(content.as_bytes()).unwrap();
+                (" /Filter /FlateDecode", encoder.finish().unwrap())
+            } else {
+                ("", content.into_bytes())
+            };
+            objects.push((
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                    first_page + i * 2 + 1
+                ),
+                None,
+            ));
+            objects.push((format!("<< /Length {}{filter} >>", data.len()), Some(data)));
+        }
+
+        let mut out = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, (dict, stream)) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n{dict}\n", i + 1).as_bytes());
+            if let Some(data) = stream {
+                out.extend_from_slice(b"stream\n");
+                out.extend_from_slice(data);
+                out.extend_from_slice(b"\nendstream\n");
+            }
+            out.extend_from_slice(b"endobj\n");
+        }
+        let xref = out.len();
+        out.extend_from_slice(format!("xref\n0 {}\n", offsets.len() + 1).as_bytes());
+        out.extend_from_slice(b"0000000000 65535 f \n");
+        for offset in offsets {
+            out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        out.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+                objects.len() + 1
+            )
+            .as_bytes(),
+        );
+        out
+    }
+
+    #[test]
+    fn pages_are_extracted_in_order() {
+        let pdf = sample_pdf(&["Quarterly report\nRevenue (EUR) grew", "Second page"]);
+        let pages = extract_pages(&pdf).unwrap();
+        assert_eq!(
+            pages,
+            vec!["Quarterly report\nRevenue (EUR) grew", "Second page"]
+        );
+    }
+
+    #[test]
+    fn encrypted_and_non_pdf_files_are_rejected() {
+        // Any dictionary under /Encrypt marks the file encrypted; the font
+        // dictionary is no security handler, so the empty password fails.
+        let mut pdf = sample_pdf(&["secret"]);
+        let marker = b"/Root 1 0 R >>";
+        let at = pdf.windows(marker.len()).position(|w| w == marker).unwrap();
+        pdf.splice(
+            at..at + marker.len(),
+            b"/Root 1 0 R /Encrypt 3 0 R >>".iter().copied(),
+        );
+        let err = extract_pages(&pdf).unwrap_err();
+        assert!(err.contains("encrypted"), "{err}");
+        assert!(extract_pages(b"PK\x03\x04").is_err());
+    }
+
+    #[test]
+    fn damaged_files_are_errors_not_panics() {
+        let pdf = sample_pdf(&["first", "second"]);
+        assert!(extract_pages(&pdf[..pdf.len() / 2]).is_err());
+        let object_stream = b"%PDF-1.5\n1 0 obj\n<< /Type /ObjStm /N 1 /First 18446744073709551615 /Length 4 >>\nstream\n1 0 \nendstream\nendobj\ntrailer\n<< /Root 1 0 R >>\n%%EOF\n";
+        assert!(extract_pages(object_stream).is_err());
+    }
+}
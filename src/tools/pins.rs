@@ -0,0 +1,141 @@
+//! Pinned notes: constraints that stay in the context window.
+//!
+//! A pin is a `pin` tape entry added with `,pin <note>` or the `pin.add`
+//! tool, and removed by a later `pin.removed` entry (`,unpin <id>`). Pins
+//! are read from the whole tape, not just the entries since the last
+//! anchor, and go into the request as a system message, so neither
+//! `,handoff` nor context trimming drops them. `,tape.reset` clears them.
+//!
+//! Tools cannot write the tape while a turn runs, so `pin.add` queues its
+//! note in the turn's [`PinLog`] and the agent loop records it afterwards.
+
+use std::sync::{Arc, Mutex};
+
+use crate::tape::store::TapeStore;
+
+/// Tape entry holding one pinned note (`{"note": ..., "origin": ...}`).
+pub const PIN_EVENT: &str = "pin";
+
+/// Tape entry removing a pin (`{"pin": <entry id>}`).
+pub const UNPIN_EVENT: &str = "pin.removed";
+
+/// A pinned note and the ID of the tape entry that holds it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pin {
+    pub id: u64,
+    pub note: String,
+}
+
+/// Notes pinned by `pin.add` during the current turn.
+#[derive(Debug, Clone, Default)]
+pub struct PinLog {
+    notes: Arc<Mutex<Vec<String>>>,
+}
+
+impl PinLog {
+    pub fn record(&self, note: &str) {
+        self.notes.lock().unwrap().push(note.to_string());
+    }
+
+    /// Drain the queued notes, leaving the log empty for the next turn.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.notes.lock().unwrap())
+    }
+}
+
+/// Record a pin and return its ID.
+pub fn add(tape: &mut TapeStore, note: &str, origin: &str) -> std::io::Result<u64> {
+    tape.append_event(
+        PIN_EVENT,
+        serde_json::json!({ "note": note, "origin": origin }),
+    )
+    .map(|entry| entry.id)
+}
+
+/// Remove the pin with entry ID `id`; `false` if there is no such pin.
+pub fn remove(tape: &mut TapeStore, id: u64) -> std::io::Result<bool> {
+    if !active_pins(tape).iter().any(|p| p.id == id) {
+        return Ok(false);
+    }
+    tape.append_event(UNPIN_EVENT, serde_json::json!({ "pin": id }))?;
+    Ok(true)
+}
+
+/// Pins that have not been removed, oldest first.
+pub fn active_pins(tape: &TapeStore) -> Vec<Pin> {
+    let entries = tape.entries();
+    let removed: Vec<u64> = entries
+        .iter()
+        .filter(|e| e.kind == UNPIN_EVENT)
+        .filter_map(|e| e.payload["pin"].as_u64())
+        .collect();
+    entries
+        .iter()
+        .filter(|e| e.kind == PIN_EVENT && !removed.contains(&e.id))
+        .filter_map(|e| {
+            let note = e.payload["note"].as_str()?.trim();
+            (!note.is_empty()).then(|| Pin {
+                id: e.id,
+                note: note.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// System message listing the pins, or `None` when there are none.
+pub fn pins_message(pins: &[Pin]) -> Option<String> {
+    if pins.is_empty() {
+        return None;
+    }
+    let mut lines = vec!["Pinned notes. They apply for the whole session:".to_string()];
+    lines.extend(pins.iter().map(|p| format!("- {}", p.note)));
+    Some(lines.join("\n"))
+}
+
+/// Pins with their IDs, for `,pins`.
+pub fn format_pins(pins: &[Pin]) -> String {
+    if pins.is_empty() {
+        return "No pinned notes. Add one with ,pin <note>.".to_string();
+    }
+    let mut lines = vec![format!("Pinned notes ({}):", pins.len())];
+    lines.extend(pins.iter().map(|p| format!("  #{} {}", p.id, p.note)));
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pins_survive_anchors_until_removed() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "test").unwrap();
+        let keep = add(&mut tape, "never touch the prod config", "human").unwrap();
+        let drop = add(&mut tape, "use metric units", "model").unwrap();
+        tape.anchor("handoff", serde_json::json!({})).unwrap();
+
+        assert!(remove(&mut tape, drop).unwrap());
+        assert!(!remove(&mut tape, drop).unwrap());
+        assert!(!remove(&mut tape, 999).unwrap());
+
+        let pins = active_pins(&tape);
+        assert_eq!(
+            pins,
+            [Pin {
+                id: keep,
+                note: "never touch the prod config".to_string()
+            }]
+        );
+        assert!(
+            pins_message(&pins)
+                .unwrap()
+                .ends_with("\n- never touch the prod config")
+        );
+    }
+
+    #[test]
+    fn no_pins_means_no_message() {
+        assert!(pins_message(&[]).is_none());
+        assert!(format_pins(&[]).starts_with("No pinned notes"));
+    }
+}
@@ -2,6 +2,7 @@ use std::collections::HashSet;
 
 use regex::Regex;
 
+use crate::tools::output_budget::PAGING_TOOL;
 use crate::tools::registry::ToolRegistry;
 
 /// Progressive tool view that starts compact and expands on demand.
@@ -110,7 +111,7 @@ impl ProgressiveToolView {
         let mut lines = vec!["<tool_details>".to_string()];
         for name in sorted_expanded(&self.expanded) {
             if let Some(descriptor) = self.registry.get(&name) {
-                let params = crate::tools::registry::tool_parameters(&name);
+                let params = self.registry.parameters(&name);
                 lines.push(format!("  <tool name=\"{}\">", descriptor.name));
                 lines.push(format!("    description: {}", descriptor.description));
                 lines.push(format!(
@@ -136,11 +137,16 @@ impl ProgressiveToolView {
             return crate::tools::registry::to_tool_definitions(&self.registry);
         }
 
-        // Only send expanded tools as API definitions
+        // Only send expanded tools as API definitions, plus `tool.output`
+        // so truncated results can always be paged.
+        let mut names = sorted_expanded(&self.expanded);
+        if !self.expanded.contains(PAGING_TOOL) {
+            names.push(PAGING_TOOL.to_string());
+        }
         let mut defs = Vec::new();
-        for name in sorted_expanded(&self.expanded) {
+        for name in names {
             if self.registry.has(&name) {
-                let params = crate::tools::registry::tool_parameters(&name);
+                let params = self.registry.parameters(&name);
                 defs.push(crate::llm::api_types::ToolDefinition {
                     tool_type: "function".to_string(),
                     function: crate::llm::api_types::FunctionDefinition {
@@ -163,6 +169,12 @@ impl ProgressiveToolView {
         self.expanded.len()
     }
 
+    /// Swap in a rebuilt registry, keeping expansions for tools that remain.
+    pub fn replace_registry(&mut self, registry: ToolRegistry) {
+        self.expanded.retain(|name| registry.has(name));
+        self.registry = registry;
+    }
+
     /// Clear expanded state.
     pub fn reset(&mut self) {
         self.expanded.clear();
@@ -227,6 +239,20 @@ mod tests {
         assert!(!block.contains("shell.exec"));
     }
 
+    #[test]
+    fn replace_registry_drops_removed_expansions() {
+        let mut view = ProgressiveToolView::new(test_registry());
+        view.note_selected("file.write");
+        view.note_selected("shell.exec");
+
+        let mut registry = ToolRegistry::new();
+        registry.register("shell.exec", "Execute shell commands", "builtin");
+        view.replace_registry(registry);
+
+        assert_eq!(view.expanded_count(), 1);
+        assert_eq!(view.all_tools(), vec!["shell.exec".to_string()]);
+    }
+
     #[test]
     fn note_hint_case_insensitive() {
         let mut view = ProgressiveToolView::new(test_registry());
@@ -303,6 +329,18 @@ mod tests {
         assert_eq!(defs[0].function.name, "file.write");
     }
 
+    #[test]
+    fn paging_tool_is_always_sent() {
+        let mut view = ProgressiveToolView::new(crate::tools::registry::builtin_registry());
+        view.note_selected("file.read");
+        let names: Vec<String> = view
+            .tool_definitions()
+            .into_iter()
+            .map(|d| d.function.name)
+            .collect();
+        assert_eq!(names, vec!["file.read", PAGING_TOOL]);
+    }
+
     #[test]
     fn all_tools_returns_names() {
         let view = ProgressiveToolView::new(test_registry());
@@ -0,0 +1,241 @@
+//! Quiet hours: a daily window during which scheduler notifications are
+//! held and delivered afterwards as one digest per destination.
+//!
+//! [`hold_during`] wraps a job's notifier; the scheduler itself is unaware
+//! of the window.
+
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{Local, NaiveTime, Timelike};
+use serde::Serialize;
+use tracing::debug;
+
+use crate::tools::schedule::Notifier;
+
+/// A daily local-time window, e.g. `22:00-07:00`. The start is inclusive and
+/// the end exclusive; a window whose end is earlier than its start spans
+/// midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct QuietHours {
+    #[serde(serialize_with = "serialize_time")]
+    pub start: NaiveTime,
+    #[serde(serialize_with = "serialize_time")]
+    pub end: NaiveTime,
+}
+
+fn serialize_time<S: serde::Serializer>(time: &NaiveTime, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&time.format("%H:%M").to_string())
+}
+
+impl QuietHours {
+    /// Parse `HH:MM-HH:MM`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (start, end) = value.split_once('-')?;
+        let time = |s: &str| NaiveTime::parse_from_str(s.trim(), "%H:%M").ok();
+        let (start, end) = (time(start)?, time(end)?);
+        (start != end).then_some(Self { start, end })
+    }
+
+    /// Whether `now` falls inside the window.
+    pub fn contains(&self, now: NaiveTime) -> bool {
+        if self.start < self.end {
+            self.start <= now && now < self.end
+        } else {
+            now >= self.start || now < self.end
+        }
+    }
+
+    /// Time from `now` until the window next ends.
+    pub fn until_end(&self, now: NaiveTime) -> Duration {
+        let seconds = |t: NaiveTime| i64::from(t.num_seconds_from_midnight());
+        let wait = (seconds(self.end) - seconds(now)).rem_euclid(86_400);
+        Duration::from_secs(if wait == 0 { 86_400 } else { wait as u64 })
+    }
+}
+
+impl std::fmt::Display for QuietHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M")
+        )
+    }
+}
+
+/// Wrap `notifier` so that text sent inside `window` is held and delivered
+/// once the window ends, merged with whatever else was held for it.
+pub fn hold_during(notifier: Notifier, window: QuietHours) -> Notifier {
+    Arc::new(move |text: String| {
+        if !window.contains(Local::now().time()) {
+            notifier(text);
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            // Nothing could deliver the digest later.
+            notifier(text);
+            return;
+        };
+        let mut held = held().lock().unwrap();
+        held.items.push((notifier.clone(), text, window));
+        debug!(held = held.items.len(), %window, "quiet_hours: notification held");
+        if !held.flush_pending {
+            held.flush_pending = true;
+            runtime.spawn(flush_when_due());
+        }
+    })
+}
+
+static HELD: OnceLock<Mutex<HeldNotifications>> = OnceLock::new();
+
+fn held() -> &'static Mutex<HeldNotifications> {
+    HELD.get_or_init(|| Mutex::new(HeldNotifications::default()))
+}
+
+/// Deliver held notifications as their windows end, until none are left.
+async fn flush_when_due() {
+    loop {
+        let wait = {
+            let mut held = held().lock().unwrap();
+            match held.next_flush(Local::now().time()) {
+                Some(wait) => wait,
+                None => {
+                    held.flush_pending = false;
+                    return;
+                }
+            }
+        };
+        tokio::time::sleep(wait).await;
+        let due = held().lock().unwrap().take_due(Local::now().time());
+        for (notifier, digest) in due {
+            notifier(digest);
+        }
+    }
+}
+
+/// Notifications held until their quiet window ends.
+#[derive(Default)]
+struct HeldNotifications {
+    items: Vec<(Notifier, String, QuietHours)>,
+    /// Whether a task is already waiting to flush the queue.
+    flush_pending: bool,
+}
+
+impl HeldNotifications {
+    /// Remove the notifications whose window is over at `now` and merge them
+    /// into one digest per notifier, in the order they were held.
+    fn take_due(&mut self, now: NaiveTime) -> Vec<(Notifier, String)> {
+        let (due, held): (Vec<_>, Vec<_>) = std::mem::take(&mut self.items)
+            .into_iter()
+            .partition(|(_, _, window)| !window.contains(now));
+        self.items = held;
+
+        let mut groups: Vec<(Notifier, Vec<String>)> = Vec::new();
+        for (notifier, text, _) in due {
+            match groups.iter_mut().find(|(n, _)| Arc::ptr_eq(n, &notifier)) {
+                Some((_, texts)) => texts.push(text),
+                None => groups.push((notifier, vec![text])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(notifier, mut texts)| {
+                let text = if texts.len() == 1 {
+                    texts.remove(0)
+                } else {
+                    format!(
+                        "\u{1f319} {} notifications held during quiet hours:\n\n{}",
+                        texts.len(),
+                        texts.join("\n\n---\n\n")
+                    )
+                };
+                (notifier, text)
+            })
+            .collect()
+    }
+
+    /// Time from `now` until the earliest held window ends.
+    fn next_flush(&self, now: NaiveTime) -> Option<Duration> {
+        self.items
+            .iter()
+            .map(|(_, _, window)| window.until_end(now))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(h: u32, m: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(h, m, 0).unwrap()
+    }
+
+    #[test]
+    fn windows_may_span_midnight() {
+        let night = QuietHours::parse("22:00-07:00").unwrap();
+        assert!(night.contains(at(23, 30)));
+        assert!(night.contains(at(3, 0)));
+        assert!(!night.contains(at(7, 0)));
+        assert!(!night.contains(at(12, 0)));
+        assert_eq!(night.until_end(at(3, 0)), Duration::from_secs(4 * 3600));
+        assert_eq!(night.until_end(at(23, 0)), Duration::from_secs(8 * 3600));
+        assert_eq!(night.to_string(), "22:00-07:00");
+
+        let lunch = QuietHours::parse(" 12:00 - 13:30 ").unwrap();
+        assert!(lunch.contains(at(12, 0)) && !lunch.contains(at(13, 30)));
+
+        assert!(QuietHours::parse("22:00").is_none());
+        assert!(QuietHours::parse("25:00-07:00").is_none());
+        assert!(QuietHours::parse("07:00-07:00").is_none());
+    }
+
+    #[test]
+    fn due_notifications_are_merged_per_notifier() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let chat: Notifier = Arc::new(move |text| sink.lock().unwrap().push(text));
+        let other: Notifier = Arc::new(|_| {});
+        let night = QuietHours::parse("22:00-07:00").unwrap();
+        let early = QuietHours::parse("22:00-06:00").unwrap();
+
+        let mut held = HeldNotifications::default();
+        held.items.push((chat.clone(), "first".into(), night));
+        held.items.push((other, "elsewhere".into(), early));
+        held.items.push((chat, "second".into(), night));
+        assert_eq!(
+            held.next_flush(at(3, 0)),
+            Some(Duration::from_secs(3 * 3600))
+        );
+
+        let due = held.take_due(at(6, 0));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].1, "elsewhere");
+
+        let due = held.take_due(at(7, 0));
+        assert_eq!(due.len(), 1);
+        assert!(due[0].1.starts_with("\u{1f319} 2 notifications held"));
+        assert!(due[0].1.ends_with("first\n\n---\n\nsecond"));
+        (due[0].0)(due[0].1.clone());
+        assert_eq!(seen.lock().unwrap().len(), 1);
+        assert!(held.items.is_empty());
+        assert_eq!(held.next_flush(at(7, 0)), None);
+    }
+
+    #[test]
+    fn outside_the_window_notifications_pass_straight_through() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let chat: Notifier = Arc::new(move |text| sink.lock().unwrap().push(text));
+        let now = Local::now().time();
+        // A one-minute window that ended a minute ago.
+        let window = QuietHours {
+            start: now - chrono::Duration::minutes(2),
+            end: now - chrono::Duration::minutes(1),
+        };
+        hold_during(chat, window)("now".into());
+        assert_eq!(*seen.lock().unwrap(), vec!["now"]);
+    }
+}
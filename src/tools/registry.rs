@@ -3,6 +3,13 @@ use std::sync::Arc;
 
 use serde::Serialize;
 
+use crate::core::config::NotifyConfig;
+use crate::tools::citations::{self, CitationLog};
+use crate::tools::external::ExternalTool;
+use crate::tools::output::{self, ToolOutput};
+use crate::tools::output_budget::{OutputBudget, PAGING_TOOL};
+use crate::tools::pins::PinLog;
+use crate::tools::quiet_hours::QuietHours;
 use crate::tools::schedule::{AgentRunner, Notifier};
 
 /// Execution context passed to tools during a model turn.
@@ -20,6 +27,55 @@ pub struct ToolContext {
     /// When set, schedule jobs can run the full agent pipeline
     /// (LLM + tools) and deliver results on fire.
     pub agent_runner: Option<AgentRunner>,
+    /// Web sources consulted during the current turn, cited after the
+    /// final answer.
+    pub citations: CitationLog,
+    /// Notes pinned with `pin.add` during the current turn.
+    pub pins: PinLog,
+    /// Calendars read by `calendar.upcoming` (`CALENDAR_URLS`).
+    pub calendars: Vec<String>,
+    /// Settings for the `notify_via` targets of `schedule.add`.
+    pub notify: NotifyConfig,
+    /// Default quiet hours for jobs added in this session (`QUIET_HOURS`).
+    pub quiet_hours: Option<QuietHours>,
+    /// Size limits for tool results in the current turn.
+    pub outputs: OutputBudget,
+    /// Asks the user before tools that run commands or change files
+    /// ([`needs_approval`]); `None` runs them without asking.
+    pub approver: Option<ToolApprover>,
+    /// Tools declared in `.agent/tools.json`, loaded with the registry
+    /// ([`register_external_tools`]).
+    pub external_tools: Vec<ExternalTool>,
+}
+
+/// Asks the user whether a tool call (name, JSON arguments) may run.
+pub type ToolApprover = Arc<dyn Fn(&str, &str) -> bool + Send + Sync>;
+
+/// Whether `name` runs commands or changes the workspace. `lsp.*` calls may
+/// start a language server.
+pub fn needs_approval(name: &str) -> bool {
+    matches!(
+        name,
+        "shell.exec"
+            | "rust.check"
+            | "rust.clippy"
+            | "rust.test"
+            | "file.write"
+            | "file.edit"
+            | "archive.extract"
+            | "lsp.definition"
+            | "lsp.references"
+            | "lsp.diagnostics"
+    )
+}
+
+/// Whether a call to an approval tool only reads the workspace, so it runs
+/// without asking: `shell.exec` with a read-only command
+/// ([`crate::core::shell::is_read_only`]).
+fn is_read_only_call(name: &str, args: &str, workspace: &std::path::Path) -> bool {
+    name == "shell.exec"
+        && parse_json_arg(args, "command")
+            .is_some_and(|command| crate::core::shell::is_read_only(&command, workspace))
 }
 
 impl ToolContext {
@@ -28,6 +84,14 @@ impl ToolContext {
         Self {
             notifier: None,
             agent_runner: None,
+            citations: CitationLog::default(),
+            pins: PinLog::default(),
+            calendars: Vec::new(),
+            notify: NotifyConfig::default(),
+            quiet_hours: None,
+            outputs: OutputBudget::default(),
+            approver: None,
+            external_tools: Vec::new(),
         }
     }
 
@@ -36,6 +100,14 @@ impl ToolContext {
         Self {
             notifier: Some(Arc::new(f)),
             agent_runner: None,
+            citations: CitationLog::default(),
+            pins: PinLog::default(),
+            calendars: Vec::new(),
+            notify: NotifyConfig::default(),
+            quiet_hours: None,
+            outputs: OutputBudget::default(),
+            approver: None,
+            external_tools: Vec::new(),
         }
     }
 }
@@ -46,6 +118,10 @@ pub struct ToolDescriptor {
     pub name: String,
     pub description: String,
     pub source: String,
+    /// Argument schema of tools that are not built in (see
+    /// [`ToolRegistry::parameters`]).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
 }
 
 /// Registry for tool descriptors.
@@ -73,10 +149,34 @@ impl ToolRegistry {
                 name: name.to_string(),
                 description: description.to_string(),
                 source: source.to_string(),
+                parameters: None,
             },
         );
     }
 
+    /// Register a tool that brings its own argument schema.
+    pub fn register_with_parameters(
+        &mut self,
+        name: &str,
+        description: &str,
+        source: &str,
+        parameters: serde_json::Value,
+    ) {
+        self.register(name, description, source);
+        if let Some(tool) = self.tools.get_mut(name) {
+            tool.parameters = Some(parameters);
+        }
+    }
+
+    /// JSON schema of a tool's arguments: its own schema, or the built-in
+    /// one ([`tool_parameters`]).
+    pub fn parameters(&self, name: &str) -> serde_json::Value {
+        self.tools
+            .get(name)
+            .and_then(|tool| tool.parameters.clone())
+            .unwrap_or_else(|| tool_parameters(name))
+    }
+
     /// Check if a tool exists.
     pub fn has(&self, name: &str) -> bool {
         self.tools.contains_key(name)
@@ -100,6 +200,11 @@ impl ToolRegistry {
             .collect()
     }
 
+    /// Keep only the tools whose name satisfies `keep`.
+    pub fn retain<F: FnMut(&str) -> bool>(&mut self, mut keep: F) {
+        self.tools.retain(|name, _| keep(name));
+    }
+
     /// Number of registered tools.
     pub fn len(&self) -> usize {
         self.tools.len()
@@ -139,6 +244,24 @@ pub fn builtin_tool_specs() -> Vec<BuiltinToolSpec> {
             description: "Show tape session info (entry count, file path)",
             parameters: empty_tool_parameters(),
         },
+        BuiltinToolSpec {
+            name: "file.send",
+            description: "Send a workspace file to the user with your reply (images are shown inline).",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "File path relative to the workspace"
+                    },
+                    "caption": {
+                        "type": "string",
+                        "description": "Optional caption shown with the file"
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
         BuiltinToolSpec {
             name: "help",
             description: "Show available commands",
@@ -149,6 +272,28 @@ pub fn builtin_tool_specs() -> Vec<BuiltinToolSpec> {
             description: "List all registered tools",
             parameters: empty_tool_parameters(),
         },
+        BuiltinToolSpec {
+            name: PAGING_TOOL,
+            description: "Read more of a truncated tool result, by the id and offset given in its truncation note.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Output id from the truncation note (e.g. out-1)"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "Byte offset to start from"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum bytes to return (default: the tool output limit)"
+                    }
+                },
+                "required": ["id", "offset"]
+            }),
+        },
         BuiltinToolSpec {
             name: "skills",
             description: "List discovered skills from workspace",
@@ -168,15 +313,145 @@ pub fn builtin_tool_specs() -> Vec<BuiltinToolSpec> {
                 "required": ["command"]
             }),
         },
+        BuiltinToolSpec {
+            name: "rust.check",
+            description: "Run `cargo check` on all targets and return the compiler errors and warnings as a short file:line list with suggestions. Prefer this over shell.exec for Rust builds.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Crate or workspace directory containing Cargo.toml, relative to the workspace root (default: the root)"
+                    },
+                    "package": {
+                        "type": "string",
+                        "description": "Only this package (cargo -p)"
+                    }
+                }
+            }),
+        },
+        BuiltinToolSpec {
+            name: "rust.clippy",
+            description: "Run `cargo clippy` on all targets and return lints and errors as a short file:line list with suggestions.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Crate or workspace directory containing Cargo.toml, relative to the workspace root (default: the root)"
+                    },
+                    "package": {
+                        "type": "string",
+                        "description": "Only this package (cargo -p)"
+                    }
+                }
+            }),
+        },
+        BuiltinToolSpec {
+            name: "rust.test",
+            description: "Run `cargo test` and return pass/fail totals, each failed test's panic message, and any compile errors.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Crate or workspace directory containing Cargo.toml, relative to the workspace root (default: the root)"
+                    },
+                    "package": {
+                        "type": "string",
+                        "description": "Only this package (cargo -p)"
+                    },
+                    "filter": {
+                        "type": "string",
+                        "description": "Only run tests whose name contains this (e.g. a module path)"
+                    }
+                }
+            }),
+        },
+        BuiltinToolSpec {
+            name: "scratch.read",
+            description: "Read this conversation's scratchpad: notes, plans and partial results saved with scratch.write/append in earlier tool rounds.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {}
+            }),
+        },
+        BuiltinToolSpec {
+            name: "scratch.write",
+            description: "Replace this conversation's scratchpad with new content. Use it for plans and intermediate results you need in later tool rounds; it is private to the session and not part of the workspace.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "content": {
+                        "type": "string",
+                        "description": "Markdown text"
+                    }
+                },
+                "required": ["content"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "scratch.append",
+            description: "Append a note to this conversation's scratchpad.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "content": {
+                        "type": "string",
+                        "description": "Markdown text"
+                    }
+                },
+                "required": ["content"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "pin.add",
+            description: "Pin a note that stays in the context for the rest of the session, even after older messages are dropped. Use it for lasting constraints the user states, e.g. \"never touch the prod config\".",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "note": {
+                        "type": "string",
+                        "description": "The constraint or fact to keep, in one sentence"
+                    }
+                },
+                "required": ["note"]
+            }),
+        },
         BuiltinToolSpec {
             name: "file.read",
-            description: "Read the contents of a file in the workspace. Path is relative to workspace root.",
+            description: "Read the contents of a file in the workspace. Path is relative to workspace root. Binary files return their size and type instead; use mode=hexdump to see their bytes.",
             parameters: serde_json::json!({
                 "type": "object",
                 "properties": {
                     "path": {
                         "type": "string",
                         "description": "Path to the file relative to the workspace root"
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["auto", "metadata", "hexdump"],
+                        "description": "auto (default): text, or metadata for binary files; metadata: size and type only; hexdump: metadata plus a hexdump"
+                    },
+                    "encoding": {
+                        "type": "string",
+                        "description": "Encoding of a non-UTF-8 text file, e.g. gbk, shift_jis, latin1"
+                    },
+                    "offset": {
+                        "type": "integer",
+                        "description": "First byte of the hexdump (default 0)"
+                    },
+                    "length": {
+                        "type": "integer",
+                        "description": "Hexdump length in bytes (default 256, max 4096)"
+                    },
+                    "start_line": {
+                        "type": "integer",
+                        "description": "First line of text to return (1-based); pair with code.outline ranges"
+                    },
+                    "end_line": {
+                        "type": "integer",
+                        "description": "Last line of text to return (1-based, inclusive)"
                     }
                 },
                 "required": ["path"]
@@ -214,6 +489,230 @@ pub fn builtin_tool_specs() -> Vec<BuiltinToolSpec> {
                 "required": []
             }),
         },
+        BuiltinToolSpec {
+            name: "file.tree",
+            description: "Show a depth-limited tree of a workspace directory with file sizes, skipping gitignored, hidden and build directories. Use it for a project overview instead of repeated file.list calls.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Directory relative to the workspace root. Empty string for root."
+                    },
+                    "depth": {
+                        "type": "integer",
+                        "description": "Levels to descend (default 3, max 10)"
+                    }
+                },
+                "required": []
+            }),
+        },
+        BuiltinToolSpec {
+            name: "doc.extract",
+            description: "Extract the text of a PDF, DOCX or XLSX file in the workspace as markdown: PDF pages, DOCX headings/lists/tables, one table per XLSX sheet. Use it to read reports and spreadsheets that file.read cannot show.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the document relative to the workspace root"
+                    },
+                    "pages": {
+                        "type": "string",
+                        "description": "PDF pages or XLSX sheets to extract, e.g. \"1-3,5\" or \"4-\" (default: all)"
+                    },
+                    "max_chars": {
+                        "type": "integer",
+                        "description": "Maximum characters returned (default 20000)"
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "calc",
+            description: "Evaluate arithmetic exactly, convert units and do date math. Use it instead of mental arithmetic. Examples: \"1249.99 * 12 * 1.0825\", \"15% of 80\", \"5.2 km to mi\", \"72 F to C\", \"2026-03-01 + 45 days\", \"2026-12-25 - today\".",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "expression": {
+                        "type": "string",
+                        "description": "Arithmetic (+ - * / % ^ !, sqrt, round(x, n), min, max, ...), '<value> <unit> to <unit>', or dates (YYYY-MM-DD, today, now) plus/minus durations (3 weeks, 1h30m, 2 months)"
+                    },
+                    "precision": {
+                        "type": "integer",
+                        "description": "Decimal places kept when a division does not terminate (default 20)"
+                    }
+                },
+                "required": ["expression"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "csv.query",
+            description: "Query a CSV file in the workspace: filter rows, select columns, group and aggregate, sort, and return only the first or last rows (max 200). Use it to answer questions about tabular data instead of reading the whole file. The result starts with the row count and column types.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the CSV file relative to the workspace root"
+                    },
+                    "columns": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Columns to return (default: all)"
+                    },
+                    "filters": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Conditions rows must all meet, e.g. \"amount > 100\", \"status == paid\", \"name ~ smith\" (~ is case-insensitive contains; also !=, >=, <, <=, !~)"
+                    },
+                    "group_by": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Columns to group by"
+                    },
+                    "aggregates": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Aggregates per group: count, count:col, distinct:col, sum:col, avg:col, min:col, max:col (default with group_by: count)"
+                    },
+                    "sort_by": {
+                        "type": "string",
+                        "description": "Column (or aggregate such as \"sum(amount)\") to sort by; prefix with - for descending"
+                    },
+                    "head": {
+                        "type": "integer",
+                        "description": "Return the first N rows (default 20, max 200)"
+                    },
+                    "tail": {
+                        "type": "integer",
+                        "description": "Return the last N rows instead"
+                    },
+                    "delimiter": {
+                        "type": "string",
+                        "description": "Field delimiter (default: detected from the header; use \"tab\" for TSV)"
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "archive.list",
+            description: "List the files inside a .zip, .tar or .tar.gz archive in the workspace, with sizes.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the archive relative to the workspace root"
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "archive.extract",
+            description: "Extract a .zip, .tar or .tar.gz archive in the workspace. Existing files are not overwritten; links and paths leaving the destination are skipped, and extraction stops at 100 MB or 5000 files.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the archive relative to the workspace root"
+                    },
+                    "dest": {
+                        "type": "string",
+                        "description": "Destination directory relative to the workspace root (default: a directory named after the archive, next to it)"
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "code.outline",
+            description: "Outline a source file: functions, types, impls and classes with their line ranges (Rust, Python, Go, JS/TS, Java, C#, C/C++). Use it to navigate a large file, then read only the ranges you need with file.read start_line/end_line.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the source file relative to the workspace root"
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "lsp.definition",
+            description: "Find where a symbol is defined using the workspace's language server (precise across files, unlike text search). Give the line and the symbol name as it appears there.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the source file relative to the workspace root"
+                    },
+                    "line": {
+                        "type": "integer",
+                        "description": "Line of the symbol (1-based)"
+                    },
+                    "column": {
+                        "type": "integer",
+                        "description": "Column of the symbol (1-based); or pass symbol instead"
+                    },
+                    "symbol": {
+                        "type": "string",
+                        "description": "The identifier as written on that line, used to find the column"
+                    }
+                },
+                "required": ["path", "line"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "lsp.references",
+            description: "List every reference to a symbol using the workspace's language server. Give the line and the symbol name as it appears there.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the source file relative to the workspace root"
+                    },
+                    "line": {
+                        "type": "integer",
+                        "description": "Line of the symbol (1-based)"
+                    },
+                    "column": {
+                        "type": "integer",
+                        "description": "Column of the symbol (1-based); or pass symbol instead"
+                    },
+                    "symbol": {
+                        "type": "string",
+                        "description": "The identifier as written on that line, used to find the column"
+                    },
+                    "include_declaration": {
+                        "type": "boolean",
+                        "description": "Also list the declaration itself (default true)"
+                    }
+                },
+                "required": ["path", "line"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "lsp.diagnostics",
+            description: "Errors and warnings the workspace's language server reports for a file (type errors, unresolved names, lints).",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path to the source file relative to the workspace root"
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
         BuiltinToolSpec {
             name: "file.search",
             description: "Search for text within files in the workspace (recursive grep). Case-insensitive.",
@@ -286,6 +785,23 @@ pub fn builtin_tool_specs() -> Vec<BuiltinToolSpec> {
                 "required": ["query"]
             }),
         },
+        BuiltinToolSpec {
+            name: "calendar.upcoming",
+            description: "List upcoming events from the configured calendars, soonest first, with start times and seconds until each starts (usable as schedule.add after_seconds).",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "limit": {
+                        "type": "integer",
+                        "description": "Maximum number of events (default 10)"
+                    },
+                    "days": {
+                        "type": "integer",
+                        "description": "How many days ahead to look (default 7)"
+                    }
+                }
+            }),
+        },
         BuiltinToolSpec {
             name: "schedule.add",
             description: "Schedule a reminder. Specify after_seconds (one-shot) or interval_seconds (repeating).",
@@ -302,34 +818,100 @@ pub fn builtin_tool_specs() -> Vec<BuiltinToolSpec> {
                     },
                     "interval_seconds": {
                         "type": "integer",
-                        "description": "Fire repeatedly at this interval in seconds"
-                    },
-                    "mode": {
-                        "type": "string",
-                        "enum": ["reminder", "agent"],
-                        "description": "IMPORTANT: Use 'agent' when the task requires action (web fetching, analysis, summarization, etc.). Use 'reminder' only for simple text notifications like 'drink water'. Default is 'reminder'."
+                        "description": "Fire repeatedly at this interval in seconds"
+                    },
+                    "mode": {
+                        "type": "string",
+                        "enum": ["reminder", "agent"],
+                        "description": "IMPORTANT: Use 'agent' when the task requires action (web fetching, analysis, summarization, etc.). Use 'reminder' only for simple text notifications like 'drink water'. Default is 'reminder'."
+                    },
+                    "quiet_hours": {
+                        "type": "string",
+                        "description": "Override the configured quiet hours for this job: 'HH:MM-HH:MM' (local time) or 'off' to always deliver immediately. Notifications during quiet hours are held and sent as one digest afterwards."
+                    },
+                    "notify_via": {
+                        "type": "string",
+                        "description": "Comma-separated delivery targets: origin (this chat, the default), telegram:<chat_id>, desktop, webhook[:<url>], email[:<address>]. Only set this when the user asks for a different destination."
+                    }
+                },
+                "required": ["message"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "schedule.list",
+            description: "List all active scheduled jobs.",
+            parameters: empty_tool_parameters(),
+        },
+        BuiltinToolSpec {
+            name: "schedule.history",
+            description: "Show past runs of scheduled jobs: start and end time, success or error, tools called and an output summary. Give job_id to inspect one job (e.g. to find out why a recurring job stopped producing useful results).",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "job_id": {
+                        "type": "string",
+                        "description": "Job to inspect; omit for the most recent runs across all jobs"
+                    },
+                    "limit": {
+                        "type": "integer",
+                        "description": "Runs to list across all jobs (default 20)"
+                    }
+                }
+            }),
+        },
+        BuiltinToolSpec {
+            name: "schedule.remove",
+            description: "Remove a scheduled job by its ID.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "job_id": {
+                        "type": "string",
+                        "description": "The ID of the job to remove"
+                    }
+                },
+                "required": ["job_id"]
+            }),
+        },
+        BuiltinToolSpec {
+            name: "watch.add",
+            description: "Watch a workspace file, directory or glob (e.g. 'src/**/*.rs') and report when it changes. With 'prompt', run that task as the agent with the changed files listed instead of sending a notification.",
+            parameters: serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Workspace-relative path or glob to watch"
+                    },
+                    "prompt": {
+                        "type": "string",
+                        "description": "Optional task for the agent to run when the files change (e.g. 'The build log changed; summarize any errors')"
+                    },
+                    "interval_seconds": {
+                        "type": "integer",
+                        "description": "How often to check for changes (default 5)"
                     }
                 },
-                "required": ["message"]
+                "required": ["path"]
             }),
         },
         BuiltinToolSpec {
-            name: "schedule.list",
-            description: "List all active scheduled jobs.",
+            name: "watch.list",
+            description: "List active file watches.",
             parameters: empty_tool_parameters(),
         },
         BuiltinToolSpec {
-            name: "schedule.remove",
-            description: "Remove a scheduled job by its ID.",
+            name: "watch.remove",
+            description: "Stop a file watch by its ID.",
             parameters: serde_json::json!({
                 "type": "object",
                 "properties": {
-                    "job_id": {
+                    "watch_id": {
                         "type": "string",
-                        "description": "The ID of the job to remove"
+                        "description": "The ID of the watch to remove"
                     }
                 },
-                "required": ["job_id"]
+                "required": ["watch_id"]
             }),
         },
     ]
@@ -374,6 +956,34 @@ pub fn register_skills(registry: &mut ToolRegistry, workspace: &std::path::Path)
     }
 }
 
+/// Register the tools declared in the workspace's `.agent/tools.json` and
+/// return them, for [`ToolContext::external_tools`].
+///
+/// An invalid manifest is logged and none of its tools are registered.
+pub fn register_external_tools(
+    registry: &mut ToolRegistry,
+    workspace: &std::path::Path,
+) -> Vec<ExternalTool> {
+    use crate::tools::external;
+    match external::load(workspace) {
+        Ok(tools) => {
+            for tool in &tools {
+                registry.register_with_parameters(
+                    &tool.name,
+                    &tool.description,
+                    external::SOURCE,
+                    tool.parameters.clone(),
+                );
+            }
+            tools
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "tools.external.invalid");
+            Vec::new()
+        }
+    }
+}
+
 /// Generate OpenAI-compatible tool definitions from the registry.
 ///
 /// Tools with parameters get proper JSON schemas; others get empty params.
@@ -382,7 +992,7 @@ pub fn to_tool_definitions(registry: &ToolRegistry) -> Vec<crate::llm::api_types
         .list()
         .into_iter()
         .map(|tool| {
-            let parameters = tool_parameters(&tool.name);
+            let parameters = registry.parameters(&tool.name);
             crate::llm::api_types::ToolDefinition {
                 tool_type: "function".to_string(),
                 function: crate::llm::api_types::FunctionDefinition {
@@ -404,11 +1014,41 @@ pub fn tool_parameters(name: &str) -> serde_json::Value {
         .unwrap_or_else(empty_tool_parameters)
 }
 
+/// Check a tool call's arguments against the tool's parameter schema.
+///
+/// Returns the message sent back to the model instead of running the tool
+/// when they do not match: the problems found and the expected schema.
+/// Empty arguments count as `{}`.
+pub fn check_tool_arguments(name: &str, args: &str) -> Option<String> {
+    argument_error(name, args, &tool_parameters(name))
+}
+
+/// [`check_tool_arguments`] against a given schema.
+pub(crate) fn argument_error(name: &str, args: &str, schema: &serde_json::Value) -> Option<String> {
+    let problems = if args.trim().is_empty() {
+        crate::core::json_schema::validate(&serde_json::json!({}), schema)
+    } else {
+        match serde_json::from_str::<serde_json::Value>(args) {
+            Ok(value) => crate::core::json_schema::validate(&value, schema),
+            Err(e) => vec![format!("arguments are not valid JSON ({e})")],
+        }
+    };
+    if problems.is_empty() {
+        return None;
+    }
+    let schema = serde_json::to_string_pretty(schema).unwrap_or_default();
+    Some(format!(
+        "Error: invalid arguments for {name}:\n- {}\nExpected parameters (JSON Schema):\n{schema}\nCall {name} again with arguments that match.",
+        problems.join("\n- ")
+    ))
+}
+
 /// Execute a tool by name and return the result as a string.
 ///
 /// Supports builtin tools, `shell.exec`, and skill tools.
 /// The `ctx` parameter carries session-specific context (e.g. notification
-/// callbacks for schedule jobs).
+/// callbacks for schedule jobs). Results are fitted to the turn's output
+/// budget; `tool.output` pages through the truncated ones.
 pub fn execute_tool(
     name: &str,
     args: &str,
@@ -416,17 +1056,98 @@ pub fn execute_tool(
     workspace: &std::path::Path,
     ctx: &ToolContext,
 ) -> String {
+    execute_tool_output(name, args, tape, workspace, ctx).for_model()
+}
+
+/// Like [`execute_tool`], but keeps the kind of result ([`ToolOutput`]).
+///
+/// JSON results that exceed the output budget are truncated as text.
+pub fn execute_tool_output(
+    name: &str,
+    args: &str,
+    tape: &crate::tape::store::TapeStore,
+    workspace: &std::path::Path,
+    ctx: &ToolContext,
+) -> ToolOutput {
+    if name == PAGING_TOOL {
+        let id = parse_json_arg(args, "id").unwrap_or_default();
+        if id.is_empty() {
+            return "Error: 'id' argument is required.".to_string().into();
+        }
+        let (offset, limit) = match serde_json::from_str::<serde_json::Value>(args) {
+            Ok(v) => (v["offset"].as_u64().unwrap_or(0), v["limit"].as_u64()),
+            Err(_) => (0, None),
+        };
+        return ctx
+            .outputs
+            .page(&id, offset as usize, limit.map(|n| n as usize))
+            .into();
+    }
+    // Declared tools run commands, so they need approval too; the user is
+    // shown the command line they expand to.
+    let external = ctx.external_tools.iter().find(|tool| tool.name == name);
+    let approval_args = external.map_or_else(|| args.to_string(), |tool| tool.approval_args(args));
+    if let Some(approver) = &ctx.approver
+        && (needs_approval(name) || external.is_some())
+        && !is_read_only_call(name, args, workspace)
+        && !approver(name, &approval_args)
+    {
+        return format!("Error: the user declined to run {name}.").into();
+    }
+    match run_tool(name, args, tape, workspace, ctx) {
+        ToolOutput::Text(text) => ctx.outputs.apply(name, text).into(),
+        ToolOutput::Json(value) => {
+            let output = ToolOutput::Json(value);
+            let rendered = output.for_model();
+            let fitted = ctx.outputs.apply(name, rendered.clone());
+            if fitted == rendered {
+                output
+            } else {
+                fitted.into()
+            }
+        }
+        attachment => attachment,
+    }
+}
+
+fn run_tool(
+    name: &str,
+    args: &str,
+    tape: &crate::tape::store::TapeStore,
+    workspace: &std::path::Path,
+    ctx: &ToolContext,
+) -> ToolOutput {
     match name {
         "tape.info" => {
             let info = tape.info();
-            format!(
-                "Tape: {}\nEntries: {}\nAnchors: {}\nLast anchor: {}",
-                info.name,
-                info.entries,
-                info.anchors,
-                info.last_anchor.as_deref().unwrap_or("none")
-            )
+            ToolOutput::Json(serde_json::json!({
+                "tape": info.name,
+                "entries": info.entries,
+                "anchors": info.anchors,
+                "last_anchor": info.last_anchor,
+                "entries_since_last_anchor": info.entries_since_last_anchor,
+            }))
+        }
+        "file.send" => {
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            if path.is_empty() {
+                return "Error: 'path' argument is required.".to_string().into();
+            }
+            let caption = parse_json_arg(args, "caption").filter(|c| !c.is_empty());
+            output::send_file(workspace, &path, caption)
         }
+        _ => run_text_tool(name, args, tape, workspace, ctx).into(),
+    }
+}
+
+fn run_text_tool(
+    name: &str,
+    args: &str,
+    tape: &crate::tape::store::TapeStore,
+    workspace: &std::path::Path,
+    ctx: &ToolContext,
+) -> String {
+    match name {
         "tape.reset" => {
             // Note: actual reset requires &mut TapeStore, so we just report status
             "Tape reset is only available via the ,tape.reset command.".to_string()
@@ -478,22 +1199,70 @@ pub fn execute_tool(
                 }
             };
 
-            let result = crate::core::shell::execute_shell(&command, workspace);
+            let result = crate::core::shell::execute_shell_as(
+                &command,
+                workspace,
+                crate::core::shell::CommandOrigin::Tool,
+            );
             let output = crate::core::shell::format_shell_output(&result);
+            let ok = result.exit_code == 0 && !result.timed_out;
+            crate::core::audit::record(workspace, tape, "tool", name, &command, ok, &output);
 
-            if result.exit_code == 0 && !result.timed_out {
+            if ok {
                 output
             } else {
                 crate::core::shell::wrap_failure_context(&command, &result)
             }
         }
+        "rust.check" | "rust.clippy" | "rust.test" => {
+            use crate::tools::cargo;
+            let Some(command) = cargo::CargoCommand::from_tool(name) else {
+                return format!("Error: unknown tool '{name}'.");
+            };
+            let value: serde_json::Value = serde_json::from_str(args).unwrap_or_default();
+            let request = cargo::CargoRequest::from_args(&value);
+            let (output, ok) = cargo::run(command, workspace, &request);
+            crate::core::audit::record(workspace, tape, "tool", name, args, ok, &output);
+            output
+        }
+        "scratch.read" => crate::tools::scratch::read(tape),
+        "scratch.write" | "scratch.append" => {
+            let Some(content) = parse_json_arg(args, "content") else {
+                return "Error: 'content' argument is required.".to_string();
+            };
+            if name == "scratch.write" {
+                crate::tools::scratch::write(tape, &content)
+            } else {
+                crate::tools::scratch::append(tape, &content)
+            }
+        }
+        "pin.add" => {
+            let Some(note) = parse_json_arg(args, "note").filter(|n| !n.trim().is_empty()) else {
+                return "Error: 'note' argument is required.".to_string();
+            };
+            ctx.pins.record(note.trim());
+            format!("Pinned: {}", note.trim())
+        }
         "file.read" => {
             use crate::tools::file_ops;
             let path = parse_json_arg(args, "path").unwrap_or_default();
             if path.is_empty() {
                 return "Error: 'path' argument is required.".to_string();
             }
-            file_ops::read_file(workspace, &path)
+            let value: serde_json::Value = serde_json::from_str(args).unwrap_or_default();
+            let mode = value["mode"].as_str().unwrap_or_default();
+            let Some(mode) = file_ops::ReadMode::parse(mode) else {
+                return format!("Error: unknown mode '{mode}' (auto, metadata or hexdump).");
+            };
+            let options = file_ops::ReadOptions {
+                mode,
+                encoding: parse_json_arg(args, "encoding").filter(|e| !e.is_empty()),
+                offset: value["offset"].as_u64().unwrap_or(0),
+                length: value["length"].as_u64().map(|n| n as usize),
+                start_line: value["start_line"].as_u64().map(|n| n as usize),
+                end_line: value["end_line"].as_u64().map(|n| n as usize),
+            };
+            file_ops::read_file_with(workspace, &path, &options)
         }
         "file.write" => {
             use crate::tools::file_ops;
@@ -502,13 +1271,111 @@ pub fn execute_tool(
             if path.is_empty() {
                 return "Error: 'path' argument is required.".to_string();
             }
-            file_ops::write_file(workspace, &path, &content)
+            let output = file_ops::write_file(workspace, &path, &content);
+            let ok = !output.starts_with("Error");
+            crate::core::audit::record(workspace, tape, "tool", name, &path, ok, &output);
+            output
         }
         "file.list" => {
             use crate::tools::file_ops;
             let path = parse_json_arg(args, "path").unwrap_or_default();
             file_ops::list_directory(workspace, &path)
         }
+        "file.tree" => {
+            use crate::tools::file_ops;
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            let depth = serde_json::from_str::<serde_json::Value>(args)
+                .ok()
+                .and_then(|v| v["depth"].as_u64())
+                .map_or(file_ops::DEFAULT_TREE_DEPTH, |d| d as usize);
+            file_ops::tree_directory(workspace, &path, depth)
+        }
+        "doc.extract" => {
+            use crate::tools::documents;
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            if path.is_empty() {
+                return "Error: 'path' argument is required.".to_string();
+            }
+            let pages = parse_json_arg(args, "pages").unwrap_or_default();
+            let max_chars = serde_json::from_str::<serde_json::Value>(args)
+                .ok()
+                .and_then(|v| v["max_chars"].as_u64())
+                .map_or(documents::DEFAULT_MAX_CHARS, |n| n as usize);
+            documents::extract_document(workspace, &path, &pages, max_chars)
+        }
+        "calc" => {
+            use crate::tools::calc;
+            let expression = parse_json_arg(args, "expression").unwrap_or_default();
+            let precision = serde_json::from_str::<serde_json::Value>(args)
+                .ok()
+                .and_then(|v| v["precision"].as_u64())
+                .map_or(calc::DEFAULT_PRECISION, |n| {
+                    n.min(u64::from(u32::MAX)) as u32
+                });
+            calc::evaluate(&expression, precision)
+        }
+        "csv.query" => {
+            use crate::tools::csv_query::{CsvQuery, run_query};
+            let value = serde_json::from_str::<serde_json::Value>(args).unwrap_or_default();
+            match CsvQuery::from_args(&value) {
+                Ok(query) => run_query(workspace, &query),
+                Err(e) => format!("Error: {e}"),
+            }
+        }
+        "archive.list" => {
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            if path.is_empty() {
+                return "Error: 'path' argument is required.".to_string();
+            }
+            crate::tools::archive::list_archive(workspace, &path)
+        }
+        "archive.extract" => {
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            if path.is_empty() {
+                return "Error: 'path' argument is required.".to_string();
+            }
+            let dest = parse_json_arg(args, "dest").unwrap_or_default();
+            let output = crate::tools::archive::extract_archive(workspace, &path, &dest);
+            let ok = !output.starts_with("Error") && !output.starts_with("Access denied");
+            crate::core::audit::record(workspace, tape, "tool", name, &path, ok, &output);
+            output
+        }
+        "code.outline" => {
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            if path.is_empty() {
+                return "Error: 'path' argument is required.".to_string();
+            }
+            crate::tools::code_outline::outline_file(workspace, &path)
+        }
+        "lsp.definition" | "lsp.references" => {
+            use crate::tools::lsp;
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            if path.is_empty() {
+                return "Error: 'path' argument is required.".to_string();
+            }
+            let value: serde_json::Value = serde_json::from_str(args).unwrap_or_default();
+            let Some(line) = value["line"].as_u64() else {
+                return "Error: 'line' argument is required.".to_string();
+            };
+            let position = lsp::Position {
+                line: line as usize,
+                column: value["column"].as_u64().map(|n| n as usize),
+                symbol: parse_json_arg(args, "symbol").filter(|s| !s.is_empty()),
+            };
+            if name == "lsp.definition" {
+                lsp::definition(workspace, &path, &position)
+            } else {
+                let include_declaration = value["include_declaration"].as_bool().unwrap_or(true);
+                lsp::references(workspace, &path, &position, include_declaration)
+            }
+        }
+        "lsp.diagnostics" => {
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            if path.is_empty() {
+                return "Error: 'path' argument is required.".to_string();
+            }
+            crate::tools::lsp::diagnostics(workspace, &path)
+        }
         "file.search" => {
             use crate::tools::file_ops;
             let query = parse_json_arg(args, "query").unwrap_or_default();
@@ -533,7 +1400,10 @@ pub fn execute_tool(
                 Ok(v) => v["replace_all"].as_bool().unwrap_or(false),
                 Err(_) => false,
             };
-            file_ops::edit_file(workspace, &path, &old, &new, replace_all)
+            let output = file_ops::edit_file(workspace, &path, &old, &new, replace_all);
+            let ok = !output.starts_with("Error");
+            crate::core::audit::record(workspace, tape, "tool", name, &path, ok, &output);
+            output
         }
         "web.fetch" => {
             use crate::tools::web;
@@ -541,7 +1411,16 @@ pub fn execute_tool(
             if url.is_empty() {
                 return "Error: 'url' argument is required.".to_string();
             }
-            web::fetch_url(&url)
+            let output = web::fetch_url(&url);
+            match web::normalize_url(&url) {
+                Some(source) if !output.starts_with("Error") => {
+                    let n = ctx
+                        .citations
+                        .record(&source, citations::page_title(&output));
+                    format!("[Source {n}: {source}]\n\n{output}")
+                }
+                _ => output,
+            }
         }
         "web.search" => {
             use crate::tools::web;
@@ -549,7 +1428,17 @@ pub fn execute_tool(
             if query.is_empty() {
                 return "Error: 'query' argument is required.".to_string();
             }
-            web::web_search(&query)
+            let n = ctx
+                .citations
+                .record(&web::search_url(&query), Some(format!("Search: {query}")));
+            format!("[Source {n}]\n{}", web::web_search(&query))
+        }
+        "calendar.upcoming" => {
+            let (limit, days) = match serde_json::from_str::<serde_json::Value>(args) {
+                Ok(v) => (v["limit"].as_u64(), v["days"].as_u64()),
+                Err(_) => (None, None),
+            };
+            crate::tools::calendar::upcoming_events(&ctx.calendars, limit.map(|n| n as usize), days)
         }
         "schedule.add" => {
             use crate::tools::schedule::{JobMode, global_scheduler};
@@ -574,19 +1463,63 @@ pub fn execute_tool(
             } else {
                 None
             };
-            global_scheduler().add_job(
+            let notify_via = parse_json_arg(args, "notify_via").unwrap_or_default();
+            let notifier = match crate::tools::notify::notifier_for(
+                &notify_via,
+                ctx.notifier.clone(),
+                &ctx.notify,
+            ) {
+                Ok(notifier) => notifier,
+                Err(e) => return format!("Error: {e}"),
+            };
+            let quiet_hours = match parse_json_arg(args, "quiet_hours")
+                .as_deref()
+                .map(str::trim)
+            {
+                None | Some("") => ctx.quiet_hours,
+                Some("off") => None,
+                Some(value) => match QuietHours::parse(value) {
+                    Some(window) => Some(window),
+                    None => {
+                        return format!(
+                            "Error: invalid quiet_hours '{value}': expected HH:MM-HH:MM or off"
+                        );
+                    }
+                },
+            };
+            let notifier = match quiet_hours {
+                Some(window) => notifier.map(|n| crate::tools::quiet_hours::hold_during(n, window)),
+                None => notifier,
+            };
+            let scheduled = match global_scheduler().schedule(
                 &message,
                 after_seconds,
                 interval_seconds,
                 mode,
-                ctx.notifier.clone(),
+                notifier,
                 agent_runner,
-            )
+            ) {
+                Ok(scheduled) => scheduled,
+                Err(e) => return e,
+            };
+            global_scheduler().set_job_session(&scheduled.id, &tape.info().name);
+            let mut scheduled = scheduled.to_string();
+            if !notify_via.trim().is_empty() {
+                scheduled.push_str(&format!(" notify={}", notify_via.trim()));
+            }
+            if let Some(window) = quiet_hours {
+                scheduled.push_str(&format!(" quiet={window}"));
+            }
+            scheduled
         }
         "schedule.list" => {
             use crate::tools::schedule::global_scheduler;
             global_scheduler().list_jobs()
         }
+        "schedule.history" => {
+            use crate::tools::schedule::global_scheduler;
+            schedule_history(global_scheduler(), &tape.info().name, args)
+        }
         "schedule.remove" => {
             use crate::tools::schedule::global_scheduler;
             let job_id = parse_json_arg(args, "job_id").unwrap_or_default();
@@ -595,6 +1528,39 @@ pub fn execute_tool(
             }
             global_scheduler().remove_job(&job_id)
         }
+        "watch.add" => {
+            use crate::tools::watch::{global_watcher, interval_from_secs};
+            let path = parse_json_arg(args, "path").unwrap_or_default();
+            if path.is_empty() {
+                return "Error: 'path' argument is required.".to_string();
+            }
+            let prompt = parse_json_arg(args, "prompt");
+            let interval_seconds = match serde_json::from_str::<serde_json::Value>(args) {
+                Ok(v) => v["interval_seconds"].as_u64(),
+                Err(_) => None,
+            };
+            let agent_runner = if prompt.is_some() {
+                ctx.agent_runner.clone()
+            } else {
+                None
+            };
+            global_watcher().add_watch(
+                workspace,
+                &path,
+                prompt.as_deref(),
+                interval_from_secs(interval_seconds),
+                ctx.notifier.clone(),
+                agent_runner,
+            )
+        }
+        "watch.list" => crate::tools::watch::global_watcher().list_watches(),
+        "watch.remove" => {
+            let watch_id = parse_json_arg(args, "watch_id").unwrap_or_default();
+            if watch_id.is_empty() {
+                return "Error: 'watch_id' argument is required.".to_string();
+            }
+            crate::tools::watch::global_watcher().remove_watch(&watch_id)
+        }
         _ if name.starts_with("skill.") => {
             // Skill tool: load the skill body and return as context.
             let skill_name = &name["skill.".len()..];
@@ -604,11 +1570,38 @@ pub fn execute_tool(
                 None => format!("Skill not found: {skill_name}"),
             }
         }
-        _ => format!("Unknown tool: {name}"),
+        _ => match ctx.external_tools.iter().find(|tool| tool.name == name) {
+            Some(tool) => tool.run(args, tape, workspace),
+            None => format!("Unknown tool: {name}"),
+        },
     }
 }
 
 /// Helper: parse a string value from a JSON args string.
+/// The `schedule.history` tool: the runs of the jobs `session` added to
+/// `scheduler`, or one job's detail when `job_id` is given.
+fn schedule_history(
+    scheduler: &crate::tools::schedule::SchedulerService,
+    session: &str,
+    args: &str,
+) -> String {
+    use crate::tools::schedule_store::{format_history, format_job_detail};
+    let jobs = scheduler.session_jobs(session);
+    match parse_json_arg(args, "job_id").filter(|id| !id.is_empty()) {
+        Some(id) => match jobs.iter().find(|job| job.id == id) {
+            Some(job) => format_job_detail(job),
+            None => format!("Error: job not found: {id}"),
+        },
+        None => {
+            let limit = serde_json::from_str::<serde_json::Value>(args)
+                .ok()
+                .and_then(|v| v["limit"].as_u64())
+                .unwrap_or(20);
+            format_history(&jobs, limit as usize)
+        }
+    }
+}
+
 fn parse_json_arg(args: &str, key: &str) -> Option<String> {
     serde_json::from_str::<serde_json::Value>(args)
         .ok()
@@ -698,6 +1691,54 @@ mod tests {
         assert!(desc.description.contains("shell command"));
     }
 
+    #[test]
+    fn pin_add_queues_the_note_for_the_agent_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let tape = crate::tape::store::TapeStore::open(dir.path(), "test").unwrap();
+        let ctx = ToolContext::empty();
+        let args = r#"{"note": " keep replies short "}"#;
+        let result = execute_tool("pin.add", args, &tape, dir.path(), &ctx);
+        assert_eq!(result, "Pinned: keep replies short");
+        assert_eq!(ctx.pins.take(), ["keep replies short"]);
+        let result = execute_tool("pin.add", "{}", &tape, dir.path(), &ctx);
+        assert!(result.starts_with("Error"));
+    }
+
+    #[test]
+    fn typed_outputs_keep_their_kind() {
+        let dir = tempfile::tempdir().unwrap();
+        let tape = crate::tape::store::TapeStore::open(dir.path(), "test").unwrap();
+        std::fs::write(dir.path().join("chart.png"), b"png").unwrap();
+        let ctx = ToolContext::empty();
+
+        let info = execute_tool_output("tape.info", "{}", &tape, dir.path(), &ctx);
+        let ToolOutput::Json(value) = &info else {
+            panic!("expected JSON, got {info:?}");
+        };
+        assert_eq!(value["tape"], "test");
+        assert!(
+            execute_tool("tape.info", "{}", &tape, dir.path(), &ctx).contains("\"tape\": \"test\"")
+        );
+
+        let args = r#"{"path": "chart.png", "caption": "Weekly"}"#;
+        let sent = execute_tool_output("file.send", args, &tape, dir.path(), &ctx);
+        assert_eq!(
+            sent,
+            ToolOutput::Image {
+                path: dir.path().canonicalize().unwrap().join("chart.png"),
+                caption: Some("Weekly".into())
+            }
+        );
+        let text = execute_tool_output(
+            "shell.exec",
+            r#"{"command": "echo hi"}"#,
+            &tape,
+            dir.path(),
+            &ctx,
+        );
+        assert!(matches!(text, ToolOutput::Text(_)));
+    }
+
     #[test]
     fn execute_shell_exec_tool() {
         let dir = tempfile::tempdir().unwrap();
@@ -712,6 +1753,136 @@ mod tests {
         assert!(result.contains("tool_works"));
     }
 
+    #[test]
+    fn approver_is_asked_before_side_effects() {
+        let dir = tempfile::tempdir().unwrap();
+        let tape = crate::tape::store::TapeStore::open(dir.path(), "test").unwrap();
+        let asked = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&asked);
+        let ctx = ToolContext {
+            approver: Some(Arc::new(move |name: &str, _: &str| {
+                log.lock().unwrap().push(name.to_string());
+                false
+            })),
+            ..ToolContext::empty()
+        };
+        let args = r#"{"path": "a.txt", "content": "x"}"#;
+        let result = execute_tool("file.write", args, &tape, dir.path(), &ctx);
+        assert_eq!(result, "Error: the user declined to run file.write.");
+        assert!(!dir.path().join("a.txt").exists());
+        // Read-only tools run without asking.
+        let result = execute_tool("file.list", "{}", &tape, dir.path(), &ctx);
+        assert!(!result.starts_with("Error"), "{result}");
+        // So do read-only shell commands; mutating ones are still gated.
+        let result = execute_tool(
+            "shell.exec",
+            r#"{"command": "echo hi"}"#,
+            &tape,
+            dir.path(),
+            &ctx,
+        );
+        assert!(result.contains("hi"), "{result}");
+        let result = execute_tool(
+            "shell.exec",
+            r#"{"command": "touch b.txt"}"#,
+            &tape,
+            dir.path(),
+            &ctx,
+        );
+        assert!(result.starts_with("Error: the user declined"), "{result}");
+        assert_eq!(*asked.lock().unwrap(), ["file.write", "shell.exec"]);
+        assert!(needs_approval("lsp.diagnostics") && needs_approval("lsp.definition"));
+    }
+
+    #[test]
+    fn approver_sees_the_command_a_declared_tool_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let tape = crate::tape::store::TapeStore::open(dir.path(), "test").unwrap();
+        std::fs::create_dir_all(dir.path().join(".agent")).unwrap();
+        std::fs::write(
+            dir.path().join(".agent/tools.json"),
+            r#"{"tools": [{"name": "wipe", "description": "Remove a file",
+                "parameters": {"type": "object", "properties": {"path": {"type": "string"}}},
+                "command": "rm -rf {{path}}"}]}"#,
+        )
+        .unwrap();
+        let asked = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = Arc::clone(&asked);
+        let mut reg = builtin_registry();
+        let ctx = ToolContext {
+            approver: Some(Arc::new(move |name: &str, args: &str| {
+                log.lock()
+                    .unwrap()
+                    .push((name.to_string(), args.to_string()));
+                false
+            })),
+            external_tools: register_external_tools(&mut reg, dir.path()),
+            ..ToolContext::empty()
+        };
+
+        let result = execute_tool("wipe", r#"{"path": "src"}"#, &tape, dir.path(), &ctx);
+        assert_eq!(result, "Error: the user declined to run wipe.");
+        let (name, shown) = asked.lock().unwrap()[0].clone();
+        let shown: serde_json::Value = serde_json::from_str(&shown).unwrap();
+        assert_eq!(name, "wipe");
+        assert!(
+            shown["command"].as_str().unwrap().starts_with("rm -rf "),
+            "{shown}"
+        );
+        assert_eq!(shown["arguments"]["path"], "src");
+
+        // The manifest is not read again on each call.
+        std::fs::remove_file(dir.path().join(".agent/tools.json")).unwrap();
+        let result = execute_tool("wipe", r#"{"path": "src"}"#, &tape, dir.path(), &ctx);
+        assert_eq!(result, "Error: the user declined to run wipe.");
+        assert_eq!(asked.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn large_results_are_truncated_and_paged() {
+        let dir = tempfile::tempdir().unwrap();
+        let tape = crate::tape::store::TapeStore::open(dir.path(), "test").unwrap();
+        std::fs::write(dir.path().join("big.txt"), "x".repeat(60_000) + "END").unwrap();
+        let ctx = ToolContext::empty();
+
+        let result = execute_tool(
+            "file.read",
+            r#"{"path": "big.txt"}"#,
+            &tape,
+            dir.path(),
+            &ctx,
+        );
+        assert!(result.contains("showing bytes 0-50000 of 60003"));
+
+        let page = execute_tool(
+            PAGING_TOOL,
+            r#"{"id": "out-1", "offset": 50000}"#,
+            &tape,
+            dir.path(),
+            &ctx,
+        );
+        assert!(page.contains("END"));
+        assert!(page.ends_with("end of output.]"));
+    }
+
+    #[test]
+    fn tool_arguments_are_checked_against_the_schema() {
+        assert!(check_tool_arguments("file.read", r#"{"path": "a.txt"}"#).is_none());
+        assert!(check_tool_arguments("tools", "").is_none());
+        // Skills and unknown tools have no schema to check against.
+        assert!(check_tool_arguments("skill.deploy", r#"{"x": 1}"#).is_none());
+
+        let error = check_tool_arguments("file.read", "{}").unwrap();
+        assert!(error.starts_with("Error: invalid arguments for file.read:"));
+        assert!(error.contains("- $: missing required property 'path'"));
+        assert!(error.contains("\"required\": ["));
+
+        let error = check_tool_arguments("calendar.upcoming", r#"{"limit": "ten"}"#).unwrap();
+        assert!(error.contains("$.limit: expected integer, got string"));
+        let error = check_tool_arguments("file.read", r#"{"path": "a"#).unwrap();
+        assert!(error.contains("not valid JSON"));
+    }
+
     #[test]
     fn execute_shell_exec_empty_args() {
         let dir = tempfile::tempdir().unwrap();
@@ -792,6 +1963,30 @@ mod tests {
         assert!(reg.has("skill.my-skill"));
     }
 
+    #[test]
+    fn external_tools_are_registered_with_their_schema() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join(".agent")).unwrap();
+        std::fs::write(
+            dir.path().join(".agent/tools.json"),
+            r#"{"tools": [{"name": "issue.show", "description": "Show an issue",
+                "parameters": {"type": "object", "properties": {"id": {"type": "integer"}},
+                               "required": ["id"]},
+                "command": "gh issue view {{id}}"}]}"#,
+        )
+        .unwrap();
+
+        let mut reg = builtin_registry();
+        register_external_tools(&mut reg, dir.path());
+        assert_eq!(reg.get("issue.show").unwrap().source, "workspace");
+        let defs = to_tool_definitions(&reg);
+        let def = defs
+            .iter()
+            .find(|d| d.function.name == "issue.show")
+            .unwrap();
+        assert_eq!(def.function.parameters["required"][0], "id");
+    }
+
     #[test]
     fn tool_definitions_shell_exec_has_params() {
         let reg = builtin_registry();
@@ -818,6 +2013,67 @@ mod tests {
         assert!(result.contains("hello from tool"));
     }
 
+    #[tokio::test]
+    async fn execute_schedule_history_tool() {
+        use crate::tools::schedule::{JobMode, SchedulerService};
+        use crate::tools::schedule_store::JobStatus;
+        let dir = tempfile::tempdir().unwrap();
+        let scheduler = SchedulerService::new();
+        scheduler.persist_to(&dir.path().join("schedule.json"));
+        let history = |session: &str, args: &str| schedule_history(&scheduler, session, args);
+        assert!(history("history-tool", "{}").contains("no job runs recorded"));
+
+        let scheduled = scheduler
+            .schedule(
+                "summarize the news",
+                Some(0),
+                None,
+                JobMode::Reminder,
+                None,
+                None,
+            )
+            .unwrap();
+        scheduler.set_job_session(&scheduled.id, "history-tool");
+        let id = scheduled.id;
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while scheduler.session_jobs("history-tool")[0].status != JobStatus::Completed {
+            assert!(std::time::Instant::now() < deadline, "job never ran");
+            tokio::task::yield_now().await;
+        }
+
+        let detail = history("history-tool", &format!(r#"{{"job_id": "{id}"}}"#));
+        assert!(detail.contains("status:   completed"), "{detail}");
+        assert!(detail.contains("message:  summarize the news"), "{detail}");
+        assert!(
+            history("history-tool", r#"{"job_id": "nope"}"#).starts_with("Error: job not found")
+        );
+        assert!(history("history-tool", r#"{"limit": 5}"#).starts_with(&format!("{id} ")));
+
+        // Other sessions see neither the job nor its runs.
+        let detail = history("history-other", &format!(r#"{{"job_id": "{id}"}}"#));
+        assert!(detail.starts_with("Error: job not found"), "{detail}");
+        assert!(history("history-other", "{}").contains("no job runs recorded"));
+    }
+
+    #[tokio::test]
+    async fn schedule_add_tool_records_the_session() {
+        crate::tools::schedule::persisted_global_scheduler();
+        let dir = tempfile::tempdir().unwrap();
+        let tape = crate::tape::store::TapeStore::open(dir.path(), "schedule-add-tool").unwrap();
+        let scheduled = execute_tool(
+            "schedule.add",
+            r#"{"message": "water the plants", "after_seconds": 600}"#,
+            &tape,
+            dir.path(),
+            &ToolContext::empty(),
+        );
+        assert!(scheduled.starts_with("scheduled: "), "{scheduled}");
+        let jobs = crate::tools::schedule::global_scheduler().session_jobs("schedule-add-tool");
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].message, "water the plants");
+        crate::tools::schedule::global_scheduler().remove_job(&jobs[0].id);
+    }
+
     #[test]
     fn execute_file_write_tool() {
         let dir = tempfile::tempdir().unwrap();
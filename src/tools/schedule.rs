@@ -1,11 +1,25 @@
 use std::collections::HashMap;
 use std::future::Future;
+use std::path::Path;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
+use chrono::Utc;
+use serde::Serialize;
 use tracing::{debug, error, info, warn};
 
+use crate::llm::api_types::Usage;
+use crate::tools::schedule_store::{JobRecord, JobRun, JobStatus, JobStore};
+
+/// Maximum characters of job output kept in the persisted run history.
+const OUTPUT_PREVIEW_CHARS: usize = 120;
+const OUTPUT_SUMMARY_CHARS: usize = 600;
+
+/// Jitter never delays an interval job by more than this share of its
+/// interval (1/10).
+const MAX_JITTER_FRACTION: u32 = 10;
+
 /// Whether a schedule job sends a static reminder or runs the agent.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum JobMode {
@@ -57,15 +71,129 @@ impl ScheduledJob {
     }
 }
 
+/// A job [`SchedulerService::schedule`] accepted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scheduled {
+    pub id: String,
+    /// When the job fires, e.g. `once in 60s`.
+    pub description: String,
+}
+
+impl std::fmt::Display for Scheduled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "scheduled: {} fires={}", self.id, self.description)
+    }
+}
+
 /// Notification callback type — each job captures its own notifier.
 pub type Notifier = Arc<dyn Fn(String) + Send + Sync>;
 
-/// Async agent runner callback — runs the full agent pipeline with a prompt.
+/// Structured record of one agent-mode job run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct AgentRunReport {
+    pub job_id: String,
+    pub prompt: String,
+    /// Tool names invoked while running the job.
+    pub tool_calls: Vec<String>,
+    pub tool_rounds: usize,
+    pub usage: Option<Usage>,
+    pub output: Option<String>,
+    pub error: Option<String>,
+    pub duration_ms: u64,
+    /// Workspace snapshot taken before the run (see `core::snapshot`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+impl AgentRunReport {
+    /// Whether the run finished without an error.
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+
+    /// Human-readable summary for delivery through a notifier.
+    pub fn to_notification(&self) -> String {
+        let status = if self.succeeded() { "ok" } else { "failed" };
+        let mut lines = vec![format!(
+            "\u{1f4cb} [Schedule {}] {status} in {:.1}s",
+            self.job_id,
+            self.duration_ms as f64 / 1000.0
+        )];
+        lines.push(format!("Prompt: {}", self.prompt));
+        if !self.tool_calls.is_empty() {
+            lines.push(format!(
+                "Tools: {} ({} round(s))",
+                self.tool_calls.join(", "),
+                self.tool_rounds
+            ));
+        }
+        if let Some(usage) = &self.usage {
+            lines.push(format!(
+                "Tokens: {} prompt + {} completion",
+                usage.prompt_tokens, usage.completion_tokens
+            ));
+        }
+        if let Some(error) = &self.error {
+            lines.push(format!("Error: {error}"));
+        }
+        if let Some(snapshot) = &self.snapshot {
+            lines.push(format!("Undo: ,snapshot restore {snapshot}"));
+        }
+        if let Some(output) = &self.output {
+            lines.push(String::new());
+            lines.push(output.clone());
+        }
+        lines.join("\n")
+    }
+}
+
+/// Retry policy for failed agent-mode job runs.
+///
+/// A run is retried up to `max_retries` times, waiting `base_delay`
+/// after the first failure and doubling the wait each time. Once retries
+/// are exhausted the job's notifier receives the error instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+}
+
+/// Upper bound for a single retry wait.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(600);
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_delay: Duration::from_secs(30),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: u32, base_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            base_delay,
+        }
+    }
+
+    /// Wait before retry number `retry` (0-based).
+    pub fn delay_for(&self, retry: u32) -> Duration {
+        self.base_delay
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+/// Async agent runner callback — runs the full agent pipeline for a job.
 ///
-/// Captures config, workspace, session_id, and delivery mechanism.
-/// When invoked, it calls the agent loop and sends the result to the user.
-pub type AgentRunner =
-    Arc<dyn Fn(String) -> Pin<Box<dyn Future<Output = ()> + Send>> + Send + Sync>;
+/// Called with `(job_id, prompt)`. Captures config, workspace, session_id,
+/// and delivery mechanism; it runs the agent loop, delivers the result to
+/// the user, and returns the run report.
+pub type AgentRunner = Arc<
+    dyn Fn(String, String) -> Pin<Box<dyn Future<Output = AgentRunReport> + Send>> + Send + Sync,
+>;
 
 /// In-memory scheduler that manages timed jobs.
 ///
@@ -77,6 +205,16 @@ pub struct SchedulerService {
     jobs: Arc<Mutex<HashMap<String, ScheduledJob>>>,
     /// Handles for spawned tokio tasks, keyed by job ID.
     handles: Arc<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>>,
+    /// Optional on-disk store mirroring job state and run history.
+    store: Arc<Mutex<Option<JobStore>>>,
+    /// Retry policy captured by agent-mode jobs when they are added.
+    retry: Arc<Mutex<RetryPolicy>>,
+    /// Deliver full run reports instead of just the agent's answer.
+    full_reports: Arc<Mutex<bool>>,
+    /// Slots shared by agent-mode runs (`None` = unlimited).
+    agent_slots: Arc<Mutex<Option<Arc<tokio::sync::Semaphore>>>>,
+    /// Largest random delay added to each interval firing.
+    jitter: Arc<Mutex<Duration>>,
 }
 
 impl std::fmt::Debug for SchedulerService {
@@ -92,6 +230,55 @@ impl SchedulerService {
         Self {
             jobs: Arc::new(Mutex::new(HashMap::new())),
             handles: Arc::new(Mutex::new(HashMap::new())),
+            store: Arc::new(Mutex::new(None)),
+            retry: Arc::new(Mutex::new(RetryPolicy::default())),
+            full_reports: Arc::new(Mutex::new(false)),
+            agent_slots: Arc::new(Mutex::new(None)),
+            jitter: Arc::new(Mutex::new(Duration::ZERO)),
+        }
+    }
+
+    /// Set the retry policy used by subsequently added jobs.
+    pub fn set_retry_policy(&self, policy: RetryPolicy) {
+        *self.retry.lock().unwrap() = policy;
+    }
+
+    /// Deliver full run reports (tools, usage, duration) for subsequently
+    /// added agent-mode jobs instead of just the agent's answer.
+    pub fn set_full_reports(&self, enabled: bool) {
+        *self.full_reports.lock().unwrap() = enabled;
+    }
+
+    /// Limit how many agent-mode runs execute at once (0 = unlimited).
+    ///
+    /// Jobs added afterwards share the limit; runs beyond it wait for a slot.
+    pub fn set_max_concurrent_agent_jobs(&self, max: usize) {
+        *self.agent_slots.lock().unwrap() =
+            (max > 0).then(|| Arc::new(tokio::sync::Semaphore::new(max)));
+    }
+
+    /// Delay each firing of subsequently added interval jobs by a random
+    /// amount up to `max` (capped at a tenth of the interval), so jobs with
+    /// the same interval do not all fire at once.
+    pub fn set_jitter(&self, max: Duration) {
+        *self.jitter.lock().unwrap() = max;
+    }
+
+    /// Persist job state and run history to `path`.
+    ///
+    /// Jobs left active by a previous process are marked interrupted, since
+    /// their timers did not survive the restart.
+    pub fn persist_to(&self, path: &Path) {
+        match JobStore::load(path) {
+            Ok(mut store) => {
+                if let Err(e) = store.mark_active_interrupted() {
+                    warn!(error = %e, "schedule: failed to update job store");
+                }
+                *self.store.lock().unwrap() = Some(store);
+            }
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "schedule: failed to load job store")
+            }
         }
     }
 
@@ -99,9 +286,10 @@ impl SchedulerService {
     ///
     /// - **Reminder mode**: fires `notifier(message)` — cheap, no LLM call.
     /// - **Agent mode**: fires `agent_runner(message).await` — runs full agent
-    ///   pipeline (LLM + tools) and delivers the result.
+    ///   pipeline (LLM + tools) and delivers the result through `notifier`.
     ///
-    /// Each job captures its own callbacks — context-bound closures.
+    /// Each job captures its own callbacks — context-bound closures. The
+    /// notifier may fan out to several sinks (see `tools::notify`).
     pub fn add_job(
         &self,
         message: &str,
@@ -111,12 +299,40 @@ impl SchedulerService {
         notifier: Option<Notifier>,
         agent_runner: Option<AgentRunner>,
     ) -> String {
+        match self.schedule(
+            message,
+            after_seconds,
+            interval_seconds,
+            mode,
+            notifier,
+            agent_runner,
+        ) {
+            Ok(scheduled) => scheduled.to_string(),
+            Err(e) => e,
+        }
+    }
+
+    /// [`Self::add_job`] returning the new job's id and schedule instead of
+    /// the display line, or the `Error: ...` message.
+    pub fn schedule(
+        &self,
+        message: &str,
+        after_seconds: Option<u64>,
+        interval_seconds: Option<u64>,
+        mode: JobMode,
+        notifier: Option<Notifier>,
+        agent_runner: Option<AgentRunner>,
+    ) -> Result<Scheduled, String> {
         if after_seconds.is_none() && interval_seconds.is_none() {
-            return "Error: must specify either 'after_seconds' or 'interval_seconds'".to_string();
+            return Err(
+                "Error: must specify either 'after_seconds' or 'interval_seconds'".to_string(),
+            );
         }
         if mode == JobMode::Agent && agent_runner.is_none() {
-            return "Error: agent mode requires an agent runner (not available in this channel)"
-                .to_string();
+            return Err(
+                "Error: agent mode requires an agent runner (not available in this channel)"
+                    .to_string(),
+            );
         }
 
         let id = generate_job_id();
@@ -134,6 +350,22 @@ impl SchedulerService {
         };
 
         let description = job.schedule_description();
+        let first_fire = after.or(interval).unwrap_or_default();
+        with_store(&self.store, |store| {
+            store.upsert(JobRecord {
+                id: id.clone(),
+                message: message.to_string(),
+                mode: mode.to_string(),
+                after_seconds,
+                interval_seconds,
+                created_at: Utc::now(),
+                next_fire_at: Some(Utc::now() + first_fire),
+                status: JobStatus::Active,
+                failures: 0,
+                runs: Vec::new(),
+                session: None,
+            })
+        });
 
         // Store the job
         {
@@ -144,6 +376,14 @@ impl SchedulerService {
         // Spawn the timer task
         let jobs_ref = self.jobs.clone();
         let handles_ref = self.handles.clone();
+        let store_ref = self.store.clone();
+        let retry = *self.retry.lock().unwrap();
+        let slots = self.agent_slots.lock().unwrap().clone();
+        let jitter = *self.jitter.lock().unwrap();
+        let delivery = Delivery {
+            notifier,
+            full_reports: *self.full_reports.lock().unwrap(),
+        };
         let job_id = id.clone();
         let msg = message.to_string();
 
@@ -152,7 +392,10 @@ impl SchedulerService {
             Err(_) => {
                 let mut jobs = self.jobs.lock().unwrap();
                 jobs.remove(&id);
-                return "Error: no async runtime available to schedule jobs".to_string();
+                with_store(&self.store, |store| {
+                    store.set_status(&id, JobStatus::Cancelled)
+                });
+                return Err("Error: no async runtime available to schedule jobs".to_string());
             }
         };
 
@@ -166,7 +409,8 @@ impl SchedulerService {
                 };
                 if !cancelled {
                     debug!(job_id = %job_id, "schedule: firing one-shot");
-                    fire_job(&notifier, &agent_runner, &job_id, &msg).await;
+                    let run = fire_job(&delivery, &agent_runner, retry, &slots, &job_id, &msg).await;
+                    with_store(&store_ref, |store| store.record_run(&job_id, run, None));
                     let mut jobs = jobs_ref.lock().unwrap();
                     jobs.remove(&job_id);
                 }
@@ -178,6 +422,11 @@ impl SchedulerService {
                 ticker.tick().await; // first tick fires immediately, skip it
                 loop {
                     ticker.tick().await;
+                    let delay = jitter_delay(interval_dur, jitter);
+                    if !delay.is_zero() {
+                        debug!(job_id = %job_id, delay_ms = delay.as_millis() as u64, "schedule: jitter");
+                        tokio::time::sleep(delay).await;
+                    }
                     let cancelled = {
                         let jobs = jobs_ref.lock().unwrap();
                         jobs.get(&job_id).map(|j| j.cancelled).unwrap_or(true)
@@ -186,7 +435,11 @@ impl SchedulerService {
                         break;
                     }
                     debug!(job_id = %job_id, "schedule: firing interval");
-                    fire_job(&notifier, &agent_runner, &job_id, &msg).await;
+                    let run = fire_job(&delivery, &agent_runner, retry, &slots, &job_id, &msg).await;
+                    let next = Utc::now() + interval_dur;
+                    with_store(&store_ref, |store| {
+                        store.record_run(&job_id, run, Some(next))
+                    });
                 }
                 let mut handles = handles_ref.lock().unwrap();
                 handles.remove(&job_id);
@@ -196,7 +449,7 @@ impl SchedulerService {
         let mut handles = self.handles.lock().unwrap();
         handles.insert(id.clone(), task_handle);
 
-        format!("scheduled: {id} fires={description}")
+        Ok(Scheduled { id, description })
     }
 
     /// List all active jobs.
@@ -235,6 +488,9 @@ impl SchedulerService {
                 None => return format!("Error: job not found: {job_id}"),
             }
         }
+        with_store(&self.store, |store| {
+            store.set_status(job_id, JobStatus::Cancelled)
+        });
 
         {
             let mut handles = self.handles.lock().unwrap();
@@ -246,6 +502,29 @@ impl SchedulerService {
         format!("removed: {job_id}")
     }
 
+    /// Record that the session with tape `session` added job `id`.
+    pub fn set_job_session(&self, id: &str, session: &str) {
+        with_store(&self.store, |store| store.set_session(id, session));
+    }
+
+    /// Persisted records of the jobs the session with tape `session` added,
+    /// oldest first. Empty when the scheduler does not persist its state.
+    pub fn session_jobs(&self, session: &str) -> Vec<JobRecord> {
+        self.store
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|store| {
+                store
+                    .jobs()
+                    .iter()
+                    .filter(|job| job.session.as_deref() == Some(session))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
     /// Number of active (non-cancelled) jobs.
     pub fn active_count(&self) -> usize {
         let jobs = self.jobs.lock().unwrap();
@@ -253,42 +532,206 @@ impl SchedulerService {
     }
 }
 
+/// Apply a change to the persisted job store, if one is configured.
+fn with_store(
+    store: &Mutex<Option<JobStore>>,
+    f: impl FnOnce(&mut JobStore) -> std::io::Result<()>,
+) {
+    if let Some(store) = store.lock().unwrap().as_mut()
+        && let Err(e) = f(store)
+    {
+        warn!(error = %e, "schedule: failed to persist job state");
+    }
+}
+
+/// Where and how a job delivers what it produced.
+struct Delivery {
+    notifier: Option<Notifier>,
+    full_reports: bool,
+}
+
 /// Fire a job — either runs the agent pipeline or sends a simple notification.
+///
+/// Returns the run outcome for the persisted history.
 async fn fire_job(
-    notifier: &Option<Notifier>,
+    delivery: &Delivery,
     agent_runner: &Option<AgentRunner>,
+    retry: RetryPolicy,
+    slots: &Option<Arc<tokio::sync::Semaphore>>,
     job_id: &str,
     message: &str,
-) {
+) -> JobRun {
+    let started_at = Utc::now();
+    let start = Instant::now();
+
     // Agent mode: run the full agent pipeline with the message as prompt
     if let Some(runner) = agent_runner {
-        info!(job_id = %job_id, "schedule: running agent-mode job");
-        let fut = runner(message.to_string());
-        match tokio::task::spawn(fut).await {
-            Ok(()) => {
-                info!(job_id = %job_id, "schedule: agent-mode job completed");
+        let report = run_agent_with_retry(runner, retry, slots, job_id, message).await;
+        if !report.succeeded() {
+            dead_letter(&delivery.notifier, &report, retry.max_retries + 1);
+        } else {
+            let reply = if delivery.full_reports {
+                Some(report.to_notification())
+            } else {
+                report.output.clone()
+            };
+            match (reply, &delivery.notifier) {
+                (Some(reply), Some(notify_fn)) => notify_fn(reply),
+                (Some(reply), None) => eprintln!("[schedule:{job_id}] {reply}"),
+                (None, _) => warn!(job_id = %job_id, "schedule: agent run returned empty response"),
             }
-            Err(e) => {
-                error!(job_id = %job_id, error = %e, "schedule: agent-mode job panicked");
-                // Fall back to sending the error via notifier so the user knows
-                if let Some(notify_fn) = notifier {
-                    notify_fn(format!(
-                        "\u{26a0} [Schedule {job_id}] Agent job failed: {e}"
-                    ));
-                }
+        }
+        let mut tools: Vec<String> = Vec::new();
+        for tool in report.tool_calls {
+            if !tools.contains(&tool) {
+                tools.push(tool);
             }
         }
-        return;
+        return JobRun {
+            started_at,
+            finished_at: Some(Utc::now()),
+            duration_ms: start.elapsed().as_millis() as u64,
+            ok: report.error.is_none(),
+            error: report.error,
+            output_preview: report.output.as_deref().map(preview),
+            output_summary: report.output.as_deref().map(summarize),
+            tools,
+        };
     }
 
     // Reminder mode: just send the message text
     let text = format!("\u{23f0} [Reminder: {job_id}] {message}");
-    if let Some(notify_fn) = notifier {
+    if let Some(notify_fn) = &delivery.notifier {
         notify_fn(text);
     } else {
         warn!(job_id = %job_id, "schedule: no notifier available, printing to stderr");
         eprintln!("[schedule:{job_id}] {message}");
     }
+    JobRun {
+        started_at,
+        finished_at: Some(Utc::now()),
+        duration_ms: start.elapsed().as_millis() as u64,
+        ok: true,
+        output_preview: Some(preview(message)),
+        ..Default::default()
+    }
+}
+
+/// Run an agent-mode job, retrying failed attempts with exponential backoff.
+///
+/// Each attempt holds one of `slots` while it runs; the backoff between
+/// attempts does not. Returns the report of the last attempt.
+async fn run_agent_with_retry(
+    runner: &AgentRunner,
+    retry: RetryPolicy,
+    slots: &Option<Arc<tokio::sync::Semaphore>>,
+    job_id: &str,
+    message: &str,
+) -> AgentRunReport {
+    let mut attempt = 0;
+    loop {
+        let permit = match slots {
+            Some(slots) => {
+                if slots.available_permits() == 0 {
+                    debug!(job_id = %job_id, "schedule: waiting for an agent-mode slot");
+                }
+                slots.clone().acquire_owned().await.ok()
+            }
+            None => None,
+        };
+        info!(job_id = %job_id, attempt = attempt + 1, "schedule: running agent-mode job");
+        let fut = runner(job_id.to_string(), message.to_string());
+        let report = match tokio::task::spawn(fut).await {
+            Ok(report) => {
+                info!(
+                    job_id = %job_id,
+                    ok = report.succeeded(),
+                    tool_rounds = report.tool_rounds,
+                    duration_ms = report.duration_ms,
+                    "schedule: agent-mode job completed"
+                );
+                report
+            }
+            Err(e) => {
+                error!(job_id = %job_id, error = %e, "schedule: agent-mode job panicked");
+                AgentRunReport {
+                    job_id: job_id.to_string(),
+                    prompt: message.to_string(),
+                    error: Some(format!("agent task panicked: {e}")),
+                    ..Default::default()
+                }
+            }
+        };
+        if report.succeeded() || attempt >= retry.max_retries {
+            return report;
+        }
+        drop(permit);
+        let delay = retry.delay_for(attempt);
+        warn!(
+            job_id = %job_id,
+            attempt = attempt + 1,
+            retry_in_ms = delay.as_millis() as u64,
+            error = report.error.as_deref().unwrap_or_default(),
+            "schedule: agent-mode job failed, retrying"
+        );
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Tell the originating channel that a job gave up after all retries.
+fn dead_letter(notifier: &Option<Notifier>, report: &AgentRunReport, attempts: u32) {
+    let job_id = &report.job_id;
+    let error_text = report.error.as_deref().unwrap_or("unknown error");
+    error!(job_id = %job_id, attempts, error = %error_text, "schedule: agent-mode job gave up");
+
+    let mut lines = vec![
+        format!("\u{26a0} [Schedule {job_id}] Agent job failed after {attempts} attempt(s)"),
+        format!("Prompt: {}", report.prompt),
+    ];
+    if !report.tool_calls.is_empty() {
+        lines.push(format!("Tools: {}", report.tool_calls.join(", ")));
+    }
+    lines.push(format!("Error: {error_text}"));
+    let text = lines.join("\n");
+
+    match notifier {
+        Some(notify_fn) => notify_fn(text),
+        None => eprintln!("[schedule:{job_id}] {text}"),
+    }
+}
+
+/// Random delay before an interval firing: up to `max`, and at most a tenth
+/// of `interval`.
+fn jitter_delay(interval: Duration, max: Duration) -> Duration {
+    let cap = max.min(interval / MAX_JITTER_FRACTION);
+    if cap.is_zero() {
+        return Duration::ZERO;
+    }
+    let millis = cap.as_millis() as u64;
+    Duration::from_millis(rand::random::<u64>() % (millis + 1))
+}
+
+/// Single-line, length-capped preview of job output.
+fn preview(text: &str) -> String {
+    let line = text.lines().next().unwrap_or_default();
+    if line.chars().count() > OUTPUT_PREVIEW_CHARS {
+        let cut: String = line.chars().take(OUTPUT_PREVIEW_CHARS).collect();
+        format!("{cut}…")
+    } else {
+        line.to_string()
+    }
+}
+
+/// Whitespace-collapsed, length-capped excerpt of job output.
+fn summarize(text: &str) -> String {
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.chars().count() > OUTPUT_SUMMARY_CHARS {
+        let cut: String = collapsed.chars().take(OUTPUT_SUMMARY_CHARS).collect();
+        format!("{cut}…")
+    } else {
+        collapsed
+    }
 }
 
 /// Generate a short random job ID (8 hex chars).
@@ -311,6 +754,18 @@ pub fn global_scheduler() -> &'static SchedulerService {
     GLOBAL_SCHEDULER.get_or_init(SchedulerService::new)
 }
 
+/// [`global_scheduler`] persisting to one store shared by every test, for
+/// tests of the tools and commands that read its history.
+#[cfg(test)]
+pub(crate) fn persisted_global_scheduler() -> &'static SchedulerService {
+    static PERSISTED: std::sync::Once = std::sync::Once::new();
+    PERSISTED.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("crabclaw-schedule-{}", std::process::id()));
+        global_scheduler().persist_to(&dir.join("schedule.json"));
+    });
+    global_scheduler()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -399,6 +854,310 @@ mod tests {
         assert_eq!(svc.active_count(), 2);
     }
 
+    #[test]
+    fn report_notification_lists_tools_usage_and_output() {
+        let report = AgentRunReport {
+            job_id: "abc".to_string(),
+            prompt: "check the news".to_string(),
+            tool_calls: vec!["web.fetch".to_string()],
+            tool_rounds: 1,
+            usage: Some(Usage {
+                prompt_tokens: 10,
+                completion_tokens: 5,
+                total_tokens: 15,
+            }),
+            output: Some("Nothing new.".to_string()),
+            error: None,
+            duration_ms: 1500,
+            snapshot: Some("20260101-090000".to_string()),
+        };
+        let text = report.to_notification();
+        assert!(text.contains("[Schedule abc] ok in 1.5s"), "got: {text}");
+        assert!(text.contains("Tools: web.fetch (1 round(s))"));
+        assert!(text.contains("10 prompt + 5 completion"));
+        assert!(text.contains("Undo: ,snapshot restore 20260101-090000"));
+        assert!(text.ends_with("Nothing new."));
+    }
+
+    #[tokio::test]
+    async fn agent_runner_receives_job_id_and_prompt() {
+        let svc = fresh_service();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let seen_clone = seen.clone();
+        let runner: AgentRunner = Arc::new(move |job_id, prompt| {
+            let seen = seen_clone.clone();
+            Box::pin(async move {
+                seen.lock().unwrap().push((job_id.clone(), prompt.clone()));
+                AgentRunReport {
+                    job_id,
+                    prompt,
+                    ..Default::default()
+                }
+            })
+        });
+
+        let added = svc.add_job(
+            "summarize",
+            Some(0),
+            None,
+            JobMode::Agent,
+            None,
+            Some(runner),
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1);
+        assert!(added.contains(&seen[0].0), "got: {added}");
+        assert_eq!(seen[0].1, "summarize");
+    }
+
+    #[tokio::test]
+    async fn persisted_store_records_runs_and_cancellation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schedule.json");
+        let svc = fresh_service();
+        svc.persist_to(&path);
+
+        svc.add_job("fire now", Some(0), None, JobMode::Reminder, None, None);
+        let pending = svc.add_job("later", Some(60), None, JobMode::Reminder, None, None);
+        let pending_id = pending
+            .strip_prefix("scheduled: ")
+            .unwrap()
+            .split_whitespace()
+            .next()
+            .unwrap()
+            .to_string();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        svc.remove_job(&pending_id);
+
+        let store = JobStore::load(&path).unwrap();
+        let fired = store
+            .jobs()
+            .iter()
+            .find(|j| j.message == "fire now")
+            .unwrap();
+        assert_eq!(fired.status, JobStatus::Completed);
+        assert_eq!(fired.runs.len(), 1);
+        assert!(fired.runs[0].ok);
+        assert_eq!(store.get(&pending_id).unwrap().status, JobStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn session_jobs_are_those_the_session_added() {
+        let dir = tempfile::tempdir().unwrap();
+        let svc = fresh_service();
+        assert!(svc.session_jobs("alice").is_empty());
+        svc.persist_to(&dir.path().join("schedule.json"));
+
+        let add = |message: &str| {
+            svc.schedule(message, Some(60), None, JobMode::Reminder, None, None)
+                .unwrap()
+        };
+        let mine = add("mine");
+        let theirs = add("theirs");
+        assert_eq!(
+            mine.to_string(),
+            format!("scheduled: {} fires={}", mine.id, mine.description)
+        );
+        svc.set_job_session(&mine.id, "alice");
+        svc.set_job_session(&theirs.id, "bob");
+
+        let jobs = svc.session_jobs("alice");
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, mine.id);
+        assert!(svc.session_jobs("carol").is_empty());
+    }
+
+    #[tokio::test]
+    async fn agent_runs_record_tools_and_output_summary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("schedule.json");
+        let svc = fresh_service();
+        svc.persist_to(&path);
+        let runner: AgentRunner = Arc::new(|job_id, prompt| {
+            Box::pin(async move {
+                AgentRunReport {
+                    job_id,
+                    prompt,
+                    tool_calls: vec!["web.fetch".into(), "web.fetch".into(), "file.write".into()],
+                    output: Some("Top stories:\n\n1. Rust 2027 edition".to_string()),
+                    ..Default::default()
+                }
+            })
+        });
+
+        svc.add_job("digest", Some(0), None, JobMode::Agent, None, Some(runner));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let store = JobStore::load(&path).unwrap();
+        let run = &store.jobs()[0].runs[0];
+        assert_eq!(run.tools, vec!["web.fetch", "file.write"]);
+        assert_eq!(run.output_preview.as_deref(), Some("Top stories:"));
+        assert_eq!(
+            run.output_summary.as_deref(),
+            Some("Top stories: 1. Rust 2027 edition")
+        );
+        assert!(run.finished_at.unwrap() >= run.started_at);
+    }
+
+    #[test]
+    fn jitter_is_capped_by_a_tenth_of_the_interval() {
+        let hour = Duration::from_secs(3600);
+        assert_eq!(jitter_delay(hour, Duration::ZERO), Duration::ZERO);
+        for _ in 0..50 {
+            assert!(jitter_delay(hour, Duration::from_secs(60)) <= Duration::from_secs(60));
+            assert!(
+                jitter_delay(Duration::from_secs(100), Duration::from_secs(60))
+                    <= Duration::from_secs(10)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn agent_jobs_wait_for_a_free_slot() {
+        let svc = fresh_service();
+        svc.set_max_concurrent_agent_jobs(1);
+        let running = Arc::new(Mutex::new((0usize, 0usize)));
+        let running_clone = running.clone();
+        let runner: AgentRunner = Arc::new(move |job_id, prompt| {
+            let running = running_clone.clone();
+            Box::pin(async move {
+                {
+                    let mut r = running.lock().unwrap();
+                    r.0 += 1;
+                    r.1 = r.1.max(r.0);
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                running.lock().unwrap().0 -= 1;
+                AgentRunReport {
+                    job_id,
+                    prompt,
+                    ..Default::default()
+                }
+            })
+        });
+
+        for _ in 0..3 {
+            svc.add_job(
+                "run",
+                Some(0),
+                None,
+                JobMode::Agent,
+                None,
+                Some(runner.clone()),
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let (now, peak) = *running.lock().unwrap();
+        assert_eq!((now, peak), (0, 1));
+    }
+
+    #[test]
+    fn retry_delay_doubles_and_is_capped() {
+        let policy = RetryPolicy::new(5, Duration::from_secs(30));
+        assert_eq!(policy.delay_for(0), Duration::from_secs(30));
+        assert_eq!(policy.delay_for(2), Duration::from_secs(120));
+        assert_eq!(policy.delay_for(10), MAX_RETRY_DELAY);
+    }
+
+    fn flaky_runner(failures: usize, calls: Arc<Mutex<usize>>) -> AgentRunner {
+        Arc::new(move |job_id, prompt| {
+            let calls = calls.clone();
+            Box::pin(async move {
+                let mut n = calls.lock().unwrap();
+                *n += 1;
+                AgentRunReport {
+                    job_id,
+                    prompt,
+                    error: (*n <= failures).then(|| "provider error: 503".to_string()),
+                    output: (*n > failures).then(|| "done".to_string()),
+                    ..Default::default()
+                }
+            })
+        })
+    }
+
+    #[tokio::test]
+    async fn failed_agent_job_is_retried_until_success() {
+        let svc = fresh_service();
+        svc.set_retry_policy(RetryPolicy::new(2, Duration::ZERO));
+        let calls = Arc::new(Mutex::new(0));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recv_clone = received.clone();
+        let notifier: Notifier = Arc::new(move |msg| recv_clone.lock().unwrap().push(msg));
+
+        svc.add_job(
+            "flaky",
+            Some(0),
+            None,
+            JobMode::Agent,
+            Some(notifier),
+            Some(flaky_runner(2, calls.clone())),
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(*calls.lock().unwrap(), 3);
+        // Only the successful run's answer is delivered.
+        assert_eq!(*received.lock().unwrap(), vec!["done"]);
+    }
+
+    #[tokio::test]
+    async fn full_reports_deliver_the_run_summary() {
+        let svc = fresh_service();
+        svc.set_full_reports(true);
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recv_clone = received.clone();
+        let notifier: Notifier = Arc::new(move |msg| recv_clone.lock().unwrap().push(msg));
+
+        svc.add_job(
+            "report",
+            Some(0),
+            None,
+            JobMode::Agent,
+            Some(notifier),
+            Some(flaky_runner(0, Arc::new(Mutex::new(0)))),
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let msgs = received.lock().unwrap();
+        assert_eq!(msgs.len(), 1, "got: {msgs:?}");
+        assert!(msgs[0].contains("Prompt: report"), "got: {}", msgs[0]);
+        assert!(msgs[0].ends_with("done"));
+    }
+
+    #[tokio::test]
+    async fn exhausted_retries_notify_with_error_context() {
+        let svc = fresh_service();
+        svc.set_retry_policy(RetryPolicy::new(1, Duration::ZERO));
+        let calls = Arc::new(Mutex::new(0));
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recv_clone = received.clone();
+        let notifier: Notifier = Arc::new(move |msg| recv_clone.lock().unwrap().push(msg));
+
+        svc.add_job(
+            "always fails",
+            Some(0),
+            None,
+            JobMode::Agent,
+            Some(notifier),
+            Some(flaky_runner(usize::MAX, calls.clone())),
+        );
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(*calls.lock().unwrap(), 2);
+        let msgs = received.lock().unwrap();
+        assert_eq!(msgs.len(), 1, "got: {msgs:?}");
+        assert!(
+            msgs[0].contains("failed after 2 attempt(s)"),
+            "got: {}",
+            msgs[0]
+        );
+        assert!(msgs[0].contains("Prompt: always fails"));
+        assert!(msgs[0].contains("provider error: 503"));
+    }
+
     #[tokio::test]
     async fn per_job_notifier_is_called() {
         let svc = fresh_service();
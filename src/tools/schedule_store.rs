@@ -0,0 +1,447 @@
+//! Persisted scheduler state: job records and their recent run results.
+//!
+//! The in-memory scheduler writes every change to
+//! `<workspace>/.crabclaw/schedule.json` of the workspace it started in, so
+//! that `crabclaw schedule ...` can inspect jobs from outside the serving
+//! process. `,schedule.history` and the `schedule.history` tool read the
+//! same store through the scheduler, limited to the asking session's jobs.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// File name of the persisted scheduler state inside `.crabclaw/`.
+pub const SCHEDULE_STATE_FILE: &str = "schedule.json";
+
+/// Number of run results kept per job.
+const MAX_RUNS_PER_JOB: usize = 20;
+
+/// Lifecycle state of a persisted job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Active,
+    Completed,
+    Cancelled,
+    /// The process that owned the job exited before it finished.
+    Interrupted,
+}
+
+impl std::fmt::Display for JobStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Active => write!(f, "active"),
+            JobStatus::Completed => write!(f, "completed"),
+            JobStatus::Cancelled => write!(f, "cancelled"),
+            JobStatus::Interrupted => write!(f, "interrupted"),
+        }
+    }
+}
+
+/// Result of one job firing.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct JobRun {
+    pub started_at: DateTime<Utc>,
+    /// When delivery finished (absent in files written by older versions).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    pub duration_ms: u64,
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// First line of the output, for one-line listings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_preview: Option<String>,
+    /// Longer excerpt of the output, whitespace collapsed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_summary: Option<String>,
+    /// Tools an agent-mode run called, in first-use order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tools: Vec<String>,
+}
+
+/// Persisted description of a scheduled job.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobRecord {
+    pub id: String,
+    pub message: String,
+    pub mode: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub after_seconds: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_seconds: Option<u64>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_fire_at: Option<DateTime<Utc>>,
+    pub status: JobStatus,
+    #[serde(default)]
+    pub failures: u32,
+    /// Most recent runs, oldest first.
+    #[serde(default)]
+    pub runs: Vec<JobRun>,
+    /// Tape of the session that added the job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreFile {
+    jobs: Vec<JobRecord>,
+}
+
+/// JSON-file backed job store.
+#[derive(Debug)]
+pub struct JobStore {
+    path: PathBuf,
+    jobs: Vec<JobRecord>,
+}
+
+impl JobStore {
+    /// Load the store at `path` (an empty store if the file does not exist).
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let jobs = if path.exists() {
+            let content = fs::read_to_string(path)?;
+            serde_json::from_str::<StoreFile>(&content)
+                .map(|f| f.jobs)
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            path: path.to_path_buf(),
+            jobs,
+        })
+    }
+
+    /// Store location for a workspace.
+    pub fn workspace_path(workspace: &Path) -> PathBuf {
+        workspace.join(".crabclaw").join(SCHEDULE_STATE_FILE)
+    }
+
+    /// All job records, in insertion order.
+    pub fn jobs(&self) -> &[JobRecord] {
+        &self.jobs
+    }
+
+    /// Find a job by ID.
+    pub fn get(&self, id: &str) -> Option<&JobRecord> {
+        self.jobs.iter().find(|j| j.id == id)
+    }
+
+    /// Mark jobs left active by a previous process as interrupted.
+    pub fn mark_active_interrupted(&mut self) -> std::io::Result<()> {
+        let mut changed = false;
+        for job in &mut self.jobs {
+            if job.status == JobStatus::Active {
+                job.status = JobStatus::Interrupted;
+                job.next_fire_at = None;
+                changed = true;
+            }
+        }
+        if changed { self.save() } else { Ok(()) }
+    }
+
+    /// Insert or replace a job record.
+    pub fn upsert(&mut self, record: JobRecord) -> std::io::Result<()> {
+        match self.jobs.iter_mut().find(|j| j.id == record.id) {
+            Some(existing) => *existing = record,
+            None => self.jobs.push(record),
+        }
+        self.save()
+    }
+
+    /// Record the session that added a job.
+    pub fn set_session(&mut self, id: &str, session: &str) -> std::io::Result<()> {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            job.session = Some(session.to_string());
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Update a job's status.
+    pub fn set_status(&mut self, id: &str, status: JobStatus) -> std::io::Result<()> {
+        if let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) {
+            job.status = status;
+            if status != JobStatus::Active {
+                job.next_fire_at = None;
+            }
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Record a run and the next fire time (`None` once the job is done).
+    pub fn record_run(
+        &mut self,
+        id: &str,
+        run: JobRun,
+        next_fire_at: Option<DateTime<Utc>>,
+    ) -> std::io::Result<()> {
+        let Some(job) = self.jobs.iter_mut().find(|j| j.id == id) else {
+            return Ok(());
+        };
+        if !run.ok {
+            job.failures += 1;
+        }
+        job.runs.push(run);
+        if job.runs.len() > MAX_RUNS_PER_JOB {
+            let excess = job.runs.len() - MAX_RUNS_PER_JOB;
+            job.runs.drain(..excess);
+        }
+        job.next_fire_at = next_fire_at;
+        if next_fire_at.is_none() && job.status == JobStatus::Active {
+            job.status = JobStatus::Completed;
+        }
+        self.save()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let body = serde_json::to_string_pretty(&StoreFile {
+            jobs: self.jobs.clone(),
+        })
+        .map_err(std::io::Error::other)?;
+        fs::write(&self.path, body)
+    }
+}
+
+fn schedule_label(job: &JobRecord) -> String {
+    match (job.after_seconds, job.interval_seconds) {
+        (_, Some(interval)) => format!("every {interval}s"),
+        (Some(after), None) => format!("once after {after}s"),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+fn format_time(time: Option<DateTime<Utc>>) -> String {
+    time.map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn format_run(run: &JobRun) -> String {
+    let status = if run.ok { "ok" } else { "failed" };
+    let mut line = format!(
+        "{} {status} ({}ms)",
+        run.started_at.format("%Y-%m-%d %H:%M:%S UTC"),
+        run.duration_ms
+    );
+    if let Some(error) = &run.error {
+        line.push_str(&format!(" error: {error}"));
+    } else if let Some(preview) = &run.output_preview {
+        line.push_str(&format!(" → {preview}"));
+    }
+    line
+}
+
+/// One line per job: id, status, mode, schedule, next fire, runs/failures.
+pub fn format_job_list(jobs: &[JobRecord]) -> String {
+    if jobs.is_empty() {
+        return "(no scheduled jobs)".to_string();
+    }
+    jobs.iter()
+        .map(|j| {
+            format!(
+                "{} [{}] mode={} schedule={} next={} runs={} failures={} msg={}",
+                j.id,
+                j.status,
+                j.mode,
+                schedule_label(j),
+                format_time(j.next_fire_at),
+                j.runs.len(),
+                j.failures,
+                j.message
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// A run with its end time, tools and output excerpt on indented lines.
+fn format_run_detail(run: &JobRun) -> Vec<String> {
+    let mut lines = vec![format_run(run)];
+    if let Some(finished) = run.finished_at {
+        lines.push(format!("  ended: {}", format_time(Some(finished))));
+    }
+    if !run.tools.is_empty() {
+        lines.push(format!("  tools: {}", run.tools.join(", ")));
+    }
+    match &run.output_summary {
+        Some(summary) => lines.push(format!("  output: {summary}")),
+        None if run.ok && run.output_preview.is_none() => {
+            lines.push("  output: (none)".to_string())
+        }
+        None => {}
+    }
+    lines
+}
+
+/// Full detail of one job including its recent runs.
+pub fn format_job_detail(job: &JobRecord) -> String {
+    let mut lines = vec![
+        format!("Job {}", job.id),
+        format!("  status:   {}", job.status),
+        format!("  mode:     {}", job.mode),
+        format!("  schedule: {}", schedule_label(job)),
+        format!("  created:  {}", format_time(Some(job.created_at))),
+        format!("  next:     {}", format_time(job.next_fire_at)),
+        format!("  failures: {}", job.failures),
+        format!("  message:  {}", job.message),
+    ];
+    if job.runs.is_empty() {
+        lines.push("  runs:     (none yet)".to_string());
+    } else {
+        lines.push(format!("  runs ({}, newest first):", job.runs.len()));
+        for run in job.runs.iter().rev() {
+            lines.extend(format_run_detail(run).iter().map(|l| format!("    {l}")));
+        }
+    }
+    lines.join("\n")
+}
+
+/// The `limit` most recent runs across all jobs, newest first.
+pub fn format_history(jobs: &[JobRecord], limit: usize) -> String {
+    let mut runs: Vec<(&JobRecord, &JobRun)> = jobs
+        .iter()
+        .flat_map(|j| j.runs.iter().map(move |r| (j, r)))
+        .collect();
+    if runs.is_empty() {
+        return "(no job runs recorded)".to_string();
+    }
+    runs.sort_by_key(|(_, run)| std::cmp::Reverse(run.started_at));
+    runs.into_iter()
+        .take(limit)
+        .map(|(job, run)| format!("{} {}", job.id, format_run(run)))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn record(id: &str) -> JobRecord {
+        JobRecord {
+            id: id.to_string(),
+            message: "ping".to_string(),
+            mode: "reminder".to_string(),
+            after_seconds: None,
+            interval_seconds: Some(60),
+            created_at: Utc::now(),
+            next_fire_at: Some(Utc::now()),
+            status: JobStatus::Active,
+            failures: 0,
+            runs: Vec::new(),
+            session: None,
+        }
+    }
+
+    fn run(ok: bool) -> JobRun {
+        JobRun {
+            started_at: Utc::now(),
+            duration_ms: 5,
+            ok,
+            error: (!ok).then(|| "boom".to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn store_round_trips_and_counts_failures() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SCHEDULE_STATE_FILE);
+        let mut store = JobStore::load(&path).unwrap();
+        store.upsert(record("a1")).unwrap();
+        store.record_run("a1", run(true), Some(Utc::now())).unwrap();
+        store
+            .record_run("a1", run(false), Some(Utc::now()))
+            .unwrap();
+
+        let reloaded = JobStore::load(&path).unwrap();
+        let job = reloaded.get("a1").unwrap();
+        assert_eq!(job.runs.len(), 2);
+        assert_eq!(job.failures, 1);
+        assert_eq!(job.status, JobStatus::Active);
+    }
+
+    #[test]
+    fn final_run_completes_job_and_runs_are_capped() {
+        let dir = tempdir().unwrap();
+        let mut store = JobStore::load(&dir.path().join(SCHEDULE_STATE_FILE)).unwrap();
+        store.upsert(record("b2")).unwrap();
+        for _ in 0..MAX_RUNS_PER_JOB + 5 {
+            store.record_run("b2", run(true), Some(Utc::now())).unwrap();
+        }
+        store.record_run("b2", run(true), None).unwrap();
+
+        let job = store.get("b2").unwrap();
+        assert_eq!(job.runs.len(), MAX_RUNS_PER_JOB);
+        assert_eq!(job.status, JobStatus::Completed);
+        assert!(job.next_fire_at.is_none());
+    }
+
+    #[test]
+    fn runs_from_older_files_still_load() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join(SCHEDULE_STATE_FILE);
+        let mut job = serde_json::to_value(record("e5")).unwrap();
+        job["runs"] = serde_json::json!([{
+            "started_at": "2026-01-01T00:00:00Z",
+            "duration_ms": 7,
+            "ok": true,
+            "output_preview": "hello"
+        }]);
+        fs::write(&path, serde_json::json!({ "jobs": [job] }).to_string()).unwrap();
+
+        let store = JobStore::load(&path).unwrap();
+        let run = &store.get("e5").unwrap().runs[0];
+        assert_eq!(run.output_preview.as_deref(), Some("hello"));
+        assert!(run.finished_at.is_none() && run.tools.is_empty());
+    }
+
+    #[test]
+    fn active_jobs_marked_interrupted() {
+        let dir = tempdir().unwrap();
+        let mut store = JobStore::load(&dir.path().join(SCHEDULE_STATE_FILE)).unwrap();
+        store.upsert(record("c3")).unwrap();
+        store.mark_active_interrupted().unwrap();
+        assert_eq!(store.get("c3").unwrap().status, JobStatus::Interrupted);
+    }
+
+    #[test]
+    fn formatting_shows_status_and_history() {
+        let mut job = record("d4");
+        job.runs.push(run(false));
+        job.failures = 1;
+
+        let list = format_job_list(std::slice::from_ref(&job));
+        assert!(list.contains("d4 [active]"), "got: {list}");
+        assert!(list.contains("failures=1"));
+
+        let detail = format_job_detail(&job);
+        assert!(detail.contains("error: boom"), "got: {detail}");
+
+        job.runs.push(JobRun {
+            finished_at: Some(Utc::now()),
+            output_summary: Some("No new posts since yesterday.".to_string()),
+            tools: vec!["web.fetch".to_string()],
+            ..run(true)
+        });
+        let detail = format_job_detail(&job);
+        assert!(detail.contains("      tools: web.fetch"), "got: {detail}");
+        assert!(
+            detail.contains("      output: No new posts"),
+            "got: {detail}"
+        );
+        assert!(detail.contains("      ended: "), "got: {detail}");
+
+        let history = format_history(&[job], 10);
+        assert!(history.starts_with("d4 "), "got: {history}");
+        assert_eq!(format_history(&[], 10), "(no job runs recorded)");
+    }
+}
@@ -0,0 +1,79 @@
+//! `scratch.*`: a per-session notepad for the model.
+//!
+//! Plans and partial results that should survive between tool rounds go to
+//! a Markdown file next to the session's tape (`.crabclaw/<tape>.scratch.md`)
+//! instead of the workspace, so they never show up in `file.search` or in
+//! the user's project. `,reset` clears it with the tape.
+
+use crate::tape::store::TapeStore;
+
+/// Largest scratchpad kept; writes beyond it are refused.
+const MAX_SCRATCH_BYTES: usize = 256 * 1024;
+
+/// Replace the scratchpad with `content`.
+pub fn write(tape: &TapeStore, content: &str) -> String {
+    save(tape, content.to_string())
+}
+
+/// Add `content` to the end of the scratchpad on a new line.
+pub fn append(tape: &TapeStore, content: &str) -> String {
+    let mut text = read_text(tape);
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+    text.push_str(content);
+    save(tape, text)
+}
+
+/// The scratchpad's content.
+pub fn read(tape: &TapeStore) -> String {
+    let text = read_text(tape);
+    if text.trim().is_empty() {
+        "(scratchpad is empty)".to_string()
+    } else {
+        text
+    }
+}
+
+fn read_text(tape: &TapeStore) -> String {
+    std::fs::read_to_string(tape.scratch_path()).unwrap_or_default()
+}
+
+fn save(tape: &TapeStore, text: String) -> String {
+    if text.len() > MAX_SCRATCH_BYTES {
+        return format!(
+            "Error: scratchpad would be {} bytes (limit {MAX_SCRATCH_BYTES}); rewrite it shorter with scratch.write.",
+            text.len()
+        );
+    }
+    match std::fs::write(tape.scratch_path(), &text) {
+        Ok(()) => format!("Scratchpad saved ({} lines).", text.lines().count()),
+        Err(e) => format!("Error writing scratchpad: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scratchpad_is_per_tape_and_cleared_on_reset() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut tape = TapeStore::open(dir.path(), "cli_main").unwrap();
+        let other = TapeStore::open(dir.path(), "telegram_42").unwrap();
+
+        assert_eq!(read(&tape), "(scratchpad is empty)");
+        assert_eq!(
+            write(&tape, "# Plan\n1. read"),
+            "Scratchpad saved (2 lines)."
+        );
+        append(&tape, "2. fix");
+        assert_eq!(read(&tape), "# Plan\n1. read\n2. fix");
+        assert_eq!(read(&other), "(scratchpad is empty)");
+        assert!(dir.path().join("cli_main.scratch.md").is_file());
+
+        assert!(append(&tape, &"x".repeat(MAX_SCRATCH_BYTES)).starts_with("Error"));
+        tape.reset(false).unwrap();
+        assert_eq!(read(&tape), "(scratchpad is empty)");
+    }
+}
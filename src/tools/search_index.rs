@@ -0,0 +1,239 @@
+//! Persistent index behind `file.search`, kept in `.crabclaw/index`.
+//!
+//! For every file it has seen, the index records the size, the modification
+//! time and a trigram filter: a bitset of the (lowercased) three-byte
+//! sequences in the file. A search still walks the tree, but only with
+//! `stat`; it reads just the files whose filter holds every trigram of the
+//! query, which in a large repository is a small fraction. Files whose size
+//! or mtime changed are re-read and re-filtered, new files are added and
+//! vanished ones dropped, so the index follows the workspace incrementally.
+//!
+//! The filter has false positives but no false negatives, so results are the
+//! same as without the index. Queries shorter than three bytes read every
+//! file.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+/// Where the index lives, relative to the workspace.
+pub const INDEX_DIR: &str = ".crabclaw/index";
+const INDEX_FILE: &str = "search.json";
+/// Bumped whenever the filter layout changes; older indexes are rebuilt.
+const VERSION: u32 = 1;
+
+/// Filter bits per distinct trigram; about 12% of a file's bits end up set.
+const BITS_PER_TRIGRAM: usize = 8;
+const MIN_FILTER_BITS: usize = 64;
+const MAX_FILTER_BITS: usize = 1 << 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct FileEntry {
+    size: u64,
+    mtime_ns: u64,
+    /// Base64 trigram bitset; `None` for files that cannot be searched
+    /// (not UTF-8), which are skipped without being read.
+    filter: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct IndexFile {
+    version: u32,
+    files: BTreeMap<String, FileEntry>,
+}
+
+/// The index of one workspace, loaded for a single search.
+pub struct SearchIndex {
+    path: PathBuf,
+    files: BTreeMap<String, FileEntry>,
+    /// Files seen during this search, for pruning.
+    seen: HashSet<String>,
+    dirty: bool,
+}
+
+impl SearchIndex {
+    /// Load the index of `workspace`; a missing or outdated index starts
+    /// empty.
+    pub fn load(workspace: &Path) -> Self {
+        let path = workspace.join(INDEX_DIR).join(INDEX_FILE);
+        let files = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<IndexFile>(&text).ok())
+            .filter(|index| index.version == VERSION)
+            .map(|index| index.files)
+            .unwrap_or_default();
+        Self {
+            path,
+            files,
+            seen: HashSet::new(),
+            dirty: false,
+        }
+    }
+
+    /// Whether `file` (at `rel` in the workspace) may contain `query`, which
+    /// must already be lowercased. Re-indexes the file when it changed.
+    pub fn may_contain(&mut self, rel: &str, file: &Path, query: &str) -> bool {
+        self.seen.insert(rel.to_string());
+        let Ok(meta) = std::fs::metadata(file) else {
+            return false;
+        };
+        let size = meta.len();
+        let mtime_ns = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_nanos() as u64);
+
+        let current = self
+            .files
+            .get(rel)
+            .is_some_and(|e| e.size == size && e.mtime_ns == mtime_ns);
+        if !current {
+            let filter = std::fs::read_to_string(file)
+                .ok()
+                .map(|content| BASE64.encode(build_filter(&content.to_lowercase())));
+            let entry = FileEntry {
+                size,
+                mtime_ns,
+                filter,
+            };
+            self.files.insert(rel.to_string(), entry);
+            self.dirty = true;
+        }
+        match &self.files[rel].filter {
+            None => false,
+            Some(filter) => BASE64
+                .decode(filter)
+                .map_or(true, |bits| filter_may_contain(&bits, query)),
+        }
+    }
+
+    /// Drop files under `root` (a workspace-relative prefix, empty for the
+    /// whole workspace) that this search did not come across.
+    pub fn prune(&mut self, root: &str) {
+        let before = self.files.len();
+        let seen = &self.seen;
+        self.files.retain(|rel, _| {
+            let inside = root.is_empty()
+                || rel == root
+                || rel
+                    .strip_prefix(root)
+                    .is_some_and(|rest| rest.starts_with('/'));
+            !inside || seen.contains(rel)
+        });
+        self.dirty |= self.files.len() != before;
+    }
+
+    /// Write the index back if anything changed. Failures (e.g. a read-only
+    /// workspace) only cost the next search its speed-up.
+    pub fn save(self) {
+        if !self.dirty {
+            return;
+        }
+        let index = IndexFile {
+            version: VERSION,
+            files: self.files,
+        };
+        let result = (|| {
+            let dir = self.path.parent().unwrap_or(Path::new("."));
+            std::fs::create_dir_all(dir)?;
+            let tmp = self
+                .path
+                .with_extension(format!("tmp{}", std::process::id()));
+            std::fs::write(&tmp, serde_json::to_vec(&index)?)?;
+            std::fs::rename(&tmp, &self.path)
+        })();
+        if let Err(e) = result {
+            debug!(path = %self.path.display(), "search_index.save_failed: {e}");
+        }
+    }
+}
+
+fn trigrams(text: &str) -> impl Iterator<Item = u32> + '_ {
+    text.as_bytes()
+        .windows(3)
+        .map(|w| u32::from(w[0]) << 16 | u32::from(w[1]) << 8 | u32::from(w[2]))
+}
+
+/// Bit of `trigram` in a filter of `bits` bits (a power of two).
+fn bit(trigram: u32, bits: usize) -> usize {
+    (trigram.wrapping_mul(0x9E37_79B1) >> 8) as usize & (bits - 1)
+}
+
+fn build_filter(content: &str) -> Vec<u8> {
+    let distinct: HashSet<u32> = trigrams(content).collect();
+    let bits = (distinct.len() * BITS_PER_TRIGRAM)
+        .next_power_of_two()
+        .clamp(MIN_FILTER_BITS, MAX_FILTER_BITS);
+    let mut filter = vec![0u8; bits / 8];
+    for trigram in distinct {
+        let b = bit(trigram, bits);
+        filter[b / 8] |= 1 << (b % 8);
+    }
+    filter
+}
+
+fn filter_may_contain(filter: &[u8], query: &str) -> bool {
+    let bits = filter.len() * 8;
+    if !bits.is_power_of_two() {
+        return true;
+    }
+    trigrams(query).all(|trigram| {
+        let b = bit(trigram, bits);
+        filter[b / 8] & (1 << (b % 8)) != 0
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn filters_have_no_false_negatives() {
+        let content = "fn parse_config(path: &Path) -> Result<Config> {}\n".repeat(3);
+        let filter = build_filter(&content.to_lowercase());
+        for query in ["parse_config", "result<config>", "fn ", "path"] {
+            assert!(filter_may_contain(&filter, query), "{query}");
+        }
+        assert!(!filter_may_contain(&filter, "tokio::spawn_blocking"));
+        // Too short to filter.
+        assert!(filter_may_contain(&filter, "zq"));
+    }
+
+    #[test]
+    fn changed_files_are_reindexed_and_missing_ones_pruned() {
+        let dir = tempdir().unwrap();
+        let ws = dir.path();
+        std::fs::create_dir(ws.join("src")).unwrap();
+        let (a, b) = (ws.join("src/a.rs"), ws.join("src/b.rs"));
+        std::fs::write(&a, "fn alpha() {}").unwrap();
+        std::fs::write(&b, "fn beta() {}").unwrap();
+
+        let mut index = SearchIndex::load(ws);
+        assert!(index.may_contain("src/a.rs", &a, "alpha"));
+        assert!(!index.may_contain("src/b.rs", &b, "alpha"));
+        index.prune("");
+        index.save();
+        assert!(ws.join(INDEX_DIR).join(INDEX_FILE).exists());
+
+        std::fs::write(&b, "fn beta() { alpha() }").unwrap();
+        std::fs::remove_file(&a).unwrap();
+        let mut index = SearchIndex::load(ws);
+        assert_eq!(index.files.len(), 2);
+        assert!(index.may_contain("src/b.rs", &b, "alpha"));
+        index.prune("src");
+        assert_eq!(index.files.keys().collect::<Vec<_>>(), vec!["src/b.rs"]);
+        index.save();
+
+        // Unchanged files come from the index without being re-read.
+        let mut index = SearchIndex::load(ws);
+        assert!(!index.may_contain("src/b.rs", &b, "gamma"));
+        assert!(!index.dirty);
+    }
+}
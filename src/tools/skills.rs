@@ -4,7 +4,10 @@ use std::path::{Path, PathBuf};
 use serde::Serialize;
 
 const PROJECT_SKILLS_DIR: &str = ".agent/skills";
+const USER_SKILLS_DIR: &str = ".config/crabclaw/skills";
 const SKILL_FILE_NAME: &str = "SKILL.md";
+/// SKILL.md files larger than this are flagged by `lint_skills`.
+const MAX_SKILL_BODY_BYTES: usize = 32 * 1024;
 
 /// Metadata for a discovered skill.
 #[derive(Debug, Clone, Serialize)]
@@ -15,51 +18,192 @@ pub struct SkillMetadata {
     pub source: String,
 }
 
-/// Discover skills from project and global directories.
+/// Discover skills from project, user and global directories.
 ///
 /// Aligned with bub's `discover_skills`:
-/// - Scans `.agent/skills/*/SKILL.md`
-/// - Priority: project (workspace) → global (~/.agent/skills)
-/// - First occurrence wins (by case-insensitive name)
+/// - Scans `<root>/*/SKILL.md`
+/// - Priority: project (`.agent/skills`) → user (`~/.config/crabclaw/skills`)
+///   → global (`~/.agent/skills`)
+/// - First occurrence wins (by case-insensitive name), so workspace skills
+///   override same-named user-level ones
 pub fn discover_skills(workspace: &Path) -> Vec<SkillMetadata> {
-    let roots = [
+    discover_skills_in_roots(&skill_roots(workspace))
+}
+
+/// Every `SKILL.md` under the skill roots, without parsing them.
+pub fn skill_files(workspace: &Path) -> Vec<PathBuf> {
+    skill_roots(workspace)
+        .iter()
+        .flat_map(|(root, _)| skill_dirs(root))
+        .map(|dir| dir.join(SKILL_FILE_NAME))
+        .collect()
+}
+
+/// Skill roots in priority order, paired with their source label.
+fn skill_roots(workspace: &Path) -> Vec<(PathBuf, &'static str)> {
+    let home = dirs::home_dir().unwrap_or_default();
+    vec![
         (workspace.join(PROJECT_SKILLS_DIR), "project"),
-        (
-            dirs::home_dir()
-                .unwrap_or_default()
-                .join(PROJECT_SKILLS_DIR),
-            "global",
-        ),
-    ];
+        (home.join(USER_SKILLS_DIR), "user"),
+        (home.join(PROJECT_SKILLS_DIR), "global"),
+    ]
+}
 
+/// Skill directories directly under `root`, sorted by directory name.
+fn skill_dirs(root: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return Vec::new();
+    };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Discover skills from an ordered list of `(root, source)` pairs.
+fn discover_skills_in_roots(roots: &[(PathBuf, &str)]) -> Vec<SkillMetadata> {
     let mut by_name: std::collections::BTreeMap<String, SkillMetadata> =
         std::collections::BTreeMap::new();
 
-    for (root, source) in &roots {
-        if !root.is_dir() {
-            continue;
+    for (root, source) in roots {
+        for dir in skill_dirs(root) {
+            if let Some(meta) = read_skill(&dir, source) {
+                let key = meta.name.to_lowercase();
+                by_name.entry(key).or_insert(meta);
+            }
         }
+    }
 
-        let Ok(entries) = fs::read_dir(root) else {
-            continue;
-        };
+    by_name.into_values().collect()
+}
+
+/// Severity of a skill lint finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LintSeverity {
+    Error,
+    Warning,
+}
+
+/// One problem found by `lint_skills`.
+#[derive(Debug, Clone, Serialize)]
+pub struct SkillLintIssue {
+    pub severity: LintSeverity,
+    pub location: PathBuf,
+    pub message: String,
+}
+
+/// Check every SKILL.md under the skill roots for common mistakes.
+///
+/// Errors: missing frontmatter or description, empty name, duplicate names
+/// within one root, `parameters` that are not a JSON object, oversized files.
+/// Warnings: missing `name` (directory name is used) and skills shadowed by a
+/// higher-priority root.
+pub fn lint_skills(workspace: &Path) -> Vec<SkillLintIssue> {
+    lint_skills_in_roots(&skill_roots(workspace))
+}
 
-        let mut dirs: Vec<_> = entries.filter_map(|e| e.ok()).collect();
-        dirs.sort_by_key(|e| e.file_name());
+fn lint_skills_in_roots(roots: &[(PathBuf, &str)]) -> Vec<SkillLintIssue> {
+    let mut issues = Vec::new();
+    // lowercase name -> (source, location) of the first definition seen
+    let mut seen: std::collections::HashMap<String, (String, PathBuf)> =
+        std::collections::HashMap::new();
 
-        for entry in dirs {
-            if !entry.path().is_dir() {
+    for (root, source) in roots {
+        for dir in skill_dirs(root) {
+            let skill_file = dir.join(SKILL_FILE_NAME);
+            let Ok(content) = fs::read_to_string(&skill_file) else {
                 continue;
+            };
+            let mut issue = |severity, message: String| {
+                issues.push(SkillLintIssue {
+                    severity,
+                    location: skill_file.clone(),
+                    message,
+                })
+            };
+
+            if content.len() > MAX_SKILL_BODY_BYTES {
+                issue(
+                    LintSeverity::Error,
+                    format!(
+                        "file is {} bytes (limit {MAX_SKILL_BODY_BYTES})",
+                        content.len()
+                    ),
+                );
             }
 
-            if let Some(meta) = read_skill(&entry.path(), source) {
-                let key = meta.name.to_lowercase();
-                by_name.entry(key).or_insert(meta);
+            if content.lines().next().map(str::trim) != Some("---") {
+                issue(LintSeverity::Error, "missing frontmatter".to_string());
+                continue;
+            }
+
+            let frontmatter = parse_frontmatter(&content);
+            let name = match frontmatter.get("name") {
+                Some(name) if name.trim().is_empty() => {
+                    issue(LintSeverity::Error, "name is empty".to_string());
+                    continue;
+                }
+                Some(name) => name.clone(),
+                None => {
+                    issue(
+                        LintSeverity::Warning,
+                        "missing name; directory name is used".to_string(),
+                    );
+                    dir.file_name()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or("unknown")
+                        .to_string()
+                }
+            };
+
+            if frontmatter
+                .get("description")
+                .is_none_or(|d| d.trim().is_empty())
+            {
+                issue(LintSeverity::Error, "missing description".to_string());
+            }
+
+            if let Some(params) = frontmatter.get("parameters") {
+                match serde_json::from_str::<serde_json::Value>(params) {
+                    Ok(value) if value.is_object() => {}
+                    Ok(_) => issue(
+                        LintSeverity::Error,
+                        "parameters must be a JSON object".to_string(),
+                    ),
+                    Err(e) => issue(
+                        LintSeverity::Error,
+                        format!("parameters is not valid JSON: {e}"),
+                    ),
+                }
+            }
+
+            match seen.get(&name.to_lowercase()) {
+                Some((first_source, first)) if first_source == source => issue(
+                    LintSeverity::Error,
+                    format!("duplicate skill name '{name}' (also {})", first.display()),
+                ),
+                Some((first_source, first)) => issue(
+                    LintSeverity::Warning,
+                    format!(
+                        "shadowed by {first_source} skill '{name}' at {}",
+                        first.display()
+                    ),
+                ),
+                None => {
+                    seen.insert(
+                        name.to_lowercase(),
+                        (source.to_string(), skill_file.clone()),
+                    );
+                }
             }
         }
     }
 
-    by_name.into_values().collect()
+    issues
 }
 
 /// Load the full SKILL.md body for a skill by name.
@@ -73,6 +217,80 @@ pub fn load_skill_body(name: &str, workspace: &Path) -> Option<String> {
     None
 }
 
+/// Select the `top_k` skills most relevant to `prompt` by keyword overlap.
+///
+/// Name matches weigh more than description matches. Skills with no
+/// overlapping keywords are never selected; ties are broken by name.
+pub fn select_relevant_skills(
+    skills: &[SkillMetadata],
+    prompt: &str,
+    top_k: usize,
+) -> Vec<SkillMetadata> {
+    let prompt_words = keywords(prompt);
+    if prompt_words.is_empty() || top_k == 0 {
+        return Vec::new();
+    }
+
+    let mut scored: Vec<(usize, &SkillMetadata)> = skills
+        .iter()
+        .filter_map(|skill| {
+            let name_hits = keywords(&skill.name).intersection(&prompt_words).count();
+            let desc_hits = keywords(&skill.description)
+                .intersection(&prompt_words)
+                .count();
+            let score = name_hits * 2 + desc_hits;
+            (score > 0).then_some((score, skill))
+        })
+        .collect();
+
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.name.cmp(&b.1.name)));
+    scored
+        .into_iter()
+        .take(top_k)
+        .map(|(_, skill)| skill.clone())
+        .collect()
+}
+
+/// Lowercased alphanumeric words of three or more characters, plus the
+/// character bigrams of CJK runs, which are written without spaces.
+fn keywords(text: &str) -> std::collections::HashSet<String> {
+    const STOPWORDS: &[&str] = &[
+        "the", "and", "for", "with", "this", "that", "from", "into", "you", "your", "are", "can",
+        "use", "how", "what", "when", "please",
+    ];
+    let mut words = std::collections::HashSet::new();
+    for word in text.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = word.chars().collect();
+        for run in chars.chunk_by(|a, b| is_cjk(*a) == is_cjk(*b)) {
+            if is_cjk(run[0]) {
+                if run.len() == 1 {
+                    words.insert(run[0].to_string());
+                }
+                words.extend(run.windows(2).map(|pair| pair.iter().collect::<String>()));
+            } else if run.len() >= 3 {
+                let word = run.iter().collect::<String>().to_lowercase();
+                if !STOPWORDS.contains(&word.as_str()) {
+                    words.insert(word);
+                }
+            }
+        }
+    }
+    words
+}
+
+/// Han, kana and hangul characters.
+fn is_cjk(c: char) -> bool {
+    matches!(
+        c,
+        '\u{3040}'..='\u{30ff}'
+            | '\u{3400}'..='\u{4dbf}'
+            | '\u{4e00}'..='\u{9fff}'
+            | '\u{ac00}'..='\u{d7af}'
+            | '\u{f900}'..='\u{faff}'
+            | '\u{20000}'..='\u{2ffff}'
+    )
+}
+
 fn read_skill(skill_dir: &Path, source: &str) -> Option<SkillMetadata> {
     let skill_file = skill_dir.join(SKILL_FILE_NAME);
     if !skill_file.is_file() {
@@ -107,10 +325,24 @@ fn read_skill(skill_dir: &Path, source: &str) -> Option<SkillMetadata> {
     })
 }
 
+/// Return the document body after a leading `---` frontmatter block.
+pub(crate) fn strip_frontmatter(content: &str) -> &str {
+    let Some(rest) = content.trim_start().strip_prefix("---") else {
+        return content;
+    };
+    match rest.find("\n---") {
+        Some(end) => {
+            let after = &rest[end + 4..];
+            after.split_once('\n').map(|(_, body)| body).unwrap_or("")
+        }
+        None => content,
+    }
+}
+
 /// Parse YAML-style frontmatter delimited by `---`.
 ///
 /// Supports simple `key: value` pairs only (no nested structures).
-fn parse_frontmatter(content: &str) -> std::collections::HashMap<String, String> {
+pub(crate) fn parse_frontmatter(content: &str) -> std::collections::HashMap<String, String> {
     let mut map = std::collections::HashMap::new();
     let lines: Vec<&str> = content.lines().collect();
 
@@ -163,6 +395,159 @@ mod tests {
         assert_eq!(skills[0].source, "project");
     }
 
+    #[test]
+    fn project_skills_override_user_skills() {
+        let project = tempdir().unwrap();
+        let user = tempdir().unwrap();
+        write_skill(
+            project.path(),
+            "shared",
+            "---\nname: shared\ndescription: Project copy\n---\n",
+        );
+        write_skill(
+            user.path(),
+            "Shared",
+            "---\nname: Shared\ndescription: User copy\n---\n",
+        );
+        write_skill(
+            user.path(),
+            "user-only",
+            "---\nname: user-only\ndescription: Only in user dir\n---\n",
+        );
+
+        let roots = [
+            (project.path().join(PROJECT_SKILLS_DIR), "project"),
+            (user.path().join(PROJECT_SKILLS_DIR), "user"),
+        ];
+        let skills = discover_skills_in_roots(&roots);
+        assert_eq!(skills.len(), 2);
+        assert_eq!(skills[0].name, "shared");
+        assert_eq!(skills[0].description, "Project copy");
+        assert_eq!(skills[0].source, "project");
+        assert_eq!(skills[1].name, "user-only");
+        assert_eq!(skills[1].source, "user");
+    }
+
+    fn meta(name: &str, description: &str) -> SkillMetadata {
+        SkillMetadata {
+            name: name.to_string(),
+            description: description.to_string(),
+            location: PathBuf::new(),
+            source: "project".to_string(),
+        }
+    }
+
+    #[test]
+    fn select_relevant_skills_ranks_by_overlap() {
+        let skills = vec![
+            meta("docker-deploy", "Build and push docker images"),
+            meta("pdf-report", "Generate a PDF report from markdown"),
+            meta("release-notes", "Draft release notes from git history"),
+        ];
+
+        let picked = select_relevant_skills(&skills, "Deploy the docker image please", 2);
+        assert_eq!(picked.len(), 1);
+        assert_eq!(picked[0].name, "docker-deploy");
+
+        let picked = select_relevant_skills(&skills, "write release notes from git", 5);
+        assert_eq!(picked[0].name, "release-notes");
+
+        assert!(select_relevant_skills(&skills, "hello there", 3).is_empty());
+        assert!(select_relevant_skills(&skills, "docker", 0).is_empty());
+    }
+
+    #[test]
+    fn keywords_count_characters_and_split_cjk() {
+        let words = keywords("ün déjà vu");
+        assert!(words.contains("déjà"));
+        assert!(!words.contains("ün"));
+
+        let words = keywords("部署Docker镜像，好");
+        for expected in ["部署", "docker", "镜像", "好"] {
+            assert!(
+                words.contains(expected),
+                "{expected} missing from {words:?}"
+            );
+        }
+
+        let skills = vec![
+            meta("docker-deploy", "构建并推送 Docker 镜像"),
+            meta("pdf-report", "从 Markdown 生成 PDF 报告"),
+        ];
+        let picked = select_relevant_skills(&skills, "帮我推送一下镜像", 3);
+        assert_eq!(picked.len(), 1);
+        assert_eq!(picked[0].name, "docker-deploy");
+    }
+
+    #[test]
+    fn lint_skills_reports_errors_and_warnings() {
+        let project = tempdir().unwrap();
+        let user = tempdir().unwrap();
+        write_skill(
+            project.path(),
+            "good",
+            "---\nname: good\ndescription: Fine\nparameters: {\"type\": \"object\"}\n---\n",
+        );
+        write_skill(project.path(), "bare", "# No frontmatter");
+        write_skill(
+            project.path(),
+            "dup",
+            "---\nname: Good\ndescription: Clashes\n---\n",
+        );
+        write_skill(
+            project.path(),
+            "params",
+            "---\nname: params\ndescription: Bad params\nparameters: [1, 2\n---\n",
+        );
+        write_skill(
+            user.path(),
+            "good",
+            "---\nname: good\ndescription: Shadowed\n---\n",
+        );
+
+        let roots = [
+            (project.path().join(PROJECT_SKILLS_DIR), "project"),
+            (user.path().join(PROJECT_SKILLS_DIR), "user"),
+        ];
+        let issues = lint_skills_in_roots(&roots);
+        let errors: Vec<_> = issues
+            .iter()
+            .filter(|i| i.severity == LintSeverity::Error)
+            .map(|i| i.message.as_str())
+            .collect();
+        assert_eq!(errors.len(), 3, "{issues:?}");
+        assert!(errors.iter().any(|m| m.contains("missing frontmatter")));
+        assert!(errors.iter().any(|m| m.contains("duplicate skill name")));
+        assert!(errors.iter().any(|m| m.contains("not valid JSON")));
+        assert!(issues.iter().any(|i| {
+            i.severity == LintSeverity::Warning && i.message.contains("shadowed by project")
+        }));
+    }
+
+    #[test]
+    fn lint_skills_flags_oversized_file() {
+        let project = tempdir().unwrap();
+        let body = format!(
+            "---\nname: big\ndescription: Big\n---\n{}",
+            "x".repeat(MAX_SKILL_BODY_BYTES)
+        );
+        write_skill(project.path(), "big", &body);
+
+        let roots = [(project.path().join(PROJECT_SKILLS_DIR), "project")];
+        let issues = lint_skills_in_roots(&roots);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].message.contains("limit"));
+    }
+
+    #[test]
+    fn strip_frontmatter_returns_body() {
+        assert_eq!(
+            strip_frontmatter("---\nname: x\n---\nBody line\n"),
+            "Body line\n"
+        );
+        assert_eq!(strip_frontmatter("No frontmatter"), "No frontmatter");
+    }
+
     #[test]
     fn frontmatter_parsing() {
         let fm = parse_frontmatter("---\nname: test\ndescription: A skill\n---\nBody");
@@ -0,0 +1,509 @@
+//! File watches that tell the session when workspace files change.
+//!
+//! `watch.add` registers a path or glob (`target/report.txt`, `src/**/*.rs`)
+//! relative to the workspace. A background task polls the size and
+//! modification time of matching files and, when something changes, either
+//! sends a notification through the session's [`Notifier`] or runs an
+//! agent-mode prompt through its [`AgentRunner`] with the changed paths
+//! appended.
+//!
+//! Like the scheduler, the service is a process-wide singleton so the sync
+//! `execute_tool` can reach it; watches do not survive a restart.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use regex::Regex;
+use tracing::{debug, warn};
+
+use crate::tools::file_ops::resolve_safe_path;
+use crate::tools::schedule::{AgentRunner, Notifier};
+
+/// Poll interval when `interval_seconds` is not given.
+pub const DEFAULT_INTERVAL_SECS: u64 = 5;
+/// Longest accepted poll interval.
+const MAX_INTERVAL_SECS: u64 = 3_600;
+/// Files tracked per watch; larger trees are truncated.
+const MAX_WATCHED_FILES: usize = 10_000;
+/// Changed paths listed in one notification.
+const MAX_REPORTED_CHANGES: usize = 20;
+
+/// Directories skipped while walking, unless the watch points inside them.
+const SKIP_DIRS: &[&str] = &[".git", ".crabclaw", "node_modules", "__pycache__", ".venv"];
+
+/// Size and modification time of each matched file, keyed by
+/// workspace-relative path.
+type Snapshot = BTreeMap<String, (u64, Option<SystemTime>)>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Change {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl Change {
+    fn label(self) -> &'static str {
+        match self {
+            Change::Added => "added",
+            Change::Modified => "modified",
+            Change::Removed => "removed",
+        }
+    }
+}
+
+/// A workspace path or glob, compiled for matching.
+#[derive(Debug, Clone)]
+struct WatchTarget {
+    workspace: PathBuf,
+    /// Directory or file to walk: the pattern up to its first glob component.
+    root: PathBuf,
+    /// Matches workspace-relative paths; `None` accepts everything under `root`.
+    matcher: Option<Regex>,
+}
+
+impl WatchTarget {
+    fn new(workspace: &Path, pattern: &str) -> Result<Self, String> {
+        let pattern = pattern.trim().trim_start_matches("./");
+        if pattern.is_empty() {
+            return Err("'path' argument is required.".to_string());
+        }
+        let workspace = workspace
+            .canonicalize()
+            .unwrap_or_else(|_| workspace.to_path_buf());
+        let literal: Vec<&str> = pattern
+            .split('/')
+            .take_while(|part| !part.contains(['*', '?', '[']))
+            .collect();
+        let is_glob = literal.len() < pattern.split('/').count();
+        let base = if literal.is_empty() {
+            ".".to_string()
+        } else {
+            literal.join("/")
+        };
+        let root = resolve_safe_path(&workspace, &base)
+            .ok_or_else(|| format!("Access denied: path escapes workspace: {pattern}"))?;
+        let matcher = if is_glob {
+            let relative = root
+                .strip_prefix(&workspace)
+                .map(|p| p.to_string_lossy().replace('\\', "/"))
+                .unwrap_or_default();
+            let rest = pattern
+                .split('/')
+                .skip(literal.len())
+                .collect::<Vec<_>>()
+                .join("/");
+            let full = if relative.is_empty() {
+                rest.to_string()
+            } else {
+                format!("{relative}/{rest}")
+            };
+            Some(glob_to_regex(&full)?)
+        } else {
+            None
+        };
+        Ok(Self {
+            workspace,
+            root,
+            matcher,
+        })
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        let mut files = Snapshot::new();
+        self.walk(&self.root, &mut files);
+        files
+    }
+
+    fn walk(&self, path: &Path, files: &mut Snapshot) {
+        if files.len() >= MAX_WATCHED_FILES {
+            return;
+        }
+        let Ok(meta) = std::fs::metadata(path) else {
+            return;
+        };
+        if meta.is_dir() {
+            let Ok(entries) = std::fs::read_dir(path) else {
+                return;
+            };
+            for entry in entries.flatten() {
+                let child = entry.path();
+                let skipped = child.is_dir()
+                    && entry
+                        .file_name()
+                        .to_str()
+                        .is_some_and(|name| SKIP_DIRS.contains(&name));
+                if !skipped {
+                    self.walk(&child, files);
+                }
+            }
+            return;
+        }
+        let relative = path
+            .strip_prefix(&self.workspace)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        if self.matcher.as_ref().is_none_or(|m| m.is_match(&relative)) {
+            files.insert(relative, (meta.len(), meta.modified().ok()));
+        }
+    }
+}
+
+/// Translate a glob (`*`, `?`, `**`) over `/`-separated paths to a regex.
+fn glob_to_regex(glob: &str) -> Result<Regex, String> {
+    let mut re = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    re.push('$');
+    Regex::new(&re).map_err(|e| format!("invalid pattern '{glob}': {e}"))
+}
+
+fn diff(before: &Snapshot, after: &Snapshot) -> Vec<(String, Change)> {
+    let mut changes = Vec::new();
+    for (path, state) in after {
+        match before.get(path) {
+            None => changes.push((path.clone(), Change::Added)),
+            Some(old) if old != state => changes.push((path.clone(), Change::Modified)),
+            Some(_) => {}
+        }
+    }
+    for path in before.keys() {
+        if !after.contains_key(path) {
+            changes.push((path.clone(), Change::Removed));
+        }
+    }
+    changes
+}
+
+fn format_changes(changes: &[(String, Change)]) -> String {
+    let mut lines: Vec<String> = changes
+        .iter()
+        .take(MAX_REPORTED_CHANGES)
+        .map(|(path, change)| format!("  {}: {path}", change.label()))
+        .collect();
+    if changes.len() > MAX_REPORTED_CHANGES {
+        lines.push(format!(
+            "  [... {} more]",
+            changes.len() - MAX_REPORTED_CHANGES
+        ));
+    }
+    lines.join("\n")
+}
+
+#[derive(Debug, Clone)]
+struct WatchEntry {
+    pattern: String,
+    prompt: Option<String>,
+    interval: Duration,
+}
+
+/// Registry of active watches and their polling tasks.
+#[derive(Clone, Default)]
+pub struct WatchService {
+    watches: Arc<Mutex<HashMap<String, WatchEntry>>>,
+    handles: Arc<Mutex<HashMap<String, tokio::task::JoinHandle<()>>>>,
+}
+
+impl std::fmt::Debug for WatchService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchService")
+            .field("watches", &self.watches)
+            .finish()
+    }
+}
+
+impl WatchService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start watching `pattern` in `workspace`.
+    ///
+    /// Without a `prompt`, changes are reported through `notifier`; with one,
+    /// `agent_runner` runs the prompt with the changed paths appended.
+    pub fn add_watch(
+        &self,
+        workspace: &Path,
+        pattern: &str,
+        prompt: Option<&str>,
+        interval: Duration,
+        notifier: Option<Notifier>,
+        agent_runner: Option<AgentRunner>,
+    ) -> String {
+        let target = match WatchTarget::new(workspace, pattern) {
+            Ok(target) => target,
+            Err(e) => return format!("Error: {e}"),
+        };
+        let prompt = prompt.map(str::trim).filter(|p| !p.is_empty());
+        if prompt.is_some() && agent_runner.is_none() {
+            return "Error: watch prompts require an agent runner (not available in this channel)"
+                .to_string();
+        }
+        if prompt.is_none() && notifier.is_none() {
+            return "Error: no notifier available in this channel to report changes".to_string();
+        }
+        let handle = match tokio::runtime::Handle::try_current() {
+            Ok(h) => h,
+            Err(_) => return "Error: no async runtime available to watch files".to_string(),
+        };
+
+        let id = generate_watch_id();
+        let pattern = pattern.trim().to_string();
+        let initial = target.snapshot();
+        let matched = initial.len();
+        self.watches.lock().unwrap().insert(
+            id.clone(),
+            WatchEntry {
+                pattern: pattern.clone(),
+                prompt: prompt.map(ToString::to_string),
+                interval,
+            },
+        );
+
+        let action = if prompt.is_some() {
+            "runs the prompt"
+        } else {
+            "notifies"
+        };
+        let summary = format!(
+            "watching: {id} — {pattern} ({matched} file(s) matched, checked every {}s, {action} on change)",
+            interval.as_secs().max(1)
+        );
+
+        let watches = self.watches.clone();
+        let watch_id = id.clone();
+        let prompt = prompt.map(ToString::to_string);
+        let task = handle.spawn(async move {
+            let mut last = initial;
+            loop {
+                tokio::time::sleep(interval).await;
+                if !watches.lock().unwrap().contains_key(&watch_id) {
+                    break;
+                }
+                let target = target.clone();
+                let Ok(current) = tokio::task::spawn_blocking(move || target.snapshot()).await
+                else {
+                    continue;
+                };
+                let changes = diff(&last, &current);
+                last = current;
+                if changes.is_empty() {
+                    continue;
+                }
+                debug!(watch_id = %watch_id, changes = changes.len(), "watch: change detected");
+                let listing = format_changes(&changes);
+                match (&prompt, &agent_runner, &notifier) {
+                    (Some(prompt), Some(runner), _) => {
+                        let report = runner(
+                            watch_id.clone(),
+                            format!("{prompt}\n\nChanged files ({pattern}):\n{listing}"),
+                        )
+                        .await;
+                        if let Some(error) = &report.error {
+                            warn!(watch_id = %watch_id, %error, "watch: agent run failed");
+                            if let Some(notify) = &notifier {
+                                notify(report.to_notification());
+                            }
+                        } else if let (Some(output), Some(notify)) = (report.output, &notifier) {
+                            notify(output);
+                        }
+                    }
+                    (_, _, Some(notify)) => notify(format!(
+                        "\u{1f440} [Watch: {watch_id}] {pattern} changed:\n{listing}"
+                    )),
+                    _ => {}
+                }
+            }
+        });
+        self.handles.lock().unwrap().insert(id, task);
+        summary
+    }
+
+    /// List active watches.
+    pub fn list_watches(&self) -> String {
+        let watches = self.watches.lock().unwrap();
+        if watches.is_empty() {
+            return "No active watches.".to_string();
+        }
+        let mut ids: Vec<&String> = watches.keys().collect();
+        ids.sort();
+        let mut lines = vec![format!("Active watches ({}):", ids.len())];
+        for id in ids {
+            let watch = &watches[id];
+            let action = match &watch.prompt {
+                Some(prompt) => format!("prompt: {prompt}"),
+                None => "notify".to_string(),
+            };
+            lines.push(format!(
+                "  {id}: {} every {}s ({action})",
+                watch.pattern,
+                watch.interval.as_secs().max(1)
+            ));
+        }
+        lines.join("\n")
+    }
+
+    /// Stop and remove a watch.
+    pub fn remove_watch(&self, id: &str) -> String {
+        if self.watches.lock().unwrap().remove(id).is_none() {
+            return format!("Error: watch not found: {id}");
+        }
+        if let Some(handle) = self.handles.lock().unwrap().remove(id) {
+            handle.abort();
+        }
+        format!("removed watch: {id}")
+    }
+}
+
+/// Clamp a requested interval to the accepted range.
+pub fn interval_from_secs(secs: Option<u64>) -> Duration {
+    Duration::from_secs(
+        secs.unwrap_or(DEFAULT_INTERVAL_SECS)
+            .clamp(1, MAX_INTERVAL_SECS),
+    )
+}
+
+fn generate_watch_id() -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    SystemTime::now().hash(&mut hasher);
+    std::thread::current().id().hash(&mut hasher);
+    format!("w{:07x}", hasher.finish() as u32 >> 4)
+}
+
+/// Global watch service singleton.
+static GLOBAL_WATCHER: std::sync::OnceLock<WatchService> = std::sync::OnceLock::new();
+
+/// Get or initialize the global watch service.
+pub fn global_watcher() -> &'static WatchService {
+    GLOBAL_WATCHER.get_or_init(WatchService::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn globs_match_relative_paths() {
+        let re = glob_to_regex("src/**/*.rs").unwrap();
+        assert!(re.is_match("src/main.rs"));
+        assert!(re.is_match("src/core/agent_loop.rs"));
+        assert!(!re.is_match("src/main.rsx"));
+        assert!(!re.is_match("tests/a.rs"));
+        let re = glob_to_regex("logs/app-?.log").unwrap();
+        assert!(re.is_match("logs/app-1.log"));
+        assert!(!re.is_match("logs/app-10.log"));
+    }
+
+    #[test]
+    fn targets_stay_inside_workspace() {
+        let ws = tempdir().unwrap();
+        assert!(WatchTarget::new(ws.path(), "../elsewhere").is_err());
+        assert!(WatchTarget::new(ws.path(), "").is_err());
+    }
+
+    #[test]
+    fn snapshots_diff_added_modified_removed() {
+        let ws = tempdir().unwrap();
+        std::fs::create_dir_all(ws.path().join("src/core")).unwrap();
+        std::fs::write(ws.path().join("src/a.rs"), "a").unwrap();
+        std::fs::write(ws.path().join("src/core/b.rs"), "b").unwrap();
+        std::fs::write(ws.path().join("src/notes.txt"), "n").unwrap();
+
+        let target = WatchTarget::new(ws.path(), "src/**/*.rs").unwrap();
+        let before = target.snapshot();
+        assert_eq!(
+            before.keys().collect::<Vec<_>>(),
+            ["src/a.rs", "src/core/b.rs"]
+        );
+
+        std::fs::write(ws.path().join("src/a.rs"), "changed").unwrap();
+        std::fs::remove_file(ws.path().join("src/core/b.rs")).unwrap();
+        std::fs::write(ws.path().join("src/c.rs"), "c").unwrap();
+        std::fs::write(ws.path().join("src/notes.txt"), "ignored").unwrap();
+        let changes = diff(&before, &target.snapshot());
+        assert_eq!(
+            changes,
+            [
+                ("src/a.rs".to_string(), Change::Modified),
+                ("src/c.rs".to_string(), Change::Added),
+                ("src/core/b.rs".to_string(), Change::Removed),
+            ]
+        );
+        assert_eq!(
+            format_changes(&changes),
+            "  modified: src/a.rs\n  added: src/c.rs\n  removed: src/core/b.rs"
+        );
+    }
+
+    #[tokio::test]
+    async fn changes_are_reported_through_the_notifier() {
+        let ws = tempdir().unwrap();
+        let file = ws.path().join("build.log");
+        std::fs::write(&file, "start").unwrap();
+
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        let notifier: Notifier = Arc::new(move |msg| {
+            let _ = tx.send(msg);
+        });
+        let svc = WatchService::new();
+        let added = svc.add_watch(
+            ws.path(),
+            "build.log",
+            None,
+            Duration::from_millis(50),
+            Some(notifier),
+            None,
+        );
+        assert!(added.starts_with("watching: "), "{added}");
+        let id = added["watching: ".len()..].split(' ').next().unwrap();
+        assert!(svc.list_watches().contains("build.log"));
+
+        std::fs::write(&file, "finished ok").unwrap();
+        let message = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("change notification")
+            .unwrap();
+        assert!(message.contains("modified: build.log"), "{message}");
+
+        assert_eq!(svc.remove_watch(id), format!("removed watch: {id}"));
+        assert_eq!(svc.list_watches(), "No active watches.");
+        assert!(svc.remove_watch(id).starts_with("Error"));
+    }
+
+    #[tokio::test]
+    async fn prompts_need_an_agent_runner() {
+        let ws = tempdir().unwrap();
+        let notifier: Notifier = Arc::new(|_| {});
+        let result = WatchService::new().add_watch(
+            ws.path(),
+            ".",
+            Some("summarize the diff"),
+            Duration::from_secs(1),
+            Some(notifier),
+            None,
+        );
+        assert!(result.contains("agent runner"), "{result}");
+    }
+}
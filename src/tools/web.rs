@@ -6,7 +6,7 @@ const MAX_FETCH_BYTES: usize = 1_000_000; // 1 MB
 const WEB_USER_AGENT: &str = "crabclaw/0.1";
 
 /// Normalize a raw URL string, prepending `https://` if no scheme is present.
-fn normalize_url(raw: &str) -> Option<String> {
+pub(crate) fn normalize_url(raw: &str) -> Option<String> {
     let trimmed = raw.trim();
     if trimmed.is_empty() {
         return None;
@@ -98,11 +98,16 @@ pub fn fetch_url(raw_url: &str) -> String {
     }
 }
 
+/// DuckDuckGo search URL for the given query.
+pub fn search_url(query: &str) -> String {
+    format!("https://duckduckgo.com/?q={}", urlencoding::encode(query))
+}
+
 /// Generate a DuckDuckGo search URL for the given query.
 pub fn web_search(query: &str) -> String {
-    let encoded = urlencoding::encode(query);
     format!(
-        "Search URL: https://duckduckgo.com/?q={encoded}\n\nTip: Use web.fetch to retrieve the content of specific result pages."
+        "Search URL: {}\n\nTip: Use web.fetch to retrieve the content of specific result pages.",
+        search_url(query)
     )
 }
 
@@ -224,13 +229,12 @@ pub fn strip_html_to_markdown(html: &str) -> String {
                         result.push('\n');
                         last_was_newline = true;
                     }
-                    _ if tag.starts_with("/p")
+                    _ if (tag.starts_with("/p")
                         || tag.starts_with("/div")
-                        || tag.starts_with("/li") =>
+                        || tag.starts_with("/li"))
+                        && !result.ends_with('\n') =>
                     {
-                        if !result.ends_with('\n') {
-                            result.push('\n');
-                        }
+                        result.push('\n');
                     }
                     _ => {} // strip unknown tags
                 }
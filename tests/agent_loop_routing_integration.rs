@@ -103,3 +103,59 @@ async fn api_error_is_propagated() {
     let result = agent.handle_input("trigger error").await;
     assert!(result.error.is_some());
 }
+
+#[tokio::test]
+async fn redo_resends_the_last_prompt_with_another_model() {
+    let mut server = mockito::Server::new_async().await;
+    let first = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({ "model": "test-model" }),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("A sloppy answer."))
+        .expect(2)
+        .create_async()
+        .await;
+    let redo = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({ "model": "other-model" }),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("A careful answer."))
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_redo", None, None).unwrap();
+
+    agent.handle_input("Explain lifetimes").await;
+    let retried = agent.handle_input(",retry").await;
+    assert_eq!(
+        retried.assistant_output.as_deref(),
+        Some("A sloppy answer.")
+    );
+
+    let redone = agent.handle_input(",redo --model openai:other-model").await;
+    first.assert_async().await;
+    redo.assert_async().await;
+    assert_eq!(
+        redone.assistant_output.as_deref(),
+        Some("A careful answer.")
+    );
+    assert_eq!(
+        redone.immediate_output.as_deref(),
+        Some("Retrying with openai:other-model.")
+    );
+
+    // Only the latest attempt stays in the conversation, and the session
+    // model is unchanged.
+    let messages = crabclaw::core::context::build_messages(agent.tape(), None, 50);
+    let contents: Vec<&str> = messages.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, vec!["Explain lifetimes", "A careful answer."]);
+    assert_eq!(crabclaw::core::agent_loop::tape_model(agent.tape()), None);
+}
@@ -1,8 +1,8 @@
 mod support;
 
 use crabclaw::core::agent_loop::AgentLoop;
-use support::builders::openai_config;
-use support::responses::{text_response, tool_call_response};
+use support::builders::{anthropic_config, openai_config};
+use support::responses::{text_response, tool_call_response, truncated_response};
 use support::sse::{sse_content_chunk, sse_stream, sse_tool_call_args, sse_tool_call_start};
 use tempfile::TempDir;
 
@@ -169,3 +169,673 @@ async fn tool_loop_breaks_after_max_iterations_without_hanging() {
     assert_eq!(result.tool_rounds, DEFAULT_MAX_TOOL_ITERATIONS);
     assert!(result.assistant_output.is_none() || result.assistant_output.as_deref() == Some(""));
 }
+
+#[tokio::test]
+async fn first_exchange_names_the_session() {
+    let mut server = mockito::Server::new_async().await;
+    let title_mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("Write a title".into()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("Weekend hiking plans."))
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("Try the ridge trail."))
+        .create_async()
+        .await;
+
+    let mut config = openai_config(&server.url());
+    config.session_titles = crabclaw::core::title::TitleMode::Model;
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_title", None, None).unwrap();
+
+    agent.handle_input("where should I hike?").await;
+    agent.handle_input("and next week?").await;
+    title_mock.assert_async().await;
+
+    let tape =
+        crabclaw::tape::store::TapeStore::open(&workspace.path().join(".crabclaw"), "test_title")
+            .unwrap();
+    assert_eq!(
+        crabclaw::core::title::tape_title(&tape).as_deref(),
+        Some("Weekend hiking plans")
+    );
+}
+
+#[tokio::test]
+async fn truncated_answer_is_continued_and_stitched() {
+    let mut server = mockito::Server::new_async().await;
+
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(truncated_response("The three steps are: one, two,"))
+        .create_async()
+        .await;
+
+    let continuation = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("Continue exactly".to_string()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response(" and three."))
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent =
+        AgentLoop::open(&config, workspace.path(), "test_continue", None, None).unwrap();
+
+    let result = agent.handle_input("list the steps").await;
+    continuation.assert_async().await;
+    assert!(result.error.is_none());
+    assert_eq!(
+        result.assistant_output.as_deref(),
+        Some("The three steps are: one, two, and three.")
+    );
+}
+
+#[tokio::test]
+async fn streaming_truncation_stops_after_configured_continuations() {
+    let mut server = mockito::Server::new_async().await;
+
+    let truncated = sse_stream(&[
+        &sse_content_chunk("Part"),
+        r#"{"choices":[{"delta":{},"finish_reason":"length"}]}"#,
+    ]);
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(truncated)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let mut config = openai_config(&server.url());
+    config.max_continuations = 1;
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(
+        &config,
+        workspace.path(),
+        "test_continue_stream",
+        None,
+        None,
+    )
+    .unwrap();
+
+    let mut tokens = Vec::<String>::new();
+    let result = agent
+        .handle_input_stream("write a lot", |token| tokens.push(token.to_string()))
+        .await;
+
+    mock.assert_async().await;
+    assert!(result.error.is_none());
+    assert_eq!(tokens, vec!["Part", "Part"]);
+    assert_eq!(result.assistant_output.as_deref(), Some("PartPart"));
+}
+
+#[tokio::test]
+async fn structured_output_is_retried_until_it_matches_the_schema() {
+    let mut server = mockito::Server::new_async().await;
+    let retry = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("was rejected".into()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response(r#"```json\n{\"count\": 2}\n```"#))
+        .expect(1)
+        .create_async()
+        .await;
+    let first = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("matches this JSON Schema".into()))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response(r#"{\"count\": \"two\"}"#))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_schema", None, None).unwrap();
+    agent.set_next_output_schema(Some(serde_json::json!({
+        "type": "object",
+        "properties": { "count": { "type": "integer" } },
+        "required": ["count"]
+    })));
+
+    let result = agent.handle_input("how many?").await;
+    first.assert_async().await;
+    retry.assert_async().await;
+    assert!(result.error.is_none(), "{:?}", result.error);
+    assert_eq!(
+        result.structured_output,
+        Some(serde_json::json!({ "count": 2 }))
+    );
+}
+
+#[tokio::test]
+async fn structured_output_fails_after_retries_run_out() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("I think there are two."))
+        .expect(3)
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent =
+        AgentLoop::open(&config, workspace.path(), "test_schema_fail", None, None).unwrap();
+    agent.set_next_output_schema(Some(serde_json::json!({ "type": "object" })));
+
+    let result = agent.handle_input("how many?").await;
+    mock.assert_async().await;
+    assert!(result.structured_output.is_none());
+    let error = result.error.unwrap();
+    assert!(error.contains("rejected after 3 attempt(s)"), "{error}");
+
+    // The schema applies to one turn only.
+    let result = agent.handle_input("thanks").await;
+    assert!(result.error.is_none());
+}
+
+#[tokio::test]
+async fn invalid_tool_arguments_are_rejected_with_the_schema() {
+    let mut server = mockito::Server::new_async().await;
+    let follow_up = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex(
+            "invalid arguments for file.read.*missing required property 'path'".into(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("Which file?"))
+        .expect(1)
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(tool_call_response(
+            "file.read",
+            "call_bad",
+            r#"{"file": "a.txt"}"#,
+        ))
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent =
+        AgentLoop::open(&config, workspace.path(), "test_bad_args", None, None).unwrap();
+
+    let result = agent.handle_input("read it").await;
+    follow_up.assert_async().await;
+    assert_eq!(result.assistant_output.as_deref(), Some("Which file?"));
+}
+
+#[tokio::test]
+async fn malformed_streamed_tool_arguments_are_repaired_and_recorded() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(sse_stream(&[
+            &sse_tool_call_start(0, "call_glm", "file.list"),
+            &sse_tool_call_args(0, r#"{"path": ".","#),
+        ]))
+        .create_async()
+        .await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(sse_stream(&[&sse_content_chunk("Listed.")]))
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_repair", None, None).unwrap();
+
+    let result = agent.handle_input_stream("list files", |_| {}).await;
+    assert!(result.error.is_none(), "{:?}", result.error);
+    assert_eq!(result.tool_rounds, 1);
+
+    let repair = agent
+        .tape()
+        .entries()
+        .iter()
+        .find(|e| e.kind == "tool.args_repaired")
+        .expect("repair recorded");
+    assert_eq!(repair.payload["tool"], "file.list");
+    assert_eq!(repair.payload["original"], r#"{"path": ".","#);
+    assert_eq!(repair.payload["repaired"], r#"{"path": "."}"#);
+}
+
+#[tokio::test]
+async fn tool_arguments_cut_off_mid_string_are_not_run() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(sse_stream(&[
+            &sse_tool_call_start(0, "call_cut", "file.write"),
+            &sse_tool_call_args(0, r#"{"path": "notes.txt", "content": "first hal"#),
+        ]))
+        .create_async()
+        .await;
+    let follow_up = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex(
+            "invalid arguments for file.write.*not valid JSON".into(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(sse_stream(&[&sse_content_chunk("Retrying.")]))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    std::fs::write(workspace.path().join("notes.txt"), "original").unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_cut", None, None).unwrap();
+
+    let result = agent.handle_input_stream("write notes", |_| {}).await;
+    follow_up.assert_async().await;
+    assert_eq!(result.assistant_output.as_deref(), Some("Retrying."));
+    assert_eq!(
+        std::fs::read_to_string(workspace.path().join("notes.txt")).unwrap(),
+        "original"
+    );
+    assert!(
+        !agent
+            .tape()
+            .entries()
+            .iter()
+            .any(|e| e.kind == "tool.args_repaired")
+    );
+}
+
+#[tokio::test]
+async fn tool_arguments_are_not_repaired_after_the_token_limit() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            tool_call_response("file.write", "call_len", r#"{"path": "notes.txt","#).replace(
+                r#""finish_reason":"tool_calls""#,
+                r#""finish_reason":"length""#,
+            ),
+        )
+        .create_async()
+        .await;
+    let follow_up = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex(
+            "invalid arguments for file.write".into(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("Retrying."))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_len", None, None).unwrap();
+
+    let result = agent.handle_input("write notes").await;
+    follow_up.assert_async().await;
+    assert_eq!(result.assistant_output.as_deref(), Some("Retrying."));
+    assert!(!workspace.path().join("notes.txt").exists());
+}
+
+#[tokio::test]
+async fn broken_stream_keeps_partial_text_and_resumes() {
+    let mut server = mockito::Server::new_async().await;
+    let text_delta = |text: &str| {
+        format!(
+            r#"{{"type":"content_block_delta","index":0,"delta":{{"type":"text_delta","text":"{text}"}}}}"#
+        )
+    };
+    let event = |data: &str| format!("data: {data}\n\n");
+    let broken = [
+        event(
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        ),
+        event(&text_delta("Half an ")),
+        event(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#),
+    ]
+    .concat();
+    let rest = [
+        event(
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        ),
+        event(&text_delta(" answer.")),
+        event(r#"{"type":"message_stop"}"#),
+    ]
+    .concat();
+    let first = server
+        .mock("POST", "/v1/messages")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(broken)
+        .expect(1)
+        .create_async()
+        .await;
+    let resumed = server
+        .mock("POST", "/v1/messages")
+        // Resumed with the partial text as the assistant prefix, trailing
+        // whitespace trimmed.
+        .match_body(mockito::Matcher::Regex(
+            r#"\{"content":"Half an","role":"assistant"\}\]"#.into(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(rest)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut config = anthropic_config(&server.url());
+    config.llm_retry.initial_delay_ms = 0;
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_partial", None, None).unwrap();
+
+    let mut tokens = Vec::<String>::new();
+    let result = agent
+        .handle_input_stream("explain", |token| tokens.push(token.to_string()))
+        .await;
+
+    first.assert_async().await;
+    resumed.assert_async().await;
+    assert!(result.error.is_none(), "{:?}", result.error);
+    assert_eq!(tokens, vec!["Half an ", "answer."]);
+    assert_eq!(result.assistant_output.as_deref(), Some("Half an answer."));
+
+    let partial = agent
+        .tape()
+        .entries()
+        .iter()
+        .find(|e| e.kind == "turn.partial")
+        .expect("partial recorded");
+    assert_eq!(partial.payload["text"], "Half an ");
+    assert_eq!(partial.payload["resumed"], true);
+    assert!(
+        partial.payload["error"]
+            .as_str()
+            .unwrap()
+            .contains("Overloaded")
+    );
+}
+
+#[tokio::test]
+async fn broken_stream_restarts_from_scratch_when_configured() {
+    use crabclaw::core::config::StreamRetry;
+    use crabclaw::core::model_runner::RESTART_NOTICE;
+
+    let mut server = mockito::Server::new_async().await;
+    let event = |data: &str| format!("data: {data}\n\n");
+    let text_stream = |text: &str, end: &str| {
+        [
+            event(
+                r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+            ),
+            event(&format!(
+                r#"{{"type":"content_block_delta","index":0,"delta":{{"type":"text_delta","text":"{text}"}}}}"#
+            )),
+            event(end),
+        ]
+        .concat()
+    };
+    let broken = server
+        .mock("POST", "/v1/messages")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(text_stream(
+            "Half an ",
+            r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#,
+        ))
+        .expect(1)
+        .create_async()
+        .await;
+    let restarted = server
+        .mock("POST", "/v1/messages")
+        // The same request again: it still ends with the user's message.
+        .match_body(mockito::Matcher::Regex(
+            r#"\{"content":"explain","role":"user"\}\]"#.into(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(text_stream("A whole answer.", r#"{"type":"message_stop"}"#))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut config = anthropic_config(&server.url());
+    config.llm_retry.initial_delay_ms = 0;
+    config.llm_retry.stream = StreamRetry::Restart;
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_restart", None, None).unwrap();
+
+    let mut tokens = Vec::<String>::new();
+    let result = agent
+        .handle_input_stream("explain", |token| tokens.push(token.to_string()))
+        .await;
+
+    broken.assert_async().await;
+    restarted.assert_async().await;
+    assert!(result.error.is_none(), "{:?}", result.error);
+    assert_eq!(tokens, vec!["Half an ", RESTART_NOTICE, "A whole answer."]);
+    assert_eq!(result.assistant_output.as_deref(), Some("A whole answer."));
+    assert!(
+        !agent
+            .tape()
+            .entries()
+            .iter()
+            .any(|e| e.kind == "turn.partial")
+    );
+}
+
+#[tokio::test]
+async fn broken_stream_is_kept_once_retries_run_out() {
+    let mut server = mockito::Server::new_async().await;
+    let event = |data: &str| format!("data: {data}\n\n");
+    let broken = [
+        event(
+            r#"{"type":"content_block_start","index":0,"content_block":{"type":"text","text":""}}"#,
+        ),
+        event(
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Half an "}}"#,
+        ),
+        event(r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#),
+    ]
+    .concat();
+    let mock = server
+        .mock("POST", "/v1/messages")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(broken)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut config = anthropic_config(&server.url());
+    config.llm_retry.max_retries = 0;
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_give_up", None, None).unwrap();
+
+    let result = agent.handle_input_stream("explain", |_| {}).await;
+
+    mock.assert_async().await;
+    assert!(result.error.unwrap().contains("Overloaded"));
+    let partial = agent
+        .tape()
+        .entries()
+        .iter()
+        .find(|e| e.kind == "turn.partial")
+        .expect("partial recorded");
+    assert_eq!(partial.payload["text"], "Half an ");
+    assert_eq!(partial.payload["resumed"], false);
+}
+
+#[tokio::test]
+async fn replies_bring_the_linked_exchange_into_the_prompt() {
+    use crabclaw::core::reply_context::{ReplyTarget, link_sent_messages};
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex(
+            r"replied to\] Use port 8080\..*why that one\?".to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("Because 80 needs root."))
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_reply", None, None).unwrap();
+    let tape = agent.tape_mut();
+    tape.append_message("user", "Which port should the dev server use?")
+        .unwrap();
+    tape.append_message("assistant", "Use port 8080.").unwrap();
+    link_sent_messages(tape, &[501]).unwrap();
+    tape.anchor("compacted", serde_json::json!({})).unwrap();
+
+    agent.set_reply_to(Some(ReplyTarget {
+        message_id: Some(501),
+        text: "Use port 8080.".to_string(),
+    }));
+    let result = agent.handle_input("why that one?").await;
+    assert!(result.error.is_none(), "{:?}", result.error);
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn ask_all_compares_models_and_merges_with_the_judge() {
+    let mut server = mockito::Server::new_async().await;
+    for (model, answer) in [("model-a", "Use a lock."), ("model-b", "Use a channel.")] {
+        server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex(format!(r#""model":"{model}""#)))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(text_response(answer))
+            .expect(1)
+            .create_async()
+            .await;
+    }
+    let judge = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::Regex(r#""model":"judge""#.into()),
+            mockito::Matcher::Regex(r"Answer 2 \(openai:model-b\)".into()),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("Either works; a channel is simpler."))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let mut config = openai_config(&server.url());
+    config.consensus_models = vec!["openai:model-a".into(), "openai:model-b".into()];
+    config.consensus_judge = Some("openai:judge".into());
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_ask_all", None, None).unwrap();
+
+    let result = agent.handle_input(",ask-all How do I share state?").await;
+    let output = result.immediate_output.unwrap();
+    assert!(
+        output.starts_with("### openai:model-a ("),
+        "unexpected output: {output}"
+    );
+    assert!(output.contains("Use a lock."));
+    assert!(output.contains("Use a channel."));
+    assert!(output.contains("### Merged by openai:judge"));
+    assert!(output.ends_with("Either works; a channel is simpler."));
+    judge.assert_async().await;
+
+    let events: Vec<_> = agent
+        .tape()
+        .entries()
+        .iter()
+        .filter(|e| e.kind == "consensus")
+        .collect();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].payload["answers"][1]["text"], "Use a channel.");
+
+    let result = agent.handle_input(",ask-all --no-judge").await;
+    assert!(result.immediate_output.unwrap().starts_with("Usage"));
+}
+
+#[tokio::test]
+async fn turn_id_tags_requests_tape_entries_and_errors() {
+    let mut server = mockito::Server::new_async().await;
+    let turn_header = || mockito::Matcher::Regex("^[0-9a-f]{12}$".into());
+    let tool_mock = server
+        .mock("POST", "/chat/completions")
+        .match_header("x-client-request-id", turn_header())
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(tool_call_response("tools", "call_1", "{}"))
+        .expect(1)
+        .create_async()
+        .await;
+    let error_mock = server
+        .mock("POST", "/chat/completions")
+        .match_header("x-client-request-id", turn_header())
+        .with_status(500)
+        .with_body(r#"{"error":{"message":"boom"}}"#)
+        .expect(1)
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let workspace = TempDir::new().unwrap();
+    let mut agent = AgentLoop::open(&config, workspace.path(), "test_turn_id", None, None).unwrap();
+    let before = agent.tape().entries().len();
+
+    let result = agent.handle_input("what tools?").await;
+    tool_mock.assert_async().await;
+    error_mock.assert_async().await;
+
+    let turn_id = result.turn_id.clone().unwrap();
+    let error = result.error.unwrap();
+    assert!(error.ends_with(&format!("(turn {turn_id})")), "{error}");
+    let entries = &agent.tape().entries()[before..];
+    assert!(entries.len() > 2);
+    assert!(
+        entries
+            .iter()
+            .all(|e| e.turn.as_deref() == Some(turn_id.as_str()))
+    );
+
+    let next = agent.handle_input(",help").await;
+    assert_ne!(next.turn_id, Some(turn_id));
+}
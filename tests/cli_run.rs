@@ -1,3 +1,5 @@
+mod support;
+
 use std::fs;
 
 use assert_cmd::Command;
@@ -13,6 +15,9 @@ fn base_command() -> Command {
     cmd.env_remove("PROFILE_DEV_API_KEY");
     cmd.env_remove("PROFILE_DEV_BASE_URL");
     cmd.env_remove("PROFILE_DEV_MODEL");
+    cmd.env_remove("CRABCLAW_WORKSPACE");
+    cmd.env_remove("CRABCLAW_SESSION");
+    cmd.env_remove("CRABCLAW_RELEASES_URL");
     cmd
 }
 
@@ -120,6 +125,108 @@ fn run_comma_command_via_prompt() {
         .stdout(predicate::str::contains("Available commands"));
 }
 
+#[test]
+fn run_targets_workspace_and_session() {
+    let tmp = tempdir().expect("tempdir");
+    base_command()
+        .env("API_KEY", "test-key")
+        .args(["run", "--prompt", ",tape.info", "--session", "nightly"])
+        .arg("--workspace")
+        .arg(tmp.path())
+        .assert()
+        .success();
+    assert!(tmp.path().join(".crabclaw/nightly.jsonl").exists());
+
+    base_command()
+        .env("API_KEY", "test-key")
+        .env("CRABCLAW_WORKSPACE", tmp.path())
+        .env("CRABCLAW_SESSION", "from-env")
+        .args(["run", "--prompt", ",tape.info"])
+        .assert()
+        .success();
+    assert!(tmp.path().join(".crabclaw/from-env.jsonl").exists());
+
+    base_command()
+        .env("API_KEY", "test-key")
+        .args(["run", "--prompt", ",help", "--session", "../escape"])
+        .arg("--workspace")
+        .arg(tmp.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid session name"));
+    base_command()
+        .args(["run", "--prompt", ",help", "--workspace"])
+        .arg(tmp.path().join("missing"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("workspace"));
+}
+
+#[test]
+fn run_output_jsonl_streams_turn_events() {
+    use support::sse::{sse_content_chunk, sse_stream, sse_tool_call_args, sse_tool_call_start};
+
+    let tmp = tempdir().expect("tempdir");
+    fs::write(tmp.path().join("notes.txt"), "remember the milk").unwrap();
+    let mut server = mockito::Server::new();
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(sse_stream(&[
+            &sse_tool_call_start(0, "call_1", "file.read"),
+            &sse_tool_call_args(0, r#"{"path":"notes.txt"}"#),
+        ]))
+        .create();
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(sse_stream(&[
+            &sse_content_chunk("Buy "),
+            &sse_content_chunk("milk."),
+        ]))
+        .create();
+
+    let output = base_command()
+        .env("API_KEY", "test-key")
+        .env("BASE_URL", server.url())
+        .env("MODEL", "openai:test-model")
+        .args(["run", "--prompt", "what do I need?", "--output", "jsonl"])
+        .arg("--workspace")
+        .arg(tmp.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let events: Vec<serde_json::Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+    assert_eq!(
+        types,
+        [
+            "turn_started",
+            "tool_call",
+            "tool_result",
+            "token",
+            "token",
+            "turn_completed"
+        ]
+    );
+    assert_eq!(events[0]["prompt"], "what do I need?");
+    assert_eq!(events[1]["id"], "call_1");
+    assert!(
+        events[2]["result"]
+            .as_str()
+            .unwrap()
+            .contains("remember the milk")
+    );
+    assert_eq!(events[5]["output"], "Buy milk.");
+    assert_eq!(events[5]["tool_rounds"], 1);
+}
+
 #[test]
 fn run_rejects_both_prompt_sources() {
     let tmp = tempdir().expect("tempdir");
@@ -149,3 +256,336 @@ fn run_rejects_empty_prompt() {
         .failure()
         .stderr(predicate::str::contains("empty"));
 }
+
+#[test]
+fn skills_lint_passes_for_valid_skills() {
+    let tmp = tempdir().expect("tempdir");
+    let skill_dir = tmp.path().join(".agent/skills/ok");
+    fs::create_dir_all(&skill_dir).expect("mkdir");
+    fs::write(
+        skill_dir.join("SKILL.md"),
+        "---\nname: ok\ndescription: Valid skill\n---\nBody",
+    )
+    .expect("write");
+
+    let mut cmd = base_command();
+    cmd.current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["skills", "lint"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("skills lint passed"));
+}
+
+#[test]
+fn skills_lint_fails_on_errors() {
+    let tmp = tempdir().expect("tempdir");
+    let skill_dir = tmp.path().join(".agent/skills/broken");
+    fs::create_dir_all(&skill_dir).expect("mkdir");
+    fs::write(skill_dir.join("SKILL.md"), "no frontmatter").expect("write");
+
+    let mut cmd = base_command();
+    cmd.current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["skills", "lint"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("missing frontmatter"))
+        .stderr(predicate::str::contains("skills lint failed"));
+}
+
+#[test]
+fn schedule_list_and_show_read_persisted_state() {
+    let tmp = tempdir().expect("tempdir");
+    let state_dir = tmp.path().join(".crabclaw");
+    fs::create_dir_all(&state_dir).expect("mkdir");
+    fs::write(
+        state_dir.join("schedule.json"),
+        r#"{"jobs":[{"id":"abcd1234","message":"stand up","mode":"reminder",
+        "interval_seconds":600,"created_at":"2026-01-01T00:00:00Z",
+        "status":"interrupted","failures":2,
+        "runs":[{"started_at":"2026-01-01T00:10:00Z","duration_ms":3,"ok":true}]}]}"#,
+    )
+    .expect("write");
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["schedule", "list"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("abcd1234 [interrupted]"))
+        .stdout(predicate::str::contains("failures=2"));
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["schedule", "show", "abcd1234"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("every 600s"));
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["schedule", "show", "missing"])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("schedule job not found"));
+}
+
+#[test]
+fn audit_tail_prints_recent_records() {
+    let tmp = tempdir().expect("tempdir");
+    let audit_dir = tmp
+        .path()
+        .join(".crabclaw/audit")
+        .join(crabclaw::core::audit::workspace_key(tmp.path()));
+    fs::create_dir_all(&audit_dir).expect("mkdir");
+    fs::write(
+        audit_dir.join("2026-01-01.jsonl"),
+        concat!(
+            r#"{"timestamp":"2026-01-01T00:00:00Z","channel":"cli","session":"default","origin":"human","action":"shell","target":"ls","success":true,"result":""}"#,
+            "\n",
+            r#"{"timestamp":"2026-01-01T00:01:00Z","channel":"telegram","user":"42","session":"telegram_42","origin":"tool","action":"file.write","target":"a.txt","success":false,"result":"Error"}"#,
+            "\n",
+        ),
+    )
+    .expect("write");
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["audit", "tail", "-n", "1"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("telegram/42"))
+        .stdout(predicate::str::contains("file.write failed: a.txt"))
+        .stdout(predicate::str::contains("shell").not());
+}
+
+#[test]
+fn tape_list_shows_session_titles() {
+    let tmp = tempdir().expect("tempdir");
+    let tape_dir = tmp.path().join(".crabclaw");
+    fs::create_dir_all(&tape_dir).expect("mkdir");
+    fs::write(
+        tape_dir.join("telegram_42.jsonl"),
+        concat!(
+            r#"{"id":1,"kind":"message","payload":{"role":"user","content":"cron help"},"timestamp":"2026-01-01T00:00:00Z"}"#,
+            "\n",
+            r#"{"id":2,"kind":"session.title","payload":{"title":"Setting up cron jobs"},"timestamp":"2026-01-01T00:00:05Z"}"#,
+            "\n",
+        ),
+    )
+    .expect("write");
+
+    base_command()
+        .args(["tape", "list", "--workspace"])
+        .arg(tmp.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "telegram_42 \"Setting up cron jobs\": 1 message(s)",
+        ));
+}
+
+#[test]
+fn auth_login_stores_validated_key_used_by_config() {
+    let tmp = tempdir().expect("tempdir");
+    let mut server = mockito::Server::new();
+    let ping = server
+        .mock("GET", "/models")
+        .match_header("authorization", "Bearer sk-test-123456789")
+        .with_status(200)
+        .with_body(r#"{"data":[]}"#)
+        .create();
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["auth", "login", "openai", "--key", "sk-test-123456789"])
+        .args(["--api-base", &server.url()])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("openai key sk-t…6789 validated"));
+    ping.assert();
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["run", "--prompt", "hi", "--dry-run"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"api_key_present\": true"));
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["auth", "status"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("openai: API key sk-t…6789"))
+        .stdout(predicate::str::contains("anthropic: no stored key"));
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["auth", "logout", "openai"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("openai key removed"));
+}
+
+#[test]
+fn auth_login_rejects_invalid_key() {
+    let tmp = tempdir().expect("tempdir");
+    let mut server = mockito::Server::new();
+    server
+        .mock("GET", "/v1/models")
+        .with_status(401)
+        .with_body("invalid x-api-key")
+        .create();
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["auth", "login", "anthropic", "--key", "bad-key-value"])
+        .args(["--api-base", &server.url()])
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("rejected the API key"));
+    assert!(!tmp.path().join(".crabclaw/credentials.json").exists());
+}
+
+#[test]
+fn batch_runs_tasks_in_their_workspaces_and_reports_failures() {
+    let tmp = tempdir().expect("tempdir");
+    let mut server = mockito::Server::new();
+    let ok = server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"choices":[{"index":0,"message":{"role":"assistant","content":"done in a"},"finish_reason":"stop"}]}"#,
+        )
+        .expect_at_least(1)
+        .create();
+    server
+        .mock("POST", "/broken/chat/completions")
+        .with_status(400)
+        .with_body(r#"{"error":{"message":"bad request","type":"invalid_request_error"}}"#)
+        .create();
+
+    for (dir, base) in [
+        ("a", server.url()),
+        ("b", format!("{}/broken", server.url())),
+    ] {
+        fs::create_dir_all(tmp.path().join(dir)).unwrap();
+        fs::write(
+            tmp.path().join(dir).join(".env.local"),
+            format!("API_KEY=test-key\nBASE_URL={base}\n"),
+        )
+        .unwrap();
+    }
+    let tasks = tmp.path().join("tasks.yaml");
+    fs::write(
+        &tasks,
+        "concurrency: 2\nmodel: openai:test-model\ntasks:\n  - name: a\n    workspace: a\n    prompt: hello\n  - name: b\n    workspace: b\n    prompt: hello\n",
+    )
+    .unwrap();
+    let report = tmp.path().join("report.json");
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .args(["batch", tasks.to_str().unwrap(), "--output"])
+        .arg(&report)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("1 of 2 task(s) failed"));
+    ok.assert();
+
+    let report: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&report).unwrap()).unwrap();
+    assert_eq!(report["succeeded"], 1);
+    assert_eq!(report["results"][0]["name"], "a");
+    assert_eq!(report["results"][0]["output"], "done in a");
+    assert_eq!(report["results"][1]["ok"], false);
+    assert!(tmp.path().join("a/.crabclaw").is_dir());
+}
+
+#[test]
+fn self_update_check_reports_newer_releases() {
+    let mut server = mockito::Server::new();
+    server
+        .mock("GET", "/latest")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"tag_name":"v99.0.0","html_url":"https://example.com/r","assets":[]}"#)
+        .create();
+    server
+        .mock("GET", "/current")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(format!(
+            r#"{{"tag_name":"v{}","assets":[]}}"#,
+            env!("CARGO_PKG_VERSION")
+        ))
+        .create();
+
+    base_command()
+        .env("CRABCLAW_RELEASES_URL", format!("{}/latest", server.url()))
+        .args(["self-update", "--check"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("crabclaw 99.0.0 is available"));
+    base_command()
+        .env("CRABCLAW_RELEASES_URL", format!("{}/current", server.url()))
+        .args(["self-update"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("is up to date"));
+}
+
+#[test]
+fn doctor_pings_the_provider_and_reports_fixes() {
+    let tmp = tempdir().expect("tempdir");
+    let mut server = mockito::Server::new();
+    server
+        .mock("GET", "/good/models")
+        .with_status(200)
+        .with_body(r#"{"data":[]}"#)
+        .create();
+    server
+        .mock("GET", "/bad/models")
+        .with_status(401)
+        .with_body("invalid key")
+        .create();
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .env("API_KEY", "test-key")
+        .env("MODEL", "openai:gpt-4o")
+        .env("BASE_URL", format!("{}/good", server.url()))
+        .arg("doctor")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("✅ provider openai"))
+        .stdout(predicate::str::contains("✅ tapes"));
+
+    base_command()
+        .current_dir(tmp.path())
+        .env("HOME", tmp.path())
+        .env("API_KEY", "test-key")
+        .env("MODEL", "openai:gpt-4o")
+        .env("BASE_URL", format!("{}/bad", server.url()))
+        .arg("doctor")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("❌ provider openai"))
+        .stdout(predicate::str::contains(
+            "Fix: Check API_KEY, or run `crabclaw auth login openai`.",
+        ));
+}
@@ -216,3 +216,88 @@ async fn openai_multi_turn_keeps_context() {
         Some("You asked about my name.")
     );
 }
+
+#[tokio::test]
+async fn responses_model_runs_function_calls_with_api_key() {
+    let mut server = mockito::Server::new_async().await;
+    let call = concat!(
+        "data: {\"type\":\"response.output_item.added\",\"output_index\":0,",
+        "\"item\":{\"type\":\"function_call\",\"name\":\"file__list\",\"call_id\":\"call_r1\"}}\n\n",
+        "data: {\"type\":\"response.function_call_arguments.done\",\"output_index\":0,",
+        "\"arguments\":\"{\\\"path\\\":\\\".\\\"}\"}\n\n",
+    );
+    // The follow-up carrying the tool result is registered first so it takes
+    // precedence over the catch-all first-request mock.
+    let second = server
+        .mock("POST", "/responses")
+        .match_body(mockito::Matcher::Regex(
+            "\"call_id\":\"call_r1\",\"output\":".to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(
+            "data: {\"type\":\"response.output_text.delta\",\"delta\":\"Listed.\"}\n\ndata: [DONE]\n\n",
+        )
+        .create_async()
+        .await;
+
+    let first = server
+        .mock("POST", "/responses")
+        .match_header("authorization", "Bearer test-key")
+        .match_body(mockito::Matcher::PartialJsonString(
+            r#"{"model":"o3-pro","stream":true,"tool_choice":"auto"}"#.to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "text/event-stream")
+        .with_body(call)
+        .create_async()
+        .await;
+    let mut config = openai_config(&server.url());
+    config.model = "responses:o3-pro".to_string();
+    let workspace = TempDir::new().unwrap();
+    let response = process_message(
+        "list files",
+        &config,
+        workspace.path(),
+        "test:responses",
+        None,
+        None,
+    )
+    .await;
+
+    first.assert_async().await;
+    second.assert_async().await;
+    assert_ok_reply(&response, "Listed.");
+}
+
+#[tokio::test]
+async fn xai_model_uses_its_own_endpoint_and_key() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/xai/chat/completions")
+        .match_header("authorization", "Bearer xai-key")
+        .match_body(mockito::Matcher::PartialJsonString(
+            r#"{"model":"grok-4"}"#.to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("Hello from Grok"))
+        .create_async()
+        .await;
+
+    // BASE_URL / API_KEY belong to another provider and must not be used.
+    let mut config = openai_config("http://127.0.0.1:9");
+    config.model = "xai:grok-4".to_string();
+    config.provider_endpoints.insert(
+        "xai".to_string(),
+        crabclaw::core::config::ProviderEndpoint {
+            api_base: format!("{}/xai", server.url()),
+            api_key: Some("xai-key".to_string()),
+        },
+    );
+    let workspace = TempDir::new().unwrap();
+    let response = process_message("hi", &config, workspace.path(), "test:xai", None, None).await;
+
+    mock.assert_async().await;
+    assert_ok_reply(&response, "Hello from Grok");
+}
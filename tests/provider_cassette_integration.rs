@@ -0,0 +1,97 @@
+mod support;
+
+use std::path::{Path, PathBuf};
+
+use crabclaw::core::config::AppConfig;
+use crabclaw::llm::api_types::{ChatRequest, Message, StreamChunk};
+use crabclaw::llm::cassette::{CassetteConfig, CassetteMode};
+use crabclaw::llm::client::{send_chat_request, send_chat_request_stream};
+use support::builders::anthropic_config;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures/cassettes")
+        .join(name)
+}
+
+/// A config that replays `name` and would fail to connect if it did not.
+fn replaying(model: &str, name: &str) -> AppConfig {
+    let mut config = anthropic_config("http://127.0.0.1:9");
+    config.llm_retry.initial_delay_ms = 10;
+    config.model = model.to_string();
+    config.llm_cassette = Some(CassetteConfig {
+        path: fixture(name),
+        mode: CassetteMode::Replay,
+    });
+    config
+}
+
+fn request(config: &AppConfig) -> ChatRequest {
+    ChatRequest {
+        model: config.model.clone(),
+        messages: vec![Message::user("What is in Cargo.toml?")],
+        max_tokens: None,
+        temperature: None,
+        tools: None,
+        tool_choice: None,
+    }
+}
+
+#[tokio::test]
+async fn anthropic_tool_use_stream_replays_across_split_chunks() {
+    let config = replaying("anthropic:test-model", "anthropic_tool_use_stream.json");
+    let mut rx = send_chat_request_stream(&config, &request(&config))
+        .await
+        .unwrap();
+
+    let mut chunks = Vec::new();
+    while let Some(chunk) = rx.recv().await {
+        let chunk = chunk.unwrap();
+        let done = matches!(chunk, StreamChunk::Done);
+        chunks.push(chunk);
+        if done {
+            break;
+        }
+    }
+
+    let text: String = chunks
+        .iter()
+        .filter_map(|c| match c {
+            StreamChunk::Content(text) => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    let arguments: String = chunks
+        .iter()
+        .filter_map(|c| match c {
+            StreamChunk::ToolCallArgument { index: 1, text } => Some(text.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(text, "Let me look.");
+    assert!(chunks.iter().any(|c| matches!(
+        c,
+        StreamChunk::ToolCallStart { index: 1, id, name } if id == "toolu_01" && name == "file.read"
+    )));
+    assert_eq!(arguments, r#"{"path": "Cargo.toml"}"#);
+    assert!(
+        chunks
+            .iter()
+            .any(|c| matches!(c, StreamChunk::Finish(reason) if reason == "tool_use"))
+    );
+}
+
+#[tokio::test]
+async fn responses_rate_limit_is_retried_then_tool_call_is_parsed() {
+    let config = replaying(
+        "responses:test-model",
+        "responses_retry_then_tool_call.json",
+    );
+    let response = send_chat_request(&config, &request(&config)).await.unwrap();
+
+    let calls = response.tool_calls().unwrap();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].id, "call_01");
+    assert_eq!(calls[0].function.name, "shell.exec");
+    assert_eq!(calls[0].function.arguments, r#"{"command":"cargo test"}"#);
+}
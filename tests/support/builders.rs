@@ -7,11 +7,56 @@ pub fn openai_config(api_base: &str) -> AppConfig {
         api_base: api_base.to_string(),
         model: "openai:test-model".to_string(),
         system_prompt: None,
+        system_prompt_file: None,
+        skills: Vec::new(),
+        reply_language: None,
+        timezone: None,
         telegram_token: Some("fake-token".to_string()),
         telegram_allow_from: vec![],
         telegram_allow_chats: vec![],
         telegram_proxy: None,
+        telegram_persona: None,
+        telegram_output_filters: Vec::new(),
         max_context_messages: 50,
+        skill_top_k: None,
+        persona: None,
+        schedule_report: false,
+        schedule_max_retries: 2,
+        schedule_retry_backoff_seconds: 30,
+        schedule_max_concurrent_jobs: 2,
+        schedule_jitter_seconds: 0,
+        quiet_hours: None,
+        telegram_rate_limit_per_minute: None,
+        telegram_session_ttl_hours: None,
+        telegram_max_concurrent_turns: None,
+        telegram_workers: 8,
+        agent_cache_size: 0,
+        tape_sync: Default::default(),
+        telegram_admins: vec![],
+        telegram_user_workspace_root: None,
+        model_capabilities: None,
+        session_titles: crabclaw::core::title::TitleMode::Off,
+        workspaces: Vec::new(),
+        command_aliases: Vec::new(),
+        command_prefix: ',',
+        telegram_command_prefix: ',',
+        max_continuations: 2,
+        http_pools: Default::default(),
+        llm_timeouts: Default::default(),
+        llm_retry: Default::default(),
+        llm_cassette: None,
+        dump_dir: None,
+        assistant_commands: Default::default(),
+        circuit_breaker: crabclaw::core::config::CircuitBreakerConfig::disabled(),
+        fallback_model: None,
+        consensus_models: Vec::new(),
+        consensus_judge: None,
+        anthropic_params: None,
+        provider_endpoints: Default::default(),
+        provider_quirks: Vec::new(),
+        calendar_urls: Vec::new(),
+        notify: Default::default(),
+        tool_output_limits: Default::default(),
     }
 }
 
@@ -0,0 +1,48 @@
+//! Tape append throughput under each `TAPE_FSYNC` policy.
+//!
+//! A smoke benchmark for chatty tool loops: it checks that every entry is
+//! persisted and prints entries per second. Run with:
+//!   cargo test --release --test tape_append_bench -- --nocapture
+
+use std::time::{Duration, Instant};
+
+use crabclaw::tape::store::{TapeStore, TapeSync};
+use tempfile::TempDir;
+
+const ENTRIES: usize = 2_000;
+
+fn append_many(sync: TapeSync, entries: usize) -> Duration {
+    let dir = TempDir::new().unwrap();
+    let payload = serde_json::json!({
+        "tool": "file.read",
+        "output": "x".repeat(512),
+    });
+    let started = Instant::now();
+    {
+        let mut tape = TapeStore::open(dir.path(), "bench").unwrap();
+        tape.set_sync(sync);
+        for _ in 0..entries {
+            tape.append_event("tool_result", payload.clone()).unwrap();
+        }
+    }
+    let elapsed = started.elapsed();
+
+    let tape = TapeStore::open(dir.path(), "bench").unwrap();
+    assert_eq!(tape.entries().len(), entries);
+    assert_eq!(tape.entries().last().unwrap().id, entries as u64);
+    elapsed
+}
+
+#[test]
+fn tape_append_throughput() {
+    for (label, sync, entries) in [
+        ("never", TapeSync::Never, ENTRIES),
+        ("interval 5s", TapeSync::default(), ENTRIES),
+        // A sync per entry is bounded by the disk; keep the run short.
+        ("always", TapeSync::Always, ENTRIES / 10),
+    ] {
+        let elapsed = append_many(sync, entries);
+        let rate = entries as f64 / elapsed.as_secs_f64().max(f64::EPSILON);
+        println!("TAPE_FSYNC={label}: {entries} entries in {elapsed:?} ({rate:.0}/s)");
+    }
+}
@@ -1,6 +1,6 @@
 mod support;
 
-use crabclaw::channels::telegram::process_message;
+use crabclaw::channels::telegram::{process_message, quick_answer};
 use support::assertions::{assert_has_error, assert_ok_reply};
 use support::builders::{anthropic_config, openai_config};
 use support::responses::text_response;
@@ -199,3 +199,33 @@ async fn openai_system_prompt_contains_identity_contract() {
     .await;
     mock.assert_async().await;
 }
+
+#[tokio::test]
+async fn inline_quick_answer_is_a_single_toolless_call() {
+    let mut server = mockito::Server::new_async().await;
+    let with_tools = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("\"tools\"".to_string()))
+        .with_status(500)
+        .expect(0)
+        .create_async()
+        .await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::Regex("typed inline".to_string()),
+            mockito::Matcher::Regex("what is 2\\+2".to_string()),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response(" 4 "))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let config = openai_config(&server.url());
+    let answer = quick_answer("what is 2+2", &config).await.unwrap();
+    assert_eq!(answer, "4");
+    mock.assert_async().await;
+    with_tools.assert_async().await;
+}
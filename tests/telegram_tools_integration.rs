@@ -209,3 +209,32 @@ async fn malformed_file_write_args_model_recovers() {
         Some("I need a valid path to write the file.")
     );
 }
+
+#[tokio::test]
+async fn output_filters_shape_the_reply() {
+    let mut server = mockito::Server::new_async().await;
+    server
+        .mock("POST", "/chat/completions")
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(text_response("<note>draft</note> Done."))
+        .create_async()
+        .await;
+
+    let mut config = openai_config(&server.url());
+    config.telegram_output_filters =
+        crabclaw::channels::postprocess::parse_filters("strip:<note>.*?</note>; footer:-- bot")
+            .unwrap();
+    let workspace = TempDir::new().unwrap();
+    let response = process_message(
+        "finish up",
+        &config,
+        workspace.path(),
+        "test:filters",
+        None,
+        None,
+    )
+    .await;
+
+    assert_ok_reply(&response, "Done.\n\n-- bot");
+}
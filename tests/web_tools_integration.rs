@@ -62,7 +62,14 @@ async fn e2e_web_fetch_tool_returns_page_content() {
 
     web_mock.assert_async().await;
     final_mock.assert_async().await;
-    assert_ok_reply(&response, "The page says Hello CrabClaw.");
+    assert_ok_reply(
+        &response,
+        &format!("The page says Hello CrabClaw.\n\nSources:\n[1] Hello CrabClaw — {fetch_url}"),
+    );
+
+    let tape =
+        std::fs::read_to_string(workspace.path().join(".crabclaw/test_web_fetch.jsonl")).unwrap();
+    assert!(tape.contains(r#""kind":"turn.sources""#));
 }
 
 #[tokio::test]
@@ -101,7 +108,11 @@ async fn e2e_web_search_tool_returns_search_url() {
     .await;
 
     final_mock.assert_async().await;
-    assert_ok_reply(&response, "Here is a DuckDuckGo search link for Rust.");
+    assert_ok_reply(
+        &response,
+        "Here is a DuckDuckGo search link for Rust.\n\nSources:\n\
+         [1] Search: rust programming — https://duckduckgo.com/?q=rust%20programming",
+    );
 }
 
 #[tokio::test]
@@ -189,5 +200,8 @@ async fn e2e_web_fetch_plain_text_passthrough() {
 
     web_mock.assert_async().await;
     final_mock.assert_async().await;
-    assert_ok_reply(&response, "The robots.txt disallows /private/.");
+    assert_ok_reply(
+        &response,
+        &format!("The robots.txt disallows /private/.\n\nSources:\n[1] {fetch_url}"),
+    );
 }